@@ -0,0 +1,170 @@
+//! Stylesheets and scripts linked from pages.
+//!
+//! Pages link the theme's assets, the built-in ones in use, those added by
+//! extensions and `html_css_files`/`html_js_files`, ordered by priority like
+//! Sphinx: lower priorities come first and equal ones keep the order they
+//! were added in. Directives can add assets to just the pages they appear
+//! on, and the assets of features like math or code blocks are only loaded
+//! by the pages using them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Priority of the theme's and the built-in assets
+pub const BUILTIN_PRIORITY: i32 = 200;
+
+/// Priority of assets added without one, as by extensions
+pub const DEFAULT_PRIORITY: i32 = 500;
+
+/// Priority of `html_css_files` and `html_js_files`
+pub const CONFIG_PRIORITY: i32 = 800;
+
+/// A stylesheet or script
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Asset {
+    /// File under `_static`, path from the output root starting with
+    /// `_static/`, or URL
+    pub filename: String,
+    pub priority: i32,
+    /// Attributes of the tag besides the link, like `defer` or `media`
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Asset {
+    pub fn new(filename: impl Into<String>) -> Self {
+        Self {
+            filename: filename.into(),
+            priority: DEFAULT_PRIORITY,
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Add an attribute to the tag; boolean attributes like `defer`,
+    /// `async` take their name as value
+    pub fn with_attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.push((name.into(), value.into()));
+        self
+    }
+
+    /// A script loaded as an ES module
+    pub fn module(self) -> Self {
+        self.with_attribute("type", "module")
+    }
+
+    /// Path from the output root, or the URL
+    pub fn url(&self) -> String {
+        if self.filename.contains("://")
+            || self.filename.starts_with("//")
+            || self.filename.starts_with("_static/")
+        {
+            self.filename.clone()
+        } else {
+            format!("_static/{}", self.filename)
+        }
+    }
+}
+
+/// Content of a page that needs assets of its own, which only the pages
+/// using it load
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PageFeature {
+    /// Code blocks, which get copy buttons
+    Code,
+    /// `math` directives and roles, typeset by MathJax
+    Math,
+    /// `mermaid` diagrams
+    Mermaid,
+    /// Grids, cards, buttons and tabs of the design stylesheet
+    Design,
+}
+
+impl PageFeature {
+    pub const ALL: [PageFeature; 4] = [
+        PageFeature::Code,
+        PageFeature::Math,
+        PageFeature::Mermaid,
+        PageFeature::Design,
+    ];
+}
+
+/// Stylesheets and scripts of a page, and the features they are for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageAssets {
+    pub css_files: Vec<Asset>,
+    pub js_files: Vec<Asset>,
+    pub features: BTreeSet<PageFeature>,
+}
+
+impl PageAssets {
+    pub fn add_css_file(&mut self, asset: Asset) {
+        self.css_files.push(asset);
+    }
+
+    pub fn add_js_file(&mut self, asset: Asset) {
+        self.js_files.push(asset);
+    }
+
+    pub fn uses(&mut self, feature: PageFeature) {
+        self.features.insert(feature);
+    }
+
+    pub fn extend(&mut self, other: PageAssets) {
+        self.css_files.extend(other.css_files);
+        self.js_files.extend(other.js_files);
+        self.features.extend(other.features);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.css_files.is_empty() && self.js_files.is_empty() && self.features.is_empty()
+    }
+}
+
+/// Assets in the order pages link them: by priority, then in the order
+/// given, each file once
+pub fn order(mut assets: Vec<Asset>) -> Vec<Asset> {
+    assets.sort_by_key(|asset| asset.priority);
+    let mut seen = std::collections::HashSet::new();
+    assets.retain(|asset| seen.insert(asset.url()));
+    assets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assets_ordered_by_priority() {
+        let assets = order(vec![
+            Asset::new("custom.css").with_priority(CONFIG_PRIORITY),
+            Asset::new("ext.css"),
+            Asset::new("_static/theme.css").with_priority(BUILTIN_PRIORITY),
+            Asset::new("https://cdn.example.com/lib.css"),
+            Asset::new("ext.css").with_priority(100),
+        ]);
+        let urls: Vec<String> = assets.iter().map(Asset::url).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "_static/ext.css",
+                "_static/theme.css",
+                "https://cdn.example.com/lib.css",
+                "_static/custom.css"
+            ]
+        );
+        assert_eq!(
+            Asset::new("app.js")
+                .module()
+                .with_attribute("defer", "defer")
+                .attributes,
+            vec![
+                ("type".to_string(), "module".to_string()),
+                ("defer".to_string(), "defer".to_string())
+            ]
+        );
+    }
+}
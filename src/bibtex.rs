@@ -0,0 +1,964 @@
+//! BibTeX bibliographies for the `:cite:` role and `bibliography` directive.
+//!
+//! The `.bib` files named by `bibtex_files` are parsed here without a TeX
+//! installation: `@string` macros, `#` concatenation and the common accent
+//! commands are understood, and other commands keep just their argument.
+//! Entries cited anywhere in the project are listed, labelled and formatted
+//! according to the configured style, at the page with the bibliography.
+
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::BibtexStyle;
+
+lazy_static::lazy_static! {
+    static ref CITE_ROLE_RE: Regex = Regex::new(r":cite(?::[pt])?:`([^`]+)`").unwrap();
+}
+
+/// One entry of a `.bib` file, with its fields as written (braces kept)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BibEntry {
+    pub key: String,
+    /// Entry type in lowercase, like `article`
+    pub entry_type: String,
+    /// Fields by lowercase name
+    pub fields: BTreeMap<String, String>,
+}
+
+/// A problem in a `.bib` file, with its line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BibError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Keys cited with `:cite:`, `:cite:p:` and `:cite:t:` in RST source, in order
+pub fn cited_keys(source: &str) -> Vec<String> {
+    CITE_ROLE_RE
+        .captures_iter(source)
+        .flat_map(|caps| split_keys(&caps[1]))
+        .collect()
+}
+
+/// The keys of a `:cite:` role, separated by commas
+pub fn split_keys(content: &str) -> Vec<String> {
+    content
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse the entries of a `.bib` file. Broken entries are skipped and
+/// reported; the rest of the file is still read.
+pub fn parse(source: &str) -> (Vec<BibEntry>, Vec<BibError>) {
+    let mut parser = BibParser {
+        chars: source.chars().collect(),
+        pos: 0,
+        macros: HashMap::new(),
+    };
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    while let Some(at) = parser.chars[parser.pos..].iter().position(|&c| c == '@') {
+        parser.pos += at;
+        let start = parser.pos;
+        match parser.item() {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {}
+            Err(message) => {
+                errors.push(BibError {
+                    line: parser.line_of(start),
+                    message,
+                });
+                parser.pos = start + 1;
+            }
+        }
+    }
+    (entries, errors)
+}
+
+struct BibParser {
+    chars: Vec<char>,
+    pos: usize,
+    /// `@string` macros by lowercase name
+    macros: HashMap<String, String>,
+}
+
+impl BibParser {
+    fn line_of(&self, pos: usize) -> usize {
+        self.chars[..pos].iter().filter(|&&c| c == '\n').count() + 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", expected))
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| !c.is_whitespace() && !"{}(),=#\"".contains(c))
+        {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// An `@type{...}` item at the current position: an entry, or `None`
+    /// for comments, preambles and macros
+    fn item(&mut self) -> Result<Option<BibEntry>, String> {
+        self.pos += 1;
+        let entry_type = self.identifier().to_lowercase();
+        if entry_type.is_empty() {
+            return Err("missing entry type after '@'".to_string());
+        }
+        self.skip_whitespace();
+        let close = match self.peek() {
+            Some('{') => '}',
+            Some('(') => ')',
+            _ => return Err(format!("expected '{{' after @{}", entry_type)),
+        };
+        self.pos += 1;
+
+        match entry_type.as_str() {
+            "comment" | "preamble" => {
+                self.pos -= 1;
+                self.balanced(close)?;
+                Ok(None)
+            }
+            "string" => {
+                self.skip_whitespace();
+                let name = self.identifier().to_lowercase();
+                self.expect('=')?;
+                let value = self.value(close)?;
+                self.macros.insert(name, value);
+                self.expect(close)?;
+                Ok(None)
+            }
+            _ => {
+                self.skip_whitespace();
+                let key = self.identifier();
+                if key.is_empty() {
+                    return Err(format!("missing citation key in @{} entry", entry_type));
+                }
+                let mut fields = BTreeMap::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => {
+                            self.pos += 1;
+                            continue;
+                        }
+                        Some(c) if c == close => {
+                            self.pos += 1;
+                            break;
+                        }
+                        None => return Err(format!("unterminated entry '{}'", key)),
+                        _ => {}
+                    }
+                    let name = self.identifier().to_lowercase();
+                    if name.is_empty() {
+                        return Err(format!("expected a field name in entry '{}'", key));
+                    }
+                    self.expect('=').map_err(|_| {
+                        format!("expected '=' after field '{}' in entry '{}'", name, key)
+                    })?;
+                    let value = self.value(close)?;
+                    self.skip_whitespace();
+                    if self.peek() != Some(',') && self.peek() != Some(close) {
+                        return Err(format!(
+                            "expected ',' after field '{}' in entry '{}'",
+                            name, key
+                        ));
+                    }
+                    fields.insert(name, value);
+                }
+                Ok(Some(BibEntry {
+                    key,
+                    entry_type,
+                    fields,
+                }))
+            }
+        }
+    }
+
+    /// Skip a braced or parenthesized group starting at the current position
+    fn balanced(&mut self, close: char) -> Result<String, String> {
+        let open = self.chars[self.pos];
+        let start = self.pos + 1;
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == open || (c == '{' && open != '{') {
+                depth += 1;
+            } else if c == close || (c == '}' && close != '}') {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(self.chars[start..self.pos - 1].iter().collect());
+                }
+            }
+        }
+        Err("unbalanced braces".to_string())
+    }
+
+    /// A field value: braced or quoted text, a number or a macro, joined
+    /// with `#`
+    fn value(&mut self, close: char) -> Result<String, String> {
+        let mut value = String::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('{') => value.push_str(&self.balanced('}')?),
+                Some('"') => {
+                    self.pos += 1;
+                    let start = self.pos;
+                    let mut depth = 0;
+                    loop {
+                        match self.peek() {
+                            None => return Err("unterminated quoted value".to_string()),
+                            Some('{') => depth += 1,
+                            Some('}') => depth -= 1,
+                            Some('"') if depth == 0 => break,
+                            _ => {}
+                        }
+                        self.pos += 1;
+                    }
+                    value.extend(&self.chars[start..self.pos]);
+                    self.pos += 1;
+                }
+                Some(c) if c.is_ascii_digit() => {
+                    while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                        value.push(self.chars[self.pos]);
+                        self.pos += 1;
+                    }
+                }
+                Some(c) if c != close && c != ',' => {
+                    let name = self.identifier().to_lowercase();
+                    if name.is_empty() {
+                        return Err(format!("unexpected '{}' in a value", c));
+                    }
+                    match self.macros.get(&name) {
+                        Some(text) => value.push_str(text),
+                        None => match month_name(&name) {
+                            Some(month) => value.push_str(month),
+                            None => return Err(format!("undefined macro '{}'", name)),
+                        },
+                    }
+                }
+                _ => return Err("missing value".to_string()),
+            }
+            self.skip_whitespace();
+            if self.peek() == Some('#') {
+                self.pos += 1;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+}
+
+/// The predefined month macros
+fn month_name(name: &str) -> Option<&'static str> {
+    let months = [
+        ("jan", "January"),
+        ("feb", "February"),
+        ("mar", "March"),
+        ("apr", "April"),
+        ("may", "May"),
+        ("jun", "June"),
+        ("jul", "July"),
+        ("aug", "August"),
+        ("sep", "September"),
+        ("oct", "October"),
+        ("nov", "November"),
+        ("dec", "December"),
+    ];
+    months
+        .iter()
+        .find(|(short, _)| *short == name)
+        .map(|(_, long)| *long)
+}
+
+/// Plain text of a field value: accents composed, other commands replaced
+/// by their argument, braces dropped and whitespace collapsed
+pub fn latex_to_text(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut text = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                i += 1;
+                let Some(&c) = chars.get(i) else {
+                    break;
+                };
+                if let Some(mark) = accent_mark(c) {
+                    // `\"o`, `\"{o}` or, for letter accents, `\c c`
+                    i += 1;
+                    if c.is_alphabetic() {
+                        while chars.get(i) == Some(&' ') {
+                            i += 1;
+                        }
+                    }
+                    let braced = chars.get(i) == Some(&'{');
+                    if braced {
+                        i += 1;
+                    }
+                    if let Some(&base) = chars.get(i) {
+                        let base = if base == '\\' && chars.get(i + 1) == Some(&'i') {
+                            i += 1;
+                            'i'
+                        } else {
+                            base
+                        };
+                        text.extend([base, mark].iter().collect::<String>().nfc());
+                        i += 1;
+                    }
+                    if braced && chars.get(i) == Some(&'}') {
+                        i += 1;
+                    }
+                } else if c.is_alphabetic() {
+                    let start = i;
+                    while chars.get(i).is_some_and(|c| c.is_alphabetic()) {
+                        i += 1;
+                    }
+                    let command: String = chars[start..i].iter().collect();
+                    text.push_str(match command.as_str() {
+                        "ss" => "ß",
+                        "o" => "ø",
+                        "O" => "Ø",
+                        "ae" => "æ",
+                        "AE" => "Æ",
+                        "oe" => "œ",
+                        "OE" => "Œ",
+                        "aa" => "å",
+                        "AA" => "Å",
+                        "l" => "ł",
+                        "L" => "Ł",
+                        "i" => "ı",
+                        "TeX" => "TeX",
+                        "LaTeX" => "LaTeX",
+                        _ => "",
+                    });
+                    // A space after a command name only ends the name
+                    if chars.get(i) == Some(&' ') {
+                        i += 1;
+                    }
+                } else {
+                    // `\&`, `\%`, `\$`, `\_`, `\{` and the like
+                    text.push(c);
+                    i += 1;
+                }
+            }
+            '{' | '}' => i += 1,
+            '~' => {
+                text.push('\u{a0}');
+                i += 1;
+            }
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                if chars.get(i + 2) == Some(&'-') {
+                    text.push('—');
+                    i += 3;
+                } else {
+                    text.push('–');
+                    i += 2;
+                }
+            }
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    // Collapse whitespace, keeping the no-break spaces of `~`
+    text.split_ascii_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The combining mark of an accent command like `\"`
+fn accent_mark(command: char) -> Option<char> {
+    Some(match command {
+        '"' => '\u{308}',
+        '\'' => '\u{301}',
+        '`' => '\u{300}',
+        '^' => '\u{302}',
+        '~' => '\u{303}',
+        '=' => '\u{304}',
+        '.' => '\u{307}',
+        'c' => '\u{327}',
+        'v' => '\u{30c}',
+        'u' => '\u{306}',
+        'H' => '\u{30b}',
+        _ => return None,
+    })
+}
+
+/// A name from an `author` or `editor` field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Person {
+    pub first: String,
+    /// Last name with its particles, like `van Rossum`
+    pub last: String,
+}
+
+impl Person {
+    fn full(&self) -> String {
+        if self.first.is_empty() {
+            self.last.clone()
+        } else {
+            format!("{} {}", self.first, self.last)
+        }
+    }
+
+    /// Last name without particles, for labels and sorting
+    fn surname(&self) -> &str {
+        self.last
+            .split_whitespace()
+            .find(|word| word.chars().next().is_some_and(char::is_uppercase))
+            .unwrap_or(&self.last)
+    }
+}
+
+/// Split `value` at whitespace or at `separator` words, outside braces
+fn split_top_level<'a>(value: &'a str, separator: Option<&str>) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut word_start = None;
+    let bytes: Vec<(usize, char)> = value.char_indices().collect();
+    for (index, &(offset, c)) in bytes.iter().enumerate() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        let boundary = c.is_whitespace() && depth == 0;
+        match separator {
+            None if boundary => {
+                if start < offset {
+                    parts.push(&value[start..offset]);
+                }
+                start = offset + c.len_utf8();
+            }
+            Some(separator) if boundary => {
+                if let Some(word) = word_start {
+                    if value[word..offset].eq_ignore_ascii_case(separator) {
+                        parts.push(value[start..word].trim());
+                        start = offset;
+                    }
+                }
+                word_start = bytes.get(index + 1).map(|&(next, _)| next);
+            }
+            _ => {}
+        }
+    }
+    let rest = value[start..].trim();
+    if !rest.is_empty() {
+        parts.push(rest);
+    }
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// The people of an `author` or `editor` field: `Last, First`,
+/// `Last, Jr, First` or `First von Last`, joined with `and`
+pub fn parse_names(value: &str) -> Vec<Person> {
+    split_top_level(value, Some("and"))
+        .into_iter()
+        .map(|name| {
+            let parts: Vec<&str> = name.split(',').map(str::trim).collect();
+            match parts.as_slice() {
+                [last, first] | [last, _, first] => Person {
+                    first: latex_to_text(first),
+                    last: latex_to_text(last),
+                },
+                _ => {
+                    let words = split_top_level(name, None);
+                    // Lowercase words before the last start the last name
+                    let last_start = words[..words.len().saturating_sub(1)]
+                        .iter()
+                        .position(|word| word.chars().next().is_some_and(char::is_lowercase))
+                        .unwrap_or(words.len().saturating_sub(1));
+                    Person {
+                        first: latex_to_text(&words[..last_start].join(" ")),
+                        last: latex_to_text(&words[last_start..].join(" ")),
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+impl BibEntry {
+    /// A field as plain text
+    pub fn field(&self, name: &str) -> Option<String> {
+        self.fields
+            .get(name)
+            .map(|value| latex_to_text(value))
+            .filter(|value| !value.is_empty())
+    }
+
+    /// The authors, or else the editors
+    fn people(&self) -> Vec<Person> {
+        self.fields
+            .get("author")
+            .or_else(|| self.fields.get("editor"))
+            .map(|value| parse_names(value))
+            .unwrap_or_default()
+    }
+
+    /// Names for text citations: `Knuth`, `Kernighan and Ritchie` or
+    /// `Gamma et al.`
+    pub fn short_authors(&self) -> String {
+        let people = self.people();
+        match people.as_slice() {
+            [] => self.key.clone(),
+            [one] => one.surname().to_string(),
+            [one, two] => format!("{} and {}", one.surname(), two.surname()),
+            [one, ..] => format!("{} et al.", one.surname()),
+        }
+    }
+
+    /// Label of the alpha styles, like `Knu84` or `KR88`, before telling
+    /// equal labels apart
+    fn alpha_label(&self) -> String {
+        let people = self.people();
+        let initials = |person: &Person| {
+            person
+                .surname()
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+        };
+        let mut label: String = match people.as_slice() {
+            [] => self.key.chars().take(3).collect(),
+            [one] => initials(one).chars().take(3).collect(),
+            _ => people
+                .iter()
+                .take(if people.len() > 4 { 3 } else { people.len() })
+                .filter_map(|person| initials(person).chars().next())
+                .collect(),
+        };
+        if people.len() > 4 {
+            label.push('+');
+        }
+        if let Some(year) = self.field("year") {
+            let digits: String = year.chars().filter(char::is_ascii_digit).collect();
+            label.push_str(&digits[digits.len().saturating_sub(2)..]);
+        }
+        label
+    }
+
+    /// Sort key of the sorted styles: authors, year, title
+    fn sort_key(&self) -> (String, String, String) {
+        let authors = self
+            .people()
+            .iter()
+            .map(|person| format!("{} {}", person.last, person.first))
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        (
+            authors,
+            self.field("year").unwrap_or_default(),
+            self.field("title").unwrap_or_default().to_lowercase(),
+        )
+    }
+
+    /// The reference as HTML, in the sentences of BibTeX's plain style
+    pub fn to_html(&self) -> String {
+        let text = |name: &str| {
+            self.field(name)
+                .map(|value| html_escape::encode_text(&value).into_owned())
+        };
+        let emphasized = |name: &str| text(name).map(|value| format!("<em>{}</em>", value));
+
+        let mut sentences: Vec<String> = Vec::new();
+        let people = self.people();
+        if !people.is_empty() {
+            let names: Vec<String> = people.iter().map(Person::full).collect();
+            let mut authors = match names.as_slice() {
+                [one] => one.clone(),
+                [one, two] => format!("{} and {}", one, two),
+                [rest @ .., last] => format!("{}, and {}", rest.join(", "), last),
+                [] => String::new(),
+            };
+            if !self.fields.contains_key("author") {
+                authors.push_str(if names.len() > 1 {
+                    ", editors"
+                } else {
+                    ", editor"
+                });
+            }
+            sentences.push(html_escape::encode_text(&authors).into_owned());
+        }
+
+        let year = text("year");
+        let pages = text("pages");
+        let publisher = |sentences: &mut Vec<String>, name: &str| {
+            let parts: Vec<String> = [text(name), year.clone()].into_iter().flatten().collect();
+            if !parts.is_empty() {
+                sentences.push(parts.join(", "));
+            }
+        };
+        match self.entry_type.as_str() {
+            "article" => {
+                sentences.extend(text("title"));
+                let mut source: Vec<String> = emphasized("journal").into_iter().collect();
+                let mut issue = text("volume").unwrap_or_default();
+                if let Some(number) = text("number") {
+                    issue.push_str(&format!("({})", number));
+                }
+                if let Some(pages) = &pages {
+                    issue.push_str(&format!(":{}", pages));
+                }
+                if !issue.is_empty() {
+                    source.push(issue);
+                }
+                source.extend(year.clone());
+                if !source.is_empty() {
+                    sentences.push(source.join(", "));
+                }
+            }
+            "book" | "booklet" | "manual" => {
+                sentences.extend(emphasized("title"));
+                publisher(&mut sentences, "publisher");
+            }
+            "inproceedings" | "conference" | "incollection" => {
+                sentences.extend(text("title"));
+                let mut source: Vec<String> = emphasized("booktitle")
+                    .map(|title| format!("In {}", title))
+                    .into_iter()
+                    .collect();
+                source.extend(pages.map(|pages| format!("pages {}", pages)));
+                if !source.is_empty() {
+                    sentences.push(source.join(", "));
+                }
+                publisher(&mut sentences, "publisher");
+            }
+            "phdthesis" | "mastersthesis" => {
+                sentences.extend(emphasized("title"));
+                let kind = if self.entry_type == "phdthesis" {
+                    "PhD thesis"
+                } else {
+                    "Master's thesis"
+                };
+                let parts: Vec<String> = [Some(kind.to_string()), text("school"), year.clone()]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                sentences.push(parts.join(", "));
+            }
+            "techreport" => {
+                sentences.extend(text("title"));
+                let report = match text("number") {
+                    Some(number) => format!("Technical Report {}", number),
+                    None => "Technical Report".to_string(),
+                };
+                let parts: Vec<String> = [Some(report), text("institution"), year.clone()]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                sentences.push(parts.join(", "));
+            }
+            _ => {
+                sentences.extend(text("title"));
+                publisher(&mut sentences, "howpublished");
+            }
+        }
+        sentences.extend(text("note"));
+
+        let mut html = sentences.join(". ");
+        if !html.is_empty() && !html.ends_with(['.', '?', '!']) {
+            html.push('.');
+        }
+        if let Some(doi) = self.field("doi") {
+            html.push_str(&format!(
+                " <a class=\"reference external\" href=\"https://doi.org/{}\">doi:{}</a>",
+                html_escape::encode_double_quoted_attribute(&doi),
+                html_escape::encode_text(&doi)
+            ));
+        }
+        if let Some(url) = self.fields.get("url").map(|url| url.trim().to_string()) {
+            html.push_str(&format!(
+                " URL: <a class=\"reference external\" href=\"{}\">{}</a>.",
+                html_escape::encode_double_quoted_attribute(&url),
+                html_escape::encode_text(&url)
+            ));
+        }
+        html
+    }
+}
+
+/// The entries of the bibliography files, with the cited ones labelled and
+/// ordered for the page listing them
+#[derive(Debug, Default)]
+pub struct Bibliography {
+    entries: HashMap<String, BibEntry>,
+    /// Listed entries in order, with their labels
+    listed: Vec<(String, String)>,
+    labels: HashMap<String, String>,
+    /// Page with the `bibliography` directive that citations link to
+    page: Option<String>,
+}
+
+impl Bibliography {
+    /// Label and order the entries: those `cited`, in citation order, or all
+    /// of them
+    pub fn new(
+        entries: Vec<BibEntry>,
+        cited: &[String],
+        all: bool,
+        page: Option<String>,
+        style: BibtexStyle,
+    ) -> Self {
+        let mut by_key = HashMap::new();
+        let mut order = Vec::new();
+        for entry in entries {
+            if !by_key.contains_key(&entry.key) {
+                order.push(entry.key.clone());
+                by_key.insert(entry.key.clone(), entry);
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut listed: Vec<&BibEntry> = Vec::new();
+        let keys = cited
+            .iter()
+            .chain(if all { order.iter() } else { [].iter() });
+        for key in keys {
+            if let Some(entry) = by_key.get(key) {
+                if seen.insert(key.clone()) {
+                    listed.push(entry);
+                }
+            }
+        }
+        if matches!(style, BibtexStyle::Plain | BibtexStyle::Alpha) {
+            listed.sort_by_key(|entry| entry.sort_key());
+        }
+
+        let mut labels: Vec<String> = match style {
+            BibtexStyle::Plain | BibtexStyle::Unsrt => {
+                (1..=listed.len()).map(|n| n.to_string()).collect()
+            }
+            BibtexStyle::Alpha | BibtexStyle::Unsrtalpha => {
+                listed.iter().map(|entry| entry.alpha_label()).collect()
+            }
+        };
+        // Equal alpha labels get a, b, ...
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for label in &labels {
+            *counts.entry(label.clone()).or_default() += 1;
+        }
+        let mut used: HashMap<String, u8> = HashMap::new();
+        for label in labels.iter_mut() {
+            if counts[label.as_str()] > 1 {
+                let suffix = used.entry(label.clone()).or_insert(0);
+                label.push((b'a' + *suffix) as char);
+                *suffix += 1;
+            }
+        }
+
+        let listed: Vec<(String, String)> = listed
+            .iter()
+            .map(|entry| entry.key.clone())
+            .zip(labels)
+            .collect();
+        Self {
+            labels: listed.iter().cloned().collect(),
+            listed,
+            entries: by_key,
+            page,
+        }
+    }
+
+    /// An entry of the files with its label, if it is listed
+    pub fn entry(&self, key: &str) -> Option<(&BibEntry, Option<&str>)> {
+        let entry = self.entries.get(key)?;
+        Some((entry, self.labels.get(key).map(String::as_str)))
+    }
+
+    /// The listed entries with their labels, in order
+    pub fn listed(&self) -> impl Iterator<Item = (&BibEntry, &str)> {
+        self.listed
+            .iter()
+            .map(|(key, label)| (&self.entries[key], label.as_str()))
+    }
+
+    /// Page that lists the entries
+    pub fn page(&self) -> Option<&str> {
+        self.page.as_deref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIB: &str = r#"
+@string{aw = "Addison-Wesley"}
+
+@comment{ Not an entry: @article{x, title = {y}} }
+
+@book{knuth84,
+  author    = {Donald E. Knuth},
+  title     = {The {\TeX}book},
+  publisher = aw # " Professional",
+  year      = 1984,
+}
+
+@article{kr88,
+  author  = "Kernighan, Brian W. and Ritchie, Dennis M.",
+  title   = {The {C} Programming Language},
+  journal = {Software---Practice \& Experience},
+  volume  = {18},
+  number  = {2},
+  pages   = {100--120},
+  year    = {1988},
+  month   = feb,
+  doi     = {10.1000/xyz},
+}
+
+@misc{broken,
+  title = {Missing comma}
+  year = 2000,
+}
+
+@inproceedings{vanrossum,
+  author    = {Guido van Rossum and Jos{\'e} {\"O}zt{\"u}rk and Fran{\c c}ois Dupont},
+  title     = {Python},
+  booktitle = {Proceedings},
+  year      = {1991},
+}
+"#;
+
+    #[test]
+    fn test_parse_entries_and_macros() {
+        let (entries, errors) = parse(BIB);
+        let keys: Vec<&str> = entries.iter().map(|entry| entry.key.as_str()).collect();
+        assert_eq!(keys, vec!["knuth84", "kr88", "vanrossum"]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 25);
+        assert_eq!(
+            errors[0].message,
+            "expected ',' after field 'title' in entry 'broken'"
+        );
+
+        let knuth = &entries[0];
+        assert_eq!(knuth.entry_type, "book");
+        assert_eq!(
+            knuth.field("publisher").as_deref(),
+            Some("Addison-Wesley Professional")
+        );
+        assert_eq!(knuth.field("title").as_deref(), Some("The TeXbook"));
+        assert_eq!(entries[1].field("month").as_deref(), Some("February"));
+        assert_eq!(
+            entries[1].field("journal").as_deref(),
+            Some("Software—Practice & Experience")
+        );
+    }
+
+    #[test]
+    fn test_latex_to_text_and_names() {
+        assert_eq!(
+            latex_to_text(r#"Jos{\'e} {\"O}zt{\"u}rk, Fran{\c c}ois, \ss{}, \emph{x}~y"#),
+            "José Öztürk, François, ß, x\u{a0}y"
+        );
+        let names =
+            parse_names(r"Guido van Rossum and Ritchie, Jr., Dennis and {Barnes and Noble}");
+        assert_eq!(
+            names,
+            vec![
+                Person {
+                    first: "Guido".to_string(),
+                    last: "van Rossum".to_string()
+                },
+                Person {
+                    first: "Dennis".to_string(),
+                    last: "Ritchie".to_string()
+                },
+                Person {
+                    first: String::new(),
+                    last: "Barnes and Noble".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_entries() {
+        let (entries, _) = parse(BIB);
+        assert_eq!(
+            entries[0].to_html(),
+            "Donald E. Knuth. <em>The TeXbook</em>. Addison-Wesley Professional, 1984."
+        );
+        assert_eq!(
+            entries[1].to_html(),
+            "Brian W. Kernighan and Dennis M. Ritchie. The C Programming Language. <em>Software—Practice &amp; Experience</em>, 18(2):100–120, 1988. <a class=\"reference external\" href=\"https://doi.org/10.1000/xyz\">doi:10.1000/xyz</a>"
+        );
+        assert_eq!(
+            entries[2].to_html(),
+            "Guido van Rossum, José Öztürk, and François Dupont. Python. In <em>Proceedings</em>. 1991."
+        );
+        assert_eq!(entries[2].short_authors(), "Rossum et al.");
+        assert_eq!(entries[1].short_authors(), "Kernighan and Ritchie");
+    }
+
+    #[test]
+    fn test_labels_and_order() {
+        let (entries, _) = parse(BIB);
+        let cited = vec![
+            "vanrossum".to_string(),
+            "knuth84".to_string(),
+            "missing".to_string(),
+        ];
+        let labels = |style, all| {
+            let bibliography = Bibliography::new(entries.clone(), &cited, all, None, style);
+            bibliography
+                .listed()
+                .map(|(entry, label)| format!("{}={}", entry.key, label))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            labels(BibtexStyle::Unsrt, false),
+            vec!["vanrossum=1", "knuth84=2"]
+        );
+        assert_eq!(
+            labels(BibtexStyle::Plain, false),
+            vec!["knuth84=1", "vanrossum=2"]
+        );
+        assert_eq!(
+            labels(BibtexStyle::Alpha, true),
+            vec!["kr88=KR88", "knuth84=Knu84", "vanrossum=RÖD91"]
+        );
+        assert_eq!(
+            labels(BibtexStyle::Unsrtalpha, false),
+            vec!["vanrossum=RÖD91", "knuth84=Knu84"]
+        );
+        assert_eq!(
+            cited_keys("See :cite:`a, b` and :cite:t:`c`."),
+            vec!["a", "b", "c"]
+        );
+    }
+}
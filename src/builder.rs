@@ -1,23 +1,58 @@
 use anyhow::{Context, Result};
 use log::{debug, info, warn};
 use rayon::prelude::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::assets::{self, Asset, PageAssets, PageFeature};
+use crate::bibtex::{self, Bibliography};
 use crate::cache::BuildCache;
-use crate::config::BuildConfig;
-use crate::document::Document;
-use crate::error::{BuildErrorReport, BuildWarning};
+use crate::cancellation::CancellationToken;
+use crate::config::{BuildConfig, ExtraPathConflicts};
+use crate::docstrings;
+use crate::directives::validation::{
+    DirectiveRoleParser, DirectiveValidationResult, DirectiveValidationSystem,
+    RoleValidationResult, ValidationStatistics,
+};
+use crate::document::{Document, DocumentContent, MessageLevel, RstNode, SystemMessage, TocEntry};
+use crate::environment::{self, BuildEnvironment, DomainObject, Label, Toctree, STANDARD_LABELS};
+use crate::error::{BuildError, BuildErrorReport, BuildOutcome, BuildWarning, ErrorType, PhaseFailure, WarningType};
 use crate::extensions::{ExtensionLoader, SphinxApp};
+use crate::genindex::{self, IndexTerm, GENINDEX_PAGE};
+use crate::intern::Symbol;
+use crate::manifest::{ContentRefs, Manifest, ManifestPage};
 use crate::matching;
-use crate::navigation::{NavigationBuilder, PageNavigation, ToctreeOptions};
+use crate::navigation::{self, NavigationBuilder, PageNavigation, ToctreeOptions};
+use crate::numfig;
+use crate::optimize::{self, CompressionFormat};
 use crate::parser::Parser;
-use crate::renderer::HtmlRenderer;
-use crate::template::{SafeHtml, TemplateContext, TemplateEngine};
+use crate::paths;
+use crate::postprocess::{HtmlPostProcessor, PostProcessorRegistry, RenderedPage};
+use crate::redirects::{self, Redirect};
+use crate::scheduler;
+use crate::renderer::{design, HtmlRenderer, MediaFile, TocSection};
+use crate::social_cards::{self, SocialCard, SocialCardRenderer, CARD_HEIGHT, CARD_WIDTH};
+use crate::smartquotes::SmartQuotes;
+use crate::stats::{self, DocumentTiming, PhaseTimings, DEFAULT_SLOWEST_DOCUMENTS};
+use crate::template::{
+    self, AssetAttributes, SafeHtml, SpooledHtml, TemplateContext, TemplateEngine, TemplateError,
+};
 use crate::theme::{Theme, ThemeRegistry};
+use crate::observer::BuildObserver;
+use crate::progress::{ProgressReporter, ProgressTracker};
+use crate::trace::{self, BuildTrace};
+use crate::transforms::{DocumentTransform, TransformRegistry};
 use crate::utils;
+use crate::validation::accessibility::{self, AccessibilityIssueKind};
+use crate::validation::spelling::{SpellChecker, WordTally};
+use crate::validation::style::{self, StyleIssueKind};
+use crate::validation::{ConstraintEngine, ValidationSeverity};
+use crate::versions::VersionSwitcher;
 
 #[derive(Debug, Clone)]
 pub struct BuildStats {
@@ -30,6 +65,153 @@ pub struct BuildStats {
     pub warnings: usize,
     pub warning_details: Vec<BuildWarning>,
     pub error_details: Vec<BuildErrorReport>,
+    /// Time spent in each build phase
+    pub phases: PhaseTimings,
+    /// The documents that took longest to build, slowest first
+    pub slowest_documents: Vec<DocumentTiming>,
+    /// Output files of removed documents deleted by the build
+    pub stale_outputs_removed: usize,
+}
+
+impl BuildStats {
+    /// Machine-readable report of the counts and timings
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "files_processed": self.files_processed,
+            "files_skipped": self.files_skipped,
+            "cache_hits": self.cache_hits,
+            "warnings": self.warnings,
+            "errors": self.errors,
+            "build_time_ms": stats::millis(self.build_time),
+            "output_size_mb": self.output_size_mb,
+            "phases": self.phases,
+            "slowest_documents": self.slowest_documents,
+            "stale_outputs_removed": self.stale_outputs_removed,
+            "outcome": self.outcome(),
+        })
+    }
+
+    /// Outcome of the build these are the statistics of
+    pub fn outcome(&self) -> BuildOutcome {
+        if self.warnings > 0 || self.errors > 0 {
+            BuildOutcome::SuccessWithWarnings
+        } else {
+            BuildOutcome::Success
+        }
+    }
+
+    /// Human-readable timing summary, one line per phase and slow document
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Build time: {}\n", stats::format_duration(self.build_time));
+        for (name, duration) in self.phases.phases() {
+            summary.push_str(&format!("  {:<12}{:>10}\n", name, stats::format_duration(duration)));
+        }
+        if !self.slowest_documents.is_empty() {
+            summary.push_str("Slowest documents:\n");
+            let width = self
+                .slowest_documents
+                .iter()
+                .map(|timing| timing.doc_path.len())
+                .max()
+                .unwrap_or(0);
+            for timing in &self.slowest_documents {
+                summary.push_str(&format!(
+                    "  {:<width$}{:>10} (parse {}, render {}, write {})\n",
+                    timing.doc_path,
+                    stats::format_duration(timing.total()),
+                    stats::format_duration(timing.parse),
+                    stats::format_duration(timing.render),
+                    stats::format_duration(timing.write),
+                    width = width + 2,
+                ));
+            }
+        }
+        summary
+    }
+}
+
+/// Diagnostics of a validate-only run, see [`SphinxBuilder::lint`]
+#[derive(Debug, Clone)]
+pub struct LintReport {
+    /// Number of documents checked
+    pub documents: usize,
+    /// Warnings, ordered by file and line
+    pub warnings: Vec<BuildWarning>,
+    /// Errors, ordered by file and line
+    pub errors: Vec<BuildErrorReport>,
+    pub duration: Duration,
+}
+
+impl LintReport {
+    /// Whether the project has no warnings and no errors
+    pub fn is_clean(&self) -> bool {
+        self.warnings.is_empty() && self.errors.is_empty()
+    }
+
+    /// Machine-readable report with one entry per diagnostic
+    pub fn to_json(&self) -> serde_json::Value {
+        let warnings = self.warnings.iter().map(|warning| {
+            serde_json::json!({
+                "file": warning.file,
+                "line": warning.line,
+                "severity": "warning",
+                "category": warning.warning_type.category(),
+                "message": warning.message,
+                "suggestions": warning.suggestions,
+            })
+        });
+        let errors = self.errors.iter().map(|error| {
+            serde_json::json!({
+                "file": error.file,
+                "line": error.line,
+                "severity": "error",
+                "category": error.error_type.category(),
+                "message": error.message,
+                "suggestions": [],
+            })
+        });
+        serde_json::json!({
+            "documents": self.documents,
+            "duration_ms": stats::millis(self.duration),
+            "warnings": self.warnings.len(),
+            "errors": self.errors.len(),
+            "diagnostics": errors.chain(warnings).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// A source file parsed once and shared by every later build phase
+struct ParsedSource {
+    path: PathBuf,
+    /// Page name without extension, like `guide/intro`
+    doc_path: Symbol,
+    document: Arc<Document>,
+    /// Source text, absent when the document came from the incremental cache
+    /// and needs no rendering
+    content: Option<String>,
+    parse_time: Duration,
+}
+
+/// What the project-wide passes need of a document. Summaries of every
+/// document are kept until the end of the build, while in bounded memory
+/// mode the parsed documents themselves are only kept for a batch.
+struct DocumentSummary {
+    source_path: PathBuf,
+    /// Page name without extension, like `guide/intro`
+    doc_path: Symbol,
+    /// Toctree entries, with globs expanded
+    toctree_entries: Vec<Symbol>,
+    /// The toctrees holding them
+    toctrees: Vec<Toctree>,
+    /// Files pulled in with `include` directives
+    includes: Vec<PathBuf>,
+    /// Entries of `index` directives and `:index:` roles
+    index_terms: Vec<IndexTerm>,
+    /// BibTeX keys of the `:cite:` roles, in order
+    cited_keys: Vec<String>,
+    /// Whether the document has a `bibliography` directive, and whether it
+    /// lists every entry (`:all:`)
+    bibliography: Option<bool>,
 }
 
 /// NavLink with SafeHtml title for template rendering (no escaping needed)
@@ -53,15 +235,16 @@ pub struct SphinxBuilder {
     source_dir: PathBuf,
     output_dir: PathBuf,
     cache: BuildCache,
-    parser: Parser,
+    parser: Arc<Parser>,
     parallel_jobs: usize,
+    /// Worker threads shared by the parallel build phases, sized by `parallel_jobs`
+    thread_pool: Mutex<Option<Arc<rayon::ThreadPool>>>,
     incremental: bool,
     warnings: Arc<Mutex<Vec<BuildWarning>>>,
     errors: Arc<Mutex<Vec<BuildErrorReport>>>,
-    /// Map of document paths (without extension) to their titles
-    document_titles: Arc<Mutex<HashMap<String, String>>>,
-    /// Map of document paths to their sections (title, anchor) for nested toctree entries
-    document_sections: Arc<Mutex<HashMap<String, Vec<(String, String)>>>>,
+    /// Documents, titles, sections, labels and toctrees of the project,
+    /// shared with the page renderers once collected
+    environment: Mutex<Arc<BuildEnvironment>>,
     #[allow(dead_code)]
     sphinx_app: Option<SphinxApp>,
     #[allow(dead_code)]
@@ -69,27 +252,75 @@ pub struct SphinxBuilder {
     /// Theme registry for discovering themes
     #[allow(dead_code)]
     theme_registry: ThemeRegistry,
-    /// The active theme
-    active_theme: Option<Theme>,
+    /// The active theme, reloaded along with the templates
+    active_theme: RwLock<Option<Theme>>,
     /// Navigation builder for document hierarchy
     navigation: Arc<Mutex<NavigationBuilder>>,
-    /// Template engine for rendering HTML
-    template_engine: TemplateEngine,
+    /// Transforms of the parsed documents, run before anything reads them
+    transforms: TransformRegistry,
+    /// Transformations applied to the final HTML of every page
+    post_processors: PostProcessorRegistry,
+    /// Versions switcher for multi-version builds
+    version_switcher: Option<VersionSwitcher>,
+    /// Last git commit time of each source file, for `last_updated`
+    commit_times: Arc<Mutex<HashMap<PathBuf, chrono::DateTime<chrono::Utc>>>>,
+    /// Extra `<link>`/`<script>` attributes of the static assets, such as
+    /// their integrity hashes
+    asset_attributes: Mutex<Arc<AssetAttributes>>,
+    /// Stylesheets and scripts added with `add_css_file` and `add_js_file`
+    extra_assets: PageAssets,
+    /// Template engine for rendering HTML, replaced when the templates
+    /// change between builds
+    template_engine: RwLock<Arc<TemplateEngine>>,
+    /// Newest modification time of the template and theme files when they
+    /// were loaded
+    templates_modified: Mutex<SystemTime>,
+    /// Validators run over every directive and role in the sources
+    directive_validation: Arc<Mutex<DirectiveValidationSystem>>,
+    spell_checker: Option<SpellChecker>,
+    /// Why spell checking is enabled but can't run, reported in each build
+    spelling_unavailable: Option<String>,
+    /// Renderer of the per-page OpenGraph preview images
+    social_cards: Option<SocialCardRenderer>,
+    /// Time spent on each rendered document
+    document_timings: Mutex<Vec<DocumentTiming>>,
+    /// Number of slowest documents kept in the build statistics
+    slowest_documents: usize,
+    /// Spans of the build phases and documents, when tracing
+    trace: Option<BuildTrace>,
+    progress: ProgressTracker,
+    /// Phase the build is in, named by the error of a build that stops
+    current_phase: Mutex<&'static str>,
+    observers: Vec<Arc<dyn BuildObserver>>,
+    /// Sources given in memory, by absolute path, built instead of or in
+    /// addition to the files on disk
+    memory_sources: HashMap<PathBuf, String>,
+    /// Documents generated from documentation comments in code, by absolute
+    /// path, found again by every build
+    collected_sources: Mutex<HashMap<PathBuf, String>>,
+    /// Entries of the pages written by this build, for `manifest.json`
+    manifest_pages: Mutex<Vec<ManifestPage>>,
+    /// Whether any source uses the grid, card or button directives, which
+    /// need the design stylesheet
+    uses_design: AtomicBool,
+    /// Entries of the `bibtex_files` cited in the project, loaded after the
+    /// first pass
+    bibliography: Mutex<Arc<Bibliography>>,
+    /// Checked between documents; once cancelled, builds stop
+    cancellation: CancellationToken,
+    /// Pages written by the current build, reported when it is cancelled
+    documents_written: AtomicUsize,
 }
 
 impl SphinxBuilder {
     pub fn new(config: BuildConfig, source_dir: PathBuf, output_dir: PathBuf) -> Result<Self> {
-        let cache_dir = output_dir.join(".sphinx-ultra-cache");
+        let cache_dir = output_dir.join(CACHE_DIR);
         let cache = BuildCache::new(cache_dir)?;
 
         let mut parser = Parser::new(&config)?;
         parser.set_source_dir(source_dir.clone());
 
-        let parallel_jobs = config.parallel_jobs.unwrap_or_else(|| {
-            std::thread::available_parallelism()
-                .map(|n| n.get())
-                .unwrap_or(4)
-        });
+        let parallel_jobs = config.parallel_jobs.unwrap_or(0);
 
         // Initialize Sphinx app with extensions
         let mut sphinx_app = SphinxApp::new(config.clone())?;
@@ -117,27 +348,78 @@ impl SphinxBuilder {
         let master_doc = config.root_doc.clone().unwrap_or_else(|| "index".to_string());
         let navigation = NavigationBuilder::new(master_doc);
 
-        // Initialize template engine
-        let template_engine = TemplateEngine::new(&config)?;
+        let templates_modified = Self::newest_template_change(&config, &source_dir, active_theme.as_ref());
+        let template_engine = Self::load_template_engine(&config, &source_dir)?;
+
+        let transforms = TransformRegistry::from_config(&config);
+        let post_processors = PostProcessorRegistry::from_config(&config.post_processing);
+
+        let social_cards = if config.opengraph.enabled && config.opengraph.social_cards.enabled {
+            Some(
+                SocialCardRenderer::new(
+                    &config.opengraph.social_cards,
+                    &source_dir,
+                    config.html_logo.as_deref(),
+                )
+                .context("Failed to set up social cards")?,
+            )
+        } else {
+            None
+        };
+
+        // A missing dictionary turns spell checking off with a warning in
+        // every build rather than failing the project
+        let (spell_checker, spelling_unavailable) = if config.spelling.enabled {
+            match SpellChecker::from_config(&config.spelling, &source_dir) {
+                Ok(checker) => (Some(checker), None),
+                Err(err) => (None, Some(format!("{:#}", err))),
+            }
+        } else {
+            (None, None)
+        };
 
         Ok(Self {
             config,
             source_dir,
             output_dir,
             cache,
-            parser,
-            parallel_jobs,
+            parser: Arc::new(parser),
+            parallel_jobs: effective_parallel_jobs(parallel_jobs),
+            thread_pool: Mutex::new(None),
             incremental: false,
             warnings: Arc::new(Mutex::new(Vec::new())),
             errors: Arc::new(Mutex::new(Vec::new())),
-            document_titles: Arc::new(Mutex::new(HashMap::new())),
-            document_sections: Arc::new(Mutex::new(HashMap::new())),
+            environment: Mutex::default(),
             sphinx_app: Some(sphinx_app),
             extension_loader,
             theme_registry,
-            active_theme,
+            active_theme: RwLock::new(active_theme),
             navigation: Arc::new(Mutex::new(navigation)),
-            template_engine,
+            commit_times: Arc::new(Mutex::new(HashMap::new())),
+            asset_attributes: Mutex::new(Arc::default()),
+            extra_assets: PageAssets::default(),
+            version_switcher: None,
+            transforms,
+            post_processors,
+            template_engine: RwLock::new(Arc::new(template_engine)),
+            templates_modified: Mutex::new(templates_modified),
+            directive_validation: Arc::new(Mutex::new(DirectiveValidationSystem::new())),
+            social_cards,
+            spell_checker,
+            spelling_unavailable,
+            document_timings: Mutex::new(Vec::new()),
+            slowest_documents: DEFAULT_SLOWEST_DOCUMENTS,
+            trace: None,
+            progress: ProgressTracker::default(),
+            current_phase: Mutex::new("setup"),
+            observers: Vec::new(),
+            uses_design: AtomicBool::new(false),
+            bibliography: Mutex::new(Arc::default()),
+            cancellation: CancellationToken::new(),
+            documents_written: AtomicUsize::new(0),
+            memory_sources: HashMap::new(),
+            collected_sources: Mutex::default(),
+            manifest_pages: Mutex::new(Vec::new()),
         })
     }
 
@@ -156,7 +438,7 @@ impl SphinxBuilder {
         }
 
         // Add themes directory relative to source directory
-        let src_themes = source_dir.join("_themes");
+        let src_themes = source_dir.join(crate::theme::PROJECT_THEMES_DIR);
         if src_themes.exists() {
             registry.add_search_path(src_themes);
         }
@@ -204,24 +486,291 @@ impl SphinxBuilder {
         }
     }
 
+    /// Template engine with the built-in templates, overridden by those in
+    /// `templates_path`
+    fn load_template_engine(config: &BuildConfig, source_dir: &Path) -> Result<TemplateEngine> {
+        let mut template_engine = TemplateEngine::new(config)?;
+
+        // Templates in templates_path override the theme's templates of the
+        // same name and hold those of html_additional_pages; the first
+        // directory listed wins
+        for templates_dir in config.templates_path.iter().rev() {
+            let templates_dir = source_dir.join(templates_dir);
+            if templates_dir.is_dir() {
+                template_engine.add_override_templates(&templates_dir)?;
+            }
+        }
+        Ok(template_engine)
+    }
+
+    /// Directories of `templates_path` and the active theme's directory
+    fn template_dirs(config: &BuildConfig, source_dir: &Path, theme: Option<&Theme>) -> Vec<PathBuf> {
+        config
+            .templates_path
+            .iter()
+            .map(|dir| source_dir.join(dir))
+            .chain(theme.map(|theme| theme.path.clone()))
+            .collect()
+    }
+
+    /// Newest modification time of the files in `templates_path` and the
+    /// active theme's directory
+    fn newest_template_change(config: &BuildConfig, source_dir: &Path, theme: Option<&Theme>) -> SystemTime {
+        Self::template_dirs(config, source_dir, theme)
+            .into_iter()
+            .flat_map(|dir| walkdir::WalkDir::new(dir).into_iter().filter_map(|entry| entry.ok()))
+            .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+            .max()
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+
+    /// Fingerprint stored with the incremental cache: the configuration's,
+    /// and the names and contents of the template and theme files, so
+    /// documents cached before a template changed are rebuilt
+    fn cache_fingerprint(&self) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.config.fingerprint().as_bytes());
+        for dir in Self::template_dirs(&self.config, &self.source_dir, self.active_theme().as_ref()) {
+            let files = walkdir::WalkDir::new(&dir)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file());
+            for file in files {
+                let Ok(content) = std::fs::read(file.path()) else {
+                    continue;
+                };
+                let name = paths::relative_to(file.path(), &dir).unwrap_or_else(|| file.path().to_path_buf());
+                hasher.update(paths::to_slash(&name).as_bytes());
+                hasher.update(blake3::hash(&content).as_bytes());
+            }
+        }
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// Reload the templates and the active theme when any of their files
+    /// changed since they were loaded, so a builder that builds again, as in
+    /// watch mode, renders with the edited templates. A template that no
+    /// longer compiles is reported and skipped.
+    fn reload_changed_templates(&self) {
+        let modified = Self::newest_template_change(&self.config, &self.source_dir, self.active_theme().as_ref());
+        let mut loaded = self.templates_modified.lock();
+        if modified <= *loaded {
+            return;
+        }
+        *loaded = modified;
+
+        info!("Templates changed, reloading");
+        match Self::load_template_engine(&self.config, &self.source_dir) {
+            Ok(engine) => *self.template_engine.write() = Arc::new(engine),
+            Err(e) => warn!("Failed to reload templates, keeping the previous ones: {:#}", e),
+        }
+        let mut active_theme = self.active_theme.write();
+        if let Some(path) = active_theme.as_ref().map(|theme| theme.path.clone()) {
+            match Theme::from_path(&path) {
+                Ok(theme) => *active_theme = Some(theme),
+                Err(e) => warn!("Failed to reload theme from {}: {:#}", path.display(), e),
+            }
+        }
+    }
+
+    fn template_engine(&self) -> Arc<TemplateEngine> {
+        self.template_engine.read().clone()
+    }
+
+    fn active_theme(&self) -> RwLockReadGuard<'_, Option<Theme>> {
+        self.active_theme.read()
+    }
+
+    /// Record a `template.render` warning for a page that fell back to the
+    /// plain layout, at the error's location in the template, once per
+    /// location
+    fn report_template_error(&self, engine: &TemplateEngine, document: &Document, error: &anyhow::Error) {
+        let warning = match error.downcast_ref::<TemplateError>() {
+            Some(template_error) => {
+                let file = engine
+                    .template_file(&template_error.name)
+                    .map(Path::to_path_buf)
+                    .unwrap_or_else(|| PathBuf::from(&template_error.name));
+                let mut message = format!("template error: {}", template_error.detail);
+                if let Some(ref expression) = template_error.expression {
+                    message.push_str(&format!(" (in `{}`)", expression));
+                }
+                BuildWarning::new(file, template_error.line, message, WarningType::TemplateRender)
+            }
+            None => BuildWarning::new(
+                document.source_path.clone(),
+                None,
+                format!("template error: {:#}", error),
+                WarningType::TemplateRender,
+            ),
+        };
+        debug!(
+            "Template rendering failed for {}: {:#}, using fallback",
+            document.source_path.display(),
+            error
+        );
+        let reported = self
+            .warnings
+            .lock()
+            .iter()
+            .any(|w| w.file == warning.file && w.line == warning.line && w.message == warning.message);
+        if !reported {
+            self.add_warning(warning);
+        }
+    }
+
+    /// Set the number of worker threads; 0 uses one per CPU core
     pub fn set_parallel_jobs(&mut self, jobs: usize) {
-        self.parallel_jobs = jobs;
+        self.parallel_jobs = effective_parallel_jobs(jobs);
+        *self.thread_pool.get_mut() = None;
+    }
+
+    /// The thread pool of the parallel build phases, created on first use
+    fn thread_pool(&self) -> Result<Arc<rayon::ThreadPool>> {
+        let mut pool = self.thread_pool.lock();
+        if let Some(pool) = pool.as_ref() {
+            return Ok(Arc::clone(pool));
+        }
+        let created = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(self.parallel_jobs)
+                .thread_name(|index| format!("sphinx-ultra-{}", index))
+                .build()?,
+        );
+        *pool = Some(Arc::clone(&created));
+        Ok(created)
+    }
+
+    /// Token that cancels the builds of this builder, for Ctrl-C handlers
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Use `token` to cancel builds, like a fresh one after a cancelled build
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation = token;
+    }
+
+    /// Stop with `BuildError::Cancelled` if the build was cancelled
+    fn check_cancelled(&self) -> Result<()> {
+        if self.cancellation.is_cancelled() {
+            return Err(BuildError::Cancelled {
+                documents_written: self.documents_written.load(Ordering::Relaxed),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Set how many of the slowest documents the build statistics list
+    pub fn set_slowest_documents(&mut self, count: usize) {
+        self.slowest_documents = count;
+    }
+
+    /// Record a trace of the build phases and of every document
+    pub fn enable_trace(&mut self) {
+        self.trace = Some(BuildTrace::new());
+    }
+
+    /// Report the phases and the parsed and rendered documents of every
+    /// build to `reporter`
+    pub fn set_progress_reporter(&mut self, reporter: Arc<dyn ProgressReporter>) {
+        self.progress = ProgressTracker::new(reporter);
+    }
+
+    /// Tell `observer` about every following build and its documents
+    pub fn add_observer(&mut self, observer: Arc<dyn BuildObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Build `content` as the source file at `path`, relative to the source
+    /// directory, in place of the file on disk or as a document of its own
+    pub fn set_source(&mut self, path: impl AsRef<Path>, content: impl Into<String>) {
+        self.memory_sources
+            .insert(self.source_dir.join(path), content.into());
+    }
+
+    /// Stop building the in-memory source at `path`, relative to the source
+    /// directory. A file on disk at the path is built again.
+    pub fn remove_source(&mut self, path: impl AsRef<Path>) {
+        self.memory_sources.remove(&self.source_dir.join(path));
+    }
+
+    /// The trace of the last build, if tracing is enabled
+    pub fn trace(&self) -> Option<&BuildTrace> {
+        self.trace.as_ref()
+    }
+
+    /// Record a span ending now in the trace
+    fn trace_span(&self, name: &str, category: &str, start: Instant) {
+        if let Some(trace) = &self.trace {
+            trace.record(name, category, start, Instant::now());
+        }
+    }
+
+    /// Start `phase` of the build, for the progress reporter and the outcome
+    /// of a build that stops in it
+    fn start_phase(&self, phase: &'static str, total: usize) {
+        *self.current_phase.lock() = phase;
+        self.progress.start_phase(phase, total);
+    }
+
+    /// Add the time since `start` to a phase of the build statistics
+    fn end_phase(&self, phase: &mut Duration, name: &str, start: Instant) {
+        *phase += start.elapsed();
+        self.trace_span(name, trace::PHASE, start);
+    }
+
+    /// Register a transform of the parsed documents, run among the built-in
+    /// ones by its priority
+    pub fn add_transform(&mut self, transform: Box<dyn DocumentTransform + Send + Sync>) {
+        self.transforms.register(transform);
+    }
+
+    /// Register a transformation of the final HTML of every page, run after
+    /// the built-in ones in registration order
+    pub fn add_post_processor(&mut self, processor: Box<dyn HtmlPostProcessor + Send + Sync>) {
+        self.post_processors.register(processor);
+    }
+
+    /// Link a stylesheet from every page, among the others by its priority
+    pub fn add_css_file(&mut self, asset: Asset) {
+        self.extra_assets.add_css_file(asset);
+    }
+
+    /// Load a script on every page, among the others by its priority
+    pub fn add_js_file(&mut self, asset: Asset) {
+        self.extra_assets.add_js_file(asset);
+    }
+
+    /// Give every page the `versions` and `current_version` template
+    /// variables of a multi-version build
+    pub fn set_version_switcher(&mut self, switcher: VersionSwitcher) {
+        self.version_switcher = Some(switcher);
     }
 
     pub fn enable_incremental(&mut self) {
         self.incremental = true;
     }
 
-    /// Add a warning to the collection
-    #[allow(dead_code)]
+    /// Add a warning to the collection unless its category is suppressed
     pub fn add_warning(&self, warning: BuildWarning) {
-        self.warnings.lock().unwrap().push(warning);
+        if self.is_suppressed(&warning) {
+            return;
+        }
+        self.warnings.lock().push(warning);
+    }
+
+    fn is_suppressed(&self, warning: &BuildWarning) -> bool {
+        let category = warning.warning_type.category();
+        self.config.suppress_warnings.iter().any(|c| c == category)
     }
 
     /// Add an error to the collection
     #[allow(dead_code)]
     pub fn add_error(&self, error: BuildErrorReport) {
-        self.errors.lock().unwrap().push(error);
+        self.errors.lock().push(error);
     }
 
     /// Check if warnings should be treated as errors
@@ -230,6 +779,20 @@ impl SphinxBuilder {
         self.config.fail_on_warning
     }
 
+    /// Check if the build's warnings violate the configured warning policy
+    pub fn warning_policy_violated(&self, stats: &BuildStats) -> bool {
+        self.config.fail_on_warning
+            && self
+                .config
+                .warning_policy
+                .is_violated(&stats.warning_details)
+    }
+
+    /// Statistics gathered by directive and role validation during the last build
+    pub fn directive_validation_statistics(&self) -> ValidationStatistics {
+        self.directive_validation.lock().statistics().clone()
+    }
+
     pub async fn clean(&self) -> Result<()> {
         if self.output_dir.exists() {
             tokio::fs::remove_dir_all(&self.output_dir).await?;
@@ -237,98 +800,262 @@ impl SphinxBuilder {
         Ok(())
     }
 
-    /// Collect document titles and toctree entries from all source files (first pass).
+    /// Collect document titles and toctree entries from the parsed sources (first pass).
     /// This is used to populate toctree entries with proper document titles and build navigation.
-    fn collect_document_titles(&self, files: &[PathBuf]) -> Result<()> {
-        let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(self.parallel_jobs)
-            .build()?;
-
-        // Pre-canonicalize output directory for comparison
-        let canonical_output = self.output_dir.canonicalize().ok();
+    /// `documents` names every document of the project, for toctree globs.
+    fn collect_document_titles(
+        &self,
+        sources: &[ParsedSource],
+        documents: &[String],
+    ) -> Result<Vec<DocumentSummary>> {
+        let pool = self.thread_pool()?;
 
         // Collect titles and toctree entries
         let doc_info: Vec<_> = pool.install(|| {
-            files
+            sources
                 .par_iter()
-                .filter_map(|file_path| {
-                    // Safety check: skip files that are inside the output directory
-                    if let Some(ref output) = canonical_output {
-                        if let Ok(canonical_file) = file_path.canonicalize() {
-                            if canonical_file.starts_with(output) {
-                                log::warn!(
-                                    "Skipping file inside output directory: {}",
-                                    file_path.display()
-                                );
-                                return None;
-                            }
-                        }
-                    }
-
-                    // Read and parse the file to extract its title
-                    let content = std::fs::read_to_string(file_path).ok()?;
-                    let doc = self.parser.parse(file_path, &content).ok()?;
+                .map(|source| {
+                    self.check_cancelled()?;
+                    let doc = &source.document;
 
-                    // Get the document path relative to source dir, without extension
-                    let relative_path = file_path.strip_prefix(&self.source_dir).ok()?;
-                    let doc_path = relative_path
-                        .with_extension("")
-                        .to_string_lossy()
-                        .replace('\\', "/"); // Normalize path separators
-
-                    // Extract toctree entries
-                    let toctree_entries = self.extract_toctree_references(&doc).unwrap_or_default();
+                    // Extract toctrees and their entries
+                    let toctrees = self.extract_toctrees(doc, documents);
 
                     // Extract sections (sub-titles) from the document for nested toctree entries
-                    let sections = Self::extract_document_sections(&doc);
+                    let sections = Self::extract_document_sections(doc);
 
                     // Return doc info
                     let title = if !doc.title.is_empty() && doc.title != "Untitled" {
-                        doc.title
+                        Symbol::intern(&doc.title)
                     } else {
-                        doc_path.clone()
+                        source.doc_path.clone()
                     };
+                    let toctree_entries: Vec<Symbol> =
+                        toctrees.iter().flat_map(|toctree| toctree.entries.iter().cloned()).collect();
+                    let includes = doc.includes.clone();
+                    let index_terms = doc.metadata.index_terms.clone();
+                    let mut citations = (Vec::new(), None);
+                    if let DocumentContent::RestructuredText(rst) = &doc.content {
+                        if design::uses_design(&rst.raw) {
+                            self.uses_design.store(true, Ordering::Relaxed);
+                        }
+                        citations.0 = bibtex::cited_keys(&rst.raw);
+                        citations.1 = rst.ast.iter().find_map(|node| match node {
+                            RstNode::Directive { name, options, .. } if name == "bibliography" => {
+                                Some(options.contains_key("all"))
+                            }
+                            _ => None,
+                        });
+                    }
 
-                    Some((doc_path, title, toctree_entries, sections))
+                    Ok((source, title, (toctree_entries, toctrees), includes, index_terms, citations, sections))
                 })
-                .collect()
-        });
-
-        // Store collected titles, sections, and build navigation
-        let mut doc_titles = self.document_titles.lock().unwrap();
-        let mut doc_sections = self.document_sections.lock().unwrap();
-        let mut nav = self.navigation.lock().unwrap();
+                .collect::<Result<_>>()
+        })?;
 
-        for (path, title, toctree_entries, sections) in doc_info {
-            doc_titles.insert(path.clone(), title.clone());
+        // Store collected titles, sections and labels, and build navigation
+        let mut environment = self.environment.lock();
+        let environment = Arc::make_mut(&mut environment);
+        let mut nav = self.navigation.lock();
+
+        let mut summaries = Vec::with_capacity(doc_info.len());
+        for (source, title, (toctree_entries, toctrees), includes, index_terms, (cited_keys, bibliography), sections) in doc_info {
+            let path = &source.doc_path;
+            nav.register_document(path, &title);
+            let mtime = match self.in_memory_source(&source.path) {
+                Some(_) => 0.0,
+                None => utils::get_file_mtime(&source.path).map_or(0.0, |mtime| mtime.timestamp_millis() as f64 / 1000.0),
+            };
+            environment.add_document(path.clone(), mtime);
+            environment.source_paths.insert(path.clone(), source.path.clone());
+            environment.titles.insert(path.clone(), title);
             if !sections.is_empty() {
-                doc_sections.insert(path.clone(), sections);
+                environment.sections.insert(path.clone(), sections);
+            }
+            if !includes.is_empty() {
+                environment.dependencies.insert(path.to_string(), includes.iter().cloned().collect());
+            }
+            if let DocumentContent::RestructuredText(rst) = &source.document.content {
+                for (index, node) in rst.ast.iter().enumerate() {
+                    if let RstNode::LinkTarget { name, .. } = node {
+                        let title = match rst.ast.get(index + 1) {
+                            Some(RstNode::Title { text, .. }) => Some(text.clone()),
+                            _ => None,
+                        };
+                        let label = Label { docname: path.clone(), anchor: name.clone(), title };
+                        environment.add_label(name, label);
+                    }
+                }
+                let enumerables = numfig::enumerables(&rst.ast);
+                if !enumerables.is_empty() {
+                    environment.enumerables.insert(path.clone(), enumerables);
+                }
             }
-            nav.register_document(&path, &title);
             if !toctree_entries.is_empty() {
-                nav.register_toctree(&path, toctree_entries);
+                nav.register_toctree(path, toctree_entries.iter().cloned());
             }
+            summaries.push(DocumentSummary {
+                source_path: source.path.clone(),
+                doc_path: path.clone(),
+                toctree_entries,
+                toctrees,
+                includes,
+                index_terms,
+                cited_keys,
+                bibliography,
+            });
         }
 
-        Ok(())
+        Ok(summaries)
+    }
+
+    /// Complete the environment once every document is known: resolve the
+    /// toctree entries, list the documents and labels in the standard domain
+    /// and record the assets of the pages
+    fn finish_environment(&self, summaries: &[DocumentSummary]) {
+        let css_files = self.css_files();
+        let js_files = self.script_files();
+        let attributes: AssetAttributes = css_files
+            .iter()
+            .chain(&js_files)
+            .filter(|asset| !asset.attributes.is_empty())
+            .map(|asset| (asset.url(), asset.attributes.clone()))
+            .collect();
+        *self.asset_attributes.lock() = Arc::new(attributes);
+        let mut environment = self.environment.lock();
+        let environment = Arc::make_mut(&mut environment);
+        for name in STANDARD_LABELS {
+            let label = Label { docname: Symbol::intern(name), anchor: String::new(), title: None };
+            environment.add_label(name, label);
+        }
+        for summary in summaries {
+            let toctrees: Vec<Toctree> = summary
+                .toctrees
+                .iter()
+                .map(|toctree| Toctree {
+                    entries: toctree
+                        .entries
+                        .iter()
+                        .filter_map(|entry| {
+                            let (_, target) = navigation::split_toctree_entry(entry);
+                            let resolved = navigation::resolve_toctree_target(&summary.doc_path, target, |name| {
+                                environment.has_document(name)
+                            });
+                            environment.has_document(&resolved).then(|| Symbol::from(resolved))
+                        })
+                        .collect(),
+                    ..toctree.clone()
+                })
+                .collect();
+            let children: Vec<Symbol> =
+                toctrees.iter().flat_map(|toctree| toctree.entries.iter().cloned()).collect();
+            if !children.is_empty() {
+                environment.toctree_includes.insert(summary.doc_path.clone(), children);
+            }
+            if !toctrees.is_empty() {
+                environment.toctrees.insert(summary.doc_path.clone(), toctrees);
+            }
+        }
+
+        let mut domains = environment::create_standard_domains();
+        let std_domain = domains.get_mut("std").expect("standard domain");
+        for docname in &environment.found_docs {
+            std_domain.add_object("doc", DomainObject::new(docname.to_string(), "doc".to_string(), docname.to_string(), None, 1));
+        }
+        for (name, label) in &environment.labels {
+            let anchor = Some(label.anchor.clone()).filter(|anchor| !anchor.is_empty());
+            let object = DomainObject::new(name.clone(), "label".to_string(), label.docname.to_string(), anchor, 1);
+            std_domain.add_object("label", object.with_display_name(label.title.clone().unwrap_or_else(|| name.clone())));
+        }
+        environment.domains = domains;
+        if self.config.numfig {
+            environment.assign_figure_numbers();
+        }
+        environment.assign_section_numbers();
+        environment.css_files = css_files.iter().map(Asset::url).collect();
+        environment.js_files = js_files.iter().map(Asset::url).collect();
+    }
+
+    /// The environment of the last build, with everything known about its
+    /// documents
+    pub fn environment(&self) -> Arc<BuildEnvironment> {
+        Arc::clone(&self.environment.lock())
+    }
+
+    /// Read the `bibtex_files` and label the entries cited in the project,
+    /// listed at the first document with a `bibliography` directive
+    fn load_bibliography(&self, summaries: &[DocumentSummary]) {
+        if self.config.bibtex_files.is_empty() {
+            return;
+        }
+        let mut entries = Vec::new();
+        for file in &self.config.bibtex_files {
+            let path = self.source_dir.join(file);
+            let source = match std::fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(err) => {
+                    self.add_warning(BuildWarning::new(
+                        path,
+                        None,
+                        format!("cannot read BibTeX file: {}", err),
+                        WarningType::MissingFile,
+                    ));
+                    continue;
+                }
+            };
+            let (file_entries, errors) = bibtex::parse(&source);
+            for error in errors {
+                self.add_warning(BuildWarning::new(
+                    path.clone(),
+                    Some(error.line),
+                    format!("invalid BibTeX entry: {}", error.message),
+                    WarningType::Other,
+                ));
+            }
+            entries.extend(file_entries);
+        }
+
+        let cited: Vec<String> = summaries
+            .iter()
+            .flat_map(|summary| summary.cited_keys.iter().cloned())
+            .collect();
+        let listing = summaries
+            .iter()
+            .find_map(|summary| summary.bibliography.map(|all| (summary.doc_path.to_string(), all)));
+        if listing.is_none() && !cited.is_empty() {
+            self.add_warning(BuildWarning::new(
+                self.source_dir.clone(),
+                None,
+                "citations are not linked: no document has a bibliography directive".to_string(),
+                WarningType::BrokenCrossReference,
+            ));
+        }
+        let (page, all) = listing.map_or((None, false), |(page, all)| (Some(page), all));
+        *self.bibliography.lock() = Arc::new(Bibliography::new(
+            entries,
+            &cited,
+            all,
+            page,
+            self.config.bibtex_default_style,
+        ));
     }
 
-    /// Extract sections (sub-titles) from a document for nested toctree entries.
-    /// Returns a vector of (title, anchor) tuples for level 2 headers.
-    fn extract_document_sections(doc: &Document) -> Vec<(String, String)> {
+    /// Extract sections (sub-titles) from a document for nested toctree entries,
+    /// in document order with their heading levels (the title is level 1).
+    fn extract_document_sections(doc: &Document) -> Vec<TocSection> {
         use crate::document::{DocumentContent, RstNode};
-        use crate::renderer::slugify;
+        use crate::renderer::SlugRegistry;
 
         let mut sections = Vec::new();
 
         if let DocumentContent::RestructuredText(rst) = &doc.content {
+            // The title takes its id too, so all headings are registered
+            let mut slugs = SlugRegistry::new();
             for node in &rst.ast {
                 if let RstNode::Title { text, level, .. } = node {
-                    // Only include level 2 headers (immediate sub-sections)
-                    if *level == 2 {
-                        // Generate anchor/slug from title
-                        let anchor = slugify(text);
-                        sections.push((text.clone(), anchor));
+                    let anchor = slugs.heading_id(text);
+                    if *level >= 2 {
+                        sections.push(TocSection::new(text.clone(), anchor, *level));
                     }
                 }
             }
@@ -337,61 +1064,236 @@ impl SphinxBuilder {
         sections
     }
 
+    /// Forget the diagnostics, documents and navigation of the last run
+    fn reset(&self) {
+        *self.current_phase.lock() = "setup";
+        self.reload_changed_templates();
+        self.document_timings.lock().clear();
+        self.warnings.lock().clear();
+        self.errors.lock().clear();
+        self.manifest_pages.lock().clear();
+        self.uses_design.store(false, Ordering::Relaxed);
+        self.documents_written.store(0, Ordering::Relaxed);
+        *self.bibliography.lock() = Arc::default();
+        *self.environment.lock() = Arc::new(BuildEnvironment::new(self.config.clone()));
+        let master_doc = self.config.root_doc.clone().unwrap_or_else(|| "index".to_string());
+        *self.navigation.lock() = NavigationBuilder::new(master_doc);
+        if let Some(reason) = &self.spelling_unavailable {
+            self.add_warning(BuildWarning::new(
+                self.source_dir.clone(),
+                None,
+                format!("spell checking skipped: {}", reason),
+                WarningType::Spelling,
+            ));
+        }
+        // The strings only the last run's documents used
+        crate::intern::release_unused();
+    }
+
+    /// Build the project. A builder can build any number of times; each
+    /// build starts over from the current sources.
     pub async fn build(&self) -> Result<BuildStats> {
+        for observer in &self.observers {
+            observer.build_started();
+        }
+        let result = self.build_all().await.map_err(|error| {
+            let phase = *self.current_phase.lock();
+            error.context(PhaseFailure { phase })
+        });
+        self.progress.finish();
+        for observer in &self.observers {
+            observer.build_finished(result.as_ref());
+        }
+        result
+    }
+
+    async fn build_all(&self) -> Result<BuildStats> {
         let start_time = Instant::now();
         info!("Starting build process...");
+        let mut phases = PhaseTimings::default();
+        self.reset();
+
+        // Documents cached under other options or templates may render
+        // differently
+        if self.incremental && self.cache.check_config(&self.cache_fingerprint())? {
+            info!("Configuration changed, rebuilding every document");
+        }
 
         // Ensure output directory exists
         tokio::fs::create_dir_all(&self.output_dir).await
             .with_context(|| format!("Failed to create output directory: {}", self.output_dir.display()))?;
 
         // Discover all source files
+        let phase_start = Instant::now();
+        self.start_phase("discovery", 0);
         let source_files = self.discover_source_files().await?;
         info!("Discovered {} source files", source_files.len());
+        self.check_cancelled()?;
 
-        // Build dependency graph
-        let dependency_graph = self.build_dependency_graph(&source_files).await?;
+        // Look up commit times in one batch for the last-updated dates
+        if self.config.html_last_updated_fmt.is_some() {
+            *self.commit_times.lock() = utils::git_last_commit_times(&self.source_dir);
+        }
+
+        self.end_phase(&mut phases.discovery, "discovery", phase_start);
+
+        // Parse every source once; later passes share the documents. In
+        // bounded memory mode only one batch is parsed at a time, and parsed
+        // again for rendering.
+        let bounded_memory = self.config.memory.bounded;
+        let batch_size = if bounded_memory {
+            self.config.memory.batch_size.max(1)
+        } else {
+            source_files.len().max(1)
+        };
+        let document_names = self.document_names(&source_files);
+        let mut parsed_batches = Vec::new();
+        let mut summaries = Vec::with_capacity(source_files.len());
+        self.start_phase("parse", source_files.len());
+        for batch in source_files.chunks(batch_size) {
+            let batch_start = Instant::now();
+            let sources = self.parse_source_files(batch)?;
+            self.trace_span("parse", trace::PHASE, batch_start);
+
+            // First pass: Collect document titles for toctree rendering
+            let batch_start = Instant::now();
+            summaries.extend(self.collect_document_titles(&sources, &document_names)?);
+            self.trace_span("collect titles", trace::PHASE, batch_start);
+            if bounded_memory {
+                phases.parse += sources.iter().map(|source| source.parse_time).sum::<Duration>();
+            } else {
+                parsed_batches.push(sources);
+            }
+        }
         debug!(
-            "Built dependency graph with {} nodes",
-            dependency_graph.len()
+            "Collected {} document titles",
+            self.environment.lock().titles.len()
         );
+        self.load_bibliography(&summaries);
+        self.finish_environment(&summaries);
 
-        // First pass: Collect document titles for toctree rendering
-        self.collect_document_titles(&source_files)?;
+        // Build dependency graph
+        let phase_start = Instant::now();
+        let dependency_graph = self.build_dependency_graph(&summaries);
         debug!(
-            "Collected {} document titles",
-            self.document_titles.lock().unwrap().len()
+            "Built dependency graph with {} edges",
+            dependency_graph.values().map(Vec::len).sum::<usize>()
         );
+        self.end_phase(&mut phases.discovery, "dependency graph", phase_start);
+
+        // Static assets are finished before pages are rendered, so page tags
+        // can carry integrity hashes of the files as served
+        self.check_cancelled()?;
+        let phase_start = Instant::now();
+        self.start_phase("static assets", 0);
+        self.copy_static_assets().await?;
+        self.prepare_static_assets()?;
+        self.end_phase(&mut phases.assets, "static assets", phase_start);
+
+        // Process files in dependency order, validating each batch of
+        // documents before it is dropped
+        self.directive_validation.lock().reset_statistics();
+        self.start_phase("render", summaries.len());
+        let mut parsed_batches = parsed_batches.into_iter();
+        let mut files_processed = 0;
+        // Files that crashed the parser were reported in the first pass
+        let parsed_files: HashSet<&Path> = summaries
+            .iter()
+            .map(|summary| summary.source_path.as_path())
+            .collect();
+        for batch in source_files.chunks(batch_size) {
+            let batch_start = Instant::now();
+            let sources = match parsed_batches.next() {
+                Some(sources) => sources,
+                None => {
+                    let batch: Vec<PathBuf> = batch
+                        .iter()
+                        .filter(|file| parsed_files.contains(file.as_path()))
+                        .cloned()
+                        .collect();
+                    self.parse_source_files(&batch)?
+                }
+            };
+            let processed_docs = self
+                .process_files_parallel(&sources, &dependency_graph)
+                .await?;
+            self.trace_span("render", trace::PHASE, batch_start);
+            drop(sources);
+            files_processed += processed_docs.len();
 
-        // Process files in dependency order
-        let processed_docs = self
-            .process_files_parallel(&source_files, &dependency_graph)
-            .await?;
+            let phase_start = Instant::now();
+            self.validate_document_contents(&processed_docs)?;
+            self.end_phase(&mut phases.validation, "validation", phase_start);
+        }
+        debug!(
+            "Directive validation:\n{}",
+            self.directive_validation.lock().statistics()
+        );
 
-        // Validate documents and collect warnings/errors
-        self.validate_documents(&processed_docs, &source_files)
-            .await?;
+        // Validate the toctree structure and collect warnings/errors
+        self.check_cancelled()?;
+        let phase_start = Instant::now();
+        self.start_phase("validation", 0);
+        self.validate_documents(&summaries).await?;
+        self.end_phase(&mut phases.validation, "toctree validation", phase_start);
 
         // Generate cross-references and indices
-        self.generate_indices(&processed_docs).await?;
-
-        // Copy static assets
-        self.copy_static_assets().await?;
+        let phase_start = Instant::now();
+        self.start_phase("indices", 0);
+        self.generate_indices(&summaries).await?;
+
+        // Render html_additional_pages and the 404 page
+        self.write_additional_pages()?;
+        self.write_not_found_page(&summaries)?;
+        self.end_phase(&mut phases.index, "indices", phase_start);
+
+        // GitHub Pages files go first so html_extra_path can override them
+        self.check_cancelled()?;
+        let phase_start = Instant::now();
+        self.start_phase("extra paths", 0);
+        self.write_github_pages_files()?;
+        self.write_redirects(&summaries)?;
 
         // Copy html_extra_path directories to output root
-        self.copy_extra_paths().await?;
+        let extra_files = self.copy_extra_paths(&summaries).await?;
+        self.end_phase(&mut phases.assets, "extra paths", phase_start);
 
         // Generate sitemap and search index
-        self.generate_search_index(&processed_docs).await?;
+        let phase_start = Instant::now();
+        self.start_phase("search index", 0);
+        self.generate_search_index(&summaries).await?;
+        if self.config.output.manifest {
+            self.write_manifest(&summaries)?;
+        }
+        self.end_phase(&mut phases.index, "search index", phase_start);
+
+        self.check_cancelled()?;
+        let stale_outputs_removed = self.remove_stale_outputs(&summaries, &extra_files)?;
+
+        // Pre-compress the finished output
+        if self.config.output.compress_output && !self.config.output.compression_formats.is_empty() {
+            let phase_start = Instant::now();
+            self.start_phase("precompress", 0);
+            let compressed = self.thread_pool()?.install(|| {
+                optimize::precompress(&self.output_dir, &self.config.output.compression_formats)
+            })?;
+            info!("Wrote {} pre-compressed files", compressed);
+            self.end_phase(&mut phases.assets, "precompress", phase_start);
+        }
+
+        let document_timings = std::mem::take(&mut *self.document_timings.lock());
+        for timing in &document_timings {
+            phases.add_document(timing);
+        }
 
         let build_time = start_time.elapsed();
         let output_size = utils::calculate_directory_size(&self.output_dir).await?;
 
-        let warnings = self.warnings.lock().unwrap();
-        let errors = self.errors.lock().unwrap();
+        let warnings = self.warnings.lock();
+        let errors = self.errors.lock();
 
         let stats = BuildStats {
-            files_processed: processed_docs.len(),
+            files_processed,
             files_skipped: 0, // TODO: Track skipped files
             build_time,
             output_size_mb: output_size as f64 / 1024.0 / 1024.0,
@@ -400,12 +1302,116 @@ impl SphinxBuilder {
             warnings: warnings.len(),
             warning_details: warnings.clone(),
             error_details: errors.clone(),
+            phases,
+            slowest_documents: stats::slowest_documents(document_timings, self.slowest_documents),
+            stale_outputs_removed,
         };
 
         info!("Build completed in {:?}", build_time);
         Ok(stats)
     }
 
+    /// Check the project without writing any output, for pre-commit hooks
+    /// and editors. Runs discovery, parsing, directive, role and content
+    /// validation, the toctree checks and a check of `:doc:`, `:ref:` and
+    /// `:download:` targets. Document bodies are rendered to find include
+    /// problems, but no page is templated or written.
+    pub async fn lint(&self) -> Result<LintReport> {
+        let start_time = Instant::now();
+        self.reset();
+        let source_files = self.discover_source_files().await?;
+        info!("Checking {} source files", source_files.len());
+
+        let document_names = self.document_names(&source_files);
+        let sources = self.parse_source_files(&source_files)?;
+        let summaries = self.collect_document_titles(&sources, &document_names)?;
+        self.load_bibliography(&summaries);
+        self.finish_environment(&summaries);
+
+        self.directive_validation.lock().reset_statistics();
+        self.thread_pool()?.install(|| {
+            sources.par_iter().try_for_each(|source| {
+                self.check_cancelled()?;
+                let Some(content) = &source.content else {
+                    return Ok(());
+                };
+                self.isolate_crash(&source.path, "rendering", || self.render_body(source, content))
+                    .map(drop)
+            })
+        })?;
+        let documents: Vec<Arc<Document>> = sources
+            .iter()
+            .map(|source| Arc::clone(&source.document))
+            .collect();
+        self.validate_document_contents(&documents)?;
+        self.check_references(&sources);
+        self.validate_documents(&summaries).await?;
+
+        let mut warnings = std::mem::take(&mut *self.warnings.lock());
+        let mut errors = std::mem::take(&mut *self.errors.lock());
+        warnings.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        errors.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        Ok(LintReport {
+            documents: sources.len(),
+            warnings,
+            errors,
+            duration: start_time.elapsed(),
+        })
+    }
+
+    /// Warn about `:doc:` targets that are not documents, `:ref:` targets
+    /// that are not labels and `:download:` targets that are not files
+    fn check_references(&self, sources: &[ParsedSource]) {
+        let environment = self.environment();
+        let document_names = environment.document_names();
+        let label_names = environment.label_names();
+
+        for source in sources {
+            let DocumentContent::RestructuredText(rst) = &source.document.content else {
+                continue;
+            };
+            let parser = DirectiveRoleParser::new(source.path.display().to_string());
+            let (_, roles) = parser.parse_content(&rst.raw);
+            for role in roles {
+                let target = role.target.trim_start_matches(['~', '!']).trim();
+                // Whether the target exists, and the names it may be a typo of
+                let (found, typo_of) = match role.name.as_str() {
+                    "doc" => {
+                        let resolved = navigation::resolve_toctree_target(&source.doc_path, target, |name| {
+                            environment.has_document(name)
+                        });
+                        (environment.has_document(&resolved), Some((resolved, &document_names)))
+                    }
+                    "ref" => {
+                        let label = target.to_lowercase();
+                        (environment.resolve_label(&label).is_some(), Some((label, &label_names)))
+                    }
+                    "download" => {
+                        let path = match target.strip_prefix('/') {
+                            Some(from_root) => self.source_dir.join(from_root),
+                            None => source.path.parent().unwrap_or(&self.source_dir).join(target),
+                        };
+                        (path.exists(), None)
+                    }
+                    _ => continue,
+                };
+                if !found {
+                    let suggestions = typo_of
+                        .map(|(name, candidates)| similar_names(&name, candidates))
+                        .unwrap_or_default();
+                    self.add_warning(
+                        BuildWarning::broken_cross_reference(
+                            source.path.clone(),
+                            Some(role.location.line),
+                            &format!(":{}:`{}`", role.name, role.target),
+                        )
+                        .with_suggestions(suggestions),
+                    );
+                }
+            }
+        }
+    }
+
     async fn discover_source_files(&self) -> Result<Vec<PathBuf>> {
         // Use pattern-based file discovery like Sphinx
         let mut include_patterns = self.config.include_patterns.clone();
@@ -420,9 +1426,9 @@ impl SphinxBuilder {
             ];
         }
 
-        // Add built-in exclude patterns for common build artifacts and hidden files
-        let mut all_exclude_patterns = exclude_patterns.clone();
-        all_exclude_patterns.extend_from_slice(&[
+        // Built-in exclude patterns for common build artifacts and hidden
+        // files come first, so `!` patterns of the project can override them
+        let mut all_exclude_patterns = vec![
             "_build/**".to_string(),
             "__pycache__/**".to_string(),
             ".git/**".to_string(),
@@ -431,14 +1437,15 @@ impl SphinxBuilder {
             ".*/**".to_string(), // Skip all hidden directories
             "Thumbs.db".to_string(),
             ".DS_Store".to_string(),
-        ]);
+        ];
+        all_exclude_patterns.extend_from_slice(exclude_patterns);
 
         // Exclude the actual output directory if it's inside the source directory
         // Canonicalize source (should always exist), but handle output specially
-        let canonical_source = self.source_dir.canonicalize().unwrap_or_else(|_| self.source_dir.clone());
+        let canonical_source = paths::canonicalize(&self.source_dir).unwrap_or_else(|_| self.source_dir.clone());
 
         // For output dir, try canonicalize, but if it doesn't exist yet, construct the path manually
-        let canonical_output = self.output_dir.canonicalize().unwrap_or_else(|_| {
+        let canonical_output = paths::canonicalize(&self.output_dir).unwrap_or_else(|_| {
             // If output_dir is relative, join with source_dir
             if self.output_dir.is_relative() {
                 canonical_source.join(&self.output_dir)
@@ -464,12 +1471,18 @@ impl SphinxBuilder {
             );
         }
 
-        match matching::get_matching_files(
+        let match_options = matching::MatchOptions {
+            follow_symlinks: self.config.follow_symlinks,
+            ignore_files: self.config.ignore_files.clone(),
+            ..matching::MatchOptions::default()
+        };
+        let mut files = match matching::get_matching_files_with(
             &self.source_dir,
             &include_patterns,
             &all_exclude_patterns,
+            &match_options,
         ) {
-            Ok(files) => Ok(files),
+            Ok(files) => files,
             Err(e) => {
                 log::warn!(
                     "Pattern matching failed, falling back to simple discovery: {}",
@@ -478,8 +1491,53 @@ impl SphinxBuilder {
                 // Fallback to old method if pattern matching fails
                 let mut files = Vec::new();
                 self.discover_files_sync(&self.source_dir, &mut files)?;
-                Ok(files)
+                files
+            }
+        };
+
+        // In-memory and generated sources are built whether or not a file
+        // exists
+        self.collect_docstrings();
+        let collected = self.collected_sources.lock();
+        if !self.memory_sources.is_empty() || !collected.is_empty() {
+            files.extend(self.memory_sources.keys().cloned());
+            files.extend(collected.keys().cloned());
+            files.sort();
+            files.dedup();
+        }
+        Ok(files)
+    }
+
+    /// Generate the documents of the documentation comments in code
+    fn collect_docstrings(&self) {
+        let mut collected = self.collected_sources.lock();
+        collected.clear();
+        if self.config.docstrings.patterns.is_empty() {
+            return;
+        }
+        match docstrings::collect(&self.source_dir, &self.config.docstrings) {
+            Ok(documents) => {
+                collected.extend(
+                    documents
+                        .into_iter()
+                        .map(|document| (self.source_dir.join(document.path), document.content)),
+                );
             }
+            Err(message) => self.add_warning(BuildWarning::new(
+                self.source_dir.join(&self.config.docstrings.root),
+                None,
+                message,
+                WarningType::MissingFile,
+            )),
+        }
+    }
+
+    /// Content of a source given in memory or generated, rather than read
+    /// from disk
+    fn in_memory_source(&self, file_path: &Path) -> Option<String> {
+        match self.memory_sources.get(file_path) {
+            Some(content) => Some(content.clone()),
+            None => self.collected_sources.lock().get(file_path).cloned(),
         }
     }
 
@@ -495,7 +1553,7 @@ impl SphinxBuilder {
             if path.is_dir() {
                 // Skip the output directory to avoid infinite loops
                 // Use canonicalize to handle relative vs absolute paths
-                let dominated_by_output = match (path.canonicalize(), self.output_dir.canonicalize()) {
+                let dominated_by_output = match (paths::canonicalize(&path), paths::canonicalize(&self.output_dir)) {
                     (Ok(canonical_path), Ok(canonical_output)) => {
                         canonical_path == canonical_output || canonical_path.starts_with(&canonical_output)
                     }
@@ -535,53 +1593,109 @@ impl SphinxBuilder {
         }
     }
 
-    async fn build_dependency_graph(
-        &self,
-        files: &[PathBuf],
-    ) -> Result<HashMap<PathBuf, Vec<PathBuf>>> {
-        let mut graph = HashMap::new();
+    /// Map each source file to the source files rendered before it: the
+    /// documents it includes and its toctree children, so numbering can be
+    /// assigned bottom-up
+    fn build_dependency_graph(&self, documents: &[DocumentSummary]) -> HashMap<PathBuf, Vec<PathBuf>> {
+        let sources: HashMap<&str, &PathBuf> = documents
+            .iter()
+            .map(|doc| (doc.doc_path.as_str(), &doc.source_path))
+            .collect();
+        let known_sources: HashSet<&PathBuf> = sources.values().copied().collect();
 
-        // For now, simple implementation - process files in alphabetical order
-        // TODO: Parse files to find actual dependencies (includes, references, etc.)
-        for file in files {
-            graph.insert(file.clone(), Vec::new());
-        }
-
-        Ok(graph)
+        documents
+            .iter()
+            .map(|doc| {
+                let mut dependencies: Vec<PathBuf> = doc
+                    .includes
+                    .iter()
+                    .filter(|path| known_sources.contains(path))
+                    .cloned()
+                    .collect();
+                for entry in &doc.toctree_entries {
+                    let (_, target) = navigation::split_toctree_entry(entry);
+                    if navigation::is_external(target) {
+                        continue;
+                    }
+                    let child = navigation::resolve_toctree_target(&doc.doc_path, target, |name| {
+                        sources.contains_key(name)
+                    });
+                    if let Some(path) = sources.get(child.as_str()) {
+                        dependencies.push((*path).clone());
+                    }
+                }
+                dependencies.retain(|path| path != &doc.source_path);
+                (doc.source_path.clone(), dependencies)
+            })
+            .collect()
     }
 
-    async fn process_files_parallel(
-        &self,
-        files: &[PathBuf],
-        _dependency_graph: &HashMap<PathBuf, Vec<PathBuf>>,
-    ) -> Result<Vec<Document>> {
-        info!(
-            "Processing {} files with {} parallel jobs",
-            files.len(),
-            self.parallel_jobs
-        );
+    /// Read and parse every source file in parallel. Unchanged documents
+    /// are taken from the cache on incremental builds.
+    /// Files whose parser panicked are reported as errors and left out.
+    fn parse_source_files(&self, files: &[PathBuf]) -> Result<Vec<ParsedSource>> {
+        let pool = self.thread_pool()?;
 
-        // Configure rayon thread pool
-        let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(self.parallel_jobs)
-            .build()?;
+        // Pre-canonicalize output directory for comparison
+        let canonical_output = paths::canonicalize(&self.output_dir).ok();
 
-        let documents: Result<Vec<_>, _> = pool.install(|| {
+        let sources: Vec<Option<ParsedSource>> = pool.install(|| {
             files
                 .par_iter()
-                .map(|file_path| self.process_single_file(file_path))
-                .collect()
-        });
+                .map(|file_path| {
+                    self.check_cancelled()?;
+                    let source = self.isolate_crash(file_path, "parsing", || {
+                        self.parse_source_file(file_path, canonical_output.as_deref())
+                    });
+                    self.progress.document_done("parse");
+                    source
+                })
+                .collect::<Result<_>>()
+        })?;
+        Ok(sources.into_iter().flatten().collect())
+    }
 
-        documents
+    /// Run `task` for the document at `path`, turning a panic into an error
+    /// report for the document so the rest of the build carries on
+    fn isolate_crash<T>(
+        &self,
+        path: &Path,
+        stage: &str,
+        task: impl FnOnce() -> Result<T>,
+    ) -> Result<Option<T>> {
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(task)) {
+            Ok(Ok(result)) => Ok(Some(result)),
+            Ok(Err(error)) => {
+                for observer in &self.observers {
+                    observer.document_failed(path, &format!("{:#}", error));
+                }
+                Err(error)
+            }
+            Err(payload) => {
+                let reason = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("unknown cause");
+                let message = format!("Crashed while {} the document: {}", stage, reason);
+                for observer in &self.observers {
+                    observer.document_failed(path, &message);
+                }
+                self.add_error(BuildErrorReport::new(
+                    path.to_path_buf(),
+                    None,
+                    message,
+                    ErrorType::Crash,
+                ));
+                Ok(None)
+            }
+        }
     }
 
-    fn process_single_file(&self, file_path: &Path) -> Result<Document> {
+    fn parse_source_file(&self, file_path: &Path, canonical_output: Option<&Path>) -> Result<ParsedSource> {
         // Safety check: refuse to process files inside the output directory
-        if let (Ok(canonical_file), Ok(canonical_output)) =
-            (file_path.canonicalize(), self.output_dir.canonicalize())
-        {
-            if canonical_file.starts_with(&canonical_output) {
+        if let (Ok(canonical_file), Some(canonical_output)) = (paths::canonicalize(file_path), canonical_output) {
+            if canonical_file.starts_with(canonical_output) {
                 return Err(anyhow::anyhow!(
                     "Refusing to process file inside output directory: {}. \
                      Please delete the output directory and rebuild.",
@@ -590,7 +1704,7 @@ impl SphinxBuilder {
             }
         }
 
-        let relative_path = file_path.strip_prefix(&self.source_dir).map_err(|_| {
+        let doc_path = paths::doc_name(file_path, &self.source_dir).ok_or_else(|| {
             anyhow::anyhow!(
                 "Path '{}' is not inside source directory '{}'. \
                  This can happen with symlinks or mixed absolute/relative paths.",
@@ -598,76 +1712,428 @@ impl SphinxBuilder {
                 self.source_dir.display()
             )
         })?;
-        debug!("Processing file: {}", relative_path.display());
+        let doc_path = Symbol::from(doc_path);
+        let parse_start = Instant::now();
 
         // Check cache if incremental build is enabled
-        if self.incremental {
+        let memory_source = self.in_memory_source(file_path);
+        if self.incremental && memory_source.is_none() {
             if let Ok(cached_doc) = self.cache.get_document(file_path) {
                 let file_mtime = utils::get_file_mtime(file_path)?;
-                if cached_doc.source_mtime >= file_mtime {
-                    debug!("Using cached version of {}", relative_path.display());
-                    return Ok(cached_doc);
+                // Included files changed since the document was parsed make
+                // it stale too
+                let includes_unchanged = cached_doc.includes.iter().all(|include| {
+                    utils::get_file_mtime(include).is_ok_and(|mtime| mtime <= cached_doc.build_time)
+                });
+                if cached_doc.source_mtime >= file_mtime && includes_unchanged {
+                    debug!("Using cached version of {}", doc_path);
+                    return Ok(ParsedSource {
+                        path: file_path.to_path_buf(),
+                        doc_path,
+                        document: Arc::new(cached_doc),
+                        content: None,
+                        parse_time: parse_start.elapsed(),
+                    });
                 }
             }
         }
 
-        // Read and parse the file
-        let content = std::fs::read_to_string(file_path)
-            .with_context(|| format!("Failed to read source file: {}", file_path.display()))?;
-        let document = self.parser.parse(file_path, &content)
+        debug!("Parsing file: {}", doc_path);
+        let content = match memory_source {
+            Some(content) => content,
+            None => std::fs::read_to_string(file_path)
+                .with_context(|| format!("Failed to read source file: {}", file_path.display()))?,
+        };
+        let mut document = self.parser.parse(file_path, &content)
             .with_context(|| format!("Failed to parse file: {}", file_path.display()))?;
+        if let DocumentContent::RestructuredText(rst) = &document.content {
+            self.report_system_messages(file_path, &rst.messages);
+        }
+        let warnings = self
+            .transforms
+            .apply(&mut document, &doc_path, &self.config, &self.parser)?;
+        for warning in warnings {
+            self.add_warning(BuildWarning::new(
+                file_path.to_path_buf(),
+                Some(warning.line),
+                warning.message,
+                warning.warning_type,
+            ));
+        }
+        self.trace_span(&doc_path, "parse", parse_start);
+
+        Ok(ParsedSource {
+            path: file_path.to_path_buf(),
+            doc_path,
+            document: Arc::new(document),
+            content: Some(content),
+            parse_time: parse_start.elapsed(),
+        })
+    }
 
-        // Get the document path for navigation lookup
-        let doc_path = relative_path
-            .with_extension("")
-            .to_string_lossy()
-            .replace('\\', "/");
+    /// Report the malformed markup the parser recovered from in the source
+    /// at `file_path`, as warnings and errors like those of docutils
+    fn report_system_messages(&self, file_path: &Path, messages: &[SystemMessage]) {
+        for message in messages {
+            let file = message.source.clone().unwrap_or_else(|| file_path.to_path_buf());
+            match message.level {
+                MessageLevel::Warning => self.add_warning(BuildWarning::new(
+                    file,
+                    Some(message.line),
+                    message.message.clone(),
+                    WarningType::MalformedMarkup,
+                )),
+                MessageLevel::Error => self.add_error(BuildErrorReport::new(
+                    file,
+                    Some(message.line),
+                    message.message.clone(),
+                    ErrorType::ParseError,
+                )),
+            }
+        }
+    }
 
-        // Render document content to HTML with document titles and sections for toctree
+    async fn process_files_parallel(
+        &self,
+        sources: &[ParsedSource],
+        dependency_graph: &HashMap<PathBuf, Vec<PathBuf>>,
+    ) -> Result<Vec<Arc<Document>>> {
+        info!(
+            "Processing {} files with {} parallel jobs",
+            sources.len(),
+            self.parallel_jobs
+        );
+
+        // Dependencies outside of the batch are already rendered
+        let positions: HashMap<&Path, usize> = sources
+            .iter()
+            .enumerate()
+            .map(|(index, source)| (source.path.as_path(), index))
+            .collect();
+        let dependencies: Vec<Vec<usize>> = sources
+            .iter()
+            .map(|source| {
+                dependency_graph
+                    .get(&source.path)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|path| positions.get(path.as_path()).copied())
+                    .collect()
+            })
+            .collect();
+
+        let pool = self.thread_pool()?;
+        let documents = scheduler::run_in_dependency_order(&pool, sources, &dependencies, |source| {
+            self.check_cancelled()?;
+            let document =
+                self.isolate_crash(&source.path, "rendering", || self.process_single_file(source));
+            self.progress.document_done("render");
+            document
+        })?;
+        Ok(documents.into_iter().flatten().collect())
+    }
+
+    /// Render the content of a parsed document to HTML, spooled to a
+    /// temporary file, recording the renderer's and the spell checker's
+    /// warnings. Returns the body and the media files it references.
+    fn render_body(&self, source: &ParsedSource, content: &str) -> Result<(PageBody, Vec<MediaFile>, PageAssets)> {
+        let file_path = source.path.as_path();
+        let doc_path = source.doc_path.as_str();
         let mut renderer = HtmlRenderer::new();
         renderer.set_source_dir(self.source_dir.clone());
-        {
-            let titles = self.document_titles.lock().unwrap();
-            for (path, title) in titles.iter() {
-                renderer.register_document_title(path, title);
-            }
+        renderer.set_parser(Arc::clone(&self.parser));
+        renderer.set_current_doc(doc_path);
+        renderer.set_strict_csp(self.config.security.strict_csp);
+        renderer.set_show_authors(self.config.show_authors);
+        renderer.set_highlight_language(self.config.highlight_language.clone());
+        renderer.set_issue_tracker(&self.config.issue_tracker);
+        renderer.set_custom_roles(&self.config.custom_roles);
+        renderer.set_custom_directives(&self.config.custom_directives);
+        renderer.set_permalink_icon(self.config.html_permalinks.unwrap_or(true).then(|| {
+            self.config.html_permalinks_icon.clone().unwrap_or_else(|| "¶".to_string())
+        }));
+        renderer.set_smartquotes(self.config.uses_smartquotes("html").then(|| {
+            SmartQuotes::new(
+                self.config.smartquotes_action.as_deref().unwrap_or("qDe"),
+                self.config.language.as_deref().unwrap_or("en"),
+            )
+        }));
+        renderer.set_footnotes(self.config.footnotes.clone());
+        renderer.set_bibliography(Arc::clone(&self.bibliography.lock()));
+        renderer.set_environment(self.environment());
+        let spool = tempfile::tempfile().context("Failed to create a temporary file for the page body")?;
+        let mut scanner = BodyScanner::new(
+            std::io::BufWriter::new(spool),
+            self.spell_checker.as_ref().map(|checker| checker.tally(content)),
+            self.config.output.manifest.then(ContentRefs::default),
+        );
+        renderer
+            .render_to(&source.document.content, &mut scanner)
+            .and_then(|()| scanner.flush())
+            .with_context(|| format!("Failed to render {}", file_path.display()))?;
+        scanner.finish();
+        let BodyScanner { out, summary, spelling, refs, .. } = scanner;
+        let spool = out.into_inner().map_err(|e| e.into_error())?;
+        for warning in renderer.take_warnings() {
+            self.add_warning(BuildWarning::new(
+                file_path.to_path_buf(),
+                warning.line,
+                warning.message,
+                warning.warning_type,
+            ));
         }
-        {
-            let sections = self.document_sections.lock().unwrap();
-            for (path, section_list) in sections.iter() {
-                renderer.register_document_sections(path, section_list.clone());
+        let media_files = renderer.take_media_files();
+        let mut page_assets = renderer.take_page_assets();
+        if summary.has_code {
+            page_assets.uses(PageFeature::Code);
+        }
+        if let Some(tally) = spelling {
+            for misspelling in tally.finish() {
+                let message = if misspelling.occurrences > 1 {
+                    format!(
+                        "Misspelled word '{}' ({} occurrences)",
+                        misspelling.word, misspelling.occurrences
+                    )
+                } else {
+                    format!("Misspelled word '{}'", misspelling.word)
+                };
+                self.add_warning(BuildWarning::new(
+                    file_path.to_path_buf(),
+                    misspelling.line,
+                    message,
+                    WarningType::Spelling,
+                ));
             }
         }
-        let body_html = renderer.render_document_content(&document.content);
+        let body = PageBody {
+            html: Arc::new(SpooledHtml::new(spool)),
+            summary,
+            refs,
+        };
+        Ok((body, media_files, page_assets))
+    }
+
+    /// Render and write a parsed document
+    fn process_single_file(&self, source: &ParsedSource) -> Result<Arc<Document>> {
+        // Cached documents are already rendered
+        let Some(content) = &source.content else {
+            return Ok(Arc::clone(&source.document));
+        };
+        let file_path = source.path.as_path();
+        let document = source.document.as_ref();
+        let doc_path = source.doc_path.as_str();
+        debug!("Processing file: {}", file_path.display());
+
+        // Render document content to HTML with document titles and sections for toctree
+        let render_start = Instant::now();
+        let (mut body, media_files, page_assets) = self.render_body(source, content)?;
 
         // Get navigation context for this page
         let page_nav = {
-            let nav = self.navigation.lock().unwrap();
-            nav.get_page_navigation(&doc_path)
+            let nav = self.navigation.lock();
+            nav.get_page_navigation(doc_path)
         };
 
-        // Build the full HTML document using the template engine
-        let rendered_html = self.render_full_html(&document, &body_html, &doc_path, &page_nav);
-
-        // Write output file
         let output_path = self.get_output_path(file_path)?;
         if let Some(parent) = output_path.parent() {
             std::fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
         }
-        std::fs::write(&output_path, &rendered_html)
-            .with_context(|| format!("Failed to write output file: {}", output_path.display()))?;
+
+        // A project's own 404 document is served from arbitrary paths too
+        let is_not_found_page = self.config.not_found.enabled && doc_path == NOT_FOUND_PAGE;
+        let (render_time, write_start) = if self.post_processors.is_empty()
+            && !self.config.output.minify_html
+            && !is_not_found_page
+        {
+            // Nothing rewrites the page, so the template streams it straight
+            // to the output file
+            self.stream_full_html(document, &body, doc_path, &page_nav, &page_assets, &output_path)?;
+            (render_start.elapsed(), Instant::now())
+        } else {
+            // Build the full HTML document using the template engine
+            let rendered_html = self.render_full_html(document, &body, doc_path, &page_nav, &page_assets)?;
+            let rendered_html = if is_not_found_page {
+                absolutize_urls(&rendered_html, &self.not_found_urls_prefix())
+            } else {
+                rendered_html
+            };
+
+            let page = RenderedPage {
+                doc_path,
+                source_path: Some(file_path),
+                output_path: &output_path,
+            };
+            let rendered_html = self.finish_page(&page, rendered_html)?;
+            let render_time = render_start.elapsed();
+
+            let write_start = Instant::now();
+            std::fs::write(&output_path, &rendered_html)
+                .with_context(|| format!("Failed to write output file: {}", output_path.display()))?;
+            (render_time, write_start)
+        };
+
+        if self.config.html_copy_source.unwrap_or(true) {
+            let source_copy = self
+                .output_dir
+                .join("_sources")
+                .join(self.source_link_name(file_path));
+            if let Some(parent) = source_copy.parent() {
+                std::fs::create_dir_all(parent).with_context(|| {
+                    format!("Failed to create output directory: {}", parent.display())
+                })?;
+            }
+            std::fs::write(&source_copy, content).with_context(|| {
+                format!("Failed to copy source file: {}", source_copy.display())
+            })?;
+        }
+        for media in &media_files {
+            self.copy_media_file(media)?;
+        }
+        if let Some(trace) = &self.trace {
+            trace.record(doc_path, "render", render_start, write_start);
+            trace.record(doc_path, "write", write_start, Instant::now());
+        }
+        if self.config.output.manifest {
+            self.manifest_pages.lock().push(ManifestPage::new(
+                self.output_relative(&output_path),
+                doc_path.to_string(),
+                document.title.clone(),
+                paths::to_slash(&self.source_relative(file_path)),
+                body.refs.take().unwrap_or_default(),
+            )
+            .with_authors(&document.metadata.authors, &document.metadata.code_authors));
+        }
+        self.documents_written.fetch_add(1, Ordering::Relaxed);
+        for observer in &self.observers {
+            observer.document_written(doc_path, file_path, &output_path);
+        }
+        self.document_timings.lock().push(DocumentTiming {
+            doc_path: doc_path.to_string(),
+            parse: source.parse_time,
+            render: render_time,
+            write: write_start.elapsed(),
+        });
 
         // Cache the document
-        if self.incremental {
-            self.cache.store_document(file_path, &document)?;
+        if self.incremental && self.in_memory_source(file_path).is_none() {
+            self.cache.store_document(file_path, document)?;
+        }
+
+        Ok(Arc::clone(&source.document))
+    }
+
+    /// Write `manifest.json`. Pages of an incremental build that were not
+    /// rendered again keep their entries from the previous manifest.
+    fn write_manifest(&self, summaries: &[DocumentSummary]) -> Result<()> {
+        let mut pages = std::mem::take(&mut *self.manifest_pages.lock());
+        let rendered: HashSet<String> = pages.iter().map(|page| page.doc.clone()).collect();
+        let built: HashSet<&str> = summaries.iter().map(|summary| summary.doc_path.as_str()).collect();
+        if let Some(previous) = Manifest::read(&self.output_dir) {
+            pages.extend(previous.pages.into_iter().filter(|page| {
+                built.contains(page.doc.as_str()) && !rendered.contains(&page.doc)
+            }));
+        }
+        let manifest = Manifest::new(pages);
+        manifest.write(&self.output_dir)?;
+        info!("Wrote manifest of {} pages", manifest.pages.len());
+        Ok(())
+    }
+
+    /// Pages and source copies the build writes, relative to the output
+    /// directory: those of the documents, the generated pages and the
+    /// redirect stubs
+    fn page_outputs(&self, documents: &[DocumentSummary]) -> BTreeSet<String> {
+        let mut outputs = BTreeSet::new();
+        for doc in documents {
+            if let Ok(output_path) = self.get_output_path(&doc.source_path) {
+                outputs.insert(self.output_relative(&output_path));
+            }
+            if self.config.html_copy_source.unwrap_or(true) {
+                outputs.insert(format!("_sources/{}", self.source_link_name(&doc.source_path)));
+            }
+        }
+        let redirects = self
+            .config
+            .redirects
+            .pages
+            .iter()
+            .filter_map(|(from, to)| Redirect::new(from, to).ok())
+            .map(|redirect| redirect.from);
+        for page in self.generated_pages().into_iter().chain(redirects) {
+            outputs.insert(format!("{}.html", page));
+        }
+        outputs
+    }
+
+    /// Delete the pages and source copies that the previous build wrote and
+    /// this one doesn't, like those of removed documents, along with their
+    /// compressed siblings, unless `keep_stale` is set. Other files in the
+    /// output directory are left alone, and so are `extra_files`, the ones
+    /// this build copied from `html_extra_path` over a former page. Returns
+    /// the number of files deleted.
+    fn remove_stale_outputs(&self, documents: &[DocumentSummary], extra_files: &BTreeSet<String>) -> Result<usize> {
+        let record = self.output_dir.join(CACHE_DIR).join(OUTPUTS_FILE);
+        let previous: BTreeSet<String> = std::fs::read_to_string(&record)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        let mut outputs = self.page_outputs(documents);
+
+        let mut removed = 0;
+        let stale: Vec<String> = previous
+            .difference(&outputs)
+            .filter(|output| !extra_files.contains(*output))
+            .cloned()
+            .collect();
+        if self.config.output.keep_stale {
+            // Still tracked, for a later build to delete
+            outputs.extend(stale);
+        } else {
+            for output in &stale {
+                let path = self.output_dir.join(output);
+                let compressed = [CompressionFormat::Gzip, CompressionFormat::Brotli]
+                    .map(|format| PathBuf::from(format!("{}.{}", path.display(), format.extension())));
+                for file in std::iter::once(path.clone()).chain(compressed) {
+                    if std::fs::remove_file(&file).is_ok() {
+                        removed += 1;
+                    }
+                }
+                // Directories left empty go too
+                for dir in path.ancestors().skip(1).take_while(|dir| *dir != self.output_dir) {
+                    if std::fs::remove_dir(dir).is_err() {
+                        break;
+                    }
+                }
+            }
+            if removed > 0 {
+                info!("Removed {} stale output files", removed);
+            }
+        }
+
+        if let Some(parent) = record.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create cache directory: {}", parent.display()))?;
         }
+        std::fs::write(&record, serde_json::to_string(&outputs)?)
+            .with_context(|| format!("Failed to write {}", record.display()))?;
+        Ok(removed)
+    }
+
+    /// Path of an output file relative to the output directory, with `/`
+    /// separators
+    fn output_relative(&self, path: &Path) -> String {
+        paths::to_slash(&paths::relative_to(path, &self.output_dir).unwrap_or_else(|| path.to_path_buf()))
+    }
 
-        Ok(document)
+    /// Path of a file relative to the source directory, or the path itself
+    /// when it is outside
+    fn source_relative(&self, path: &Path) -> PathBuf {
+        paths::relative_to(path, &self.source_dir).unwrap_or_else(|| path.to_path_buf())
     }
 
     fn get_output_path(&self, source_path: &Path) -> Result<PathBuf> {
-        let relative_path = source_path.strip_prefix(&self.source_dir).map_err(|_| {
+        let relative_path = paths::relative_to(source_path, &self.source_dir).ok_or_else(|| {
             anyhow::anyhow!(
                 "Path '{}' is not inside source directory '{}'. \
                  This can happen with symlinks or mixed absolute/relative paths.",
@@ -687,40 +2153,74 @@ impl SphinxBuilder {
     fn render_full_html(
         &self,
         document: &Document,
-        body_html: &str,
+        body: &PageBody,
         doc_path: &str,
         page_nav: &PageNavigation,
-    ) -> String {
-        // Build CSS file list
-        let mut css_files: Vec<String> = Vec::new();
-        if let Some(ref theme) = self.active_theme {
-            for stylesheet in &theme.stylesheets {
-                if !stylesheet.path.is_empty() {
-                    css_files.push(format!("_static/{}", stylesheet.path));
-                }
-            }
-        }
-        for css_file in &self.config.html_css_files {
-            if !css_file.is_empty() {
-                css_files.push(format!("_static/{}", css_file));
-            }
-        }
+        page_assets: &PageAssets,
+    ) -> Result<String> {
+        let (ctx, values) = self.page_context(document, body, doc_path, page_nav, page_assets);
 
-        // Build JS file list
-        let mut script_files: Vec<String> = Vec::new();
-        if let Some(ref theme) = self.active_theme {
-            for script in &theme.scripts {
-                if !script.path.is_empty() {
-                    script_files.push(format!("_static/{}", script.path));
-                }
+        // Try to render using the template engine
+        let engine = self.template_engine();
+        let ctx = ctx.build();
+        match engine.render_with_values("layout.html", &ctx, values) {
+            Ok(html) => Ok(html),
+            Err(e) => {
+                // Fallback to simple HTML if template fails
+                self.report_template_error(&engine, document, &e);
+                let body_html = body.html.read_to_string().context("Failed to read back the page body")?;
+                Ok(self.render_fallback_html(document, &body_html, doc_path, page_nav, &ctx))
             }
         }
-        for js_file in &self.config.html_js_files {
-            if !js_file.is_empty() {
-                script_files.push(format!("_static/{}", js_file));
-            }
+    }
+
+    /// Render a full HTML document through a buffered writer into
+    /// `output_path`, copying the spooled body into it, so the page is never
+    /// held in memory as a whole
+    fn stream_full_html(
+        &self,
+        document: &Document,
+        body: &PageBody,
+        doc_path: &str,
+        page_nav: &PageNavigation,
+        page_assets: &PageAssets,
+        output_path: &Path,
+    ) -> Result<()> {
+        let (ctx, values) = self.page_context(document, body, doc_path, page_nav, page_assets);
+
+        let file = std::fs::File::create(output_path)
+            .with_context(|| format!("Failed to write output file: {}", output_path.display()))?;
+        let mut writer = std::io::BufWriter::new(file);
+        let engine = self.template_engine();
+        let ctx = ctx.build();
+        let streamed = engine
+            .render_to("layout.html", &ctx, values, &mut writer)
+            .and_then(|()| Ok(writer.flush()?));
+        drop(writer);
+
+        if let Err(e) = streamed {
+            // Fallback to simple HTML if template fails, replacing the part
+            // of the page already written
+            self.report_template_error(&engine, document, &e);
+            let body_html = body.html.read_to_string().context("Failed to read back the page body")?;
+            let html = self.render_fallback_html(document, &body_html, doc_path, page_nav, &ctx);
+            std::fs::write(output_path, html)
+                .with_context(|| format!("Failed to write output file: {}", output_path.display()))?;
         }
+        Ok(())
+    }
 
+    /// Template context of a document page: the base context plus its
+    /// navigation, page TOC, source details and the assets its directives
+    /// need
+    fn page_context(
+        &self,
+        document: &Document,
+        body: &PageBody,
+        doc_path: &str,
+        page_nav: &PageNavigation,
+        page_assets: &PageAssets,
+    ) -> (TemplateContext, HashMap<String, minijinja::Value>) {
         // Get page title
         let title = if document.title.is_empty() || document.title == "Untitled" {
             String::new()
@@ -728,61 +2228,140 @@ impl SphinxBuilder {
             document.title.clone()
         };
 
+        let (mut ctx, mut values) = self.base_context(
+            doc_path,
+            &title,
+            minijinja::Value::from_dyn_object(Arc::clone(&body.html)),
+            &body.summary,
+            &document.metadata.meta,
+        );
+
+        // Render page TOC from document's own TOC entries
+        let page_toc_html = self.render_page_toc(document, doc_path);
+        let display_toc = document.toc.len() > 1 && page_tocdepth(document) != Some(1);
+
+        // Navigation (with SafeHtml titles to avoid escaping rendered HTML)
+        let parents_safe: Vec<NavLinkSafe> = page_nav.parents.iter().map(NavLinkSafe::from_nav_link).collect();
+        let prev_safe = page_nav.prev.as_ref().map(NavLinkSafe::from_nav_link);
+        let next_safe = page_nav.next.as_ref().map(NavLinkSafe::from_nav_link);
+        ctx.insert("parents", &parents_safe).ok();
+        ctx.insert("prev", &prev_safe).ok();
+        ctx.insert("next", &next_safe).ok();
+        let rellinks = self.rellinks(page_nav.next.as_ref(), page_nav.prev.as_ref());
+        let relbar = template::render_relbar(
+            doc_path,
+            &rellinks,
+            (&self.config.root_doc.clone().unwrap_or_else(|| "index".to_string()), &self.docstitle()),
+            &page_nav.parents,
+            &html_escape::encode_text(&title),
+        );
+        values.insert("relbar".to_string(), template::html_function(relbar));
+        ctx.insert("rellinks", &rellinks).ok();
+
+        // Page TOC
+        ctx.insert("toc", &page_toc_html).ok();
+        ctx.insert("display_toc", display_toc).ok();
+
+        // Last updated: the file's last commit, or its modification time,
+        // no later than SOURCE_DATE_EPOCH
+        if let Some(fmt) = &self.config.html_last_updated_fmt {
+            let updated = self
+                .commit_times
+                .lock()
+                .get(&document.source_path)
+                .copied()
+                .unwrap_or(document.source_mtime);
+            let updated = utils::reproducible_time(updated);
+            ctx.insert("last_updated", utils::format_timestamp(&updated, fmt)).ok();
+        }
+
+        if self.config.reading_time.enabled {
+            let words = body.summary.words;
+            ctx.insert("word_count", words).ok();
+            ctx.insert("reading_time", reading_minutes(words, self.config.reading_time.words_per_minute)).ok();
+        }
+
+        // Source info
+        ctx.insert("show_source", self.config.html_show_sourcelink.unwrap_or(true)).ok();
+        ctx.insert("has_source", self.config.html_copy_source.unwrap_or(true)).ok();
+        ctx.insert("sourcename", self.source_link_name(&document.source_path)).ok();
+
+        // Names from sectionauthor, moduleauthor and codeauthor directives
+        ctx.insert("authors", &document.metadata.authors).ok();
+        ctx.insert("code_authors", &document.metadata.code_authors).ok();
+
+        let mut page_assets = page_assets.clone();
+        page_assets.extend(self.feature_assets(&page_assets.features));
+        if !page_assets.is_empty() {
+            self.insert_page_assets(&mut ctx, &mut values, doc_path, &page_assets);
+        }
+
+        (ctx, values)
+    }
+
+    /// Template context shared by every page, documents and additional pages
+    /// alike: project details, assets, sidebar navigation and theme options,
+    /// plus the `toctree(...)` callable. `summary` describes the `body` and
+    /// `meta` holds the fields of the page's `meta` directives.
+    fn base_context(
+        &self,
+        doc_path: &str,
+        title: &str,
+        body: minijinja::Value,
+        summary: &BodySummary,
+        meta: &[(String, String)],
+    ) -> (TemplateContext, HashMap<String, minijinja::Value>) {
         // Get master_doc (root_doc in config)
         let master_doc = self.config.root_doc.clone().unwrap_or_else(|| "index".to_string());
 
         // Render toctree for sidebar
-        let toctree_html = {
-            let nav = self.navigation.lock().unwrap();
-            let mut options = ToctreeOptions::default();
-            options.current_doc = Some(doc_path.to_string());
-            nav.render_toctree(&options)
-        };
-
-        // Render page TOC from document's own TOC entries
-        let page_toc_html = self.render_page_toc(document);
-        let display_toc = document.toc.len() > 1;
+        let sidebar_options = self.sidebar_toctree_options(doc_path);
+        let toctree_html = self.navigation.lock().render_toctree(&sidebar_options);
 
         // Build template context
         let mut ctx = TemplateContext::new();
 
-        // Core content
-        ctx.insert("body", body_html).ok();
-        ctx.insert("title", &title).ok();
+        // Core content; the body goes in as a template value below, sparing
+        // large pages a round trip through JSON
+        ctx.insert("title", title).ok();
 
-        // Build docstitle in Sphinx format: "{project} {version} documentation"
-        let docstitle = if let Some(ref version) = self.config.version {
-            format!("{} {} documentation", self.config.project, version)
-        } else {
-            format!("{} documentation", self.config.project)
-        };
+        let docstitle = self.docstitle();
         ctx.insert("docstitle", &docstitle).ok();
         ctx.insert("project", &self.config.project).ok();
         ctx.insert("version", &self.config.version).ok();
+        ctx.insert("release", &self.config.release).ok();
+
+        // Canonical URL and link-preview meta tags
+        let page_url = self
+            .config
+            .html_baseurl
+            .as_deref()
+            .filter(|base| !base.is_empty())
+            .map(|base| format!("{}/{}.html", base.trim_end_matches('/'), doc_path));
+        // Marked safe so the slashes of the URL are not entity-escaped
+        let page_url = page_url
+            .map(|url| SafeHtml::new(html_escape::encode_double_quoted_attribute(&url)));
+        ctx.insert("pageurl", &page_url).ok();
+        let metatags = self.page_meta_tags(title, summary, doc_path, meta);
+        ctx.insert("metatags", SafeHtml::new(metatags)).ok();
 
         // Language
         ctx.insert("language", self.config.language.as_deref().unwrap_or("en")).ok();
 
         // CSS and JS files
-        ctx.insert("css_files", &css_files).ok();
-        ctx.insert("script_files", &script_files).ok();
+        let environment = self.environment();
+        ctx.insert("css_files", &environment.css_files).ok();
+        ctx.insert("script_files", &environment.js_files).ok();
 
-        // Navigation (with SafeHtml titles to avoid escaping rendered HTML)
-        let parents_safe: Vec<NavLinkSafe> = page_nav.parents.iter().map(NavLinkSafe::from_nav_link).collect();
-        let prev_safe = page_nav.prev.as_ref().map(NavLinkSafe::from_nav_link);
-        let next_safe = page_nav.next.as_ref().map(NavLinkSafe::from_nav_link);
-        ctx.insert("parents", &parents_safe).ok();
-        ctx.insert("prev", &prev_safe).ok();
-        ctx.insert("next", &next_safe).ok();
         ctx.insert("master_doc", &master_doc).ok();
+        ctx.insert("root_doc", &master_doc).ok();
+        ctx.insert("pagename", doc_path).ok();
+        ctx.insert("builder", "html").ok();
+        ctx.insert("file_suffix", ".html").ok();
 
         // Toctree for sidebar
         ctx.insert("toctree_html", &toctree_html).ok();
 
-        // Page TOC
-        ctx.insert("toc", &page_toc_html).ok();
-        ctx.insert("display_toc", display_toc).ok();
-
         // Logo and favicon - use just the filename since we copy to _static
         if let Some(ref logo_path) = self.config.html_logo {
             if let Some(filename) = std::path::Path::new(logo_path).file_name() {
@@ -806,83 +2385,465 @@ impl SphinxBuilder {
         ctx.insert("show_sphinx", true).ok();
         ctx.insert("sphinx_version", env!("CARGO_PKG_VERSION")).ok();
 
-        // Source info
-        ctx.insert("show_source", self.config.html_show_sourcelink.unwrap_or(true)).ok();
-        ctx.insert("has_source", true).ok();
-        let sourcename = format!("{}.rst.txt", doc_path);
-        ctx.insert("sourcename", &sourcename).ok();
+        // Templates avoid inline scripts and styles under a strict CSP
+        ctx.insert("strict_csp", self.config.security.strict_csp).ok();
+
+        // Version switcher
+        if let Some(switcher) = &self.version_switcher {
+            let versions = switcher.links(doc_path);
+            let current = versions.iter().find(|version| version.current);
+            ctx.insert("current_version", current).ok();
+            ctx.insert("versions", &versions).ok();
+        }
 
         // Theme options (with theme_ prefix for template access)
         // Use default values from the theme's options schema
-        if let Some(ref theme) = self.active_theme {
-            for (key, spec) in &theme.options_schema {
+        if let Some(ref theme) = *self.active_theme() {
+            for key in theme.options_schema.keys() {
                 let theme_key = format!("theme_{}", key);
-                ctx.insert(&theme_key, &spec.default).ok();
+                ctx.insert(&theme_key, self.theme_option(key)).ok();
             }
         }
 
-        // Try to render using the template engine
-        match self.template_engine.render("layout.html", &ctx.build()) {
-            Ok(html) => html,
-            Err(e) => {
-                // Fallback to simple HTML if template fails
-                warn!("Template rendering failed: {}, using fallback", e);
-                self.render_fallback_html(document, body_html, &css_files, &script_files)
-            }
+        let mut values = HashMap::new();
+        values.insert("body".to_string(), body);
+
+        // `toctree(...)` callable with Sphinx's keyword arguments
+        let asset_attributes = Arc::clone(&self.asset_attributes.lock());
+        values.insert(
+            "css_tag".to_string(),
+            template::asset_tag_function(Arc::clone(&asset_attributes), template::css_tag, doc_path),
+        );
+        values.insert(
+            "js_tag".to_string(),
+            template::asset_tag_function(asset_attributes, template::js_tag, doc_path),
+        );
+        values.insert(
+            "toctree".to_string(),
+            template::toctree_function(Arc::clone(&self.navigation), sidebar_options),
+        );
+
+        // Sphinx's page helpers: `pathto(...)` relative to this page,
+        // `hasdoc(name)` and the `relbar()` navigation bar
+        values.insert("pathto".to_string(), template::pathto_function(doc_path));
+        values.insert(
+            "hasdoc".to_string(),
+            template::hasdoc_function(self.environment(), self.generated_pages()),
+        );
+        let rellinks = self.rellinks(None, None);
+        values.insert(
+            "relbar".to_string(),
+            template::html_function(template::render_relbar(
+                doc_path,
+                &rellinks,
+                (&master_doc, &docstitle),
+                &[],
+                &html_escape::encode_text(title),
+            )),
+        );
+        ctx.insert("rellinks", &rellinks).ok();
+
+        (ctx, values)
+    }
+
+    /// Title of the documentation in Sphinx format: "{project} {version}
+    /// documentation"
+    fn docstitle(&self) -> String {
+        if let Some(ref version) = self.config.version {
+            format!("{} {} documentation", self.config.project, version)
+        } else {
+            format!("{} documentation", self.config.project)
         }
     }
 
-    /// Render the page's own table of contents
-    fn render_page_toc(&self, document: &Document) -> String {
-        if document.toc.is_empty() {
-            return String::new();
+    /// Pages written besides the documents, which `hasdoc()` knows about:
+    /// the general index, `html_additional_pages` and the 404 page
+    fn generated_pages(&self) -> Vec<String> {
+        let mut pages: Vec<String> = self.config.html_additional_pages.keys().cloned().collect();
+        if self.config.html_use_index.unwrap_or(true) {
+            pages.push(GENINDEX_PAGE.to_string());
+        }
+        if self.config.not_found.enabled {
+            pages.push(NOT_FOUND_PAGE.to_string());
         }
+        pages
+    }
 
-        let renderer = crate::renderer::HtmlRenderer::new();
-        let mut html = String::from("<ul>\n");
-        for entry in &document.toc {
-            // Render inline markup in the title (like `code` and :ref:)
-            let rendered_title = renderer.render_rst_inline(&entry.title);
-            html.push_str(&format!(
-                "<li><a class=\"reference internal\" href=\"#{}\">{}</a></li>\n",
-                html_escape::encode_text(&entry.anchor),
-                rendered_title
+    /// Links of the relation bar, like Sphinx's `rellinks`: the general
+    /// index, then the next and previous pages
+    fn rellinks(&self, next: Option<&navigation::NavLink>, prev: Option<&navigation::NavLink>) -> Vec<template::RelLink> {
+        let mut rellinks = Vec::new();
+        if self.config.html_use_index.unwrap_or(true) {
+            rellinks.push(template::RelLink(
+                GENINDEX_PAGE.to_string(),
+                "General Index".to_string(),
+                "I".to_string(),
+                "index".to_string(),
             ));
         }
-        html.push_str("</ul>\n");
-        html
+        for (link, key, text) in [(next, "N", "next"), (prev, "P", "previous")] {
+            if let Some(link) = link {
+                rellinks.push(template::RelLink(
+                    link.docname.clone(),
+                    link.title.clone(),
+                    key.to_string(),
+                    text.to_string(),
+                ));
+            }
+        }
+        rellinks
     }
 
-    /// Fallback HTML rendering when template engine fails
-    fn render_fallback_html(
-        &self,
-        document: &Document,
-        body_html: &str,
-        css_files: &[String],
-        script_files: &[String],
-    ) -> String {
-        let page_title = if document.title.is_empty() || document.title == "Untitled" {
-            self.config.project.clone()
-        } else {
-            format!("{} — {}", document.title, self.config.project)
-        };
-
-        let css_section: String = css_files
-            .iter()
-            .map(|f| format!(r#"<link rel="stylesheet" href="{}" />"#, f))
-            .collect::<Vec<_>>()
-            .join("\n    ");
-
-        let js_section: String = script_files
-            .iter()
-            .map(|f| format!(r#"<script src="{}"></script>"#, f))
-            .collect::<Vec<_>>()
-            .join("\n    ");
+    /// Stylesheets linked from every page by priority: the theme's, the
+    /// highlighting classes in strict CSP mode, those added by
+    /// `add_css_file`, then `html_css_files`
+    fn css_files(&self) -> Vec<Asset> {
+        let mut css_files: Vec<Asset> = Vec::new();
+        if let Some(ref theme) = *self.active_theme() {
+            for stylesheet in &theme.stylesheets {
+                if !stylesheet.path.is_empty() {
+                    css_files.push(Asset::new(&stylesheet.path).with_priority(stylesheet.priority));
+                }
+            }
+        }
+        if self.config.security.strict_csp {
+            css_files.push(Asset::new(HIGHLIGHT_STYLESHEET).with_priority(assets::BUILTIN_PRIORITY));
+        }
+        css_files.extend(self.extra_assets.css_files.iter().cloned());
+        for css_file in &self.config.html_css_files {
+            if !css_file.is_empty() {
+                css_files.push(Asset::new(css_file).with_priority(assets::CONFIG_PRIORITY));
+            }
+        }
+        assets::order(css_files)
+    }
 
-        format!(
-            r#"<!DOCTYPE html>
-<html lang="{}">
-<head>
+    /// Scripts loaded by every page by priority: the theme's, those added by
+    /// `add_js_file`, then `html_js_files`
+    fn script_files(&self) -> Vec<Asset> {
+        let mut script_files: Vec<Asset> = Vec::new();
+        if let Some(ref theme) = *self.active_theme() {
+            for script in &theme.scripts {
+                if !script.path.is_empty() {
+                    let mut asset = Asset::new(&script.path).with_priority(script.priority);
+                    if script.defer {
+                        asset = asset.with_attribute("defer", "defer");
+                    }
+                    if script.async_ {
+                        asset = asset.with_attribute("async", "async");
+                    }
+                    script_files.push(asset);
+                }
+            }
+        }
+        script_files.extend(self.extra_assets.js_files.iter().cloned());
+        for js_file in &self.config.html_js_files {
+            if !js_file.is_empty() {
+                script_files.push(Asset::new(js_file).with_priority(assets::CONFIG_PRIORITY));
+            }
+        }
+        assets::order(script_files)
+    }
+
+    /// Assets of the features a page uses: the copy buttons of code blocks,
+    /// MathJax, mermaid and the design stylesheet
+    fn feature_assets(&self, features: &BTreeSet<PageFeature>) -> PageAssets {
+        let mut assets = PageAssets::default();
+        for feature in features {
+            match feature {
+                PageFeature::Code if self.config.copybutton.enabled => {
+                    assets.add_css_file(Asset::new(COPYBUTTON_STYLESHEET));
+                    assets.add_js_file(Asset::new(COPYBUTTON_SCRIPT));
+                }
+                PageFeature::Code => {}
+                PageFeature::Math => {
+                    assets.add_js_file(Asset::new(&self.config.mathjax_path).with_attribute("defer", "defer"));
+                }
+                PageFeature::Mermaid => {
+                    assets.add_js_file(Asset::new(&self.config.mermaid_path).with_attribute("defer", "defer"));
+                }
+                PageFeature::Design => assets.add_css_file(Asset::new(DESIGN_STYLESHEET)),
+            }
+        }
+        assets
+    }
+
+    /// Link every page's assets and those of `page_assets` from the page,
+    /// all in priority order
+    fn insert_page_assets(
+        &self,
+        ctx: &mut TemplateContext,
+        values: &mut HashMap<String, minijinja::Value>,
+        doc_path: &str,
+        page_assets: &PageAssets,
+    ) {
+        let css_files = assets::order(self.css_files().into_iter().chain(page_assets.css_files.iter().cloned()).collect());
+        let js_files = assets::order(self.script_files().into_iter().chain(page_assets.js_files.iter().cloned()).collect());
+        let mut attributes = AssetAttributes::clone(&self.asset_attributes.lock());
+        for asset in css_files.iter().chain(&js_files) {
+            if !asset.attributes.is_empty() {
+                attributes.entry(asset.url()).or_insert_with(|| asset.attributes.clone());
+            }
+        }
+        let urls = |assets: &[Asset]| assets.iter().map(Asset::url).collect::<Vec<_>>();
+        ctx.insert("css_files", urls(&css_files)).ok();
+        ctx.insert("script_files", urls(&js_files)).ok();
+        let attributes = Arc::new(attributes);
+        values.insert(
+            "css_tag".to_string(),
+            template::asset_tag_function(Arc::clone(&attributes), template::css_tag, doc_path),
+        );
+        values.insert("js_tag".to_string(), template::asset_tag_function(attributes, template::js_tag, doc_path));
+    }
+
+    /// Value of a theme option: `html_theme_options` first, then the theme's default
+    fn theme_option(&self, key: &str) -> serde_json::Value {
+        if let Some(value) = self.config.theme.options.get(key) {
+            return value.clone();
+        }
+        self.active_theme()
+            .as_ref()
+            .and_then(|theme| theme.options_schema.get(key))
+            .map(|spec| spec.default.clone())
+            .unwrap_or(serde_json::Value::Null)
+    }
+
+    /// Sidebar toctree options for a page, taken from the `navigation_depth`,
+    /// `collapse_navigation`, `titles_only` and `includehidden` theme options
+    fn sidebar_toctree_options(&self, doc_path: &str) -> ToctreeOptions {
+        let defaults = ToctreeOptions::default();
+        let as_bool = |key: &str, default: bool| match self.theme_option(key) {
+            serde_json::Value::Bool(value) => value,
+            serde_json::Value::String(value) => value.eq_ignore_ascii_case("true"),
+            _ => default,
+        };
+        // Sphinx uses -1 for unlimited depth
+        let maxdepth = self
+            .theme_option("navigation_depth")
+            .as_i64()
+            .map_or(defaults.maxdepth, |depth| depth.max(0) as usize);
+
+        ToctreeOptions {
+            maxdepth,
+            collapse: as_bool("collapse_navigation", defaults.collapse),
+            includehidden: as_bool("includehidden", defaults.includehidden),
+            titles_only: as_bool("titles_only", defaults.titles_only),
+            current_doc: Some(doc_path.to_string()),
+        }
+    }
+
+    /// Meta tags describing a page for search engines and link previews: the
+    /// tags of the page's `meta` directives, a description taken from the
+    /// first paragraph unless one of them gives it, plus, when enabled, the
+    /// OpenGraph and Twitter card tags.
+    fn page_meta_tags(&self, title: &str, body: &BodySummary, doc_path: &str, meta: &[(String, String)]) -> String {
+        let og = &self.config.opengraph;
+        let meta_description = meta
+            .iter()
+            .find(|(field, _)| field.split_whitespace().next() == Some("description"))
+            .map(|(_, content)| content.clone());
+        let mut html = render_directive_meta_tags(meta);
+        let mut tags: Vec<(&str, &str, String)> = Vec::new();
+        let description = match meta_description {
+            Some(description) => Some(description),
+            None => {
+                let description = body.description(og.description_length);
+                if let Some(description) = &description {
+                    tags.push(("name", "description", description.clone()));
+                }
+                description
+            }
+        };
+        if !og.enabled {
+            html.push_str(&render_meta_tags(&tags));
+            return html;
+        }
+
+        let title = if title.is_empty() { &self.config.project } else { title };
+        tags.push(("property", "og:title", title.to_string()));
+        tags.push(("property", "og:type", "website".to_string()));
+        let site_url = og
+            .site_url
+            .as_deref()
+            .or(self.config.html_baseurl.as_deref())
+            .filter(|url| !url.is_empty())
+            .map(|url| url.trim_end_matches('/'));
+        if let Some(site_url) = site_url {
+            tags.push(("property", "og:url", format!("{}/{}.html", site_url, doc_path)));
+        }
+        let site_name = og.site_name.as_deref().unwrap_or(&self.config.project);
+        tags.push(("property", "og:site_name", site_name.to_string()));
+        if let Some(description) = &description {
+            tags.push(("property", "og:description", description.clone()));
+        }
+        // A fixed image wins over the generated card
+        let card_image = match (&og.image, &self.social_cards) {
+            (None, Some(cards)) => {
+                let card = SocialCard {
+                    title,
+                    description: description.as_deref(),
+                    site_name,
+                    site_url,
+                };
+                self.write_social_card(cards, &card, doc_path)
+            }
+            _ => None,
+        };
+        let image = og.image.clone().or(card_image.clone());
+        if let Some(image) = &image {
+            let image = match site_url {
+                Some(site_url) if !image.contains("://") => {
+                    format!("{}/{}", site_url, image.trim_start_matches('/'))
+                }
+                _ => image.clone(),
+            };
+            tags.push(("property", "og:image", image));
+            if card_image.is_some() {
+                tags.push(("property", "og:image:width", CARD_WIDTH.to_string()));
+                tags.push(("property", "og:image:height", CARD_HEIGHT.to_string()));
+            }
+            let alt = og.image_alt.clone().or(card_image.map(|_| title.to_string()));
+            if let Some(alt) = alt {
+                tags.push(("property", "og:image:alt", alt));
+            }
+        }
+        let card = if image.is_some() { "summary_large_image" } else { "summary" };
+        tags.push(("name", "twitter:card", card.to_string()));
+
+        html.push_str(&render_meta_tags(&tags));
+        html
+    }
+
+    /// Render a page's social card into the output directory, returning its
+    /// path relative to the output root
+    fn write_social_card(
+        &self,
+        cards: &SocialCardRenderer,
+        card: &SocialCard,
+        doc_path: &str,
+    ) -> Option<String> {
+        let image = social_cards::card_path(doc_path);
+        let path = self.output_dir.join(&image);
+        let written = cards.render(card).and_then(|png| {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, png)?;
+            Ok(())
+        });
+        match written {
+            Ok(()) => Some(image),
+            Err(e) => {
+                warn!("Failed to write social card {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Name of a source file's copy under `_sources/`: its path relative to the
+    /// source directory with `html_sourcelink_suffix` appended, as in Sphinx
+    /// (`guide/intro.rst` becomes `guide/intro.rst.txt`).
+    fn source_link_name(&self, source_path: &Path) -> String {
+        let relative = paths::to_slash(&self.source_relative(source_path));
+        let suffix = self.config.html_sourcelink_suffix.as_deref().unwrap_or(".txt");
+        if relative.ends_with(suffix) {
+            relative
+        } else {
+            format!("{}{}", relative, suffix)
+        }
+    }
+
+    /// Render the in-page table of contents as nested lists rooted at the page
+    /// title. A `:tocdepth:` docinfo field caps how many heading levels are
+    /// shown, counting the title as the first. The outer list carries
+    /// `data-scrollspy` and every link a `data-section` naming its anchor, so
+    /// theme scripts can highlight the section currently in view.
+    fn render_page_toc(&self, document: &Document, doc_path: &str) -> String {
+        if document.toc.is_empty() {
+            return String::new();
+        }
+
+        let renderer = crate::renderer::HtmlRenderer::new();
+        let entries = TocEntry::nest(&document.toc);
+        // Section numbers by anchor, the title's being under the empty one
+        let mut numbers = self.environment().toc_secnumbers.get(doc_path).cloned().unwrap_or_default();
+        if let Some(number) = numbers.remove("") {
+            numbers.insert(document.toc[0].anchor.clone(), number);
+        }
+        let mut html = String::new();
+        render_page_toc_entries(&renderer, &entries, 1, page_tocdepth(document), &numbers, &mut html);
+        html
+    }
+
+    /// Fallback HTML rendering when template engine fails
+    fn render_fallback_html(
+        &self,
+        document: &Document,
+        body_html: &str,
+        doc_path: &str,
+        page_nav: &PageNavigation,
+        ctx: &serde_json::Map<String, serde_json::Value>,
+    ) -> String {
+        let docstitle = self.docstitle();
+        let page_title = if document.title.is_empty() || document.title == "Untitled" {
+            html_escape::encode_text(&docstitle).into_owned()
+        } else {
+            format!(
+                "{} &#8212; {}",
+                html_escape::encode_text(&template::strip_tags(&document.title)),
+                html_escape::encode_text(&docstitle)
+            )
+        };
+
+        // The page's stylesheets and scripts, theme ones included, linked
+        // relative to the page
+        let asset_attributes = Arc::clone(&self.asset_attributes.lock());
+        let asset_tags = |key: &str, tag: fn(&str, &[(String, String)]) -> String| {
+            let files = ctx.get(key).and_then(|files| files.as_array()).cloned().unwrap_or_default();
+            files
+                .iter()
+                .filter_map(|file| file.as_str())
+                .map(|file| {
+                    let attributes = asset_attributes.get(file).map(Vec::as_slice).unwrap_or_default();
+                    tag(&template::pathto(doc_path, file, true), attributes)
+                })
+                .collect::<Vec<_>>()
+                .join("\n    ")
+        };
+        let css_section = asset_tags("css_files", template::css_tag);
+        let js_section = asset_tags("script_files", template::js_tag);
+
+        let toctree_html = self
+            .navigation
+            .lock()
+            .render_toctree(&self.sidebar_toctree_options(doc_path));
+        let page_toc = if document.toc.len() > 1 && page_tocdepth(document) != Some(1) {
+            format!(
+                "\n        <nav class=\"page-toc\" aria-label=\"On this page\">\n            {}\n        </nav>",
+                self.render_page_toc(document, doc_path)
+            )
+        } else {
+            String::new()
+        };
+        let related: String = [("prev", "Previous", &page_nav.prev), ("next", "Next", &page_nav.next)]
+            .iter()
+            .filter_map(|(rel, label, link)| {
+                let link = link.as_ref()?;
+                Some(format!(
+                    "\n            <a class=\"{}-page\" rel=\"{}\" href=\"{}\">{}: {}</a>",
+                    rel,
+                    rel,
+                    html_escape::encode_double_quoted_attribute(&link.link),
+                    label,
+                    link.title
+                ))
+            })
+            .collect();
+
+        format!(
+            r#"<!DOCTYPE html>
+<html lang="{}">
+<head>
     <meta charset="utf-8" />
     <meta name="viewport" content="width=device-width, initial-scale=1.0" />
     <title>{}</title>
@@ -890,8 +2851,15 @@ impl SphinxBuilder {
 </head>
 <body>
     <div class="document">
+        <nav class="sphinxsidebar" aria-label="Site navigation">
+            <a href="{}">{}</a>
+            <div id="searchbox"></div>
+            {}
+        </nav>
         <div class="body">
             {}
+        </div>{}
+        <div class="related-pages">{}
         </div>
     </div>
     {}
@@ -900,14 +2868,213 @@ impl SphinxBuilder {
             self.config.language.as_deref().unwrap_or("en"),
             page_title,
             css_section,
+            template::pathto(doc_path, &self.config.root_doc.clone().unwrap_or_else(|| "index".to_string()), false),
+            html_escape::encode_text(&docstitle),
+            toctree_html,
             body_html,
+            page_toc,
+            related,
             js_section
         )
     }
 
-    async fn generate_indices(&self, _documents: &[Document]) -> Result<()> {
+    async fn generate_indices(&self, documents: &[DocumentSummary]) -> Result<()> {
         info!("Generating indices and cross-references");
-        // TODO: Implement index generation
+        self.write_general_index(documents)
+    }
+
+    /// Write `genindex.html` with the entries of `index` directives and
+    /// `:index:` roles, unless `html_use_index` is off or the project has its
+    /// own `genindex` page
+    fn write_general_index(&self, documents: &[DocumentSummary]) -> Result<()> {
+        let has_own_page = documents.iter().any(|doc| doc.doc_path == GENINDEX_PAGE)
+            || self.config.html_additional_pages.contains_key(GENINDEX_PAGE);
+        if !self.config.html_use_index.unwrap_or(true) || has_own_page {
+            return Ok(());
+        }
+
+        let mut documents: Vec<&DocumentSummary> = documents.iter().collect();
+        documents.sort_by(|a, b| a.doc_path.as_str().cmp(b.doc_path.as_str()));
+        let groups = genindex::build_index(
+            documents
+                .iter()
+                .map(|doc| (doc.doc_path.as_str(), doc.index_terms.as_slice())),
+        );
+        let body = genindex::render_index(&groups);
+        let summary = BodySummary::of(&body);
+        let (ctx, values) = self.base_context(GENINDEX_PAGE, "Index", body.into(), &summary, &[]);
+        let html = self
+            .template_engine()
+            .render_with_values("layout.html", &ctx.build(), values)
+            .context("Failed to render the general index")?;
+        self.write_page(GENINDEX_PAGE, &html)
+    }
+
+    /// Render each `html_additional_pages` template to `<page>.html`
+    fn write_additional_pages(&self) -> Result<()> {
+        let mut pages: Vec<(&String, &String)> = self.config.html_additional_pages.iter().collect();
+        pages.sort();
+        for (page, template_name) in pages {
+            let (ctx, values) = self.base_context(page, "", "".into(), &BodySummary::default(), &[]);
+            let html = self
+                .template_engine()
+                .render_with_values(template_name, &ctx.build(), values)
+                .with_context(|| format!("Failed to render additional page '{}'", page))?;
+            self.write_page(page, &html)?;
+        }
+        Ok(())
+    }
+
+    /// Write `404.html` unless the project has its own `404` document. Links
+    /// on the page get the configured URL prefix since static hosts serve it
+    /// for missing URLs at any depth.
+    fn write_not_found_page(&self, documents: &[DocumentSummary]) -> Result<()> {
+        let not_found = &self.config.not_found;
+        let has_own_page = documents.iter().any(|doc| doc.doc_path == NOT_FOUND_PAGE)
+            || self.config.html_additional_pages.contains_key(NOT_FOUND_PAGE);
+        if !not_found.enabled || has_own_page {
+            return Ok(());
+        }
+
+        let summary = BodySummary::of(&not_found.body);
+        let (mut ctx, values) =
+            self.base_context(NOT_FOUND_PAGE, &not_found.title, not_found.body.as_str().into(), &summary, &[]);
+        // A canonical URL would point search engines at the error page
+        ctx.insert("pageurl", Option::<String>::None).ok();
+        let html = self
+            .template_engine()
+            .render_with_values("layout.html", &ctx.build(), values)
+            .context("Failed to render the 404 page")?;
+        self.write_page(NOT_FOUND_PAGE, &absolutize_urls(&html, &self.not_found_urls_prefix()))
+    }
+
+    /// URL prefix for links on the 404 page, from `urls_prefix` or the path
+    /// of `html_baseurl`
+    fn not_found_urls_prefix(&self) -> String {
+        if let Some(prefix) = &self.config.not_found.urls_prefix {
+            return prefix.clone();
+        }
+        self.base_url_path()
+    }
+
+    /// Path the site is served at, from `html_baseurl`, like `/` or `/docs/`
+    fn base_url_path(&self) -> String {
+        let base_path = self
+            .config
+            .html_baseurl
+            .as_deref()
+            .map(|base| match base.split_once("://") {
+                Some((_, rest)) => rest.find('/').map_or("", |slash| &rest[slash..]),
+                None => base,
+            })
+            .unwrap_or("")
+            .trim_matches('/');
+        if base_path.is_empty() {
+            "/".to_string()
+        } else {
+            format!("/{}/", base_path)
+        }
+    }
+
+    /// Copy a file embedded by a page into the output. Copies that are as new
+    /// as their source are kept, as videos can be large.
+    fn copy_media_file(&self, media: &MediaFile) -> Result<()> {
+        if !media.source.is_file() {
+            return Ok(());
+        }
+        let target = self.output_dir.join(&media.output);
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        if let (Some(copied), Some(source)) = (modified(&target), modified(&media.source)) {
+            if copied >= source {
+                return Ok(());
+            }
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
+        }
+        std::fs::copy(&media.source, &target)
+            .with_context(|| format!("Failed to copy media file: {}", media.source.display()))?;
+        Ok(())
+    }
+
+    /// Run the post-processors over a page, then minify it if configured
+    fn finish_page(&self, page: &RenderedPage, html: String) -> Result<String> {
+        let html = self.post_processors.apply(page, html)?;
+        if self.config.output.minify_html {
+            Ok(optimize::minify_html(&html))
+        } else {
+            Ok(html)
+        }
+    }
+
+    /// Write a rendered page to `<page>.html` in the output directory
+    fn write_page(&self, page: &str, html: &str) -> Result<()> {
+        let output_path = self.output_dir.join(format!("{}.html", page));
+        let rendered_page = RenderedPage {
+            doc_path: page,
+            source_path: None,
+            output_path: &output_path,
+        };
+        let html = self.finish_page(&rendered_page, html.to_string())?;
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
+        }
+        std::fs::write(&output_path, html)
+            .with_context(|| format!("Failed to write output file: {}", output_path.display()))
+    }
+
+    /// Write generated stylesheets into `_static`, minify the assets and
+    /// hash them for subresource integrity
+    fn prepare_static_assets(&self) -> Result<()> {
+        let static_dir = self.output_dir.join("_static");
+        if self.config.security.strict_csp {
+            utils::write_if_changed(&static_dir.join(HIGHLIGHT_STYLESHEET), HtmlRenderer::new().highlight_css())?;
+        }
+        if self.config.copybutton.enabled {
+            // The settings go in the script itself, which strict CSP allows.
+            // The files sit in a subdirectory so that the built-in assets,
+            // copied into every build, leave them out.
+            let script = format!(
+                "var COPYBUTTON = {};\n{}",
+                self.config.copybutton.script_settings(),
+                include_str!("../static/copybutton/copybutton.js")
+            );
+            utils::write_if_changed(&static_dir.join(COPYBUTTON_SCRIPT), script)?;
+            utils::write_if_changed(
+                &static_dir.join(COPYBUTTON_STYLESHEET),
+                include_str!("../static/copybutton/copybutton.css"),
+            )?;
+        }
+        if self.uses_design.load(Ordering::Relaxed) {
+            utils::write_if_changed(&static_dir.join(DESIGN_STYLESHEET), include_str!("../static/design/design.css"))?;
+        }
+        if self.config.output.minify_assets {
+            let minified = self.thread_pool()?.install(|| optimize::minify_assets(&static_dir))?;
+            debug!("Minified {} static assets", minified);
+        }
+        if self.config.security.subresource_integrity {
+            let mut attributes = AssetAttributes::clone(&self.asset_attributes.lock());
+            let environment = self.environment();
+            let features = self.feature_assets(&PageFeature::ALL.into_iter().collect());
+            let feature_files: Vec<String> =
+                features.css_files.iter().chain(&features.js_files).map(Asset::url).collect();
+            for asset in environment.css_files.iter().chain(&environment.js_files).chain(&feature_files) {
+                let Ok(content) = std::fs::read(self.output_dir.join(asset)) else {
+                    debug!("No integrity hash for missing asset {}", asset);
+                    continue;
+                };
+                let mut tag_attributes =
+                    vec![("integrity".to_string(), utils::subresource_integrity(&content))];
+                if !self.config.security.crossorigin.is_empty() {
+                    tag_attributes
+                        .push(("crossorigin".to_string(), self.config.security.crossorigin.clone()));
+                }
+                attributes.entry(asset.clone()).or_default().extend(tag_attributes);
+            }
+            *self.asset_attributes.lock() = Arc::new(attributes);
+        }
         Ok(())
     }
 
@@ -919,12 +3086,15 @@ impl SphinxBuilder {
         tokio::fs::create_dir_all(&static_output_dir).await
             .with_context(|| format!("Failed to create static output directory: {}", static_output_dir.display()))?;
 
-        // Copy theme static assets first (so project assets can override)
-        if let Some(ref theme) = self.active_theme {
+        // Files are collected first and copied together; later entries for the
+        // same destination win. Theme static assets come first (so project
+        // assets can override)
+        let mut copies = Vec::new();
+        if let Some(ref theme) = *self.active_theme() {
             if let Some(ref theme_static_dir) = theme.static_dir {
                 if theme_static_dir.exists() {
                     info!("Copying theme static assets from {}", theme_static_dir.display());
-                    self.copy_dir_to_static(theme_static_dir, &static_output_dir).await?;
+                    copies.extend(utils::plan_dir_copy(theme_static_dir, &static_output_dir, None)?);
                 }
             }
         }
@@ -954,11 +3124,11 @@ impl SphinxBuilder {
                         .with_context(|| format!("Failed to read entry in static directory: {}", builtin_static_dir.display()))?;
                     let file_path = entry.path();
                     if file_path.is_file() {
-                        let file_name = file_path.file_name().unwrap();
-                        let dest_path = static_output_dir.join(file_name);
-                        tokio::fs::copy(&file_path, &dest_path).await
-                            .with_context(|| format!("Failed to copy static asset {} to {}", file_path.display(), dest_path.display()))?;
-                        debug!("Copied static asset: {:?}", file_name);
+                        let dest_path = static_output_dir.join(entry.file_name());
+                        copies.push(utils::FileCopy {
+                            source: file_path,
+                            destination: dest_path,
+                        });
                     }
                 }
                 static_assets_copied = true;
@@ -968,7 +3138,8 @@ impl SphinxBuilder {
 
         if !static_assets_copied {
             debug!("No built-in static assets found, creating basic ones");
-            // Create minimal CSS files if not found
+            // Create minimal CSS files if not found, over the theme's files
+            self.copy_files(std::mem::take(&mut copies))?;
             self.create_default_static_assets(&static_output_dir)
                 .await?;
         }
@@ -978,7 +3149,7 @@ impl SphinxBuilder {
             let project_static = self.source_dir.join(static_path);
             if project_static.exists() {
                 info!("Copying static assets from {}", project_static.display());
-                self.copy_dir_to_static(&project_static, &static_output_dir).await?;
+                copies.extend(utils::plan_dir_copy(&project_static, &static_output_dir, None)?);
             } else {
                 debug!("Static path does not exist: {}", project_static.display());
             }
@@ -991,9 +3162,11 @@ impl SphinxBuilder {
                 let logo_filename = logo_src.file_name()
                     .ok_or_else(|| anyhow::anyhow!("Invalid logo path"))?;
                 let logo_dest = static_output_dir.join(logo_filename);
-                tokio::fs::copy(&logo_src, &logo_dest).await
-                    .with_context(|| format!("Failed to copy logo from {} to {}", logo_src.display(), logo_dest.display()))?;
-                info!("Copied logo to {}", logo_dest.display());
+                info!("Copying logo to {}", logo_dest.display());
+                copies.push(utils::FileCopy {
+                    source: logo_src,
+                    destination: logo_dest,
+                });
             }
         }
 
@@ -1004,32 +3177,133 @@ impl SphinxBuilder {
                 let favicon_filename = favicon_src.file_name()
                     .ok_or_else(|| anyhow::anyhow!("Invalid favicon path"))?;
                 let favicon_dest = static_output_dir.join(favicon_filename);
-                tokio::fs::copy(&favicon_src, &favicon_dest).await
-                    .with_context(|| format!("Failed to copy favicon from {} to {}", favicon_src.display(), favicon_dest.display()))?;
-                info!("Copied favicon to {}", favicon_dest.display());
+                info!("Copying favicon to {}", favicon_dest.display());
+                copies.push(utils::FileCopy {
+                    source: favicon_src,
+                    destination: favicon_dest,
+                });
             }
         }
 
+        self.copy_files(copies)
+    }
+
+    /// Copy files on the build's thread pool, skipping unchanged ones
+    fn copy_files(&self, copies: Vec<utils::FileCopy>) -> Result<()> {
+        if copies.is_empty() {
+            return Ok(());
+        }
+        let stats = self.thread_pool()?.install(|| utils::copy_files(copies))?;
+        debug!("Copied {} files, {} unchanged", stats.copied, stats.unchanged);
+        Ok(())
+    }
+
+    /// Write `.nojekyll` and, when a custom domain is known, `CNAME` into the
+    /// output root for publishing on GitHub Pages
+    fn write_github_pages_files(&self) -> Result<()> {
+        if !self.config.github_pages.enabled {
+            return Ok(());
+        }
+
+        let nojekyll = self.output_dir.join(".nojekyll");
+        std::fs::write(&nojekyll, "")
+            .with_context(|| format!("Failed to write {}", nojekyll.display()))?;
+
+        if let Some(domain) = github_pages_cname(&self.config) {
+            let cname = self.output_dir.join("CNAME");
+            std::fs::write(&cname, domain)
+                .with_context(|| format!("Failed to write {}", cname.display()))?;
+        }
         Ok(())
     }
 
-    /// Copy contents of a directory into the static output directory
-    async fn copy_dir_to_static(&self, src_dir: &Path, dest_dir: &Path) -> Result<()> {
-        utils::copy_dir_recursive(src_dir, dest_dir).await
+    /// Write a stub page for each redirect, and the configured server-side
+    /// redirect files. Redirects from an existing document are skipped.
+    fn write_redirects(&self, documents: &[DocumentSummary]) -> Result<()> {
+        let config = &self.config.redirects;
+        if config.pages.is_empty() {
+            return Ok(());
+        }
+        let documents: HashMap<&str, &Path> = documents
+            .iter()
+            .map(|doc| (doc.doc_path.as_str(), doc.source_path.as_path()))
+            .collect();
+
+        let mut redirects = Vec::new();
+        for (from, to) in &config.pages {
+            let redirect = match Redirect::new(from, to) {
+                Ok(redirect) => redirect,
+                Err(error) => {
+                    self.add_warning(BuildWarning::new(
+                        self.source_dir.clone(),
+                        None,
+                        format!("{}, skipped", error),
+                        WarningType::Redirect,
+                    ));
+                    continue;
+                }
+            };
+            if let Some(source) = documents.get(redirect.from.as_str()) {
+                self.add_warning(BuildWarning::new(
+                    source.to_path_buf(),
+                    None,
+                    format!("redirect from '{}' skipped, the document still exists", redirect.from),
+                    WarningType::Redirect,
+                ));
+                continue;
+            }
+            if let Some(target) = redirect.target_doc() {
+                if !documents.contains_key(target) {
+                    self.add_warning(BuildWarning::new(
+                        self.source_dir.clone(),
+                        None,
+                        format!("redirect from '{}' points to nonexisting document '{}'", redirect.from, target),
+                        WarningType::Redirect,
+                    ));
+                }
+            }
+
+            let url = redirect.stub_url();
+            let canonical = match self.config.html_baseurl.as_deref() {
+                Some(base) if !base.is_empty() && !redirect.is_external() => {
+                    Some(format!("{}/{}", base.trim_end_matches('/'), redirect.target_path()))
+                }
+                _ => None,
+            };
+            let path = self.output_dir.join(format!("{}.html", redirect.from));
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
+            }
+            std::fs::write(&path, redirects::stub_html(&url, canonical.as_deref(), to))
+                .with_context(|| format!("Failed to write redirect: {}", path.display()))?;
+            redirects.push(redirect);
+        }
+
+        let prefix = self.base_url_path();
+        for format in &config.map_files {
+            let path = self.output_dir.join(format.file_name());
+            std::fs::write(&path, redirects::map_file(*format, &redirects, &prefix))
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        info!("Wrote {} redirects", redirects.len());
+        Ok(())
     }
 
-    /// Copy html_extra_path directories to the output root
-    async fn copy_extra_paths(&self) -> Result<()> {
+    /// Copy html_extra_path directories to the output root, returning the
+    /// copied files relative to the output directory
+    async fn copy_extra_paths(&self, documents: &[DocumentSummary]) -> Result<BTreeSet<String>> {
         if self.config.html_extra_path.is_empty() {
-            return Ok(());
+            return Ok(BTreeSet::new());
         }
 
         info!("Copying extra paths to output directory");
 
         // Pre-canonicalize source and output for safety checks
-        let canonical_source = self.source_dir.canonicalize().ok();
-        let canonical_output = self.output_dir.canonicalize().ok();
+        let canonical_source = paths::canonicalize(&self.source_dir).ok();
+        let canonical_output = paths::canonicalize(&self.output_dir).ok();
 
+        let mut copies = Vec::new();
         for extra_path in &self.config.html_extra_path {
             // Resolve path relative to source directory
             let src_path = if extra_path.is_absolute() {
@@ -1044,7 +3318,7 @@ impl SphinxBuilder {
             }
 
             // Safety check: don't copy the source directory itself or the output directory
-            if let Ok(canonical_src) = src_path.canonicalize() {
+            if let Ok(canonical_src) = paths::canonicalize(&src_path) {
                 if let Some(ref source) = canonical_source {
                     if &canonical_src == source || source.starts_with(&canonical_src) {
                         warn!(
@@ -1068,23 +3342,79 @@ impl SphinxBuilder {
             if src_path.is_dir() {
                 // Copy directory contents to output root, excluding output directory
                 info!("Copying extra directory: {}", src_path.display());
-                utils::copy_dir_recursive_excluding(&src_path, &self.output_dir, canonical_output.as_ref()).await
+                let files = utils::plan_dir_copy(&src_path, &self.output_dir, canonical_output.as_ref())
                     .with_context(|| format!(
                         "Failed to copy html_extra_path directory '{}' to '{}'",
                         src_path.display(),
                         self.output_dir.display()
                     ))?;
+                copies.extend(files);
             } else if src_path.is_file() {
                 // Copy single file to output root
                 let file_name = src_path.file_name()
                     .ok_or_else(|| anyhow::anyhow!("Invalid file path: {}", src_path.display()))?;
                 let dest_path = self.output_dir.join(file_name);
                 info!("Copying extra file: {} -> {}", src_path.display(), dest_path.display());
-                tokio::fs::copy(&src_path, &dest_path).await
-                    .with_context(|| format!("Failed to copy extra file {} to {}", src_path.display(), dest_path.display()))?;
+                copies.push(utils::FileCopy {
+                    source: src_path,
+                    destination: dest_path,
+                });
+            }
+        }
+
+        self.check_extra_path_conflicts(&copies, documents)?;
+        let copied = copies.iter().map(|copy| self.output_relative(&copy.destination)).collect();
+        self.copy_files(copies)?;
+        Ok(copied)
+    }
+
+    /// Report files of `html_extra_path` that replace generated pages or
+    /// other extra files, listing both sources, as warnings or, with
+    /// `html_extra_path_conflicts: error`, by failing the build
+    fn check_extra_path_conflicts(&self, copies: &[utils::FileCopy], documents: &[DocumentSummary]) -> Result<()> {
+        let mut generated: HashMap<PathBuf, String> = documents
+            .iter()
+            .filter_map(|doc| {
+                let output_path = self.get_output_path(&doc.source_path).ok()?;
+                Some((output_path, format!("generated from {}", doc.source_path.display())))
+            })
+            .collect();
+        for page in self.generated_pages() {
+            generated.insert(self.output_dir.join(format!("{}.html", page)), "a generated page".to_string());
+        }
+
+        let mut conflicts = Vec::new();
+        let mut copied: HashMap<&Path, &Path> = HashMap::new();
+        for copy in copies {
+            let output = copy.destination.strip_prefix(&self.output_dir).unwrap_or(&copy.destination);
+            if let Some(page) = generated.get(&copy.destination) {
+                conflicts.push(BuildWarning::new(
+                    copy.source.clone(),
+                    None,
+                    format!("html_extra_path file replaces '{}', {}", output.display(), page),
+                    WarningType::ExtraPathConflict,
+                ));
+            }
+            if let Some(earlier) = copied.insert(&copy.destination, &copy.source) {
+                conflicts.push(BuildWarning::new(
+                    copy.source.clone(),
+                    None,
+                    format!("html_extra_path file replaces '{}', also copied from {}", output.display(), earlier.display()),
+                    WarningType::ExtraPathConflict,
+                ));
             }
         }
 
+        if self.config.html_extra_path_conflicts == ExtraPathConflicts::Error && !conflicts.is_empty() {
+            let conflicts: Vec<String> = conflicts
+                .iter()
+                .map(|conflict| format!("{}: {}", conflict.file.display(), conflict.message))
+                .collect();
+            anyhow::bail!("html_extra_path files conflict:\n  {}", conflicts.join("\n  "));
+        }
+        for conflict in conflicts {
+            self.add_warning(conflict);
+        }
         Ok(())
     }
 
@@ -1121,68 +3451,45 @@ impl SphinxBuilder {
         Ok(())
     }
 
-    async fn validate_documents(
-        &self,
-        processed_docs: &[Document],
-        _source_files: &[PathBuf],
-    ) -> Result<()> {
+    async fn validate_documents(&self, documents: &[DocumentSummary]) -> Result<()> {
         info!("Validating documents and checking for warnings...");
 
         let mut toctree_references = HashSet::new();
         let mut referenced_files = HashSet::new();
-        let mut all_documents = HashSet::new();
-
-        // Collect all documents and their toctree references
-        for doc in processed_docs {
-            // Get relative path for comparison
-            let doc_path_relative = doc
-                .source_path
-                .strip_prefix(&self.source_dir)
-                .unwrap_or(&doc.source_path);
-            let doc_path_no_ext = doc_path_relative.with_extension("");
-            all_documents.insert(doc_path_no_ext.to_string_lossy().to_string());
-
-            // Check for toctree directives and collect their references
-            if let Some(toctree_refs) = self.extract_toctree_references(doc) {
-                for toc_ref in toctree_refs {
-                    toctree_references.insert((doc.source_path.clone(), toc_ref.clone()));
-                    referenced_files.insert(toc_ref);
+        let all_documents: HashSet<&str> =
+            documents.iter().map(|doc| doc.doc_path.as_str()).collect();
+
+        // Collect the toctree references of all documents
+        for doc in documents {
+            for toc_ref in &doc.toctree_entries {
+                let (_, target) = navigation::split_toctree_entry(toc_ref);
+                // Skip external URLs and special references
+                if navigation::is_external(target) || target.starts_with('@') {
+                    continue;
                 }
+                let resolved = navigation::resolve_toctree_target(&doc.doc_path, target, |name| {
+                    all_documents.contains(name)
+                });
+                toctree_references.insert((doc.source_path.clone(), target.to_string(), resolved.clone()));
+                referenced_files.insert(resolved);
             }
         }
 
         // Check for missing toctree references
-        for (source_file, reference) in &toctree_references {
-            // Skip external URLs and special references
-            if reference.starts_with("http://")
-                || reference.starts_with("https://")
-                || reference.contains('<')  // External link syntax: "Title <url>"
-                || reference.starts_with('@')  // Some external link conventions
-            {
-                continue;
-            }
-
-            let ref_path = format!("{}/index", reference);
-            let alt_ref_path = reference.clone();
-
-            if !all_documents.contains(&ref_path) && !all_documents.contains(&alt_ref_path) {
+        for (source_file, reference, resolved) in &toctree_references {
+            if !all_documents.contains(resolved.as_str()) {
                 let warning = BuildWarning::missing_toctree_ref(
                     source_file.clone(),
                     Some(10), // TODO: Extract actual line number
                     reference,
                 );
-                self.warnings.lock().unwrap().push(warning);
+                self.add_warning(warning);
             }
         }
 
         // Check for orphaned documents
-        for doc in processed_docs {
-            let doc_path_relative = doc
-                .source_path
-                .strip_prefix(&self.source_dir)
-                .unwrap_or(&doc.source_path);
-            let doc_path_no_ext = doc_path_relative.with_extension("");
-            let doc_path_str = doc_path_no_ext.to_string_lossy().to_string();
+        for doc in documents {
+            let doc_path_str = &doc.doc_path;
 
             // Skip the main index file
             if doc_path_str == "index" {
@@ -1191,66 +3498,658 @@ impl SphinxBuilder {
 
             // Check if this document is referenced in any toctree
             let is_referenced = referenced_files.iter().any(|ref_path| {
-                ref_path == &doc_path_str
+                ref_path == doc_path_str.as_str()
                     || ref_path == &format!("{}/index", doc_path_str)
                     || doc_path_str.starts_with(&format!("{}/", ref_path))
             });
 
             if !is_referenced {
                 let warning = BuildWarning::orphaned_document(doc.source_path.clone());
-                self.warnings.lock().unwrap().push(warning);
+                self.add_warning(warning);
             }
         }
 
-        let warning_count = self.warnings.lock().unwrap().len();
+        let warning_count = self.warnings.lock().len();
         info!("Validation completed. Found {} warnings", warning_count);
 
         Ok(())
     }
 
-    fn extract_toctree_references(&self, doc: &Document) -> Option<Vec<String>> {
-        use crate::document::DocumentContent;
-
-        let mut references = Vec::new();
+    /// Checks of each document on its own: directives and roles, content
+    /// rules and the enabled lints
+    fn validate_document_contents(&self, processed_docs: &[Arc<Document>]) -> Result<()> {
+        self.validate_directives_and_roles(processed_docs);
+        self.validate_content_rules(processed_docs)
+            .context("Content rule validation failed")?;
+        if self.config.lint.style || self.config.lint.accessibility {
+            self.lint_styles(processed_docs);
+        }
+        if self.config.lint.accessibility {
+            self.check_accessibility(processed_docs);
+        }
+        Ok(())
+    }
 
-        if let DocumentContent::RestructuredText(rst_content) = &doc.content {
-            for node in &rst_content.ast {
-                if let crate::document::RstNode::Directive { name, content, .. } = node {
-                    if name == "toctree" {
-                        // Extract references from toctree content
-                        for line in content.lines() {
-                            let trimmed = line.trim();
-                            if !trimmed.is_empty()
-                                && !trimmed.starts_with(':')
-                                && !trimmed.starts_with("..")
-                            {
-                                references.push(trimmed.to_string());
-                            }
+    /// Run the registered directive and role validators over every RST document
+    /// and record their findings as build warnings.
+    fn validate_directives_and_roles(&self, processed_docs: &[Arc<Document>]) {
+        let mut known_directives = crate::directives::DirectiveRegistry::new();
+        known_directives.register_custom_directives(&self.config.custom_directives);
+        let mut known_roles = crate::roles::RoleRegistry::new();
+        known_roles.register_tracker_roles(&self.config.issue_tracker);
+        known_roles.register_custom_roles(&self.config.custom_roles);
+        let mut system = self.directive_validation.lock();
+
+        let mut warnings = Vec::new();
+        for doc in processed_docs {
+            let raw = match &doc.content {
+                DocumentContent::RestructuredText(rst) => &rst.raw,
+                _ => continue,
+            };
+            let lines: Vec<&str> = raw.lines().collect();
+            let parser = DirectiveRoleParser::new(doc.source_path.display().to_string());
+            let (directives, roles) = parser.parse_content(raw);
+
+            for directive in &directives {
+                let line = Some(directive.location.line);
+                let warning = match system.validate_directive(directive) {
+                    DirectiveValidationResult::Valid => continue,
+                    DirectiveValidationResult::Warning(message)
+                    | DirectiveValidationResult::Error(message) => BuildWarning::new(
+                        doc.source_path.clone(),
+                        line,
+                        format!("\"{}\" directive: {}", directive.name, message),
+                        WarningType::InvalidDirective,
+                    )
+                    .with_suggestions(system.get_directive_suggestions(directive)),
+                    DirectiveValidationResult::Unknown => {
+                        if known_directives.get(&directive.name).is_some() {
+                            continue;
                         }
+                        BuildWarning::new(
+                            doc.source_path.clone(),
+                            line,
+                            format!("Unknown directive type \"{}\".", directive.name),
+                            WarningType::UnknownDirective,
+                        )
+                        .with_suggestions(similar_names(&directive.name, &known_directives.names()))
                     }
+                };
+                warnings.push(warning);
+            }
+
+            for role in &roles {
+                // Only interpreted text (:name:`...`) is rendered as a role
+                let source_line = role
+                    .location
+                    .line
+                    .checked_sub(1)
+                    .and_then(|index| lines.get(index))
+                    .copied()
+                    .unwrap_or("");
+                if !source_line.contains(&format!(":{}:`", role.name)) {
+                    continue;
                 }
+
+                let line = Some(role.location.line);
+                let warning = match system.validate_role(role) {
+                    RoleValidationResult::Valid => continue,
+                    RoleValidationResult::Warning(message) | RoleValidationResult::Error(message) => {
+                        BuildWarning::new(
+                            doc.source_path.clone(),
+                            line,
+                            format!("\"{}\" role: {}", role.name, message),
+                            WarningType::InvalidRole,
+                        )
+                        .with_suggestions(system.get_role_suggestions(role))
+                    }
+                    RoleValidationResult::Unknown => {
+                        if known_roles.get(&role.name).is_some() {
+                            continue;
+                        }
+                        BuildWarning::new(
+                            doc.source_path.clone(),
+                            line,
+                            format!("Unknown interpreted text role \"{}\".", role.name),
+                            WarningType::UnknownRole,
+                        )
+                        .with_suggestions(similar_names(&role.name, &known_roles.names()))
+                    }
+                };
+                warnings.push(warning);
             }
         }
 
-        if references.is_empty() {
-            None
-        } else {
-            Some(references)
+        drop(system);
+        for warning in warnings {
+            self.add_warning(warning);
         }
     }
 
-    async fn generate_search_index(&self, _documents: &[Document]) -> Result<()> {
-        info!("Generating search index");
-        // TODO: Implement search index generation
+    /// Check documents against the project's configured content rules
+    fn validate_content_rules(&self, processed_docs: &[Arc<Document>]) -> Result<()> {
+        let rules = &self.config.content_rules;
+        if rules.is_empty() {
+            return Ok(());
+        }
+
+        let validation_config = rules.validation_config();
+        let mut engine = ConstraintEngine::new();
+
+        for doc in processed_docs {
+            let docname = paths::to_slash(&self.source_relative(&doc.source_path).with_extension(""));
+
+            let violations =
+                rules.check_document(&mut engine, &validation_config, doc, &docname)?;
+            for violation in violations {
+                let line = Some(violation.line);
+                match violation.severity {
+                    ValidationSeverity::Info => {
+                        info!("{}:{}: {}", doc.source_path.display(), violation.line, violation.message);
+                    }
+                    ValidationSeverity::Warning => self.add_warning(BuildWarning::new(
+                        doc.source_path.clone(),
+                        line,
+                        violation.message,
+                        WarningType::ContentRule,
+                    )),
+                    ValidationSeverity::Error | ValidationSeverity::Critical => {
+                        self.add_error(BuildErrorReport::new(
+                            doc.source_path.clone(),
+                            line,
+                            violation.message,
+                            ErrorType::ValidationError,
+                        ))
+                    }
+                }
+            }
+        }
+
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
+    /// Report heading hierarchy and title formatting problems. With only the
+    /// accessibility lint enabled, just heading order is checked.
+    fn lint_styles(&self, processed_docs: &[Arc<Document>]) {
+        let heading_order_only = !self.config.lint.style;
+        for doc in processed_docs {
+            let DocumentContent::RestructuredText(rst) = &doc.content else {
+                continue;
+            };
+            for issue in style::lint_headings(&rst.raw) {
+                if heading_order_only && issue.kind != StyleIssueKind::SkippedHeadingLevel {
+                    continue;
+                }
+                let warning_type = match issue.kind {
+                    StyleIssueKind::SkippedHeadingLevel => WarningType::SkippedHeadingLevel,
+                    StyleIssueKind::MultipleTitles => WarningType::MultipleTitles,
+                    StyleIssueKind::TitleTrailingWhitespace => WarningType::TitleWhitespace,
+                    StyleIssueKind::UnderlineLength => WarningType::UnderlineLength,
+                };
+                self.add_warning(
+                    BuildWarning::new(
+                        doc.source_path.clone(),
+                        Some(issue.line),
+                        issue.message,
+                        warning_type,
+                    )
+                    .with_suggestions(vec![issue.fix_hint]),
+                );
+            }
+        }
+    }
+
+    /// Report images without alt text, tables without headers and
+    /// non-descriptive link text
+    fn check_accessibility(&self, processed_docs: &[Arc<Document>]) {
+        for doc in processed_docs {
+            let DocumentContent::RestructuredText(rst) = &doc.content else {
+                continue;
+            };
+            for issue in accessibility::check_accessibility(&rst.raw) {
+                let warning_type = match issue.kind {
+                    AccessibilityIssueKind::MissingAltText => WarningType::MissingAltText,
+                    AccessibilityIssueKind::TableWithoutHeader => WarningType::TableWithoutHeader,
+                    AccessibilityIssueKind::AmbiguousLinkText => WarningType::AmbiguousLinkText,
+                };
+                self.add_warning(BuildWarning::new(
+                    doc.source_path.clone(),
+                    Some(issue.line),
+                    issue.message,
+                    warning_type,
+                ));
+            }
+        }
+    }
+
+    /// Document names (source paths relative to the source directory, without
+    /// extension) as used in toctree entries
+    fn document_names(&self, files: &[PathBuf]) -> Vec<String> {
+        files
+            .iter()
+            .filter_map(|file| paths::doc_name(file, &self.source_dir))
+            .collect()
+    }
+
+    /// Toctrees of a document, with `:glob:` patterns expanded against
+    /// `documents`; entries are as written, or `/`-prefixed for glob matches
+    fn extract_toctrees(&self, doc: &Document, documents: &[String]) -> Vec<Toctree> {
+        use crate::document::DocumentContent;
+
+        let mut toctrees = Vec::new();
+        let own_name = self.document_names(std::slice::from_ref(&doc.source_path));
+        let base = own_name.first().map_or("", String::as_str);
+        // Glob matches skip documents the toctree already lists, as in Sphinx
+        let mut listed: HashSet<String> = HashSet::new();
+        // Sections before the toctree, counted like `extract_document_sections`
+        let mut position = 0;
+
+        if let DocumentContent::RestructuredText(rst_content) = &doc.content {
+            for node in &rst_content.ast {
+                match node {
+                    crate::document::RstNode::Title { level, .. } if *level >= 2 => position += 1,
+                    crate::document::RstNode::Directive { name, options, content, .. } if name == "toctree" => {
+                        let glob = options.contains_key("glob");
+                        let mut references = Vec::new();
+                        // Extract references from toctree content
+                        for line in content.lines() {
+                            let trimmed = line.trim();
+                            if trimmed.is_empty()
+                                || trimmed.starts_with(':')
+                                || trimmed.starts_with("..")
+                            {
+                                continue;
+                            }
+                            if !(glob && trimmed.contains(['*', '?', '['])) {
+                                let (_, target) = navigation::split_toctree_entry(trimmed);
+                                listed.insert(navigation::resolve_toctree_target(base, target, |name| {
+                                    documents.iter().any(|document| document == name)
+                                }));
+                                references.push(Symbol::intern(trimmed));
+                                continue;
+                            }
+                            // Patterns are relative to the document; matches are
+                            // returned as absolute (`/`-prefixed) document names
+                            let pattern = navigation::resolve_docname(base, trimmed);
+                            if let Ok(regex) = matching::compile_pattern(&pattern) {
+                                let mut matches: Vec<&String> = documents
+                                    .iter()
+                                    .filter(|document| regex.is_match(document))
+                                    .filter(|document| !own_name.contains(document))
+                                    .filter(|document| !listed.contains(document.as_str()))
+                                    .collect();
+                                matches.sort();
+                                listed.extend(matches.iter().map(|doc| doc.to_string()));
+                                references.extend(matches.into_iter().map(|doc| Symbol::intern(&format!("/{}", doc))));
+                            }
+                        }
+                        toctrees.push(Toctree {
+                            entries: references,
+                            numbered: navigation::numbered_depth(options.get("numbered").map(String::as_str)),
+                            position,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        toctrees
+    }
+
+    async fn generate_search_index(&self, _documents: &[DocumentSummary]) -> Result<()> {
+        info!("Generating search index");
+        // TODO: Implement search index generation
+        Ok(())
+    }
+}
+
+/// Number of worker threads for a `--jobs` value: 0 means one per CPU core
+fn effective_parallel_jobs(jobs: usize) -> usize {
+    if jobs > 0 {
+        return jobs;
+    }
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+/// Suggest registered names that look like the misspelled one
+fn similar_names(name: &str, candidates: &[&str]) -> Vec<String> {
+    crate::directives::validation::similar_names(name, candidates.iter().copied())
+        .into_iter()
+        .map(|candidate| format!("Did you mean '{}'?", candidate))
+        .collect()
+}
+
+/// What a page template needs to know of a rendered page body
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct BodySummary {
+    /// Number of words in its text
+    words: usize,
+    /// Whether it has `<pre>` blocks
+    has_code: bool,
+    /// Inner HTML of its first paragraph
+    first_paragraph: Option<String>,
+}
+
+impl BodySummary {
+    fn of(html: &str) -> Self {
+        let mut scanner = BodyScanner::new(std::io::sink(), None, None);
+        scanner.write_all(html.as_bytes()).ok();
+        scanner.finish();
+        scanner.summary
+    }
+
+    /// Plain text of the first paragraph, shortened to at most `max_length`
+    /// characters (ending in "..." when cut).
+    fn description(&self, max_length: usize) -> Option<String> {
+        let paragraph = self.first_paragraph.as_deref()?;
+        let text = template::strip_tags(paragraph);
+        let text = html_escape::decode_html_entities(&text);
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() || max_length == 0 {
+            return None;
+        }
+        if text.chars().count() <= max_length {
+            return Some(text);
+        }
+        let cut: String = text.chars().take(max_length.saturating_sub(3)).collect();
+        Some(format!("{}...", cut.trim_end()))
+    }
+}
+
+/// A rendered page body, spooled to a temporary file as the renderer wrote
+/// it
+struct PageBody {
+    html: Arc<SpooledHtml>,
+    summary: BodySummary,
+    /// Anchors and links for the manifest, when it is written
+    refs: Option<ContentRefs>,
+}
+
+/// Passes page HTML on to `out` while gathering its [`BodySummary`], the
+/// words for the spell checker and the anchors and links for the manifest,
+/// so none of them needs the whole HTML in memory
+struct BodyScanner<'a, W> {
+    out: W,
+    summary: BodySummary,
+    spelling: Option<WordTally<'a>>,
+    refs: Option<ContentRefs>,
+    /// The tag being read, from its `<`
+    tag: Option<Vec<u8>>,
+    /// Text since the last tag or whitespace
+    word: Vec<u8>,
+    /// Inner HTML of the first paragraph while it is read
+    paragraph: Option<Vec<u8>>,
+}
+
+impl<'a, W: std::io::Write> BodyScanner<'a, W> {
+    fn new(out: W, spelling: Option<WordTally<'a>>, refs: Option<ContentRefs>) -> Self {
+        Self {
+            out,
+            summary: BodySummary::default(),
+            spelling,
+            refs,
+            tag: None,
+            word: Vec::new(),
+            paragraph: None,
+        }
+    }
+
+    fn scan(&mut self, byte: u8) {
+        if let Some(tag) = &mut self.tag {
+            tag.push(byte);
+            // Comments may hold `>`
+            if byte == b'>' && (!tag.starts_with(b"<!--") || tag.ends_with(b"-->")) {
+                let tag = self.tag.take().unwrap_or_default();
+                self.end_tag(&String::from_utf8_lossy(&tag));
+            }
+            return;
+        }
+        if byte == b'<' {
+            self.end_word();
+            self.tag = Some(vec![byte]);
+            return;
+        }
+        if let Some(paragraph) = &mut self.paragraph {
+            paragraph.push(byte);
+        }
+        if byte.is_ascii_whitespace() {
+            self.end_word();
+        } else {
+            self.word.push(byte);
+        }
+    }
+
+    fn end_word(&mut self) {
+        if self.word.is_empty() {
+            return;
+        }
+        let word = String::from_utf8_lossy(&self.word);
+        self.summary.words += html_escape::decode_html_entities(&word)
+            .split_whitespace()
+            .filter(|word| word.chars().any(char::is_alphanumeric))
+            .count();
+        if let Some(tally) = &mut self.spelling {
+            tally.push_text(&word);
+        }
+        self.word.clear();
+    }
+
+    fn end_tag(&mut self, tag: &str) {
+        if tag.starts_with("<pre") {
+            self.summary.has_code = true;
+        }
+        if let Some(paragraph) = &mut self.paragraph {
+            if tag == "</p>" {
+                let paragraph = self.paragraph.take().unwrap_or_default();
+                self.summary.first_paragraph = Some(String::from_utf8_lossy(&paragraph).into_owned());
+            } else {
+                paragraph.extend_from_slice(tag.as_bytes());
+            }
+        } else if self.summary.first_paragraph.is_none()
+            && tag
+                .strip_prefix("<p")
+                .is_some_and(|rest| rest == ">" || rest.starts_with(char::is_whitespace))
+        {
+            self.paragraph = Some(Vec::new());
+        }
+        if let Some(tally) = &mut self.spelling {
+            tally.push_tag(tag);
+        }
+        if let Some(refs) = &mut self.refs {
+            refs.push_tag(tag);
+        }
+    }
+
+    /// Take in the last word of the HTML
+    fn finish(&mut self) {
+        self.end_word();
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for BodyScanner<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.out.write(buf)?;
+        for &byte in &buf[..written] {
+            self.scan(byte);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Minutes needed to read `words` words, rounded up; at least one for a
+/// page with any text
+fn reading_minutes(words: usize, words_per_minute: usize) -> usize {
+    words.div_ceil(words_per_minute.max(1))
+}
+
+fn render_meta_tags(tags: &[(&str, &str, String)]) -> String {
+    tags.iter()
+        .map(|(attribute, name, content)| {
+            format!(
+                "<meta {}=\"{}\" content=\"{}\" />\n",
+                attribute,
+                name,
+                html_escape::encode_double_quoted_attribute(content)
+            )
+        })
+        .collect()
+}
+
+/// Tags of `meta` directive fields. The first word of a field is the tag's
+/// `name`, unless it is an attribute itself like `http-equiv=refresh`; the
+/// words after it are further attributes like `lang=en`.
+fn render_directive_meta_tags(meta: &[(String, String)]) -> String {
+    meta.iter()
+        .map(|(field, content)| {
+            let attributes: String = field
+                .split_whitespace()
+                .enumerate()
+                .map(|(i, word)| match word.split_once('=') {
+                    Some((attribute, value)) => (attribute, value),
+                    None if i == 0 => ("name", word),
+                    None => (word, ""),
+                })
+                .filter(|(attribute, _)| {
+                    !attribute.is_empty()
+                        && attribute.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':'))
+                })
+                .map(|(attribute, value)| {
+                    format!("{}=\"{}\" ", attribute, html_escape::encode_double_quoted_attribute(value))
+                })
+                .collect();
+            format!(
+                "<meta {}content=\"{}\" />\n",
+                attributes,
+                html_escape::encode_double_quoted_attribute(content)
+            )
+        })
+        .collect()
+}
+
+/// Custom domain for GitHub Pages: the configured `cname`, or the host of
+/// `html_baseurl` when it is not a `github.io` address
+fn github_pages_cname(config: &BuildConfig) -> Option<String> {
+    if let Some(cname) = config.github_pages.cname.as_deref().map(str::trim) {
+        return (!cname.is_empty()).then(|| cname.to_string());
+    }
+    let base_url = config.html_baseurl.as_deref()?;
+    let host = base_url.split_once("://")?.1.split(['/', ':']).next()?;
+    if host.is_empty() || host.ends_with(".github.io") {
+        None
+    } else {
+        Some(host.to_string())
+    }
+}
+
+/// Page name of the generated error page for missing URLs
+const NOT_FOUND_PAGE: &str = "404";
+
+/// Directory of the build cache, in the output directory
+const CACHE_DIR: &str = ".sphinx-ultra-cache";
+
+/// File in the cache directory listing the pages the last build wrote
+const OUTPUTS_FILE: &str = "outputs.json";
+
+/// Stylesheet in `_static` with the highlighting classes of strict CSP mode
+const HIGHLIGHT_STYLESHEET: &str = "highlight.css";
+
+/// Script and stylesheet of the code block copy buttons, in `_static`
+const COPYBUTTON_SCRIPT: &str = "copybutton.js";
+const COPYBUTTON_STYLESHEET: &str = "copybutton.css";
+
+/// Stylesheet in `_static` of the grid, card and button directives
+const DESIGN_STYLESHEET: &str = "design.css";
+
+lazy_static::lazy_static! {
+    static ref URL_ATTRIBUTE_RE: regex::Regex =
+        regex::Regex::new(r#"\b(href|src|action)="([^"]*)""#).unwrap();
+}
+
+/// Prefix the relative URLs of `href`, `src` and `action` attributes, leaving
+/// absolute, protocol-relative, fragment and special-scheme URLs alone
+fn absolutize_urls(html: &str, prefix: &str) -> String {
+    if prefix.is_empty() {
+        return html.to_string();
+    }
+    URL_ATTRIBUTE_RE
+        .replace_all(html, |caps: &regex::Captures| {
+            let url = &caps[2];
+            let keep = url.is_empty()
+                || url.starts_with('/')
+                || url.starts_with('#')
+                || url.contains("://")
+                || ["mailto:", "tel:", "data:", "javascript:"]
+                    .iter()
+                    .any(|scheme| url.starts_with(scheme));
+            if keep {
+                caps[0].to_string()
+            } else {
+                format!("{}=\"{}{}\"", &caps[1], prefix, url)
+            }
+        })
+        .into_owned()
+}
+
+/// The `:tocdepth:` docinfo field of a page, if it is set to a positive number.
+fn page_tocdepth(document: &Document) -> Option<usize> {
+    let value = document.metadata.custom.get("tocdepth")?;
+    value
+        .as_str()
+        .and_then(|depth| depth.trim().parse::<usize>().ok())
+        .or_else(|| value.as_u64().map(|depth| depth as usize))
+        .filter(|depth| *depth > 0)
+}
+
+/// Render the page TOC entries as nested lists; `numbers` holds the section
+/// numbers of their anchors
+fn render_page_toc_entries(
+    renderer: &crate::renderer::HtmlRenderer,
+    entries: &[TocEntry],
+    depth: usize,
+    max_depth: Option<usize>,
+    numbers: &HashMap<String, Vec<u32>>,
+    html: &mut String,
+) {
+    if depth == 1 {
+        html.push_str("<ul class=\"local-toc\" data-scrollspy=\"true\">\n");
+    } else {
+        html.push_str("<ul>\n");
+    }
+    for entry in entries {
+        // Render inline markup in the title (like `code` and :ref:)
+        let rendered_title = renderer.render_rst_inline(&entry.title);
+        let number = numbers
+            .get(&entry.anchor)
+            .map(|number| crate::renderer::section_number_prefix(number))
+            .unwrap_or_default();
+        let anchor = html_escape::encode_double_quoted_attribute(&entry.anchor);
+        html.push_str(&format!(
+            "<li class=\"toc-l{}\"><a class=\"reference internal\" href=\"#{}\" data-section=\"{}\">{}{}</a>",
+            depth, anchor, anchor, number, rendered_title
+        ));
+        if !entry.children.is_empty() && max_depth.is_none_or(|max| depth < max) {
+            html.push('\n');
+            render_page_toc_entries(renderer, &entry.children, depth + 1, max_depth, numbers, html);
+        }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ul>\n");
+}
+
+#[cfg(test)]
+mod tests {
     use crate::document::TocEntry;
+    use crate::error::BuildOutcome;
 
     #[test]
+    #[allow(clippy::bool_comparison, clippy::useless_vec)]
     fn test_display_toc_logic() {
         // display_toc should be true when toc.len() > 1
         // (first entry is page heading which is skipped)
@@ -1283,4 +4182,1462 @@ mod tests {
         assert!((one_item_toc.len() > 1) == false);
         assert!((two_item_toc.len() > 1) == true);
     }
+
+    #[test]
+    fn test_page_toc_nests_and_respects_tocdepth() {
+        use crate::document::Document;
+        use std::path::PathBuf;
+
+        let mut document = Document::new(PathBuf::from("page.rst"), PathBuf::from("page.html"));
+        document.toc = vec![
+            TocEntry::new("Page".to_string(), 1, "page".to_string(), 1),
+            TocEntry::new("Install".to_string(), 2, "install".to_string(), 4),
+            TocEntry::new("From source".to_string(), 3, "from-source".to_string(), 7),
+            TocEntry::new("Usage".to_string(), 2, "usage".to_string(), 10),
+        ];
+        let renderer = crate::renderer::HtmlRenderer::new();
+
+        let mut html = String::new();
+        super::render_page_toc_entries(
+            &renderer,
+            &TocEntry::nest(&document.toc),
+            1,
+            super::page_tocdepth(&document),
+            &std::collections::HashMap::from([("install".to_string(), vec![2, 1])]),
+            &mut html,
+        );
+        assert!(html.starts_with("<ul class=\"local-toc\" data-scrollspy=\"true\">"));
+        assert!(html.contains("<li class=\"toc-l2\"><a class=\"reference internal\" href=\"#install\" data-section=\"install\"><span class=\"section-number\">2.1. </span>Install</a>\n<ul>\n<li class=\"toc-l3\">"));
+        assert_eq!(html.matches("<ul").count(), 3);
+
+        document
+            .metadata
+            .custom
+            .insert("tocdepth".to_string(), serde_json::Value::String("2".to_string()));
+        let mut html = String::new();
+        super::render_page_toc_entries(
+            &renderer,
+            &TocEntry::nest(&document.toc),
+            1,
+            super::page_tocdepth(&document),
+            &std::collections::HashMap::new(),
+            &mut html,
+        );
+        assert!(html.contains("href=\"#usage\""));
+        assert!(!html.contains("from-source"));
+    }
+
+    #[test]
+    fn test_page_description_from_first_paragraph() {
+        let html = "<h1>Title</h1>\n<p class=\"lead\">Fast <em>Sphinx</em>\n builds &amp; more.</p><p>Second.</p>";
+        assert_eq!(
+            super::BodySummary::of(html).description(200).as_deref(),
+            Some("Fast Sphinx builds & more.")
+        );
+        assert_eq!(super::BodySummary::of(html).description(14).as_deref(), Some("Fast Sphinx..."));
+        assert_eq!(super::BodySummary::of("<h1>Only a title</h1>").description(200), None);
+
+        let tags =
+            super::render_meta_tags(&[("name", "description", "A \"quoted\" page".to_string())]);
+        assert_eq!(
+            tags,
+            "<meta name=\"description\" content=\"A &quot;quoted&quot; page\" />\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_meta_directive_tags_in_page_head() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. meta::\n   :description: The landing page\n   :keywords lang=en: docs, rust\n   :property=og:type: article\n\nFirst paragraph.\n",
+        )
+        .unwrap();
+        let builder = test_builder(dir.path());
+
+        builder.build().await.unwrap();
+        let html = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        let head = &html[..html.find("</head>").unwrap()];
+        assert!(head.contains("<meta name=\"description\" content=\"The landing page\" />"), "{}", head);
+        assert!(head.contains("<meta name=\"keywords\" lang=\"en\" content=\"docs, rust\" />"), "{}", head);
+        assert!(head.contains("<meta property=\"og:type\" content=\"article\" />"), "{}", head);
+        assert!(!head.contains("og:title"), "OpenGraph tags are opt-in: {}", head);
+        assert!(!html.contains("First paragraph.\" />"), "the directive replaces the generated description");
+        assert!(!html.contains("meta directive"));
+    }
+
+    #[tokio::test]
+    async fn test_index_entries_in_general_index() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. toctree::\n\n   guide/loops\n\n.. index:: single: iteration; protocol\n\nAbout :index:`recursion`.\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("guide")).unwrap();
+        std::fs::write(
+            dir.path().join("guide/loops.rst"),
+            "Loops\n=====\n\n.. index::\n   !pair: loop; statement\n   see: cycle; loop\n\nUse a :index:`for loop <single: loop; for>`.\n",
+        )
+        .unwrap();
+        let builder = test_builder(dir.path());
+
+        builder.build().await.unwrap();
+        let loops = std::fs::read_to_string(dir.path().join("_build/guide/loops.html")).unwrap();
+        assert!(loops.contains("<span class=\"target\" id=\"index-loop-statement\"></span>"), "{}", loops);
+        assert!(loops.contains("Use a <span class=\"target\" id=\"index-loop-for\"></span>for loop."), "{}", loops);
+
+        let index = std::fs::read_to_string(dir.path().join("_build/genindex.html")).unwrap();
+        assert!(index.contains("<h2 id=\"L\">L</h2>"), "{}", index);
+        assert!(index.contains("<a href=\"guide/loops.html#index-loop-statement\"><strong>statement</strong></a>"), "{}", index);
+        assert!(index.contains("<a href=\"guide/loops.html#index-loop-for\">for</a>"), "{}", index);
+        assert!(index.contains("<li>see loop</li>"), "{}", index);
+        assert!(index.contains("<a href=\"index.html#index-recursion\">recursion</a>"), "{}", index);
+        assert!(index.contains("<a href=\"index.html#index-iteration-protocol\">protocol</a>"), "{}", index);
+    }
+
+    #[tokio::test]
+    async fn test_permalinks_follow_config() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. figure:: plot.png\n   :name: plot\n\n   A plot.\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("plot.png"), b"png").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.html_permalinks_icon = Some("#".to_string());
+
+        builder.build().await.unwrap();
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(index.contains("title=\"Link to this heading\">#</a>"), "{}", index);
+        assert!(index.contains("<a class=\"headerlink\" href=\"#plot\" title=\"Link to this image\">#</a>"), "{}", index);
+        assert!(dir.path().join("_build/_images/plot.png").is_file());
+
+        let mut builder = test_builder(dir.path());
+        builder.config.html_permalinks = Some(false);
+        builder.build().await.unwrap();
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(!index.contains("class=\"headerlink\""), "{}", index);
+    }
+
+    #[tokio::test]
+    async fn test_custom_roles_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\nPress :kbd-seq:`Ctrl+C` in :product:`Acme <acme>`.\n",
+        )
+        .unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.custom_roles.insert(
+            "kbd-seq".to_string(),
+            crate::config::CustomRoleConfig {
+                template: Some("<kbd class=\"kbd-seq\">{text}</kbd>".to_string()),
+                ..Default::default()
+            },
+        );
+        builder.config.custom_roles.insert(
+            "product".to_string(),
+            crate::config::CustomRoleConfig {
+                element: Some("strong".to_string()),
+                classes: vec!["product".to_string(), "brand".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let stats = builder.build().await.unwrap();
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(index.contains("Press <kbd class=\"kbd-seq\">Ctrl+C</kbd> in <strong class=\"product brand\">Acme</strong>."), "{}", index);
+        assert!(
+            stats.warning_details.iter().all(|warning| warning.warning_type != crate::error::WarningType::UnknownRole),
+            "{:?}",
+            stats.warning_details
+        );
+    }
+
+    #[tokio::test]
+    async fn test_copybutton_assets() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. code-block:: console\n\n   $ ls\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.copybutton.enabled = true;
+
+        builder.build().await.unwrap();
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(index.contains("_static/copybutton.css"), "{}", index);
+        assert!(index.contains("_static/copybutton.js"), "{}", index);
+        let script = std::fs::read_to_string(dir.path().join("_build/_static/copybutton.js")).unwrap();
+        assert!(
+            script.starts_with(r#"var COPYBUTTON = {"only_copy_prompt_lines":true,"prompt_regex":">>> |\\.\\.\\. |\\$ ","selector":"div.highlight pre"};"#),
+            "{}",
+            script
+        );
+        assert!(dir.path().join("_build/_static/copybutton.css").exists());
+    }
+
+    #[tokio::test]
+    async fn test_feature_assets_only_on_pages_using_them() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   features\n").unwrap();
+        std::fs::write(
+            dir.path().join("features.rst"),
+            "Features\n========\n\n\
+             .. code-block:: python\n\n   x = 1\n\n\
+             .. math::\n\n   e = mc^2\n\n\
+             .. mermaid::\n\n   graph TD\n     A-->B\n\n\
+             .. tab-set::\n\n   .. tab-item:: One\n\n      First.\n",
+        )
+        .unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.copybutton.enabled = true;
+
+        builder.build().await.unwrap();
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        let features = std::fs::read_to_string(dir.path().join("_build/features.html")).unwrap();
+        for asset in ["copybutton.css", "copybutton.js", "design.css", "mathjax@3", "mermaid@11"] {
+            assert!(features.contains(asset), "{} missing: {}", asset, features);
+            assert!(!index.contains(asset), "{} on a page without it: {}", asset, index);
+        }
+        assert!(features.contains("<pre class=\"mermaid\">"), "{}", features);
+        assert!(features.contains("\\[e = mc^2\\]"), "{}", features);
+        assert!(features.contains("class=\"sd-tab-set docutils\""), "{}", features);
+    }
+
+    #[tokio::test]
+    async fn test_design_stylesheet_only_when_used() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   cards\n").unwrap();
+        let builder = test_builder(dir.path());
+        builder.build().await.unwrap();
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(!index.contains("_static/design.css"), "{}", index);
+        assert!(!dir.path().join("_build/_static/design.css").exists());
+
+        std::fs::write(
+            dir.path().join("cards.rst"),
+            "Cards\n=====\n\n.. grid:: 2\n\n   .. grid-item-card:: One\n\n      Text.\n",
+        )
+        .unwrap();
+        let stats = builder.build().await.unwrap();
+        assert!(stats.warning_details.is_empty(), "{:?}", stats.warning_details);
+        let cards = std::fs::read_to_string(dir.path().join("_build/cards.html")).unwrap();
+        assert!(cards.contains("_static/design.css"), "{}", cards);
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(!index.contains("_static/design.css"), "{}", index);
+        let stylesheet = std::fs::read_to_string(dir.path().join("_build/_static/design.css")).unwrap();
+        assert!(stylesheet.contains(".sd-card"));
+    }
+
+    #[tokio::test]
+    async fn test_bibtex_citations_link_to_bibliography() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("refs.bib"),
+            "@book{knuth84, author = {Donald E. Knuth}, title = {The {\\TeX}book}, publisher = {Addison-Wesley}, year = 1984}\n\
+             @article{kr88, author = {Kernighan, Brian and Ritchie, Dennis}, title = {C}, journal = {Bell}, year = 1988}\n\
+             @misc{unused, title = {Never cited}}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\nAs :cite:t:`kr88` and :cite:p:`knuth84, kr88` show, :cite:`nobody`.\n\n.. toctree::\n\n   references\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("references.rst"), "References\n==========\n\n.. bibliography::\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.bibtex_files = vec!["refs.bib".to_string()];
+        builder.config.bibtex_default_style = crate::config::BibtexStyle::Alpha;
+
+        let stats = builder.build().await.unwrap();
+        let messages: Vec<&str> = stats.warning_details.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(messages, vec!["citation key 'nobody' is not in the BibTeX files"]);
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(
+            index.contains(concat!(
+                "As Kernighan and Ritchie [<a class=\"reference internal\" href=\"references.html#bibtex-kr88\" role=\"doc-biblioref\">KR88</a>] and ",
+                "[<a class=\"reference internal\" href=\"references.html#bibtex-knuth84\" role=\"doc-biblioref\">Knu84</a>, ",
+                "<a class=\"reference internal\" href=\"references.html#bibtex-kr88\" role=\"doc-biblioref\">KR88</a>] show, [nobody]."
+            )),
+            "{}",
+            index
+        );
+        let references = std::fs::read_to_string(dir.path().join("_build/references.html")).unwrap();
+        let knuth = references.find("id=\"bibtex-knuth84\"").unwrap();
+        let kr = references.find("id=\"bibtex-kr88\"").unwrap();
+        // Sorted by author
+        assert!(kr < knuth);
+        assert!(references.contains("<p>Donald E. Knuth. <em>The TeXbook</em>. Addison-Wesley, 1984.</p>"), "{}", references);
+        assert!(!references.contains("Never cited"));
+    }
+
+    #[tokio::test]
+    async fn test_docstrings_become_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = dir.path().join("docs");
+        std::fs::create_dir_all(dir.path().join("infra")).unwrap();
+        std::fs::create_dir_all(&docs).unwrap();
+        std::fs::write(
+            dir.path().join("infra/deploy.sh"),
+            "#!/bin/sh\n### doc: Deploying\n### Run ``deploy.sh`` with the *environment*.\nset -e\n",
+        )
+        .unwrap();
+        std::fs::write(docs.join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   code/index\n").unwrap();
+        let mut builder = test_builder(&docs);
+        builder.config.docstrings.root = "..".to_string();
+        builder.config.docstrings.patterns = vec!["infra/**/*.sh".to_string()];
+
+        let stats = builder.build().await.unwrap();
+        assert!(stats.warning_details.is_empty(), "{:?}", stats.warning_details);
+        let page = std::fs::read_to_string(docs.join("_build/code/infra/deploy.sh.html")).unwrap();
+        assert!(page.contains("<section id=\"deploying\">\n<h2>Deploying"), "{}", page);
+        assert!(page.contains("with the <em>environment</em>."), "{}", page);
+        let index = std::fs::read_to_string(docs.join("_build/code/index.html")).unwrap();
+        assert!(index.contains("href=\"infra/deploy.sh.html\""), "{}", index);
+        assert!(!docs.join("code").exists());
+    }
+
+    #[tokio::test]
+    async fn test_environment_after_build() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Home\n====\n\n.. toctree::\n\n   guide\n").unwrap();
+        std::fs::write(
+            dir.path().join("guide.rst"),
+            "Guide\n=====\n\n.. _install:\n\nInstalling\n----------\n\n.. include:: snippet.inc\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("snippet.inc"), "Text.\n").unwrap();
+        let builder = test_builder(dir.path());
+        builder.build().await.unwrap();
+
+        let environment = builder.environment();
+        assert_eq!(environment.document_names(), vec!["guide", "index"]);
+        assert_eq!(environment.title("guide").map(|title| title.as_str()), Some("Guide"));
+        assert_eq!(environment.doc2path("guide"), dir.path().join("guide.rst"));
+        assert_eq!(environment.toctree_children("index"), &[crate::intern::Symbol::from("guide")]);
+        assert_eq!(environment.document_sections("guide")[0].title.as_str(), "Installing");
+        let label = environment.resolve_label("Install").unwrap();
+        assert_eq!((label.docname.as_str(), label.title.as_deref()), ("guide", Some("Installing")));
+        assert!(environment.resolve_label("genindex").is_some());
+        assert_eq!(environment.dependencies["guide"].len(), 1);
+        let labels = environment.domains["std"].get_objects_by_type("label").unwrap();
+        assert!(labels.iter().any(|object| object.name == "install" && object.docname == "guide"));
+        assert_eq!(
+            environment.collect_relations()["guide"],
+            (Some("index".to_string()), Some("index".to_string()), None)
+        );
+        assert!(environment.css_files.iter().any(|file| file.ends_with(".css")));
+    }
+
+    #[tokio::test]
+    async fn test_document_transforms() {
+        /// Drops the paragraphs starting with "TODO"
+        struct DropTodos;
+
+        impl crate::transforms::DocumentTransform for DropTodos {
+            fn apply(
+                &self,
+                _context: &mut crate::transforms::TransformContext,
+                nodes: &mut Vec<crate::document::RstNode>,
+            ) -> anyhow::Result<()> {
+                nodes.retain(|node| {
+                    !matches!(node, crate::document::RstNode::Paragraph { content, .. } if content.starts_with("TODO"))
+                });
+                Ok(())
+            }
+
+            fn get_name(&self) -> &str {
+                "drop-todos"
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. only:: html and internal\n\n   Internal\n   --------\n\n   TODO: write this.\n\n.. only:: latex\n\n   Printed.\n\n.. only:: html and\n\n   Kept.\n",
+        )
+        .unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.tags = vec!["internal".to_string()];
+        builder.transforms = crate::transforms::TransformRegistry::from_config(&builder.config);
+        builder.add_transform(Box::new(DropTodos));
+        let stats = builder.build().await.unwrap();
+
+        let html = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(html.contains("Internal"));
+        assert!(!html.contains("TODO"));
+        assert!(!html.contains("Printed."));
+        assert!(html.contains("Kept."));
+        assert_eq!(builder.environment().document_sections("index")[0].title.as_str(), "Internal");
+        let warning = stats
+            .warning_details
+            .iter()
+            .find(|warning| warning.message.contains("invalid only expression"))
+            .unwrap();
+        assert_eq!(warning.line, Some(15));
+    }
+
+    #[tokio::test]
+    async fn test_numbered_figures_and_lists() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. toctree::\n\n   second\n   first\n\n.. figlist::\n   :caption: Figures\n\n.. tablelist::\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("first.rst"),
+            "First\n=====\n\n.. figure:: a.png\n\n   A *plot*.\n\n.. list-table:: Sizes\n   :name: sizes\n\n   * - a\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("second.rst"),
+            "Second\n======\n\n.. figure:: b.png\n   :name: overview\n\n   Overview.\n\n.. figure:: c.png\n\n.. code-block:: python\n   :caption: Example\n\n   x = 1\n",
+        )
+        .unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.numfig = true;
+        builder.config.numfig_format.table = "Tab. %s:".to_string();
+        builder.transforms = crate::transforms::TransformRegistry::from_config(&builder.config);
+        builder.build().await.unwrap();
+
+        let second = std::fs::read_to_string(dir.path().join("_build/second.html")).unwrap();
+        assert!(second.contains("<figure class=\"align-default\" id=\"overview\">"));
+        assert!(second.contains("<p><span class=\"caption-number\">Fig. 1 </span><span class=\"caption-text\">Overview.</span>"));
+        assert!(second.contains("<div class=\"code-block-caption\"><span class=\"caption-number\">Listing 1 </span><span class=\"caption-text\">Example</span>"));
+        let first = std::fs::read_to_string(dir.path().join("_build/first.html")).unwrap();
+        assert!(first.contains("<figure class=\"align-default\" id=\"figure-1\">"));
+        assert!(first.contains("<span class=\"caption-number\">Fig. 2 </span><span class=\"caption-text\">A <em>plot</em>.</span>"));
+        assert!(first.contains("<caption><span class=\"caption-number\">Tab. 1: </span><span class=\"caption-text\">Sizes</span>"));
+
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(index.contains(
+            "<div class=\"figlist\">\n<p class=\"caption\"><span class=\"caption-text\">Figures</span></p>\n<ul>\n\
+             <li><a class=\"reference internal\" href=\"second.html#overview\"><span class=\"caption-number\">Fig. 1 </span><span class=\"caption-text\">Overview.</span></a></li>\n\
+             <li><a class=\"reference internal\" href=\"first.html#figure-1\"><span class=\"caption-number\">Fig. 2 </span><span class=\"caption-text\">A <em>plot</em>.</span></a></li>\n</ul>\n</div>"
+        ), "got: {}", index);
+        assert!(index.contains("href=\"first.html#sizes\"><span class=\"caption-number\">Tab. 1: </span>"));
+    }
+
+    #[tokio::test]
+    async fn test_page_helpers_in_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   guide/intro\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("guide")).unwrap();
+        std::fs::write(dir.path().join("guide/intro.rst"), "Intro\n=====\n\nText.\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("_templates")).unwrap();
+        std::fs::write(
+            dir.path().join("_templates/extra.html"),
+            "{{ pathto(root_doc) }}|{{ pathto('style.css', resource=true) }}|{{ hasdoc('guide/intro') }}|\
+             {{ hasdoc('genindex') }}|{{ hasdoc('missing') }}|{% for link in rellinks %}{{ pathto(link[0]) }}{% endfor %}",
+        )
+        .unwrap();
+        let mut config = crate::config::BuildConfig::default();
+        config.theme.theme_paths = vec![std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("themes")];
+        config.templates_path = vec![std::path::PathBuf::from("_templates")];
+        config.html_additional_pages.insert("sub/extra".to_string(), "extra.html".to_string());
+        let builder = super::SphinxBuilder::new(config, dir.path().to_path_buf(), dir.path().join("_build")).unwrap();
+        builder.build().await.unwrap();
+
+        let extra = std::fs::read_to_string(dir.path().join("_build/sub/extra.html")).unwrap();
+        assert_eq!(extra, "../index.html|../_static/style.css|True|True|False|../genindex.html");
+
+        let intro = std::fs::read_to_string(dir.path().join("_build/guide/intro.html")).unwrap();
+        assert!(intro.contains("href=\"../genindex.html\""), "got: {}", intro);
+        assert!(!intro.contains("href=\"_static/"), "got: {}", intro);
+    }
+
+    #[tokio::test]
+    async fn test_templates_reload_and_report_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\nText.\n\n.. toctree::\n\n   guide/intro\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("guide")).unwrap();
+        std::fs::write(
+            dir.path().join("guide/intro.rst"),
+            "Intro\n=====\n\nSetup\n-----\n\nText.\n\nUsage\n-----\n\nMore.\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("_templates")).unwrap();
+        let layout = dir.path().join("_templates/layout.html");
+        let write_layout = |source: &str, age: u64| {
+            std::fs::write(&layout, source).unwrap();
+            let modified = std::time::SystemTime::now() + std::time::Duration::from_secs(age);
+            std::fs::File::options().write(true).open(&layout).unwrap().set_modified(modified).unwrap();
+        };
+        write_layout("<p>first {{ title }}</p>", 0);
+        let mut builder = test_builder(dir.path());
+        builder.config.templates_path = vec![std::path::PathBuf::from("_templates")];
+        // Pages other than documents fail the build on template errors
+        builder.config.html_use_index = Some(false);
+        builder.config.not_found.enabled = false;
+        let builder = super::SphinxBuilder::new(builder.config.clone(), dir.path().to_path_buf(), dir.path().join("_build")).unwrap();
+        let index = || std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+
+        let template_warnings = |stats: &super::BuildStats| -> Vec<crate::error::BuildWarning> {
+            stats
+                .warning_details
+                .iter()
+                .filter(|w| w.warning_type == crate::error::WarningType::TemplateRender)
+                .cloned()
+                .collect()
+        };
+
+        builder.build().await.unwrap();
+        assert_eq!(index(), "<p>first Index</p>");
+
+        write_layout("<p>second</p>\n<p>{{ missing.attribute }}</p>", 10);
+        let stats = builder.build().await.unwrap();
+        assert!(index().contains("Text."), "should fall back, got: {}", index());
+        // Reported once for both pages
+        let warnings = template_warnings(&stats);
+        assert_eq!(warnings.len(), 1, "got: {:?}", warnings);
+        assert_eq!(warnings[0].file, layout);
+        assert_eq!(warnings[0].line, Some(2));
+        assert!(warnings[0].message.contains("undefined value"), "got: {}", warnings[0].message);
+        assert!(warnings[0].message.contains("(in `missing.attribute`)"), "got: {}", warnings[0].message);
+
+        // The fallback page keeps the navigation and the theme's assets
+        let intro = std::fs::read_to_string(dir.path().join("_build/guide/intro.html")).unwrap();
+        assert!(intro.contains("<title>Intro &#8212;"), "got: {}", intro);
+        assert!(intro.contains("href=\"../_static/"), "got: {}", intro);
+        assert!(intro.contains("<div id=\"searchbox\"></div>"), "got: {}", intro);
+        assert!(intro.contains("class=\"toctree-l1 current"), "got: {}", intro);
+        assert!(intro.contains("class=\"page-toc\"") && intro.contains("href=\"#setup\""), "got: {}", intro);
+        assert!(intro.contains("rel=\"prev\" href=\"../index.html\">Previous: Index</a>"), "got: {}", intro);
+
+        write_layout("<p>third {{ title }}</p>", 20);
+        let stats = builder.build().await.unwrap();
+        assert_eq!(index(), "<p>third Index</p>");
+        assert!(template_warnings(&stats).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_extra_path_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   guide\n").unwrap();
+        std::fs::write(dir.path().join("guide.rst"), "Guide\n=====\n\nText.\n").unwrap();
+        for extra in ["extra", "more"] {
+            std::fs::create_dir_all(dir.path().join(extra)).unwrap();
+            std::fs::write(dir.path().join(extra).join("robots.txt"), extra).unwrap();
+        }
+        std::fs::write(dir.path().join("extra/guide.html"), "static guide").unwrap();
+        std::fs::write(dir.path().join("more/genindex.html"), "static index").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.html_extra_path = vec!["extra".into(), "more".into()];
+        let stats = builder.build().await.unwrap();
+        assert_eq!(stats.outcome(), BuildOutcome::SuccessWithWarnings);
+
+        let mut conflicts: Vec<String> = stats
+            .warning_details
+            .iter()
+            .filter(|w| w.warning_type == crate::error::WarningType::ExtraPathConflict)
+            .map(|w| format!("{}: {}", w.file.strip_prefix(dir.path()).unwrap().display(), w.message))
+            .collect();
+        conflicts.sort();
+        assert_eq!(
+            conflicts,
+            [
+                format!(
+                    "extra/guide.html: html_extra_path file replaces 'guide.html', generated from {}",
+                    dir.path().join("guide.rst").display()
+                ),
+                "more/genindex.html: html_extra_path file replaces 'genindex.html', a generated page"
+                    .to_string(),
+                format!(
+                    "more/robots.txt: html_extra_path file replaces 'robots.txt', also copied from {}",
+                    dir.path().join("extra/robots.txt").display()
+                ),
+            ]
+        );
+        // The last one copied still wins
+        assert_eq!(std::fs::read_to_string(dir.path().join("_build/robots.txt")).unwrap(), "more");
+        assert_eq!(std::fs::read_to_string(dir.path().join("_build/guide.html")).unwrap(), "static guide");
+
+        builder.config.html_extra_path_conflicts = crate::config::ExtraPathConflicts::Error;
+        let result = builder.build().await;
+        assert_eq!(
+            result.as_ref().map_or_else(BuildOutcome::failed, super::BuildStats::outcome),
+            BuildOutcome::Failed {
+                phase: "extra paths".to_string()
+            }
+        );
+        let err = result.unwrap_err();
+        assert!(format!("{:#}", err).contains("more/robots.txt: html_extra_path file replaces"), "got: {:#}", err);
+
+        builder.config.html_extra_path.clear();
+        for extra in ["extra", "more"] {
+            std::fs::remove_dir_all(dir.path().join(extra)).unwrap();
+        }
+        let result = builder.build().await;
+        assert_eq!(
+            result.as_ref().map_or_else(BuildOutcome::failed, super::BuildStats::outcome),
+            BuildOutcome::Success
+        );
+        assert_eq!(result.unwrap().to_json()["outcome"], serde_json::json!({"status": "success"}));
+    }
+
+    #[tokio::test]
+    async fn test_stale_outputs_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   old/page\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("old")).unwrap();
+        std::fs::write(dir.path().join("old/page.rst"), "Old\n===\n\nText.\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.output.compress_output = true;
+        builder.build().await.unwrap();
+        let output = dir.path().join("_build");
+        assert!(output.join("old/page.html.gz").exists());
+        std::fs::write(output.join("old/notes.txt"), "not ours").unwrap();
+
+        std::fs::remove_file(dir.path().join("old/page.rst")).unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n").unwrap();
+        builder.config.output.keep_stale = true;
+        let stats = builder.build().await.unwrap();
+        assert_eq!(stats.stale_outputs_removed, 0);
+        assert!(output.join("old/page.html").exists());
+
+        builder.config.output.keep_stale = false;
+        let stats = builder.build().await.unwrap();
+        // The page, its source copy and their .gz and .br siblings
+        assert_eq!(stats.stale_outputs_removed, 6);
+        assert!(!output.join("old/page.html").exists());
+        assert!(!output.join("old/page.html.br").exists());
+        assert!(!output.join("_sources/old").exists());
+        assert!(output.join("old/notes.txt").exists());
+        assert!(output.join("index.html").exists());
+
+        // A page replaced by a file of html_extra_path keeps the copy
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   old/page\n").unwrap();
+        std::fs::write(dir.path().join("old/page.rst"), "Old\n===\n\nText.\n").unwrap();
+        builder.build().await.unwrap();
+        std::fs::remove_file(dir.path().join("old/page.rst")).unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("extra/old")).unwrap();
+        std::fs::write(dir.path().join("extra/old/page.html"), "moved").unwrap();
+        builder.config.html_extra_path = vec!["extra".into()];
+        builder.build().await.unwrap();
+        assert_eq!(std::fs::read_to_string(output.join("old/page.html")).unwrap(), "moved");
+        assert!(!output.join("_sources/old").exists());
+    }
+
+    #[tokio::test]
+    async fn test_source_dir_given_as_non_canonical_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = dir.path().join("docs");
+        std::fs::create_dir_all(docs.join("guide/img")).unwrap();
+        std::fs::write(docs.join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   guide/intro\n").unwrap();
+        std::fs::write(docs.join("guide/intro.rst"), "Intro\n=====\n\n.. image:: img/plot.png\n").unwrap();
+        std::fs::write(docs.join("guide/img/plot.png"), b"png").unwrap();
+
+        // Discovery yields canonical paths, which must still be found inside
+        // a source directory given with `..`
+        let source_dir = docs.join("guide").join("..");
+        let builder = super::SphinxBuilder::new(test_builder(&docs).config.clone(), source_dir, docs.join("_build")).unwrap();
+        let stats = builder.build().await.unwrap();
+        assert!(stats.error_details.is_empty(), "got: {:?}", stats.error_details);
+
+        let intro = std::fs::read_to_string(docs.join("_build/guide/intro.html")).unwrap();
+        assert!(intro.contains("src=\"../_images/guide/img/plot.png\""), "got: {}", intro);
+        assert!(intro.contains("href=\"../_sources/guide/intro.rst.txt\""), "got: {}", intro);
+        assert!(docs.join("_build/_images/guide/img/plot.png").is_file());
+        assert!(docs.join("_build/_sources/guide/intro.rst.txt").is_file());
+        let index = std::fs::read_to_string(docs.join("_build/index.html")).unwrap();
+        assert!(index.contains("href=\"guide/intro.html\""), "got: {}", index);
+    }
+
+    #[tokio::test]
+    async fn test_assets_by_priority_and_per_page() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   chart\n").unwrap();
+        std::fs::write(dir.path().join("chart.rst"), "Chart\n=====\n\n.. chart::\n\n   Data.\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.html_css_files = vec!["custom.css".to_string()];
+        builder.config.custom_directives.insert(
+            "chart".to_string(),
+            crate::config::CustomDirectiveConfig {
+                css_files: vec!["chart.css".to_string()],
+                js_files: vec!["chart.js".to_string()],
+                ..Default::default()
+            },
+        );
+        builder.add_css_file(crate::assets::Asset::new("early.css").with_priority(100));
+        builder.add_css_file(crate::assets::Asset::new("ext.css"));
+        builder.add_js_file(crate::assets::Asset::new("ext.js").module().with_attribute("defer", "defer"));
+        builder.build().await.unwrap();
+
+        let links = |page: &str| -> Vec<String> {
+            let html = std::fs::read_to_string(dir.path().join("_build").join(page)).unwrap();
+            html.lines()
+                .map(str::trim)
+                .filter(|line| line.starts_with("<link rel=\"stylesheet\"") || line.starts_with("<script"))
+                .map(str::to_string)
+                .collect()
+        };
+        assert_eq!(
+            links("index.html"),
+            vec![
+                "<link rel=\"stylesheet\" href=\"_static/early.css\" type=\"text/css\" />",
+                "<link rel=\"stylesheet\" href=\"_static/css/theme.css\" type=\"text/css\" />",
+                "<link rel=\"stylesheet\" href=\"_static/ext.css\" type=\"text/css\" />",
+                "<link rel=\"stylesheet\" href=\"_static/custom.css\" type=\"text/css\" />",
+                "<script src=\"_static/ext.js\" type=\"module\" defer=\"defer\"></script>",
+            ]
+        );
+        let chart = links("chart.html");
+        assert_eq!(chart[3], "<link rel=\"stylesheet\" href=\"_static/chart.css\" type=\"text/css\" />");
+        assert_eq!(chart[4], "<link rel=\"stylesheet\" href=\"_static/custom.css\" type=\"text/css\" />");
+        assert_eq!(chart[6], "<script src=\"_static/chart.js\"></script>");
+    }
+
+    #[tokio::test]
+    async fn test_issue_tracker_roles() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\nFixed :issue:`12, other/lib#3` in :pr:`34` (:commit:`a1b2c3d4e5f6`), thanks :user:`jdoe`.\n",
+        )
+        .unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.issue_tracker.project = Some("owner/repo".to_string());
+
+        let stats = builder.build().await.unwrap();
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(
+            index.contains(concat!(
+                "Fixed <a class=\"reference external\" href=\"https://github.com/owner/repo/issues/12\">#12</a>, ",
+                "<a class=\"reference external\" href=\"https://github.com/other/lib/issues/3\">other/lib#3</a> in ",
+                "<a class=\"reference external\" href=\"https://github.com/owner/repo/pull/34\">#34</a> ",
+                "(<a class=\"reference external\" href=\"https://github.com/owner/repo/commit/a1b2c3d4e5f6\">a1b2c3d</a>), ",
+                "thanks <a class=\"reference external\" href=\"https://github.com/jdoe\">@jdoe</a>."
+            )),
+            "{}",
+            index
+        );
+        assert!(
+            stats.warning_details.iter().all(|warning| warning.warning_type != crate::error::WarningType::UnknownRole),
+            "{:?}",
+            stats.warning_details
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_directives_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. my-note:: Heads up\n   :audience: admins\n   :class: wide\n\n   Read **this**.\n\n   And that.\n\n.. my-note::\n\n   No audience.\n",
+        )
+        .unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.custom_directives.insert(
+            "my-note".to_string(),
+            crate::config::CustomDirectiveConfig {
+                classes: vec!["admonition".to_string(), "my-note".to_string()],
+                title: crate::config::CustomDirectiveTitle::Optional,
+                default_title: Some("Note".to_string()),
+                required_options: vec!["audience".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let stats = builder.build().await.unwrap();
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(
+            index.contains("<div class=\"admonition my-note wide\"><p class=\"admonition-title\">Heads up</p><p>Read <strong>this</strong>.</p>\n<p>And that.</p></div>"),
+            "{}",
+            index
+        );
+        let messages: Vec<&str> = stats.warning_details.iter().map(|warning| warning.message.as_str()).collect();
+        assert!(messages.contains(&"my-note: missing required option(s): audience"), "{:?}", messages);
+        assert!(!messages.iter().any(|message| message.contains("Unknown directive")), "{:?}", messages);
+    }
+
+    #[tokio::test]
+    async fn test_directive_validator_warnings_reach_the_build() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. note::\n   :bogus: yes\n\n   Body.\n\n.. warning:: Careful here.\n\n.. note:: This is a note.\n\n.. notte::\n\n   Typo.\n",
+        )
+        .unwrap();
+        let stats = test_builder(dir.path()).build().await.unwrap();
+        let warnings: Vec<(Option<usize>, &str)> = stats
+            .warning_details
+            .iter()
+            .map(|warning| (warning.line, warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (Some(4), "\"note\" directive: Unknown option 'bogus' for note directive"),
+                (Some(13), "Unknown directive type \"notte\"."),
+            ]
+        );
+        assert_eq!(stats.warning_details[1].suggestions, ["Did you mean 'note'?"]);
+    }
+
+    #[tokio::test]
+    async fn test_not_found_page_is_opt_in() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n").unwrap();
+        test_builder(dir.path()).build().await.unwrap();
+        assert!(!dir.path().join("_build/404.html").exists());
+
+        let mut builder = test_builder(dir.path());
+        builder.config.not_found.enabled = true;
+        builder.build().await.unwrap();
+        let page = std::fs::read_to_string(dir.path().join("_build/404.html")).unwrap();
+        assert!(page.contains("Page not found"), "{}", page);
+    }
+
+    #[test]
+    fn test_absolutize_urls_for_not_found_page() {
+        let html = r##"<link href="_static/theme.css" /><a href="guide/intro.html">Intro</a><a href="#top">Top</a><a href="https://example.org/">Out</a><img src="/logo.png" /><a href="mailto:docs@example.org">Mail</a>"##;
+        let rewritten = super::absolutize_urls(html, "/en/latest/");
+        assert!(rewritten.contains(r#"href="/en/latest/_static/theme.css""#));
+        assert!(rewritten.contains(r#"href="/en/latest/guide/intro.html""#));
+        assert!(rewritten.contains(r##"href="#top""##));
+        assert!(rewritten.contains(r#"href="https://example.org/""#));
+        assert!(rewritten.contains(r#"src="/logo.png""#));
+        assert!(rewritten.contains(r#"href="mailto:docs@example.org""#));
+        assert_eq!(super::absolutize_urls(html, ""), html);
+    }
+
+    #[test]
+    fn test_github_pages_cname() {
+        let mut config = crate::config::BuildConfig::default();
+        assert_eq!(super::github_pages_cname(&config), None);
+
+        config.html_baseurl = Some("https://docs.example.org:8080/en/latest/".to_string());
+        assert_eq!(super::github_pages_cname(&config).as_deref(), Some("docs.example.org"));
+
+        config.html_baseurl = Some("https://example.github.io/project/".to_string());
+        assert_eq!(super::github_pages_cname(&config), None);
+
+        config.github_pages.cname = Some("docs.example.com".to_string());
+        assert_eq!(super::github_pages_cname(&config).as_deref(), Some("docs.example.com"));
+    }
+
+    #[test]
+    fn test_reading_time() {
+        let html = "<h1>Title</h1>\n<p>Some <em>emphasized</em> words &amp; more.</p>\n<pre>let x = 1;</pre>";
+        assert_eq!(super::BodySummary::of(html).words, 8);
+        assert_eq!(super::reading_minutes(0, 200), 0);
+        assert_eq!(super::reading_minutes(8, 200), 1);
+        assert_eq!(super::reading_minutes(401, 200), 3);
+    }
+
+    #[test]
+    fn test_body_scanner_gathers_from_any_chunking() {
+        use std::io::Write;
+
+        let html = "<section id=\"intro\"><h1>Intro</h1><!-- a > b -->\n<p class=\"lead\">Caf\u{e9} <a href=\"../_images/x.png\">na\u{ef}ve</a> &amp; more</p>\n<pre>code</pre><p>Later</p></section>";
+        let mut scanner = super::BodyScanner::new(Vec::new(), None, Some(crate::manifest::ContentRefs::default()));
+        for byte in html.as_bytes() {
+            scanner.write_all(std::slice::from_ref(byte)).unwrap();
+        }
+        scanner.finish();
+
+        assert_eq!(String::from_utf8(scanner.out).unwrap(), html);
+        assert_eq!(scanner.summary, super::BodySummary::of(html));
+        assert_eq!(scanner.summary.words, 6);
+        assert!(scanner.summary.has_code);
+        assert_eq!(
+            scanner.summary.first_paragraph.as_deref(),
+            Some("Caf\u{e9} <a href=\"../_images/x.png\">na\u{ef}ve</a> &amp; more")
+        );
+        assert_eq!(scanner.refs, Some(crate::manifest::ContentRefs::from_html(html)));
+    }
+
+    #[test]
+    fn test_similar_names() {
+        let candidates = ["code-block", "note", "toctree"];
+        assert_eq!(
+            super::similar_names("code", &candidates),
+            vec!["Did you mean 'code-block'?".to_string()]
+        );
+        assert!(super::similar_names("figure", &candidates).is_empty());
+    }
+
+    fn test_builder(source_dir: &std::path::Path) -> super::SphinxBuilder {
+        let mut config = crate::config::BuildConfig::default();
+        config.theme.theme_paths = vec![std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("themes")];
+        super::SphinxBuilder::new(config, source_dir.to_path_buf(), source_dir.join("_build")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_spelling_extension_builds_without_a_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("conf.py"),
+            "project = 'Test'\nextensions = ['sphinxcontrib.spelling']\nspelling_lang = 'xx_NOWHERE'\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\nSome txet.\n").unwrap();
+        let mut parser = crate::python_config::PythonConfigParser::new().unwrap();
+        let conf = parser.parse_conf_py(dir.path().join("conf.py")).unwrap();
+        let mut config = conf.to_build_config();
+        config.theme.theme_paths = vec![std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("themes")];
+
+        // Listing the extension alone doesn't turn the spell checker on
+        let builder = super::SphinxBuilder::new(config.clone(), dir.path().to_path_buf(), dir.path().join("_build")).unwrap();
+        let stats = builder.build().await.unwrap();
+        assert_eq!(stats.warnings, 0, "{:?}", stats.warning_details);
+
+        // Enabled without a dictionary, it is skipped with a warning
+        config.spelling.enabled = true;
+        let builder = super::SphinxBuilder::new(config, dir.path().to_path_buf(), dir.path().join("_build")).unwrap();
+        let stats = builder.build().await.unwrap();
+        assert_eq!(stats.warnings, 1, "{:?}", stats.warning_details);
+        let warning = &stats.warning_details[0];
+        assert_eq!(warning.warning_type, crate::error::WarningType::Spelling);
+        assert!(warning.message.contains("No hunspell dictionary found for 'xx_NOWHERE'"), "{}", warning.message);
+        assert!(dir.path().join("_build/index.html").is_file());
+    }
+
+    #[tokio::test]
+    async fn test_lint_reports_without_writing_output() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. toctree::\n\n   guide\n\n.. _top:\n\nSee :ref:`top`, :doc:`guide` and :doc:`missing`.\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("guide.rst"), "Guide\n=====\n\nBack to :ref:`nowhere`.\n").unwrap();
+
+        let report = test_builder(dir.path()).lint().await.unwrap();
+        assert_eq!(report.documents, 2);
+        assert!(report.errors.is_empty());
+        let messages: Vec<(String, Option<usize>, &str)> = report
+            .warnings
+            .iter()
+            .map(|warning| {
+                let file = warning.file.file_name().unwrap().to_string_lossy().into_owned();
+                (file, warning.line, warning.message.as_str())
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                ("guide.rst".to_string(), Some(4), "cross-reference target not found: ':ref:`nowhere`'"),
+                ("index.rst".to_string(), Some(10), "cross-reference target not found: ':doc:`missing`'"),
+            ]
+        );
+        assert!(!report.is_clean());
+        assert_eq!(report.to_json()["diagnostics"][0]["category"], "broken-cross-reference");
+        assert!(!dir.path().join("_build").exists());
+    }
+
+    #[tokio::test]
+    async fn test_lint_suggests_similar_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            ".. _installation-guide:\n\nIndex\n=====\n\n.. toctree::\n\n   guide\n\n\
+             See :ref:`instalation-guide` and :doc:`guid`.\n\n.. code-blok:: rust\n\n   fn main() {}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("guide.rst"), "Guide\n=====\n").unwrap();
+
+        let report = test_builder(dir.path()).lint().await.unwrap();
+        let suggestions: Vec<&[String]> = report
+            .warnings
+            .iter()
+            .map(|warning| warning.suggestions.as_slice())
+            .collect();
+        assert_eq!(
+            suggestions,
+            [
+                &["Did you mean 'installation-guide'?".to_string()][..],
+                &["Did you mean 'guide'?".to_string()][..],
+                &["Did you mean 'code-block'?".to_string()][..],
+            ]
+        );
+    }
+
+    #[derive(Default)]
+    struct EventLog(parking_lot::Mutex<Vec<String>>);
+
+    impl crate::observer::BuildObserver for EventLog {
+        fn build_started(&self) {
+            self.0.lock().push("started".to_string());
+        }
+
+        fn document_written(&self, doc_path: &str, _source: &std::path::Path, output: &std::path::Path) {
+            assert!(output.exists());
+            self.0.lock().push(format!("written {}", doc_path));
+        }
+
+        fn build_finished(&self, result: Result<&super::BuildStats, &anyhow::Error>) {
+            let warnings = result.unwrap().warnings;
+            self.0.lock().push(format!("finished with {} warnings", warnings));
+        }
+    }
+
+    /// Cancels the build once the first page is written
+    struct CancelAfterFirstPage(crate::cancellation::CancellationToken);
+
+    impl crate::observer::BuildObserver for CancelAfterFirstPage {
+        fn document_written(&self, _doc_path: &str, _source: &std::path::Path, _output: &std::path::Path) {
+            self.0.cancel();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_toctree_glob_skips_listed_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. toctree::\n   :glob:\n\n   Start here <guide>\n   *\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("guide.rst"), "Guide\n=====\n").unwrap();
+        std::fs::write(dir.path().join("usage.rst"), "Usage\n=====\n").unwrap();
+        let builder = test_builder(dir.path());
+        builder.build().await.unwrap();
+
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        let body = &index[index.find("toctree-wrapper").unwrap()..];
+        let body = &body[..body.find("</div>").unwrap()];
+        assert_eq!(body.matches("href=\"guide.html\"").count(), 1, "{}", body);
+        assert_eq!(body.matches("href=\"usage.html\"").count(), 1, "{}", body);
+
+        // The sidebar lists it once too
+        let usage = std::fs::read_to_string(dir.path().join("_build/usage.html")).unwrap();
+        let entry = "toctree-l1\"><a class=\"reference internal\" href=\"guide.html\"";
+        assert_eq!(usage.matches(entry).count(), 1, "{}", usage);
+    }
+
+    #[tokio::test]
+    async fn test_numbered_toctree_numbers_document_headings() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. toctree::\n   :numbered:\n\n   guide\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("guide.rst"), "Guide\n=====\n\nInstall\n-------\n\nText.\n").unwrap();
+        let builder = test_builder(dir.path());
+        builder.build().await.unwrap();
+
+        let guide = std::fs::read_to_string(dir.path().join("_build/guide.html")).unwrap();
+        assert!(guide.contains("<span class=\"section-number\">1. </span>Guide"), "{}", guide);
+        assert!(guide.contains("<span class=\"section-number\">1.1. </span>Install"), "{}", guide);
+        let index = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(!index.contains("section-number\">1. </span>Index"), "{}", index);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_build_stops_between_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n   :glob:\n\n   *\n").unwrap();
+        for index in 0..20 {
+            std::fs::write(dir.path().join(format!("page{}.rst", index)), "Page\n====\n").unwrap();
+        }
+        let mut builder = test_builder(dir.path());
+        builder.set_parallel_jobs(1);
+        builder.enable_incremental();
+        builder.add_observer(std::sync::Arc::new(CancelAfterFirstPage(builder.cancellation_token())));
+
+        let error = builder.build().await.unwrap_err();
+        match error.downcast_ref::<crate::error::BuildError>() {
+            Some(crate::error::BuildError::Cancelled { documents_written }) => assert_eq!(*documents_written, 1),
+            _ => panic!("not cancelled: {:#}", error),
+        }
+        let pages = std::fs::read_dir(dir.path().join("_build"))
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "html"))
+            .count();
+        assert_eq!(pages, 1);
+
+        // A fresh token builds everything again
+        builder.set_cancellation_token(crate::cancellation::CancellationToken::new());
+        builder.observers.clear();
+        let stats = builder.build().await.unwrap();
+        assert_eq!(stats.files_processed, 21);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_builds_with_memory_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   guide\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.set_parallel_jobs(1);
+        let events = std::sync::Arc::new(EventLog::default());
+        builder.add_observer(events.clone());
+        builder.set_source("guide.rst", "Guide\n=====\n\nFirst :doc:`missing`.\n");
+        builder.set_source("orphan.rst", "Orphan\n======\n");
+
+        builder.build().await.unwrap();
+        let guide = dir.path().join("_build/guide.html");
+        assert!(std::fs::read_to_string(&guide).unwrap().contains("First"));
+        assert!(dir.path().join("_build/orphan.html").exists());
+
+        builder.set_source("guide.rst", "Guide\n=====\n\nSecond.\n");
+        builder.remove_source("orphan.rst");
+        let stats = builder.build().await.unwrap();
+        assert!(std::fs::read_to_string(&guide).unwrap().contains("Second"));
+        assert_eq!(stats.files_processed, 2);
+
+        let mut events = events.0.lock().clone();
+        events[1..4].sort();
+        events[5..7].sort();
+        assert_eq!(
+            events,
+            [
+                "started",
+                "written guide",
+                "written index",
+                "written orphan",
+                "finished with 1 warnings",
+                "started",
+                "written guide",
+                "written index",
+                "finished with 0 warnings",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manifest_lists_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("guide")).unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   guide/usage\n").unwrap();
+        std::fs::write(
+            dir.path().join("guide/usage.rst"),
+            "Usage\n=====\n\n.. codeauthor:: Ravi Rao\n\nInstall\n-------\n\nGet :download:`the script <example.py>`.\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("guide/example.py"), "print()\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.output.manifest = true;
+
+        builder.build().await.unwrap();
+        let manifest = crate::manifest::Manifest::read(&dir.path().join("_build")).unwrap();
+        let pages: Vec<(&str, &str, &str)> = manifest
+            .pages
+            .iter()
+            .map(|page| (page.page.as_str(), page.title.as_str(), page.source.as_str()))
+            .collect();
+        assert_eq!(
+            pages,
+            [("guide/usage.html", "Usage", "guide/usage.rst"), ("index.html", "Index", "index.rst")]
+        );
+        let usage = &manifest.pages[0];
+        assert!(usage.anchors.contains(&"install".to_string()));
+        assert_eq!(usage.assets, ["guide/example.py"]);
+        assert_eq!(usage.code_authors, ["Ravi Rao"]);
+        assert!(manifest.pages[1].code_authors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_stubs_and_map_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("guide")).unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   guide/install\n").unwrap();
+        std::fs::write(dir.path().join("guide/install.rst"), "Install\n=======\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.html_baseurl = Some("https://example.org/docs/".to_string());
+        let redirects = &mut builder.config.redirects;
+        for (from, to) in [
+            ("old/setup.html", "guide/install#linux"),
+            ("guide/install", "index"),
+            ("faq", "guide/faq"),
+            ("../escaped", "index"),
+        ] {
+            redirects.pages.insert(from.to_string(), to.to_string());
+        }
+        redirects.map_files = vec![crate::redirects::RedirectMapFormat::Netlify];
+
+        let stats = builder.build().await.unwrap();
+        let output = dir.path().join("_build");
+        let stub = std::fs::read_to_string(output.join("old/setup.html")).unwrap();
+        assert!(stub.contains(r#"content="0; url=../guide/install.html#linux""#));
+        assert!(stub.contains(r#"href="https://example.org/docs/guide/install.html#linux""#));
+        assert!(std::fs::read_to_string(output.join("guide/install.html")).unwrap().contains("<h1"));
+        assert!(output.join("faq.html").exists());
+        assert!(!dir.path().join("escaped.html").exists());
+        assert_eq!(
+            std::fs::read_to_string(output.join("_redirects")).unwrap(),
+            "/docs/faq.html /docs/guide/faq.html 301\n/docs/old/setup.html /docs/guide/install.html#linux 301\n"
+        );
+        let mut messages: Vec<&str> = stats
+            .warning_details
+            .iter()
+            .map(|warning| warning.message.as_str())
+            .collect();
+        messages.sort();
+        assert_eq!(
+            messages,
+            [
+                "redirect from '../escaped' is not a page path below the output directory, skipped",
+                "redirect from 'faq' points to nonexisting document 'guide/faq'",
+                "redirect from 'guide/install' skipped, the document still exists",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builds_of_the_same_sources_are_identical() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("guide")).unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. toctree::\n\n   guide/install\n   guide/usage\n\n.. index:: setup, install\n\nSee :ref:`steps`.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("guide/install.rst"),
+            "Install\n=======\n\n.. _steps:\n\nSteps\n-----\n\n.. code-block:: python\n\n   import x\n\n.. glossary::\n\n   wheel\n      A built package.\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("guide/usage.md"), "# Usage\n\nRun it, see {term}`wheel`.\n").unwrap();
+
+        let output = dir.path().join("_build");
+        let tree = || -> std::collections::BTreeMap<String, Vec<u8>> {
+            walkdir::WalkDir::new(&output)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| {
+                    let name = crate::paths::to_slash(entry.path().strip_prefix(&output).unwrap());
+                    (name, std::fs::read(entry.path()).unwrap())
+                })
+                .collect()
+        };
+        let builder = || {
+            let mut builder = test_builder(dir.path());
+            builder.config.output.manifest = true;
+            builder
+        };
+        builder().build().await.unwrap();
+        let first = tree();
+        assert!(first.contains_key("manifest.json"), "got: {:?}", first.keys().collect::<Vec<_>>());
+
+        // A second later, so anything stamped with the build time differs
+        std::fs::remove_dir_all(&output).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        builder().build().await.unwrap();
+        let second = tree();
+        assert_eq!(first.keys().collect::<Vec<_>>(), second.keys().collect::<Vec<_>>());
+        for (name, content) in &first {
+            assert!(second[name] == *content, "{} differs between builds", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_config_change_rebuilds_cached_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\nSection\n-------\n\nText.\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.enable_incremental();
+
+        builder.build().await.unwrap();
+        let stats = builder.build().await.unwrap();
+        // Hits are counted over every build of the builder
+        assert_eq!(stats.cache_hits, 1);
+
+        builder.config.html_permalinks_icon = Some("#".to_string());
+        let stats = builder.build().await.unwrap();
+        assert_eq!(stats.cache_hits, 1);
+        let page = std::fs::read_to_string(dir.path().join("_build/index.html")).unwrap();
+        assert!(page.contains(">#</a>"), "got: {}", page);
+        assert!(!page.contains("¶"), "got: {}", page);
+
+        // Options that don't change the output keep the cache
+        builder.config.parallel_jobs = Some(2);
+        let stats = builder.build().await.unwrap();
+        assert_eq!(stats.cache_hits, 2);
+
+        // So do unchanged templates, but an edited one doesn't
+        std::fs::create_dir(dir.path().join("_templates")).unwrap();
+        std::fs::write(dir.path().join("_templates/extra.html"), "one").unwrap();
+        builder.config.templates_path = vec![std::path::PathBuf::from("_templates")];
+        builder.build().await.unwrap();
+        let stats = builder.build().await.unwrap();
+        assert_eq!(stats.cache_hits, 3);
+        std::fs::write(dir.path().join("_templates/extra.html"), "two").unwrap();
+        let stats = builder.build().await.unwrap();
+        assert_eq!(stats.cache_hits, 3);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_markup_reported_at_its_location() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "Index\n=====\n\n.. include:: _parts/list.txt\n\n======\nTitle\n------\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("_parts")).unwrap();
+        std::fs::write(dir.path().join("_parts/list.txt"), "- item\nunindented\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.config.exclude_patterns.push("_parts/**".to_string());
+
+        let stats = builder.build().await.unwrap();
+        let warnings: Vec<_> = stats
+            .warning_details
+            .iter()
+            .filter(|w| w.warning_type == crate::error::WarningType::MalformedMarkup)
+            .map(|w| (w.file.file_name().unwrap().to_string_lossy().to_string(), w.line, w.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [(
+                "list.txt".to_string(),
+                Some(2),
+                "Bullet list ends without a blank line; unexpected unindent."
+            )]
+        );
+        assert_eq!(stats.error_details.len(), 1);
+        assert_eq!(stats.error_details[0].line, Some(7));
+        assert_eq!(stats.error_details[0].message, "Title overline & underline mismatch.");
+
+        builder.config.suppress_warnings = vec!["malformed-markup".to_string()];
+        let stats = builder.build().await.unwrap();
+        assert!(stats.warning_details.iter().all(|w| w.warning_type != crate::error::WarningType::MalformedMarkup));
+    }
+
+    #[tokio::test]
+    async fn test_changed_includes_rebuild_their_includers() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. include:: _parts/intro.txt\n").unwrap();
+        std::fs::create_dir(dir.path().join("_parts")).unwrap();
+        let intro = dir.path().join("_parts/intro.txt");
+        std::fs::write(&intro, "First version.\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.enable_incremental();
+        builder.config.exclude_patterns.push("_parts/**".to_string());
+
+        builder.build().await.unwrap();
+        let page = dir.path().join("_build/index.html");
+        assert!(std::fs::read_to_string(&page).unwrap().contains("First version."));
+
+        std::fs::write(&intro, "Second version.\n").unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options().write(true).open(&intro).unwrap().set_modified(later).unwrap();
+        builder.build().await.unwrap();
+        assert!(std::fs::read_to_string(&page).unwrap().contains("Second version."));
+    }
+
+    #[tokio::test]
+    async fn test_circular_includes_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. include:: a.inc\n").unwrap();
+        std::fs::write(dir.path().join("a.inc"), ".. include:: b.inc\n").unwrap();
+        std::fs::write(dir.path().join("b.inc"), "B\n\n.. include:: a.inc\n").unwrap();
+
+        let error = test_builder(dir.path()).build().await.unwrap_err();
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Circular include at line 3: index.rst -> a.inc -> b.inc -> a.inc"
+        );
+    }
+
+    #[test]
+    fn test_panics_become_error_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = test_builder(dir.path());
+        let path = dir.path().join("broken.rst");
+
+        let result: Option<()> = builder
+            .isolate_crash(&path, "rendering", || panic!("table has no rows"))
+            .unwrap();
+        assert!(result.is_none());
+        assert_eq!(
+            builder.isolate_crash(&path, "parsing", || Ok(7)).unwrap(),
+            Some(7)
+        );
+        assert!(builder
+            .isolate_crash(&path, "parsing", || -> anyhow::Result<()> { anyhow::bail!("unreadable") })
+            .is_err());
+
+        let errors = builder.errors.lock();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].file, path);
+        assert_eq!(
+            errors[0].message,
+            "Crashed while rendering the document: table has no rows"
+        );
+    }
+
+    struct PanicOn(&'static str);
+
+    impl crate::postprocess::HtmlPostProcessor for PanicOn {
+        fn process(&self, page: &crate::postprocess::RenderedPage, html: String) -> anyhow::Result<String> {
+            if page.doc_path == self.0 {
+                panic!("table has no rows");
+            }
+            Ok(html)
+        }
+
+        fn get_name(&self) -> &str {
+            "panic-on"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicking_document_does_not_stop_the_build() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.rst"), "Index\n=====\n\n.. toctree::\n\n   broken\n   guide\n").unwrap();
+        std::fs::write(dir.path().join("broken.rst"), "Broken\n======\n").unwrap();
+        std::fs::write(dir.path().join("guide.rst"), "Guide\n=====\n").unwrap();
+        let mut builder = test_builder(dir.path());
+        builder.add_post_processor(Box::new(PanicOn("broken")));
+
+        // The builder stays usable after a crash, so a second build works too
+        for _ in 0..2 {
+            let stats = builder.build().await.unwrap();
+            assert_eq!(stats.errors, 1);
+            assert_eq!(stats.error_details[0].file, dir.path().join("broken.rst"));
+            assert_eq!(
+                stats.error_details[0].message,
+                "Crashed while rendering the document: table has no rows"
+            );
+            assert!(dir.path().join("_build/index.html").is_file());
+            assert!(dir.path().join("_build/guide.html").is_file());
+            assert!(!dir.path().join("_build/broken.html").exists());
+        }
+    }
+
+    #[test]
+    fn test_build_stats_report() {
+        use crate::stats::{DocumentTiming, PhaseTimings};
+        use std::time::Duration;
+
+        let stats = super::BuildStats {
+            files_processed: 2,
+            files_skipped: 0,
+            build_time: Duration::from_millis(1500),
+            output_size_mb: 0.5,
+            cache_hits: 0,
+            errors: 0,
+            warnings: 1,
+            warning_details: Vec::new(),
+            error_details: Vec::new(),
+            phases: PhaseTimings {
+                render: Duration::from_millis(900),
+                ..PhaseTimings::default()
+            },
+            slowest_documents: vec![DocumentTiming {
+                doc_path: "guide/intro".to_string(),
+                parse: Duration::from_millis(2),
+                render: Duration::from_millis(600),
+                write: Duration::from_micros(500),
+            }],
+            stale_outputs_removed: 0,
+        };
+
+        let json = stats.to_json();
+        assert_eq!(json["build_time_ms"], 1500.0);
+        assert_eq!(json["phases"]["render_ms"], 900.0);
+        assert_eq!(json["slowest_documents"][0]["doc_path"], "guide/intro");
+
+        let summary = stats.summary();
+        assert!(summary.starts_with("Build time: 1.50s\n"));
+        assert!(summary.contains("  render         900.0ms\n"));
+        assert!(summary.contains(
+            "Slowest documents:\n  guide/intro     602.5ms (parse 2.0ms, render 600.0ms, write 0.5ms)\n"
+        ));
+    }
 }
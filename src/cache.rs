@@ -13,6 +13,10 @@ use std::time::{Duration, UNIX_EPOCH};
 use crate::document::Document;
 use crate::error::BuildError;
 
+/// File of the cache directory holding the fingerprint of the configuration
+/// the cached documents were built with
+const CONFIG_FINGERPRINT_FILE: &str = "config.hash";
+
 pub struct BuildCache {
     cache_dir: PathBuf,
     documents: Arc<DashMap<PathBuf, CachedDocument>>,
@@ -33,10 +37,8 @@ struct CachedDocument {
 }
 
 impl BuildCache {
+    /// The cache directory is created when the first document is stored.
     pub fn new(cache_dir: PathBuf) -> Result<Self> {
-        std::fs::create_dir_all(&cache_dir)
-            .with_context(|| format!("Failed to create cache directory: {}", cache_dir.display()))?;
-
         let cache = Self {
             cache_dir,
             documents: Arc::new(DashMap::new()),
@@ -56,19 +58,17 @@ impl BuildCache {
     pub fn get_document(&self, file_path: &Path) -> Result<Document> {
         let hash = self.calculate_file_hash(file_path)?;
 
-        if let Some(cached) = self.documents.get(file_path) {
+        if let Some(mut cached) = self.documents.get_mut(file_path) {
             if cached.hash == hash && !self.is_expired(&cached.cached_at) {
                 // Update access count
-                self.documents.alter(file_path, |_, mut cached| {
-                    cached.access_count += 1;
-                    cached
-                });
+                cached.access_count += 1;
 
                 *self.hit_count.write() += 1;
                 debug!("Cache hit for {}", file_path.display());
                 return Ok(cached.document.clone());
             }
-            // Remove expired or outdated entry
+            // Remove expired or outdated entry, once the entry guard is released
+            drop(cached);
             self.documents.remove(file_path);
         }
 
@@ -147,6 +147,27 @@ impl BuildCache {
         Ok(())
     }
 
+    /// Drop every cached document unless `fingerprint`, of the configuration
+    /// of this build, is the one they were cached under, since any option may
+    /// change how a document renders. Returns whether documents were dropped.
+    pub fn check_config(&self, fingerprint: &str) -> Result<bool> {
+        let fingerprint_file = self.cache_dir.join(CONFIG_FINGERPRINT_FILE);
+        let previous = std::fs::read_to_string(&fingerprint_file).ok();
+        if previous.as_deref() == Some(fingerprint) {
+            return Ok(false);
+        }
+
+        let cached: Vec<PathBuf> = self.documents.iter().map(|entry| entry.key().clone()).collect();
+        for path in &cached {
+            self.invalidate(path);
+        }
+        std::fs::create_dir_all(&self.cache_dir)
+            .with_context(|| format!("Failed to create cache directory: {}", self.cache_dir.display()))?;
+        std::fs::write(&fingerprint_file, fingerprint)
+            .with_context(|| format!("Failed to write cache file: {}", fingerprint_file.display()))?;
+        Ok(!cached.is_empty())
+    }
+
     pub fn hit_count(&self) -> usize {
         *self.hit_count.read()
     }
@@ -314,7 +335,12 @@ impl BuildCache {
         if let Some(cached_doc) = self.documents.get(file_path) {
             let content = serde_json::to_string_pretty(&*cached_doc)
                 .with_context(|| format!("Failed to serialize cache entry for: {}", file_path.display()))?;
-            std::fs::write(&cache_file, content)
+            // Written aside and renamed, so an interrupted build never
+            // leaves half an entry
+            let partial_file = cache_file.with_extension("json.partial");
+            std::fs::write(&partial_file, content)
+                .with_context(|| format!("Failed to write cache file: {}", partial_file.display()))?;
+            std::fs::rename(&partial_file, &cache_file)
                 .with_context(|| format!("Failed to write cache file: {}", cache_file.display()))?;
         }
 
@@ -327,3 +353,51 @@ impl BuildCache {
         self.cache_dir.join(filename)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_hit_returns_stored_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("index.rst");
+        std::fs::write(&source, "Title\n=====\n").unwrap();
+
+        let cache = BuildCache::new(dir.path().join("cache")).unwrap();
+        let mut document = Document::new(source.clone(), dir.path().join("index.html"));
+        document.title = "Title".to_string();
+        cache.store_document(&source, &document).unwrap();
+
+        assert_eq!(cache.get_document(&source).unwrap().title, "Title");
+        assert_eq!(cache.get_document(&source).unwrap().title, "Title");
+        assert_eq!(cache.hit_count(), 2);
+
+        std::fs::write(&source, "Changed\n=======\n").unwrap();
+        assert!(cache.get_document(&source).is_err());
+    }
+
+    #[test]
+    fn test_config_change_drops_cached_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("index.rst");
+        std::fs::write(&source, "Title\n=====\n").unwrap();
+
+        let cache = BuildCache::new(dir.path().join("cache")).unwrap();
+        assert!(!cache.check_config("first").unwrap());
+        let document = Document::new(source.clone(), dir.path().join("index.html"));
+        cache.store_document(&source, &document).unwrap();
+        assert!(!cache.check_config("first").unwrap());
+
+        // The fingerprint is kept with the entries on disk
+        let cache = BuildCache::new(dir.path().join("cache")).unwrap();
+        assert!(!cache.check_config("first").unwrap());
+        assert!(cache.get_document(&source).is_ok());
+        assert!(cache.check_config("second").unwrap());
+        assert!(cache.get_document(&source).is_err());
+
+        let cache = BuildCache::new(dir.path().join("cache")).unwrap();
+        assert!(cache.get_document(&source).is_err());
+        assert!(!cache.check_config("second").unwrap());
+    }
+}
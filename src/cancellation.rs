@@ -0,0 +1,42 @@
+//! Cooperative cancellation of builds.
+//!
+//! A `CancellationToken` is shared between the builder and whoever may stop
+//! it, like a Ctrl-C handler. The build phases check it between documents,
+//! so a document that has started is always written (and cached) whole,
+//! and the build then returns `BuildError::Cancelled`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Flag asking a build to stop; clones share it
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the builds using the token to stop at the next document
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_cancellation() {
+        let token = CancellationToken::new();
+        let handle = token.clone();
+        assert!(!token.is_cancelled());
+        handle.cancel();
+        assert!(token.is_cancelled());
+    }
+}
@@ -0,0 +1,435 @@
+//! Locating named definitions in source files for `literalinclude`.
+//!
+//! Sources are parsed with tree-sitter rather than scanned line by line, so
+//! decorators, attributes, nested scopes and any indentation width are
+//! handled the way the language itself sees them.
+
+use tree_sitter::{Node, Parser};
+
+/// Languages whose definitions `:object:` can extract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeLanguage {
+    Python,
+    Rust,
+    Go,
+    JavaScript,
+}
+
+impl CodeLanguage {
+    /// The language for a `:language:` name or a file extension
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "python" | "python3" | "py" | "py3" => Some(CodeLanguage::Python),
+            "rust" | "rs" => Some(CodeLanguage::Rust),
+            "go" | "golang" => Some(CodeLanguage::Go),
+            "javascript" | "js" | "jsx" | "mjs" | "cjs" => Some(CodeLanguage::JavaScript),
+            _ => None,
+        }
+    }
+
+    fn grammar(&self) -> tree_sitter::Language {
+        match self {
+            CodeLanguage::Python => tree_sitter_python::LANGUAGE.into(),
+            CodeLanguage::Rust => tree_sitter_rust::LANGUAGE.into(),
+            CodeLanguage::Go => tree_sitter_go::LANGUAGE.into(),
+            CodeLanguage::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+        }
+    }
+
+    /// Whether `node`, directly above a definition, belongs to it: Rust
+    /// attributes and doc comments, Go doc comments and JSDoc blocks
+    fn is_leading(&self, node: Node, source: &[u8]) -> bool {
+        let text = node.utf8_text(source).unwrap_or_default();
+        match (self, node.kind()) {
+            (CodeLanguage::Rust, "attribute_item") => true,
+            (CodeLanguage::Rust, "line_comment") => text.starts_with("///"),
+            (CodeLanguage::Rust, "block_comment") => text.starts_with("/**"),
+            (CodeLanguage::Go, "comment") => true,
+            (CodeLanguage::JavaScript, "comment") => text.starts_with("/**"),
+            _ => false,
+        }
+    }
+}
+
+/// A named definition among the statements of a scope
+struct Definition<'t> {
+    /// The node to extract, including wrappers like decorators or `export`
+    node: Node<'t>,
+    /// Dotted name within the scope; Go methods are named `Type.Method`
+    name: String,
+    /// Scope holding the members of classes, impls, traits and modules
+    members: Option<Node<'t>>,
+    /// Only a scope for members, like a Rust `impl` block
+    members_only: bool,
+}
+
+/// Lines of the object at `path` in `source`: a name, or a dotted path like
+/// `Outer.Inner.method` (Rust paths may use `::`). Decorators, attributes
+/// and doc comments are part of the object. The first definition wins when
+/// a name is defined more than once.
+pub fn find_object(source: &str, language: CodeLanguage, path: &str) -> Option<String> {
+    let path = path.replace("::", ".");
+    let parts: Vec<&str> = path.split('.').collect();
+    if parts.iter().any(|part| part.is_empty()) {
+        return None;
+    }
+    let mut parser = Parser::new();
+    parser.set_language(&language.grammar()).ok()?;
+    let tree = parser.parse(source, None)?;
+    let (node, start_row) = find_definition(language, tree.root_node(), &parts, source.as_bytes())?;
+    Some(lines_between(source, start_row, end_row(node)))
+}
+
+/// Lines of the Python function, class or method at `path`, for `:pyobject:`
+pub fn find_python_object(source: &str, path: &str) -> Option<String> {
+    find_object(source, CodeLanguage::Python, path)
+}
+
+/// The definition at `parts` in `scope` and the row its leading attributes
+/// and comments start on
+fn find_definition<'t>(
+    language: CodeLanguage,
+    scope: Node<'t>,
+    parts: &[&str],
+    source: &[u8],
+) -> Option<(Node<'t>, usize)> {
+    let mut cursor = scope.walk();
+    let statements: Vec<Node> = scope.named_children(&mut cursor).collect();
+    for (index, statement) in statements.iter().enumerate() {
+        for definition in definitions(language, *statement, source) {
+            let name: Vec<&str> = definition.name.split('.').collect();
+            if !parts.starts_with(&name) {
+                continue;
+            }
+            let rest = &parts[name.len()..];
+            if rest.is_empty() {
+                if definition.members_only {
+                    continue;
+                }
+                return Some((definition.node, leading_start(language, &statements[..index], definition.node, source)));
+            }
+            if let Some(found) = definition
+                .members
+                .and_then(|members| find_definition(language, members, rest, source))
+            {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// The definitions a statement introduces
+fn definitions<'t>(language: CodeLanguage, statement: Node<'t>, source: &[u8]) -> Vec<Definition<'t>> {
+    let text = |node: Option<Node>| node.and_then(|node| node.utf8_text(source).ok()).map(str::to_string);
+    let named = |node: Node<'t>, name: Option<String>, members: Option<Node<'t>>| {
+        name.map(|name| Definition { node, name, members, members_only: false })
+            .into_iter()
+            .collect::<Vec<_>>()
+    };
+
+    match (language, statement.kind()) {
+        (CodeLanguage::Python, "decorated_definition") => statement
+            .child_by_field_name("definition")
+            .map(|definition| {
+                definitions(language, definition, source)
+                    .into_iter()
+                    .map(|inner| Definition { node: statement, ..inner })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        (CodeLanguage::Python, "function_definition") => {
+            named(statement, text(statement.child_by_field_name("name")), None)
+        }
+        (CodeLanguage::Python, "class_definition") => named(
+            statement,
+            text(statement.child_by_field_name("name")),
+            statement.child_by_field_name("body"),
+        ),
+
+        (CodeLanguage::Rust, "impl_item") => {
+            let self_type = statement
+                .child_by_field_name("type")
+                .map(|node| node.child_by_field_name("type").unwrap_or(node));
+            text(self_type)
+                .map(|name| Definition {
+                    node: statement,
+                    name: name.rsplit("::").next().unwrap_or_default().to_string(),
+                    members: statement.child_by_field_name("body"),
+                    members_only: true,
+                })
+                .into_iter()
+                .collect()
+        }
+        (CodeLanguage::Rust, "mod_item" | "trait_item") => named(
+            statement,
+            text(statement.child_by_field_name("name")),
+            statement.child_by_field_name("body"),
+        ),
+        (
+            CodeLanguage::Rust,
+            "function_item" | "function_signature_item" | "struct_item" | "enum_item" | "union_item"
+            | "type_item" | "const_item" | "static_item" | "macro_definition",
+        ) => named(statement, text(statement.child_by_field_name("name")), None),
+
+        (CodeLanguage::Go, "function_declaration") => {
+            named(statement, text(statement.child_by_field_name("name")), None)
+        }
+        (CodeLanguage::Go, "method_declaration") => {
+            let receiver = statement
+                .child_by_field_name("receiver")
+                .and_then(|receivers| receivers.named_child(0))
+                .and_then(|receiver| receiver.child_by_field_name("type"));
+            let receiver = receiver.map(|node| match node.kind() {
+                "pointer_type" => node.named_child(0).unwrap_or(node),
+                _ => node,
+            });
+            let receiver = receiver.map(|node| node.child_by_field_name("type").unwrap_or(node));
+            match (text(receiver), text(statement.child_by_field_name("name"))) {
+                (Some(receiver), Some(name)) => named(statement, Some(format!("{}.{}", receiver, name)), None),
+                _ => Vec::new(),
+            }
+        }
+        (CodeLanguage::Go, "type_declaration") => {
+            let mut cursor = statement.walk();
+            let specs: Vec<Node> = statement
+                .named_children(&mut cursor)
+                .filter(|node| node.kind() == "type_spec")
+                .collect();
+            // A spec in a grouped `type ( ... )` declaration stands alone
+            let whole = specs.len() == 1;
+            specs
+                .into_iter()
+                .flat_map(|spec| {
+                    let node = if whole { statement } else { spec };
+                    named(node, text(spec.child_by_field_name("name")), None)
+                })
+                .collect()
+        }
+
+        (CodeLanguage::JavaScript, "export_statement") => statement
+            .child_by_field_name("declaration")
+            .map(|declaration| {
+                definitions(language, declaration, source)
+                    .into_iter()
+                    .map(|inner| Definition { node: statement, ..inner })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        (CodeLanguage::JavaScript, "function_declaration" | "generator_function_declaration") => {
+            named(statement, text(statement.child_by_field_name("name")), None)
+        }
+        (CodeLanguage::JavaScript, "class_declaration") => named(
+            statement,
+            text(statement.child_by_field_name("name")),
+            statement.child_by_field_name("body"),
+        ),
+        (CodeLanguage::JavaScript, "method_definition") => {
+            named(statement, text(statement.child_by_field_name("name")), None)
+        }
+        (CodeLanguage::JavaScript, "field_definition") => {
+            named(statement, text(statement.child_by_field_name("property")), None)
+        }
+        (CodeLanguage::JavaScript, "lexical_declaration" | "variable_declaration") => {
+            let mut cursor = statement.walk();
+            let declarators: Vec<Node> = statement
+                .named_children(&mut cursor)
+                .filter(|node| node.kind() == "variable_declarator")
+                .collect();
+            // `const a = 1, b = 2` extracts just the declarator asked for
+            let whole = declarators.len() == 1;
+            declarators
+                .into_iter()
+                .flat_map(|declarator| {
+                    let node = if whole { statement } else { declarator };
+                    named(node, text(declarator.child_by_field_name("name")), None)
+                })
+                .collect()
+        }
+
+        _ => Vec::new(),
+    }
+}
+
+/// First row of `node` together with the attributes and doc comments
+/// directly above it, with no blank line in between
+fn leading_start(language: CodeLanguage, preceding: &[Node], node: Node, source: &[u8]) -> usize {
+    let mut start = node.start_position().row;
+    for previous in preceding.iter().rev() {
+        if !language.is_leading(*previous, source) || end_row(*previous) + 1 < start {
+            break;
+        }
+        start = previous.start_position().row;
+    }
+    start
+}
+
+/// Last row covered by `node`; a node ending at the start of a line, like
+/// a line comment with its newline, does not cover that line
+fn end_row(node: Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row {
+        end.row - 1
+    } else {
+        end.row
+    }
+}
+
+/// Source lines `start..=end`
+fn lines_between(source: &str, start: usize, end: usize) -> String {
+    source
+        .lines()
+        .skip(start)
+        .take(end + 1 - start)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_python_object_with_decorators_async_and_nesting() {
+        let source = r#"import functools
+
+@functools.cache
+@other(
+    arg=1,
+)
+async def fetch(url):
+  """Fetch a URL."""
+  if url:
+    return await get(url)
+
+class Outer:
+  class Inner:
+    @property
+    def value(self):
+      return 1
+
+    def other(self):
+      pass
+
+  def method(self):
+    pass
+
+def fetch():
+    pass
+"#;
+
+        assert_eq!(
+            find_python_object(source, "fetch").unwrap(),
+            "@functools.cache\n@other(\n    arg=1,\n)\nasync def fetch(url):\n  \"\"\"Fetch a URL.\"\"\"\n  if url:\n    return await get(url)"
+        );
+        assert_eq!(
+            find_python_object(source, "Outer.Inner.value").unwrap(),
+            "    @property\n    def value(self):\n      return 1"
+        );
+        let inner = find_python_object(source, "Outer.Inner").unwrap();
+        assert!(inner.starts_with("  class Inner:"));
+        assert!(inner.ends_with("      pass"));
+        assert_eq!(
+            find_python_object(source, "Outer.method").unwrap(),
+            "  def method(self):\n    pass"
+        );
+
+        assert!(find_python_object(source, "Outer.missing").is_none());
+        assert!(find_python_object(source, "fetch.inner").is_none());
+        assert!(find_python_object(source, "functools").is_none());
+        assert!(find_python_object(source, "Outer.").is_none());
+    }
+
+    #[test]
+    fn test_find_object_in_rust_go_and_javascript() {
+        let rust = r#"use std::fmt;
+
+/// A point.
+#[derive(Debug)]
+pub struct Point<T> {
+    x: T,
+}
+
+// Not a doc comment
+impl<T> Point<T> {
+    /// Makes a point.
+    pub fn new(x: T) -> Self {
+        Point { x }
+    }
+}
+
+impl fmt::Display for Point<i32> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.x)
+    }
+}
+"#;
+        assert_eq!(
+            find_object(rust, CodeLanguage::Rust, "Point").unwrap(),
+            "/// A point.\n#[derive(Debug)]\npub struct Point<T> {\n    x: T,\n}"
+        );
+        assert_eq!(
+            find_object(rust, CodeLanguage::Rust, "Point::new").unwrap(),
+            "    /// Makes a point.\n    pub fn new(x: T) -> Self {\n        Point { x }\n    }"
+        );
+        assert!(find_object(rust, CodeLanguage::Rust, "Point.fmt").unwrap().contains("write!"));
+        assert!(find_object(rust, CodeLanguage::Rust, "fmt").is_none());
+
+        let go = r#"package shapes
+
+// Circle is round.
+type Circle struct {
+	R float64
+}
+
+// Area of the circle.
+func (c *Circle) Area() float64 {
+	return 3.14 * c.R * c.R
+}
+
+func New() *Circle { return &Circle{} }
+"#;
+        assert_eq!(
+            find_object(go, CodeLanguage::Go, "Circle").unwrap(),
+            "// Circle is round.\ntype Circle struct {\n\tR float64\n}"
+        );
+        assert_eq!(
+            find_object(go, CodeLanguage::Go, "Circle.Area").unwrap(),
+            "// Area of the circle.\nfunc (c *Circle) Area() float64 {\n\treturn 3.14 * c.R * c.R\n}"
+        );
+        assert_eq!(
+            find_object(go, CodeLanguage::Go, "New").unwrap(),
+            "func New() *Circle { return &Circle{} }"
+        );
+
+        let js = r#"/** Greets. */
+export function greet(name) {
+  return `hi ${name}`;
+}
+
+class Counter {
+  count = 0;
+  increment() {
+    this.count += 1;
+  }
+}
+
+const double = (x) => x * 2, triple = (x) => x * 3;
+"#;
+        assert_eq!(
+            find_object(js, CodeLanguage::JavaScript, "greet").unwrap(),
+            "/** Greets. */\nexport function greet(name) {\n  return `hi ${name}`;\n}"
+        );
+        assert_eq!(
+            find_object(js, CodeLanguage::JavaScript, "Counter.increment").unwrap(),
+            "  increment() {\n    this.count += 1;\n  }"
+        );
+        assert_eq!(
+            find_object(js, CodeLanguage::JavaScript, "triple").unwrap(),
+            "const double = (x) => x * 2, triple = (x) => x * 3;"
+        );
+
+        assert_eq!(CodeLanguage::from_name("rs"), Some(CodeLanguage::Rust));
+        assert_eq!(CodeLanguage::from_name("ruby"), None);
+    }
+}
@@ -1,12 +1,17 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
+use crate::error::{BuildOutcome, BuildWarning};
+use crate::optimize::CompressionFormat;
 use crate::python_config::PythonConfigParser;
+use crate::redirects::RedirectMapFormat;
+use crate::validation::ContentRules;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuildConfig {
-    /// Number of parallel jobs to use (defaults to number of CPU cores)
+    /// Number of parallel jobs to use; unset or 0 uses one per CPU core
     pub parallel_jobs: Option<usize>,
 
     /// Maximum cache size in MB
@@ -67,6 +72,11 @@ pub struct BuildConfig {
     /// HTML extra paths - directories copied to output root
     pub html_extra_path: Vec<PathBuf>,
 
+    /// What to do when files of `html_extra_path` collide with generated
+    /// pages or with each other
+    #[serde(default)]
+    pub html_extra_path_conflicts: ExtraPathConflicts,
+
     /// HTML logo file
     pub html_logo: Option<String>,
 
@@ -103,20 +113,935 @@ pub struct BuildConfig {
     /// Last updated format
     pub html_last_updated_fmt: Option<String>,
 
+    /// Base URL the HTML is published at, used for canonical page URLs
+    #[serde(default)]
+    pub html_baseurl: Option<String>,
+
+    /// Show the names given by `sectionauthor`, `moduleauthor` and
+    /// `codeauthor` directives in the pages
+    #[serde(default)]
+    pub show_authors: bool,
+
+    /// Add permalinks to headings and to captioned tables, figures and code
+    /// blocks
+    #[serde(default)]
+    pub html_permalinks: Option<bool>,
+
+    /// Text or HTML of the permalinks
+    #[serde(default)]
+    pub html_permalinks_icon: Option<String>,
+
+    /// Turn straight quotes, dashes and ellipses in prose into typographic
+    /// characters
+    #[serde(default)]
+    pub smartquotes: Option<bool>,
+
+    /// Conversions made by `smartquotes`: `q` quotes, `D` en and em dashes
+    /// for `--` and `---`, `e` ellipses
+    #[serde(default)]
+    pub smartquotes_action: Option<String>,
+
+    /// Languages and builders that keep straight quotes
+    #[serde(default)]
+    pub smartquotes_excludes: SmartQuotesExcludes,
+
+    /// Roles defined without an extension, by name
+    #[serde(default)]
+    pub custom_roles: BTreeMap<String, CustomRoleConfig>,
+
+    /// Container directives defined without an extension, by name
+    #[serde(default)]
+    pub custom_directives: BTreeMap<String, CustomDirectiveConfig>,
+
+    /// Tracker linked by the `:issue:`, `:pr:`, `:commit:` and `:user:` roles
+    #[serde(default)]
+    pub issue_tracker: IssueTrackerConfig,
+
+    /// Extra pages rendered from templates, mapping page name to template name
+    #[serde(default)]
+    pub html_additional_pages: HashMap<String, String>,
+
+    /// Reading-time estimate of each page, for templates
+    #[serde(default)]
+    pub reading_time: ReadingTimeConfig,
+
+    /// Language of literal blocks and of code blocks without one, until a
+    /// `highlight` directive changes it; `guess` picks one from each block's
+    /// content. Unset, they are not highlighted.
+    #[serde(default)]
+    pub highlight_language: Option<String>,
+
+    /// Copy-to-clipboard buttons on highlighted code blocks
+    #[serde(default)]
+    pub copybutton: CopyButtonConfig,
+
+    /// MathJax script loaded by the pages with math
+    #[serde(default = "default_mathjax_path")]
+    pub mathjax_path: String,
+
+    /// Mermaid script loaded by the pages with `mermaid` diagrams
+    #[serde(default = "default_mermaid_path")]
+    pub mermaid_path: String,
+
+    /// Labels and numbering of auto-numbered footnotes
+    #[serde(default)]
+    pub footnotes: FootnoteConfig,
+
+    /// BibTeX files, relative to the source directory, whose entries the
+    /// `:cite:` role references and the `bibliography` directive lists
+    #[serde(default)]
+    pub bibtex_files: Vec<String>,
+
+    /// Labels and order of the entries listed by `bibliography`
+    #[serde(default)]
+    pub bibtex_default_style: BibtexStyle,
+
+    /// Documentation comments collected from source code into documents
+    #[serde(default)]
+    pub docstrings: DocstringsConfig,
+
+    /// Number the captioned figures, tables and code blocks of the project
+    /// in toctree order
+    #[serde(default)]
+    pub numfig: bool,
+
+    /// Caption prefixes of the numbered figures, tables and code blocks
+    #[serde(default)]
+    pub numfig_format: NumfigFormat,
+
+    /// Generated `404.html` error page
+    #[serde(default)]
+    pub not_found: NotFoundConfig,
+
+    /// Publishing files for GitHub Pages
+    #[serde(default)]
+    pub github_pages: GitHubPagesConfig,
+
+    /// Redirects from the old paths of moved pages
+    #[serde(default)]
+    pub redirects: RedirectsConfig,
+
+    /// Git refs built side by side by the `multiversion` command
+    #[serde(default)]
+    pub versions: VersionsConfig,
+
+    /// Tags tested by `.. only::` expressions, in addition to the builder's
+    /// `html`, `format_html` and `builder_html`
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Built-in transformations of the final page HTML
+    #[serde(default)]
+    pub post_processing: PostProcessingConfig,
+
+    /// Subresource integrity and Content-Security-Policy friendly output
+    #[serde(default)]
+    pub security: SecurityConfig,
+
+    /// Memory use of builds of very large projects
+    #[serde(default)]
+    pub memory: MemoryConfig,
+
     /// Templates path
     pub templates_path: Vec<PathBuf>,
 
     /// Turn warnings into errors
     pub fail_on_warning: bool,
 
+    /// Which warnings count towards `fail_on_warning`
+    #[serde(default)]
+    pub warning_policy: WarningPolicy,
+
+    /// Exit status of the command line build for each outcome
+    #[serde(default)]
+    pub exit_codes: ExitCodes,
+
+    /// Project-specific content rules checked during the build
+    #[serde(default)]
+    pub content_rules: ContentRules,
+
+    /// Optional lint passes run over the sources
+    #[serde(default)]
+    pub lint: LintConfig,
+
+    /// Spell-check pass over the rendered text
+    #[serde(default)]
+    pub spelling: SpellingConfig,
+
+    /// OpenGraph and Twitter card tags for link previews
+    #[serde(default)]
+    pub opengraph: OpenGraphConfig,
+
+    /// Warning categories that are not reported at all
+    #[serde(default)]
+    pub suppress_warnings: Vec<String>,
+
+    /// How a title whose underline is shorter than its text is reported;
+    /// the title is accepted either way, as Sphinx does
+    #[serde(default)]
+    pub short_title_underline: ShortTitleUnderline,
+
     /// Glob-style patterns for file inclusion (Sphinx compatibility)
     /// Default: ["**"] (include all files)
     pub include_patterns: Vec<String>,
 
     /// Glob-style patterns for file exclusion (Sphinx compatibility)
     /// Default: [] (exclude nothing)
-    /// Exclusions have priority over inclusions
+    /// Exclusions have priority over inclusions; a pattern starting with
+    /// `!` re-includes what earlier patterns excluded
     pub exclude_patterns: Vec<String>,
+
+    /// Walk into symlinked directories when discovering sources
+    #[serde(default = "default_follow_symlinks")]
+    pub follow_symlinks: bool,
+
+    /// Gitignore-style files whose patterns leave sources out, like
+    /// `.gitignore`
+    #[serde(default = "default_ignore_files")]
+    pub ignore_files: Vec<String>,
+}
+
+fn default_ignore_files() -> Vec<String> {
+    vec![".sphinxultraignore".to_string()]
+}
+
+fn default_follow_symlinks() -> bool {
+    true
+}
+
+/// Policy deciding when warnings fail a build with `fail_on_warning` enabled
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WarningPolicy {
+    /// Warning categories that are fatal (empty means every category)
+    #[serde(default)]
+    pub fatal: Vec<String>,
+
+    /// Warning categories that never fail the build
+    #[serde(default)]
+    pub tolerated: Vec<String>,
+
+    /// Number of fatal warnings allowed before the build fails
+    #[serde(default)]
+    pub max_warnings: usize,
+}
+
+/// Exit status of `sphinx-ultra build` for each [`BuildOutcome`]. A build
+/// that `fail_on_warning` fails exits with `failed`, and a cancelled one
+/// with 130.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExitCodes {
+    /// Built without warnings or errors
+    pub success: i32,
+    /// Built, with warnings or document errors
+    pub warnings: i32,
+    /// The build stopped before finishing
+    pub failed: i32,
+}
+
+impl Default for ExitCodes {
+    fn default() -> Self {
+        Self {
+            success: 0,
+            warnings: 0,
+            failed: 1,
+        }
+    }
+}
+
+impl ExitCodes {
+    /// Exit status for `outcome`
+    pub fn for_outcome(&self, outcome: &BuildOutcome) -> i32 {
+        match outcome {
+            BuildOutcome::Success => self.success,
+            BuildOutcome::SuccessWithWarnings => self.warnings,
+            BuildOutcome::Failed { .. } => self.failed,
+        }
+    }
+}
+
+/// Optional lint passes; all disabled by default
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LintConfig {
+    /// Heading hierarchy and title formatting checks
+    #[serde(default)]
+    pub style: bool,
+
+    /// Alt text, table header, link text and heading order checks
+    #[serde(default)]
+    pub accessibility: bool,
+}
+
+/// Spell checking of rendered text against hunspell dictionaries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpellingConfig {
+    /// Run the spell-check pass
+    pub enabled: bool,
+
+    /// Dictionary language, looked up as `<lang>.dic` in the hunspell
+    /// search path when `dictionaries` is empty
+    pub lang: String,
+
+    /// Explicit `.dic` files; a `.aff` file next to each is used for affixes
+    pub dictionaries: Vec<PathBuf>,
+
+    /// Project word list, one word per line (relative to the source directory).
+    /// Defaults to `spelling_wordlist.txt` when that file exists.
+    pub wordlist: Option<PathBuf>,
+
+    /// Accept words written entirely in capitals
+    pub ignore_acronyms: bool,
+}
+
+impl Default for SpellingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lang: "en_US".to_string(),
+            dictionaries: vec![],
+            wordlist: None,
+            ignore_acronyms: true,
+        }
+    }
+}
+
+/// A role defined in the configuration: its content either fills an HTML
+/// template or is wrapped in an element with classes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomRoleConfig {
+    /// HTML in which `{text}` is replaced by the role's text and `{target}`
+    /// by its target, both escaped; `:role:`text <target>`` sets them apart
+    pub template: Option<String>,
+
+    /// Element wrapping the text when there is no template, `span` by default
+    pub element: Option<String>,
+
+    /// Classes of the wrapping element
+    pub classes: Vec<String>,
+}
+
+/// A container directive defined in the configuration: its content is
+/// wrapped in an element with classes, below an optional title
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomDirectiveConfig {
+    /// Wrapping element, `div` by default
+    pub element: Option<String>,
+
+    /// Classes of the wrapping element, the directive name by default; the
+    /// `:class:` option adds more
+    pub classes: Vec<String>,
+
+    /// Whether the directive's argument is a title
+    pub title: CustomDirectiveTitle,
+
+    /// Title shown when the argument is left out
+    pub default_title: Option<String>,
+
+    /// Class of the title paragraph
+    pub title_class: Option<String>,
+
+    /// Options that must be given
+    pub required_options: Vec<String>,
+
+    /// Other options the directive accepts besides `class` and `name`
+    pub options: Vec<String>,
+
+    /// Stylesheets linked from the pages using the directive, like
+    /// `html_css_files`
+    pub css_files: Vec<String>,
+
+    /// Scripts loaded by the pages using the directive, like `html_js_files`
+    pub js_files: Vec<String>,
+}
+
+/// How a configured directive treats its argument
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomDirectiveTitle {
+    /// No argument and no title, unless there is a default title
+    #[default]
+    None,
+    /// The argument, if given, is the title
+    Optional,
+    /// The argument is a title that must be given
+    Required,
+}
+
+/// How files of `html_extra_path` colliding with generated pages or with
+/// each other are reported; the last one copied wins
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtraPathConflicts {
+    /// Warn and copy anyway
+    #[default]
+    Warn,
+    /// Fail the build
+    Error,
+}
+
+/// Severity of a title underline shorter than the title. Underlines of
+/// fewer than four characters never make a title.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShortTitleUnderline {
+    /// Accept the title silently
+    Ignore,
+    /// Accept the title and warn
+    #[default]
+    Warn,
+    /// Accept the title and report an error
+    Error,
+}
+
+/// Hosting service whose URL layout the issue tracker roles follow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueTrackerKind {
+    #[default]
+    GitHub,
+    GitLab,
+    Jira,
+}
+
+/// Links of the `:issue:`, `:pr:`, `:commit:` and `:user:` roles, which are
+/// only defined once a project or a URL template is set
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IssueTrackerConfig {
+    /// Service whose URL layout is used
+    pub kind: IssueTrackerKind,
+
+    /// Base URL of the service, the public GitHub or GitLab site by default;
+    /// Jira needs one
+    pub url: Option<String>,
+
+    /// Repository as `owner/repo`, or the Jira project key
+    pub project: Option<String>,
+
+    /// URL templates replacing those of `kind`, in which `{url}`, `{project}`
+    /// and `{id}` are replaced
+    pub issue_uri: Option<String>,
+    pub pr_uri: Option<String>,
+    pub commit_uri: Option<String>,
+    pub user_uri: Option<String>,
+}
+
+impl IssueTrackerConfig {
+    /// URL template of one of the `issue`, `pr`, `commit` and `user` roles,
+    /// with the base URL filled in, if the role is available
+    pub fn uri_template(&self, role: &str) -> Option<String> {
+        let configured = match role {
+            "issue" => &self.issue_uri,
+            "pr" => &self.pr_uri,
+            "commit" => &self.commit_uri,
+            "user" => &self.user_uri,
+            _ => return None,
+        };
+        let url = self.url.as_deref().or(match self.kind {
+            IssueTrackerKind::GitHub => Some("https://github.com"),
+            IssueTrackerKind::GitLab => Some("https://gitlab.com"),
+            IssueTrackerKind::Jira => None,
+        });
+        let template = match configured {
+            Some(template) => template.as_str(),
+            // The default templates link into the project
+            None if self.project.is_none() => return None,
+            None => match (self.kind, role) {
+                (IssueTrackerKind::GitHub, "issue") => "{url}/{project}/issues/{id}",
+                (IssueTrackerKind::GitHub, "pr") => "{url}/{project}/pull/{id}",
+                (IssueTrackerKind::GitHub, "commit") => "{url}/{project}/commit/{id}",
+                (IssueTrackerKind::GitLab, "issue") => "{url}/{project}/-/issues/{id}",
+                (IssueTrackerKind::GitLab, "pr") => "{url}/{project}/-/merge_requests/{id}",
+                (IssueTrackerKind::GitLab, "commit") => "{url}/{project}/-/commit/{id}",
+                (IssueTrackerKind::Jira, "issue") => "{url}/browse/{id}",
+                (IssueTrackerKind::Jira, "user") => "{url}/secure/ViewProfile.jspa?name={id}",
+                (_, "user") => "{url}/{id}",
+                _ => return None,
+            },
+        };
+        if template.contains("{url}") {
+            Some(template.replace("{url}", url?.trim_end_matches('/')))
+        } else {
+            Some(template.to_string())
+        }
+    }
+}
+
+/// Where `smartquotes` does not apply, as in Sphinx's `smartquotes_excludes`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmartQuotesExcludes {
+    /// Document languages, like `ja`
+    pub languages: Vec<String>,
+
+    /// Builder names
+    pub builders: Vec<String>,
+}
+
+impl Default for SmartQuotesExcludes {
+    fn default() -> Self {
+        Self {
+            languages: vec!["ja".to_string()],
+            builders: vec!["man".to_string(), "text".to_string()],
+        }
+    }
+}
+
+/// Estimated reading time of pages, given to templates as `reading_time`
+/// in minutes along with the page's `word_count`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadingTimeConfig {
+    /// Compute the estimate
+    pub enabled: bool,
+
+    /// Reading speed the estimate assumes
+    pub words_per_minute: usize,
+}
+
+impl Default for ReadingTimeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            words_per_minute: 200,
+        }
+    }
+}
+
+/// Copy buttons on code blocks, as the sphinx-copybutton extension adds them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CopyButtonConfig {
+    /// Add the buttons
+    pub enabled: bool,
+
+    /// Prompts removed from the start of copied lines
+    pub prompts: Vec<String>,
+
+    /// Regular expression matching prompts, replacing `prompts`
+    pub prompt_regex: Option<String>,
+
+    /// In blocks with prompts, copy only the lines that have one, leaving
+    /// out the output
+    pub only_copy_prompt_lines: bool,
+
+    /// CSS selector of the blocks that get a button
+    pub selector: String,
+}
+
+impl Default for CopyButtonConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            prompts: vec![">>> ".to_string(), "... ".to_string(), "$ ".to_string()],
+            prompt_regex: None,
+            only_copy_prompt_lines: true,
+            selector: "div.highlight pre".to_string(),
+        }
+    }
+}
+
+impl CopyButtonConfig {
+    /// Settings for the button script, with prompts as one regular expression
+    pub fn script_settings(&self) -> serde_json::Value {
+        let prompt_regex = self.prompt_regex.clone().or_else(|| {
+            let prompts: Vec<String> = self
+                .prompts
+                .iter()
+                .filter(|prompt| !prompt.is_empty())
+                .map(|prompt| regex::escape(prompt))
+                .collect();
+            (!prompts.is_empty()).then(|| prompts.join("|"))
+        });
+        serde_json::json!({
+            "selector": self.selector,
+            "prompt_regex": prompt_regex,
+            "only_copy_prompt_lines": self.only_copy_prompt_lines,
+        })
+    }
+}
+
+/// Labels and numbering of auto-numbered footnotes (`[#]` and `[#name]`);
+/// footnotes numbered by hand keep their number
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FootnoteConfig {
+    /// Labels of auto-numbered footnotes
+    pub style: FootnoteStyle,
+
+    /// Where auto-numbering starts over
+    pub numbering: FootnoteNumbering,
+}
+
+/// Labels of auto-numbered footnotes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FootnoteStyle {
+    /// 1, 2, 3, ...
+    #[default]
+    Numeric,
+    /// *, †, ‡, ..., shared with the `[*]` footnotes
+    Symbol,
+}
+
+/// Where auto-numbering of footnotes starts over
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FootnoteNumbering {
+    /// Once per document
+    #[default]
+    Document,
+    /// At every section heading
+    Section,
+}
+
+/// Caption prefixes of numbered items, with `%s` standing for the number
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NumfigFormat {
+    pub figure: String,
+    pub table: String,
+    #[serde(rename = "code-block")]
+    pub code_block: String,
+}
+
+impl Default for NumfigFormat {
+    fn default() -> Self {
+        Self {
+            figure: "Fig. %s".to_string(),
+            table: "Table %s".to_string(),
+            code_block: "Listing %s".to_string(),
+        }
+    }
+}
+
+impl NumfigFormat {
+    /// Prefix of a `figure`, `table` or `code-block`
+    pub fn prefix(&self, figtype: &str) -> Option<&str> {
+        match figtype {
+            "figure" => Some(&self.figure),
+            "table" => Some(&self.table),
+            "code-block" => Some(&self.code_block),
+            _ => None,
+        }
+    }
+}
+
+/// Documentation comment blocks collected from source files outside the
+/// documentation, such as scripts and configuration of an infra repository.
+/// Each file with blocks becomes a document under `directory`, listed by a
+/// generated `index` document there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocstringsConfig {
+    /// Directory scanned, relative to the source directory
+    pub root: String,
+
+    /// Files scanned, as glob patterns relative to `root`; none disables
+    /// the collector
+    pub patterns: Vec<String>,
+
+    /// Lines starting a block: a comment prefix and a keyword. The block
+    /// goes on over the following lines with the same comment prefix.
+    pub markers: Vec<String>,
+
+    /// Directory of the generated documents in the project
+    pub directory: String,
+
+    /// Title of the generated index document
+    pub title: String,
+}
+
+impl Default for DocstringsConfig {
+    fn default() -> Self {
+        Self {
+            root: ".".to_string(),
+            patterns: Vec::new(),
+            markers: vec!["//! doc:".to_string(), "### doc:".to_string()],
+            directory: "code".to_string(),
+            title: "Code Documentation".to_string(),
+        }
+    }
+}
+
+/// Labels and order of bibliography entries, after the BibTeX styles of the
+/// same names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BibtexStyle {
+    /// Numbered, sorted by author, year and title
+    #[default]
+    Plain,
+    /// Numbered, in the order first cited
+    Unsrt,
+    /// Author and year labels like `Knu84`, sorted like `plain`
+    Alpha,
+    /// Author and year labels, in the order first cited
+    Unsrtalpha,
+}
+
+/// The `404.html` page served by static hosts for missing URLs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotFoundConfig {
+    /// Generate the page (a `404` document in the project takes its place)
+    pub enabled: bool,
+
+    /// Page title
+    pub title: String,
+
+    /// Page body as HTML
+    pub body: String,
+
+    /// Prefix for the page's links, which must be absolute because the page
+    /// is served from arbitrary paths. Defaults to the path of `html_baseurl`,
+    /// or `/`; an empty prefix leaves links relative.
+    pub urls_prefix: Option<String>,
+}
+
+impl Default for NotFoundConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            title: "Page not found".to_string(),
+            body: "<h1>Page not found</h1>\n\n<p>Unfortunately we couldn't find the content you were looking for.</p>".to_string(),
+            urls_prefix: None,
+        }
+    }
+}
+
+/// Files GitHub Pages needs in the output root, as `sphinx.ext.githubpages`
+/// writes them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GitHubPagesConfig {
+    /// Write `.nojekyll` so Jekyll does not drop `_static` and the other
+    /// underscore-prefixed directories
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Custom domain for the `CNAME` file. Defaults to the host of
+    /// `html_baseurl` unless that is a `github.io` address.
+    #[serde(default)]
+    pub cname: Option<String>,
+}
+
+/// Redirect stubs for pages that moved, like `html_redirects` of
+/// sphinx-reredirects
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RedirectsConfig {
+    /// Old document names mapped to the new document name (with an optional
+    /// `#anchor`) or an absolute URL
+    #[serde(default)]
+    pub pages: BTreeMap<String, String>,
+
+    /// Server-side redirect files to write as well
+    #[serde(default)]
+    pub map_files: Vec<RedirectMapFormat>,
+}
+
+/// Built-in HTML post-processors, all disabled by default
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PostProcessingConfig {
+    /// Add `loading="lazy"` to images
+    #[serde(default)]
+    pub lazy_images: bool,
+
+    /// HTML appended to every page's `<head>`, such as an analytics script
+    #[serde(default)]
+    pub head_html: Option<String>,
+
+    /// HTML appended to every page's `<body>`
+    #[serde(default)]
+    pub body_html: Option<String>,
+
+    /// Rewriting of `reference external` links
+    #[serde(default)]
+    pub external_links: ExternalLinksConfig,
+}
+
+/// How external links are emitted; by default they are left as rendered
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExternalLinksConfig {
+    /// Open external links in a new tab, with `rel="noopener"`
+    pub new_tab: bool,
+
+    /// Class added to external links, for themes to mark them with an icon
+    pub icon_class: Option<String>,
+
+    /// URL schemes that may be linked, like `https` and `mailto`; empty
+    /// allows every scheme that is not denied
+    pub allowed_schemes: Vec<String>,
+
+    /// URL schemes that are never linked, like `javascript`
+    pub denied_schemes: Vec<String>,
+}
+
+impl ExternalLinksConfig {
+    /// Whether any option changes the links
+    pub fn is_active(&self) -> bool {
+        self.new_tab
+            || self.icon_class.is_some()
+            || !self.allowed_schemes.is_empty()
+            || !self.denied_schemes.is_empty()
+    }
+
+    /// Whether a link may use `scheme`
+    pub fn allows_scheme(&self, scheme: &str) -> bool {
+        let listed = |schemes: &[String]| schemes.iter().any(|listed| listed.eq_ignore_ascii_case(scheme));
+        !listed(&self.denied_schemes) && (self.allowed_schemes.is_empty() || listed(&self.allowed_schemes))
+    }
+}
+
+/// Hardening of the emitted pages for sites served with a strict
+/// Content-Security-Policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecurityConfig {
+    /// Add sha384 `integrity` attributes to local stylesheet and script tags
+    pub subresource_integrity: bool,
+
+    /// `crossorigin` attribute emitted alongside `integrity`
+    pub crossorigin: String,
+
+    /// Avoid inline styles and scripts, highlighting code with CSS classes
+    /// from `_static/highlight.css` instead of `style` attributes
+    pub strict_csp: bool,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            subresource_integrity: false,
+            crossorigin: "anonymous".to_string(),
+            strict_csp: false,
+        }
+    }
+}
+
+/// Batching of documents to bound the memory of very large builds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// Keep the parsed documents of one batch in memory at a time, dropping
+    /// them once rendered. Every document is parsed twice, once to index
+    /// titles and toctrees and once to render it, trading CPU for memory.
+    pub bounded: bool,
+
+    /// Number of documents per batch in bounded mode
+    pub batch_size: usize,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            bounded: false,
+            batch_size: 500,
+        }
+    }
+}
+
+/// Selection of the git refs built into versioned subdirectories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VersionsConfig {
+    /// Tags to build, as a regular expression
+    pub tag_pattern: String,
+
+    /// Local branches to build, as a regular expression
+    pub branch_pattern: String,
+
+    /// Version marked as preferred in the switcher and linked from the
+    /// output root (defaults to the newest tag, else the first branch)
+    pub latest: Option<String>,
+}
+
+impl Default for VersionsConfig {
+    fn default() -> Self {
+        Self {
+            tag_pattern: "^.*$".to_string(),
+            branch_pattern: "^.*$".to_string(),
+            latest: None,
+        }
+    }
+}
+
+/// OpenGraph and Twitter card meta tags added to every page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OpenGraphConfig {
+    /// Emit the social meta tags
+    pub enabled: bool,
+
+    /// Site name shown in previews (defaults to the project name)
+    pub site_name: Option<String>,
+
+    /// Site URL for `og:url` (defaults to `html_baseurl`)
+    pub site_url: Option<String>,
+
+    /// Preview image, an absolute URL or a path relative to the site URL
+    pub image: Option<String>,
+
+    /// Alt text for the preview image
+    pub image_alt: Option<String>,
+
+    /// Maximum length in characters of the page description
+    pub description_length: usize,
+
+    /// Generated per-page preview images
+    pub social_cards: SocialCardsConfig,
+}
+
+/// Per-page preview images with the page title, rendered to PNG during the
+/// build and used as `og:image` when no fixed image is configured
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SocialCardsConfig {
+    /// Generate the cards
+    pub enabled: bool,
+
+    /// TrueType/OpenType font for the text; common system fonts are tried
+    /// when unset
+    pub font: Option<PathBuf>,
+
+    /// PNG logo shown in the corner, relative to the source directory
+    /// (defaults to `html_logo`)
+    pub image: Option<String>,
+
+    /// Color of the text
+    pub text_color: String,
+
+    /// Color of the accent line along the bottom edge
+    pub line_color: String,
+}
+
+impl Default for SocialCardsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            font: None,
+            image: None,
+            text_color: "#4a4a4a".to_string(),
+            line_color: "#5a626b".to_string(),
+        }
+    }
+}
+
+impl Default for OpenGraphConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            site_name: None,
+            site_url: None,
+            image: None,
+            image_alt: None,
+            description_length: 200,
+            social_cards: SocialCardsConfig::default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -136,8 +1061,38 @@ pub struct OutputConfig {
     /// Minify output HTML
     pub minify_html: bool,
 
+    /// Minify CSS and JavaScript copied to `_static`
+    #[serde(default)]
+    pub minify_assets: bool,
+
     /// Compress output files
     pub compress_output: bool,
+
+    /// Encodings written next to each output file when `compress_output` is set
+    #[serde(default = "default_compression_formats")]
+    pub compression_formats: Vec<CompressionFormat>,
+
+    /// Write `manifest.json` listing every page with its title, source,
+    /// anchors and assets
+    #[serde(default)]
+    pub manifest: bool,
+
+    /// Leave the pages of removed documents in the output directory instead
+    /// of deleting them
+    #[serde(default)]
+    pub keep_stale: bool,
+}
+
+fn default_mathjax_path() -> String {
+    "https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-mml-chtml.js".to_string()
+}
+
+fn default_mermaid_path() -> String {
+    "https://cdn.jsdelivr.net/npm/mermaid@11/dist/mermaid.min.js".to_string()
+}
+
+fn default_compression_formats() -> Vec<CompressionFormat> {
+    vec![CompressionFormat::Gzip, CompressionFormat::Brotli]
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -206,6 +1161,7 @@ impl Default for BuildConfig {
             html_js_files: vec![],
             html_static_path: vec![PathBuf::from("_static")],
             html_extra_path: vec![],
+            html_extra_path_conflicts: ExtraPathConflicts::Warn,
             html_logo: None,
             html_favicon: None,
             html_title: None,
@@ -218,14 +1174,54 @@ impl Default for BuildConfig {
             html_use_index: Some(true),
             html_use_opensearch: Some(false),
             html_last_updated_fmt: Some("%b %d, %Y".to_string()),
+            html_baseurl: None,
+            show_authors: false,
+            html_permalinks: Some(true),
+            html_permalinks_icon: Some("¶".to_string()),
+            smartquotes: Some(true),
+            smartquotes_action: Some("qDe".to_string()),
+            smartquotes_excludes: SmartQuotesExcludes::default(),
+            custom_roles: BTreeMap::new(),
+            custom_directives: BTreeMap::new(),
+            issue_tracker: IssueTrackerConfig::default(),
+            html_additional_pages: HashMap::new(),
+            reading_time: ReadingTimeConfig::default(),
+            highlight_language: None,
+            copybutton: CopyButtonConfig::default(),
+            mathjax_path: default_mathjax_path(),
+            mermaid_path: default_mermaid_path(),
+            footnotes: FootnoteConfig::default(),
+            bibtex_files: Vec::new(),
+            bibtex_default_style: BibtexStyle::default(),
+            docstrings: DocstringsConfig::default(),
+            numfig: false,
+            numfig_format: NumfigFormat::default(),
+            not_found: NotFoundConfig::default(),
+            github_pages: GitHubPagesConfig::default(),
+            redirects: RedirectsConfig::default(),
+            versions: VersionsConfig::default(),
+            tags: Vec::new(),
+            post_processing: PostProcessingConfig::default(),
+            security: SecurityConfig::default(),
+            memory: MemoryConfig::default(),
             templates_path: vec![PathBuf::from("_templates")],
 
             // Warning handling
             fail_on_warning: false,
+            warning_policy: WarningPolicy::default(),
+            exit_codes: ExitCodes::default(),
+            content_rules: ContentRules::default(),
+            lint: LintConfig::default(),
+            spelling: SpellingConfig::default(),
+            opengraph: OpenGraphConfig::default(),
+            suppress_warnings: vec![],
+            short_title_underline: ShortTitleUnderline::Warn,
 
             // File pattern matching (Sphinx compatibility)
             include_patterns: vec!["**".to_string()],
             exclude_patterns: vec![],
+            follow_symlinks: true,
+            ignore_files: default_ignore_files(),
         }
     }
 }
@@ -238,7 +1234,11 @@ impl Default for OutputConfig {
             highlight_theme: "github".to_string(),
             search_index: true,
             minify_html: false,
+            minify_assets: false,
             compress_output: false,
+            compression_formats: default_compression_formats(),
+            manifest: false,
+            keep_stale: false,
         }
     }
 }
@@ -267,7 +1267,80 @@ impl Default for OptimizationConfig {
     }
 }
 
+impl WarningPolicy {
+    /// Whether a warning of the given category counts towards failure
+    pub fn is_fatal(&self, category: &str) -> bool {
+        if self.tolerated.iter().any(|c| c == category) {
+            return false;
+        }
+        self.fatal.is_empty() || self.fatal.iter().any(|c| c == category)
+    }
+
+    /// Warnings that count towards failure under this policy
+    pub fn fatal_warnings<'a>(&self, warnings: &'a [BuildWarning]) -> Vec<&'a BuildWarning> {
+        warnings
+            .iter()
+            .filter(|w| self.is_fatal(w.warning_type.category()))
+            .collect()
+    }
+
+    /// Whether the given warnings exceed the allowed threshold
+    pub fn is_violated(&self, warnings: &[BuildWarning]) -> bool {
+        self.fatal_warnings(warnings).len() > self.max_warnings
+    }
+}
+
+/// Options that don't change what a build writes, left out of
+/// [`BuildConfig::fingerprint`]
+const OUTPUT_NEUTRAL_OPTIONS: &[&str] = &[
+    "parallel_jobs",
+    "max_cache_size_mb",
+    "cache_expiration_hours",
+    "fail_on_warning",
+    "warning_policy",
+    "exit_codes",
+];
+
+/// `value` with the keys of every object in order, so maps hash the same
+/// whatever order their entries were inserted in
+fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let sorted: BTreeMap<String, serde_json::Value> =
+                map.into_iter().map(|(key, value)| (key, sort_keys(value))).collect();
+            serde_json::Value::Object(sorted.into_iter().collect())
+        }
+        serde_json::Value::Array(items) => serde_json::Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
 impl BuildConfig {
+    /// Whether smart quotes apply to pages built by `builder`
+    pub fn uses_smartquotes(&self, builder: &str) -> bool {
+        let language = self.language.as_deref().unwrap_or("en");
+        self.smartquotes.unwrap_or(true)
+            && !self.smartquotes_excludes.languages.iter().any(|excluded| excluded == language)
+            && !self.smartquotes_excludes.builders.iter().any(|excluded| excluded == builder)
+    }
+
+    /// Hash of the options that can change the output and of the
+    /// sphinx-ultra version, stored with the incremental cache so documents
+    /// cached under other options or by another release are rebuilt.
+    /// Parallelism, cache limits and warning policy are left out.
+    pub fn fingerprint(&self) -> String {
+        let mut options = serde_json::to_value(self).unwrap_or_default();
+        if let Some(options) = options.as_object_mut() {
+            for name in OUTPUT_NEUTRAL_OPTIONS {
+                options.remove(*name);
+            }
+        }
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update(sort_keys(options).to_string().as_bytes());
+        hasher.finalize().to_hex().to_string()
+    }
+
     pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
         let content = std::fs::read_to_string(path)
@@ -341,3 +1414,122 @@ impl BuildConfig {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::WarningType;
+
+    fn warning(warning_type: WarningType) -> BuildWarning {
+        BuildWarning::new(PathBuf::from("index.rst"), None, "test".to_string(), warning_type)
+    }
+
+    #[test]
+    fn test_default_warning_policy_fails_on_any_warning() {
+        let policy = WarningPolicy::default();
+        assert!(!policy.is_violated(&[]));
+        assert!(policy.is_violated(&[warning(WarningType::OrphanedDocument)]));
+    }
+
+    #[test]
+    fn test_warning_policy_categories() {
+        let policy = WarningPolicy {
+            fatal: vec!["missing-toctree-ref".to_string(), "orphaned-document".to_string()],
+            tolerated: vec!["orphaned-document".to_string()],
+            max_warnings: 0,
+        };
+        assert!(!policy.is_violated(&[warning(WarningType::OrphanedDocument)]));
+        assert!(!policy.is_violated(&[warning(WarningType::DuplicateLabel)]));
+        assert!(policy.is_violated(&[warning(WarningType::MissingToctreeRef)]));
+    }
+
+    #[test]
+    fn test_warning_policy_threshold() {
+        let policy = WarningPolicy {
+            max_warnings: 2,
+            ..Default::default()
+        };
+        let warnings = vec![
+            warning(WarningType::Other),
+            warning(WarningType::MissingFile),
+        ];
+        assert!(!policy.is_violated(&warnings));
+
+        let mut more = warnings.clone();
+        more.push(warning(WarningType::EmptyToctree));
+        assert!(policy.is_violated(&more));
+    }
+
+    #[test]
+    fn test_warning_policy_deserializes_partially() {
+        let config: WarningPolicy = serde_json::from_str(r#"{"tolerated": ["other"]}"#).unwrap();
+        assert!(config.fatal.is_empty());
+        assert_eq!(config.max_warnings, 0);
+        assert!(!config.is_fatal("other"));
+    }
+
+    #[test]
+    fn test_fingerprint_follows_output_options() {
+        let config = BuildConfig::default();
+        let fingerprint = config.fingerprint();
+        assert_eq!(config.clone().fingerprint(), fingerprint);
+
+        let mut faster = config.clone();
+        faster.parallel_jobs = Some(16);
+        faster.fail_on_warning = true;
+        assert_eq!(faster.fingerprint(), fingerprint);
+
+        let mut themed = config.clone();
+        themed.theme.options = serde_json::json!({"navigation_depth": 2});
+        assert_ne!(themed.fingerprint(), fingerprint);
+        let mut extended = config.clone();
+        extended.extensions.push("sphinx.ext.todo".to_string());
+        assert_ne!(extended.fingerprint(), fingerprint);
+
+        // Map entries inserted in another order hash the same
+        let mut first = config.clone();
+        let mut second = config;
+        for page in ["a", "b", "c"] {
+            first.html_additional_pages.insert(page.to_string(), format!("{}.html", page));
+        }
+        for page in ["c", "a", "b"] {
+            second.html_additional_pages.insert(page.to_string(), format!("{}.html", page));
+        }
+        assert_eq!(first.fingerprint(), second.fingerprint());
+    }
+
+    #[test]
+    fn test_exit_codes_per_outcome() {
+        let codes: ExitCodes = serde_json::from_str(r#"{"warnings": 2}"#).unwrap();
+        assert_eq!(codes.for_outcome(&BuildOutcome::Success), 0);
+        assert_eq!(codes.for_outcome(&BuildOutcome::SuccessWithWarnings), 2);
+        let failed = BuildOutcome::Failed {
+            phase: "render".to_string(),
+        };
+        assert_eq!(codes.for_outcome(&failed), 1);
+        assert_eq!(ExitCodes::default().for_outcome(&BuildOutcome::SuccessWithWarnings), 0);
+    }
+
+    #[test]
+    fn test_issue_tracker_templates() {
+        let tracker: IssueTrackerConfig =
+            serde_json::from_str(r#"{"kind": "gitlab", "url": "https://git.example.com/", "project": "group/app"}"#)
+                .unwrap();
+        assert_eq!(
+            tracker.uri_template("pr").as_deref(),
+            Some("https://git.example.com/{project}/-/merge_requests/{id}")
+        );
+        assert_eq!(tracker.uri_template("user").as_deref(), Some("https://git.example.com/{id}"));
+
+        let tracker: IssueTrackerConfig = serde_json::from_str(r#"{"kind": "jira", "project": "APP"}"#).unwrap();
+        assert_eq!(tracker.uri_template("issue"), None);
+        let tracker = IssueTrackerConfig {
+            url: Some("https://jira.example.com".to_string()),
+            ..tracker
+        };
+        assert_eq!(tracker.uri_template("issue").as_deref(), Some("https://jira.example.com/browse/{id}"));
+        assert_eq!(tracker.uri_template("pr"), None);
+
+        assert_eq!(IssueTrackerConfig::default().uri_template("issue"), None);
+    }
+}
@@ -0,0 +1,176 @@
+//! Comparison of two output trees, for previews of documentation changes.
+//!
+//! Pages are the HTML files of a build. A page is changed when its bytes
+//! differ; anchors are the `id` attributes of its elements, so links into a
+//! section that was renamed or removed show up as removed anchors.
+
+use crate::manifest::html_ids;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A page present in both trees with different content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageChange {
+    /// Path of the page relative to the output directory, with `/` separators
+    pub page: String,
+    pub added_anchors: Vec<String>,
+    pub removed_anchors: Vec<String>,
+}
+
+/// Differences between an old and a new output tree
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutputDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<PageChange>,
+}
+
+impl OutputDiff {
+    /// Compare the HTML pages of two output directories
+    pub fn compare(old_dir: &Path, new_dir: &Path) -> Result<Self> {
+        let old_pages = html_pages(old_dir)?;
+        let new_pages = html_pages(new_dir)?;
+
+        let mut diff = OutputDiff::default();
+        for (page, new_path) in &new_pages {
+            let Some(old_path) = old_pages.get(page) else {
+                diff.added.push(page.clone());
+                continue;
+            };
+            let old_html = read_page(old_path)?;
+            let new_html = read_page(new_path)?;
+            if old_html == new_html {
+                continue;
+            }
+            let old_anchors = html_ids(&old_html);
+            let new_anchors = html_ids(&new_html);
+            diff.changed.push(PageChange {
+                page: page.clone(),
+                added_anchors: new_anchors.difference(&old_anchors).cloned().collect(),
+                removed_anchors: old_anchors.difference(&new_anchors).cloned().collect(),
+            });
+        }
+        diff.removed = old_pages
+            .into_keys()
+            .filter(|page| !new_pages.contains_key(page))
+            .collect();
+        Ok(diff)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Markdown summary, suitable for a pull request comment
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from("## Documentation changes\n\n");
+        if self.is_empty() {
+            markdown.push_str("No pages changed.\n");
+            return markdown;
+        }
+        markdown.push_str(&format!(
+            "{} added, {} removed, {} changed\n",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        ));
+
+        for (heading, pages) in [("Added pages", &self.added), ("Removed pages", &self.removed)] {
+            if pages.is_empty() {
+                continue;
+            }
+            markdown.push_str(&format!("\n### {}\n\n", heading));
+            for page in pages {
+                markdown.push_str(&format!("- `{}`\n", page));
+            }
+        }
+
+        if !self.changed.is_empty() {
+            markdown.push_str("\n### Changed pages\n\n| Page | Anchors added | Anchors removed |\n|---|---|---|\n");
+            for change in &self.changed {
+                markdown.push_str(&format!(
+                    "| `{}` | {} | {} |\n",
+                    change.page,
+                    anchor_list(&change.added_anchors),
+                    anchor_list(&change.removed_anchors)
+                ));
+            }
+        }
+        markdown
+    }
+}
+
+/// HTML files below `dir` by their relative path
+fn html_pages(dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    if !dir.is_dir() {
+        anyhow::bail!("Output directory not found: {}", dir.display());
+    }
+    let mut pages = BTreeMap::new();
+    for entry in WalkDir::new(dir) {
+        let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+        let relative = crate::paths::to_slash(path.strip_prefix(dir)?);
+        pages.insert(relative, path.to_path_buf());
+    }
+    Ok(pages)
+}
+
+fn read_page(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read page: {}", path.display()))
+}
+
+fn anchor_list(anchors: &[String]) -> String {
+    if anchors.is_empty() {
+        return "–".to_string();
+    }
+    anchors
+        .iter()
+        .map(|anchor| format!("`#{}`", anchor))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_reports_pages_and_anchors() {
+        let old = tempfile::tempdir().unwrap();
+        let new = tempfile::tempdir().unwrap();
+        let write = |dir: &Path, page: &str, html: &str| {
+            let path = dir.join(page);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, html).unwrap();
+        };
+        write(old.path(), "index.html", r#"<section id="intro"></section>"#);
+        write(new.path(), "index.html", r#"<section id="intro"></section>"#);
+        write(old.path(), "guide/usage.html", r#"<section id="install"><span id="setup"></span></section>"#);
+        write(new.path(), "guide/usage.html", r#"<section id="installation"><span id="setup"></span></section>"#);
+        write(old.path(), "old.html", "");
+        write(new.path(), "guide/new.html", "");
+        write(new.path(), "_static/theme.css", "");
+
+        let diff = OutputDiff::compare(old.path(), new.path()).unwrap();
+        assert_eq!(diff.added, ["guide/new.html"]);
+        assert_eq!(diff.removed, ["old.html"]);
+        assert_eq!(
+            diff.changed,
+            [PageChange {
+                page: "guide/usage.html".to_string(),
+                added_anchors: vec!["installation".to_string()],
+                removed_anchors: vec!["install".to_string()],
+            }]
+        );
+
+        let markdown = diff.to_markdown();
+        assert!(markdown.contains("1 added, 1 removed, 1 changed"));
+        assert!(markdown.contains("| `guide/usage.html` | `#installation` | `#install` |"));
+        assert!(OutputDiff::compare(old.path(), old.path()).unwrap().to_markdown().contains("No pages changed."));
+    }
+}
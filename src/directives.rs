@@ -1,11 +1,17 @@
 use anyhow::{anyhow, Result};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use syntect::highlighting::ThemeSet;
 use syntect::html::highlighted_html_for_string;
 use syntect::parsing::SyntaxSet;
 
+use crate::assets::{Asset, PageAssets};
+use crate::config::{CustomDirectiveConfig, CustomDirectiveTitle};
+use crate::document::RstNode;
+use crate::postprocess::url_scheme;
+use crate::renderer::{highlighted_html_with_classes, SYNTAX_SET, THEME_SET};
+
 /// Directive validation module for comprehensive validation
 pub mod validation;
 
@@ -15,16 +21,34 @@ pub struct Directive {
     pub name: String,
     pub arguments: Vec<String>,
     pub options: HashMap<String, String>,
+    /// Source lines of the content
     pub content: Vec<String>,
+    /// The content parsed as blocks, for processors that
+    /// [parse their content](DirectiveProcessor::parses_content)
+    pub children: Vec<RstNode>,
     pub line_number: usize,
     pub source_file: String,
 }
 
+/// Renders the `children` of a directive to HTML
+pub type RenderChildren<'a> = dyn Fn(&[RstNode]) -> String + 'a;
+
 /// Directive processor trait
 pub trait DirectiveProcessor {
-    fn process(&self, directive: &Directive) -> Result<String>;
+    /// HTML of `directive`; `render` renders its `children`
+    fn process(&self, directive: &Directive, render: &RenderChildren) -> Result<String>;
     fn get_name(&self) -> &str;
     fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType>;
+
+    /// Whether the content is markup, parsed into the directive's `children`
+    fn parses_content(&self) -> bool {
+        false
+    }
+
+    /// Stylesheets and scripts linked from the pages the directive appears on
+    fn page_assets(&self) -> PageAssets {
+        PageAssets::default()
+    }
 }
 
 /// Directive option types
@@ -45,6 +69,22 @@ pub enum DirectiveOptionType {
     Encoding,
 }
 
+/// Admonitions: the specific ones, titled by their name, and the generic
+/// `admonition`, titled by its argument
+pub const ADMONITIONS: &[&str] = &[
+    "attention",
+    "caution",
+    "danger",
+    "error",
+    "hint",
+    "important",
+    "note",
+    "seealso",
+    "tip",
+    "warning",
+    "admonition",
+];
+
 /// Built-in directive processors
 pub struct DirectiveRegistry {
     processors: HashMap<String, Box<dyn DirectiveProcessor + Send + Sync>>,
@@ -76,9 +116,45 @@ impl DirectiveRegistry {
         self.processors.get(name).map(|boxed| boxed.as_ref())
     }
 
-    pub fn process_directive(&self, directive: &Directive) -> Result<String> {
+    /// Names of all registered directives
+    pub fn names(&self) -> Vec<&str> {
+        self.processors.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// Highlight `code-block` content with CSS classes instead of inline
+    /// styles, for pages served with a strict Content-Security-Policy
+    pub fn set_strict_csp(&mut self, strict_csp: bool) {
+        self.register(Box::new(CodeBlockDirective {
+            strict_csp,
+            ..Default::default()
+        }));
+    }
+
+    /// Register the container directives defined in the configuration,
+    /// replacing built-in directives of the same name
+    pub fn register_custom_directives(&mut self, directives: &BTreeMap<String, CustomDirectiveConfig>) {
+        for (name, config) in directives {
+            self.register(Box::new(ConfigDirective {
+                name: name.clone(),
+                config: config.clone(),
+            }));
+        }
+    }
+
+    /// Assets of the pages a directive appears on
+    pub fn page_assets(&self, name: &str) -> PageAssets {
+        self.get(name).map(|processor| processor.page_assets()).unwrap_or_default()
+    }
+
+    /// Whether the content of a directive is markup to parse into its
+    /// `children`
+    pub fn parses_content(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|processor| processor.parses_content())
+    }
+
+    pub fn process_directive(&self, directive: &Directive, render: &RenderChildren) -> Result<String> {
         if let Some(processor) = self.get(&directive.name) {
-            processor.process(directive)
+            processor.process(directive, render)
         } else {
             // Unknown directives produce no visible output
             Ok(String::new())
@@ -87,17 +163,20 @@ impl DirectiveRegistry {
 
     fn register_builtin_directives(&mut self) {
         // Admonition directives
-        self.register(Box::new(AdmonitionDirective::new("note")));
-        self.register(Box::new(AdmonitionDirective::new("warning")));
-        self.register(Box::new(AdmonitionDirective::new("important")));
-        self.register(Box::new(AdmonitionDirective::new("tip")));
-        self.register(Box::new(AdmonitionDirective::new("caution")));
-        self.register(Box::new(AdmonitionDirective::new("danger")));
-        self.register(Box::new(AdmonitionDirective::new("error")));
-        self.register(Box::new(AdmonitionDirective::new("hint")));
-        self.register(Box::new(AdmonitionDirective::new("attention")));
-        self.register(Box::new(AdmonitionDirective::new("seealso")));
-        self.register(Box::new(GenericAdmonitionDirective));
+        for name in ADMONITIONS {
+            self.register(Box::new(AdmonitionDirective::new(name)));
+        }
+        self.register(Box::new(DropdownDirective { name: "dropdown" }));
+        self.register(Box::new(DropdownDirective { name: "collapse" }));
+
+        // Layout and task summary directives, laid out by the renderer
+        self.register(Box::new(GridDirective));
+        self.register(Box::new(GridItemDirective));
+        self.register(Box::new(GridItemCardDirective));
+        self.register(Box::new(ButtonLinkDirective));
+        self.register(Box::new(TabSetDirective));
+        self.register(Box::new(TabItemDirective));
+        self.register(Box::new(TasklistSummaryDirective));
 
         // Code directives
         self.register(Box::new(CodeBlockDirective::default()));
@@ -106,14 +185,22 @@ impl DirectiveRegistry {
 
         // Structure directives
         self.register(Box::new(ToctreeDirective));
+        self.register(Box::new(ContentsDirective));
         self.register(Box::new(IndexDirective));
         self.register(Box::new(OnlyDirective));
         self.register(Box::new(IfConfigDirective));
+        self.register(Box::new(FiglistDirective));
+        self.register(Box::new(TablelistDirective));
 
         // Image directives
         self.register(Box::new(ImageDirective));
         self.register(Box::new(FigureDirective));
 
+        // Media directives
+        self.register(Box::new(VideoDirective));
+        self.register(Box::new(IframeDirective));
+        self.register(Box::new(YoutubeDirective));
+
         // Table directives
         self.register(Box::new(TableDirective));
         self.register(Box::new(CsvTableDirective));
@@ -126,14 +213,27 @@ impl DirectiveRegistry {
         // Math directives
         self.register(Box::new(MathDirective));
 
+        // Diagram directives
+        self.register(Box::new(MermaidDirective));
+
         // Domain-specific directives
         self.register(Box::new(AutoDocDirective));
         self.register(Box::new(AutoModuleDirective));
         self.register(Box::new(AutoClassDirective));
         self.register(Box::new(AutoFunctionDirective));
 
+        // Citation list, placed by the renderer
+        self.register(Box::new(BibliographyDirective));
+
+        // Configuration reference, read from a schema file by the renderer
+        self.register(Box::new(ConfigSchemaDirective));
+
         // Meta directives
         self.register(Box::new(MetaDirective));
+        self.register(Box::new(AuthorDirective { name: "sectionauthor" }));
+        self.register(Box::new(AuthorDirective { name: "moduleauthor" }));
+        self.register(Box::new(AuthorDirective { name: "codeauthor" }));
+        self.register(Box::new(CenteredDirective));
         self.register(Box::new(SidebarDirective));
         self.register(Box::new(TopicDirective));
         self.register(Box::new(RubricDirective));
@@ -150,15 +250,17 @@ impl DirectiveRegistry {
     }
 }
 
+lazy_static::lazy_static! {
+    static ref DIRECTIVE_RE: Regex = Regex::new(r"^\.\. ([a-zA-Z][a-zA-Z0-9_-]*)::\s*(.*?)$").unwrap();
+}
+
 /// Parse a directive from RST text
 pub fn parse_directive(
     text: &str,
     line_number: usize,
     source_file: &str,
 ) -> Result<Option<Directive>> {
-    let directive_regex = Regex::new(r"^\.\. ([a-zA-Z][a-zA-Z0-9_-]*)::\s*(.*?)$")?;
-
-    if let Some(captures) = directive_regex.captures(text) {
+    if let Some(captures) = DIRECTIVE_RE.captures(text) {
         let name = captures.get(1).unwrap().as_str().to_string();
         let args_str = captures.get(2).unwrap().as_str();
 
@@ -174,6 +276,7 @@ pub fn parse_directive(
             arguments,
             options: HashMap::new(),
             content: Vec::new(),
+            children: Vec::new(),
             line_number,
             source_file: source_file.to_string(),
         }))
@@ -196,36 +299,135 @@ impl AdmonitionDirective {
 }
 
 impl DirectiveProcessor for AdmonitionDirective {
-    fn process(&self, directive: &Directive) -> Result<String> {
-        let class = if self.name == "seealso" {
-            "seealso"
-        } else {
-            &self.name
+    /// The title, the argument of a generic `admonition` only, comes
+    /// rendered
+    fn process(&self, directive: &Directive, render: &RenderChildren) -> Result<String> {
+        let argument = directive.arguments.join(" ");
+        let title = match self.name.as_str() {
+            "admonition" if argument.trim().is_empty() => return Err(anyhow!("a title argument is required")),
+            "admonition" => argument.trim(),
+            "note" => "Note",
+            "warning" => "Warning",
+            "important" => "Important",
+            "tip" => "Tip",
+            "caution" => "Caution",
+            "danger" => "Danger",
+            "error" => "Error",
+            "hint" => "Hint",
+            "attention" => "Attention",
+            "seealso" => "See also",
+            name => name,
         };
-        let title = if directive.arguments.is_empty() {
-            match self.name.as_str() {
-                "note" => "Note",
-                "warning" => "Warning",
-                "important" => "Important",
-                "tip" => "Tip",
-                "caution" => "Caution",
-                "danger" => "Danger",
-                "error" => "Error",
-                "hint" => "Hint",
-                "attention" => "Attention",
-                "seealso" => "See also",
-                _ => &self.name,
-            }
+
+        let mut classes = vec!["admonition".to_string()];
+        if self.name != "admonition" {
+            classes.push(self.name.clone());
+        }
+        if let Some(extra) = directive.options.get("class") {
+            classes.extend(extra.split_whitespace().map(str::to_string));
+        }
+        let mut html = format!(
+            "<div class=\"{}\"",
+            html_escape::encode_double_quoted_attribute(&classes.join(" "))
+        );
+        if let Some(name) = directive.options.get("name") {
+            html.push_str(&format!(
+                " id=\"{}\"",
+                html_escape::encode_double_quoted_attribute(&crate::renderer::slugify(name))
+            ));
+        }
+        html.push_str(&format!(">\n<p class=\"admonition-title\">{}</p>\n", title));
+        html.push_str(render(&directive.children).trim_end());
+        html.push_str("\n</div>");
+        Ok(html)
+    }
+
+    fn parses_content(&self) -> bool {
+        true
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        let mut options = HashMap::new();
+        options.insert("class".to_string(), DirectiveOptionType::ClassOption);
+        options.insert("name".to_string(), DirectiveOptionType::String);
+        options
+    }
+}
+
+// Container directives defined in the configuration
+struct ConfigDirective {
+    name: String,
+    config: CustomDirectiveConfig,
+}
+
+impl DirectiveProcessor for ConfigDirective {
+    fn process(&self, directive: &Directive, render: &RenderChildren) -> Result<String> {
+        let missing: Vec<&str> = self
+            .config
+            .required_options
+            .iter()
+            .filter(|option| !directive.options.contains_key(option.as_str()))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!("missing required option(s): {}", missing.join(", ")));
+        }
+        let argument = directive.arguments.first().filter(|argument| !argument.trim().is_empty());
+        let title = match (self.config.title, argument) {
+            (CustomDirectiveTitle::None, Some(_)) => return Err(anyhow!("no argument is allowed")),
+            (CustomDirectiveTitle::Required, None) => return Err(anyhow!("a title argument is required")),
+            (_, Some(argument)) => Some(argument.as_str()),
+            (_, None) => self.config.default_title.as_deref(),
+        };
+
+        // Only a plain tag name can be used as the element
+        let element = self
+            .config
+            .element
+            .as_deref()
+            .filter(|element| !element.is_empty() && element.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or("div");
+        let mut classes = if self.config.classes.is_empty() {
+            vec![self.name.clone()]
         } else {
-            &directive.arguments[0]
+            self.config.classes.clone()
         };
+        if let Some(extra) = directive.options.get("class") {
+            classes.extend(extra.split_whitespace().map(str::to_string));
+        }
 
-        let content = directive.content.join("\n");
+        let mut html = format!(
+            "<{} class=\"{}\"",
+            element,
+            html_escape::encode_double_quoted_attribute(&classes.join(" "))
+        );
+        if let Some(name) = directive.options.get("name") {
+            html.push_str(&format!(
+                " id=\"{}\"",
+                html_escape::encode_double_quoted_attribute(&crate::renderer::slugify(name))
+            ));
+        }
+        html.push('>');
+        if let Some(title) = title {
+            html.push_str(&format!(
+                "<p class=\"{}\">{}</p>",
+                html_escape::encode_double_quoted_attribute(
+                    self.config.title_class.as_deref().unwrap_or("admonition-title")
+                ),
+                html_escape::encode_text(title)
+            ));
+        }
+        html.push_str(render(&directive.children).trim_end());
+        html.push_str(&format!("</{}>", element));
+        Ok(html)
+    }
 
-        Ok(format!(
-            "<div class=\"admonition {}\"><p class=\"admonition-title\">{}</p>{}</div>",
-            class, title, content
-        ))
+    fn parses_content(&self) -> bool {
+        true
     }
 
     fn get_name(&self) -> &str {
@@ -236,31 +438,73 @@ impl DirectiveProcessor for AdmonitionDirective {
         let mut options = HashMap::new();
         options.insert("class".to_string(), DirectiveOptionType::ClassOption);
         options.insert("name".to_string(), DirectiveOptionType::String);
+        for option in self.config.required_options.iter().chain(&self.config.options) {
+            options.insert(option.clone(), DirectiveOptionType::Unchanged);
+        }
         options
     }
+    fn page_assets(&self) -> PageAssets {
+        PageAssets {
+            css_files: self.config.css_files.iter().map(Asset::new).collect(),
+            js_files: self.config.js_files.iter().map(Asset::new).collect(),
+            ..PageAssets::default()
+        }
+    }
+}
+
+// Collapsible block; the title argument and the content are HTML
+struct DropdownDirective {
+    name: &'static str,
 }
 
-// Generic Admonition Directive
-struct GenericAdmonitionDirective;
+impl DirectiveProcessor for DropdownDirective {
+    /// The title comes rendered
+    fn process(&self, directive: &Directive, render: &RenderChildren) -> Result<String> {
+        let mut classes = vec!["dropdown".to_string()];
+        if let Some(extra) = directive.options.get("class") {
+            classes.extend(extra.split_whitespace().map(str::to_string));
+        }
 
-impl DirectiveProcessor for GenericAdmonitionDirective {
-    fn process(&self, directive: &Directive) -> Result<String> {
-        let default_title = "Admonition".to_string();
-        let title = directive.arguments.first().unwrap_or(&default_title);
-        let content = directive.content.join("\n");
+        let mut html = format!(
+            "<details class=\"{}\"",
+            html_escape::encode_double_quoted_attribute(&classes.join(" "))
+        );
+        if let Some(name) = directive.options.get("name") {
+            html.push_str(&format!(
+                " id=\"{}\"",
+                html_escape::encode_double_quoted_attribute(&crate::renderer::slugify(name))
+            ));
+        }
+        if directive.options.contains_key("open") {
+            html.push_str(" open");
+        }
+        html.push_str(">\n<summary class=\"dropdown-title\">");
+        if let Some(icon) = directive.options.get("icon").filter(|icon| !icon.trim().is_empty()) {
+            html.push_str(&format!(
+                "<span class=\"dropdown-icon {}\" aria-hidden=\"true\"></span>",
+                html_escape::encode_double_quoted_attribute(icon.trim())
+            ));
+        }
+        let title = directive.arguments.join(" ");
+        html.push_str(if title.trim().is_empty() { "Details" } else { title.trim() });
+        html.push_str("</summary>\n<div class=\"dropdown-body\">\n");
+        html.push_str(render(&directive.children).trim_end());
+        html.push_str("\n</div>\n</details>");
+        Ok(html)
+    }
 
-        Ok(format!(
-            "<div class=\"admonition admonition-generic\"><p class=\"admonition-title\">{}</p>{}</div>",
-            title, content
-        ))
+    fn parses_content(&self) -> bool {
+        true
     }
 
     fn get_name(&self) -> &str {
-        "admonition"
+        self.name
     }
 
     fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
         let mut options = HashMap::new();
+        options.insert("open".to_string(), DirectiveOptionType::Flag);
+        options.insert("icon".to_string(), DirectiveOptionType::String);
         options.insert("class".to_string(), DirectiveOptionType::ClassOption);
         options.insert("name".to_string(), DirectiveOptionType::String);
         options
@@ -269,15 +513,17 @@ impl DirectiveProcessor for GenericAdmonitionDirective {
 
 // Code Block Directive
 struct CodeBlockDirective {
-    syntax_set: SyntaxSet,
-    theme_set: ThemeSet,
+    syntax_set: &'static SyntaxSet,
+    theme_set: &'static ThemeSet,
+    strict_csp: bool,
 }
 
 impl Default for CodeBlockDirective {
     fn default() -> Self {
         Self {
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
+            syntax_set: &SYNTAX_SET,
+            theme_set: &THEME_SET,
+            strict_csp: false,
         }
     }
 }
@@ -292,7 +538,12 @@ impl CodeBlockDirective {
             .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
 
         // Generate highlighted HTML
-        match highlighted_html_for_string(code, &self.syntax_set, syntax, theme) {
+        let highlighted = if self.strict_csp {
+            highlighted_html_with_classes(code, self.syntax_set, syntax)
+        } else {
+            highlighted_html_for_string(code, self.syntax_set, syntax, theme)
+        };
+        match highlighted {
             Ok(html) => html,
             Err(_) => {
                 // Fallback to plain code block if highlighting fails
@@ -304,10 +555,14 @@ impl CodeBlockDirective {
 }
 
 impl DirectiveProcessor for CodeBlockDirective {
-    fn process(&self, directive: &Directive) -> Result<String> {
+    fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
         let default_language = "text".to_string();
         let language = directive.arguments.first().unwrap_or(&default_language);
-        let _linenos = directive.options.contains_key("linenos");
+        let first_line_number = match directive.options.get("lineno-start") {
+            Some(start) => Some(start.trim().parse().unwrap_or(1)),
+            None if directive.options.contains_key("linenos") => Some(1),
+            None => None,
+        };
         let _emphasize_lines = directive.options.get("emphasize-lines");
         let caption = directive.options.get("caption");
         let _name = directive.options.get("name");
@@ -324,7 +579,15 @@ impl DirectiveProcessor for CodeBlockDirective {
         }
 
         // Use syntect for syntax highlighting
-        let highlighted = self.highlight_code(&content, language);
+        let mut highlighted = self.highlight_code(&content, language);
+        if first_line_number.is_some() {
+            highlighted = crate::renderer::decorate_code_lines(
+                &highlighted,
+                first_line_number,
+                &Default::default(),
+                "",
+            );
+        }
         html.push_str(&format!(
             "<div class=\"highlight-{} notranslate\">{}</div>",
             language,
@@ -355,7 +618,7 @@ impl DirectiveProcessor for CodeBlockDirective {
 struct LiteralIncludeDirective;
 
 impl DirectiveProcessor for LiteralIncludeDirective {
-    fn process(&self, directive: &Directive) -> Result<String> {
+    fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
         let filename = directive
             .arguments
             .first()
@@ -432,6 +695,7 @@ impl DirectiveProcessor for LiteralIncludeDirective {
         options.insert("tab-width".to_string(), DirectiveOptionType::Integer);
         options.insert("encoding".to_string(), DirectiveOptionType::Encoding);
         options.insert("pyobject".to_string(), DirectiveOptionType::String);
+        options.insert("object".to_string(), DirectiveOptionType::String);
         options.insert("caption".to_string(), DirectiveOptionType::String);
         options.insert("name".to_string(), DirectiveOptionType::String);
         options.insert("class".to_string(), DirectiveOptionType::ClassOption);
@@ -444,11 +708,10 @@ impl DirectiveProcessor for LiteralIncludeDirective {
 struct HighlightDirective;
 
 impl DirectiveProcessor for HighlightDirective {
-    fn process(&self, directive: &Directive) -> Result<String> {
-        let default_language = "text".to_string();
-        let language = directive.arguments.first().unwrap_or(&default_language);
-        // This directive sets the highlighting language for subsequent code blocks
-        Ok(format!("<!-- highlight language set to {} -->", language))
+    fn process(&self, _directive: &Directive, _render: &RenderChildren) -> Result<String> {
+        // The renderer applies the language and line number threshold to the
+        // code blocks that follow; the directive itself produces no output
+        Ok(String::new())
     }
 
     fn get_name(&self) -> &str {
@@ -471,7 +734,7 @@ macro_rules! stub_directive {
         struct $name;
 
         impl DirectiveProcessor for $name {
-            fn process(&self, directive: &Directive) -> Result<String> {
+            fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
                 Ok(format!(
                     "<!-- {} directive: {} -->",
                     $directive_name,
@@ -494,7 +757,7 @@ macro_rules! stub_directive {
 struct ToctreeDirective;
 
 impl DirectiveProcessor for ToctreeDirective {
-    fn process(&self, directive: &Directive) -> Result<String> {
+    fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
         let caption = directive.options.get("caption");
         let hidden = directive.options.contains_key("hidden");
         let _maxdepth = directive.options.get("maxdepth");
@@ -515,7 +778,7 @@ impl DirectiveProcessor for ToctreeDirective {
 
         // Start wrapper div (with "compound" class like Sphinx)
         if hidden {
-            html.push_str("<div class=\"toctree-wrapper compound\" style=\"display: none;\">\n");
+            html.push_str("<div class=\"toctree-wrapper compound toctree-hidden\">\n");
         } else {
             html.push_str("<div class=\"toctree-wrapper compound\">\n");
         }
@@ -583,21 +846,391 @@ impl DirectiveProcessor for ToctreeDirective {
     }
 }
 
-stub_directive!(IndexDirective, "index");
+// Contents Directive - in-page table of contents, rendered by the renderer
+// since it lists the sections of the whole document
+struct ContentsDirective;
+
+impl DirectiveProcessor for ContentsDirective {
+    fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
+        Ok(format!(
+            "<!-- contents directive: {} -->",
+            directive.arguments.join(" ")
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        "contents"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        let mut options = HashMap::new();
+        options.insert("depth".to_string(), DirectiveOptionType::Integer);
+        options.insert("local".to_string(), DirectiveOptionType::Flag);
+        options.insert("backlinks".to_string(), DirectiveOptionType::String);
+        options.insert("class".to_string(), DirectiveOptionType::ClassOption);
+        options
+    }
+}
+
+// Meta Directive - per-page <meta> tags. The parser records the fields on
+// the document and the builder puts them in the page head.
+struct MetaDirective;
+
+impl DirectiveProcessor for MetaDirective {
+    fn process(&self, _directive: &Directive, _render: &RenderChildren) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn get_name(&self) -> &str {
+        "meta"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        HashMap::new()
+    }
+}
+
+// Index Directive - index entries. The parser records the entries on the
+// document for the general index and the renderer places their anchor.
+struct IndexDirective;
+
+impl DirectiveProcessor for IndexDirective {
+    fn process(&self, _directive: &Directive, _render: &RenderChildren) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn get_name(&self) -> &str {
+        "index"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        let mut options = HashMap::new();
+        options.insert("name".to_string(), DirectiveOptionType::String);
+        options
+    }
+}
+
+// Author Directives - sectionauthor, moduleauthor and codeauthor. The parser
+// records the names on the document and the renderer shows them when
+// `show_authors` is set.
+struct AuthorDirective {
+    name: &'static str,
+}
+
+impl DirectiveProcessor for AuthorDirective {
+    fn process(&self, _directive: &Directive, _render: &RenderChildren) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        HashMap::new()
+    }
+}
+
+// Centered Directive - a centered, bold line of text
+struct CenteredDirective;
+
+impl DirectiveProcessor for CenteredDirective {
+    fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
+        Ok(format!(
+            "<p class=\"centered\"><strong>{}</strong></p>",
+            html_escape::encode_text(&directive.arguments.join(" "))
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        "centered"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        HashMap::new()
+    }
+}
+
 stub_directive!(OnlyDirective, "only");
 stub_directive!(IfConfigDirective, "ifconfig");
+stub_directive!(FiglistDirective, "figlist");
+stub_directive!(TablelistDirective, "tablelist");
 stub_directive!(ImageDirective, "image");
 stub_directive!(FigureDirective, "figure");
 stub_directive!(TableDirective, "table");
 stub_directive!(CsvTableDirective, "csv-table");
 stub_directive!(ListTableDirective, "list-table");
 stub_directive!(MathDirective, "math");
+stub_directive!(GridDirective, "grid");
+stub_directive!(GridItemDirective, "grid-item");
+stub_directive!(GridItemCardDirective, "grid-item-card");
+stub_directive!(ButtonLinkDirective, "button-link");
+stub_directive!(TabSetDirective, "tab-set");
+stub_directive!(TabItemDirective, "tab-item");
+stub_directive!(MermaidDirective, "mermaid");
+stub_directive!(TasklistSummaryDirective, "tasklist-summary");
+stub_directive!(BibliographyDirective, "bibliography");
+stub_directive!(ConfigSchemaDirective, "config-schema");
+
+// Video Directive - HTML5 video player. Local files are resolved and copied
+// to the output by the renderer, which passes their output URL as argument.
+struct VideoDirective;
+
+impl DirectiveProcessor for VideoDirective {
+    fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
+        let src = directive
+            .arguments
+            .first()
+            .filter(|src| !src.is_empty())
+            .ok_or_else(|| anyhow!("video directive requires a file path or URL"))?;
+        let options = &directive.options;
+
+        let mut attributes = Vec::new();
+        if !options.contains_key("nocontrols") {
+            attributes.push("controls".to_string());
+        }
+        for flag in ["autoplay", "loop", "muted", "playsinline"] {
+            if options.contains_key(flag) {
+                attributes.push(flag.to_string());
+            }
+        }
+        for option in ["poster", "width", "height", "preload"] {
+            if let Some(value) = options.get(option) {
+                attributes.push(format!(
+                    "{}=\"{}\"",
+                    option,
+                    html_escape::encode_double_quoted_attribute(value)
+                ));
+            }
+        }
+
+        let mut classes = vec!["video".to_string()];
+        if let Some(align) = options.get("align") {
+            classes.push(format!("align-{}", align));
+        }
+        if let Some(class) = options.get("class") {
+            classes.extend(class.split_whitespace().map(String::from));
+        }
+
+        let src = html_escape::encode_double_quoted_attribute(src);
+        let mime_type = video_mime_type(&src)
+            .map(|mime| format!(" type=\"{}\"", mime))
+            .unwrap_or_default();
+        let fallback = match options.get("alt") {
+            Some(alt) => html_escape::encode_text(alt).to_string(),
+            None => format!("<a href=\"{}\">Download the video</a>", src),
+        };
+        let video = format!(
+            "<video class=\"{}\" {}>\n<source src=\"{}\"{} />\n{}\n</video>",
+            classes.join(" "),
+            attributes.join(" "),
+            src,
+            mime_type,
+            fallback
+        );
+
+        Ok(match options.get("caption") {
+            Some(caption) => format!(
+                "<figure class=\"video-figure\">\n{}\n<figcaption>{}</figcaption>\n</figure>",
+                video,
+                html_escape::encode_text(caption)
+            ),
+            None => video,
+        })
+    }
+
+    fn get_name(&self) -> &str {
+        "video"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        let mut options = HashMap::new();
+        for flag in ["autoplay", "loop", "muted", "playsinline", "nocontrols"] {
+            options.insert(flag.to_string(), DirectiveOptionType::Flag);
+        }
+        options.insert("poster".to_string(), DirectiveOptionType::Path);
+        options.insert("width".to_string(), DirectiveOptionType::LengthOrPercentage);
+        options.insert("height".to_string(), DirectiveOptionType::LengthOrPercentage);
+        options.insert(
+            "preload".to_string(),
+            DirectiveOptionType::Choice(vec![
+                "auto".to_string(),
+                "metadata".to_string(),
+                "none".to_string(),
+            ]),
+        );
+        options.insert("alt".to_string(), DirectiveOptionType::String);
+        options.insert("caption".to_string(), DirectiveOptionType::String);
+        options.insert("align".to_string(), DirectiveOptionType::String);
+        options.insert("class".to_string(), DirectiveOptionType::ClassOption);
+        options
+    }
+}
+
+/// MIME type of a video file, from its extension
+pub fn video_mime_type(path: &str) -> Option<&'static str> {
+    let extension = path.rsplit('.').next()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "mp4" | "m4v" => Some("video/mp4"),
+        "webm" => Some("video/webm"),
+        "ogg" | "ogv" => Some("video/ogg"),
+        "mov" => Some("video/quicktime"),
+        _ => None,
+    }
+}
+
+/// Sandbox of embedded frames unless the `sandbox` option says otherwise:
+/// scripts run, but the frame cannot navigate the page or submit forms.
+/// The frame gets its own origin only with `:allow-same-origin:`, since a
+/// same-origin page that runs scripts can lift its sandbox.
+const DEFAULT_IFRAME_SANDBOX: &str = "allow-scripts allow-popups allow-presentation";
+const SAME_ORIGIN_IFRAME_SANDBOX: &str = "allow-scripts allow-same-origin allow-popups allow-presentation";
+
+/// Sandboxed, lazily loaded `<iframe>` wrapped in an `iframe-embed` div.
+/// `same_origin` keeps the frame's origin in the default sandbox.
+fn iframe_html(
+    src: &str,
+    title: &str,
+    options: &HashMap<String, String>,
+    allow: Option<&str>,
+    same_origin: bool,
+) -> String {
+    let attribute = |value: &str| html_escape::encode_double_quoted_attribute(value).to_string();
+    let mut attributes = vec![
+        format!("src=\"{}\"", attribute(src)),
+        format!("title=\"{}\"", attribute(title)),
+    ];
+    for option in ["width", "height"] {
+        if let Some(value) = options.get(option) {
+            attributes.push(format!("{}=\"{}\"", option, attribute(value)));
+        }
+    }
+    let sandbox = match options.get("sandbox") {
+        Some(sandbox) => sandbox.as_str(),
+        None if same_origin || options.contains_key("allow-same-origin") => SAME_ORIGIN_IFRAME_SANDBOX,
+        None => DEFAULT_IFRAME_SANDBOX,
+    };
+    attributes.push(format!("sandbox=\"{}\"", attribute(sandbox)));
+    if let Some(allow) = options.get("allow").map(|s| s.as_str()).or(allow) {
+        attributes.push(format!("allow=\"{}\"", attribute(allow)));
+    }
+    if options.contains_key("allowfullscreen") || allow.is_some() {
+        attributes.push("allowfullscreen".to_string());
+    }
+    attributes.push("loading=\"lazy\"".to_string());
+    attributes.push("referrerpolicy=\"strict-origin-when-cross-origin\"".to_string());
+
+    let mut classes = vec!["iframe-embed".to_string()];
+    if let Some(class) = options.get("class") {
+        classes.extend(class.split_whitespace().map(String::from));
+    }
+    format!(
+        "<div class=\"{}\"><iframe {}></iframe></div>",
+        classes.join(" "),
+        attributes.join(" ")
+    )
+}
+
+fn iframe_option_spec() -> HashMap<String, DirectiveOptionType> {
+    let mut options = HashMap::new();
+    options.insert("title".to_string(), DirectiveOptionType::String);
+    options.insert("width".to_string(), DirectiveOptionType::LengthOrPercentage);
+    options.insert("height".to_string(), DirectiveOptionType::LengthOrPercentage);
+    options.insert("sandbox".to_string(), DirectiveOptionType::Unchanged);
+    options.insert("allow".to_string(), DirectiveOptionType::Unchanged);
+    options.insert("allowfullscreen".to_string(), DirectiveOptionType::Flag);
+    options.insert("allow-same-origin".to_string(), DirectiveOptionType::Flag);
+    options.insert("class".to_string(), DirectiveOptionType::ClassOption);
+    options
+}
+
+// Iframe Directive - embeds an external page in a sandboxed frame
+struct IframeDirective;
+
+impl DirectiveProcessor for IframeDirective {
+    fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
+        let src = directive
+            .arguments
+            .first()
+            .filter(|src| !src.is_empty())
+            .ok_or_else(|| anyhow!("iframe directive requires a URL"))?;
+        // Other schemes, like `javascript:` or `data:`, would run in the page
+        if let Some(scheme) = url_scheme(src).filter(|scheme| scheme != "http" && scheme != "https") {
+            return Err(anyhow!("iframe source must be an http or https URL, not '{}:'", scheme));
+        }
+        let title = directive.options.get("title").map_or(src.as_str(), |t| t.as_str());
+        Ok(iframe_html(src, title, &directive.options, None, false))
+    }
+
+    fn get_name(&self) -> &str {
+        "iframe"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        iframe_option_spec()
+    }
+}
+
+// YouTube Directive - privacy-enhanced YouTube embed
+struct YoutubeDirective;
+
+impl DirectiveProcessor for YoutubeDirective {
+    fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
+        let id = directive
+            .arguments
+            .first()
+            .and_then(|video| youtube_video_id(video))
+            .ok_or_else(|| anyhow!("youtube directive requires a video ID or URL"))?;
+        let mut src = format!("https://www.youtube-nocookie.com/embed/{}", id);
+        if let Some(start) = directive.options.get("start") {
+            src.push_str(&format!("?start={}", start.trim()));
+        }
+        let title = directive
+            .options
+            .get("title")
+            .map_or("YouTube video player", |t| t.as_str());
+        let allow = "accelerometer; autoplay; clipboard-write; encrypted-media; gyroscope; picture-in-picture";
+        // The player needs its origin, which is never the documentation's
+        Ok(iframe_html(&src, title, &directive.options, Some(allow), true))
+    }
+
+    fn get_name(&self) -> &str {
+        "youtube"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        let mut options = iframe_option_spec();
+        options.insert("start".to_string(), DirectiveOptionType::Integer);
+        options
+    }
+}
+
+/// Video ID from a bare ID or a `youtube.com/watch?v=`, `youtu.be/` or
+/// `youtube.com/embed/` URL
+pub fn youtube_video_id(video: &str) -> Option<String> {
+    let video = video.trim();
+    let id = if let Some((_, query)) = video.split_once("v=") {
+        query.split('&').next().unwrap_or("")
+    } else if let Some((_, rest)) = video
+        .split_once("youtu.be/")
+        .or_else(|| video.split_once("/embed/"))
+    {
+        rest.split(['?', '/']).next().unwrap_or("")
+    } else {
+        video
+    };
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| id.to_string())
+}
 
 // Include Directive - includes RST content from another file
 struct IncludeDirective;
 
 impl DirectiveProcessor for IncludeDirective {
-    fn process(&self, directive: &Directive) -> Result<String> {
+    fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
         // The actual processing is done in the renderer, but we return a placeholder here
         // in case it's called through the registry (shouldn't happen with current code flow)
         Ok(format!(
@@ -628,20 +1261,25 @@ impl DirectiveProcessor for IncludeDirective {
     }
 }
 
+/// Output format of the HTML builder, as named in `raw` directives
+pub const RAW_OUTPUT_FORMAT: &str = "html";
+
+/// Whether raw content for the space-separated `formats` (like `latex html`)
+/// belongs in the output of this builder
+pub fn raw_format_matches(formats: &str) -> bool {
+    formats
+        .split_whitespace()
+        .any(|format| format.eq_ignore_ascii_case(RAW_OUTPUT_FORMAT))
+}
+
 // Raw Directive - inserts raw content in a specific format (html, latex, etc.)
 struct RawDirective;
 
 impl DirectiveProcessor for RawDirective {
-    fn process(&self, directive: &Directive) -> Result<String> {
-        // The first argument is the format (html, latex, etc.)
-        let format = directive
-            .arguments
-            .first()
-            .map(|s| s.to_lowercase())
-            .unwrap_or_default();
-
-        // Only output content if the format is html
-        if format == "html" {
+    fn process(&self, directive: &Directive, _render: &RenderChildren) -> Result<String> {
+        // Only output content meant for this builder; latex, text and other
+        // formats are dropped
+        if raw_format_matches(&directive.arguments.join(" ")) {
             // Join the content lines and return them directly without escaping
             Ok(directive.content.join("\n"))
         } else {
@@ -666,7 +1304,6 @@ stub_directive!(AutoDocDirective, "autodoc");
 stub_directive!(AutoModuleDirective, "automodule");
 stub_directive!(AutoClassDirective, "autoclass");
 stub_directive!(AutoFunctionDirective, "autofunction");
-stub_directive!(MetaDirective, "meta");
 stub_directive!(SidebarDirective, "sidebar");
 stub_directive!(TopicDirective, "topic");
 stub_directive!(RubricDirective, "rubric");
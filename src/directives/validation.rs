@@ -14,6 +14,36 @@ pub use builtin::*;
 pub use parser::*;
 pub use roles::*;
 
+/// Names among `candidates` that look like a typo of `name`, closest first.
+///
+/// Candidates are close when their Jaro-Winkler similarity is high or when
+/// one name contains the other, and are ranked by Levenshtein distance.
+pub fn similar_names<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    const MIN_SIMILARITY: f64 = 0.85;
+    const MAX_SUGGESTIONS: usize = 3;
+
+    let mut matches: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .filter(|candidate| {
+            let overlaps = name.len().min(candidate.len()) >= 3
+                && (candidate.contains(name) || name.contains(candidate));
+            overlaps || strsim::jaro_winkler(name, candidate) >= MIN_SIMILARITY
+        })
+        .map(|candidate| (strsim::levenshtein(name, candidate), candidate))
+        .collect();
+    matches.sort_unstable();
+    matches.dedup();
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
 /// Source location information for diagnostics
 #[derive(Debug, Clone, PartialEq)]
 pub struct SourceLocation {
@@ -125,12 +155,10 @@ pub trait DirectiveValidator: Send + Sync {
                     self.name()
                 ));
 
-                // Suggest similar options
-                for valid_option in &valid_options {
-                    if valid_option.contains(option) || option.contains(valid_option) {
-                        suggestions.push(format!("Did you mean '{}'?", valid_option));
-                        break;
-                    }
+                // Suggest the closest valid option
+                let options = valid_options.iter().map(String::as_str);
+                if let Some(valid_option) = similar_names(option, options).first() {
+                    suggestions.push(format!("Did you mean '{}'?", valid_option));
                 }
             }
         }
@@ -205,7 +233,11 @@ impl DirectiveRegistry {
         self.register_validator(Box::new(builtin::WarningValidator::new()));
         self.register_validator(Box::new(builtin::ImageValidator::new()));
         self.register_validator(Box::new(builtin::FigureValidator::new()));
+        self.register_validator(Box::new(builtin::VideoValidator::new()));
+        self.register_validator(Box::new(builtin::IframeValidator::new()));
+        self.register_validator(Box::new(builtin::YoutubeValidator::new()));
         self.register_validator(Box::new(builtin::TocTreeValidator::new()));
+        self.register_validator(Box::new(builtin::ContentsValidator::new()));
         self.register_validator(Box::new(builtin::IncludeValidator::new()));
         self.register_validator(Box::new(builtin::LiteralIncludeValidator::new()));
         self.register_validator(Box::new(builtin::AdmonitionValidator::new()));
@@ -228,12 +260,9 @@ impl DirectiveRegistry {
                 let mut suggestions = vec![format!("Unknown directive '{}'", directive.name)];
 
                 // Suggest similar directive names
-                for validator_name in self.validators.keys() {
-                    if validator_name.contains(&directive.name)
-                        || directive.name.contains(validator_name)
-                    {
-                        suggestions.push(format!("Did you mean '{}'?", validator_name));
-                    }
+                let names = self.validators.keys().map(String::as_str);
+                for validator_name in similar_names(&directive.name, names) {
+                    suggestions.push(format!("Did you mean '{}'?", validator_name));
                 }
 
                 suggestions
@@ -290,6 +319,14 @@ impl RoleRegistry {
         self.register_validator(Box::new(roles::KbdRoleValidator::new()));
         self.register_validator(Box::new(roles::MenuSelectionRoleValidator::new()));
         self.register_validator(Box::new(roles::GuiLabelRoleValidator::new()));
+        self.register_validator(Box::new(roles::TermRoleValidator::new()));
+        self.register_validator(Box::new(roles::EnvVarRoleValidator::new()));
+        self.register_validator(Box::new(roles::OptionRoleValidator::new()));
+        self.register_validator(Box::new(roles::NumRefRoleValidator::new()));
+        self.register_validator(Box::new(roles::KeywordRoleValidator::new()));
+        for validator in roles::DomainRoleValidator::builtin() {
+            self.register_validator(Box::new(validator));
+        }
     }
 
     /// Validates a role
@@ -308,10 +345,9 @@ impl RoleRegistry {
                 let mut suggestions = vec![format!("Unknown role '{}'", role.name)];
 
                 // Suggest similar role names
-                for validator_name in self.validators.keys() {
-                    if validator_name.contains(&role.name) || role.name.contains(validator_name) {
-                        suggestions.push(format!("Did you mean '{}'?", validator_name));
-                    }
+                let names = self.validators.keys().map(String::as_str);
+                for validator_name in similar_names(&role.name, names) {
+                    suggestions.push(format!("Did you mean '{}'?", validator_name));
                 }
 
                 suggestions
@@ -612,6 +648,39 @@ mod tests {
         assert!(registry.is_role_registered("doc"));
         assert!(registry.is_role_registered("ref"));
         assert!(registry.is_role_registered("download"));
+        assert!(registry.is_role_registered("py:func"));
+        assert!(registry.is_role_registered("class"));
+    }
+
+    #[test]
+    fn test_similar_names() {
+        let candidates = ["code-block", "code", "note", "toctree", "literalinclude"];
+        assert_eq!(
+            similar_names("code-blok", candidates),
+            vec!["code-block", "code"]
+        );
+        assert_eq!(similar_names("toctre", candidates), vec!["toctree"]);
+        assert_eq!(similar_names("ntoe", candidates), vec!["note"]);
+        assert!(similar_names("figure", candidates).is_empty());
+    }
+
+    #[test]
+    fn test_directive_suggestions_find_typos() {
+        let registry = DirectiveRegistry::with_builtin_validators();
+        let directive = ParsedDirective {
+            name: "code-blok".to_string(),
+            arguments: vec![],
+            options: HashMap::new(),
+            content: String::new(),
+            location: SourceLocation {
+                file: "test.rst".to_string(),
+                line: 1,
+                column: 1,
+            },
+        };
+
+        let suggestions = registry.get_directive_suggestions(&directive);
+        assert_eq!(suggestions[1], "Did you mean 'code-block'?");
     }
 
     #[test]
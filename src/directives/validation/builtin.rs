@@ -116,18 +116,12 @@ impl DirectiveValidator for NoteValidator {
     }
 
     fn validate(&self, directive: &ParsedDirective) -> DirectiveValidationResult {
-        // Note directive should have content
-        if directive.content.trim().is_empty() {
+        // Note directive should have content. It takes no arguments:
+        // text on the directive line starts the body, as in docutils
+        if directive.content.trim().is_empty() && directive.arguments.is_empty() {
             return DirectiveValidationResult::Error("Note directive requires content".to_string());
         }
 
-        // Note directive typically doesn't take arguments
-        if !directive.arguments.is_empty() {
-            return DirectiveValidationResult::Warning(
-                "Note directive does not expect arguments".to_string(),
-            );
-        }
-
         // Validate options
         for option in directive.options.keys() {
             match option.as_str() {
@@ -179,20 +173,14 @@ impl DirectiveValidator for WarningValidator {
     }
 
     fn validate(&self, directive: &ParsedDirective) -> DirectiveValidationResult {
-        // Warning directive should have content
-        if directive.content.trim().is_empty() {
+        // Warning directive should have content. It takes no arguments:
+        // text on the directive line starts the body, as in docutils
+        if directive.content.trim().is_empty() && directive.arguments.is_empty() {
             return DirectiveValidationResult::Error(
                 "Warning directive requires content".to_string(),
             );
         }
 
-        // Warning directive typically doesn't take arguments
-        if !directive.arguments.is_empty() {
-            return DirectiveValidationResult::Warning(
-                "Warning directive does not expect arguments".to_string(),
-            );
-        }
-
         // Validate options
         for option in directive.options.keys() {
             match option.as_str() {
@@ -401,6 +389,276 @@ impl DirectiveValidator for FigureValidator {
     }
 }
 
+/// Validator for video directive
+#[derive(Default)]
+pub struct VideoValidator;
+
+impl VideoValidator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl DirectiveValidator for VideoValidator {
+    fn name(&self) -> &str {
+        "video"
+    }
+
+    fn validate(&self, directive: &ParsedDirective) -> DirectiveValidationResult {
+        // Video directive requires a path or URL argument
+        let Some(video_path) = directive.arguments.first().filter(|path| !path.is_empty()) else {
+            return DirectiveValidationResult::Error(
+                "Video directive requires a path or URL argument".to_string(),
+            );
+        };
+
+        if crate::directives::video_mime_type(video_path).is_none() {
+            return DirectiveValidationResult::Warning(format!(
+                "Unusual video format: {} (browsers play mp4, webm and ogg)",
+                video_path
+            ));
+        }
+
+        for (option, value) in &directive.options {
+            match option.as_str() {
+                "autoplay" | "loop" | "muted" | "playsinline" | "nocontrols" => {
+                    if !value.is_empty() {
+                        return DirectiveValidationResult::Error(format!(
+                            "{} option should not have a value",
+                            option
+                        ));
+                    }
+                }
+                "preload" => {
+                    if !["auto", "metadata", "none"].contains(&value.as_str()) {
+                        return DirectiveValidationResult::Error(format!(
+                            "Invalid preload: {}. Valid options: auto, metadata, none",
+                            value
+                        ));
+                    }
+                }
+                "poster" | "width" | "height" | "alt" | "caption" | "align" | "class" => {
+                    if value.is_empty() {
+                        return DirectiveValidationResult::Error(format!(
+                            "{} option requires a value",
+                            option
+                        ));
+                    }
+                }
+                _ => {
+                    return DirectiveValidationResult::Warning(format!(
+                        "Unknown option '{}' for video directive",
+                        option
+                    ));
+                }
+            }
+        }
+
+        // Browsers only autoplay muted videos
+        if directive.options.contains_key("autoplay") && !directive.options.contains_key("muted") {
+            return DirectiveValidationResult::Warning(
+                "autoplay without muted is blocked by most browsers".to_string(),
+            );
+        }
+
+        DirectiveValidationResult::Valid
+    }
+
+    fn expected_arguments(&self) -> Vec<String> {
+        vec!["video_uri".to_string()]
+    }
+
+    fn valid_options(&self) -> Vec<String> {
+        vec![
+            "autoplay".to_string(),
+            "loop".to_string(),
+            "muted".to_string(),
+            "playsinline".to_string(),
+            "nocontrols".to_string(),
+            "preload".to_string(),
+            "poster".to_string(),
+            "width".to_string(),
+            "height".to_string(),
+            "alt".to_string(),
+            "caption".to_string(),
+            "align".to_string(),
+            "class".to_string(),
+        ]
+    }
+
+    fn requires_content(&self) -> bool {
+        false
+    }
+
+    fn allows_content(&self) -> bool {
+        false
+    }
+}
+
+/// Validator for iframe directive
+#[derive(Default)]
+pub struct IframeValidator;
+
+impl IframeValidator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Options shared by the iframe and youtube directives
+fn validate_embed_options(directive: &ParsedDirective) -> DirectiveValidationResult {
+    // Frames need an accessible name
+    if directive.options.get("title").is_none_or(|title| title.trim().is_empty()) {
+        return DirectiveValidationResult::Error(format!(
+            "{} directive requires a :title: option describing the embedded content",
+            directive.name
+        ));
+    }
+
+    for (option, value) in &directive.options {
+        match option.as_str() {
+            "title" | "sandbox" | "allow" | "class" => {}
+            "allowfullscreen" => {
+                if !value.is_empty() {
+                    return DirectiveValidationResult::Error(
+                        "allowfullscreen option should not have a value".to_string(),
+                    );
+                }
+            }
+            "width" | "height" => {
+                let number = value.trim_end_matches("px").trim_end_matches('%');
+                if number.parse::<f32>().is_err() {
+                    return DirectiveValidationResult::Error(format!(
+                        "{} must be a number of pixels or a percentage",
+                        option
+                    ));
+                }
+            }
+            "start" if directive.name == "youtube" => {
+                if value.parse::<u32>().is_err() {
+                    return DirectiveValidationResult::Error(
+                        "start must be a whole number of seconds".to_string(),
+                    );
+                }
+            }
+            _ => {
+                return DirectiveValidationResult::Warning(format!(
+                    "Unknown option '{}' for {} directive",
+                    option, directive.name
+                ));
+            }
+        }
+    }
+
+    DirectiveValidationResult::Valid
+}
+
+impl DirectiveValidator for IframeValidator {
+    fn name(&self) -> &str {
+        "iframe"
+    }
+
+    fn validate(&self, directive: &ParsedDirective) -> DirectiveValidationResult {
+        let Some(url) = directive.arguments.first().filter(|url| !url.is_empty()) else {
+            return DirectiveValidationResult::Error(
+                "Iframe directive requires a URL argument".to_string(),
+            );
+        };
+        if url.starts_with("javascript:") || url.starts_with("data:") {
+            return DirectiveValidationResult::Error(format!(
+                "Iframe URL must be an http(s) or relative URL, got {}",
+                url
+            ));
+        }
+        if url.starts_with("http://") {
+            return DirectiveValidationResult::Warning(format!(
+                "Iframe URL {} is not HTTPS and will be blocked on HTTPS sites",
+                url
+            ));
+        }
+
+        validate_embed_options(directive)
+    }
+
+    fn expected_arguments(&self) -> Vec<String> {
+        vec!["url".to_string()]
+    }
+
+    fn valid_options(&self) -> Vec<String> {
+        vec![
+            "title".to_string(),
+            "width".to_string(),
+            "height".to_string(),
+            "sandbox".to_string(),
+            "allow".to_string(),
+            "allowfullscreen".to_string(),
+            "class".to_string(),
+        ]
+    }
+
+    fn requires_content(&self) -> bool {
+        false
+    }
+
+    fn allows_content(&self) -> bool {
+        false
+    }
+}
+
+/// Validator for youtube directive
+#[derive(Default)]
+pub struct YoutubeValidator;
+
+impl YoutubeValidator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl DirectiveValidator for YoutubeValidator {
+    fn name(&self) -> &str {
+        "youtube"
+    }
+
+    fn validate(&self, directive: &ParsedDirective) -> DirectiveValidationResult {
+        let Some(video) = directive.arguments.first() else {
+            return DirectiveValidationResult::Error(
+                "YouTube directive requires a video ID or URL argument".to_string(),
+            );
+        };
+        if crate::directives::youtube_video_id(video).is_none() {
+            return DirectiveValidationResult::Error(format!("Invalid YouTube video: {}", video));
+        }
+
+        validate_embed_options(directive)
+    }
+
+    fn expected_arguments(&self) -> Vec<String> {
+        vec!["video_id".to_string()]
+    }
+
+    fn valid_options(&self) -> Vec<String> {
+        vec![
+            "title".to_string(),
+            "width".to_string(),
+            "height".to_string(),
+            "start".to_string(),
+            "sandbox".to_string(),
+            "allow".to_string(),
+            "allowfullscreen".to_string(),
+            "class".to_string(),
+        ]
+    }
+
+    fn requires_content(&self) -> bool {
+        false
+    }
+
+    fn allows_content(&self) -> bool {
+        false
+    }
+}
+
 /// Validator for toctree directive
 #[derive(Default)]
 pub struct TocTreeValidator;
@@ -490,6 +748,83 @@ impl DirectiveValidator for TocTreeValidator {
     }
 }
 
+/// Validator for contents directive
+#[derive(Default)]
+pub struct ContentsValidator;
+
+impl ContentsValidator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl DirectiveValidator for ContentsValidator {
+    fn name(&self) -> &str {
+        "contents"
+    }
+
+    fn validate(&self, directive: &ParsedDirective) -> DirectiveValidationResult {
+        for (option, value) in &directive.options {
+            match option.as_str() {
+                "depth" => {
+                    if !matches!(value.parse::<u32>(), Ok(depth) if depth > 0) {
+                        return DirectiveValidationResult::Error(
+                            "depth must be a positive integer".to_string(),
+                        );
+                    }
+                }
+                "backlinks" => {
+                    if !["entry", "top", "none"].contains(&value.as_str()) {
+                        return DirectiveValidationResult::Error(format!(
+                            "invalid backlinks '{}', expected 'entry', 'top' or 'none'",
+                            value
+                        ));
+                    }
+                }
+                "local" => {
+                    if !value.is_empty() {
+                        return DirectiveValidationResult::Warning(
+                            "local option should not have a value".to_string(),
+                        );
+                    }
+                }
+                "class" => {
+                    // Valid text option
+                }
+                _ => {
+                    return DirectiveValidationResult::Warning(format!(
+                        "Unknown option '{}' for contents directive",
+                        option
+                    ));
+                }
+            }
+        }
+
+        DirectiveValidationResult::Valid
+    }
+
+    fn expected_arguments(&self) -> Vec<String> {
+        vec!["title".to_string()]
+    }
+
+    fn valid_options(&self) -> Vec<String> {
+        vec![
+            "depth".to_string(),
+            "local".to_string(),
+            "backlinks".to_string(),
+            "class".to_string(),
+        ]
+    }
+
+    fn requires_content(&self) -> bool {
+        false
+    }
+
+    fn allows_content(&self) -> bool {
+        false
+    }
+}
+
 /// Validator for include directive
 #[derive(Default)]
 pub struct IncludeValidator;
@@ -609,8 +944,22 @@ impl DirectiveValidator for LiteralIncludeValidator {
                         );
                     }
                 }
+                "emphasize-lines" => {
+                    let valid = value.split(',').all(|part| {
+                        let bounds: Vec<&str> = part.trim().split('-').map(str::trim).collect();
+                        bounds.len() <= 2
+                            && bounds.iter().any(|bound| !bound.is_empty())
+                            && bounds.iter().all(|bound| bound.is_empty() || bound.parse::<u32>().is_ok())
+                    });
+                    if !valid {
+                        return DirectiveValidationResult::Error(format!(
+                            "invalid emphasize-lines '{}', expected line numbers and ranges like '1,3-5'",
+                            value
+                        ));
+                    }
+                }
                 "language" | "start-after" | "end-before" | "prepend" | "append" | "caption"
-                | "name" | "class" | "encoding" | "pyobject" | "diff" => {
+                | "name" | "class" | "encoding" | "pyobject" | "object" | "diff" => {
                     // Valid text options
                 }
                 "linenos" | "force" => {
@@ -655,6 +1004,7 @@ impl DirectiveValidator for LiteralIncludeValidator {
             "tab-width".to_string(),
             "encoding".to_string(),
             "pyobject".to_string(),
+            "object".to_string(),
             "caption".to_string(),
             "name".to_string(),
             "class".to_string(),
@@ -847,6 +1197,20 @@ mod tests {
             validator.validate(&directive),
             DirectiveValidationResult::Error(_)
         ));
+
+        // Body starting on the directive line
+        for validator in [&validator as &dyn DirectiveValidator, &WarningValidator::new()] {
+            let directive = create_test_directive(
+                validator.name(),
+                vec!["Careful".to_string(), "here.".to_string()],
+                HashMap::new(),
+                "",
+            );
+            assert_eq!(
+                validator.validate(&directive),
+                DirectiveValidationResult::Valid
+            );
+        }
     }
 
     #[test]
@@ -869,6 +1233,47 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_media_validators() {
+        let validator = VideoValidator::new();
+        let directive =
+            create_test_directive("video", vec!["demo.mp4".to_string()], HashMap::new(), "");
+        assert_eq!(validator.validate(&directive), DirectiveValidationResult::Valid);
+
+        let directive = create_test_directive("video", vec![], HashMap::new(), "");
+        assert!(matches!(
+            validator.validate(&directive),
+            DirectiveValidationResult::Error(_)
+        ));
+
+        let options = HashMap::from([("autoplay".to_string(), String::new())]);
+        let directive = create_test_directive("video", vec!["demo.webm".to_string()], options, "");
+        assert!(matches!(
+            validator.validate(&directive),
+            DirectiveValidationResult::Warning(_)
+        ));
+
+        // Embedded frames require a title
+        let validator = IframeValidator::new();
+        let url = vec!["https://example.org/demo".to_string()];
+        let directive = create_test_directive("iframe", url.clone(), HashMap::new(), "");
+        assert!(matches!(
+            validator.validate(&directive),
+            DirectiveValidationResult::Error(_)
+        ));
+        let options = HashMap::from([
+            ("title".to_string(), "Live demo".to_string()),
+            ("height".to_string(), "400".to_string()),
+        ]);
+        let directive = create_test_directive("iframe", url, options.clone(), "");
+        assert_eq!(validator.validate(&directive), DirectiveValidationResult::Valid);
+
+        let validator = YoutubeValidator::new();
+        let video = vec!["https://youtu.be/dQw4w9WgXcQ".to_string()];
+        let directive = create_test_directive("youtube", video, options, "");
+        assert_eq!(validator.validate(&directive), DirectiveValidationResult::Valid);
+    }
+
     #[test]
     fn test_math_validator() {
         let validator = MathValidator::new();
@@ -887,4 +1292,41 @@ mod tests {
             DirectiveValidationResult::Error(_)
         ));
     }
+
+    #[test]
+    fn test_literalinclude_emphasize_lines_validation() {
+        let validator = LiteralIncludeValidator::new();
+        for (spec, valid) in [("1,3-5", true), ("2-", true), ("1,x", false), ("1-2-3", false)] {
+            let options = HashMap::from([("emphasize-lines".to_string(), spec.to_string())]);
+            let directive = create_test_directive("literalinclude", vec!["example.py".to_string()], options, "");
+            assert_eq!(
+                validator.validate(&directive) == DirectiveValidationResult::Valid,
+                valid,
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn test_contents_option_validation() {
+        let validator = ContentsValidator::new();
+        for (option, value, valid) in [
+            ("depth", "2", true),
+            ("depth", "0", false),
+            ("backlinks", "top", true),
+            ("backlinks", "bottom", false),
+            ("local", "", true),
+        ] {
+            let options = HashMap::from([(option.to_string(), value.to_string())]);
+            let directive = create_test_directive("contents", vec![], options, "");
+            assert_eq!(
+                validator.validate(&directive) == DirectiveValidationResult::Valid,
+                valid,
+                "{}: {}",
+                option,
+                value
+            );
+        }
+    }
 }
@@ -18,7 +18,7 @@ lazy_static! {
 
     /// Regex for matching role patterns
     static ref ROLE_REGEX: Regex = Regex::new(
-        r":([a-zA-Z][a-zA-Z0-9_-]*):(`[^`]+`|[^\s]+)"
+        r":([a-zA-Z][a-zA-Z0-9_-]*(?::[a-zA-Z][a-zA-Z0-9_-]*)?):(`[^`]+`|[^\s]+)"
     ).unwrap();
 
     /// Regex for parsing role with display text
@@ -419,6 +419,18 @@ See :doc:`Custom Title <installation>` for details.
         assert_eq!(roles[4].display_text, Some("Custom Title".to_string()));
     }
 
+    #[test]
+    fn test_domain_role_parsing() {
+        let parser = DirectiveRoleParser::new("test.rst".to_string());
+
+        let roles = parser.extract_roles("Call :py:func:`~pkg.run` or :func:`main`.");
+        assert_eq!(roles.len(), 2);
+        assert_eq!(roles[0].name, "py:func");
+        assert_eq!(roles[0].target, "~pkg.run");
+        assert_eq!(roles[1].name, "func");
+        assert_eq!(roles[1].target, "main");
+    }
+
     #[test]
     fn test_statistical_parser() {
         let mut parser = StatisticalDirectiveRoleParser::new("test.rst".to_string());
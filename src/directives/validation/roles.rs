@@ -29,6 +29,12 @@ impl RoleValidator for DocRoleValidator {
             );
         }
 
+        if role.target.contains('\\') {
+            return RoleValidationResult::Warning(
+                "Document paths should use forward slashes".to_string(),
+            );
+        }
+
         // Check for common document extensions
         if role.target.ends_with(".rst") || role.target.ends_with(".md") {
             return RoleValidationResult::Warning(
@@ -479,6 +485,429 @@ impl RoleValidator for GuiLabelRoleValidator {
     }
 }
 
+/// Validator for term role
+#[derive(Default)]
+pub struct TermRoleValidator;
+
+impl TermRoleValidator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl RoleValidator for TermRoleValidator {
+    fn name(&self) -> &str {
+        "term"
+    }
+
+    fn validate(&self, role: &ParsedRole) -> RoleValidationResult {
+        if role.target.trim().is_empty() {
+            return RoleValidationResult::Error("Term role requires a glossary term".to_string());
+        }
+
+        if role.target.trim() != role.target {
+            return RoleValidationResult::Warning(
+                "Glossary term has leading or trailing whitespace".to_string(),
+            );
+        }
+
+        if role.target.contains("  ") {
+            return RoleValidationResult::Warning(
+                "Glossary term contains repeated spaces".to_string(),
+            );
+        }
+
+        RoleValidationResult::Valid
+    }
+
+    fn requires_target(&self) -> bool {
+        true
+    }
+
+    fn allows_display_text(&self) -> bool {
+        true
+    }
+}
+
+/// Validator for envvar role
+#[derive(Default)]
+pub struct EnvVarRoleValidator;
+
+impl EnvVarRoleValidator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl RoleValidator for EnvVarRoleValidator {
+    fn name(&self) -> &str {
+        "envvar"
+    }
+
+    fn validate(&self, role: &ParsedRole) -> RoleValidationResult {
+        if role.target.is_empty() {
+            return RoleValidationResult::Error(
+                "Envvar role requires an environment variable name".to_string(),
+            );
+        }
+
+        if role.target.starts_with('$') || role.target.starts_with('%') {
+            return RoleValidationResult::Warning(
+                "Environment variable names should not include the shell's '$' or '%'".to_string(),
+            );
+        }
+
+        if !is_identifier(&role.target, |_| false) {
+            return RoleValidationResult::Error(format!(
+                "Invalid environment variable name: {}",
+                role.target
+            ));
+        }
+
+        RoleValidationResult::Valid
+    }
+
+    fn requires_target(&self) -> bool {
+        true
+    }
+
+    fn allows_display_text(&self) -> bool {
+        true
+    }
+}
+
+/// Validator for option role
+#[derive(Default)]
+pub struct OptionRoleValidator;
+
+impl OptionRoleValidator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl RoleValidator for OptionRoleValidator {
+    fn name(&self) -> &str {
+        "option"
+    }
+
+    fn validate(&self, role: &ParsedRole) -> RoleValidationResult {
+        if role.target.is_empty() {
+            return RoleValidationResult::Error(
+                "Option role requires a command-line option".to_string(),
+            );
+        }
+
+        // Either "--opt" or "program --opt"; arguments like "=value" are not part of the name
+        let option = role
+            .target
+            .split_whitespace()
+            .next_back()
+            .unwrap_or_default();
+        if !["-", "/", "+"]
+            .iter()
+            .any(|prefix| option.starts_with(prefix))
+        {
+            return RoleValidationResult::Warning(format!(
+                "Option should start with '-', '--', '/' or '+': {}",
+                option
+            ));
+        }
+
+        if option.contains('=') {
+            return RoleValidationResult::Warning(
+                "Option targets should not include a value".to_string(),
+            );
+        }
+
+        RoleValidationResult::Valid
+    }
+
+    fn requires_target(&self) -> bool {
+        true
+    }
+
+    fn allows_display_text(&self) -> bool {
+        true
+    }
+}
+
+/// Validator for numref role
+#[derive(Default)]
+pub struct NumRefRoleValidator;
+
+impl NumRefRoleValidator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl RoleValidator for NumRefRoleValidator {
+    fn name(&self) -> &str {
+        "numref"
+    }
+
+    fn validate(&self, role: &ParsedRole) -> RoleValidationResult {
+        if role.target.is_empty() {
+            return RoleValidationResult::Error(
+                "Numref role requires a figure, table or code-block label".to_string(),
+            );
+        }
+
+        if role.target.contains(' ') {
+            return RoleValidationResult::Error(
+                "Reference targets cannot contain spaces".to_string(),
+            );
+        }
+
+        // Display text is a format with %s, {number} or {name} placeholders
+        if let Some(text) = &role.display_text {
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find('{') {
+                let Some(end) = rest[start..].find('}') else {
+                    return RoleValidationResult::Warning(
+                        "Unclosed placeholder in numref text".to_string(),
+                    );
+                };
+                let placeholder = &rest[start + 1..start + end];
+                if placeholder != "number" && placeholder != "name" {
+                    return RoleValidationResult::Warning(format!(
+                        "Unknown numref placeholder '{{{}}}', use {{number}} or {{name}}",
+                        placeholder
+                    ));
+                }
+                rest = &rest[start + end + 1..];
+            }
+        }
+
+        RoleValidationResult::Valid
+    }
+
+    fn requires_target(&self) -> bool {
+        true
+    }
+
+    fn allows_display_text(&self) -> bool {
+        true
+    }
+}
+
+/// Validator for keyword role
+#[derive(Default)]
+pub struct KeywordRoleValidator;
+
+impl KeywordRoleValidator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl RoleValidator for KeywordRoleValidator {
+    fn name(&self) -> &str {
+        "keyword"
+    }
+
+    fn validate(&self, role: &ParsedRole) -> RoleValidationResult {
+        if role.target.is_empty() {
+            return RoleValidationResult::Error("Keyword role requires a keyword".to_string());
+        }
+
+        let keywords = [
+            "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+            "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+            "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise",
+            "return", "try", "while", "with", "yield", "match", "case", "type", "_",
+        ];
+
+        if !keywords.contains(&role.target.as_str()) {
+            return RoleValidationResult::Warning(format!("Not a Python keyword: {}", role.target));
+        }
+
+        RoleValidationResult::Valid
+    }
+
+    fn requires_target(&self) -> bool {
+        true
+    }
+
+    fn allows_display_text(&self) -> bool {
+        true
+    }
+}
+
+/// Syntax of the object names a domain role refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainTargetSyntax {
+    /// Dotted Python names, e.g. `pkg.mod.Class.method`
+    Python,
+    /// C identifiers, with `.` for members, e.g. `my_struct.field`
+    C,
+    /// C++ qualified names, e.g. `ns::Vector<T>::push_back`
+    Cpp,
+    /// Dotted JavaScript names, e.g. `$.ajax`
+    JavaScript,
+}
+
+/// Validator for object roles of the Python, C, C++ and JavaScript domains
+pub struct DomainRoleValidator {
+    name: String,
+    syntax: DomainTargetSyntax,
+}
+
+impl DomainRoleValidator {
+    pub fn new(name: &str, syntax: DomainTargetSyntax) -> Self {
+        Self {
+            name: name.to_string(),
+            syntax,
+        }
+    }
+
+    /// Validators for the object roles of every supported domain, including
+    /// the unprefixed Python roles of Sphinx's default domain
+    pub fn builtin() -> Vec<Self> {
+        let python = [
+            "mod", "func", "data", "const", "class", "meth", "attr", "type", "exc", "obj",
+        ];
+        let c = [
+            "member",
+            "data",
+            "var",
+            "func",
+            "macro",
+            "struct",
+            "union",
+            "enum",
+            "enumerator",
+            "type",
+        ];
+        let cpp = [
+            "any",
+            "class",
+            "struct",
+            "func",
+            "member",
+            "var",
+            "type",
+            "concept",
+            "enum",
+            "enumerator",
+        ];
+        let javascript = ["mod", "func", "meth", "class", "data", "attr"];
+
+        let mut validators = Vec::new();
+        for role in python {
+            validators.push(Self::new(role, DomainTargetSyntax::Python));
+            validators.push(Self::new(
+                &format!("py:{}", role),
+                DomainTargetSyntax::Python,
+            ));
+        }
+        for role in c {
+            validators.push(Self::new(&format!("c:{}", role), DomainTargetSyntax::C));
+        }
+        for role in cpp {
+            validators.push(Self::new(&format!("cpp:{}", role), DomainTargetSyntax::Cpp));
+        }
+        for role in javascript {
+            validators.push(Self::new(
+                &format!("js:{}", role),
+                DomainTargetSyntax::JavaScript,
+            ));
+        }
+        validators
+    }
+
+    fn is_valid_target(&self, target: &str) -> bool {
+        // Sphinx strips a trailing "()" from function names
+        let target = target.strip_suffix("()").unwrap_or(target);
+        match self.syntax {
+            DomainTargetSyntax::Python => target
+                .trim_start_matches('.')
+                .split('.')
+                .all(|part| is_identifier(part, |_| false)),
+            DomainTargetSyntax::C => target.split('.').all(|part| is_identifier(part, |_| false)),
+            DomainTargetSyntax::JavaScript => target
+                .split('.')
+                .all(|part| is_identifier(part, |c| c == '$')),
+            DomainTargetSyntax::Cpp => {
+                let target = strip_brackets(target, '(', ')');
+                let target = strip_brackets(&target, '<', '>');
+                target.trim_start_matches("::").split("::").all(|part| {
+                    part.starts_with("operator")
+                        || is_identifier(part.strip_prefix('~').unwrap_or(part), |_| false)
+                })
+            }
+        }
+    }
+}
+
+impl RoleValidator for DomainRoleValidator {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn validate(&self, role: &ParsedRole) -> RoleValidationResult {
+        // "!" suppresses the link and "~" shortens the displayed name
+        let target = role.target.trim_start_matches(['!', '~']);
+        if target.is_empty() {
+            return RoleValidationResult::Error(format!(
+                "{} role requires an object name",
+                self.name
+            ));
+        }
+
+        if target.contains(char::is_whitespace) && self.syntax != DomainTargetSyntax::Cpp {
+            return RoleValidationResult::Error("Object names cannot contain spaces".to_string());
+        }
+
+        if !self.is_valid_target(target) {
+            return RoleValidationResult::Warning(format!("Malformed object name: {}", target));
+        }
+
+        RoleValidationResult::Valid
+    }
+
+    fn requires_target(&self) -> bool {
+        true
+    }
+
+    fn allows_display_text(&self) -> bool {
+        true
+    }
+}
+
+/// Whether `name` is an identifier: letters, digits and underscores, plus
+/// characters accepted by `extra`, not starting with a digit
+fn is_identifier(name: &str, extra: impl Fn(char) -> bool) -> bool {
+    let mut chars = name.chars();
+    let valid = |c: char| c.is_alphanumeric() || c == '_' || extra(c);
+    match chars.next() {
+        Some(first) if valid(first) && !first.is_numeric() => chars.all(valid),
+        _ => false,
+    }
+}
+
+/// Remove bracketed text, such as C++ template arguments or parameters,
+/// keeping unbalanced text unchanged
+fn strip_brackets(text: &str, open: char, close: char) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0usize;
+    for c in text.chars() {
+        if c == open {
+            depth += 1;
+        } else if c == close && depth > 0 {
+            depth -= 1;
+        } else if depth == 0 {
+            out.push(c);
+        }
+    }
+    if depth > 0 {
+        text.to_string()
+    } else {
+        out
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -604,4 +1033,94 @@ mod tests {
             RoleValidationResult::Error(_)
         ));
     }
+
+    #[test]
+    fn test_envvar_and_option_role_validators() {
+        let envvar = EnvVarRoleValidator::new();
+        let role = create_test_role("envvar", "SPHINX_ULTRA_HOME", None);
+        assert_eq!(envvar.validate(&role), RoleValidationResult::Valid);
+        let role = create_test_role("envvar", "$HOME", None);
+        assert!(matches!(
+            envvar.validate(&role),
+            RoleValidationResult::Warning(_)
+        ));
+        let role = create_test_role("envvar", "MY-VAR", None);
+        assert!(matches!(
+            envvar.validate(&role),
+            RoleValidationResult::Error(_)
+        ));
+
+        let option = OptionRoleValidator::new();
+        let role = create_test_role("option", "sphinx-build -W", None);
+        assert_eq!(option.validate(&role), RoleValidationResult::Valid);
+        let role = create_test_role("option", "verbose", None);
+        assert!(matches!(
+            option.validate(&role),
+            RoleValidationResult::Warning(_)
+        ));
+    }
+
+    #[test]
+    fn test_term_numref_and_keyword_role_validators() {
+        let role = create_test_role("term", "build directory", None);
+        assert_eq!(
+            TermRoleValidator::new().validate(&role),
+            RoleValidationResult::Valid
+        );
+
+        let numref = NumRefRoleValidator::new();
+        let role = create_test_role("numref", "fig-arch", Some("Figure {number}".to_string()));
+        assert_eq!(numref.validate(&role), RoleValidationResult::Valid);
+        let role = create_test_role("numref", "fig-arch", Some("Figure {num}".to_string()));
+        assert!(matches!(
+            numref.validate(&role),
+            RoleValidationResult::Warning(_)
+        ));
+
+        let keyword = KeywordRoleValidator::new();
+        let role = create_test_role("keyword", "yield", None);
+        assert_eq!(keyword.validate(&role), RoleValidationResult::Valid);
+        let role = create_test_role("keyword", "yeild", None);
+        assert!(matches!(
+            keyword.validate(&role),
+            RoleValidationResult::Warning(_)
+        ));
+    }
+
+    #[test]
+    fn test_domain_role_validator() {
+        let python = DomainRoleValidator::new("py:meth", DomainTargetSyntax::Python);
+        for target in ["pkg.Class.method", "~pkg.Class.method()", "!.method"] {
+            let role = create_test_role("py:meth", target, None);
+            assert_eq!(
+                python.validate(&role),
+                RoleValidationResult::Valid,
+                "{}",
+                target
+            );
+        }
+        let role = create_test_role("py:meth", "pkg..Class", None);
+        assert!(matches!(
+            python.validate(&role),
+            RoleValidationResult::Warning(_)
+        ));
+        let role = create_test_role("py:meth", "pkg.Class method", None);
+        assert!(matches!(
+            python.validate(&role),
+            RoleValidationResult::Error(_)
+        ));
+
+        let cpp = DomainRoleValidator::new("cpp:func", DomainTargetSyntax::Cpp);
+        let role = create_test_role("cpp:func", "ns::Vector<T>::push_back(const T &value)", None);
+        assert_eq!(cpp.validate(&role), RoleValidationResult::Valid);
+        let role = create_test_role("cpp:func", "ns:Vector", None);
+        assert!(matches!(
+            cpp.validate(&role),
+            RoleValidationResult::Warning(_)
+        ));
+
+        let js = DomainRoleValidator::new("js:func", DomainTargetSyntax::JavaScript);
+        let role = create_test_role("js:func", "$.ajax", None);
+        assert_eq!(js.validate(&role), RoleValidationResult::Valid);
+    }
 }
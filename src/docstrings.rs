@@ -0,0 +1,258 @@
+//! Documentation comments collected from source code.
+//!
+//! Files matching the `docstrings` patterns are scanned for comment blocks
+//! that start with a marker line like `//! doc: Title` or `### doc: Title`.
+//! The blocks of each file become a reStructuredText document under the
+//! configured directory, and a generated index document lists them all, so
+//! docs kept next to the code they describe build with the rest.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::DocstringsConfig;
+use crate::matching;
+
+/// A comment block with the marker line's title and the text below it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocBlock {
+    pub title: String,
+    /// Text with the comment prefix and common indentation removed
+    pub body: String,
+    /// Line of the marker
+    pub line: usize,
+}
+
+/// A generated document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectedDocument {
+    /// Path of the document relative to the source directory, like
+    /// `code/deploy/run.sh.rst`
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// The documentation blocks of `source`. A block starts at a line whose
+/// text begins with a marker, and goes on while lines begin with the
+/// marker's comment prefix (the marker without its last word).
+pub fn extract(source: &str, markers: &[String]) -> Vec<DocBlock> {
+    let mut blocks = Vec::new();
+    let lines: Vec<&str> = source.lines().collect();
+    let mut index = 0;
+    while index < lines.len() {
+        let trimmed = lines[index].trim_start();
+        let Some(marker) = markers
+            .iter()
+            .find(|marker| trimmed.starts_with(marker.as_str()))
+        else {
+            index += 1;
+            continue;
+        };
+        let prefix = comment_prefix(marker);
+        let title = trimmed[marker.len()..].trim().to_string();
+        let line = index + 1;
+        index += 1;
+
+        let mut body_lines = Vec::new();
+        while let Some(text) = lines
+            .get(index)
+            .and_then(|line| line.trim_start().strip_prefix(prefix))
+        {
+            if markers
+                .iter()
+                .any(|marker| lines[index].trim_start().starts_with(marker.as_str()))
+            {
+                break;
+            }
+            body_lines.push(text);
+            index += 1;
+        }
+        blocks.push(DocBlock {
+            title,
+            body: dedent(&body_lines),
+            line,
+        });
+    }
+    blocks
+}
+
+/// The comment prefix of a marker: `//!` of `//! doc:`
+fn comment_prefix(marker: &str) -> &str {
+    marker
+        .trim_end()
+        .rsplit_once(char::is_whitespace)
+        .map_or(marker, |(prefix, _)| prefix.trim_end())
+}
+
+/// Join lines, removing the indentation they share
+fn dedent(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let text: Vec<&str> = lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or("").trim_end())
+        .collect();
+    text.join("\n").trim_matches('\n').to_string()
+}
+
+/// Heading with an underline as long as its text
+fn heading(text: &str, underline: char) -> String {
+    format!(
+        "{}\n{}\n",
+        text,
+        underline.to_string().repeat(text.chars().count())
+    )
+}
+
+/// The document of a source file's blocks, titled with the file's path
+pub fn render_document(file: &str, blocks: &[DocBlock]) -> String {
+    let mut rst = heading(file, '=');
+    for block in blocks {
+        rst.push('\n');
+        if !block.title.is_empty() {
+            rst.push_str(&heading(&block.title, '-'));
+            rst.push('\n');
+        }
+        if !block.body.is_empty() {
+            rst.push_str(&block.body);
+            rst.push('\n');
+        }
+    }
+    rst
+}
+
+/// Collect the documents of the files under `source_dir` that `config`
+/// names, with the index listing them. Files that cannot be read as text
+/// are skipped.
+pub fn collect(
+    source_dir: &Path,
+    config: &DocstringsConfig,
+) -> Result<Vec<CollectedDocument>, String> {
+    let root = source_dir.join(&config.root);
+    let scan_error =
+        |err: &dyn std::fmt::Display| format!("cannot scan {}: {}", root.display(), err);
+    let root = crate::paths::canonicalize(&root).map_err(|err| scan_error(&err))?;
+    let excludes = vec![".*/**".to_string(), "_build/**".to_string()];
+    let files = matching::get_matching_files(&root, &config.patterns, &excludes)
+        .map_err(|err| scan_error(&err))?;
+
+    let directory = config.directory.trim_matches('/');
+    let mut documents = Vec::new();
+    let mut names = Vec::new();
+    for file in files {
+        let Ok(source) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let blocks = extract(&source, &config.markers);
+        if blocks.is_empty() {
+            continue;
+        }
+        let name = matching::normalize_path(file.strip_prefix(&root).unwrap_or(&file));
+        documents.push(CollectedDocument {
+            path: PathBuf::from(format!("{}/{}.rst", directory, name)),
+            content: render_document(&name, &blocks),
+        });
+        names.push(name);
+    }
+
+    let mut index = heading(&config.title, '=');
+    if !names.is_empty() {
+        index.push_str("\n.. toctree::\n   :maxdepth: 1\n\n");
+        for name in &names {
+            index.push_str(&format!("   {}\n", name));
+        }
+    }
+    documents.push(CollectedDocument {
+        path: PathBuf::from(format!("{}/index.rst", directory)),
+        content: index,
+    });
+    Ok(documents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_blocks() {
+        let markers = DocstringsConfig::default().markers;
+        let source = "\
+#!/bin/sh
+### doc: Deploying
+###
+### Run with the target environment::
+###
+###     ./deploy.sh staging
+set -e
+# an ordinary comment
+    ### doc:
+    ### Indented block.
+    ### doc: Next
+    ### Text.
+";
+        assert_eq!(
+            extract(source, &markers),
+            vec![
+                DocBlock {
+                    title: "Deploying".to_string(),
+                    body: "Run with the target environment::\n\n    ./deploy.sh staging"
+                        .to_string(),
+                    line: 2,
+                },
+                DocBlock {
+                    title: String::new(),
+                    body: "Indented block.".to_string(),
+                    line: 9,
+                },
+                DocBlock {
+                    title: "Next".to_string(),
+                    body: "Text.".to_string(),
+                    line: 11,
+                },
+            ]
+        );
+        assert!(extract("//! Crate docs, not marked\n", &markers).is_empty());
+    }
+
+    #[test]
+    fn test_collect_documents_and_index() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/bin")).unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            "//! doc: Library\n//! The library.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("src/bin/tool.rs"),
+            "//! doc: Tool\n//! The tool.\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("src/plain.rs"), "fn main() {}\n").unwrap();
+        let config = DocstringsConfig {
+            patterns: vec!["src/**/*.rs".to_string()],
+            ..DocstringsConfig::default()
+        };
+
+        let documents = collect(dir.path(), &config).unwrap();
+        let paths: Vec<&Path> = documents.iter().map(|doc| doc.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("code/src/bin/tool.rs.rst"),
+                Path::new("code/src/lib.rs.rst"),
+                Path::new("code/index.rst"),
+            ]
+        );
+        assert_eq!(
+            documents[1].content,
+            "src/lib.rs\n==========\n\nLibrary\n-------\n\nThe library.\n"
+        );
+        assert_eq!(
+            documents[2].content,
+            "Code Documentation\n==================\n\n.. toctree::\n   :maxdepth: 1\n\n   src/bin/tool.rs\n   src/lib.rs\n"
+        );
+    }
+}
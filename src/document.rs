@@ -58,6 +58,11 @@ pub struct Document {
 
     /// Table of contents
     pub toc: Vec<TocEntry>,
+
+    /// Files pulled in by `include` and `literalinclude` directives, at any
+    /// depth; a change to one of them means the document must be rebuilt
+    #[serde(default)]
+    pub includes: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,6 +92,35 @@ pub struct RstContent {
 
     /// Directives found in the document
     pub directives: Vec<RstDirective>,
+
+    /// Title underline characters in order of first appearance, which give
+    /// the section levels of RST parsed into the document later
+    #[serde(default)]
+    pub title_styles: Vec<char>,
+
+    /// Malformed markup the parser recovered from, in source order
+    #[serde(default)]
+    pub messages: Vec<SystemMessage>,
+}
+
+/// A problem with the markup of a document, like a docutils system message.
+/// The parser reports it and carries on with its best reading of the text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SystemMessage {
+    pub level: MessageLevel,
+    pub message: String,
+    /// Line of the problem, from 1
+    pub line: usize,
+    /// Included file the problem is in; `None` for the document itself
+    pub source: Option<PathBuf>,
+}
+
+/// Severity of a [`SystemMessage`], as the docutils levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageLevel {
+    Warning,
+    Error,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -103,9 +137,15 @@ pub struct MarkdownContent {
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct DocumentMetadata {
-    /// Document author(s)
+    /// Document author(s), from `sectionauthor` and `moduleauthor`
+    /// directives
     pub authors: Vec<String>,
 
+    /// Authors of the code shown in the document, from `codeauthor`
+    /// directives
+    #[serde(default)]
+    pub code_authors: Vec<String>,
+
     /// Document creation date
     pub created: Option<DateTime<Utc>>,
 
@@ -120,6 +160,16 @@ pub struct DocumentMetadata {
 
     /// Custom metadata fields
     pub custom: HashMap<String, serde_json::Value>,
+
+    /// Fields of `meta` directives, sorted: the field name with any
+    /// attributes, like `description` or `keywords lang=en`, and its content
+    #[serde(default)]
+    pub meta: Vec<(String, String)>,
+
+    /// Entries of `index` directives and `:index:` roles, for the general
+    /// index
+    #[serde(default)]
+    pub index_terms: Vec<crate::genindex::IndexTerm>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -203,6 +253,37 @@ pub enum RstNode {
         items: Vec<DefinitionItem>,
         line: usize,
     },
+    /// Footnote (e.g., `.. [1]`, `.. [#]`, `.. [#name]` or `.. [*]`)
+    Footnote {
+        label: String,
+        content: String,
+        line: usize,
+    },
+    /// Citation (e.g., `.. [CIT2002]`)
+    Citation {
+        label: String,
+        content: String,
+        line: usize,
+    },
+}
+
+impl RstNode {
+    /// Source line the node starts at
+    pub fn line_mut(&mut self) -> &mut usize {
+        match self {
+            RstNode::Title { line, .. }
+            | RstNode::Paragraph { line, .. }
+            | RstNode::CodeBlock { line, .. }
+            | RstNode::List { line, .. }
+            | RstNode::Table { line, .. }
+            | RstNode::Directive { line, .. }
+            | RstNode::LinkTarget { line, .. }
+            | RstNode::BlockQuote { line, .. }
+            | RstNode::DefinitionList { line, .. }
+            | RstNode::Footnote { line, .. }
+            | RstNode::Citation { line, .. } => line,
+        }
+    }
 }
 
 /// A single item in a definition list
@@ -273,6 +354,7 @@ impl Document {
             build_time: Utc::now(),
             cross_refs: Vec::new(),
             toc: Vec::new(),
+            includes: Vec::new(),
         }
     }
 
@@ -313,4 +395,33 @@ impl TocEntry {
     pub fn add_child(&mut self, child: TocEntry) {
         self.children.push(child);
     }
+
+    /// Nest a flat, document-ordered list of headings by level, so each
+    /// heading becomes a child of the nearest preceding shallower heading.
+    pub fn nest(entries: &[TocEntry]) -> Vec<TocEntry> {
+        let mut roots: Vec<TocEntry> = Vec::new();
+        let mut stack: Vec<TocEntry> = Vec::new();
+
+        fn attach(roots: &mut Vec<TocEntry>, stack: &mut [TocEntry], entry: TocEntry) {
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(entry),
+                None => roots.push(entry),
+            }
+        }
+
+        for entry in entries {
+            while stack.last().is_some_and(|top| top.level >= entry.level) {
+                let done = stack.pop().unwrap();
+                attach(&mut roots, &mut stack, done);
+            }
+            let mut entry = entry.clone();
+            entry.children.clear();
+            stack.push(entry);
+        }
+        while let Some(done) = stack.pop() {
+            attach(&mut roots, &mut stack, done);
+        }
+
+        roots
+    }
 }
@@ -2,37 +2,110 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+use crate::intern::Symbol;
+use crate::numfig::Enumerable;
+use crate::renderer::TocSection;
+
 /// Type alias for document relations: (parent, previous, next)
 type DocumentRelations = HashMap<String, (Option<String>, Option<String>, Option<String>)>;
 
-/// Build environment that mirrors Sphinx's BuildEnvironment
+/// Labels Sphinx defines for its generated pages
+pub const STANDARD_LABELS: [&str; 3] = ["genindex", "modindex", "search"];
+
+/// A toctree directive of a document
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Toctree {
+    /// Documents it lists
+    pub entries: Vec<Symbol>,
+    /// Levels of section numbers its `:numbered:` option asks for; 0 when
+    /// it has none
+    pub numbered: usize,
+    /// Number of the document's sections before it
+    pub position: usize,
+}
+
+/// Where `assign_section_numbers` is in its walk through the toctrees
+#[derive(Debug, Default)]
+struct SectionNumbering {
+    /// Counts of the levels above and at the current one
+    numstack: Vec<u32>,
+    /// Documents numbered, or whose numbered toctrees were walked
+    assigned: HashSet<String>,
+    numbers: HashMap<String, HashMap<String, Vec<u32>>>,
+}
+
+impl SectionNumbering {
+    /// Count one more item at the current level and return its number
+    fn next(&mut self) -> Vec<u32> {
+        if let Some(count) = self.numstack.last_mut() {
+            *count += 1;
+        }
+        self.numstack.clone()
+    }
+}
+
+/// Target of a `.. _name:` label
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    /// Document defining the label
+    pub docname: Symbol,
+    /// Id of the anchor in the document's page; empty for the page itself
+    pub anchor: String,
+    /// Title of the section the label is placed before, if any
+    pub title: Option<String>,
+}
+
+/// Build environment that mirrors Sphinx's BuildEnvironment. The builder
+/// fills it with what the first pass learns about every document, and
+/// shares it with the renderers, templates and extensions of the build as
+/// the one place to look up documents, titles, labels and toctrees.
 #[derive(Debug, Clone)]
 pub struct BuildEnvironment {
     pub config: crate::config::BuildConfig,
     pub domains: HashMap<String, Domain>,
-    pub found_docs: Vec<String>,
-    pub all_docs: HashMap<String, f64>, // docname -> mtime
+    pub found_docs: Vec<Symbol>,
+    pub all_docs: HashMap<Symbol, f64>, // docname -> mtime
     pub dependencies: HashMap<String, HashSet<PathBuf>>,
     pub included: HashMap<PathBuf, HashSet<String>>,
     pub temp_data: HashMap<String, serde_json::Value>,
     pub ref_context: HashMap<String, serde_json::Value>,
-    pub toctree_includes: HashMap<String, Vec<String>>,
+    pub toctree_includes: HashMap<Symbol, Vec<Symbol>>,
+    /// Toctrees of each document, in order, with resolved entries
+    pub toctrees: HashMap<Symbol, Vec<Toctree>>,
     pub files_to_rebuild: HashMap<String, HashSet<String>>,
     pub glob_toctrees: HashSet<String>,
     pub reread_always: HashSet<String>,
     pub metadata: HashMap<String, HashMap<String, String>>,
-    pub titles: HashMap<String, String>,
-    pub longtitles: HashMap<String, String>,
+    pub titles: HashMap<Symbol, Symbol>,
+    pub longtitles: HashMap<Symbol, Symbol>,
     pub tocs: HashMap<String, String>,
     pub toc_secnumbers: HashMap<String, HashMap<String, Vec<u32>>>,
     pub toc_fignumbers: HashMap<String, HashMap<String, HashMap<String, Vec<u32>>>>,
     pub toc_num_entries: HashMap<String, usize>,
     pub dlfiles: HashMap<String, (Option<String>, String)>,
     pub images: HashMap<String, String>,
+    /// Source file of each document
+    pub source_paths: HashMap<Symbol, PathBuf>,
+    /// Section headings of each document below its title, in order
+    pub sections: HashMap<Symbol, Vec<TocSection>>,
+    /// Labels by lowercase name
+    pub labels: HashMap<String, Label>,
+    /// Captioned figures, tables and code blocks of each document, in order
+    pub enumerables: HashMap<Symbol, Vec<Enumerable>>,
+    /// Stylesheets every page links, relative to the output root
+    pub css_files: Vec<String>,
+    /// Scripts every page loads, relative to the output root
+    pub js_files: Vec<String>,
 }
 
 use std::collections::HashSet;
 
+impl Default for BuildEnvironment {
+    fn default() -> Self {
+        Self::new(crate::config::BuildConfig::default())
+    }
+}
+
 impl BuildEnvironment {
     pub fn new(config: crate::config::BuildConfig) -> Self {
         Self {
@@ -45,6 +118,7 @@ impl BuildEnvironment {
             temp_data: HashMap::new(),
             ref_context: HashMap::new(),
             toctree_includes: HashMap::new(),
+            toctrees: HashMap::new(),
             files_to_rebuild: HashMap::new(),
             glob_toctrees: HashSet::new(),
             reread_always: HashSet::new(),
@@ -57,24 +131,240 @@ impl BuildEnvironment {
             toc_num_entries: HashMap::new(),
             dlfiles: HashMap::new(),
             images: HashMap::new(),
+            source_paths: HashMap::new(),
+            sections: HashMap::new(),
+            labels: HashMap::new(),
+            enumerables: HashMap::new(),
+            css_files: Vec::new(),
+            js_files: Vec::new(),
         }
     }
 
     /// Add a document to the environment
-    pub fn add_document(&mut self, docname: String, mtime: f64) {
+    pub fn add_document(&mut self, docname: impl Into<Symbol>, mtime: f64) {
+        let docname = docname.into();
         self.found_docs.push(docname.clone());
         self.all_docs.insert(docname, mtime);
     }
 
-    /// Get document path from docname
+    /// Get document path from docname: its source file when known, or
+    /// else the `.rst` file of that name
     pub fn doc2path(&self, docname: &str) -> PathBuf {
-        PathBuf::from(format!("{}.rst", docname))
+        match self.source_paths.get(docname) {
+            Some(path) => path.clone(),
+            None => PathBuf::from(format!("{}.rst", docname)),
+        }
+    }
+
+    /// Whether `docname` is a document of the project
+    pub fn has_document(&self, docname: &str) -> bool {
+        self.all_docs.contains_key(docname)
+    }
+
+    /// Names of the documents, sorted
+    pub fn document_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.found_docs.iter().map(Symbol::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Title of a document
+    pub fn title(&self, docname: &str) -> Option<&Symbol> {
+        self.titles.get(docname)
     }
 
-    /// Collect relations between documents
+    /// Section headings of a document below its title
+    pub fn document_sections(&self, docname: &str) -> &[TocSection] {
+        self.sections.get(docname).map_or(&[], Vec::as_slice)
+    }
+
+    /// Define a label; names are case-insensitive, and the first definition
+    /// of a name wins
+    pub fn add_label(&mut self, name: &str, label: Label) {
+        self.labels.entry(name.to_lowercase()).or_insert(label);
+    }
+
+    /// Target of a label, found case-insensitively
+    pub fn resolve_label(&self, name: &str) -> Option<&Label> {
+        self.labels.get(&name.to_lowercase())
+    }
+
+    /// Names of the labels, lowercase and sorted
+    pub fn label_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.labels.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Documents listed in the toctrees of `docname`
+    pub fn toctree_children(&self, docname: &str) -> &[Symbol] {
+        self.toctree_includes
+            .get(docname)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Documents whose toctrees list `docname`, sorted
+    pub fn toctree_parents(&self, docname: &str) -> Vec<&Symbol> {
+        let mut parents: Vec<&Symbol> = self
+            .toctree_includes
+            .iter()
+            .filter(|(_, children)| children.iter().any(|child| child.as_str() == docname))
+            .map(|(parent, _)| parent)
+            .collect();
+        parents.sort();
+        parents
+    }
+
+    /// Documents reachable from the root document through toctrees, in
+    /// reading order, each with the document whose toctree lists it
+    pub fn reading_order(&self) -> Vec<(String, Option<String>)> {
+        let root = self
+            .config
+            .root_doc
+            .clone()
+            .unwrap_or_else(|| "index".to_string());
+        let mut order: Vec<(String, Option<String>)> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![(root, None)];
+        while let Some((docname, parent)) = stack.pop() {
+            if !self.has_document(&docname) || !seen.insert(docname.clone()) {
+                continue;
+            }
+            for child in self.toctree_children(&docname).iter().rev() {
+                stack.push((child.to_string(), Some(docname.clone())));
+            }
+            order.push((docname, parent));
+        }
+        order
+    }
+
+    /// Parent, previous and next document of each document reachable from
+    /// the root document through toctrees, in reading order
     pub fn collect_relations(&self) -> DocumentRelations {
-        // TODO: Implement relation collection from toctree
-        HashMap::new()
+        let order = self.reading_order();
+        let mut relations = HashMap::new();
+        for (index, (docname, parent)) in order.iter().enumerate() {
+            let previous = index
+                .checked_sub(1)
+                .map(|previous| order[previous].0.clone());
+            let next = order.get(index + 1).map(|(next, _)| next.clone());
+            relations.insert(docname.clone(), (parent.clone(), previous, next));
+        }
+        relations
+    }
+
+    /// Number the figures, tables and code blocks of the documents in
+    /// reading order, counting each kind separately
+    pub fn assign_figure_numbers(&mut self) {
+        let mut counts: HashMap<&str, u32> = HashMap::new();
+        let mut numbers = HashMap::new();
+        for (docname, _) in self.reading_order() {
+            let Some(items) = self.enumerables.get(docname.as_str()) else {
+                continue;
+            };
+            let mut document_numbers: HashMap<String, HashMap<String, Vec<u32>>> = HashMap::new();
+            for item in items {
+                let count = counts.entry(item.figtype).or_insert(0);
+                *count += 1;
+                document_numbers
+                    .entry(item.figtype.to_string())
+                    .or_default()
+                    .insert(item.id.clone(), vec![*count]);
+            }
+            numbers.insert(docname, document_numbers);
+        }
+        self.toc_fignumbers = numbers;
+    }
+
+    /// Number of a figure, table or code block, when numbered
+    pub fn figure_number(&self, docname: &str, figtype: &str, id: &str) -> Option<&[u32]> {
+        self.toc_fignumbers
+            .get(docname)?
+            .get(figtype)?
+            .get(id)
+            .map(Vec::as_slice)
+    }
+
+    /// Number the sections of the documents in `:numbered:` toctrees like
+    /// Sphinx. Each numbered toctree counts its documents from 1, with their
+    /// sections below them and the documents of their own toctrees among
+    /// those sections. A document is numbered by the first toctree to reach
+    /// it in reading order; its title goes under the empty anchor.
+    pub fn assign_section_numbers(&mut self) {
+        let mut numbering = SectionNumbering::default();
+        for (docname, _) in self.reading_order() {
+            let Some(toctrees) = self.toctrees.get(docname.as_str()) else {
+                continue;
+            };
+            if toctrees.iter().all(|toctree| toctree.numbered == 0) {
+                continue;
+            }
+            numbering.assigned.insert(docname);
+            for toctree in toctrees.iter().filter(|toctree| toctree.numbered > 0) {
+                numbering.numstack = vec![0];
+                self.number_toctree(toctree, toctree.numbered, &mut numbering);
+            }
+        }
+        self.toc_secnumbers = numbering.numbers;
+    }
+
+    fn number_toctree(&self, toctree: &Toctree, depth: usize, numbering: &mut SectionNumbering) {
+        if depth == 0 {
+            return;
+        }
+        for entry in &toctree.entries {
+            if !self.has_document(entry) || !numbering.assigned.insert(entry.to_string()) {
+                continue;
+            }
+            let mut secnums = HashMap::new();
+            secnums.insert(String::new(), numbering.next());
+            numbering.numstack.push(0);
+            let sections = self.document_sections(entry).len();
+            self.number_sections(entry, 0..sections, depth - 1, &mut secnums, numbering);
+            numbering.numstack.pop();
+            numbering.numbers.insert(entry.to_string(), secnums);
+        }
+    }
+
+    /// Number the sections `range` of `docname`, which lie below one of its
+    /// headings, and the documents of the toctrees among them
+    fn number_sections(
+        &self,
+        docname: &str,
+        range: std::ops::Range<usize>,
+        depth: usize,
+        secnums: &mut HashMap<String, Vec<u32>>,
+        numbering: &mut SectionNumbering,
+    ) {
+        let sections = self.document_sections(docname);
+        // Toctrees between the heading and its first section
+        for toctree in self.toctrees.get(docname).into_iter().flatten() {
+            if toctree.position == range.start {
+                self.number_toctree(toctree, depth, numbering);
+            }
+        }
+        let mut index = range.start;
+        while index < range.end {
+            let section = &sections[index];
+            let end = sections[index + 1..range.end]
+                .iter()
+                .position(|next| next.level <= section.level)
+                .map_or(range.end, |offset| index + 1 + offset);
+            let number = numbering.next();
+            if depth > 0 {
+                secnums.insert(section.anchor.to_string(), number);
+            }
+            numbering.numstack.push(0);
+            self.number_sections(docname, index + 1..end, depth.saturating_sub(1), secnums, numbering);
+            numbering.numstack.pop();
+            index = end;
+        }
+    }
+
+    /// Section number of a heading, the document title being under the
+    /// empty anchor, when a `:numbered:` toctree numbers it
+    pub fn section_number(&self, docname: &str, anchor: &str) -> Option<&[u32]> {
+        self.toc_secnumbers.get(docname)?.get(anchor).map(Vec::as_slice)
     }
 
     /// Check if document needs to be updated
@@ -448,6 +738,114 @@ mod tests {
         assert_eq!(env.domains.len(), 0);
     }
 
+    #[test]
+    fn test_document_queries_and_relations() {
+        let mut env = BuildEnvironment::default();
+        for docname in ["index", "guide", "guide/install", "orphan"] {
+            env.add_document(docname, 0.0);
+        }
+        env.source_paths
+            .insert(Symbol::from("guide"), PathBuf::from("guide.md"));
+        env.toctree_includes
+            .insert(Symbol::from("index"), vec![Symbol::from("guide")]);
+        env.toctree_includes
+            .insert(Symbol::from("guide"), vec![Symbol::from("guide/install")]);
+        env.add_label(
+            "Setup",
+            Label {
+                docname: Symbol::from("guide/install"),
+                anchor: "setup".to_string(),
+                title: Some("Setup".to_string()),
+            },
+        );
+
+        assert_eq!(env.doc2path("guide"), PathBuf::from("guide.md"));
+        assert_eq!(env.doc2path("index"), PathBuf::from("index.rst"));
+        assert!(env.has_document("orphan") && !env.has_document("missing"));
+        assert_eq!(
+            env.document_names(),
+            vec!["guide", "guide/install", "index", "orphan"]
+        );
+        assert_eq!(
+            env.resolve_label("SETUP")
+                .map(|label| label.docname.as_str()),
+            Some("guide/install")
+        );
+        assert_eq!(
+            env.toctree_parents("guide/install"),
+            vec![&Symbol::from("guide")]
+        );
+
+        let relations = env.collect_relations();
+        assert_eq!(relations.len(), 3);
+        assert_eq!(
+            relations["guide"],
+            (
+                Some("index".to_string()),
+                Some("index".to_string()),
+                Some("guide/install".to_string())
+            )
+        );
+        assert_eq!(
+            relations["guide/install"],
+            (Some("guide".to_string()), Some("guide".to_string()), None)
+        );
+    }
+
+    #[test]
+    fn test_section_numbers_follow_numbered_toctrees() {
+        let mut env = BuildEnvironment::default();
+        for docname in ["index", "guide", "guide/extra", "api", "api/ref"] {
+            env.add_document(docname, 0.0);
+        }
+        let toctree = |entries: &[&str], numbered: usize, position: usize| Toctree {
+            entries: entries.iter().map(|entry| Symbol::from(*entry)).collect(),
+            numbered,
+            position,
+        };
+        let toctrees = [
+            ("index", vec![toctree(&["guide", "api"], usize::MAX, 0)]),
+            // Listed after the section `usage`, the third one
+            ("guide", vec![toctree(&["guide/extra"], 0, 3)]),
+            // Already numbered through the toctree of `index`
+            ("api", vec![toctree(&["api/ref"], 1, 0)]),
+        ];
+        for (docname, toctrees) in toctrees {
+            let children = toctrees.iter().flat_map(|toctree| toctree.entries.clone()).collect();
+            env.toctree_includes.insert(Symbol::from(docname), children);
+            env.toctrees.insert(Symbol::from(docname), toctrees);
+        }
+        env.sections.insert(
+            Symbol::from("guide"),
+            vec![
+                TocSection::new("Install", "install", 2),
+                TocSection::new("From source", "from-source", 3),
+                TocSection::new("Usage", "usage", 2),
+            ],
+        );
+        env.sections
+            .insert(Symbol::from("guide/extra"), vec![TocSection::new("More", "more", 2)]);
+        env.assign_section_numbers();
+
+        let number = |docname: &str, anchor: &str| env.section_number(docname, anchor).map(<[u32]>::to_vec);
+        assert_eq!(number("index", ""), None);
+        assert_eq!(number("guide", ""), Some(vec![1]));
+        assert_eq!(number("guide", "install"), Some(vec![1, 1]));
+        assert_eq!(number("guide", "from-source"), Some(vec![1, 1, 1]));
+        assert_eq!(number("guide", "usage"), Some(vec![1, 2]));
+        assert_eq!(number("guide/extra", ""), Some(vec![1, 2, 1]));
+        assert_eq!(number("guide/extra", "more"), Some(vec![1, 2, 1, 1]));
+        assert_eq!(number("api", ""), Some(vec![2]));
+        assert_eq!(number("api/ref", ""), Some(vec![2, 1]));
+
+        // A depth limits the levels numbered
+        env.toctrees.get_mut("index").unwrap()[0].numbered = 2;
+        env.assign_section_numbers();
+        assert_eq!(env.section_number("guide", "install"), Some(&[1, 1][..]));
+        assert_eq!(env.section_number("guide", "from-source"), None);
+        assert_eq!(env.section_number("guide/extra", ""), None);
+    }
+
     #[test]
     fn test_domain_object_creation() {
         let obj = DomainObject::new(
@@ -45,6 +45,41 @@ pub enum BuildError {
 
     #[error("Validation error: {0}")]
     ValidationError(String),
+
+    #[error("Build cancelled after writing {documents_written} documents")]
+    Cancelled { documents_written: usize },
+}
+
+/// How a build ended, so scripts can tell a build with warnings from one
+/// that failed
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BuildOutcome {
+    /// Every page was built without warnings or errors
+    Success,
+    /// Every page was built, but warnings or document errors were reported
+    SuccessWithWarnings,
+    /// The build stopped in `phase`, or was cancelled there
+    Failed { phase: String },
+}
+
+impl BuildOutcome {
+    /// Outcome of a build that stopped with `error`
+    pub fn failed(error: &anyhow::Error) -> Self {
+        BuildOutcome::Failed {
+            phase: error
+                .downcast_ref::<PhaseFailure>()
+                .map_or("setup", |failure| failure.phase)
+                .to_string(),
+        }
+    }
+}
+
+/// Context of an error that stopped a build: the phase it stopped in
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("build stopped in the {phase} phase")]
+pub struct PhaseFailure {
+    pub phase: &'static str,
 }
 
 #[derive(Debug, Clone)]
@@ -54,6 +89,8 @@ pub struct BuildWarning {
     pub message: String,
     #[allow(dead_code)]
     pub warning_type: WarningType,
+    /// Hints for fixing the problem, shown after the warning
+    pub suggestions: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -65,7 +102,7 @@ pub struct BuildErrorReport {
     pub error_type: ErrorType,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum WarningType {
     MissingToctreeRef,
@@ -75,6 +112,29 @@ pub enum WarningType {
     UnusedLabel,
     DuplicateLabel,
     EmptyToctree,
+    InvalidDirective,
+    UnknownDirective,
+    InvalidRole,
+    UnknownRole,
+    ContentRule,
+    SkippedHeadingLevel,
+    MultipleTitles,
+    TitleWhitespace,
+    UnderlineLength,
+    MissingAltText,
+    TableWithoutHeader,
+    AmbiguousLinkText,
+    LiteralInclude,
+    Spelling,
+    Redirect,
+    /// A page's template failed to render and the page fell back to the
+    /// plain layout
+    TemplateRender,
+    /// A file of `html_extra_path` replaces a generated page or another
+    /// extra file
+    ExtraPathConflict,
+    /// Malformed RST the parser recovered from
+    MalformedMarkup,
     Other,
 }
 
@@ -85,6 +145,9 @@ pub enum ErrorType {
     FileNotFound,
     TemplateError,
     SyntaxError,
+    ValidationError,
+    /// A panic while processing the file
+    Crash,
     Other,
 }
 
@@ -100,9 +163,16 @@ impl BuildWarning {
             line,
             message,
             warning_type,
+            suggestions: Vec::new(),
         }
     }
 
+    /// Attach suggestions for fixing the warning
+    pub fn with_suggestions(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
     pub fn missing_toctree_ref(file: PathBuf, line: Option<usize>, reference: &str) -> Self {
         Self::new(
             file,
@@ -124,7 +194,6 @@ impl BuildWarning {
         )
     }
 
-    #[allow(dead_code)]
     pub fn broken_cross_reference(file: PathBuf, line: Option<usize>, reference: &str) -> Self {
         Self::new(
             file,
@@ -135,6 +204,55 @@ impl BuildWarning {
     }
 }
 
+impl WarningType {
+    /// Category name used to refer to this warning type in configuration
+    pub fn category(&self) -> &'static str {
+        match self {
+            WarningType::MissingToctreeRef => "missing-toctree-ref",
+            WarningType::OrphanedDocument => "orphaned-document",
+            WarningType::BrokenCrossReference => "broken-cross-reference",
+            WarningType::MissingFile => "missing-file",
+            WarningType::UnusedLabel => "unused-label",
+            WarningType::DuplicateLabel => "duplicate-label",
+            WarningType::EmptyToctree => "empty-toctree",
+            WarningType::InvalidDirective => "invalid-directive",
+            WarningType::UnknownDirective => "unknown-directive",
+            WarningType::InvalidRole => "invalid-role",
+            WarningType::UnknownRole => "unknown-role",
+            WarningType::ContentRule => "content-rule",
+            WarningType::SkippedHeadingLevel => "skipped-heading-level",
+            WarningType::MultipleTitles => "multiple-titles",
+            WarningType::TitleWhitespace => "title-whitespace",
+            WarningType::UnderlineLength => "underline-length",
+            WarningType::MissingAltText => "missing-alt-text",
+            WarningType::TableWithoutHeader => "table-without-header",
+            WarningType::AmbiguousLinkText => "ambiguous-link-text",
+            WarningType::LiteralInclude => "literalinclude",
+            WarningType::Spelling => "spelling",
+            WarningType::Redirect => "redirect",
+            WarningType::TemplateRender => "template.render",
+            WarningType::ExtraPathConflict => "extra-path-conflict",
+            WarningType::MalformedMarkup => "malformed-markup",
+            WarningType::Other => "other",
+        }
+    }
+}
+
+impl ErrorType {
+    /// Category name of this error type in machine-readable reports
+    pub fn category(&self) -> &'static str {
+        match self {
+            ErrorType::ParseError => "parse",
+            ErrorType::FileNotFound => "file-not-found",
+            ErrorType::TemplateError => "template",
+            ErrorType::SyntaxError => "syntax",
+            ErrorType::ValidationError => "validation",
+            ErrorType::Crash => "crash",
+            ErrorType::Other => "other",
+        }
+    }
+}
+
 impl BuildErrorReport {
     #[allow(dead_code)]
     pub fn new(file: PathBuf, line: Option<usize>, message: String, error_type: ErrorType) -> Self {
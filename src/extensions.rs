@@ -136,7 +136,7 @@ impl SphinxApp {
     /// Call an extension's setup function
     fn call_extension_setup(&self, extension: &SphinxExtension, _setup_fn: &str) -> Result<()> {
         // Stub implementation - in a real version this would call the Python setup function
-        println!("Setting up extension: {}", extension.name);
+        log::info!("Setting up extension: {}", extension.name);
         Ok(())
     }
 
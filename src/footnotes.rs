@@ -0,0 +1,388 @@
+//! Footnotes and citations.
+//!
+//! As in docutils, `.. [1]` footnotes keep their number, `.. [#]` and
+//! `.. [#name]` are numbered automatically, skipping the numbers taken by
+//! hand, and `.. [*]` get symbols. References `[1]_`, `[#name]_`, `[#]_` and
+//! `[*]_` link to them, the anonymous ones to the matching footnotes in
+//! order. Citations like `.. [CIT2002]` are referenced by `[CIT2002]_`.
+
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+use crate::config::{FootnoteConfig, FootnoteNumbering, FootnoteStyle};
+use crate::document::RstNode;
+use crate::renderer::slugify;
+
+lazy_static::lazy_static! {
+    static ref REFERENCE_RE: Regex = Regex::new(r"\[([^\]\s]+)\]_").unwrap();
+}
+
+/// Labels of symbol footnotes, doubled, tripled and so on once used up
+const SYMBOLS: [char; 10] = ['*', '†', '‡', '§', '¶', '#', '♠', '♥', '♦', '♣'];
+
+/// The label of a footnote or citation, between the brackets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoteLabel {
+    /// `[1]`, numbered by hand
+    Number(usize),
+    /// `[#]`
+    Auto,
+    /// `[#name]`
+    AutoNamed(String),
+    /// `[*]`
+    Symbol,
+    /// `[CIT2002]`
+    Citation(String),
+}
+
+impl NoteLabel {
+    /// Classify a label, or `None` when it is neither a footnote nor a
+    /// citation label
+    pub fn parse(label: &str) -> Option<Self> {
+        match label {
+            "#" => Some(Self::Auto),
+            "*" => Some(Self::Symbol),
+            _ if label.chars().all(|c| c.is_ascii_digit()) => label.parse().ok().map(Self::Number),
+            _ => match label.strip_prefix('#') {
+                Some(name) => is_reference_name(name).then(|| Self::AutoNamed(name.to_lowercase())),
+                None => is_reference_name(label).then(|| Self::Citation(label.to_string())),
+            },
+        }
+    }
+
+    pub fn is_citation(&self) -> bool {
+        matches!(self, Self::Citation(_))
+    }
+}
+
+/// Whether `name` is a simple reference name: alphanumerics joined by
+/// single hyphens, underscores, periods, colons or plus signs
+fn is_reference_name(name: &str) -> bool {
+    let chars: Vec<char> = name.chars().collect();
+    !chars.is_empty()
+        && chars.first().is_some_and(|c| c.is_alphanumeric())
+        && chars.last().is_some_and(|c| c.is_alphanumeric())
+        && chars
+            .windows(2)
+            .all(|pair| pair[0].is_alphanumeric() || pair[1].is_alphanumeric())
+        && chars
+            .iter()
+            .all(|c| c.is_alphanumeric() || "-_.:+".contains(*c))
+}
+
+/// The `n`th symbol label, from 0
+fn symbol(n: usize) -> String {
+    SYMBOLS[n % SYMBOLS.len()]
+        .to_string()
+        .repeat(n / SYMBOLS.len() + 1)
+}
+
+/// A footnote or citation as rendered: its anchor, the label shown and the
+/// anchor of its first reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    pub id: String,
+    pub label: String,
+    pub reference_id: String,
+}
+
+/// Labels and anchors of the footnotes and citations of one document
+#[derive(Debug, Default)]
+pub struct FootnotePlan {
+    /// Footnotes and citations by their index in the document
+    definitions: HashMap<usize, Note>,
+    /// Footnotes numbered by hand by number, named footnotes by name
+    footnotes: HashMap<String, Note>,
+    citations: HashMap<String, Note>,
+    /// `[#]` and `[*]` footnotes in document order
+    anonymous: Vec<Note>,
+    symbols: Vec<Note>,
+    /// Anchors of the notes that some reference links to
+    referenced: HashSet<String>,
+}
+
+impl FootnotePlan {
+    /// Number the footnotes of a document. `raw` is its source, whose
+    /// references decide which notes get a link back.
+    pub fn new(ast: &[RstNode], raw: &str, config: &FootnoteConfig) -> Self {
+        let mut plan = Self::default();
+
+        // Numbers taken by hand in each numbering scope
+        let mut scope = 0;
+        let mut scopes = Vec::new();
+        let mut taken: HashMap<usize, HashSet<usize>> = HashMap::new();
+        for node in ast {
+            match node {
+                RstNode::Title { .. } if config.numbering == FootnoteNumbering::Section => {
+                    scope += 1
+                }
+                RstNode::Footnote { label, .. } => {
+                    if let Some(NoteLabel::Number(number)) = NoteLabel::parse(label) {
+                        taken.entry(scope).or_default().insert(number);
+                    }
+                    scopes.push(scope);
+                }
+                _ => {}
+            }
+        }
+
+        let mut next_number = 1;
+        let mut next_symbol = 0;
+        let mut current_scope = None;
+        let mut footnote_count = 0;
+        for (index, node) in ast.iter().enumerate() {
+            match node {
+                RstNode::Footnote { label, .. } => {
+                    let scope = scopes[footnote_count];
+                    footnote_count += 1;
+                    if current_scope != Some(scope) {
+                        current_scope = Some(scope);
+                        next_number = 1;
+                        next_symbol = 0;
+                    }
+                    let Some(kind) = NoteLabel::parse(label) else {
+                        continue;
+                    };
+                    let mut symbol_label = || {
+                        next_symbol += 1;
+                        symbol(next_symbol - 1)
+                    };
+                    let shown = match &kind {
+                        NoteLabel::Number(number) => number.to_string(),
+                        NoteLabel::Symbol => symbol_label(),
+                        NoteLabel::Auto | NoteLabel::AutoNamed(_)
+                            if config.style == FootnoteStyle::Symbol =>
+                        {
+                            symbol_label()
+                        }
+                        _ => {
+                            let scope_taken = taken.get(&scope);
+                            while scope_taken.is_some_and(|numbers| numbers.contains(&next_number))
+                            {
+                                next_number += 1;
+                            }
+                            next_number += 1;
+                            (next_number - 1).to_string()
+                        }
+                    };
+                    let note = Note {
+                        id: format!("footnote-{}", footnote_count),
+                        label: shown,
+                        reference_id: format!("footnote-reference-{}", footnote_count),
+                    };
+                    match kind {
+                        NoteLabel::Number(number) => {
+                            plan.footnotes
+                                .entry(number.to_string())
+                                .or_insert_with(|| note.clone());
+                        }
+                        NoteLabel::AutoNamed(name) => {
+                            plan.footnotes
+                                .entry(format!("#{}", name))
+                                .or_insert_with(|| note.clone());
+                        }
+                        NoteLabel::Auto => plan.anonymous.push(note.clone()),
+                        NoteLabel::Symbol => plan.symbols.push(note.clone()),
+                        NoteLabel::Citation(_) => {}
+                    }
+                    plan.definitions.insert(index, note);
+                }
+                RstNode::Citation { label, .. } => {
+                    let slug = slugify(label);
+                    let note = Note {
+                        id: format!("citation-{}", slug),
+                        label: label.clone(),
+                        reference_id: format!("citation-reference-{}", slug),
+                    };
+                    plan.citations
+                        .entry(label.to_lowercase())
+                        .or_insert_with(|| note.clone());
+                    plan.definitions.insert(index, note);
+                }
+                _ => {}
+            }
+        }
+
+        let mut references = FootnoteReferences::new(&plan);
+        let referenced: HashSet<String> = REFERENCE_RE
+            .captures_iter(raw)
+            .filter_map(|caps| NoteLabel::parse(&caps[1]))
+            .filter_map(|label| references.resolve(&label))
+            .map(|(note, _)| note.id)
+            .collect();
+        plan.referenced = referenced;
+        plan
+    }
+
+    /// The footnote or citation at `index` in the document
+    pub fn definition(&self, index: usize) -> Option<&Note> {
+        self.definitions.get(&index)
+    }
+
+    /// Whether some reference links to the note
+    pub fn is_referenced(&self, note: &Note) -> bool {
+        self.referenced.contains(&note.id)
+    }
+}
+
+/// Resolves the references of a document in order, pairing anonymous
+/// references with anonymous footnotes and giving every reference an anchor
+#[derive(Debug, Default)]
+pub struct FootnoteReferences {
+    footnotes: HashMap<String, Note>,
+    citations: HashMap<String, Note>,
+    anonymous: Vec<Note>,
+    symbols: Vec<Note>,
+    next_anonymous: usize,
+    next_symbol: usize,
+    /// References so far to each note, by its anchor
+    counts: HashMap<String, usize>,
+}
+
+impl FootnoteReferences {
+    pub fn new(plan: &FootnotePlan) -> Self {
+        Self {
+            footnotes: plan.footnotes.clone(),
+            citations: plan.citations.clone(),
+            anonymous: plan.anonymous.clone(),
+            symbols: plan.symbols.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// The note a reference links to and the reference's own anchor
+    pub fn resolve(&mut self, label: &NoteLabel) -> Option<(Note, String)> {
+        let note = match label {
+            NoteLabel::Number(number) => self.footnotes.get(&number.to_string()),
+            NoteLabel::AutoNamed(name) => self.footnotes.get(&format!("#{}", name)),
+            NoteLabel::Citation(name) => self.citations.get(&name.to_lowercase()),
+            NoteLabel::Auto => {
+                self.next_anonymous += 1;
+                self.anonymous.get(self.next_anonymous - 1)
+            }
+            NoteLabel::Symbol => {
+                self.next_symbol += 1;
+                self.symbols.get(self.next_symbol - 1)
+            }
+        }?
+        .clone();
+        let count = self.counts.entry(note.id.clone()).or_default();
+        *count += 1;
+        let id = match *count {
+            1 => note.reference_id.clone(),
+            n => format!("{}-{}", note.reference_id, n),
+        };
+        Some((note, id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn footnote(label: &str) -> RstNode {
+        RstNode::Footnote {
+            label: label.to_string(),
+            content: String::new(),
+            line: 0,
+        }
+    }
+
+    fn title() -> RstNode {
+        RstNode::Title {
+            text: "Section".to_string(),
+            level: 2,
+            line: 0,
+        }
+    }
+
+    fn labels(plan: &FootnotePlan, count: usize) -> Vec<String> {
+        (0..count)
+            .filter_map(|index| plan.definition(index).map(|note| note.label.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_note_labels() {
+        assert_eq!(NoteLabel::parse("12"), Some(NoteLabel::Number(12)));
+        assert_eq!(NoteLabel::parse("#"), Some(NoteLabel::Auto));
+        assert_eq!(
+            NoteLabel::parse("#Note-1"),
+            Some(NoteLabel::AutoNamed("note-1".to_string()))
+        );
+        assert_eq!(NoteLabel::parse("*"), Some(NoteLabel::Symbol));
+        assert_eq!(
+            NoteLabel::parse("CIT2002"),
+            Some(NoteLabel::Citation("CIT2002".to_string()))
+        );
+        assert_eq!(NoteLabel::parse("a b"), None);
+        assert_eq!(NoteLabel::parse("x--y"), None);
+        assert_eq!(NoteLabel::parse("-x"), None);
+    }
+
+    #[test]
+    fn test_auto_numbering_skips_manual_numbers() {
+        let ast = vec![
+            footnote("#"),
+            footnote("2"),
+            footnote("#named"),
+            footnote("*"),
+            footnote("#"),
+            footnote("*"),
+        ];
+        let plan = FootnotePlan::new(&ast, "[#]_ [#named]_ [*]_", &FootnoteConfig::default());
+        assert_eq!(labels(&plan, ast.len()), vec!["1", "2", "3", "*", "4", "†"]);
+
+        let first = plan.definition(0).unwrap();
+        assert!(plan.is_referenced(first));
+        assert!(!plan.is_referenced(plan.definition(1).unwrap()));
+        assert!(plan.is_referenced(plan.definition(3).unwrap()));
+        assert!(!plan.is_referenced(plan.definition(5).unwrap()));
+
+        let mut references = FootnoteReferences::new(&plan);
+        let (note, id) = references
+            .resolve(&NoteLabel::AutoNamed("named".to_string()))
+            .unwrap();
+        assert_eq!(
+            (note.label.as_str(), id.as_str()),
+            ("3", "footnote-reference-3")
+        );
+        let (_, id) = references
+            .resolve(&NoteLabel::AutoNamed("named".to_string()))
+            .unwrap();
+        assert_eq!(id, "footnote-reference-3-2");
+        let (note, _) = references.resolve(&NoteLabel::Auto).unwrap();
+        assert_eq!(note.label, "1");
+        let (note, _) = references.resolve(&NoteLabel::Auto).unwrap();
+        assert_eq!(note.label, "4");
+        assert!(references.resolve(&NoteLabel::Auto).is_none());
+        assert!(references.resolve(&NoteLabel::Number(7)).is_none());
+    }
+
+    #[test]
+    fn test_symbol_style_and_section_numbering() {
+        let ast = vec![
+            footnote("#"),
+            footnote("*"),
+            title(),
+            footnote("#"),
+            footnote("1"),
+            footnote("#"),
+        ];
+        let config = FootnoteConfig {
+            style: FootnoteStyle::Symbol,
+            numbering: FootnoteNumbering::Document,
+        };
+        let plan = FootnotePlan::new(&ast, "", &config);
+        assert_eq!(labels(&plan, ast.len()), vec!["*", "†", "‡", "1", "§"]);
+
+        let config = FootnoteConfig {
+            style: FootnoteStyle::Numeric,
+            numbering: FootnoteNumbering::Section,
+        };
+        let plan = FootnotePlan::new(&ast, "", &config);
+        assert_eq!(labels(&plan, ast.len()), vec!["1", "*", "2", "1", "3"]);
+        assert_eq!(plan.definition(5).unwrap().id, "footnote-5");
+        assert_eq!(symbol(11), "††");
+    }
+}
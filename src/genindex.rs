@@ -0,0 +1,524 @@
+//! Index entries and the general index page.
+//!
+//! `index` directives and `:index:` roles mark the place of a term with an
+//! anchor and record entries like `single: loop; for`, `pair: loop;
+//! statement` or `see: iteration; loop`. The entries are kept on the parsed
+//! document, and `genindex.html` lists them grouped by first letter.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::document::RstNode;
+use crate::navigation::relative_uri;
+use crate::renderer::slugify;
+
+lazy_static::lazy_static! {
+    static ref INDEX_ROLE_RE: Regex = Regex::new(r":index:`([^`]+)`").unwrap();
+}
+
+/// Document name of the general index page
+pub const GENINDEX_PAGE: &str = "genindex";
+
+/// Directives whose content is not inline markup, so `:index:` in it is
+/// literal text
+const RAW_CONTENT_DIRECTIVES: &[&str] = &[
+    "raw",
+    "code-block",
+    "code",
+    "sourcecode",
+    "literalinclude",
+    "highlight",
+    "index",
+    "math",
+];
+
+/// Kind of an index entry, given by the prefix of its text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexEntryKind {
+    /// `single: term` or `single: term; subterm`
+    Single,
+    /// `pair: first; second`, listed under both terms
+    Pair,
+    /// `triple: first; second; third`, listed under all three terms
+    Triple,
+    /// `see: term; other` refers from one term to another
+    See,
+    /// `seealso: term; other`
+    SeeAlso,
+}
+
+impl IndexEntryKind {
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "single" => Some(Self::Single),
+            // `double` is the older name of `pair`
+            "pair" | "double" => Some(Self::Pair),
+            "triple" => Some(Self::Triple),
+            "see" => Some(Self::See),
+            "seealso" => Some(Self::SeeAlso),
+            _ => None,
+        }
+    }
+
+    /// Least and most `;` separated parts of an entry of this kind, and how
+    /// to describe them
+    fn parts(self) -> (usize, usize, &'static str) {
+        match self {
+            Self::Single => (1, 2, "one or two parts"),
+            Self::Pair => (2, 2, "two parts"),
+            Self::Triple => (3, 3, "three parts"),
+            Self::See | Self::SeeAlso => (2, 2, "two parts"),
+        }
+    }
+}
+
+/// An entry of an `index` directive or `:index:` role
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexTerm {
+    pub kind: IndexEntryKind,
+    /// Text after the kind prefix, with `;` between the parts, like
+    /// `loop; statement`
+    pub value: String,
+    /// Marked with `!` as the main place the term is described
+    #[serde(default)]
+    pub main: bool,
+    /// Anchor id in the document the entry links to
+    pub target: String,
+}
+
+/// The entries of one `index` directive or `:index:` role and the anchor
+/// they link to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexTarget {
+    pub id: String,
+    pub terms: Vec<IndexTerm>,
+    /// Entries that could not be read, as messages
+    pub errors: Vec<String>,
+}
+
+impl IndexTarget {
+    fn new(id: Option<&str>, entries: &[&str]) -> Self {
+        let mut parsed = Vec::new();
+        let mut errors = Vec::new();
+        for entry in entries {
+            match parse_entry(entry) {
+                Ok(entries) => parsed.extend(entries),
+                Err(message) => errors.push(message),
+            }
+        }
+        let id = match id {
+            Some(id) => id.to_string(),
+            None => target_id(parsed.first().map_or("", |(_, value, _)| value.as_str())),
+        };
+        let terms = parsed
+            .into_iter()
+            .map(|(kind, value, main)| IndexTerm {
+                kind,
+                value,
+                main,
+                target: id.clone(),
+            })
+            .collect();
+        Self { id, terms, errors }
+    }
+}
+
+/// Anchor id for entries starting with `value`. Ids come from the entry text
+/// rather than a count, so the parser and the renderer agree on them; a term
+/// indexed twice in a document links to its first place.
+fn target_id(value: &str) -> String {
+    let slug = slugify(value);
+    if slug.is_empty() {
+        "index".to_string()
+    } else {
+        format!("index-{}", slug)
+    }
+}
+
+/// Read one line of entries: `kind: value` with an optional leading `!`, or
+/// comma-separated single entries without a kind
+fn parse_entry(entry: &str) -> Result<Vec<(IndexEntryKind, String, bool)>, String> {
+    let entry = entry.trim();
+    let (main, entry) = match entry.strip_prefix('!') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, entry),
+    };
+
+    let typed = entry
+        .split_once(':')
+        .and_then(|(prefix, value)| Some((IndexEntryKind::from_prefix(prefix.trim())?, value.trim())));
+    let Some((kind, value)) = typed else {
+        return Ok(entry
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| (IndexEntryKind::Single, value.to_string(), main))
+            .collect());
+    };
+
+    let parts: Vec<&str> = value.split(';').map(str::trim).collect();
+    let (min, max, expected) = kind.parts();
+    if parts.len() < min || parts.len() > max || parts.iter().any(|part| part.is_empty()) {
+        return Err(format!("index entry '{}' needs {} separated by ';'", entry, expected));
+    }
+    Ok(vec![(kind, parts.join("; "), main)])
+}
+
+/// Entries of an `index` directive, from its argument and content lines. The
+/// `:name:` option gives the anchor id.
+pub fn index_directive(args: &[String], options: &HashMap<String, String>, content: &str) -> IndexTarget {
+    let entries: Vec<&str> = args
+        .iter()
+        .flat_map(|arg| arg.lines())
+        .chain(content.lines())
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    IndexTarget::new(options.get("name").map(String::as_str), &entries)
+}
+
+/// The text shown for the content of an `:index:` role and its entries. A
+/// role like `` :index:`loops <pair: loop; statement>` `` shows `loops` and
+/// records the entry in brackets; without one, the text itself is a single
+/// entry.
+pub fn index_role(content: &str) -> (String, IndexTarget) {
+    let content = content.trim();
+    if let Some(open) = content.rfind('<').filter(|_| content.ends_with('>')) {
+        let text = content[..open].trim();
+        let entry = &content[open + 1..content.len() - 1];
+        return (text.to_string(), IndexTarget::new(None, &[entry]));
+    }
+    let (main, text) = match content.strip_prefix('!') {
+        Some(text) => (true, text.trim_start()),
+        None => (false, content),
+    };
+    let id = target_id(text);
+    let terms = vec![IndexTerm {
+        kind: IndexEntryKind::Single,
+        value: text.to_string(),
+        main,
+        target: id.clone(),
+    }];
+    (
+        text.to_string(),
+        IndexTarget {
+            id,
+            terms,
+            errors: Vec::new(),
+        },
+    )
+}
+
+/// Index entries of the `index` directives and `:index:` roles in a document
+pub fn collect_terms(ast: &[RstNode]) -> Vec<IndexTerm> {
+    let mut terms = Vec::new();
+    for node in ast {
+        match node {
+            RstNode::Title { text, .. } => terms.extend(role_terms(text)),
+            RstNode::Paragraph { content, .. }
+            | RstNode::BlockQuote { content, .. }
+            | RstNode::Footnote { content, .. }
+            | RstNode::Citation { content, .. } => {
+                terms.extend(role_terms(content))
+            }
+            RstNode::List { items, .. } => terms.extend(items.iter().flat_map(|item| role_terms(item))),
+            RstNode::Table { headers, rows, .. } => {
+                terms.extend(headers.iter().chain(rows.iter().flatten()).flat_map(|cell| role_terms(cell)))
+            }
+            RstNode::DefinitionList { items, .. } => {
+                for item in items {
+                    terms.extend(role_terms(&item.term));
+                    terms.extend(role_terms(&item.definition));
+                }
+            }
+            RstNode::Directive { name, args, options, content, .. } => {
+                if name == "index" {
+                    terms.extend(index_directive(args, options, content).terms);
+                } else if !RAW_CONTENT_DIRECTIVES.contains(&name.as_str()) {
+                    terms.extend(role_terms(content));
+                }
+            }
+            RstNode::CodeBlock { .. } | RstNode::LinkTarget { .. } => {}
+        }
+    }
+    terms
+}
+
+/// Entries of the `:index:` roles in inline text
+fn role_terms(text: &str) -> impl Iterator<Item = IndexTerm> + '_ {
+    INDEX_ROLE_RE
+        .captures_iter(text)
+        .flat_map(|role| index_role(&role[1]).1.terms)
+}
+
+/// A link from the general index to the place of an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexLink {
+    pub uri: String,
+    pub main: bool,
+}
+
+/// A term of the general index with its links and subterms. Subterms of
+/// `see` entries have no links.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeneralIndexEntry {
+    pub name: String,
+    pub links: Vec<IndexLink>,
+    pub subentries: Vec<(String, Vec<IndexLink>)>,
+}
+
+/// The terms of the general index starting with one letter, or with a
+/// symbol for the `Symbols` group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneralIndexGroup {
+    pub key: String,
+    pub entries: Vec<GeneralIndexEntry>,
+}
+
+/// Sort key of a term, case-insensitive with the original as tie-breaker
+fn sort_key(name: &str) -> (String, String) {
+    (name.to_lowercase(), name.to_string())
+}
+
+/// Group key of a term: its upper-cased first letter, or `Symbols`
+fn group_key(name: &str) -> String {
+    match name.chars().next() {
+        Some(first) if first.is_alphabetic() || first == '_' => first.to_uppercase().collect(),
+        _ => "Symbols".to_string(),
+    }
+}
+
+#[derive(Default)]
+struct EntryBuilder {
+    name: String,
+    links: Vec<IndexLink>,
+    subentries: BTreeMap<(String, String), Vec<IndexLink>>,
+}
+
+/// Build the general index from the index entries of the documents, given
+/// as `(document name, entries)`, with links relative to the index page
+pub fn build_index<'a>(documents: impl IntoIterator<Item = (&'a str, &'a [IndexTerm])>) -> Vec<GeneralIndexGroup> {
+    let mut entries: BTreeMap<(String, String), EntryBuilder> = BTreeMap::new();
+    let mut add = |name: &str, subentry: Option<String>, link: Option<IndexLink>| {
+        let entry = entries.entry(sort_key(name)).or_insert_with(|| EntryBuilder {
+            name: name.to_string(),
+            ..Default::default()
+        });
+        match subentry {
+            Some(subentry) => {
+                let links = entry.subentries.entry(sort_key(&subentry)).or_default();
+                links.extend(link.filter(|link| !links.contains(link)));
+            }
+            None => entry.links.extend(link.filter(|link| !entry.links.contains(link))),
+        }
+    };
+
+    for (doc, terms) in documents {
+        for term in terms {
+            let link = IndexLink {
+                uri: format!("{}#{}", relative_uri(GENINDEX_PAGE, doc), term.target),
+                main: term.main,
+            };
+            let parts: Vec<&str> = term.value.split(';').map(str::trim).collect();
+            match (term.kind, parts.as_slice()) {
+                (IndexEntryKind::Single, [name]) => add(name, None, Some(link)),
+                (IndexEntryKind::Single, [name, sub]) => add(name, Some(sub.to_string()), Some(link)),
+                (IndexEntryKind::Pair, [first, second]) => {
+                    add(first, Some(second.to_string()), Some(link.clone()));
+                    add(second, Some(first.to_string()), Some(link));
+                }
+                (IndexEntryKind::Triple, [first, second, third]) => {
+                    add(first, Some(format!("{} {}", second, third)), Some(link.clone()));
+                    add(second, Some(format!("{}, {}", third, first)), Some(link.clone()));
+                    add(third, Some(format!("{} {}", first, second)), Some(link));
+                }
+                (IndexEntryKind::See, [name, other]) => add(name, Some(format!("see {}", other)), None),
+                (IndexEntryKind::SeeAlso, [name, other]) => add(name, Some(format!("see also {}", other)), None),
+                _ => {}
+            }
+        }
+    }
+
+    let mut groups: Vec<GeneralIndexGroup> = Vec::new();
+    for entry in entries.into_values() {
+        let entry = GeneralIndexEntry {
+            subentries: entry
+                .subentries
+                .into_iter()
+                .map(|((_, name), links)| (name, links))
+                .collect(),
+            name: entry.name,
+            links: entry.links,
+        };
+        let key = group_key(&entry.name);
+        match groups.iter_mut().find(|group| group.key == key) {
+            Some(group) => group.entries.push(entry),
+            None => groups.push(GeneralIndexGroup {
+                key,
+                entries: vec![entry],
+            }),
+        }
+    }
+    // Symbols go before the letters
+    groups.sort_by_key(|group| (group.key != "Symbols", group.key.to_lowercase()));
+    groups
+}
+
+/// Term and links of an index entry as list item content: the first link on
+/// the term, further links as `[1]`, `[2]`, main places in bold
+fn render_links(name: &str, links: &[IndexLink]) -> String {
+    let strong = |link: &IndexLink, text: &str| {
+        if link.main {
+            format!("<strong>{}</strong>", text)
+        } else {
+            text.to_string()
+        }
+    };
+    let name = html_escape::encode_text(name);
+    let Some((first, rest)) = links.split_first() else {
+        return name.into_owned();
+    };
+    let mut html = format!(
+        "<a href=\"{}\">{}</a>",
+        html_escape::encode_double_quoted_attribute(&first.uri),
+        strong(first, &name)
+    );
+    for (number, link) in rest.iter().enumerate() {
+        html.push_str(&format!(
+            ", <a href=\"{}\">{}</a>",
+            html_escape::encode_double_quoted_attribute(&link.uri),
+            strong(link, &format!("[{}]", number + 1))
+        ));
+    }
+    html
+}
+
+/// Body of the general index page: a jump box with the group keys, then a
+/// two-column table of terms for each group
+pub fn render_index(groups: &[GeneralIndexGroup]) -> String {
+    let mut html = String::from("<h1 id=\"index\">Index</h1>\n\n<div class=\"genindex-jumpbox\">\n");
+    let jumps: Vec<String> = groups
+        .iter()
+        .map(|group| format!(" <a href=\"#{0}\"><strong>{0}</strong></a>", group.key))
+        .collect();
+    html.push_str(&jumps.join("\n |"));
+    html.push_str("\n</div>\n");
+
+    for group in groups {
+        html.push_str(&format!(
+            "<h2 id=\"{0}\">{0}</h2>\n<table class=\"indextable genindextable\"><tr>\n",
+            group.key
+        ));
+        let (left, right) = group.entries.split_at(group.entries.len().div_ceil(2));
+        for column in [left, right] {
+            if column.is_empty() {
+                continue;
+            }
+            html.push_str("  <td><ul>\n");
+            for entry in column {
+                html.push_str(&format!("      <li>{}", render_links(&entry.name, &entry.links)));
+                if !entry.subentries.is_empty() {
+                    html.push_str("\n      <ul>\n");
+                    for (name, links) in &entry.subentries {
+                        html.push_str(&format!("        <li>{}</li>\n", render_links(name, links)));
+                    }
+                    html.push_str("      </ul>");
+                }
+                html.push_str("</li>\n");
+            }
+            html.push_str("  </ul></td>\n");
+        }
+        html.push_str("</tr></table>\n");
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(kind: IndexEntryKind, value: &str, target: &str) -> IndexTerm {
+        IndexTerm {
+            kind,
+            value: value.to_string(),
+            main: false,
+            target: target.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_directive_entries() {
+        let args = vec!["single: loop; for".to_string()];
+        let content = "!pair: loop; statement\nseealso: loop; iteration\nwhile, until\ntriple: a; b";
+        let target = index_directive(&args, &HashMap::new(), content);
+
+        assert_eq!(target.id, "index-loop-for");
+        let kinds: Vec<_> = target.terms.iter().map(|term| (term.kind, term.value.as_str(), term.main)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (IndexEntryKind::Single, "loop; for", false),
+                (IndexEntryKind::Pair, "loop; statement", true),
+                (IndexEntryKind::SeeAlso, "loop; iteration", false),
+                (IndexEntryKind::Single, "while", false),
+                (IndexEntryKind::Single, "until", false),
+            ]
+        );
+        assert_eq!(target.errors, vec!["index entry 'triple: a; b' needs three parts separated by ';'"]);
+
+        let options = HashMap::from([("name".to_string(), "loops".to_string())]);
+        assert_eq!(index_directive(&args, &options, "").id, "loops");
+    }
+
+    #[test]
+    fn test_role_entries() {
+        let (text, target) = index_role("loops <pair: loop; statement>");
+        assert_eq!(text, "loops");
+        assert_eq!(target.id, "index-loop-statement");
+        assert_eq!(target.terms, vec![term(IndexEntryKind::Pair, "loop; statement", "index-loop-statement")]);
+
+        let (text, target) = index_role("!Recursion");
+        assert_eq!(text, "Recursion");
+        assert_eq!(target.id, "index-recursion");
+        assert!(target.terms[0].main);
+    }
+
+    #[test]
+    fn test_build_index_expands_and_groups_entries() {
+        let api = vec![
+            term(IndexEntryKind::Pair, "loop; statement", "index-loop-statement"),
+            term(IndexEntryKind::See, "iteration; loop", "index-iteration-loop"),
+            term(IndexEntryKind::Single, "_private", "index-private"),
+            term(IndexEntryKind::Single, "<=", "index"),
+        ];
+        let guide = vec![term(IndexEntryKind::Single, "Loop", "index-loop")];
+        let groups = build_index([("api/ref", api.as_slice()), ("guide", guide.as_slice())]);
+
+        let keys: Vec<&str> = groups.iter().map(|group| group.key.as_str()).collect();
+        assert_eq!(keys, vec!["Symbols", "_", "I", "L", "S"]);
+
+        let loop_group = &groups[3];
+        assert_eq!(loop_group.entries.len(), 2, "case differs, so two entries: {:?}", loop_group);
+        assert_eq!(loop_group.entries[0].name, "Loop");
+        assert_eq!(loop_group.entries[0].links[0].uri, "guide.html#index-loop");
+        assert_eq!(
+            loop_group.entries[1].subentries,
+            vec![(
+                "statement".to_string(),
+                vec![IndexLink {
+                    uri: "api/ref.html#index-loop-statement".to_string(),
+                    main: false
+                }]
+            )]
+        );
+        assert_eq!(groups[2].entries[0].subentries, vec![("see loop".to_string(), Vec::new())]);
+
+        let html = render_index(&groups);
+        assert!(html.contains("<a href=\"#Symbols\"><strong>Symbols</strong></a>"), "got: {}", html);
+        assert!(html.contains("<h2 id=\"S\">S</h2>"));
+        assert!(html.contains("<li><a href=\"api/ref.html#index-loop-statement\">loop</a></li>"), "got: {}", html);
+        assert!(html.contains("&lt;="));
+        assert!(!html.contains("style="), "got: {}", html);
+    }
+}
@@ -0,0 +1,794 @@
+//! Tokenizer for inline markup, shared by the RST and Markdown renderers.
+//!
+//! RST follows the docutils recognition rules: markup starts after
+//! whitespace or opening punctuation and ends before whitespace or closing
+//! punctuation, so a backtick inside role content or a `*` inside a word is
+//! text. Backslashes escape the next character. Emphasis and links nest;
+//! literals and role content do not.
+
+use crate::footnotes::NoteLabel;
+
+/// Inline markup flavour of a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Rst,
+    /// CommonMark-style markup with MyST `{role}`content`` roles
+    Markdown,
+}
+
+/// A piece of inline markup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inline {
+    /// Text with escapes resolved
+    Text(String),
+    /// ``literal`` text, or a Markdown code span
+    Literal(String),
+    Emphasis(Vec<Inline>),
+    Strong(Vec<Inline>),
+    /// Interpreted text: a role, or `None` for RST's default role, and its
+    /// content with escaped backticks resolved
+    Role { name: Option<String>, content: String },
+    /// RST hyperlink reference: `` `text <target>`_ `` or `name_`
+    Reference { text: String, target: ReferenceTarget },
+    /// Markdown link: `[text](url)`
+    Link { children: Vec<Inline>, url: String },
+    /// RST footnote or citation reference, `[1]_`, `[#]_` or `[CIT2002]_`,
+    /// with the label between the brackets
+    FootnoteReference(String),
+}
+
+/// Where an RST hyperlink reference points
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReferenceTarget {
+    /// An embedded URI
+    Uri(String),
+    /// A target in the document, by reference name
+    Name(String),
+}
+
+/// Split `text` into inline markup
+pub fn parse(text: &str, syntax: Syntax) -> Vec<Inline> {
+    let chars: Vec<char> = text.chars().collect();
+    match syntax {
+        Syntax::Rst => RstTokenizer::new(&chars).run(),
+        Syntax::Markdown => MarkdownTokenizer::new(&chars).run(),
+    }
+}
+
+/// The text of inline markup without the markup: roles give their title,
+/// references their text
+pub fn plain_text(nodes: &[Inline]) -> String {
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            Inline::Text(value) | Inline::Literal(value) | Inline::Reference { text: value, .. } => {
+                text.push_str(value)
+            }
+            Inline::Emphasis(children) | Inline::Strong(children) | Inline::Link { children, .. } => {
+                text.push_str(&plain_text(children))
+            }
+            Inline::Role { content, .. } => text.push_str(split_title_target(content).0),
+            Inline::FootnoteReference(_) => {}
+        }
+    }
+    text
+}
+
+/// Split role or reference content of the form `title <target>` into its
+/// title and target; content without an embedded target is both
+pub fn split_title_target(content: &str) -> (&str, &str) {
+    if let Some(open) = content.rfind('<') {
+        if content.ends_with('>') && !is_escaped(content, open) {
+            let title = content[..open].trim();
+            let target = content[open + 1..content.len() - 1].trim();
+            return (if title.is_empty() { target } else { title }, target);
+        }
+    }
+    (content.trim(), content.trim())
+}
+
+/// Whether the character at byte `index` follows an odd number of backslashes
+fn is_escaped(text: &str, index: usize) -> bool {
+    text[..index].chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+/// Characters that may precede an RST start-string
+fn is_start_prefix(c: char) -> bool {
+    c.is_whitespace() || "-:/'\"<([{‘“«’".contains(c)
+}
+
+/// Characters that may follow an RST end-string
+fn is_end_suffix(c: char) -> bool {
+    c.is_whitespace() || "-.,:;!?\\/'\")]}>’”»".contains(c)
+}
+
+/// Closing character that makes `open` + start-string + `close` a quoted
+/// start-string rather than markup, as in `"*"` or `(*)`
+fn matching_quote(open: char) -> Option<char> {
+    match open {
+        '\'' => Some('\''),
+        '"' => Some('"'),
+        '<' => Some('>'),
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '‘' => Some('’'),
+        '“' => Some('”'),
+        '«' => Some('»'),
+        _ => None,
+    }
+}
+
+/// URI schemes recognized in standalone hyperlinks, a subset of docutils'
+/// registry covering the ones found in documentation
+const STANDALONE_SCHEMES: &[&str] = &[
+    "http", "https", "ftp", "ftps", "sftp", "file", "mailto", "news", "nntp", "irc", "ircs", "ssh",
+    "git", "svn", "tel", "xmpp", "ws", "wss", "s3", "gopher", "telnet",
+];
+
+/// A standalone hyperlink at `start`: a URI with a known scheme or an email
+/// address, as its text, its URL and the position after it. Trailing
+/// punctuation belongs to the sentence, not the link.
+fn standalone_link(chars: &[char], start: usize) -> Option<(String, String, usize)> {
+    let is_uri_char = |c: &char| !c.is_whitespace() && !"<>\"`\\{}|^".contains(*c);
+    let scheme_end = start + chars[start..].iter().take_while(|c| c.is_ascii_alphanumeric() || "+-.".contains(**c)).count();
+    let scheme: String = chars[start..scheme_end].iter().collect::<String>().to_ascii_lowercase();
+    if chars.get(scheme_end) == Some(&':') && STANDALONE_SCHEMES.contains(&scheme.as_str()) {
+        let mut end = scheme_end + 1 + chars[scheme_end + 1..].iter().take_while(|c| is_uri_char(c)).count();
+        end = trim_link_end(chars, start, end);
+        if end <= scheme_end + 1 {
+            return None;
+        }
+        let rest: String = chars[scheme_end + 1..end].iter().collect();
+        let hierarchical = !matches!(scheme.as_str(), "mailto" | "news" | "tel" | "xmpp");
+        let valid = if hierarchical { rest.len() > 2 && rest.starts_with("//") } else { !rest.is_empty() };
+        if !valid {
+            return None;
+        }
+        let text: String = chars[start..end].iter().collect();
+        return Some((text.clone(), text, end));
+    }
+
+    // user@example.org
+    let is_local = |c: &char| c.is_ascii_alphanumeric() || "._%+-".contains(*c);
+    let at = start + chars[start..].iter().take_while(|c| is_local(c)).count();
+    if chars.get(at) != Some(&'@') || at == start {
+        return None;
+    }
+    let mut end = at + 1 + chars[at + 1..].iter().take_while(|c| c.is_ascii_alphanumeric() || "-.".contains(**c)).count();
+    while end > at + 1 && ".-".contains(chars[end - 1]) {
+        end -= 1;
+    }
+    let domain: String = chars[at + 1..end].iter().collect();
+    let top_level = domain.rsplit('.').next().unwrap_or_default();
+    if !domain.contains('.') || top_level.len() < 2 || !top_level.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let text: String = chars[start..end].iter().collect();
+    Some((text.clone(), format!("mailto:{}", text), end))
+}
+
+/// End of a URI without the punctuation that ends the sentence around it;
+/// a closing bracket stays when the URI opened it, as in Wikipedia links
+fn trim_link_end(chars: &[char], start: usize, mut end: usize) -> usize {
+    while end > start {
+        let last = chars[end - 1];
+        let unbalanced = |open: char| {
+            let link = &chars[start..end];
+            link.iter().filter(|&&c| c == open).count() < link.iter().filter(|&&c| c == last).count()
+        };
+        let trailing = match last {
+            '.' | ',' | ';' | ':' | '!' | '?' | '\'' | '"' | '*' => true,
+            ')' => unbalanced('('),
+            ']' => unbalanced('['),
+            _ => false,
+        };
+        if !trailing {
+            break;
+        }
+        end -= 1;
+    }
+    end
+}
+
+/// Resolve escaped backticks in interpreted text; other escapes are left
+/// for the role, since roles like `math` need their backslashes
+fn unescape_backticks(text: &str) -> String {
+    text.replace("\\`", "`")
+}
+
+/// Collects text between markup and flushes it as `Inline::Text`
+#[derive(Default)]
+struct Output {
+    nodes: Vec<Inline>,
+    text: String,
+}
+
+impl Output {
+    fn push(&mut self, node: Inline) {
+        self.flush();
+        self.nodes.push(node);
+    }
+
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            self.nodes.push(Inline::Text(std::mem::take(&mut self.text)));
+        }
+    }
+
+    fn finish(mut self) -> Vec<Inline> {
+        self.flush();
+        self.nodes
+    }
+}
+
+struct RstTokenizer<'a> {
+    chars: &'a [char],
+    pos: usize,
+    out: Output,
+}
+
+impl<'a> RstTokenizer<'a> {
+    fn new(chars: &'a [char]) -> Self {
+        Self {
+            chars,
+            pos: 0,
+            out: Output::default(),
+        }
+    }
+
+    fn run(mut self) -> Vec<Inline> {
+        while self.pos < self.chars.len() {
+            let c = self.chars[self.pos];
+            if c == '\\' {
+                // An escaped character is text; an escaped space disappears
+                match self.chars.get(self.pos + 1) {
+                    Some(next) if next.is_whitespace() => {}
+                    Some(&next) => self.out.text.push(next),
+                    None => self.out.text.push('\\'),
+                }
+                self.pos += 2;
+                continue;
+            }
+            if let Some(node) = self.markup() {
+                self.out.push(node);
+                continue;
+            }
+            if c.is_alphanumeric() {
+                if let Some(node) = self.standalone_link() {
+                    self.out.push(node);
+                    continue;
+                }
+                if let Some(node) = self.bare_reference() {
+                    self.out.push(node);
+                    continue;
+                }
+                // Markup never starts inside a word
+                while self.pos < self.chars.len() && self.chars[self.pos].is_alphanumeric() {
+                    self.out.text.push(self.chars[self.pos]);
+                    self.pos += 1;
+                }
+                continue;
+            }
+            self.out.text.push(c);
+            self.pos += 1;
+        }
+        self.out.finish()
+    }
+
+    fn at(&self, index: usize, s: &str) -> bool {
+        s.chars().enumerate().all(|(offset, c)| self.chars.get(index + offset) == Some(&c))
+    }
+
+    /// Whether a start-string of `len` characters may begin at `index`
+    fn starts(&self, index: usize, len: usize) -> bool {
+        let Some(&next) = self.chars.get(index + len) else {
+            return false;
+        };
+        if next.is_whitespace() {
+            return false;
+        }
+        match index.checked_sub(1).map(|prev| self.chars[prev]) {
+            None => true,
+            Some(prev) => is_start_prefix(prev) && matching_quote(prev) != Some(next),
+        }
+    }
+
+    /// Whether an end-string of `len` characters may end at `index`
+    fn ends(&self, index: usize, len: usize) -> bool {
+        index > 0
+            && !self.chars[index - 1].is_whitespace()
+            && self.chars.get(index + len).is_none_or(|&c| is_end_suffix(c))
+    }
+
+    /// Position of the end-string `delim` from `from`, skipping escaped
+    /// characters unless the markup is literal
+    fn find_end(&self, from: usize, delim: &str, escapes: bool, ends: impl Fn(usize) -> bool) -> Option<usize> {
+        let mut index = from;
+        while index < self.chars.len() {
+            if escapes && self.chars[index] == '\\' {
+                index += 2;
+                continue;
+            }
+            if index > from && self.at(index, delim) && ends(index) {
+                return Some(index);
+            }
+            index += 1;
+        }
+        None
+    }
+
+    fn text(&self, start: usize, end: usize) -> String {
+        self.chars[start..end].iter().collect()
+    }
+
+    /// Markup starting at the current position, which is consumed
+    fn markup(&mut self) -> Option<Inline> {
+        let pos = self.pos;
+        let (node, end) = match self.chars[pos] {
+            '`' if self.at(pos, "``") => {
+                if !self.starts(pos, 2) {
+                    return None;
+                }
+                let end = self.find_end(pos + 2, "``", false, |index| self.ends(index, 2))?;
+                (Inline::Literal(self.text(pos + 2, end)), end + 2)
+            }
+            '`' => {
+                if !self.starts(pos, 1) {
+                    return None;
+                }
+                let end = self.find_end(pos + 1, "`", true, |index| self.ends_interpreted(index))?;
+                let content = self.text(pos + 1, end);
+                self.interpreted(content, end + 1)
+            }
+            ':' => {
+                let (name, open) = self.role_prefix(pos)?;
+                if !self.starts(pos, open + 1 - pos) {
+                    return None;
+                }
+                let end = self.find_end(open + 1, "`", true, |index| self.ends(index, 1))?;
+                let content = unescape_backticks(&self.text(open + 1, end));
+                (Inline::Role { name: Some(name), content }, end + 1)
+            }
+            '[' => {
+                if !self.starts(pos, 1) {
+                    return None;
+                }
+                let close = (pos + 1..self.chars.len()).find(|&index| matches!(self.chars[index], ']' | ' ' | '\t' | '\n'))?;
+                let label = self.text(pos + 1, close);
+                if self.chars[close] != ']' || !self.at(close + 1, "_") || NoteLabel::parse(&label).is_none() {
+                    return None;
+                }
+                if !self.chars.get(close + 2).is_none_or(|&c| is_end_suffix(c)) {
+                    return None;
+                }
+                (Inline::FootnoteReference(label), close + 2)
+            }
+            '*' if self.at(pos, "**") => {
+                if !self.starts(pos, 2) {
+                    return None;
+                }
+                let end = self.find_end(pos + 2, "**", true, |index| self.ends(index, 2))?;
+                (Inline::Strong(RstTokenizer::new(&self.chars[pos + 2..end]).run()), end + 2)
+            }
+            '*' => {
+                if !self.starts(pos, 1) {
+                    return None;
+                }
+                let end = self.find_end(pos + 1, "*", true, |index| self.ends(index, 1))?;
+                (Inline::Emphasis(RstTokenizer::new(&self.chars[pos + 1..end]).run()), end + 1)
+            }
+            _ => return None,
+        };
+        self.pos = end;
+        Some(node)
+    }
+
+    /// Whether interpreted text may end at `index`, where the closing
+    /// backtick may be followed by a reference or role suffix
+    fn ends_interpreted(&self, index: usize) -> bool {
+        self.ends(index, 1) || (!self.chars[index - 1].is_whitespace() && self.chars.get(index + 1) == Some(&'_'))
+    }
+
+    /// Interpreted text closed before `after`: a reference, a role given
+    /// after the text, or the default role
+    fn interpreted(&self, content: String, after: usize) -> (Inline, usize) {
+        if self.at(after, "_") {
+            let after = if self.at(after, "__") { after + 2 } else { after + 1 };
+            return (reference(&content), after);
+        }
+        if let Some((name, close)) = self.role_suffix(after) {
+            let content = unescape_backticks(&content);
+            return (Inline::Role { name: Some(name), content }, close + 1);
+        }
+        (Inline::Role { name: None, content: unescape_backticks(&content) }, after)
+    }
+
+    /// Role name of `:name:` at `index` followed by a backtick, with the
+    /// position of the backtick
+    fn role_prefix(&self, index: usize) -> Option<(String, usize)> {
+        let close = self.role_name_end(index + 1)?;
+        (self.chars.get(close + 1) == Some(&'`')).then(|| (self.text(index + 1, close), close + 1))
+    }
+
+    /// Role name of `:name:` at `index`, with the position of its closing colon
+    fn role_suffix(&self, index: usize) -> Option<(String, usize)> {
+        if self.chars.get(index) != Some(&':') {
+            return None;
+        }
+        let close = self.role_name_end(index + 1)?;
+        Some((self.text(index + 1, close), close))
+    }
+
+    /// Position of the colon closing a role name that starts at `start`.
+    /// Names are alphanumerics joined by single `-_.:+` characters, so the
+    /// colon of `py:func` belongs to the name.
+    fn role_name_end(&self, start: usize) -> Option<usize> {
+        if !self.chars.get(start)?.is_alphanumeric() {
+            return None;
+        }
+        let mut index = start + 1;
+        while let Some(&c) = self.chars.get(index) {
+            if c.is_alphanumeric() {
+                index += 1;
+            } else if "-_.:+".contains(c) && self.chars.get(index + 1).is_some_and(|next| next.is_alphanumeric()) {
+                index += 2;
+            } else {
+                break;
+            }
+        }
+        (self.chars.get(index) == Some(&':')).then_some(index)
+    }
+
+    /// A standalone URI or email address starting at the current position,
+    /// which is consumed
+    fn standalone_link(&mut self) -> Option<Inline> {
+        if self.pos > 0 && !is_start_prefix(self.chars[self.pos - 1]) {
+            return None;
+        }
+        let (text, url, end) = standalone_link(self.chars, self.pos)?;
+        self.pos = end;
+        Some(Inline::Reference {
+            text,
+            target: ReferenceTarget::Uri(url),
+        })
+    }
+
+    /// A `name_` reference starting at the current position, which is consumed
+    fn bare_reference(&mut self) -> Option<Inline> {
+        let pos = self.pos;
+        if pos > 0 && !is_start_prefix(self.chars[pos - 1]) {
+            return None;
+        }
+        let mut end = pos + 1;
+        while let Some(&c) = self.chars.get(end) {
+            if c.is_alphanumeric() {
+                end += 1;
+            } else if "-_.+:".contains(c) && self.chars.get(end + 1).is_some_and(|next| next.is_alphanumeric()) {
+                end += 2;
+            } else {
+                break;
+            }
+        }
+        if self.chars.get(end) != Some(&'_') {
+            return None;
+        }
+        let marker = if self.at(end, "__") { 2 } else { 1 };
+        if !self.chars.get(end + marker).is_none_or(|&c| is_end_suffix(c)) {
+            return None;
+        }
+        let name = self.text(pos, end);
+        self.pos = end + marker;
+        Some(Inline::Reference {
+            text: name.clone(),
+            target: ReferenceTarget::Name(name),
+        })
+    }
+}
+
+/// A reference from the content of `` `content`_ ``
+fn reference(content: &str) -> Inline {
+    let (text, target) = split_title_target(content);
+    let embedded = content.trim_end().ends_with('>') && content.contains('<');
+    let target = match target.strip_suffix('_') {
+        Some(name) if embedded && !is_escaped(target, target.len() - 1) => ReferenceTarget::Name(name.to_string()),
+        // Whitespace in an embedded URI is line wrapping
+        _ if embedded => ReferenceTarget::Uri(target.split_whitespace().collect()),
+        _ => ReferenceTarget::Name(text.to_string()),
+    };
+    Inline::Reference {
+        text: unescape_backticks(text),
+        target,
+    }
+}
+
+struct MarkdownTokenizer<'a> {
+    chars: &'a [char],
+    pos: usize,
+    out: Output,
+}
+
+impl<'a> MarkdownTokenizer<'a> {
+    fn new(chars: &'a [char]) -> Self {
+        Self {
+            chars,
+            pos: 0,
+            out: Output::default(),
+        }
+    }
+
+    fn run(mut self) -> Vec<Inline> {
+        while self.pos < self.chars.len() {
+            let c = self.chars[self.pos];
+            if c == '\\' {
+                match self.chars.get(self.pos + 1) {
+                    Some(&next) if next.is_ascii_punctuation() => {
+                        self.out.text.push(next);
+                        self.pos += 2;
+                    }
+                    _ => {
+                        self.out.text.push('\\');
+                        self.pos += 1;
+                    }
+                }
+                continue;
+            }
+            if let Some(node) = self.markup() {
+                self.out.push(node);
+                continue;
+            }
+            let after_word = self.pos > 0 && self.chars[self.pos - 1].is_alphanumeric();
+            if c.is_ascii_alphanumeric() && !after_word {
+                if let Some((text, url, end)) = standalone_link(self.chars, self.pos) {
+                    self.out.push(Inline::Link { children: vec![Inline::Text(text)], url });
+                    self.pos = end;
+                    continue;
+                }
+            }
+            if c == '`' {
+                // An unclosed backtick run stays text as a whole
+                let run = self.run_length(self.pos, '`');
+                self.out.text.extend(&self.chars[self.pos..self.pos + run]);
+                self.pos += run;
+                continue;
+            }
+            self.out.text.push(c);
+            self.pos += 1;
+        }
+        self.out.finish()
+    }
+
+    fn run_length(&self, index: usize, c: char) -> usize {
+        self.chars[index..].iter().take_while(|&&next| next == c).count()
+    }
+
+    fn text(&self, start: usize, end: usize) -> String {
+        self.chars[start..end].iter().collect()
+    }
+
+    /// Markup starting at the current position, which is consumed
+    fn markup(&mut self) -> Option<Inline> {
+        let pos = self.pos;
+        let (node, end) = match self.chars[pos] {
+            '`' => {
+                let (content, end) = self.code_span(pos)?;
+                (Inline::Literal(content), end)
+            }
+            '{' => {
+                let close = pos + 1 + self.chars[pos + 1..].iter().position(|&c| c == '}')?;
+                let name = self.text(pos + 1, close);
+                let valid = !name.is_empty()
+                    && name.chars().all(|c| c.is_alphanumeric() || "-_.:+".contains(c));
+                if !valid || self.chars.get(close + 1) != Some(&'`') {
+                    return None;
+                }
+                let (content, end) = self.code_span(close + 1)?;
+                (Inline::Role { name: Some(name), content }, end)
+            }
+            '<' => {
+                // Autolinks: <https://example.org> and <user@example.org>
+                let close = pos + 1 + self.chars[pos + 1..].iter().position(|&c| c == '>')?;
+                let (text, url, end) = standalone_link(&self.chars[..close], pos + 1)?;
+                if end != close {
+                    return None;
+                }
+                (Inline::Link { children: vec![Inline::Text(text)], url }, close + 1)
+            }
+            '[' => {
+                let close = self.matching_bracket(pos)?;
+                if self.chars.get(close + 1) != Some(&'(') {
+                    return None;
+                }
+                let end = close + 2 + self.chars[close + 2..].iter().position(|&c| c == ')')?;
+                let children = MarkdownTokenizer::new(&self.chars[pos + 1..close]).run();
+                (Inline::Link { children, url: self.text(close + 2, end).trim().to_string() }, end + 1)
+            }
+            delim @ ('*' | '_') => {
+                let strong = self.run_length(pos, delim) >= 2;
+                let len = if strong { 2 } else { 1 };
+                let next = *self.chars.get(pos + len)?;
+                let prev = pos.checked_sub(1).map(|prev| self.chars[prev]);
+                // Underscores inside words are text
+                if next.is_whitespace() || (delim == '_' && prev.is_some_and(char::is_alphanumeric)) {
+                    return None;
+                }
+                let end = self.find_closing(pos + len, delim, len)?;
+                let children = MarkdownTokenizer::new(&self.chars[pos + len..end]).run();
+                let node = if strong { Inline::Strong(children) } else { Inline::Emphasis(children) };
+                (node, end + len)
+            }
+            _ => return None,
+        };
+        self.pos = end;
+        Some(node)
+    }
+
+    /// Content of a code span opened by the backtick run at `start`, and
+    /// the position after it. A single space padding both ends is dropped.
+    fn code_span(&self, start: usize) -> Option<(String, usize)> {
+        let run = self.run_length(start, '`');
+        let mut index = start + run;
+        while index < self.chars.len() {
+            if self.chars[index] == '`' {
+                let closing = self.run_length(index, '`');
+                if closing == run {
+                    let content = self.text(start + run, index);
+                    let content = match content.strip_prefix(' ').and_then(|inner| inner.strip_suffix(' ')) {
+                        Some(inner) if !inner.trim().is_empty() => inner.to_string(),
+                        _ => content,
+                    };
+                    return Some((content, index + run));
+                }
+                index += closing;
+            } else {
+                index += 1;
+            }
+        }
+        None
+    }
+
+    /// Position of the `]` closing the `[` at `open`
+    fn matching_bracket(&self, open: usize) -> Option<usize> {
+        let mut depth = 0;
+        let mut index = open;
+        while index < self.chars.len() {
+            match self.chars[index] {
+                '\\' => index += 1,
+                '[' => depth += 1,
+                ']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(index);
+                    }
+                }
+                _ => {}
+            }
+            index += 1;
+        }
+        None
+    }
+
+    /// Position of a run of `len` `delim` characters closing emphasis
+    fn find_closing(&self, from: usize, delim: char, len: usize) -> Option<usize> {
+        let mut index = from;
+        while index < self.chars.len() {
+            match self.chars[index] {
+                '\\' => index += 2,
+                '`' => match self.code_span(index) {
+                    Some((_, end)) => index = end,
+                    None => index += 1,
+                },
+                c if c == delim => {
+                    let run = self.run_length(index, delim);
+                    let after = self.chars.get(index + run);
+                    let closes = run >= len
+                        && index > from
+                        && !self.chars[index - 1].is_whitespace()
+                        && (delim == '*' || after.is_none_or(|c| !c.is_alphanumeric()));
+                    if closes {
+                        // The closing run of `***` ends the inner emphasis first
+                        return Some(index + run - len);
+                    }
+                    index += run;
+                }
+                _ => index += 1,
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> Inline {
+        Inline::Text(value.to_string())
+    }
+
+    fn role(name: &str, content: &str) -> Inline {
+        Inline::Role {
+            name: Some(name.to_string()),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rst_role_content_with_backticks_and_escapes() {
+        assert_eq!(parse(":code:`a`b` c", Syntax::Rst), vec![role("code", "a`b"), text(" c")]);
+        assert_eq!(parse(r":code:`\`x\``", Syntax::Rst), vec![role("code", "`x`")]);
+        assert_eq!(parse(r":math:`\alpha`", Syntax::Rst), vec![role("math", r"\alpha")]);
+        assert_eq!(parse("`target`:py:func:.", Syntax::Rst), vec![role("py:func", "target"), text(".")]);
+        assert_eq!(parse(r"\*not emphasis\*", Syntax::Rst), vec![text("*not emphasis*")]);
+        assert_eq!(parse("2*3*4 and ``a `b` c``", Syntax::Rst)[1], Inline::Literal("a `b` c".to_string()));
+    }
+
+    #[test]
+    fn test_rst_roles_nest_in_emphasis_and_references() {
+        assert_eq!(
+            parse("**see :ref:`intro`** or `Docs <https://example.org/>`_ and name_.", Syntax::Rst),
+            vec![
+                Inline::Strong(vec![text("see "), role("ref", "intro")]),
+                text(" or "),
+                Inline::Reference {
+                    text: "Docs".to_string(),
+                    target: ReferenceTarget::Uri("https://example.org/".to_string()),
+                },
+                text(" and "),
+                Inline::Reference {
+                    text: "name".to_string(),
+                    target: ReferenceTarget::Name("name".to_string()),
+                },
+                text("."),
+            ]
+        );
+        assert_eq!(plain_text(&parse("*The* :doc:`guide <intro>` ``code``", Syntax::Rst)), "The guide code");
+    }
+
+    #[test]
+    fn test_standalone_links_and_email_addresses() {
+        let uri = |text: &str, url: &str| Inline::Reference {
+            text: text.to_string(),
+            target: ReferenceTarget::Uri(url.to_string()),
+        };
+        assert_eq!(
+            parse("See https://en.wikipedia.org/wiki/Rust_(language). Mail docs@example.org, or (mailto:a@b.io)!", Syntax::Rst),
+            vec![
+                text("See "),
+                uri("https://en.wikipedia.org/wiki/Rust_(language)", "https://en.wikipedia.org/wiki/Rust_(language)"),
+                text(". Mail "),
+                uri("docs@example.org", "mailto:docs@example.org"),
+                text(", or ("),
+                uri("mailto:a@b.io", "mailto:a@b.io"),
+                text(")!"),
+            ]
+        );
+        assert_eq!(parse("``https://example.org`` and http: only", Syntax::Rst)[0], Inline::Literal("https://example.org".to_string()));
+        assert_eq!(parse("http: only", Syntax::Rst), vec![text("http: only")]);
+        assert_eq!(
+            parse("<https://example.org/a> and www.example.org", Syntax::Markdown)[0],
+            Inline::Link {
+                children: vec![text("https://example.org/a")],
+                url: "https://example.org/a".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_markdown_roles_code_spans_and_links() {
+        assert_eq!(
+            parse("{ref}`intro` and `` a`b `` in [**bold** text](page.md)", Syntax::Markdown),
+            vec![
+                role("ref", "intro"),
+                text(" and "),
+                Inline::Literal("a`b".to_string()),
+                text(" in "),
+                Inline::Link {
+                    children: vec![Inline::Strong(vec![text("bold")]), text(" text")],
+                    url: "page.md".to_string(),
+                },
+            ]
+        );
+        assert_eq!(parse("snake_case_name", Syntax::Markdown), vec![text("snake_case_name")]);
+    }
+}
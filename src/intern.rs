@@ -0,0 +1,200 @@
+//! Interned strings for document paths, anchors and titles.
+//!
+//! The same document names are keys of many maps across the builder,
+//! navigation and renderer. A [`Symbol`] shares one allocation per distinct
+//! string, so cloning it into another map is a reference count increment and
+//! comparing two symbols of the same string usually stops at the pointer.
+//! Interned strings live until [`release_unused`] finds no symbol holding
+//! them, which each build does before it starts.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+
+lazy_static::lazy_static! {
+    static ref INTERNER: dashmap::DashSet<Arc<str>> = dashmap::DashSet::new();
+}
+
+/// Forget the interned strings that no symbol holds any more, so a process
+/// that builds again and again only keeps the strings still in use
+pub fn release_unused() {
+    INTERNER.retain(|value| Arc::strong_count(value) > 1);
+}
+
+/// An interned, immutable string.
+///
+/// Symbols hash and compare like the string they hold, so maps keyed by
+/// `Symbol` can be queried with a `&str`.
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    /// The symbol of `value`, shared with every other symbol of the same string
+    pub fn intern(value: &str) -> Self {
+        if let Some(existing) = INTERNER.get(value) {
+            return Symbol(Arc::clone(&existing));
+        }
+        let value: Arc<str> = Arc::from(value);
+        if INTERNER.insert(Arc::clone(&value)) {
+            Symbol(value)
+        } else {
+            // Another thread interned the string in the meantime
+            Self::intern(&value)
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for Symbol {
+    fn default() -> Self {
+        Symbol::intern("")
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl Eq for Symbol {}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(value: &str) -> Self {
+        Symbol::intern(value)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(value: String) -> Self {
+        Symbol::intern(&value)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(value: &String) -> Self {
+        Symbol::intern(value)
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(value: Symbol) -> Self {
+        value.0.to_string()
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Ok(Symbol::intern(&value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_symbols_share_storage_and_act_as_str() {
+        let a = Symbol::intern("guide/intro");
+        let b = Symbol::from(String::from("guide/intro"));
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        assert_eq!(a, b);
+        assert_eq!(a, "guide/intro");
+        assert_ne!(a, Symbol::intern("guide/usage"));
+
+        let mut titles: HashMap<Symbol, Symbol> = HashMap::new();
+        titles.insert(a, Symbol::intern("Introduction"));
+        assert_eq!(titles.get("guide/intro").map(Symbol::as_str), Some("Introduction"));
+
+        let json = serde_json::to_string(&b).unwrap();
+        assert_eq!(json, "\"guide/intro\"");
+        let back: Symbol = serde_json::from_str(&json).unwrap();
+        assert!(Arc::ptr_eq(&back.0, &b.0));
+    }
+
+    #[test]
+    fn test_release_unused_keeps_held_strings() {
+        let held = Symbol::intern("intern-test/held");
+        drop(Symbol::intern("intern-test/dropped"));
+        release_unused();
+        assert!(INTERNER.contains("intern-test/held"));
+        assert!(!INTERNER.contains("intern-test/dropped"));
+        assert!(Arc::ptr_eq(&held.0, &Symbol::intern("intern-test/held").0));
+    }
+}
@@ -60,6 +60,12 @@ impl Inventory {
     }
 }
 
+lazy_static::lazy_static! {
+    /// `name domain:role priority uri dispname`, where the name may contain spaces
+    static ref INVENTORY_LINE_RE: regex::Regex =
+        regex::Regex::new(r"(.+?)\s+(\S+)\s+(-?\d+)\s+?(\S*)\s+(.*)").unwrap();
+}
+
 /// Inventory file handler - mirrors Sphinx's InventoryFile class
 pub struct InventoryFile;
 
@@ -240,9 +246,7 @@ impl InventoryFile {
 
     /// Parse a single inventory line, handling embedded spaces
     fn parse_inventory_line(line: &str) -> Vec<&str> {
-        let regex = regex::Regex::new(r"(.+?)\s+(\S+)\s+(-?\d+)\s+?(\S*)\s+(.*)").unwrap();
-
-        if let Some(captures) = regex.captures(line) {
+        if let Some(captures) = INVENTORY_LINE_RE.captures(line) {
             vec![
                 captures.get(1).map_or("", |m| m.as_str()),
                 captures.get(2).map_or("", |m| m.as_str()),
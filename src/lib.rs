@@ -2,29 +2,57 @@
 //!
 //! A high-performance Rust-based Sphinx documentation builder designed for large codebases.
 
+pub mod assets;
+pub mod bibtex;
 pub mod builder;
 pub mod cache;
+pub mod cancellation;
+pub mod code_objects;
 pub mod config;
+pub mod diff;
 pub mod directives;
+pub mod docstrings;
 pub mod document;
 pub mod domains;
 pub mod environment;
 pub mod error;
 pub mod extensions;
+pub mod footnotes;
+pub mod genindex;
+pub mod inline;
+pub mod intern;
 pub mod inventory;
+pub mod manifest;
 pub mod matching;
 pub mod navigation;
+pub mod numfig;
+pub mod observer;
+pub mod optimize;
 pub mod parser;
+pub mod paths;
+pub mod postprocess;
+pub mod progress;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod python_config;
+pub mod redirects;
 pub mod renderer;
 pub mod roles;
+pub mod scheduler;
 pub mod search;
+pub mod smartquotes;
+pub mod social_cards;
+pub mod stats;
+pub mod summary;
 pub mod template;
 pub mod theme;
+pub mod trace;
+pub mod transforms;
 pub mod utils;
 pub mod validation;
+pub mod versions;
 
-pub use builder::{BuildStats, SphinxBuilder};
+pub use builder::{BuildStats, LintReport, SphinxBuilder};
 pub use config::BuildConfig;
 pub use directives::{
     validation::{
@@ -37,10 +65,14 @@ pub use directives::{
 pub use document::Document;
 pub use domains::{CrossReference, DomainObject, DomainRegistry, DomainValidator, ReferenceType};
 pub use environment::BuildEnvironment;
-pub use error::BuildError;
+pub use error::{BuildError, BuildOutcome};
 pub use extensions::{ExtensionLoader, SphinxApp, SphinxExtension};
+pub use intern::Symbol;
 pub use inventory::{InventoryFile, InventoryItem};
+pub use observer::BuildObserver;
 pub use parser::Parser;
+pub use postprocess::{HtmlPostProcessor, PostProcessorRegistry};
+pub use progress::ProgressReporter;
 pub use python_config::{ConfPyConfig, PythonConfigParser};
 pub use renderer::HtmlRenderer;
 pub use search::SearchIndex;
@@ -51,3 +83,4 @@ pub use validation::{
     ConstraintEngine, ConstraintValidator, ContentItem, FieldValue, ValidationConfig,
     ValidationContext, ValidationResult, ValidationRule, ValidationSeverity, Validator,
 };
+pub use versions::MultiVersionBuilder;
@@ -4,8 +4,16 @@ use log::{info, warn};
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-use sphinx_ultra::{analyze_project, BuildConfig, SphinxBuilder};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use sphinx_ultra::diff::OutputDiff;
+use sphinx_ultra::paths;
+use sphinx_ultra::summary::{self, SummaryOptions};
+use sphinx_ultra::theme;
+use sphinx_ultra::{analyze_project, BuildConfig, BuildError, BuildOutcome, BuildStats, MultiVersionBuilder, ProgressReporter, SphinxBuilder};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -17,6 +25,10 @@ struct Cli {
     #[arg(short, long)]
     verbose: bool,
 
+    /// Only print warnings and errors, without a progress bar
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
     /// Configuration file path
     #[arg(short, long)]
     config: Option<PathBuf>,
@@ -24,6 +36,10 @@ struct Cli {
     /// Show backtrace on error
     #[arg(long)]
     backtrace: bool,
+
+    /// Print the progress bar and build summary without colors
+    #[arg(long, global = true)]
+    no_color: bool,
 }
 
 #[derive(Subcommand)]
@@ -38,7 +54,7 @@ enum Commands {
         #[arg(short, long, default_value = "_build")]
         output: PathBuf,
 
-        /// Number of parallel jobs
+        /// Number of parallel jobs (default: one per CPU core)
         #[arg(short, long)]
         jobs: Option<usize>,
 
@@ -57,6 +73,87 @@ enum Commands {
         /// Write warnings (and errors) to given file
         #[arg(short = 'w', long)]
         warning_file: Option<PathBuf>,
+
+        /// Number of fatal warnings tolerated before --fail-on-warning fails the build
+        #[arg(long)]
+        max_warnings: Option<usize>,
+
+        /// Write build statistics and timings as JSON to given file
+        #[arg(long)]
+        stats_json: Option<PathBuf>,
+
+        /// Number of slowest documents listed in the build statistics
+        #[arg(long)]
+        slowest: Option<usize>,
+
+        /// Write a trace of the build phases and documents to given file, as a
+        /// Chrome trace or, for a .folded file, as folded stacks for flamegraphs
+        #[arg(long)]
+        trace: Option<PathBuf>,
+
+        /// Render documents in batches and drop them afterwards, to bound memory use
+        #[arg(long)]
+        bounded_memory: bool,
+
+        /// Number of documents per batch in bounded memory mode
+        #[arg(long)]
+        batch_size: Option<usize>,
+
+        /// Set a tag for `.. only::` expressions (repeatable)
+        #[arg(short = 't', long = "tag")]
+        tags: Vec<String>,
+
+        /// Keep the output pages of removed documents
+        #[arg(long)]
+        keep_stale: bool,
+    },
+
+    /// Check the documentation for problems without writing any output
+    Lint {
+        /// Source directory
+        #[arg(short, long, default_value = ".")]
+        source: PathBuf,
+
+        /// Build output directory, whose files are never checked
+        #[arg(short, long, default_value = "_build")]
+        output: PathBuf,
+
+        /// Number of parallel jobs (default: one per CPU core)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        /// Print the diagnostics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Compare two output directories and summarize the changes as Markdown
+    Diff {
+        /// Output directory of the base build
+        old: PathBuf,
+
+        /// Output directory of the new build
+        new: PathBuf,
+
+        /// Write the summary to given file instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Build every selected git tag and branch into its own subdirectory
+    #[command(name = "multiversion")]
+    MultiVersion {
+        /// Source directory
+        #[arg(short, long, default_value = ".")]
+        source: PathBuf,
+
+        /// Output directory
+        #[arg(short, long, default_value = "_build")]
+        output: PathBuf,
+
+        /// Number of parallel jobs (default: one per CPU core)
+        #[arg(short, long)]
+        jobs: Option<usize>,
     },
 
     /// Clean build artifacts
@@ -71,6 +168,43 @@ enum Commands {
         /// Source directory
         #[arg(short, long, default_value = ".")]
         source: PathBuf,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Install and package themes
+    Theme {
+        #[command(subcommand)]
+        command: ThemeCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ThemeCommands {
+    /// Install a theme into the project's _themes directory
+    Install {
+        /// Theme archive (.tar.gz, .tgz or .tar), directory or git URL
+        theme: String,
+
+        /// Source directory of the project
+        #[arg(short, long, default_value = ".")]
+        source: PathBuf,
+
+        /// Replace an installed theme of the same name
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Package a theme directory as a .tar.gz archive
+    Pack {
+        /// Theme directory, with a theme.toml or theme.conf
+        theme_dir: PathBuf,
+
+        /// Archive to write (default: <name>-<version>.tar.gz)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 }
 
@@ -83,12 +217,25 @@ async fn main() {
         std::env::set_var("RUST_BACKTRACE", "1");
     }
 
-    // Initialize logging
-    let log_level = if cli.verbose { "debug" } else { "info" };
+    // Initialize logging. Log lines are printed above the progress bar of a
+    // build, which is only drawn on a terminal.
+    let log_level = if cli.verbose {
+        "debug"
+    } else if cli.quiet {
+        "warn"
+    } else {
+        "info"
+    };
     std::env::set_var("RUST_LOG", log_level);
-    env_logger::init();
+    if cli.no_color {
+        console::set_colors_enabled_stderr(false);
+    }
+    let progress = ProgressBar::hidden();
+    env_logger::Builder::from_default_env()
+        .target(env_logger::Target::Pipe(Box::new(ProgressLogWriter(progress.clone()))))
+        .init();
 
-    if let Err(err) = run(cli).await {
+    if let Err(err) = run(cli, progress).await {
         eprintln!("Error: {:#}", err);
 
         // Print the error chain
@@ -104,11 +251,11 @@ async fn main() {
             eprintln!("\nBacktrace:\n{}", backtrace);
         }
 
-        std::process::exit(1);
+        std::process::exit(err.downcast_ref::<BuildFailed>().map_or(1, |failed| failed.exit_code));
     }
 }
 
-async fn run(cli: Cli) -> Result<()> {
+async fn run(cli: Cli, progress: ProgressBar) -> Result<()> {
 
     info!("Sphinx Ultra Builder v{}", env!("CARGO_PKG_VERSION"));
 
@@ -121,6 +268,14 @@ async fn run(cli: Cli) -> Result<()> {
             incremental,
             fail_on_warning,
             warning_file,
+            max_warnings,
+            stats_json,
+            slowest,
+            trace,
+            bounded_memory,
+            batch_size,
+            tags,
+            keep_stale,
         } => {
             let mut config = if let Some(ref config_path) = cli.config {
                 BuildConfig::from_file(config_path)
@@ -135,10 +290,21 @@ async fn run(cli: Cli) -> Result<()> {
             if fail_on_warning {
                 config.fail_on_warning = true;
             }
+            if let Some(max_warnings) = max_warnings {
+                config.warning_policy.max_warnings = max_warnings;
+            }
+            if bounded_memory {
+                config.memory.bounded = true;
+            }
+            if let Some(batch_size) = batch_size {
+                config.memory.batch_size = batch_size;
+            }
+            config.tags.extend(tags);
+            if keep_stale {
+                config.output.keep_stale = true;
+            }
 
-            // Save the fail_on_warning flag before moving config
-            let should_fail_on_warning = config.fail_on_warning;
-
+            let exit_codes = config.exit_codes.clone();
             let mut builder = SphinxBuilder::new(config, source.clone(), output.clone())
                 .with_context(|| format!("Failed to create builder for source={}, output={}", source.display(), output.display()))?;
 
@@ -146,6 +312,10 @@ async fn run(cli: Cli) -> Result<()> {
                 builder.set_parallel_jobs(jobs);
             }
 
+            if let Some(slowest) = slowest {
+                builder.set_slowest_documents(slowest);
+            }
+
             if clean {
                 builder.clean().await.context("Failed to clean output directory")?;
             }
@@ -154,7 +324,62 @@ async fn run(cli: Cli) -> Result<()> {
                 builder.enable_incremental();
             }
 
-            let stats = builder.build().await.context("Build failed")?;
+            if trace.is_some() {
+                builder.enable_trace();
+            }
+
+            if !cli.quiet && !cli.verbose {
+                builder.set_progress_reporter(Arc::new(ProgressBarReporter(progress)));
+            }
+
+            // Ctrl-C stops the build after the documents being written, so
+            // pages and cache entries are never left half done; a second
+            // Ctrl-C exits at once
+            let cancellation = builder.cancellation_token();
+            tokio::spawn(async move {
+                let mut interrupted = false;
+                while tokio::signal::ctrl_c().await.is_ok() {
+                    if interrupted {
+                        eprintln!("Interrupted again, exiting without finishing");
+                        std::process::exit(130);
+                    }
+                    interrupted = true;
+                    warn!("Interrupted, finishing the documents in progress (press Ctrl-C again to exit now)...");
+                    cancellation.cancel();
+                }
+            });
+
+            let result = builder.build().await;
+
+            // The trace of a failed build is kept too
+            if let (Some(trace_path), Some(build_trace)) = (&trace, builder.trace()) {
+                if let Some(parent) = trace_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                build_trace.write(trace_path)?;
+                info!("Build trace written to {}", trace_path.display());
+            }
+
+            if let Some(BuildError::Cancelled { documents_written }) =
+                result.as_ref().err().and_then(|error| error.downcast_ref::<BuildError>())
+            {
+                eprintln!("Build cancelled after writing {} documents", documents_written);
+                std::process::exit(130);
+            }
+            let outcome = result.as_ref().map_or_else(BuildOutcome::failed, BuildStats::outcome);
+            let stats = result.map_err(|error| {
+                error.context(BuildFailed {
+                    exit_code: exit_codes.for_outcome(&outcome),
+                })
+            })?;
+
+            if let Some(ref stats_path) = stats_json {
+                if let Some(parent) = stats_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(stats_path, serde_json::to_string_pretty(&stats.to_json())?)
+                    .with_context(|| format!("Failed to write build statistics to {}", stats_path.display()))?;
+            }
 
             // Handle warning file output if specified
             let mut warning_file_handle = if let Some(ref warning_file_path) = warning_file {
@@ -187,9 +412,15 @@ async fn run(cli: Cli) -> Result<()> {
                 // Write to warning file if specified
                 if let Some(ref mut file) = warning_file_handle {
                     writeln!(file, "{}", warning_msg)?;
+                    for suggestion in &warning.suggestions {
+                        writeln!(file, "    {}", suggestion)?;
+                    }
                 }
 
                 warn!("{}", warning_msg);
+                for suggestion in &warning.suggestions {
+                    warn!("    {}", suggestion);
+                }
             }
 
             // Print errors in Sphinx-like format
@@ -215,38 +446,119 @@ async fn run(cli: Cli) -> Result<()> {
                 file.flush()?;
             }
 
+            // Print final summary; a quiet build only mentions problems
+            if !cli.quiet || stats.warnings > 0 || stats.errors > 0 {
+                let options = SummaryOptions::for_stderr(cli.no_color);
+                eprint!("{}", summary::render(&stats, &source, &options));
+            }
+
             // Check for fail-on-warning condition
-            if should_fail_on_warning && stats.warnings > 0 {
+            if builder.warning_policy_violated(&stats) {
                 eprintln!("Build failed due to warnings (caused by --fail-on-warning)");
-                std::process::exit(1);
+                std::process::exit(exit_codes.failed);
             }
 
-            // Print final summary
-            if stats.warnings > 0 || stats.errors > 0 {
-                let status_msg = if stats.errors > 0 {
-                    "build succeeded with problems"
-                } else {
-                    "build succeeded"
-                };
+            let exit_code = exit_codes.for_outcome(&stats.outcome());
+            if exit_code != 0 {
+                std::process::exit(exit_code);
+            }
+        }
+
+        Commands::Lint {
+            source,
+            output,
+            jobs,
+            json,
+        } => {
+            let config = if let Some(ref config_path) = cli.config {
+                BuildConfig::from_file(config_path)
+                    .with_context(|| format!("Failed to load config from {}", config_path.display()))?
+            } else {
+                BuildConfig::auto_detect(&source)
+                    .with_context(|| format!("Failed to auto-detect config in {}", source.display()))?
+            };
+
+            // Discovered files are absolute, so the source directory must be too
+            let source = paths::canonicalize(&source)
+                .with_context(|| format!("Source directory not found: {}", source.display()))?;
+            let mut builder = SphinxBuilder::new(config, source.clone(), output)
+                .with_context(|| format!("Failed to create builder for source={}", source.display()))?;
+            if let Some(jobs) = jobs {
+                builder.set_parallel_jobs(jobs);
+            }
+            let report = builder.lint().await.context("Lint failed")?;
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+            } else {
+                for error in &report.errors {
+                    println!("{}: ERROR: {}", location(&error.file, error.line), error.message);
+                }
+                for warning in &report.warnings {
+                    println!("{}: WARNING: {}", location(&warning.file, warning.line), warning.message);
+                    for suggestion in &warning.suggestions {
+                        println!("    {}", suggestion);
+                    }
+                }
+                info!(
+                    "Checked {} documents in {:?}: {} warnings, {} errors",
+                    report.documents,
+                    report.duration,
+                    report.warnings.len(),
+                    report.errors.len()
+                );
+            }
 
-                if stats.warnings > 0 && stats.errors > 0 {
-                    warn!(
-                        "{}, {} warnings, {} errors.",
-                        status_msg, stats.warnings, stats.errors
+            if !report.is_clean() {
+                std::process::exit(1);
+            }
+        }
+
+        Commands::Diff { old, new, output } => {
+            let diff = OutputDiff::compare(&old, &new)?;
+            let markdown = diff.to_markdown();
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, markdown)
+                        .with_context(|| format!("Failed to write diff to {}", path.display()))?;
+                    info!(
+                        "{} pages added, {} removed, {} changed; summary written to {}",
+                        diff.added.len(),
+                        diff.removed.len(),
+                        diff.changed.len(),
+                        path.display()
                     );
-                } else if stats.warnings > 0 {
-                    warn!("{}, {} warnings.", status_msg, stats.warnings);
-                } else if stats.errors > 0 {
-                    warn!("{}, {} errors.", status_msg, stats.errors);
                 }
+                None => print!("{}", markdown),
+            }
+        }
+
+        Commands::MultiVersion {
+            source,
+            output,
+            jobs,
+        } => {
+            let config = if let Some(ref config_path) = cli.config {
+                BuildConfig::from_file(config_path)
+                    .with_context(|| format!("Failed to load config from {}", config_path.display()))?
+            } else {
+                BuildConfig::auto_detect(&source)
+                    .with_context(|| format!("Failed to auto-detect config in {}", source.display()))?
+            };
+
+            let mut builder = MultiVersionBuilder::new(&config, source, output.clone());
+            if let Some(jobs) = jobs {
+                builder.set_parallel_jobs(jobs);
             }
+            let results = builder.build().await.context("Multi-version build failed")?;
 
-            info!("Build completed successfully!");
-            info!("Files processed: {}", stats.files_processed);
-            info!("Files skipped: {}", stats.files_skipped);
-            info!("Cache hits: {}", stats.cache_hits);
-            info!("Build time: {:?}", stats.build_time);
-            info!("Output size: {} MB", stats.output_size_mb);
+            for (version, stats) in &results {
+                info!(
+                    "{}: {} files, {} warnings, {} errors in {:?}",
+                    version, stats.files_processed, stats.warnings, stats.errors, stats.build_time
+                );
+            }
+            info!("Built {} versions into {}", results.len(), output.display());
         }
 
         Commands::Clean { output } => {
@@ -260,19 +572,128 @@ async fn run(cli: Cli) -> Result<()> {
             }
         }
 
-        Commands::Stats { source } => {
+        Commands::Stats { source, json } => {
             let stats = analyze_project(&source).await
                 .with_context(|| format!("Failed to analyze project in {}", source.display()))?;
 
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats.to_json())?);
+                return Ok(());
+            }
+
             println!("Project Statistics:");
             println!("  Source files: {}", stats.source_files);
             println!("  Total lines: {}", stats.total_lines);
-            println!("  Average file size: {} KB", stats.avg_file_size_kb);
-            println!("  Largest file: {} KB", stats.largest_file_kb);
+            println!("  Total words: {}", stats.total_words);
+            println!("  Average file size: {:.1} KB", stats.avg_file_size_kb);
+            println!("  Largest file: {:.1} KB", stats.largest_file_kb);
             println!("  Directory depth: {}", stats.max_depth);
             println!("  Cross-references: {}", stats.cross_references);
+
+            println!("\nDirectories:");
+            for (directory, counts) in &stats.directories {
+                println!("  {:<40}{:>6} documents{:>9} words", directory, counts.documents, counts.words);
+            }
+            println!("\nHeadings by level:");
+            for (level, count) in &stats.heading_depths {
+                println!("  {:<6}{:>6}", level, count);
+            }
+            println!("\nLargest files:");
+            for file in &stats.largest_files {
+                println!("  {:<40}{:>8.1} KB{:>7} lines{:>8} words", file.path, file.size_kb, file.lines, file.words);
+            }
+            if !stats.directives.is_empty() {
+                let mut directives: Vec<_> = stats.directives.iter().collect();
+                directives.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+                println!("\nDirectives:");
+                for (name, count) in directives {
+                    println!("  {:<30}{:>6}", name, count);
+                }
+            }
+            println!("\nPossibly broken links: {}", stats.broken_links.len());
+            for link in &stats.broken_links {
+                println!("  {}:{}: {} {}", link.file, link.line, link.kind, link.target);
+            }
         }
+
+        Commands::Theme { command } => match command {
+            ThemeCommands::Install { theme, source, force } => {
+                let themes_dir = source.join(theme::PROJECT_THEMES_DIR);
+                let installed = theme::install_theme(&theme, &themes_dir, force)
+                    .with_context(|| format!("Failed to install theme from {}", theme))?;
+                info!("Installed theme '{}' {} into {}", installed.name, installed.version, installed.path.display());
+            }
+            ThemeCommands::Pack { theme_dir, output } => {
+                let archive = theme::pack_theme(&theme_dir, output.as_deref())
+                    .with_context(|| format!("Failed to pack theme in {}", theme_dir.display()))?;
+                info!("Packed theme into {}", archive.display());
+            }
+        },
     }
 
     Ok(())
 }
+
+/// `file:line` of a diagnostic, or just the file without a line
+fn location(file: &std::path::Path, line: Option<usize>) -> String {
+    match line {
+        Some(line) => format!("{}:{}", file.display(), line),
+        None => file.display().to_string(),
+    }
+}
+
+/// Context of the error of a failed build, with the exit status configured
+/// for its outcome
+#[derive(Debug, thiserror::Error)]
+#[error("Build failed")]
+struct BuildFailed {
+    exit_code: i32,
+}
+
+/// Shows the phase and document count of a build on a progress bar
+struct ProgressBarReporter(ProgressBar);
+
+impl ProgressReporter for ProgressBarReporter {
+    fn phase_started(&self, phase: &str, total: usize) {
+        let template = if total > 0 {
+            "{spinner} {msg:<14} [{bar:40}] {pos}/{len} ({elapsed})"
+        } else {
+            "{spinner} {msg:<14} ({elapsed})"
+        };
+        self.0.set_length(total as u64);
+        self.0.set_position(0);
+        self.0.set_message(phase.to_string());
+        self.0.set_style(
+            ProgressStyle::with_template(template)
+                .expect("valid progress template")
+                .progress_chars("=> "),
+        );
+        // The bar stays hidden until the first phase, and everywhere but on
+        // a terminal
+        if self.0.is_hidden() {
+            self.0.set_draw_target(ProgressDrawTarget::stderr());
+        }
+        self.0.enable_steady_tick(Duration::from_millis(100));
+    }
+
+    fn documents_done(&self, done: usize, _total: usize) {
+        self.0.set_position(done as u64);
+    }
+
+    fn finished(&self) {
+        self.0.finish_and_clear();
+    }
+}
+
+/// Log output that clears the progress bar while a line is printed
+struct ProgressLogWriter(ProgressBar);
+
+impl Write for ProgressLogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.suspend(|| std::io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
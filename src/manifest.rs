@@ -0,0 +1,189 @@
+//! Machine-readable manifest of the generated pages.
+//!
+//! `manifest.json` lists every page with its title, source file, anchors and
+//! the files its content references, for search ingestion, redirect
+//! management and other tools working on the output.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// File name of the manifest in the output directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+lazy_static::lazy_static! {
+    static ref ID_ATTRIBUTE: Regex = Regex::new(r#"\sid="([^"]+)""#).unwrap();
+    static ref LINK_ATTRIBUTE: Regex = Regex::new(r#"\s(?:src|href)="([^"]*)""#).unwrap();
+}
+
+/// A generated page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestPage {
+    /// Path of the page in the output directory, like `guide/intro.html`
+    pub page: String,
+    /// Document name, like `guide/intro`
+    pub doc: String,
+    pub title: String,
+    /// Source file relative to the source directory
+    pub source: String,
+    /// `id`s of the elements of the page content, sorted
+    pub anchors: Vec<String>,
+    /// Files of the output directory the page content links to or embeds,
+    /// like images and downloads, sorted
+    pub assets: Vec<String>,
+    /// Names from `sectionauthor` and `moduleauthor` directives
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    /// Names from `codeauthor` directives
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub code_authors: Vec<String>,
+}
+
+impl ManifestPage {
+    /// The entry of a page from the anchors and links of its content
+    pub fn new(page: String, doc: String, title: String, source: String, refs: ContentRefs) -> Self {
+        let anchors = refs.anchors.into_iter().collect();
+        let assets = page_assets(&page, &refs.links);
+        Self {
+            page,
+            doc,
+            title,
+            source,
+            anchors,
+            assets,
+            authors: Vec::new(),
+            code_authors: Vec::new(),
+        }
+    }
+
+    pub fn with_authors(mut self, authors: &[String], code_authors: &[String]) -> Self {
+        self.authors = authors.to_vec();
+        self.code_authors = code_authors.to_vec();
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Pages sorted by path
+    pub pages: Vec<ManifestPage>,
+}
+
+impl Manifest {
+    pub fn new(mut pages: Vec<ManifestPage>) -> Self {
+        pages.sort_by(|a, b| a.page.cmp(&b.page));
+        pages.dedup_by(|a, b| a.page == b.page);
+        Self { pages }
+    }
+
+    /// The manifest written to `output_dir` by an earlier build, if any
+    pub fn read(output_dir: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(output_dir.join(MANIFEST_FILE)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn write(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(MANIFEST_FILE);
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write manifest: {}", path.display()))
+    }
+}
+
+/// The anchors and links of a page's content, gathered from its HTML as a
+/// whole or tag by tag as it is written
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContentRefs {
+    anchors: BTreeSet<String>,
+    /// `src` and `href` attribute values, still entity-encoded
+    links: BTreeSet<String>,
+}
+
+impl ContentRefs {
+    pub fn from_html(html: &str) -> Self {
+        let mut refs = Self::default();
+        refs.push_tag(html);
+        refs
+    }
+
+    /// Take in a tag of the content; any HTML containing whole tags works
+    /// too
+    pub fn push_tag(&mut self, tag: &str) {
+        self.anchors.extend(html_ids(tag));
+        self.links
+            .extend(LINK_ATTRIBUTE.captures_iter(tag).map(|captures| captures[1].to_string()));
+    }
+}
+
+/// The `id` attributes of the elements of `html`
+pub fn html_ids(html: &str) -> BTreeSet<String> {
+    ID_ATTRIBUTE
+        .captures_iter(html)
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
+/// Output files among the `links` of `page`, relative to the output
+/// directory. Other pages, anchors and external URLs are left out.
+fn page_assets(page: &str, links: &BTreeSet<String>) -> Vec<String> {
+    let page_dir = page.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let mut assets = BTreeSet::new();
+    for link in links {
+        let url = html_escape::decode_html_entities(link).into_owned();
+        let path = url.split(['#', '?']).next().unwrap_or_default();
+        if path.is_empty()
+            || path.starts_with('/')
+            || path.contains(':')
+            || path.ends_with(".html")
+            || path.ends_with('/')
+        {
+            continue;
+        }
+        if let Some(asset) = resolve(page_dir, path) {
+            assets.insert(asset);
+        }
+    }
+    assets.into_iter().collect()
+}
+
+/// `path` relative to the directory `base`, as a path from the output root;
+/// `None` when it leaves the output directory
+fn resolve(base: &str, path: &str) -> Option<String> {
+    let mut parts: Vec<&str> = base.split('/').filter(|part| !part.is_empty()).collect();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_entry_from_content() {
+        let html = r##"<section id="usage"><h1>Usage<a class="headerlink" href="#usage">¶</a></h1>
+<img src="../_images/diagram.png" alt="" /><a href="../_downloads/abc/example.py">example</a>
+<a href="intro.html#setup">Setup</a><a href="https://example.org/logo.png">Logo</a>
+<span id="install"></span><video src="../_videos/guide/demo.mp4?autoplay=1"></video></section>"##;
+        let page = ManifestPage::new(
+            "guide/usage.html".to_string(),
+            "guide/usage".to_string(),
+            "Usage".to_string(),
+            "guide/usage.rst".to_string(),
+            ContentRefs::from_html(html),
+        );
+        assert_eq!(page.anchors, ["install", "usage"]);
+        assert_eq!(
+            page.assets,
+            ["_downloads/abc/example.py", "_images/diagram.png", "_videos/guide/demo.mp4"]
+        );
+    }
+}
@@ -3,16 +3,26 @@
 //! This module provides glob-style pattern matching compatible with Sphinx's
 //! include_patterns and exclude_patterns functionality. It implements the same
 //! pattern translation and matching logic as Sphinx's util/matching.py.
+//!
+//! Exclude lists also take gitignore-style `!` patterns: the last pattern
+//! matching a path decides, so `!drafts/ready.rst` after `drafts/**` keeps
+//! that one file. Patterns match case-insensitively on Windows.
+//!
+//! The walk can also honor ignore files like `.gitignore`, read from every
+//! directory walked and from the directories above up to the repository
+//! root, with gitignore's rules: patterns without a slash match at any
+//! depth, a trailing slash matches only directories and deeper files take
+//! precedence.
 
 use log;
-use regex::Regex;
-use std::collections::HashMap;
+use regex::{Regex, RegexBuilder};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use parking_lot::Mutex;
 
 lazy_static::lazy_static! {
-    /// Cache for compiled regex patterns
-    static ref PATTERN_CACHE: Mutex<HashMap<String, Regex>> = Mutex::new(HashMap::new());
+    /// Cache for compiled regex patterns, by pattern and case-insensitivity
+    static ref PATTERN_CACHE: Mutex<HashMap<(String, bool), Regex>> = Mutex::new(HashMap::new());
 }
 
 /// Translates shell-style glob pattern to regex pattern.
@@ -127,15 +137,23 @@ pub fn translate_pattern(pattern: &str) -> String {
 
 /// Compiles a pattern into a regex, using cache for performance.
 pub fn compile_pattern(pattern: &str) -> Result<Regex, regex::Error> {
-    let mut cache = PATTERN_CACHE.lock().unwrap();
+    compile_pattern_case(pattern, false)
+}
 
-    if let Some(regex) = cache.get(pattern) {
+/// Compiles a pattern into a regex, optionally ignoring case.
+pub fn compile_pattern_case(pattern: &str, case_insensitive: bool) -> Result<Regex, regex::Error> {
+    let mut cache = PATTERN_CACHE.lock();
+    let key = (pattern.to_string(), case_insensitive);
+
+    if let Some(regex) = cache.get(&key) {
         return Ok(regex.clone());
     }
 
     let regex_pattern = translate_pattern(pattern);
-    let regex = Regex::new(&regex_pattern)?;
-    cache.insert(pattern.to_string(), regex.clone());
+    let regex = RegexBuilder::new(&regex_pattern)
+        .case_insensitive(case_insensitive)
+        .build()?;
+    cache.insert(key, regex.clone());
 
     Ok(regex)
 }
@@ -159,7 +177,190 @@ pub fn pattern_filter(names: &[String], pattern: &str) -> Result<Vec<String>, re
 /// Normalizes a path to use forward slashes for pattern matching.
 /// This ensures consistent behavior across platforms.
 pub fn normalize_path(path: &Path) -> String {
-    path.to_string_lossy().replace('\\', "/")
+    crate::paths::to_match_slash(path)
+}
+
+/// How the directory walk of `get_matching_files_with` treats symlinks,
+/// letter case and ignore files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// Walk into symlinked directories, skipping links that lead back to a
+    /// directory being walked. Symlinked files are matched either way.
+    pub follow_symlinks: bool,
+    /// Match patterns regardless of case, as paths are on Windows
+    pub case_insensitive: bool,
+    /// Names of gitignore-style files whose patterns leave files out, like
+    /// `.gitignore`
+    pub ignore_files: Vec<String>,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            follow_symlinks: true,
+            case_insensitive: cfg!(windows),
+            ignore_files: Vec::new(),
+        }
+    }
+}
+
+/// An exclude pattern, or with a leading `!` one that re-includes
+struct ExcludeRule {
+    regex: Regex,
+    negated: bool,
+    /// Literal start of the pattern, up to its first wildcard
+    prefix: String,
+    /// Matches only directories, as ignore file patterns ending with `/`
+    dir_only: bool,
+}
+
+/// The patterns of an ignore file, which apply to the paths under its
+/// directory
+struct IgnoreFile {
+    base: PathBuf,
+    rules: Vec<ExcludeRule>,
+}
+
+/// Compiles a line of a gitignore-style file, unless it is blank or a
+/// comment
+fn ignore_rule(line: &str, case_insensitive: bool) -> Option<Result<ExcludeRule, regex::Error>> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negated, pattern) = match line.strip_prefix('!') {
+        Some(pattern) => (true, pattern),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(pattern) => (true, pattern),
+        None => (false, pattern),
+    };
+    // A slash other than a trailing one anchors the pattern to the file's
+    // directory; otherwise it matches at any depth
+    let pattern = match pattern.strip_prefix('/') {
+        Some(pattern) => pattern.to_string(),
+        None if pattern.contains('/') => pattern.to_string(),
+        None => format!("**/{}", pattern),
+    };
+    if pattern.is_empty() {
+        return None;
+    }
+    Some(compile_pattern_case(&pattern, case_insensitive).map(|regex| ExcludeRule {
+        regex,
+        negated,
+        prefix: String::new(),
+        dir_only,
+    }))
+}
+
+/// Reads the ignore files of a directory that exist
+fn read_ignore_files(dir: &Path, options: &MatchOptions) -> Vec<IgnoreFile> {
+    options
+        .ignore_files
+        .iter()
+        .filter_map(|name| {
+            let path = dir.join(name);
+            let content = std::fs::read_to_string(&path).ok()?;
+            let rules = content
+                .lines()
+                .filter_map(|line| ignore_rule(line, options.case_insensitive))
+                .filter_map(|rule| {
+                    rule.map_err(|err| log::warn!("Invalid pattern in {}: {}", path.display(), err))
+                        .ok()
+                })
+                .collect();
+            Some(IgnoreFile {
+                base: dir.to_path_buf(),
+                rules,
+            })
+        })
+        .collect()
+}
+
+/// Ignore files of the directories above `dir` up to the root of the
+/// repository it is in, outermost first; none outside a repository
+fn ancestor_ignore_files(dir: &Path, options: &MatchOptions) -> Vec<IgnoreFile> {
+    if options.ignore_files.is_empty() || dir.join(".git").exists() {
+        return Vec::new();
+    }
+    let mut ancestors = Vec::new();
+    for ancestor in dir.ancestors().skip(1) {
+        ancestors.push(ancestor);
+        if ancestor.join(".git").exists() {
+            return ancestors
+                .into_iter()
+                .rev()
+                .flat_map(|ancestor| read_ignore_files(ancestor, options))
+                .collect();
+        }
+    }
+    Vec::new()
+}
+
+/// Whether ignore files leave a path out: the deepest file with a pattern
+/// matching it decides, by its last matching pattern
+fn is_ignored(ignore_files: &[IgnoreFile], path: &Path, is_dir: bool) -> bool {
+    for ignore_file in ignore_files.iter().rev() {
+        let Ok(relative_path) = path.strip_prefix(&ignore_file.base) else {
+            continue;
+        };
+        let relative_path = normalize_path(relative_path);
+        let rule = ignore_file
+            .rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(&relative_path));
+        if let Some(rule) = rule {
+            return !rule.negated;
+        }
+    }
+    false
+}
+
+/// Compiles exclude patterns; `\!` matches a literal leading `!`
+fn exclude_rules(patterns: &[String], case_insensitive: bool) -> Result<Vec<ExcludeRule>, regex::Error> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            let (negated, pattern) = match pattern.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, pattern.strip_prefix('\\').filter(|p| p.starts_with('!')).unwrap_or(pattern)),
+            };
+            let prefix = pattern[..pattern.find(['*', '?', '[']).unwrap_or(pattern.len())].to_string();
+            Ok(ExcludeRule {
+                regex: compile_pattern_case(pattern, case_insensitive)?,
+                negated,
+                prefix,
+                dir_only: false,
+            })
+        })
+        .collect()
+}
+
+/// Whether the last of the rules matching any of the forms of a path
+/// excludes it
+fn is_excluded(rules: &[ExcludeRule], forms: &[&str]) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| forms.iter().any(|form| rule.regex.is_match(form)))
+        .is_some_and(|rule| !rule.negated)
+}
+
+/// Whether an excluded directory must still be walked because a negated
+/// pattern could re-include something in it
+fn may_reinclude(rules: &[ExcludeRule], dir: &str, case_insensitive: bool) -> bool {
+    let dir = format!("{}/", dir);
+    let (dir, fold) = if case_insensitive {
+        (dir.to_lowercase(), true)
+    } else {
+        (dir, false)
+    };
+    rules.iter().filter(|rule| rule.negated).any(|rule| {
+        let prefix = if fold { rule.prefix.to_lowercase() } else { rule.prefix.clone() };
+        prefix.starts_with(&dir) || dir.starts_with(&prefix)
+    })
 }
 
 /// Gets matching files from a directory using include and exclude patterns.
@@ -174,7 +375,19 @@ pub fn get_matching_files<P: AsRef<Path>>(
     include_patterns: &[String],
     exclude_patterns: &[String],
 ) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
-    let dirname = dirname.as_ref().canonicalize()?;
+    get_matching_files_with(dirname, include_patterns, exclude_patterns, &MatchOptions::default())
+}
+
+/// Gets matching files like `get_matching_files`, with `!` patterns in the
+/// exclude list re-including what earlier patterns excluded, and the
+/// symlink and case policy of `options`.
+pub fn get_matching_files_with<P: AsRef<Path>>(
+    dirname: P,
+    include_patterns: &[String],
+    exclude_patterns: &[String],
+    options: &MatchOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let dirname = crate::paths::canonicalize(dirname.as_ref())?;
     let include_patterns = if include_patterns.is_empty() {
         vec!["**".to_string()]
     } else {
@@ -184,33 +397,52 @@ pub fn get_matching_files<P: AsRef<Path>>(
     // Compile all patterns
     let mut include_regexes = Vec::new();
     for pattern in &include_patterns {
-        include_regexes.push(compile_pattern(pattern)?);
+        include_regexes.push(compile_pattern_case(pattern, options.case_insensitive)?);
     }
 
-    let mut exclude_regexes = Vec::new();
-    for pattern in exclude_patterns {
-        exclude_regexes.push(compile_pattern(pattern)?);
-    }
+    let exclude_rules = exclude_rules(exclude_patterns, options.case_insensitive)?;
 
     let mut matched_files = Vec::new();
 
+    struct Walk<'a> {
+        base_dir: &'a Path,
+        include_regexes: &'a [Regex],
+        exclude_rules: &'a [ExcludeRule],
+        options: &'a MatchOptions,
+        /// Real paths of the directories being walked, to notice symlink cycles
+        ancestors: HashSet<PathBuf>,
+        /// Ignore files of the directories being walked and those above
+        ignore_files: Vec<IgnoreFile>,
+    }
+
     // Walk the directory recursively
     fn walk_dir(
         dir: &Path,
-        base_dir: &Path,
-        include_regexes: &[Regex],
-        exclude_regexes: &[Regex],
+        walk: &mut Walk,
         matched_files: &mut Vec<PathBuf>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         if !dir.is_dir() {
             return Ok(());
         }
+        let base_dir = walk.base_dir;
+        let outer_ignore_files = walk.ignore_files.len();
+        walk.ignore_files.extend(read_ignore_files(dir, walk.options));
 
         for entry in std::fs::read_dir(dir)? {
             let entry = entry?;
             let path = entry.path();
 
+            if is_ignored(&walk.ignore_files, &path, path.is_dir()) {
+                log::debug!("Ignoring {}", path.display());
+                continue;
+            }
+
             if path.is_dir() {
+                let is_symlink = entry.file_type().map(|kind| kind.is_symlink()).unwrap_or(false);
+                if is_symlink && !walk.options.follow_symlinks {
+                    log::debug!("Not following symlinked directory: {}", path.display());
+                    continue;
+                }
                 // Check if directory matches any exclude pattern before recursing
                 if let Ok(relative_path) = path.strip_prefix(base_dir) {
                     let normalized_path = normalize_path(relative_path);
@@ -218,11 +450,14 @@ pub fn get_matching_files<P: AsRef<Path>>(
                     // - "dir" matches patterns like "dir" or "dir/**"
                     // - "dir/" matches patterns ending with /
                     // - "dir/x" matches patterns like "dir/**" (since ** matches anything)
-                    let dir_excluded = exclude_regexes.iter().any(|regex| {
-                        regex.is_match(&normalized_path)
-                            || regex.is_match(&format!("{}/", normalized_path))
-                            || regex.is_match(&format!("{}/x", normalized_path))
-                    });
+                    let dir_excluded = is_excluded(
+                        walk.exclude_rules,
+                        &[
+                            &normalized_path,
+                            &format!("{}/", normalized_path),
+                            &format!("{}/x", normalized_path),
+                        ],
+                    ) && !may_reinclude(walk.exclude_rules, &normalized_path, walk.options.case_insensitive);
                     if dir_excluded {
                         log::debug!("Excluding directory from walk: {}", normalized_path);
                         continue;
@@ -235,14 +470,19 @@ pub fn get_matching_files<P: AsRef<Path>>(
                     continue;
                 }
 
-                // Recursively walk subdirectories
-                walk_dir(
-                    &path,
-                    base_dir,
-                    include_regexes,
-                    exclude_regexes,
-                    matched_files,
-                )?;
+                // Recursively walk subdirectories, unless a symlink leads
+                // back into one being walked
+                let real_path = crate::paths::canonicalize(&path)?;
+                if !walk.ancestors.insert(real_path.clone()) {
+                    log::warn!(
+                        "Skipping symlinked directory {}: it links back to {}",
+                        path.display(),
+                        real_path.display()
+                    );
+                    continue;
+                }
+                walk_dir(&path, walk, matched_files)?;
+                walk.ancestors.remove(&real_path);
             } else if path.is_file() {
                 // Get relative path from base directory
                 let relative_path = path.strip_prefix(base_dir).map_err(|_| {
@@ -256,15 +496,15 @@ pub fn get_matching_files<P: AsRef<Path>>(
                 let normalized_path = normalize_path(relative_path);
 
                 // Check if file matches any include pattern
-                let included = include_regexes
+                let included = walk
+                    .include_regexes
                     .iter()
                     .any(|regex| regex.is_match(&normalized_path));
 
                 if included {
-                    // Check if file matches any exclude pattern
-                    let excluded = exclude_regexes
-                        .iter()
-                        .any(|regex| regex.is_match(&normalized_path));
+                    // Check if the last exclude pattern matching the file
+                    // excludes it
+                    let excluded = is_excluded(walk.exclude_rules, &[&normalized_path]);
 
                     if !excluded {
                         matched_files.push(path);
@@ -273,16 +513,19 @@ pub fn get_matching_files<P: AsRef<Path>>(
             }
         }
 
+        walk.ignore_files.truncate(outer_ignore_files);
         Ok(())
     }
 
-    walk_dir(
-        &dirname,
-        &dirname,
-        &include_regexes,
-        &exclude_regexes,
-        &mut matched_files,
-    )?;
+    let mut walk = Walk {
+        base_dir: &dirname,
+        include_regexes: &include_regexes,
+        exclude_rules: &exclude_rules,
+        options,
+        ancestors: HashSet::from([dirname.clone()]),
+        ignore_files: ancestor_ignore_files(&dirname, options),
+    };
+    walk_dir(&dirname, &mut walk, &mut matched_files)?;
 
     // Sort for consistent results
     matched_files.sort();
@@ -365,4 +608,108 @@ mod tests {
         assert!(files.iter().any(|p| p.file_name().unwrap() == "index.rst"));
         assert!(!files.iter().any(|p| p.file_name().unwrap() == "api.rst"));
     }
+
+    #[test]
+    fn test_negated_exclude_patterns() {
+        let temp_dir = TempDir::new().unwrap();
+        let base_path = temp_dir.path();
+        fs::create_dir_all(base_path.join("drafts/old")).unwrap();
+        fs::write(base_path.join("index.rst"), "content").unwrap();
+        fs::write(base_path.join("drafts/wip.rst"), "content").unwrap();
+        fs::write(base_path.join("drafts/ready.rst"), "content").unwrap();
+        fs::write(base_path.join("drafts/old/ready.rst"), "content").unwrap();
+
+        let names = |files: Vec<PathBuf>| -> Vec<String> {
+            files
+                .iter()
+                .map(|file| normalize_path(file.strip_prefix(base_path.canonicalize().unwrap()).unwrap()))
+                .collect()
+        };
+        let excludes = ["drafts/**".to_string(), "!drafts/ready.rst".to_string()];
+        let files = get_matching_files(base_path, &[], &excludes).unwrap();
+        assert_eq!(names(files), vec!["drafts/ready.rst", "index.rst"]);
+
+        // The last matching pattern decides
+        let excludes = [
+            "drafts/**".to_string(),
+            "!**/ready.rst".to_string(),
+            "drafts/old/**".to_string(),
+        ];
+        let files = get_matching_files(base_path, &[], &excludes).unwrap();
+        assert_eq!(names(files), vec!["drafts/ready.rst", "index.rst"]);
+
+        let options = MatchOptions {
+            case_insensitive: true,
+            ..MatchOptions::default()
+        };
+        let excludes = ["DRAFTS/**".to_string(), "!Drafts/Ready.rst".to_string()];
+        let files = get_matching_files_with(base_path, &[], &excludes, &options).unwrap();
+        assert_eq!(names(files), vec!["drafts/ready.rst", "index.rst"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let base_path = temp_dir.path();
+        let shared = TempDir::new().unwrap();
+        fs::write(shared.path().join("api.rst"), "content").unwrap();
+        fs::create_dir_all(base_path.join("guide")).unwrap();
+        fs::write(base_path.join("guide/intro.rst"), "content").unwrap();
+        std::os::unix::fs::symlink(shared.path(), base_path.join("shared")).unwrap();
+        // A link back up the tree would otherwise be walked forever
+        std::os::unix::fs::symlink(base_path, base_path.join("guide/loop")).unwrap();
+
+        let files = get_matching_files(base_path, &[], &[]).unwrap();
+        let names: Vec<String> = files
+            .iter()
+            .map(|file| normalize_path(file.strip_prefix(base_path.canonicalize().unwrap()).unwrap()))
+            .collect();
+        assert_eq!(names, vec!["guide/intro.rst", "shared/api.rst"]);
+
+        let options = MatchOptions {
+            follow_symlinks: false,
+            ..MatchOptions::default()
+        };
+        let files = get_matching_files_with(base_path, &[], &[], &options).unwrap();
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn test_ignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        let base_path = repo.join("docs");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(base_path.join("node_modules/pkg")).unwrap();
+        fs::create_dir_all(base_path.join("api/generated")).unwrap();
+        fs::write(repo.join(".gitignore"), "# dependencies\nnode_modules/\n*.tmp.rst\n").unwrap();
+        fs::write(base_path.join(".sphinxultraignore"), "/api/generated\n").unwrap();
+        fs::write(base_path.join("api/.gitignore"), "!keep.tmp.rst\n").unwrap();
+        for file in [
+            "index.rst",
+            "notes.tmp.rst",
+            "node_modules/pkg/README.rst",
+            "api/index.rst",
+            "api/keep.tmp.rst",
+            "api/generated/module.rst",
+        ] {
+            fs::write(base_path.join(file), "content").unwrap();
+        }
+
+        let options = MatchOptions {
+            ignore_files: vec![".gitignore".to_string(), ".sphinxultraignore".to_string()],
+            ..MatchOptions::default()
+        };
+        let files = get_matching_files_with(&base_path, &["**/*.rst".to_string()], &[], &options).unwrap();
+        let names: Vec<String> = files
+            .iter()
+            .map(|file| normalize_path(file.strip_prefix(base_path.canonicalize().unwrap()).unwrap()))
+            .collect();
+        assert_eq!(names, vec!["api/index.rst", "api/keep.tmp.rst", "index.rst"]);
+
+        // Without ignore files everything is found
+        let files = get_matching_files(&base_path, &["**/*.rst".to_string()], &[]).unwrap();
+        assert_eq!(files.len(), 6);
+    }
 }
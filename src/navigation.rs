@@ -7,28 +7,132 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::intern::Symbol;
+
+lazy_static::lazy_static! {
+    static ref CODE_RE: Regex = Regex::new(r"`([^`]+)`").unwrap();
+}
+
 /// Process inline markup in navigation titles (backticks -> code tags)
 fn render_nav_title(title: &str) -> String {
     // First HTML escape the content
     let escaped = html_escape::encode_text(title).to_string();
 
     // Process single backticks: `code` -> <code class="code docutils literal notranslate"><span class="pre">code</span></code>
-    let code_re = Regex::new(r"`([^`]+)`").unwrap();
-    code_re
+    CODE_RE
         .replace_all(&escaped, r#"<code class="code docutils literal notranslate"><span class="pre">$1</span></code>"#)
         .to_string()
 }
 
+/// Split a toctree entry into its explicit title and target: `Title <target>`
+pub fn split_toctree_entry(entry: &str) -> (Option<&str>, &str) {
+    if let Some(angle_pos) = entry.find('<').filter(|_| entry.ends_with('>')) {
+        let title = entry[..angle_pos].trim();
+        let target = entry[angle_pos + 1..entry.len() - 1].trim();
+        (Some(title), target)
+    } else {
+        (None, entry.trim())
+    }
+}
+
+/// Levels of section numbers a toctree's `:numbered:` option asks for: all
+/// of them when it has no positive value, none without the option
+pub fn numbered_depth(option: Option<&str>) -> usize {
+    option.map_or(0, |value| {
+        value
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|depth| *depth > 0)
+            .unwrap_or(usize::MAX)
+    })
+}
+
+/// Whether a toctree target is an external URL rather than a document
+pub fn is_external(target: &str) -> bool {
+    target.starts_with("http://") || target.starts_with("https://")
+}
+
+/// Join a document reference with the directory of the document it appears
+/// in, like Sphinx's `docname_join`. Targets starting with `/` are relative
+/// to the source root.
+pub fn resolve_docname(base_doc: &str, target: &str) -> String {
+    let joined = match target.strip_prefix('/') {
+        Some(absolute) => absolute.to_string(),
+        None => match base_doc.rfind('/') {
+            Some(pos) => format!("{}/{}", &base_doc[..pos], target),
+            None => target.to_string(),
+        },
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Resolve a toctree target to a document name. A target naming a directory
+/// resolves to its `index` page, and a target that only exists relative to
+/// the source root is accepted as such.
+pub fn resolve_toctree_target(base_doc: &str, target: &str, is_document: impl Fn(&str) -> bool) -> String {
+    let resolved = resolve_docname(base_doc, target);
+    if is_document(&resolved) {
+        return resolved;
+    }
+    let index = if resolved.is_empty() {
+        "index".to_string()
+    } else {
+        format!("{}/index", resolved)
+    };
+    if is_document(&index) {
+        return index;
+    }
+    let from_root = resolve_docname("", target);
+    if is_document(&from_root) {
+        return from_root;
+    }
+    resolved
+}
+
+/// Relative URL from the page of `from_doc` to the page of `to_doc`
+pub fn relative_uri(from_doc: &str, to_doc: &str) -> String {
+    let from_dirs: Vec<&str> = from_doc.split('/').collect();
+    let from_dirs = &from_dirs[..from_dirs.len() - 1];
+    let to_parts: Vec<&str> = to_doc.split('/').collect();
+
+    let common = from_dirs
+        .iter()
+        .zip(&to_parts[..to_parts.len() - 1])
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut uri = "../".repeat(from_dirs.len() - common);
+    uri.push_str(&to_parts[common..].join("/"));
+    uri.push_str(".html");
+    uri
+}
+
 /// Represents a navigation link
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NavLink {
+    /// Document linked to
+    #[serde(default)]
+    pub docname: String,
     pub title: String,
     pub link: String,
 }
 
 impl NavLink {
-    pub fn new(title: impl Into<String>, link: impl Into<String>) -> Self {
+    pub fn new(docname: impl Into<String>, title: impl Into<String>, link: impl Into<String>) -> Self {
         Self {
+            docname: docname.into(),
             title: title.into(),
             link: link.into(),
         }
@@ -51,13 +155,13 @@ pub struct PageNavigation {
 /// A node in the document tree
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TocTreeNode {
-    pub doc_path: String,
-    pub title: String,
+    pub doc_path: Symbol,
+    pub title: Symbol,
     pub children: Vec<TocTreeNode>,
 }
 
 impl TocTreeNode {
-    pub fn new(doc_path: impl Into<String>, title: impl Into<String>) -> Self {
+    pub fn new(doc_path: impl Into<Symbol>, title: impl Into<Symbol>) -> Self {
         Self {
             doc_path: doc_path.into(),
             title: title.into(),
@@ -73,21 +177,28 @@ impl TocTreeNode {
         }
         result
     }
+
+    fn flatten_nodes<'a>(&'a self, nodes: &mut Vec<&'a TocTreeNode>) {
+        nodes.push(self);
+        for child in &self.children {
+            child.flatten_nodes(nodes);
+        }
+    }
 }
 
 /// Manages the document hierarchy and navigation
 #[derive(Debug, Default)]
 pub struct NavigationBuilder {
     /// Map from document path to its toctree children
-    toctree_entries: HashMap<String, Vec<String>>,
+    toctree_entries: HashMap<Symbol, Vec<Symbol>>,
     /// Map from document path to its title
-    titles: HashMap<String, String>,
+    titles: HashMap<Symbol, Symbol>,
     /// The root document (usually "index")
-    master_doc: String,
+    master_doc: Symbol,
 }
 
 impl NavigationBuilder {
-    pub fn new(master_doc: impl Into<String>) -> Self {
+    pub fn new(master_doc: impl Into<Symbol>) -> Self {
         Self {
             toctree_entries: HashMap::new(),
             titles: HashMap::new(),
@@ -97,130 +208,143 @@ impl NavigationBuilder {
 
     /// Register a document with its title
     pub fn register_document(&mut self, doc_path: &str, title: &str) {
-        self.titles.insert(doc_path.to_string(), title.to_string());
+        self.titles.insert(Symbol::intern(doc_path), Symbol::intern(title));
     }
 
     /// Register toctree entries for a document
-    pub fn register_toctree(&mut self, doc_path: &str, entries: Vec<String>) {
-        self.toctree_entries.insert(doc_path.to_string(), entries);
+    pub fn register_toctree(
+        &mut self,
+        doc_path: &str,
+        entries: impl IntoIterator<Item = impl Into<Symbol>>,
+    ) {
+        let entries = entries.into_iter().map(Into::into).collect();
+        self.toctree_entries.insert(Symbol::intern(doc_path), entries);
     }
 
     /// Build the document tree starting from the master document
     pub fn build_tree(&self) -> TocTreeNode {
-        self.build_tree_for(&self.master_doc)
+        let mut ancestors = Vec::new();
+        self.build_tree_for(&self.master_doc, &mut ancestors)
     }
 
-    fn build_tree_for(&self, doc_path: &str) -> TocTreeNode {
-        let title = self.titles.get(doc_path).cloned().unwrap_or_else(|| doc_path.to_string());
-        let mut node = TocTreeNode::new(doc_path, title);
+    fn build_tree_for(&self, doc_path: &Symbol, ancestors: &mut Vec<Symbol>) -> TocTreeNode {
+        let title = self.titles.get(doc_path).cloned().unwrap_or_else(|| doc_path.clone());
+        let mut node = TocTreeNode::new(doc_path.clone(), title);
 
         if let Some(entries) = self.toctree_entries.get(doc_path) {
+            ancestors.push(doc_path.clone());
             for entry in entries {
-                // Handle explicit title syntax: "Title <path>"
-                let (child_title, child_path) = if let Some(angle_pos) = entry.find('<') {
-                    if entry.ends_with('>') {
-                        let title = entry[..angle_pos].trim();
-                        let path = &entry[angle_pos + 1..entry.len() - 1];
-                        (Some(title.to_string()), path.to_string())
-                    } else {
-                        (None, entry.clone())
-                    }
-                } else {
-                    (None, entry.clone())
-                };
+                let (child_title, child_path) = self.resolve_entry(doc_path, entry);
 
                 // For external URLs, create a leaf node (no recursive building)
-                if child_path.starts_with("http://") || child_path.starts_with("https://") {
-                    let ext_title = child_title.unwrap_or_else(|| child_path.clone());
-                    let ext_node = TocTreeNode::new(&child_path, ext_title);
-                    node.children.push(ext_node);
+                if is_external(&child_path) {
+                    let ext_title = child_title.map(Symbol::from).unwrap_or_else(|| child_path.clone());
+                    node.children.push(TocTreeNode::new(child_path, ext_title));
                     continue;
                 }
 
-                let mut child_node = self.build_tree_for(&child_path);
+                // A document that includes one of its ancestors would recurse forever
+                if ancestors.contains(&child_path) {
+                    log::warn!("circular toctree reference to '{}' in '{}'", child_path, doc_path);
+                    continue;
+                }
+
+                let mut child_node = self.build_tree_for(&child_path, ancestors);
                 // Use explicit title if provided
                 if let Some(t) = child_title {
-                    child_node.title = t;
+                    child_node.title = t.into();
                 }
                 node.children.push(child_node);
             }
+            ancestors.pop();
         }
 
         node
     }
 
-    /// Get navigation context for a specific document
-    pub fn get_page_navigation(&self, doc_path: &str) -> PageNavigation {
+    /// Resolve a toctree entry of `doc_path` to (explicit title, document name or URL)
+    fn resolve_entry(&self, doc_path: &str, entry: &str) -> (Option<String>, Symbol) {
+        let (title, target) = split_toctree_entry(entry);
+        let path = if is_external(target) {
+            Symbol::intern(target)
+        } else {
+            resolve_toctree_target(doc_path, target, |doc| self.titles.contains_key(doc)).into()
+        };
+        (title.map(str::to_string), path)
+    }
+
+    /// Documents in reading order: depth-first through the toctrees, each
+    /// document at its first position, external links left out
+    pub fn reading_order(&self) -> Vec<(Symbol, Symbol)> {
         let tree = self.build_tree();
-        let flat_docs = tree.flatten();
+        let mut nodes = Vec::new();
+        tree.flatten_nodes(&mut nodes);
+        let mut seen = std::collections::HashSet::new();
+        nodes
+            .into_iter()
+            .filter(|node| !is_external(&node.doc_path) && seen.insert(&node.doc_path))
+            .map(|node| (node.doc_path.clone(), node.title.clone()))
+            .collect()
+    }
 
-        let mut nav = PageNavigation::default();
+    /// Get navigation context for a specific document. Links are relative to
+    /// the document's own page.
+    pub fn get_page_navigation(&self, doc_path: &str) -> PageNavigation {
+        let flat_docs = self.reading_order();
 
-        // Find position in flattened list for prev/next
-        let position = flat_docs.iter().position(|(path, _)| *path == doc_path);
+        let mut nav = PageNavigation::default();
+        let link = |path: &str, title: &str| NavLink::new(path, render_nav_title(title), relative_uri(doc_path, path));
 
-        if let Some(pos) = position {
-            // Previous
+        // Find position in the reading order for prev/next
+        if let Some(pos) = flat_docs.iter().position(|(path, _)| path == doc_path) {
             if pos > 0 {
-                let (prev_path, prev_title) = flat_docs[pos - 1];
-                nav.prev = Some(NavLink::new(
-                    render_nav_title(prev_title),
-                    format!("{}.html", prev_path),
-                ));
+                let (prev_path, prev_title) = &flat_docs[pos - 1];
+                nav.prev = Some(link(prev_path, prev_title));
             }
-
-            // Next
-            if pos + 1 < flat_docs.len() {
-                let (next_path, next_title) = flat_docs[pos + 1];
-                nav.next = Some(NavLink::new(
-                    render_nav_title(next_title),
-                    format!("{}.html", next_path),
-                ));
+            if let Some((next_path, next_title)) = flat_docs.get(pos + 1) {
+                nav.next = Some(link(next_path, next_title));
             }
         }
 
-        // Build parent chain
-        nav.parents = self.find_parents(doc_path, &tree);
+        nav.parents = self.breadcrumbs(doc_path);
 
         // Get direct children
         if let Some(entries) = self.toctree_entries.get(doc_path) {
             for entry in entries {
-                let (child_title, child_path) = if let Some(angle_pos) = entry.find('<') {
-                    if entry.ends_with('>') {
-                        let title = entry[..angle_pos].trim().to_string();
-                        let path = entry[angle_pos + 1..entry.len() - 1].to_string();
-                        (title, path)
-                    } else {
-                        let title = self.titles.get(entry).cloned().unwrap_or_else(|| entry.clone());
-                        (title, entry.clone())
-                    }
-                } else {
-                    let title = self.titles.get(entry).cloned().unwrap_or_else(|| entry.clone());
-                    (title, entry.clone())
-                };
-
+                let (child_title, child_path) = self.resolve_entry(doc_path, entry);
                 // Skip external URLs
-                if !child_path.starts_with("http://") && !child_path.starts_with("https://") {
-                    nav.children.push(NavLink::new(render_nav_title(&child_title), format!("{}.html", child_path)));
+                if is_external(&child_path) {
+                    continue;
                 }
+                let title = child_title
+                    .map(Symbol::from)
+                    .or_else(|| self.titles.get(&child_path).cloned())
+                    .unwrap_or_else(|| child_path.clone());
+                nav.children.push(link(&child_path, &title));
             }
         }
 
         nav
     }
 
-    fn find_parents(&self, doc_path: &str, tree: &TocTreeNode) -> Vec<NavLink> {
+    /// Ancestor chain of a document for breadcrumbs, starting with the master
+    /// document, with links relative to the document's page. Empty for the
+    /// master document itself and for documents outside the toctree hierarchy.
+    pub fn breadcrumbs(&self, doc_path: &str) -> Vec<NavLink> {
+        let tree = self.build_tree();
         let mut path = Vec::new();
-        self.find_path_to(doc_path, tree, &mut path);
-        // Remove the document itself from the path
-        if !path.is_empty() {
-            path.pop();
+        if !self.find_path_to(doc_path, &tree, &mut path) {
+            return Vec::new();
         }
-        path
+        // Remove the document itself from the path
+        path.pop();
+        path.into_iter()
+            .map(|(path, title)| NavLink::new(path.as_str(), render_nav_title(&title), relative_uri(doc_path, &path)))
+            .collect()
     }
 
-    fn find_path_to(&self, target: &str, node: &TocTreeNode, path: &mut Vec<NavLink>) -> bool {
-        path.push(NavLink::new(render_nav_title(&node.title), format!("{}.html", &node.doc_path)));
+    fn find_path_to(&self, target: &str, node: &TocTreeNode, path: &mut Vec<(Symbol, Symbol)>) -> bool {
+        path.push((node.doc_path.clone(), node.title.clone()));
 
         if node.doc_path == target {
             return true;
@@ -289,13 +413,13 @@ impl NavigationBuilder {
     }
 
     /// Get the path from root to a specific document (for current markers)
-    fn get_path_to_doc(&self, doc_path: &str, tree: &TocTreeNode) -> Vec<String> {
+    fn get_path_to_doc(&self, doc_path: &str, tree: &TocTreeNode) -> Vec<Symbol> {
         let mut path = Vec::new();
         self.find_doc_path(doc_path, tree, &mut path);
         path
     }
 
-    fn find_doc_path(&self, target: &str, node: &TocTreeNode, path: &mut Vec<String>) -> bool {
+    fn find_doc_path(&self, target: &str, node: &TocTreeNode, path: &mut Vec<Symbol>) -> bool {
         path.push(node.doc_path.clone());
 
         if node.doc_path == target {
@@ -329,17 +453,21 @@ impl NavigationBuilder {
         node: &TocTreeNode,
         depth: usize,
         options: &ToctreeOptions,
-        current_path: &[String],
+        current_path: &[Symbol],
         checkbox_id: &mut usize,
     ) -> String {
         if depth > options.maxdepth && options.maxdepth > 0 {
             return String::new();
         }
 
-        let is_external = node.doc_path.starts_with("http://") || node.doc_path.starts_with("https://");
-        let has_children = !node.children.is_empty() && (options.maxdepth == 0 || depth < options.maxdepth);
+        let is_external = is_external(&node.doc_path);
         let is_current = !is_external && current_path.contains(&node.doc_path);
-        let is_current_page = !is_external && options.current_doc.as_ref().map(|d| d == &node.doc_path).unwrap_or(false);
+        // With collapse, only the ancestors of the current page (and the page itself) are expanded
+        let expanded = !options.collapse || options.current_doc.is_none() || is_current;
+        let has_children = !node.children.is_empty()
+            && expanded
+            && (options.maxdepth == 0 || depth < options.maxdepth);
+        let is_current_page = !is_external && options.current_doc.as_ref().map(|d| node.doc_path == d.as_str()).unwrap_or(false);
 
         // Build class list
         let mut classes = vec![format!("toctree-l{}", depth)];
@@ -353,13 +481,17 @@ impl NavigationBuilder {
             classes.push("has-children".to_string());
         }
 
-        // Build link class and href
+        // Build link class and href (relative to the current page when there is one)
+        let internal_href = match options.current_doc {
+            Some(ref current_doc) => relative_uri(current_doc, &node.doc_path),
+            None => format!("{}.html", node.doc_path),
+        };
         let (link_class, href) = if is_external {
-            ("reference external", node.doc_path.clone())
+            ("reference external", node.doc_path.to_string())
         } else if is_current_page {
-            ("current reference internal", format!("{}.html", node.doc_path))
+            ("current reference internal", internal_href)
         } else {
-            ("reference internal", format!("{}.html", node.doc_path))
+            ("reference internal", internal_href)
         };
 
         let mut html = format!(
@@ -404,7 +536,7 @@ impl NavigationBuilder {
     }
 
     /// Get all registered titles
-    pub fn titles(&self) -> &HashMap<String, String> {
+    pub fn titles(&self) -> &HashMap<Symbol, Symbol> {
         &self.titles
     }
 }
@@ -412,7 +544,9 @@ impl NavigationBuilder {
 /// Options for rendering toctree
 #[derive(Debug, Clone)]
 pub struct ToctreeOptions {
+    /// Maximum nesting depth; 0 means unlimited
     pub maxdepth: usize,
+    /// Only expand entries on the path to `current_doc`
     pub collapse: bool,
     pub includehidden: bool,
     pub titles_only: bool,
@@ -562,8 +696,10 @@ mod tests {
         builder.register_toctree("index", vec!["components".to_string()]);
         builder.register_toctree("components", vec!["action".to_string()]);
 
-        let mut options = ToctreeOptions::default();
-        options.current_doc = Some("action".to_string());
+        let options = ToctreeOptions {
+            current_doc: Some("action".to_string()),
+            ..Default::default()
+        };
         let html = builder.render_toctree(&options);
 
         // Should have current classes in the path to action
@@ -593,4 +729,142 @@ mod tests {
         assert!(html.contains("has-children"));
         assert!(html.contains("<li class=\"toctree-l1\"><a class=\"reference internal\" href=\"leaf.html\">Leaf</a></li>"));
     }
+
+    #[test]
+    fn test_render_toctree_collapse() {
+        let mut builder = NavigationBuilder::new("index");
+
+        for (path, title) in [
+            ("index", "Welcome"),
+            ("guide", "Guide"),
+            ("install", "Install"),
+            ("api", "API"),
+            ("classes", "Classes"),
+            ("methods", "Methods"),
+        ] {
+            builder.register_document(path, title);
+        }
+        builder.register_toctree("index", vec!["guide".to_string(), "api".to_string()]);
+        builder.register_toctree("guide", vec!["install".to_string()]);
+        builder.register_toctree("api", vec!["classes".to_string()]);
+        builder.register_toctree("classes", vec!["methods".to_string()]);
+
+        let mut options = ToctreeOptions {
+            current_doc: Some("install".to_string()),
+            ..Default::default()
+        };
+        let html = builder.render_toctree(&options);
+        // Siblings of the current branch stay collapsed
+        assert!(html.contains("install.html"));
+        assert!(html.contains("api.html"));
+        assert!(!html.contains("classes.html"));
+
+        options.collapse = false;
+        options.maxdepth = 2;
+        let html = builder.render_toctree(&options);
+        assert!(html.contains("classes.html"));
+        assert!(!html.contains("methods.html"));
+
+        options.maxdepth = 0;
+        let html = builder.render_toctree(&options);
+        assert!(html.contains("methods.html"));
+    }
+
+    #[test]
+    fn test_resolve_docname_and_relative_uri() {
+        assert_eq!(resolve_docname("api/index", "classes"), "api/classes");
+        assert_eq!(resolve_docname("api/index", "../guide/install"), "guide/install");
+        assert_eq!(resolve_docname("api/index", "/guide"), "guide");
+        assert_eq!(resolve_docname("index", "./api/"), "api");
+
+        assert_eq!(relative_uri("index", "api/index"), "api/index.html");
+        assert_eq!(relative_uri("api/classes", "api/index"), "index.html");
+        assert_eq!(relative_uri("api/v1/methods", "guide/install"), "../../guide/install.html");
+        assert_eq!(relative_uri("api/classes", "index"), "../index.html");
+    }
+
+    #[test]
+    fn test_reading_order_across_nested_toctrees() {
+        let mut builder = NavigationBuilder::new("index");
+
+        for (path, title) in [
+            ("index", "Welcome"),
+            ("intro", "Intro"),
+            ("api/index", "API"),
+            ("api/classes", "Classes"),
+            ("api/v1/methods", "Methods"),
+            ("faq", "FAQ"),
+        ] {
+            builder.register_document(path, title);
+        }
+        // Entries are relative to the document; "api" names a directory index page
+        builder.register_toctree(
+            "index",
+            vec!["intro".to_string(), "api".to_string(), "faq".to_string()],
+        );
+        builder.register_toctree(
+            "api/index",
+            vec!["classes".to_string(), "v1/methods".to_string(), "/intro".to_string()],
+        );
+        // A cycle back to an ancestor is ignored
+        builder.register_toctree("api/v1/methods", vec!["../index".to_string()]);
+
+        let order: Vec<Symbol> = builder.reading_order().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(
+            order,
+            vec!["index", "intro", "api/index", "api/classes", "api/v1/methods", "faq"]
+        );
+
+        let nav = builder.get_page_navigation("api/v1/methods");
+        assert_eq!(nav.prev.as_ref().unwrap().link, "../classes.html");
+        assert_eq!(nav.next.as_ref().unwrap().link, "../../faq.html");
+        let parents: Vec<(&str, &str)> = nav
+            .parents
+            .iter()
+            .map(|link| (link.title.as_str(), link.link.as_str()))
+            .collect();
+        assert_eq!(
+            parents,
+            vec![("Welcome", "../../index.html"), ("API", "../index.html")]
+        );
+
+        // Leaving a subdirectory index page continues into the next top-level entry
+        let nav = builder.get_page_navigation("api/index");
+        assert_eq!(nav.prev.as_ref().unwrap().link, "../intro.html");
+        assert_eq!(nav.children.len(), 3);
+        assert_eq!(nav.children[2].link, "../intro.html");
+
+        let html = builder.render_toctree(&ToctreeOptions {
+            current_doc: Some("api/classes".to_string()),
+            ..Default::default()
+        });
+        assert!(html.contains("href=\"../faq.html\""));
+        assert!(html.contains("class=\"current reference internal\" href=\"classes.html\""));
+    }
+
+    #[test]
+    fn test_breadcrumbs() {
+        let mut builder = NavigationBuilder::new("index");
+
+        for (path, title) in [
+            ("index", "Welcome"),
+            ("guide/index", "`Guide`"),
+            ("guide/install", "Install"),
+            ("orphan", "Orphan"),
+        ] {
+            builder.register_document(path, title);
+        }
+        builder.register_toctree("index", vec!["guide/index".to_string()]);
+        builder.register_toctree("guide/index", vec!["install".to_string()]);
+
+        let crumbs = builder.breadcrumbs("guide/install");
+        assert_eq!(crumbs.len(), 2);
+        assert_eq!(crumbs[0].title, "Welcome");
+        assert_eq!(crumbs[0].link, "../index.html");
+        assert!(crumbs[1].title.contains("<code"));
+        assert_eq!(crumbs[1].link, "index.html");
+
+        assert!(builder.breadcrumbs("index").is_empty());
+        assert!(builder.breadcrumbs("orphan").is_empty());
+    }
 }
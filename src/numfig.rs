@@ -0,0 +1,221 @@
+//! Numbered figures, tables and code blocks.
+//!
+//! With `numfig`, every captioned figure, table and code block gets a number,
+//! counted per kind over the documents in toctree order like Sphinx does
+//! without section numbers. The first pass records the captioned items of
+//! each document in the build environment, which numbers them once the
+//! toctrees are known; the renderer prefixes the captions with the numbers
+//! and lists the items at `.. figlist::` and `.. tablelist::`.
+
+use anyhow::Result;
+
+use crate::document::RstNode;
+use crate::renderer::{rst_node_ids, SlugRegistry};
+use crate::transforms::{DocumentTransform, TransformContext};
+
+/// A captioned figure, table or code block of a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enumerable {
+    /// `figure`, `table` or `code-block`
+    pub figtype: &'static str,
+    /// Id of the item in its page
+    pub id: String,
+    /// Caption, as inline RST
+    pub caption: String,
+    pub line: usize,
+}
+
+/// Kind of item a directive makes, if it can be numbered
+pub fn figtype(directive: &str) -> Option<&'static str> {
+    match directive {
+        "figure" => Some("figure"),
+        "table" | "list-table" => Some("table"),
+        "code-block" | "code" | "sourcecode" | "literalinclude" => Some("code-block"),
+        _ => None,
+    }
+}
+
+/// Caption of an item, as the renderer shows it
+fn caption(
+    directive: &str,
+    args: &[String],
+    options: &std::collections::HashMap<String, String>,
+    content: &str,
+) -> Option<String> {
+    let caption = match figtype(directive)? {
+        "figure" => {
+            let content = content.trim();
+            let first = content.find("\n\n").map_or(content, |end| &content[..end]);
+            first.lines().map(str::trim).collect::<Vec<_>>().join(" ")
+        }
+        "table" => args.join(" ").trim().to_string(),
+        _ => {
+            let caption = options.get("caption")?;
+            match (directive, args.first()) {
+                ("literalinclude", Some(filename)) => caption.replace("{filename}", filename),
+                _ => caption.clone(),
+            }
+        }
+    };
+    Some(caption).filter(|caption| !caption.is_empty())
+}
+
+/// The captioned figures, tables and code blocks of a document that have
+/// an id, in order
+pub fn enumerables(nodes: &[RstNode]) -> Vec<Enumerable> {
+    // The ids the renderer gives the items, made unique among the others
+    let ids = rst_node_ids(nodes, &mut SlugRegistry::new());
+    nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, node)| {
+            let RstNode::Directive {
+                name,
+                args,
+                options,
+                content,
+                line,
+            } = node
+            else {
+                return None;
+            };
+            options.get("name")?;
+            let id = ids.get(&index)?.clone();
+            Some(Enumerable {
+                figtype: figtype(name)?,
+                id,
+                caption: caption(name, args, options, content)?,
+                line: *line,
+            })
+        })
+        .collect()
+}
+
+/// Names the captioned figures, tables and code blocks that have no
+/// `:name:`, like `figure-2`, so they can be numbered and linked to
+pub struct NameEnumerables;
+
+impl DocumentTransform for NameEnumerables {
+    fn apply(&self, _context: &mut TransformContext, nodes: &mut Vec<RstNode>) -> Result<()> {
+        let mut counts = std::collections::HashMap::new();
+        for node in nodes.iter_mut() {
+            let RstNode::Directive {
+                name,
+                args,
+                options,
+                content,
+                ..
+            } = node
+            else {
+                continue;
+            };
+            let Some(figtype) = figtype(name) else {
+                continue;
+            };
+            if caption(name, args, options, content).is_none() {
+                continue;
+            }
+            let count = counts.entry(figtype).or_insert(0);
+            *count += 1;
+            if !options.contains_key("name") {
+                options.insert("name".to_string(), format!("{}-{}", figtype, count));
+            }
+        }
+        Ok(())
+    }
+
+    fn get_name(&self) -> &str {
+        "name-enumerables"
+    }
+
+    fn priority(&self) -> u32 {
+        300
+    }
+}
+
+/// Caption prefix of a number, like `Fig. 3` of `Fig. %s`
+pub fn format_number(format: &str, number: &[u32]) -> String {
+    let number: Vec<String> = number.iter().map(u32::to_string).collect();
+    format.replace("%s", &number.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BuildConfig;
+    use crate::document::DocumentContent;
+    use crate::parser::Parser;
+    use crate::transforms::TransformRegistry;
+    use std::path::Path;
+
+    #[test]
+    fn test_enumerables_named_and_collected() {
+        let config = BuildConfig {
+            numfig: true,
+            ..BuildConfig::default()
+        };
+        let parser = Parser::new(&config).unwrap();
+        let source = "\
+.. figure:: a.png
+
+   First *plot*.
+
+   Legend.
+
+.. figure:: b.png
+   :name: Second Plot
+
+   Second plot.
+
+.. figure:: c.png
+
+.. list-table:: Sizes
+
+   * - a
+
+.. code-block:: python
+   :caption: Example
+
+   x = 1
+
+.. code-block:: python
+
+   y = 2
+";
+        let mut document = parser.parse(Path::new("page.rst"), source).unwrap();
+        let registry = TransformRegistry::from_config(&config);
+        registry
+            .apply(&mut document, "page", &config, &parser)
+            .unwrap();
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            panic!("not RST");
+        };
+        let found: Vec<(&str, String, String)> = enumerables(&rst.ast)
+            .into_iter()
+            .map(|item| (item.figtype, item.id, item.caption))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "figure",
+                    "figure-1".to_string(),
+                    "First *plot*.".to_string()
+                ),
+                (
+                    "figure",
+                    "second-plot".to_string(),
+                    "Second plot.".to_string()
+                ),
+                ("table", "table-1".to_string(), "Sizes".to_string()),
+                (
+                    "code-block",
+                    "code-block-1".to_string(),
+                    "Example".to_string()
+                ),
+            ]
+        );
+        assert_eq!(format_number("Fig. %s", &[3]), "Fig. 3");
+        assert_eq!(format_number("Table %s:", &[1, 2]), "Table 1.2:");
+    }
+}
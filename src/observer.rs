@@ -0,0 +1,24 @@
+//! Build events for applications embedding the builder.
+//!
+//! Observers added with [`SphinxBuilder::add_observer`] hear about every
+//! build and every written or failed document. Document events come from the
+//! worker threads, possibly at the same time.
+//!
+//! [`SphinxBuilder::add_observer`]: crate::SphinxBuilder::add_observer
+
+use crate::builder::BuildStats;
+use std::path::Path;
+
+pub trait BuildObserver: Send + Sync {
+    /// A build started
+    fn build_started(&self) {}
+
+    /// The page of a document was written to `output_path`
+    fn document_written(&self, _doc_path: &str, _source_path: &Path, _output_path: &Path) {}
+
+    /// A document could not be built
+    fn document_failed(&self, _source_path: &Path, _message: &str) {}
+
+    /// A build finished, with its statistics or the error that ended it
+    fn build_finished(&self, _result: Result<&BuildStats, &anyhow::Error>) {}
+}
@@ -0,0 +1,324 @@
+//! Output size optimizations: HTML/CSS/JS minification and pre-compressed
+//! `.gz`/`.br` siblings that static hosts can serve directly.
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Pre-compressed encodings written next to output files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionFormat {
+    Gzip,
+    Brotli,
+}
+
+impl CompressionFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            CompressionFormat::Gzip => "gz",
+            CompressionFormat::Brotli => "br",
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            CompressionFormat::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            CompressionFormat::Brotli => {
+                let mut output = Vec::new();
+                {
+                    let mut encoder = brotli::CompressorWriter::new(&mut output, 4096, 11, 22);
+                    encoder.write_all(data)?;
+                }
+                Ok(output)
+            }
+        }
+    }
+}
+
+/// Text formats worth compressing; images and fonts are already compressed
+const COMPRESSIBLE_EXTENSIONS: &[&str] = &[
+    "html", "htm", "css", "js", "mjs", "json", "svg", "xml", "txt", "map",
+];
+
+/// Elements whose content must be kept byte for byte
+const RAW_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style"];
+
+/// Conservatively minify HTML: comments are dropped and whitespace runs
+/// between markup are collapsed to a single character, while the content of
+/// `<pre>`, `<textarea>`, `<script>` and `<style>` is left untouched. Tags are
+/// never removed, so the rendered page is unchanged.
+pub fn minify_html(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(position) = rest.find('<') {
+        push_collapsed(&mut output, &rest[..position]);
+        rest = &rest[position..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |end| end + 3);
+            // Conditional comments are markup for old browsers
+            if rest.starts_with("<!--[if") || rest.starts_with("<!--<![endif") {
+                output.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+            // Whitespace around a dropped comment collapses into one run
+            if output.ends_with(char::is_whitespace) {
+                rest = rest.trim_start();
+            }
+            continue;
+        }
+
+        let is_tag = rest[1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '/' | '!' | '?'));
+        if !is_tag {
+            output.push('<');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let end = tag_end(rest);
+        let tag = &rest[..end];
+        output.push_str(tag);
+        rest = &rest[end..];
+
+        if let Some(name) = raw_element(tag) {
+            let close = format!("</{}", name);
+            let end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+            output.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+    push_collapsed(&mut output, rest);
+
+    output
+}
+
+/// Byte offset just past the `>` closing the tag at the start of `html`,
+/// ignoring `>` inside quoted attribute values
+fn tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (index, c) in html.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return index + 1,
+            None => {}
+        }
+    }
+    html.len()
+}
+
+/// Name of the raw element opened by `tag`, if any
+fn raw_element(tag: &str) -> Option<&'static str> {
+    let name: String = tag[1..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if tag.ends_with("/>") {
+        return None;
+    }
+    RAW_ELEMENTS.iter().copied().find(|raw| *raw == name)
+}
+
+/// Append `text` with each whitespace run collapsed to one newline (if the
+/// run contained one) or one space
+fn push_collapsed(output: &mut String, text: &str) {
+    let mut whitespace: Option<char> = None;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            whitespace = Some(if c == '\n' || whitespace == Some('\n') { '\n' } else { ' ' });
+        } else {
+            if let Some(ws) = whitespace.take() {
+                output.push(ws);
+            }
+            output.push(c);
+        }
+    }
+    if let Some(ws) = whitespace {
+        output.push(ws);
+    }
+}
+
+/// Minify a stylesheet, returning it unchanged if it cannot be parsed
+pub fn minify_css(css: &str) -> String {
+    match minifier::css::minify(css) {
+        Ok(minified) => minified.to_string(),
+        Err(_) => css.to_string(),
+    }
+}
+
+/// Minify a script
+pub fn minify_js(js: &str) -> String {
+    minifier::js::minify(js).to_string()
+}
+
+/// Minify every CSS and JavaScript file under `dir` in place. Files that are
+/// already minified (`*.min.css`, `*.min.js`) are skipped. Returns the number
+/// of files rewritten.
+pub fn minify_assets(dir: &Path) -> Result<usize> {
+    let files: Vec<PathBuf> = output_files(dir)
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            !name.contains(".min.")
+                && matches!(extension(path).as_deref(), Some("css") | Some("js"))
+        })
+        .collect();
+
+    files
+        .par_iter()
+        .map(|path| {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read asset: {}", path.display()))?;
+            let minified = match extension(path).as_deref() {
+                Some("css") => minify_css(&content),
+                _ => minify_js(&content),
+            };
+            if minified.len() < content.len() {
+                std::fs::write(path, minified)
+                    .with_context(|| format!("Failed to write asset: {}", path.display()))?;
+                Ok(1)
+            } else {
+                Ok(0)
+            }
+        })
+        .sum()
+}
+
+/// Write a compressed sibling (`index.html.gz`, `index.html.br`, ...) for
+/// every compressible file under `dir`. Siblings newer than their file are
+/// kept, so unchanged output is not compressed again. Returns the number of
+/// siblings written.
+pub fn precompress(dir: &Path, formats: &[CompressionFormat]) -> Result<usize> {
+    let files: Vec<PathBuf> = output_files(dir)
+        .filter(|path| {
+            extension(path).is_some_and(|ext| COMPRESSIBLE_EXTENSIONS.contains(&ext.as_str()))
+        })
+        .collect();
+
+    files
+        .par_iter()
+        .map(|path| {
+            let mut written = 0;
+            let mut data = None;
+            for format in formats {
+                let sibling = compressed_path(path, *format);
+                if is_up_to_date(&sibling, path) {
+                    continue;
+                }
+                if data.is_none() {
+                    data = Some(std::fs::read(path).with_context(|| {
+                        format!("Failed to read output file: {}", path.display())
+                    })?);
+                }
+                let compressed = format.compress(data.as_deref().unwrap())?;
+                std::fs::write(&sibling, compressed).with_context(|| {
+                    format!("Failed to write compressed file: {}", sibling.display())
+                })?;
+                written += 1;
+            }
+            Ok(written)
+        })
+        .sum()
+}
+
+/// Path of the compressed sibling of `path`
+pub fn compressed_path(path: &Path, format: CompressionFormat) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(format.extension());
+    PathBuf::from(name)
+}
+
+fn is_up_to_date(target: &Path, source: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(target), modified(source)) {
+        (Some(target), Some(source)) => target >= source,
+        _ => false,
+    }
+}
+
+/// Files in the output directory, skipping hidden directories such as the
+/// build cache
+fn output_files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !(entry.file_type().is_dir()
+                    && entry.file_name().to_string_lossy().starts_with('.'))
+        })
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_minify_html_keeps_preformatted_content() {
+        let html = "<!DOCTYPE html>\n<html>\n  <head>\n    <!-- a comment -->\n    <title>A  b</title>\n  </head>\n  <body>\n    <p>one\n       two  <a href=\"x\" title=\"a > b\">three</a></p>\n    <pre>  keep\n    this  </pre>\n    <script>if (a < b) {\n  run();\n}</script>\n    <p>1 < 2</p>\n  </body>\n</html>\n";
+
+        assert_eq!(
+            minify_html(html),
+            "<!DOCTYPE html>\n<html>\n<head>\n<title>A b</title>\n</head>\n<body>\n<p>one\ntwo <a href=\"x\" title=\"a > b\">three</a></p>\n<pre>  keep\n    this  </pre>\n<script>if (a < b) {\n  run();\n}</script>\n<p>1 < 2</p>\n</body>\n</html>\n"
+        );
+    }
+
+    #[test]
+    fn test_precompress_writes_siblings() {
+        let dir = tempfile::tempdir().unwrap();
+        let page = "<p>hello</p>".repeat(100);
+        std::fs::write(dir.path().join("index.html"), &page).unwrap();
+        std::fs::write(dir.path().join("logo.png"), [0u8; 16]).unwrap();
+        std::fs::create_dir(dir.path().join(".sphinx-ultra-cache")).unwrap();
+        std::fs::write(dir.path().join(".sphinx-ultra-cache/cache.json"), "{}").unwrap();
+
+        let formats = [CompressionFormat::Gzip, CompressionFormat::Brotli];
+        assert_eq!(precompress(dir.path(), &formats).unwrap(), 2);
+        assert!(!dir.path().join("logo.png.gz").exists());
+        assert!(!dir.path().join(".sphinx-ultra-cache/cache.json.gz").exists());
+
+        let mut gunzipped = String::new();
+        flate2::read::GzDecoder::new(
+            std::fs::File::open(dir.path().join("index.html.gz")).unwrap(),
+        )
+        .read_to_string(&mut gunzipped)
+        .unwrap();
+        assert_eq!(gunzipped, page);
+
+        let mut unbrotlied = String::new();
+        brotli::Decompressor::new(
+            std::fs::File::open(dir.path().join("index.html.br")).unwrap(),
+            4096,
+        )
+        .read_to_string(&mut unbrotlied)
+        .unwrap();
+        assert_eq!(unbrotlied, page);
+
+        // Up-to-date siblings are not compressed again
+        assert_eq!(precompress(dir.path(), &formats).unwrap(), 0);
+    }
+}
@@ -1,19 +1,227 @@
 use anyhow::Result;
 use log::debug;
-use pulldown_cmark::{Event, Parser as MarkdownParser, Tag};
+use pulldown_cmark::{Event, Options as MarkdownOptions, Parser as MarkdownParser, Tag, TagEnd};
 use regex::Regex;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-use crate::config::BuildConfig;
+use crate::config::{BuildConfig, ShortTitleUnderline};
 use crate::directives::DirectiveRegistry;
 use crate::document::{
-    CrossReference, Document, DocumentContent, MarkdownContent, MarkdownNode, RstContent,
-    RstDirective, RstNode, TocEntry,
+    CrossReference, Document, DocumentContent, DocumentMetadata, MarkdownContent, MarkdownNode,
+    MessageLevel, RstContent, RstDirective, RstNode, SystemMessage, TocEntry,
 };
+use crate::footnotes::NoteLabel;
+use crate::genindex;
 // use crate::roles::RoleRegistry; // TODO: Implement roles module
 use crate::utils;
 
+/// Hard tab width of included files, as in docutils
+pub(crate) const DEFAULT_TAB_WIDTH: i64 = 8;
+
+/// State of an RST parse shared by a document and the files it includes
+struct RstParseState {
+    /// Title underline characters in order of first appearance; the first
+    /// becomes level 1, the second level 2 and so on
+    seen_underline_chars: Vec<char>,
+    /// Files being parsed, canonicalized, the document first and the
+    /// innermost include last
+    file_stack: Vec<PathBuf>,
+    /// Files named by include and literalinclude directives
+    includes: Vec<PathBuf>,
+    /// Malformed markup recovered from
+    messages: Vec<SystemMessage>,
+}
+
+impl RstParseState {
+    fn new(file_path: &Path, seen_underline_chars: Vec<char>) -> Self {
+        Self {
+            seen_underline_chars,
+            file_stack: vec![canonical_path(file_path)],
+            includes: Vec::new(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Record a problem at `line` of the file being parsed
+    fn report(&mut self, level: MessageLevel, line: usize, message: impl Into<String>) {
+        let source = match self.file_stack.as_slice() {
+            [_, .., included] => Some(included.clone()),
+            _ => None,
+        };
+        self.messages.push(SystemMessage {
+            level,
+            message: message.into(),
+            line,
+            source,
+        });
+    }
+}
+
+/// Title adornment characters
+const ADORNMENT_CHARS: &str = "=-~^\"'*+#<>";
+
+/// Shortest line of adornment docutils takes for a section title's rather
+/// than for text
+const MIN_ADORNMENT_LENGTH: usize = 4;
+
+/// Whether `line` is a run of one adornment character long enough to
+/// adorn a title
+fn is_adornment(line: &str) -> bool {
+    let mut chars = line.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    ADORNMENT_CHARS.contains(first) && chars.all(|c| c == first) && line.chars().count() >= MIN_ADORNMENT_LENGTH
+}
+
+/// Whether the block of `lines` ending before `end` runs straight into
+/// unindented text, which docutils reports as "ends without a blank line".
+/// Lines indented less than a block are not taken for an unindent.
+fn ends_without_blank_line(lines: &[&str], end: usize) -> bool {
+    end > 0
+        && end < lines.len()
+        && !lines[end - 1].trim().is_empty()
+        && !lines[end].trim().is_empty()
+        && get_indent(lines[end]) == 0
+}
+
+/// Like [`ends_without_blank_line`], for explicit markup, which may be
+/// followed right away by more explicit markup
+fn markup_ends_without_blank_line(lines: &[&str], end: usize) -> bool {
+    ends_without_blank_line(lines, end) && !lines[end].starts_with("..")
+}
+
+/// Paragraph text without the `::` that ends it and introduces a literal
+/// block: `text::` keeps one colon, `text ::` none, and a lone `::` leaves
+/// no text. `None` when the paragraph doesn't end in an unescaped `::`.
+fn strip_literal_marker(text: &str) -> Option<&str> {
+    let before = text.strip_suffix("::")?;
+    let backslashes = before.chars().rev().take_while(|&c| c == '\\').count();
+    if backslashes % 2 == 1 {
+        return None;
+    }
+    if before.is_empty() || before.ends_with(char::is_whitespace) {
+        Some(before.trim_end())
+    } else {
+        Some(&text[..text.len() - 1])
+    }
+}
+
+/// Drop the `::` that ends a list item or definition, as for a paragraph.
+/// True when there was one, so a literal block follows.
+fn strip_item_literal_marker(text: &mut String) -> bool {
+    match strip_literal_marker(text) {
+        Some(stripped) => {
+            let len = stripped.len();
+            text.truncate(len);
+            true
+        }
+        None => false,
+    }
+}
+
+const UNEXPECTED_UNINDENT: &str = "ends without a blank line; unexpected unindent.";
+
+/// `path` with symlinks and `..` resolved, when it exists
+fn canonical_path(path: &Path) -> PathBuf {
+    crate::paths::canonical_or_self(path)
+}
+
+/// Path of the file named by an include directive: relative to the including
+/// file, or to the source directory when it starts with `/`. Paths that only
+/// exist relative to the source directory are found there too.
+pub(crate) fn resolve_include_path(filename: &str, including_file: Option<&Path>, source_dir: Option<&Path>) -> PathBuf {
+    let from_root = |path: &str| match source_dir {
+        Some(source_dir) => source_dir.join(path),
+        None => PathBuf::from(path),
+    };
+    if let Some(path) = filename.strip_prefix('/') {
+        return from_root(path);
+    }
+    let relative = including_file
+        .and_then(Path::parent)
+        .map(|dir| normalize_path(&dir.join(filename)))
+        .unwrap_or_else(|| PathBuf::from(filename));
+    if relative.is_file() {
+        return relative;
+    }
+    let fallback = from_root(filename);
+    if fallback.is_file() {
+        fallback
+    } else {
+        relative
+    }
+}
+
+/// `path` with `.` and `..` components resolved without touching the file
+/// system, so it matches the paths found by source discovery
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Lines of an included file selected by its `start-line`, `end-line`,
+/// `start-after` and `end-before` options
+pub(crate) fn select_include_lines<'a>(content: &'a str, options: &HashMap<String, String>) -> Vec<&'a str> {
+    let mut lines: Vec<&str> = content.lines().collect();
+
+    // start-line skips the first N lines and end-line ends before line N
+    // (0-based, like Sphinx)
+    if let Some(start) = options.get("start-line").and_then(|start| start.parse::<usize>().ok()) {
+        if start <= lines.len() {
+            lines = lines[start..].to_vec();
+        }
+    }
+    if let Some(end) = options.get("end-line").and_then(|end| end.parse::<usize>().ok()) {
+        if end <= lines.len() {
+            lines = lines[..end].to_vec();
+        }
+    }
+    if let Some(start_after) = options.get("start-after") {
+        if let Some(pos) = lines.iter().position(|line| line.contains(start_after.as_str())) {
+            lines = lines[pos + 1..].to_vec();
+        }
+    }
+    if let Some(end_before) = options.get("end-before") {
+        if let Some(pos) = lines.iter().position(|line| line.contains(end_before.as_str())) {
+            lines = lines[..pos].to_vec();
+        }
+    }
+    lines
+}
+
+/// Replace hard tabs with spaces up to the next multiple of `width`; a
+/// negative width keeps the tabs
+pub(crate) fn expand_tabs(line: &str, width: i64) -> String {
+    if width < 0 || !line.contains('\t') {
+        return line.to_string();
+    }
+    let width = width as usize;
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = if width == 0 { 0 } else { width - column % width };
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
 /// Minimum indentation for RST directive content (3 spaces or 1 tab)
 const MIN_INDENT: usize = 3;
 
@@ -26,7 +234,129 @@ fn is_indented(line: &str) -> bool {
     indent >= MIN_INDENT
 }
 
+/// Split the leading docinfo field list (`:tocdepth: 2`, `:orphan:`, ...) off
+/// an RST source. Field lines and their indented continuations are blanked
+/// in the returned body so line numbers stay the same.
+fn split_docinfo(content: &str) -> (Vec<(String, String)>, String) {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut lines: Vec<&str> = content.lines().collect();
+    let mut i = 0;
+
+    while i < lines.len() && lines[i].trim().is_empty() {
+        i += 1;
+    }
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(caps) = DOCINFO_FIELD_RE.captures(line) {
+            let value = caps.get(2).map_or("", |m| m.as_str().trim());
+            fields.push((caps[1].to_string(), value.to_string()));
+        } else if !fields.is_empty() && is_indented(line) && !line.trim().is_empty() {
+            let (_, value) = fields.last_mut().unwrap();
+            if !value.is_empty() {
+                value.push(' ');
+            }
+            value.push_str(line.trim());
+        } else {
+            break;
+        }
+        lines[i] = "";
+        i += 1;
+    }
+
+    if fields.is_empty() {
+        return (fields, content.to_string());
+    }
+    (fields, lines.join("\n"))
+}
+
+/// Split a directive option line after its leading `:` into name and value.
+/// As in docutils, the name ends at the first colon followed by whitespace,
+/// so names like `property=og:title` may contain colons.
+fn split_option_field(field: &str) -> Option<(&str, &str)> {
+    let end = field
+        .char_indices()
+        .find(|&(i, c)| c == ':' && field[i + 1..].chars().next().is_none_or(char::is_whitespace))?
+        .0;
+    Some((&field[..end], field[end + 1..].trim()))
+}
+
+/// Record the names given by `sectionauthor`, `moduleauthor` and
+/// `codeauthor` directives on the document, each once, the fields of `meta`
+/// directives and the index entries
+fn record_metadata(ast: &[RstNode], metadata: &mut DocumentMetadata) {
+    metadata.index_terms = genindex::collect_terms(ast);
+    for node in ast {
+        let RstNode::Directive { name, args, options, .. } = node else {
+            continue;
+        };
+        if name == "meta" {
+            let mut fields: Vec<_> = options.iter().map(|(field, content)| (field.clone(), content.clone())).collect();
+            fields.sort();
+            metadata.meta.extend(fields);
+            continue;
+        }
+        let authors = match name.as_str() {
+            "sectionauthor" | "moduleauthor" => &mut metadata.authors,
+            "codeauthor" => &mut metadata.code_authors,
+            _ => continue,
+        };
+        let author = args.join(" ");
+        if !author.is_empty() && !authors.contains(&author) {
+            authors.push(author);
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref DOCINFO_FIELD_RE: Regex =
+        Regex::new(r"^:([A-Za-z][\w\-.]*):(?:\s+(.*))?$").unwrap();
+    static ref BULLET_ITEM_RE: Regex = Regex::new(r"^[*+-]\s").unwrap();
+}
+
 /// Get the indentation level of a line (number of leading spaces, tabs count as 4)
+/// Label and text on the first line of a footnote or citation, `.. [label] text`
+fn parse_note_start(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix(".. [")?;
+    let close = rest.find(']')?;
+    let label = &rest[..close];
+    let text = &rest[close + 1..];
+    if !text.is_empty() && !text.starts_with([' ', '\t']) {
+        return None;
+    }
+    NoteLabel::parse(label)?;
+    Some((label, text.trim()))
+}
+
+/// Content of a footnote or citation: the text on its first line and the
+/// indented lines after it, with the number of lines after the first
+fn parse_note_body(first_line: &str, lines: &[&str]) -> (String, usize) {
+    let mut consumed = 0;
+    for (index, line) in lines.iter().enumerate() {
+        if is_indented(line) {
+            consumed = index + 1;
+        } else if !line.trim().is_empty() {
+            break;
+        }
+    }
+    let mut content = first_line.to_string();
+    for line in dedent(&lines[..consumed]) {
+        content.push('\n');
+        content.push_str(line);
+    }
+    (content.trim().to_string(), consumed)
+}
+
+/// `lines` without the indentation their non-blank lines share
+fn dedent<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| get_indent(line))
+        .min()
+        .unwrap_or(0);
+    lines.iter().map(|line| strip_indent(line, indent)).collect()
+}
+
 fn get_indent(line: &str) -> usize {
     let mut indent = 0;
     for ch in line.chars() {
@@ -73,10 +403,12 @@ pub struct Parser {
     // role_registry: RoleRegistry, // TODO: Implement roles module
     /// Source directory for resolving relative paths in include directives
     source_dir: Option<PathBuf>,
+    /// Severity of title underlines shorter than the title
+    short_title_underline: ShortTitleUnderline,
 }
 
 impl Parser {
-    pub fn new(_config: &BuildConfig) -> Result<Self> {
+    pub fn new(config: &BuildConfig) -> Result<Self> {
         // Match directive names with hyphens (e.g., code-block, csv-table)
         let rst_directive_regex = Regex::new(r"^\s*\.\.\s+([\w-]+)::\s*(.*?)$")?;
         let cross_ref_regex = Regex::new(r":(\w+):`([^`]+)`")?;
@@ -89,6 +421,7 @@ impl Parser {
             directive_registry,
             // role_registry, // TODO: Implement roles module
             source_dir: None,
+            short_title_underline: config.short_title_underline,
         })
     }
 
@@ -101,8 +434,9 @@ impl Parser {
         let output_path = self.get_output_path(file_path)?;
         let mut document = Document::new(file_path.to_path_buf(), output_path);
 
-        // Set source modification time
-        document.source_mtime = utils::get_file_mtime(file_path)?;
+        // Set source modification time; sources given in memory have no file
+        document.source_mtime = utils::get_file_mtime(file_path)
+            .unwrap_or_else(|_| utils::reproducible_time(chrono::Utc::now()));
 
         // Determine file type and parse accordingly
         let extension = file_path
@@ -112,7 +446,20 @@ impl Parser {
 
         match extension {
             "rst" => {
-                document.content = self.parse_rst(content)?;
+                let (fields, body) = split_docinfo(content);
+                for (name, value) in fields {
+                    document
+                        .metadata
+                        .custom
+                        .insert(name, serde_json::Value::String(value));
+                }
+                let (parsed, includes) = self.parse_rst(file_path, &body)?;
+                document.content = parsed;
+                document.includes = includes;
+                if let DocumentContent::RestructuredText(rst) = &mut document.content {
+                    rst.raw = content.to_string();
+                    record_metadata(&rst.ast, &mut document.metadata);
+                }
             }
             "md" => {
                 document.content = self.parse_markdown(content)?;
@@ -140,31 +487,57 @@ impl Parser {
         Ok(document)
     }
 
-    fn parse_rst(&self, content: &str) -> Result<DocumentContent> {
+    /// Parse an RST document; returns its content and the files it includes
+    fn parse_rst(&self, file_path: &Path, content: &str) -> Result<(DocumentContent, Vec<PathBuf>)> {
         let mut nodes = Vec::new();
         let mut directives = Vec::new();
         let lines: Vec<&str> = content.lines().collect();
 
-        // Track underline characters in order of first appearance to determine title levels
-        // The first underline character encountered becomes level 1, second becomes level 2, etc.
-        let mut seen_underline_chars: Vec<char> = Vec::new();
-
-        self.parse_rst_lines(&lines, &mut nodes, &mut directives, &mut seen_underline_chars)?;
+        let mut state = RstParseState::new(file_path, Vec::new());
+        self.parse_rst_lines(&lines, &mut nodes, &mut directives, &mut state)?;
 
-        Ok(DocumentContent::RestructuredText(RstContent {
+        let mut includes = state.includes;
+        includes.sort();
+        includes.dedup();
+        let content = DocumentContent::RestructuredText(RstContent {
             raw: content.to_string(),
             ast: nodes,
             directives,
-        }))
+            title_styles: state.seen_underline_chars,
+            messages: state.messages,
+        });
+        Ok((content, includes))
     }
 
-    /// Parse RST lines with shared state for header levels (used for include expansion)
+    /// Parse RST that is part of a document, such as the content of a
+    /// directive, with the section levels of the document's `title_styles`.
+    /// Returns the nodes, numbered from line 1, and the files it includes.
+    /// Malformed markup is not reported, as the content is parsed whenever
+    /// it is rendered.
+    pub fn parse_nested(
+        &self,
+        file_path: &Path,
+        content: &str,
+        title_styles: &mut Vec<char>,
+    ) -> Result<(Vec<RstNode>, Vec<PathBuf>)> {
+        let mut nodes = Vec::new();
+        let mut directives = Vec::new();
+        let lines: Vec<&str> = content.lines().collect();
+
+        let mut state = RstParseState::new(file_path, std::mem::take(title_styles));
+        let parsed = self.parse_rst_lines(&lines, &mut nodes, &mut directives, &mut state);
+        *title_styles = state.seen_underline_chars;
+        parsed?;
+        Ok((nodes, state.includes))
+    }
+
+    /// Parse RST lines with state shared with included files
     fn parse_rst_lines(
         &self,
         lines: &[&str],
         nodes: &mut Vec<RstNode>,
         directives: &mut Vec<RstDirective>,
-        seen_underline_chars: &mut Vec<char>,
+        state: &mut RstParseState,
     ) -> Result<()> {
         let mut i = 0;
         while i < lines.len() {
@@ -182,16 +555,33 @@ impl Parser {
                 let directive_args = captures.get(2).unwrap().as_str();
 
                 let (directive, consumed_lines) =
-                    self.parse_rst_directive(&lines[i..], directive_name, directive_args, i + 1)?;
+                    self.parse_rst_directive(&lines[i..], directive_name, directive_args, i + 1, state)?;
+                if markup_ends_without_blank_line(lines, i + consumed_lines) {
+                    state.report(
+                        MessageLevel::Warning,
+                        i + consumed_lines + 1,
+                        format!("Explicit markup {}", UNEXPECTED_UNINDENT),
+                    );
+                }
 
                 // Handle include directive specially - expand it inline
                 if directive_name == "include" {
-                    if let Some(included_nodes) = self.expand_include_directive(&directive, seen_underline_chars) {
+                    if let Some(included_nodes) = self.expand_include_directive(&directive, i + 1, state)? {
                         nodes.extend(included_nodes);
                     }
                     i += consumed_lines;
                     continue;
                 }
+                if directive_name == "literalinclude" {
+                    if let Some(filename) = directive.args.first() {
+                        let including_file = state.file_stack.last().map(PathBuf::as_path);
+                        state.includes.push(resolve_include_path(
+                            filename,
+                            including_file,
+                            self.source_dir.as_deref(),
+                        ));
+                    }
+                }
 
                 directives.push(directive.clone());
                 nodes.push(RstNode::Directive {
@@ -210,7 +600,7 @@ impl Parser {
             // The overline must be all the same character, followed by title text, followed by matching underline
             if i + 2 < lines.len()
                 && !trimmed.is_empty()
-                && trimmed.chars().all(|c| "=-~^\"'*+#<>".contains(c))
+                && trimmed.chars().all(|c| ADORNMENT_CHARS.contains(c))
                 && trimmed.chars().next() == trimmed.chars().last()  // all same char
             {
                 let overline_char = trimmed.chars().next().unwrap();
@@ -231,11 +621,11 @@ impl Parser {
                     && underline_char_count >= title_char_count
                 {
                     // Determine level based on order of first appearance
-                    let level = if let Some(pos) = seen_underline_chars.iter().position(|&c| c == overline_char) {
+                    let level = if let Some(pos) = state.seen_underline_chars.iter().position(|&c| c == overline_char) {
                         pos + 1
                     } else {
-                        seen_underline_chars.push(overline_char);
-                        seen_underline_chars.len()
+                        state.seen_underline_chars.push(overline_char);
+                        state.seen_underline_chars.len()
                     };
 
                     nodes.push(RstNode::Title {
@@ -247,6 +637,13 @@ impl Parser {
                     i += 3; // Skip overline, title, and underline
                     continue;
                 }
+                if is_adornment(trimmed) && !title_line.is_empty() && is_adornment(underline) {
+                    if !underline.starts_with(overline_char) {
+                        state.report(MessageLevel::Error, i + 2, "Title overline & underline mismatch.");
+                    } else {
+                        state.report(MessageLevel::Error, i + 2, "Title overline too short.");
+                    }
+                }
             }
 
             // Check for title (underlined with =, -, ~, etc.)
@@ -257,17 +654,34 @@ impl Parser {
                 let title_char_count = trimmed.chars().count();
                 let underline_char_count = next_line.trim().chars().count();
 
-                if !next_line.trim().is_empty()
-                    && next_line.trim().chars().all(|c| "=-~^\"'*+#<>".contains(c))
-                    && underline_char_count >= title_char_count
+                let underline = next_line.trim();
+                let is_underline =
+                    !underline.is_empty() && underline.chars().all(|c| ADORNMENT_CHARS.contains(c));
+                // Sphinx accepts a short underline with a warning, as long
+                // as it is long enough not to be mistaken for text
+                let is_short_underline = is_underline
+                    && underline_char_count < title_char_count
+                    && !is_indented(line)
+                    && is_adornment(underline);
+                if is_underline && (underline_char_count >= title_char_count || is_short_underline)
                 {
+                    if is_short_underline {
+                        let level = match self.short_title_underline {
+                            ShortTitleUnderline::Ignore => None,
+                            ShortTitleUnderline::Warn => Some(MessageLevel::Warning),
+                            ShortTitleUnderline::Error => Some(MessageLevel::Error),
+                        };
+                        if let Some(level) = level {
+                            state.report(level, i + 2, "Title underline too short.");
+                        }
+                    }
                     let underline_char = next_line.trim().chars().next().unwrap();
                     // Determine level based on order of first appearance
-                    let level = if let Some(pos) = seen_underline_chars.iter().position(|&c| c == underline_char) {
+                    let level = if let Some(pos) = state.seen_underline_chars.iter().position(|&c| c == underline_char) {
                         pos + 1
                     } else {
-                        seen_underline_chars.push(underline_char);
-                        seen_underline_chars.len()
+                        state.seen_underline_chars.push(underline_char);
+                        state.seen_underline_chars.len()
                     };
 
                     nodes.push(RstNode::Title {
@@ -281,18 +695,6 @@ impl Parser {
                 }
             }
 
-            // Check for code block (indented text after ::)
-            if line.ends_with("::") {
-                let (code_content, consumed_lines) = self.parse_code_block(&lines[i + 1..]);
-                nodes.push(RstNode::CodeBlock {
-                    language: None,
-                    content: code_content,
-                    line: i + 1,
-                });
-                i += consumed_lines + 1;
-                continue;
-            }
-
             // Check for internal hyperlink target (.. _link-name:)
             if let Some(target_name) = self.parse_link_target(trimmed) {
                 nodes.push(RstNode::LinkTarget {
@@ -300,12 +702,40 @@ impl Parser {
                     line: i + 1,
                 });
                 i += 1;
+                if markup_ends_without_blank_line(lines, i) {
+                    state.report(MessageLevel::Warning, i + 1, format!("Explicit markup {}", UNEXPECTED_UNINDENT));
+                }
+                continue;
+            }
+
+            // Check for footnote or citation (.. [label] text)
+            if let Some((label, first_line)) = parse_note_start(trimmed) {
+                let (content, consumed_lines) = parse_note_body(first_line, &lines[i + 1..]);
+                let node = if NoteLabel::parse(label).is_some_and(|label| label.is_citation()) {
+                    RstNode::Citation {
+                        label: label.to_string(),
+                        content,
+                        line: i + 1,
+                    }
+                } else {
+                    RstNode::Footnote {
+                        label: label.to_string(),
+                        content,
+                        line: i + 1,
+                    }
+                };
+                nodes.push(node);
+                i += consumed_lines + 1;
+                if markup_ends_without_blank_line(lines, i) {
+                    state.report(MessageLevel::Warning, i + 1, format!("Explicit markup {}", UNEXPECTED_UNINDENT));
+                }
                 continue;
             }
 
             // Check for RST comment (lines starting with ".. " that aren't directives or link targets)
             // Comments can span multiple lines if subsequent lines are indented
             if trimmed.starts_with(".. ") {
+                let start = i;
                 i += 1;
                 // Skip any following indented lines that are part of the comment
                 while i < lines.len() {
@@ -316,6 +746,13 @@ impl Parser {
                         break;
                     }
                 }
+                // A target without the colon after its name reads as a comment
+                if trimmed.starts_with(".. _") && !lines[start..i].iter().any(|line| line.contains(':')) {
+                    state.report(MessageLevel::Warning, start + 1, "malformed hyperlink target.");
+                }
+                if markup_ends_without_blank_line(lines, i) {
+                    state.report(MessageLevel::Warning, i + 1, format!("Explicit markup {}", UNEXPECTED_UNINDENT));
+                }
                 continue;
             }
 
@@ -330,18 +767,32 @@ impl Parser {
                     });
                 }
                 i += consumed_lines;
+                if ends_without_blank_line(lines, i) {
+                    state.report(MessageLevel::Warning, i + 1, format!("Block quote {}", UNEXPECTED_UNINDENT));
+                }
                 continue;
             }
 
             // Check for bullet list (lines starting with "* " or "- ")
             if trimmed.starts_with("* ") || trimmed.starts_with("- ") {
-                let (items, consumed_lines) = self.parse_bullet_list(&lines[i..]);
+                let (mut items, consumed_lines) = self.parse_bullet_list(&lines[i..]);
+                let literal = items.last_mut().is_some_and(strip_item_literal_marker);
                 nodes.push(RstNode::List {
                     items,
                     ordered: false,
                     line: i + 1,
                 });
                 i += consumed_lines;
+                // Items with other whitespace after the bullet are not read
+                // as items yet, but don't end the list either
+                let more_items = lines.get(i).is_some_and(|line| BULLET_ITEM_RE.is_match(line));
+                if ends_without_blank_line(lines, i) && !more_items {
+                    state.report(MessageLevel::Warning, i + 1, format!("Bullet list {}", UNEXPECTED_UNINDENT));
+                }
+                // The last item's literal block is the indented block after it
+                if literal {
+                    i = self.parse_literal_block(lines, i, nodes, state);
+                }
                 continue;
             }
 
@@ -357,20 +808,42 @@ impl Parser {
                 if !next_line.trim().is_empty() && is_indented(next_line) {
                     // This is a definition list - parse the definition
                     let (def_content, def_consumed) = self.parse_blockquote(&lines[next_idx..]);
+                    let mut definition = def_content.trim().to_string();
+                    let literal = strip_item_literal_marker(&mut definition);
 
                     // Create definition list item
                     nodes.push(RstNode::DefinitionList {
                         items: vec![crate::document::DefinitionItem {
                             term: paragraph_content.clone(),
-                            definition: def_content.trim().to_string(),
+                            definition,
                         }],
                         line: i + 1,
                     });
                     i += para_consumed + def_consumed;
+                    // A term right after a definition starts the next item
+                    let next_term = lines.get(i + 1).is_some_and(|line| is_indented(line) && !line.trim().is_empty());
+                    if ends_without_blank_line(lines, i) && !next_term {
+                        state.report(MessageLevel::Warning, i + 1, format!("Definition list {}", UNEXPECTED_UNINDENT));
+                    }
+                    if literal {
+                        i = self.parse_literal_block(lines, i, nodes, state);
+                    }
                     continue;
                 }
             }
 
+            // A paragraph ending in `::` introduces a literal block
+            if let Some(text) = strip_literal_marker(&paragraph_content) {
+                if !text.is_empty() {
+                    nodes.push(RstNode::Paragraph {
+                        content: text.to_string(),
+                        line: i + 1,
+                    });
+                }
+                i = self.parse_literal_block(lines, next_idx, nodes, state);
+                continue;
+            }
+
             // Regular paragraph
             nodes.push(RstNode::Paragraph {
                 content: paragraph_content,
@@ -384,80 +857,128 @@ impl Parser {
 
     /// Expand an include directive by reading the file and parsing its content.
     /// Returns the parsed nodes, or None if the file cannot be read.
+    /// Parse the file of an include directive in place of the directive.
+    /// `:literal:` and `:code:` include it as a literal block instead.
+    /// Unreadable files are left out; a file that includes itself, directly
+    /// or through other files, is an error.
     fn expand_include_directive(
         &self,
         directive: &RstDirective,
-        seen_underline_chars: &mut Vec<char>,
-    ) -> Option<Vec<RstNode>> {
-        let filename = directive.args.first()?;
-
-        // Resolve the file path relative to source_dir
-        let file_path = if let Some(ref source_dir) = self.source_dir {
-            source_dir.join(filename)
-        } else {
-            PathBuf::from(filename)
-        };
-
-        // Read the file content
-        let content = match std::fs::read_to_string(&file_path) {
-            Ok(content) => content,
-            Err(_) => return None,
+        line: usize,
+        state: &mut RstParseState,
+    ) -> Result<Option<Vec<RstNode>>> {
+        let Some(filename) = directive.args.first() else {
+            return Ok(None);
         };
-
-        // Apply line-based filtering
-        let mut lines: Vec<&str> = content.lines().collect();
-
-        // Handle start-line option (0-based: skip first N lines, like Sphinx)
-        if let Some(start_line) = directive.options.get("start-line") {
-            if let Ok(start) = start_line.parse::<usize>() {
-                if start <= lines.len() {
-                    lines = lines[start..].to_vec();
-                }
-            }
-        }
-
-        // Handle end-line option (0-based, exclusive like Sphinx)
-        if let Some(end_line) = directive.options.get("end-line") {
-            if let Ok(end) = end_line.parse::<usize>() {
-                if end <= lines.len() {
-                    lines = lines[..end].to_vec();
-                }
-            }
+        let including_file = state.file_stack.last().map(PathBuf::as_path);
+        let file_path = resolve_include_path(filename, including_file, self.source_dir.as_deref());
+        state.includes.push(file_path.clone());
+
+        let canonical = canonical_path(&file_path);
+        let literal = directive.options.contains_key("literal") || directive.options.contains_key("code");
+        if !literal && state.file_stack.contains(&canonical) {
+            let chain: Vec<String> = state
+                .file_stack
+                .iter()
+                .chain(std::iter::once(&canonical))
+                .map(|path| self.display_path(path))
+                .collect();
+            anyhow::bail!("Circular include at line {}: {}", line, chain.join(" -> "));
         }
 
-        // Handle start-after option
-        if let Some(start_after) = directive.options.get("start-after") {
-            if let Some(pos) = lines.iter().position(|line| line.contains(start_after.as_str())) {
-                lines = lines[pos + 1..].to_vec();
-            }
-        }
+        let encoding = directive.options.get("encoding").map(String::as_str);
+        let Ok(content) = utils::read_text_file(&file_path, encoding) else {
+            return Ok(None);
+        };
 
-        // Handle end-before option
-        if let Some(end_before) = directive.options.get("end-before") {
-            if let Some(pos) = lines.iter().position(|line| line.contains(end_before.as_str())) {
-                lines = lines[..pos].to_vec();
-            }
+        let tab_width = directive
+            .options
+            .get("tab-width")
+            .and_then(|width| width.trim().parse::<i64>().ok())
+            .unwrap_or(DEFAULT_TAB_WIDTH);
+        let lines: Vec<String> = select_include_lines(&content, &directive.options)
+            .into_iter()
+            .map(|line| expand_tabs(line, tab_width))
+            .collect();
+
+        let literal_language = match directive.options.get("code") {
+            Some(language) => Some(Some(language.trim()).filter(|language| !language.is_empty())),
+            None if directive.options.contains_key("literal") => Some(None),
+            None => None,
+        };
+        if let Some(language) = literal_language {
+            return Ok(Some(vec![RstNode::CodeBlock {
+                language: language.map(String::from),
+                content: lines.join("\n"),
+                line,
+            }]));
         }
 
-        // Parse the included content with the shared seen_underline_chars
+        // Parse the included content with the shared title levels
         let mut included_nodes = Vec::new();
         let mut included_directives = Vec::new();
-        let lines_refs: Vec<&str> = lines.iter().map(|s| *s).collect();
+        let lines_refs: Vec<&str> = lines.iter().map(String::as_str).collect();
+        state.file_stack.push(canonical);
+        let parsed = self.parse_rst_lines(&lines_refs, &mut included_nodes, &mut included_directives, state);
+        state.file_stack.pop();
+        parsed?;
+        Ok(Some(included_nodes))
+    }
 
-        if self.parse_rst_lines(&lines_refs, &mut included_nodes, &mut included_directives, seen_underline_chars).is_ok() {
-            Some(included_nodes)
-        } else {
-            None
-        }
+    /// A path relative to the source directory when it is inside it
+    fn display_path(&self, path: &Path) -> String {
+        let relative = self
+            .source_dir
+            .as_deref()
+            .and_then(|dir| crate::paths::relative_to(path, dir));
+        crate::paths::to_slash(relative.as_deref().unwrap_or(path))
     }
 
     fn parse_markdown(&self, content: &str) -> Result<DocumentContent> {
         let mut nodes = Vec::new();
-        let parser = MarkdownParser::new(content);
+        let parser = MarkdownParser::new_ext(content, MarkdownOptions::ENABLE_TASKLISTS);
         let current_line = 1;
+        // Items of the list being parsed, nested lists flattened into it,
+        // with the depth of nesting
+        let mut list: Option<(bool, Vec<String>)> = None;
+        let mut list_depth = 0;
 
         for event in parser {
+            // Text in a list item belongs to the item, task markers included
+            if let Some((_, items)) = list.as_mut() {
+                if let Event::Start(Tag::Item) = event {
+                    items.push(String::new());
+                } else if let Some(item) = items.last_mut() {
+                    match &event {
+                        Event::TaskListMarker(checked) => item.push_str(if *checked { "[x] " } else { "[ ] " }),
+                        Event::Text(text) => item.push_str(text),
+                        Event::Code(code) => item.push_str(&format!("`{}`", code)),
+                        Event::SoftBreak | Event::HardBreak => item.push(' '),
+                        _ => {}
+                    }
+                }
+            }
+
             match event {
+                Event::Start(Tag::List(first)) => {
+                    list_depth += 1;
+                    if list.is_none() {
+                        list = Some((first.is_some(), Vec::new()));
+                    }
+                }
+                Event::End(TagEnd::List(_)) => {
+                    list_depth -= 1;
+                    if list_depth == 0 {
+                        if let Some((ordered, items)) = list.take() {
+                            nodes.push(MarkdownNode::List {
+                                items: items.into_iter().map(|item| item.trim().to_string()).collect(),
+                                ordered,
+                                line: current_line,
+                            });
+                        }
+                    }
+                }
+                _ if list.is_some() => {}
                 Event::Start(Tag::Heading { .. }) => {
                     // We'll handle this in the text event
                 }
@@ -499,64 +1020,89 @@ impl Parser {
         name: &str,
         args: &str,
         start_line: usize,
+        state: &mut RstParseState,
     ) -> Result<(RstDirective, usize)> {
         let mut options = HashMap::new();
-        let mut content = String::new();
         let mut consumed_lines = 1;
         let mut i = 1;
-
-        // Parse options (indented lines starting with :option:)
-        while i < lines.len() {
-            let line = lines[i];
-            if line.trim().is_empty() {
-                i += 1;
-                consumed_lines += 1;
-                continue;
-            }
-
-            let trimmed = line.trim_start();
-            if is_indented(line) && trimmed.starts_with(':') {
-                // This is an option line like "   :option: value"
-                if let Some(colon_pos) = trimmed[1..].find(':') {
-                    let option_name = &trimmed[1..colon_pos + 1];
-                    let option_value = trimmed[colon_pos + 2..].trim();
-                    options.insert(option_name.to_string(), option_value.to_string());
-                }
+        let mut option_indent = 0;
+        let mut last_option: Option<String> = None;
+
+        // Text right after the directive line continues its arguments, up
+        // to a blank line or the options
+        let mut args = args.to_string();
+        if !args.is_empty() {
+            while i < lines.len()
+                && get_indent(lines[i]) > 0
+                && !lines[i].trim().is_empty()
+                && lines[i].trim().strip_prefix(':').and_then(split_option_field).is_none()
+            {
+                args.push('\n');
+                args.push_str(lines[i].trim());
                 i += 1;
                 consumed_lines += 1;
-            } else if is_indented(line) {
-                // Indented but not an option - this is content
-                break;
-            } else {
-                // Not indented - end of directive
-                break;
             }
         }
 
-        // Parse content (indented lines)
+        // Options are a field list right after the directive line, at any
+        // indentation; lines indented deeper than an option continue its
+        // value, and options without one are empty
         while i < lines.len() {
             let line = lines[i];
-            if is_indented(line) {
-                content.push_str(strip_indent(line, MIN_INDENT));
-                content.push('\n');
-                i += 1;
-                consumed_lines += 1;
-            } else if line.trim().is_empty() {
-                content.push('\n');
-                i += 1;
-                consumed_lines += 1;
+            let indent = get_indent(line);
+            if line.trim().is_empty() || indent == 0 {
+                break;
+            }
+            let trimmed = line.trim();
+            let field = trimmed
+                .strip_prefix(':')
+                .filter(|_| last_option.is_none() || indent == option_indent)
+                .and_then(split_option_field);
+            if let Some((option_name, option_value)) = field {
+                options.insert(option_name.to_string(), option_value.to_string());
+                last_option = Some(option_name.to_string());
+                option_indent = indent;
+            } else if let Some(value) = last_option
+                .as_ref()
+                .filter(|_| indent > option_indent)
+                .and_then(|option| options.get_mut(option))
+            {
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(trimmed);
             } else {
                 break;
             }
+            i += 1;
+            consumed_lines += 1;
+        }
+
+        // docutils would read content right after the options as more of them
+        if !options.is_empty() && i < lines.len() && !lines[i].trim().is_empty() && get_indent(lines[i]) > 0 {
+            state.report(
+                MessageLevel::Warning,
+                start_line + i,
+                format!("\"{}\" directive missing blank line before content.", name),
+            );
+        }
+        while i < lines.len() && lines[i].trim().is_empty() {
+            i += 1;
+            consumed_lines += 1;
         }
 
+        // Content: the indented lines that follow, without the indentation
+        // they share, so deeper lines keep their relative indentation
+        let start = i;
+        while i < lines.len() && (lines[i].trim().is_empty() || get_indent(lines[i]) > 0) {
+            i += 1;
+            consumed_lines += 1;
+        }
+        let content = dedent(&lines[start..i]).join("\n");
+
         let directive = RstDirective {
             name: name.to_string(),
-            args: if args.is_empty() {
-                Vec::new()
-            } else {
-                vec![args.to_string()]
-            },
+            args: if args.is_empty() { Vec::new() } else { vec![args] },
             options,
             content: content.trim_end().to_string(),
             line: start_line,
@@ -566,21 +1112,70 @@ impl Parser {
     }
 
 
+    /// The indented literal block at the start of `lines`, without the
+    /// indentation its lines share, and the number of lines it takes up to
+    /// its last non-blank one
+    /// Parse the literal block introduced by a `::` that ends the text
+    /// before `start`: indented, or with each line starting with the same
+    /// punctuation. Returns the index of the line after it.
+    fn parse_literal_block(
+        &self,
+        lines: &[&str],
+        start: usize,
+        nodes: &mut Vec<RstNode>,
+        state: &mut RstParseState,
+    ) -> usize {
+        let mut i = start;
+        while i < lines.len() && lines[i].trim().is_empty() {
+            i += 1;
+        }
+        if let Some(quote) = lines.get(i).and_then(|line| line.chars().next()) {
+            if quote.is_ascii_punctuation() {
+                let first = i;
+                while i < lines.len() && lines[i].starts_with(quote) {
+                    i += 1;
+                }
+                nodes.push(RstNode::CodeBlock {
+                    language: None,
+                    content: lines[first..i].join("\n"),
+                    line: first + 1,
+                });
+                if i < lines.len() && !lines[i].trim().is_empty() {
+                    state.report(MessageLevel::Error, i + 1, "Inconsistent literal block quoting.");
+                }
+                return i;
+            }
+        }
+        if i == lines.len() || get_indent(lines[i]) == 0 {
+            state.report(MessageLevel::Warning, i + 1, "Literal block expected; none found.");
+            return i;
+        }
+        let (code_content, consumed_lines) = self.parse_code_block(&lines[i..]);
+        nodes.push(RstNode::CodeBlock {
+            language: None,
+            content: code_content,
+            line: i + 1,
+        });
+        i += consumed_lines;
+        if ends_without_blank_line(lines, i) {
+            state.report(MessageLevel::Warning, i + 1, format!("Literal block {}", UNEXPECTED_UNINDENT));
+        }
+        i
+    }
+
     fn parse_code_block(&self, lines: &[&str]) -> (String, usize) {
-        let mut content = String::new();
         let mut consumed_lines = 0;
-
-        for line in lines {
-            if is_indented(line) || line.trim().is_empty() {
-                content.push_str(line);
-                content.push('\n');
-                consumed_lines += 1;
-            } else {
+        for (index, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if get_indent(line) == 0 {
                 break;
             }
+            consumed_lines = index + 1;
         }
-
-        (content.trim().to_string(), consumed_lines)
+        let content: Vec<&str> = dedent(&lines[..consumed_lines]).into_iter().map(str::trim_end).collect();
+        (content.join("\n"), consumed_lines)
     }
 
     fn parse_paragraph(&self, lines: &[&str]) -> (String, usize) {
@@ -662,7 +1257,7 @@ impl Parser {
                 } else {
                     trimmed
                 };
-                current_item.push_str("\n");
+                current_item.push('\n');
                 current_item.push_str(content);
                 consumed_lines += 1;
             } else if trimmed.is_empty() {
@@ -706,6 +1301,13 @@ impl Parser {
         None
     }
 
+    /// Extract the title and table of contents again, after the document's
+    /// content changed
+    pub fn refresh_outline(&self, document: &mut Document) {
+        document.title = self.extract_title(&document.content);
+        document.toc = self.extract_toc(&document.content);
+    }
+
     fn extract_title(&self, content: &DocumentContent) -> String {
         match content {
             DocumentContent::RestructuredText(rst) => {
@@ -731,17 +1333,17 @@ impl Parser {
     }
 
     fn extract_toc(&self, content: &DocumentContent) -> Vec<TocEntry> {
-        use crate::renderer::{extract_plain_text_for_slug, slugify};
+        use crate::renderer::SlugRegistry;
 
         let mut toc = Vec::new();
+        let mut slugs = SlugRegistry::new();
 
         match content {
             DocumentContent::RestructuredText(rst) => {
                 for node in &rst.ast {
                     if let RstNode::Title { text, level, line } = node {
-                        // Use same slug generation as renderer for consistency
-                        let plain_text = extract_plain_text_for_slug(text);
-                        let anchor = slugify(&plain_text);
+                        // Same ids as the renderer gives the sections
+                        let anchor = slugs.heading_id(text);
                         toc.push(TocEntry::new(text.clone(), *level, anchor, *line));
                     }
                 }
@@ -749,8 +1351,7 @@ impl Parser {
             DocumentContent::Markdown(md) => {
                 for node in &md.ast {
                     if let MarkdownNode::Heading { text, level, line } = node {
-                        let plain_text = extract_plain_text_for_slug(text);
-                        let anchor = slugify(&plain_text);
+                        let anchor = slugs.heading_id(text);
                         toc.push(TocEntry::new(text.clone(), *level, anchor, *line));
                     }
                 }
@@ -1069,4 +1670,329 @@ List of authors.
             panic!("Expected RST content");
         }
     }
+
+    #[test]
+    fn test_docinfo_fields_become_metadata() {
+        let parser = create_parser();
+        let content = ":tocdepth: 2\n:orphan:\n\nTitle\n=====\n\n:ref:`intro` comes first.\n";
+        let doc = parse_rst_content(&parser, content);
+
+        assert_eq!(doc.metadata.custom["tocdepth"], "2");
+        assert_eq!(doc.metadata.custom["orphan"], "");
+        assert_eq!(doc.title, "Title");
+        if let crate::document::DocumentContent::RestructuredText(rst) = &doc.content {
+            assert_eq!(rst.raw, content);
+            assert!(matches!(rst.ast[0], RstNode::Title { line: 4, .. }));
+            assert!(matches!(&rst.ast[1], RstNode::Paragraph { content, .. } if content.starts_with(":ref:")));
+        } else {
+            panic!("Expected RST content");
+        }
+    }
+
+    #[test]
+    fn test_author_directives_become_metadata() {
+        let parser = create_parser();
+        let content = "Title\n=====\n\n.. sectionauthor:: Jane Doe <jane@example.org>\n\n.. moduleauthor:: Ravi Rao\n\n.. codeauthor:: Ravi Rao\n\n.. sectionauthor:: Jane Doe <jane@example.org>\n";
+        let doc = parse_rst_content(&parser, content);
+
+        assert_eq!(doc.metadata.authors, vec!["Jane Doe <jane@example.org>", "Ravi Rao"]);
+        assert_eq!(doc.metadata.code_authors, vec!["Ravi Rao"]);
+    }
+
+    #[test]
+    fn test_option_names_end_at_colon_and_whitespace() {
+        assert_eq!(super::split_option_field("property=og:title: A page"), Some(("property=og:title", "A page")));
+        assert_eq!(super::split_option_field("local:"), Some(("local", "")));
+        assert_eq!(super::split_option_field("target: https://example.org/"), Some(("target", "https://example.org/")));
+        assert_eq!(super::split_option_field("no marker"), None);
+    }
+
+    #[test]
+    fn test_directive_options_at_any_indentation() {
+        let parser = create_parser();
+        let content = "\
+.. code-block:: python
+  :caption: Step 1: Setup
+  :linenos:
+  :emphasize-lines: 1,
+     3
+
+  x = 1
+
+.. figure:: a
+   very long name.png
+   :alt: Logo
+
+   Caption.
+
+.. note::
+
+   :field: in the content
+";
+        let doc = parse_rst_content(&parser, content);
+        let DocumentContent::RestructuredText(rst) = &doc.content else {
+            panic!("not RST");
+        };
+        let code = &rst.directives[0];
+        assert_eq!(code.options["caption"], "Step 1: Setup");
+        assert_eq!(code.options["linenos"], "");
+        assert_eq!(code.options["emphasize-lines"], "1,\n3");
+        assert_eq!(code.options.len(), 3);
+
+        let figure = &rst.directives[1];
+        assert_eq!(figure.args, ["a\nvery long name.png"]);
+        assert_eq!(figure.options["alt"], "Logo");
+        assert_eq!(figure.content, "Caption.");
+
+        // Fields after a blank line are content
+        let note = &rst.directives[2];
+        assert!(note.options.is_empty());
+        assert_eq!(note.content, ":field: in the content");
+        assert_eq!(messages(&doc), []);
+    }
+
+    #[test]
+    fn test_directive_content_loses_shared_indentation() {
+        let parser = create_parser();
+        let content = "\
+.. note::
+
+    Four spaces.
+
+    .. code-block:: python
+
+        def f():
+            return 1
+
+.. code-block:: python
+\t:linenos:
+
+\tif x:
+\t    y()
+
+.. code-block:: text
+
+  two
+";
+        let doc = parse_rst_content(&parser, content);
+        let DocumentContent::RestructuredText(rst) = &doc.content else {
+            panic!("not RST");
+        };
+        assert_eq!(
+            rst.directives[0].content,
+            "Four spaces.\n\n.. code-block:: python\n\n    def f():\n        return 1"
+        );
+        assert_eq!(rst.directives[1].content, "if x:\n    y()");
+        assert_eq!(rst.directives[2].content, "two");
+
+        // The nested block keeps the indentation of its code
+        let note = parse_rst_content(&parser, &rst.directives[0].content);
+        let DocumentContent::RestructuredText(note) = &note.content else {
+            panic!("not RST");
+        };
+        assert_eq!(note.directives[0].content, "def f():\n    return 1");
+    }
+
+    fn messages(doc: &Document) -> Vec<(MessageLevel, usize, String)> {
+        let DocumentContent::RestructuredText(rst) = &doc.content else {
+            panic!("not RST");
+        };
+        rst.messages
+            .iter()
+            .map(|message| (message.level, message.line, message.message.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_malformed_markup_is_reported() {
+        let parser = create_parser();
+        let content = "\
+Long title
+====
+
+.. _broken
+
+.. image:: logo.png
+   :alt: Logo
+   Caption text.
+Text right after.
+
+- item
+text after the list
+
+======
+Title
+------
+
+Quoted
+
+   quote
+unindented
+
+.. _fine:
+.. _also-fine:
+
+Term
+   Definition
+Next term
+   Definition
+";
+        let doc = parse_rst_content(&parser, content);
+        assert_eq!(
+            messages(&doc),
+            [
+                (MessageLevel::Warning, 2, "Title underline too short.".to_string()),
+                (MessageLevel::Warning, 4, "malformed hyperlink target.".to_string()),
+                (
+                    MessageLevel::Warning,
+                    8,
+                    "\"image\" directive missing blank line before content.".to_string()
+                ),
+                (
+                    MessageLevel::Warning,
+                    9,
+                    "Explicit markup ends without a blank line; unexpected unindent.".to_string()
+                ),
+                (
+                    MessageLevel::Warning,
+                    12,
+                    "Bullet list ends without a blank line; unexpected unindent.".to_string()
+                ),
+                (MessageLevel::Error, 15, "Title overline & underline mismatch.".to_string()),
+                (
+                    MessageLevel::Warning,
+                    21,
+                    "Block quote ends without a blank line; unexpected unindent.".to_string()
+                ),
+            ]
+        );
+
+        // Well-formed documents have no messages, and neither do blocks
+        // indented by less than a directive's content
+        let content = "Title\n=====\n\n.. note::\n   Text\n\n- a\n- b\n\nOutput::\n\n {\n }\n";
+        assert_eq!(messages(&parse_rst_content(&parser, content)), []);
+    }
+
+    #[test]
+    fn test_short_title_underline_is_accepted() {
+        let parser = create_parser();
+        let doc = parse_rst_content(&parser, "A rather long title\n====\n\nText.\n");
+        assert_eq!(doc.title, "A rather long title");
+        assert_eq!(
+            messages(&doc),
+            [(MessageLevel::Warning, 2, "Title underline too short.".to_string())]
+        );
+
+        // Too short to tell from text: not a title
+        let doc = parse_rst_content(&parser, "Some text\n===\n\nMore.\n");
+        assert_eq!(doc.title, "Untitled");
+        assert_eq!(messages(&doc), []);
+
+        for (severity, expected) in [
+            (ShortTitleUnderline::Ignore, None),
+            (ShortTitleUnderline::Error, Some(MessageLevel::Error)),
+        ] {
+            let config = crate::config::BuildConfig {
+                short_title_underline: severity,
+                ..Default::default()
+            };
+            let parser = Parser::new(&config).unwrap();
+            let doc = parse_rst_content(&parser, "A rather long title\n-----\n");
+            assert_eq!(doc.title, "A rather long title");
+            assert_eq!(
+                messages(&doc).first().map(|(level, _, _)| *level),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_literal_blocks_after_double_colon() {
+        let parser = create_parser();
+        let blocks = |content: &str| {
+            let doc = parse_rst_content(&parser, content);
+            let DocumentContent::RestructuredText(rst) = &doc.content else {
+                panic!("not RST");
+            };
+            rst.ast
+                .iter()
+                .map(|node| match node {
+                    RstNode::Paragraph { content, .. } => format!("p: {}", content),
+                    RstNode::CodeBlock { content, .. } => format!("literal: {}", content),
+                    other => format!("{:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            blocks("First line\nsecond line::\n\n    code\n      more\n\nAfter.\n"),
+            ["p: First line second line:", "literal: code\n  more", "p: After."]
+        );
+        assert_eq!(blocks("Paragraph: ::\n\n  code\n"), ["p: Paragraph:", "literal: code"]);
+        assert_eq!(
+            blocks("Intro.\n\n::\n\n    code\n\n\n    more\n\nAfter.\n"),
+            ["p: Intro.", "literal: code\n\n\nmore", "p: After."]
+        );
+        assert_eq!(blocks("Not literal\\::\n\nText.\n"), ["p: Not literal\\::", "p: Text."]);
+        assert_eq!(
+            blocks("Session::\n\n> quoted\n>> more\n\nAfter.\n"),
+            ["p: Session:", "literal: > quoted\n>> more", "p: After."]
+        );
+        // List items and definitions introduce literal blocks too
+        let list = |items: &[&str]| {
+            let items = items.iter().map(|item| item.to_string()).collect();
+            format!("{:?}", RstNode::List { items, ordered: false, line: 1 })
+        };
+        assert_eq!(
+            blocks("- first
+- item::
+
+      code
+
+After.
+"),
+            [list(&["first", "item:"]), "literal: code".to_string(), "p: After.".to_string()]
+        );
+        let definitions = blocks("term
+   definition ::
+
+      code
+");
+        assert!(definitions[0].contains("definition: \"definition\""), "{:?}", definitions);
+        assert_eq!(definitions[1..], ["literal: code"]);
+
+        let doc = parse_rst_content(
+            &parser,
+            "Expect::\n\nNo block.\n\nText::\n\n    code\nafter\n\nQuoted::\n\n> a\n# b\n",
+        );
+        assert_eq!(
+            messages(&doc),
+            [
+                (MessageLevel::Warning, 3, "Literal block expected; none found.".to_string()),
+                (
+                    MessageLevel::Warning,
+                    8,
+                    "Literal block ends without a blank line; unexpected unindent.".to_string()
+                ),
+                (MessageLevel::Error, 13, "Inconsistent literal block quoting.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_messages_of_included_files_name_them() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("part.rst"), "Text\n\n- item\nunindented\n").unwrap();
+        let source = dir.path().join("index.rst");
+        let content = "Title\n=====\n\n.. include:: part.rst\n";
+        std::fs::write(&source, content).unwrap();
+
+        let doc = create_parser().parse(&source, content).unwrap();
+        let DocumentContent::RestructuredText(rst) = &doc.content else {
+            panic!("not RST");
+        };
+        assert_eq!(rst.messages.len(), 1);
+        assert_eq!(rst.messages[0].line, 4);
+        assert_eq!(rst.messages[0].source, Some(canonical_path(&dir.path().join("part.rst"))));
+    }
 }
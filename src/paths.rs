@@ -0,0 +1,163 @@
+//! Paths that behave the same on Windows and Unix: canonical paths without
+//! Windows' `\\?\` verbatim prefix, so they still strip against the paths
+//! users give, and `/`-separated names for document names and URLs.
+
+use std::path::{Path, PathBuf};
+
+/// `path` with symlinks and `..` resolved, like [`Path::canonicalize`], but
+/// in the plain form on Windows (`C:\docs`, `\\server\share\docs`) rather
+/// than the verbatim one (`\\?\C:\docs`), which no plain path is a prefix of
+pub fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
+    path.canonicalize().map(simplify)
+}
+
+/// Canonical form of `path`, or `path` itself when it doesn't exist
+pub fn canonical_or_self(path: &Path) -> PathBuf {
+    canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// `path` without the verbatim prefix on Windows; unchanged elsewhere
+pub fn simplify(path: PathBuf) -> PathBuf {
+    if !cfg!(windows) {
+        return path;
+    }
+    match path.to_str().and_then(strip_verbatim) {
+        Some(plain) => PathBuf::from(plain),
+        None => path,
+    }
+}
+
+/// The plain form of a verbatim drive or UNC path: `\\?\C:\x` is `C:\x` and
+/// `\\?\UNC\server\share\x` is `\\server\share\x`. Other verbatim paths,
+/// like device paths, have no plain form.
+fn strip_verbatim(path: &str) -> Option<String> {
+    if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        return Some(format!(r"\\{}", rest));
+    }
+    let rest = path.strip_prefix(r"\\?\")?;
+    let mut chars = rest.chars();
+    let is_drive = matches!(
+        (chars.next(), chars.next(), chars.next()),
+        (Some(letter), Some(':'), Some('\\') | None) if letter.is_ascii_alphabetic()
+    );
+    is_drive.then(|| rest.to_string())
+}
+
+/// `path` relative to `base`. When the paths don't share a prefix as given,
+/// say one is relative, holds `..`, goes through a symlink or is verbatim,
+/// their canonical forms are compared.
+pub fn relative_to(path: &Path, base: &Path) -> Option<PathBuf> {
+    if let Ok(relative) = path.strip_prefix(base) {
+        return Some(relative.to_path_buf());
+    }
+    let path = canonicalize(path).ok()?;
+    let base = canonicalize(base).ok()?;
+    path.strip_prefix(base).ok().map(Path::to_path_buf)
+}
+
+/// `path` with `/` separators, for document names, URLs and reports. Only
+/// Windows separates with `\`; elsewhere it is part of a file name.
+pub fn to_slash(path: &Path) -> String {
+    slashed(&path.to_string_lossy(), cfg!(windows))
+}
+
+/// `path` with `/` separators for glob matching. Here `\` counts as a
+/// separator on every platform, so Windows-style paths match the same
+/// patterns as their Unix forms.
+pub fn to_match_slash(path: &Path) -> String {
+    slashed(&path.to_string_lossy(), true)
+}
+
+fn slashed(path: &str, backslash_separates: bool) -> String {
+    if backslash_separates {
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    }
+}
+
+/// Document name of a source file, like `guide/intro`: its path relative to
+/// the source directory, without extension
+pub fn doc_name(source_path: &Path, source_dir: &Path) -> Option<String> {
+    relative_to(source_path, source_dir).map(|relative| to_slash(&relative.with_extension("")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_verbatim() {
+        assert_eq!(
+            strip_verbatim(r"\\?\C:\docs\index.rst").as_deref(),
+            Some(r"C:\docs\index.rst")
+        );
+        assert_eq!(strip_verbatim(r"\\?\d:").as_deref(), Some("d:"));
+        assert_eq!(
+            strip_verbatim(r"\\?\UNC\server\share\docs").as_deref(),
+            Some(r"\\server\share\docs")
+        );
+        assert_eq!(strip_verbatim(r"\\?\Volume{b75e2c83}\docs"), None);
+        assert_eq!(strip_verbatim(r"\\?\pipe"), None);
+        assert_eq!(strip_verbatim(r"C:\docs"), None);
+        assert_eq!(strip_verbatim(r"\\server\share"), None);
+    }
+
+    #[test]
+    fn test_slashed() {
+        assert_eq!(slashed(r"guide\intro.rst", true), "guide/intro.rst");
+        assert_eq!(slashed(r"\\server\share\docs", true), "//server/share/docs");
+        // A backslash is part of a Unix file name
+        assert_eq!(slashed(r"odd\name.rst", false), r"odd\name.rst");
+        assert_eq!(to_match_slash(Path::new(r"docs\api\module.rst")), "docs/api/module.rst");
+        assert_eq!(
+            to_slash(&Path::new("guide").join("intro.rst")),
+            "guide/intro.rst"
+        );
+    }
+
+    #[test]
+    fn test_relative_to_compares_canonical_forms() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = dir.path().join("docs");
+        std::fs::create_dir_all(docs.join("guide")).unwrap();
+        std::fs::write(docs.join("guide/intro.rst"), "").unwrap();
+        let file = docs.join("guide/intro.rst");
+
+        assert_eq!(
+            relative_to(&file, &docs),
+            Some(Path::new("guide").join("intro.rst"))
+        );
+        // A canonical file under a base given with `..`
+        let roundabout = docs.join("guide").join("..");
+        let canonical_file = canonicalize(&file).unwrap();
+        assert_eq!(
+            relative_to(&canonical_file, &roundabout),
+            Some(Path::new("guide").join("intro.rst"))
+        );
+        assert_eq!(
+            doc_name(&canonical_file, &roundabout).as_deref(),
+            Some("guide/intro")
+        );
+        assert_eq!(relative_to(&file, &dir.path().join("other")), None);
+
+        // Canonical paths are plain, so plain paths are their prefixes
+        assert!(!canonical_file.to_string_lossy().starts_with(r"\\?\"));
+        assert!(canonical_file.starts_with(canonicalize(dir.path()).unwrap()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_relative_to_through_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("real")).unwrap();
+        std::fs::write(dir.path().join("real/index.rst"), "").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("real"), dir.path().join("link")).unwrap();
+
+        let canonical_file = canonicalize(&dir.path().join("real/index.rst")).unwrap();
+        assert_eq!(
+            doc_name(&canonical_file, &dir.path().join("link")).as_deref(),
+            Some("index")
+        );
+    }
+}
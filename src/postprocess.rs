@@ -0,0 +1,333 @@
+//! Post-processing of rendered HTML pages.
+//!
+//! Every page passes through the registered [`HtmlPostProcessor`]s, in
+//! registration order, right before it is written. Extensions use this to
+//! transform the final markup: lazy-loading images, rewriting asset URLs for a
+//! CDN, injecting analytics snippets and so on.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::config::{ExternalLinksConfig, PostProcessingConfig};
+
+/// The page being post-processed
+#[derive(Debug, Clone, Copy)]
+pub struct RenderedPage<'a> {
+    /// Page name without extension, like `guide/intro`
+    pub doc_path: &'a str,
+
+    /// Source file, absent for generated pages such as `404`
+    pub source_path: Option<&'a Path>,
+
+    /// File the HTML is written to
+    pub output_path: &'a Path,
+}
+
+/// Transforms the final HTML of a page
+pub trait HtmlPostProcessor {
+    fn process(&self, page: &RenderedPage, html: String) -> Result<String>;
+    fn get_name(&self) -> &str;
+}
+
+/// Post-processors applied to every page, in registration order
+#[derive(Default)]
+pub struct PostProcessorRegistry {
+    processors: Vec<Box<dyn HtmlPostProcessor + Send + Sync>>,
+}
+
+impl PostProcessorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with the built-in processors enabled in the configuration
+    pub fn from_config(config: &PostProcessingConfig) -> Self {
+        let mut registry = Self::new();
+        if config.lazy_images {
+            registry.register(Box::new(LazyImages));
+        }
+        if config.external_links.is_active() {
+            registry.register(Box::new(ExternalLinks {
+                config: config.external_links.clone(),
+            }));
+        }
+        if config.head_html.is_some() || config.body_html.is_some() {
+            registry.register(Box::new(InjectSnippets {
+                head_html: config.head_html.clone().unwrap_or_default(),
+                body_html: config.body_html.clone().unwrap_or_default(),
+            }));
+        }
+        registry
+    }
+
+    pub fn register(&mut self, processor: Box<dyn HtmlPostProcessor + Send + Sync>) {
+        self.processors.push(processor);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    /// Run every processor over the page's HTML
+    pub fn apply(&self, page: &RenderedPage, html: String) -> Result<String> {
+        self.processors.iter().try_fold(html, |html, processor| {
+            processor.process(page, html).with_context(|| {
+                format!(
+                    "HTML post-processor '{}' failed on {}",
+                    processor.get_name(),
+                    page.doc_path
+                )
+            })
+        })
+    }
+}
+
+impl std::fmt::Debug for PostProcessorRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.processors.iter().map(|processor| processor.get_name()))
+            .finish()
+    }
+}
+
+/// Post-processor built from a closure
+pub struct FnPostProcessor<F> {
+    name: String,
+    function: F,
+}
+
+impl<F> FnPostProcessor<F>
+where
+    F: Fn(&RenderedPage, String) -> Result<String>,
+{
+    pub fn new(name: impl Into<String>, function: F) -> Self {
+        Self {
+            name: name.into(),
+            function,
+        }
+    }
+}
+
+impl<F> HtmlPostProcessor for FnPostProcessor<F>
+where
+    F: Fn(&RenderedPage, String) -> Result<String>,
+{
+    fn process(&self, page: &RenderedPage, html: String) -> Result<String> {
+        (self.function)(page, html)
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref IMG_TAG_RE: regex::Regex = regex::Regex::new(r"(?i)<img\b[^>]*>").unwrap();
+    static ref LOADING_ATTR_RE: regex::Regex = regex::Regex::new(r"(?i)\sloading\s*=").unwrap();
+    static ref LINK_RE: regex::Regex = regex::Regex::new(r"(?is)<a\b([^>]*)>(.*?)</a>").unwrap();
+    static ref CLASS_ATTR_RE: regex::Regex = regex::Regex::new(r#"(?i)\bclass\s*=\s*"([^"]*)""#).unwrap();
+    static ref HREF_ATTR_RE: regex::Regex = regex::Regex::new(r#"(?i)\bhref\s*=\s*"([^"]*)""#).unwrap();
+    static ref TARGET_ATTR_RE: regex::Regex = regex::Regex::new(r"(?i)\starget\s*=").unwrap();
+}
+
+/// Adds `loading="lazy"` to images that do not set a loading mode
+pub struct LazyImages;
+
+impl HtmlPostProcessor for LazyImages {
+    fn process(&self, _page: &RenderedPage, html: String) -> Result<String> {
+        Ok(IMG_TAG_RE
+            .replace_all(&html, |caps: &regex::Captures| {
+                let tag = &caps[0];
+                if LOADING_ATTR_RE.is_match(tag) {
+                    tag.to_string()
+                } else {
+                    format!("<img loading=\"lazy\"{}", &tag[4..])
+                }
+            })
+            .into_owned())
+    }
+
+    fn get_name(&self) -> &str {
+        "lazy-images"
+    }
+}
+
+/// Opens `reference external` links in a new tab, adds an icon class to
+/// them, and turns links with disallowed URL schemes into their text
+pub struct ExternalLinks {
+    pub config: ExternalLinksConfig,
+}
+
+impl ExternalLinks {
+    fn rewrite(&self, attributes: &str, content: &str) -> Option<String> {
+        let classes = CLASS_ATTR_RE.captures(attributes)?.get(1)?;
+        if !classes.as_str().split_whitespace().any(|class| class == "external") {
+            return None;
+        }
+        let href = HREF_ATTR_RE
+            .captures(attributes)
+            .and_then(|caps| caps.get(1))
+            .map(|href| href.as_str())
+            .unwrap_or("");
+        if let Some(scheme) = url_scheme(href) {
+            if !self.config.allows_scheme(&scheme) {
+                return Some(content.to_string());
+            }
+        }
+
+        let mut attributes = attributes.to_string();
+        if let Some(icon_class) = &self.config.icon_class {
+            attributes.insert_str(classes.end(), &format!(" {}", icon_class));
+        }
+        if self.config.new_tab && !TARGET_ATTR_RE.is_match(&attributes) {
+            attributes.push_str(" target=\"_blank\" rel=\"noopener\"");
+        }
+        Some(format!("<a{}>{}</a>", attributes, content))
+    }
+}
+
+impl HtmlPostProcessor for ExternalLinks {
+    fn process(&self, _page: &RenderedPage, html: String) -> Result<String> {
+        Ok(LINK_RE
+            .replace_all(&html, |caps: &regex::Captures| {
+                self.rewrite(&caps[1], &caps[2]).unwrap_or_else(|| caps[0].to_string())
+            })
+            .into_owned())
+    }
+
+    fn get_name(&self) -> &str {
+        "external-links"
+    }
+}
+
+/// Lowercased scheme of a URL in an attribute, read like browsers do:
+/// after decoding entities and dropping whitespace and control characters
+pub(crate) fn url_scheme(href: &str) -> Option<String> {
+    let decoded: String = html_escape::decode_html_entities(href)
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    let (scheme, _) = decoded.split_once(':')?;
+    let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+/// Inserts fixed snippets, such as analytics scripts, at the end of `<head>`
+/// and `<body>`
+pub struct InjectSnippets {
+    pub head_html: String,
+    pub body_html: String,
+}
+
+impl HtmlPostProcessor for InjectSnippets {
+    fn process(&self, _page: &RenderedPage, html: String) -> Result<String> {
+        let html = insert_before_last(html, "</head>", &self.head_html);
+        Ok(insert_before_last(html, "</body>", &self.body_html))
+    }
+
+    fn get_name(&self) -> &str {
+        "inject-snippets"
+    }
+}
+
+fn insert_before_last(mut html: String, closing_tag: &str, snippet: &str) -> String {
+    if !snippet.is_empty() {
+        if let Some(position) = html.rfind(closing_tag) {
+            html.insert_str(position, &format!("{}\n", snippet));
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page() -> RenderedPage<'static> {
+        RenderedPage {
+            doc_path: "guide/intro",
+            source_path: None,
+            output_path: Path::new("_build/guide/intro.html"),
+        }
+    }
+
+    #[test]
+    fn test_builtin_processors_from_config() {
+        let config = PostProcessingConfig {
+            lazy_images: true,
+            head_html: Some("<script src=\"analytics.js\"></script>".to_string()),
+            body_html: None,
+            ..Default::default()
+        };
+        let registry = PostProcessorRegistry::from_config(&config);
+        let html = "<html><head><title>T</title></head><body><img src=\"a.png\"><IMG loading=\"eager\" src=\"b.png\" /></body></html>";
+
+        let processed = registry.apply(&page(), html.to_string()).unwrap();
+        assert!(processed.contains("<img loading=\"lazy\" src=\"a.png\">"));
+        assert!(processed.contains("<IMG loading=\"eager\" src=\"b.png\" />"));
+        assert!(processed.contains("<script src=\"analytics.js\"></script>\n</head>"));
+        assert!(PostProcessorRegistry::from_config(&PostProcessingConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_external_links_open_in_new_tab_and_drop_denied_schemes() {
+        let config = PostProcessingConfig {
+            external_links: ExternalLinksConfig {
+                new_tab: true,
+                icon_class: Some("external-icon".to_string()),
+                denied_schemes: vec!["javascript".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let registry = PostProcessorRegistry::from_config(&config);
+        let html = concat!(
+            "<a class=\"reference external\" href=\"https://example.org/\">site</a> ",
+            "<a class=\"reference external\" href=\"java&#x09;script:alert(1)\">bad</a> ",
+            "<a class=\"reference internal\" href=\"#intro\">intro</a>"
+        );
+
+        let processed = registry.apply(&page(), html.to_string()).unwrap();
+        assert_eq!(
+            processed,
+            concat!(
+                "<a class=\"reference external external-icon\" href=\"https://example.org/\" target=\"_blank\" rel=\"noopener\">site</a> ",
+                "bad ",
+                "<a class=\"reference internal\" href=\"#intro\">intro</a>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_custom_processors_run_in_order() {
+        let mut registry = PostProcessorRegistry::new();
+        registry.register(Box::new(FnPostProcessor::new(
+            "cdn",
+            |_page: &RenderedPage, html: String| {
+                Ok(html.replace("src=\"_static/", "src=\"https://cdn.example.org/_static/"))
+            },
+        )));
+        registry.register(Box::new(FnPostProcessor::new(
+            "failing",
+            |page: &RenderedPage, html: String| {
+                if page.doc_path == "broken" {
+                    anyhow::bail!("cannot process");
+                }
+                Ok(html)
+            },
+        )));
+
+        let html = registry
+            .apply(&page(), "<script src=\"_static/app.js\"></script>".to_string())
+            .unwrap();
+        assert_eq!(html, "<script src=\"https://cdn.example.org/_static/app.js\"></script>");
+
+        let broken = RenderedPage {
+            doc_path: "broken",
+            ..page()
+        };
+        let error = registry.apply(&broken, html).unwrap_err();
+        assert_eq!(error.to_string(), "HTML post-processor 'failing' failed on broken");
+    }
+}
@@ -0,0 +1,118 @@
+//! Progress reporting for long builds.
+//!
+//! A [`ProgressReporter`] set on the builder hears when each build phase
+//! starts and how many documents of the parse and render phases are done.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// Receives the progress of a build. Calls come from the worker threads, in
+/// order, one at a time.
+pub trait ProgressReporter: Send + Sync {
+    /// A build phase started. `total` is its number of documents, or 0 for
+    /// phases that do not go document by document.
+    fn phase_started(&self, _phase: &str, _total: usize) {}
+
+    /// `done` of the `total` documents of the current phase are finished
+    fn documents_done(&self, _done: usize, _total: usize) {}
+
+    /// The build is over, successfully or not
+    fn finished(&self) {}
+}
+
+/// Current phase and document count of a build, forwarded to the reporter
+#[derive(Default)]
+pub struct ProgressTracker {
+    reporter: Option<Arc<dyn ProgressReporter>>,
+    state: Mutex<PhaseProgress>,
+}
+
+#[derive(Default)]
+struct PhaseProgress {
+    phase: &'static str,
+    done: usize,
+    total: usize,
+}
+
+impl ProgressTracker {
+    pub fn new(reporter: Arc<dyn ProgressReporter>) -> Self {
+        Self {
+            reporter: Some(reporter),
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn start_phase(&self, phase: &'static str, total: usize) {
+        let Some(reporter) = &self.reporter else {
+            return;
+        };
+        let mut state = self.state.lock();
+        *state = PhaseProgress { phase, done: 0, total };
+        reporter.phase_started(phase, total);
+    }
+
+    /// Count a finished document of `phase`. Documents of any other phase
+    /// than the current one are not counted.
+    pub fn document_done(&self, phase: &str) {
+        let Some(reporter) = &self.reporter else {
+            return;
+        };
+        let mut state = self.state.lock();
+        if state.phase != phase || state.done >= state.total {
+            return;
+        }
+        state.done += 1;
+        reporter.documents_done(state.done, state.total);
+    }
+
+    pub fn finish(&self) {
+        if let Some(reporter) = &self.reporter {
+            reporter.finished();
+        }
+    }
+}
+
+impl std::fmt::Debug for ProgressTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressTracker")
+            .field("reporting", &self.reporter.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl ProgressReporter for Recorder {
+        fn phase_started(&self, phase: &str, total: usize) {
+            self.0.lock().push(format!("{} ({})", phase, total));
+        }
+
+        fn documents_done(&self, done: usize, total: usize) {
+            self.0.lock().push(format!("{}/{}", done, total));
+        }
+    }
+
+    #[test]
+    fn test_only_documents_of_the_current_phase_count() {
+        let recorder = Arc::new(Recorder::default());
+        let tracker = ProgressTracker::new(recorder.clone());
+        tracker.start_phase("parse", 2);
+        tracker.document_done("parse");
+        tracker.document_done("parse");
+        tracker.document_done("parse");
+        tracker.start_phase("render", 2);
+        tracker.document_done("parse");
+        tracker.document_done("render");
+
+        assert_eq!(
+            *recorder.0.lock(),
+            ["parse (2)", "1/2", "2/2", "render (2)", "1/2"]
+        );
+        ProgressTracker::default().document_done("parse");
+    }
+}
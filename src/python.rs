@@ -0,0 +1,308 @@
+//! Python bindings, built with the `python` feature.
+//!
+//! The `sphinx_ultra` module exposes the builder to Python pipelines, so a
+//! build runs in-process and its warnings come back as objects:
+//!
+//! ```python
+//! import sphinx_ultra
+//!
+//! config = sphinx_ultra.BuildConfig.auto_detect("docs")
+//! builder = sphinx_ultra.SphinxBuilder("docs", "_build/html", config, jobs=8)
+//! stats = builder.build()
+//! for warning in stats.warnings:
+//!     print(warning.file, warning.line, warning.category, warning.message)
+//! ```
+
+use crate::builder::{BuildStats, SphinxBuilder};
+use crate::config::BuildConfig;
+use crate::error::{BuildErrorReport, BuildWarning};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use std::path::PathBuf;
+
+fn runtime_error(error: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{:#}", error))
+}
+
+/// Overlay `values` on `base`, merging nested dicts key by key
+fn merge_values(base: &mut serde_json::Value, values: serde_json::Value) {
+    match (base, values) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(values)) => {
+            for (key, value) in values {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+/// Build configuration
+#[pyclass(name = "BuildConfig", module = "sphinx_ultra")]
+#[derive(Clone)]
+pub struct PyBuildConfig {
+    inner: BuildConfig,
+}
+
+#[pymethods]
+impl PyBuildConfig {
+    /// The default configuration
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: BuildConfig::default(),
+        }
+    }
+
+    /// Load a YAML or JSON configuration file
+    #[staticmethod]
+    fn from_file(path: PathBuf) -> PyResult<Self> {
+        let inner = BuildConfig::from_file(path).map_err(runtime_error)?;
+        Ok(Self { inner })
+    }
+
+    /// Load the configuration of a source directory, from its conf.py or
+    /// sphinx-ultra configuration file
+    #[staticmethod]
+    fn auto_detect(source_dir: PathBuf) -> PyResult<Self> {
+        let inner = BuildConfig::auto_detect(source_dir).map_err(runtime_error)?;
+        Ok(Self { inner })
+    }
+
+    /// A configuration from a dict with the keys of the YAML configuration;
+    /// missing keys keep their defaults
+    #[staticmethod]
+    fn from_dict(values: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let values: serde_json::Value = pythonize::depythonize(values)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        let mut config = serde_json::to_value(BuildConfig::default())
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        merge_values(&mut config, values);
+        let inner = serde_json::from_value(config)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// The configuration as a dict
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        pythonize::pythonize(py, &self.inner).map_err(|error| PyValueError::new_err(error.to_string()))
+    }
+
+    #[getter]
+    fn project(&self) -> String {
+        self.inner.project.clone()
+    }
+
+    #[setter]
+    fn set_project(&mut self, project: String) {
+        self.inner.project = project;
+    }
+
+    #[getter]
+    fn parallel_jobs(&self) -> Option<usize> {
+        self.inner.parallel_jobs
+    }
+
+    #[setter]
+    fn set_parallel_jobs(&mut self, jobs: Option<usize>) {
+        self.inner.parallel_jobs = jobs;
+    }
+
+    #[getter]
+    fn fail_on_warning(&self) -> bool {
+        self.inner.fail_on_warning
+    }
+
+    #[setter]
+    fn set_fail_on_warning(&mut self, fail_on_warning: bool) {
+        self.inner.fail_on_warning = fail_on_warning;
+    }
+}
+
+/// A warning or error of a build
+#[pyclass(name = "Diagnostic", module = "sphinx_ultra", get_all, frozen)]
+#[derive(Clone)]
+pub struct PyDiagnostic {
+    file: PathBuf,
+    line: Option<usize>,
+    /// `warning` or `error`
+    severity: &'static str,
+    /// Category name, as used by `suppress_warnings`
+    category: &'static str,
+    message: String,
+    suggestions: Vec<String>,
+}
+
+#[pymethods]
+impl PyDiagnostic {
+    fn __repr__(&self) -> String {
+        let location = match self.line {
+            Some(line) => format!("{}:{}", self.file.display(), line),
+            None => self.file.display().to_string(),
+        };
+        format!("<Diagnostic {}: {}: {}>", location, self.severity.to_uppercase(), self.message)
+    }
+}
+
+impl From<&BuildWarning> for PyDiagnostic {
+    fn from(warning: &BuildWarning) -> Self {
+        Self {
+            file: warning.file.clone(),
+            line: warning.line,
+            severity: "warning",
+            category: warning.warning_type.category(),
+            message: warning.message.clone(),
+            suggestions: warning.suggestions.clone(),
+        }
+    }
+}
+
+impl From<&BuildErrorReport> for PyDiagnostic {
+    fn from(error: &BuildErrorReport) -> Self {
+        Self {
+            file: error.file.clone(),
+            line: error.line,
+            severity: "error",
+            category: error.error_type.category(),
+            message: error.message.clone(),
+            suggestions: Vec::new(),
+        }
+    }
+}
+
+/// Counts, timings and diagnostics of a finished build
+#[pyclass(name = "BuildStats", module = "sphinx_ultra", frozen)]
+pub struct PyBuildStats {
+    inner: BuildStats,
+}
+
+#[pymethods]
+impl PyBuildStats {
+    #[getter]
+    fn files_processed(&self) -> usize {
+        self.inner.files_processed
+    }
+
+    #[getter]
+    fn cache_hits(&self) -> usize {
+        self.inner.cache_hits
+    }
+
+    /// Build time in seconds
+    #[getter]
+    fn build_time(&self) -> f64 {
+        self.inner.build_time.as_secs_f64()
+    }
+
+    #[getter]
+    fn output_size_mb(&self) -> f64 {
+        self.inner.output_size_mb
+    }
+
+    #[getter]
+    fn warnings(&self) -> Vec<PyDiagnostic> {
+        self.inner.warning_details.iter().map(PyDiagnostic::from).collect()
+    }
+
+    #[getter]
+    fn errors(&self) -> Vec<PyDiagnostic> {
+        self.inner.error_details.iter().map(PyDiagnostic::from).collect()
+    }
+
+    /// Counts and per-phase timings as a dict, like `--stats-json`
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        pythonize::pythonize(py, &self.inner.to_json())
+            .map_err(|error| PyValueError::new_err(error.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<BuildStats {} files, {} warnings, {} errors in {:.2}s>",
+            self.inner.files_processed,
+            self.inner.warnings,
+            self.inner.errors,
+            self.inner.build_time.as_secs_f64()
+        )
+    }
+}
+
+/// Builder of a documentation project, reusable for any number of builds
+#[pyclass(name = "SphinxBuilder", module = "sphinx_ultra")]
+pub struct PySphinxBuilder {
+    inner: SphinxBuilder,
+    runtime: tokio::runtime::Runtime,
+}
+
+#[pymethods]
+impl PySphinxBuilder {
+    /// A builder of `source_dir` into `output_dir`, configured by `config`
+    /// or by the configuration found in the source directory
+    #[new]
+    #[pyo3(signature = (source_dir, output_dir, config=None, jobs=None, incremental=false))]
+    fn new(
+        source_dir: PathBuf,
+        output_dir: PathBuf,
+        config: Option<PyBuildConfig>,
+        jobs: Option<usize>,
+        incremental: bool,
+    ) -> PyResult<Self> {
+        let config = match config {
+            Some(config) => config.inner,
+            None => BuildConfig::auto_detect(&source_dir).map_err(runtime_error)?,
+        };
+        let mut inner = SphinxBuilder::new(config, source_dir, output_dir).map_err(runtime_error)?;
+        if let Some(jobs) = jobs {
+            inner.set_parallel_jobs(jobs);
+        }
+        if incremental {
+            inner.enable_incremental();
+        }
+        let runtime = tokio::runtime::Runtime::new()?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Build the project, without holding the GIL
+    fn build(&self, py: Python<'_>) -> PyResult<PyBuildStats> {
+        let inner = py
+            .detach(|| self.runtime.block_on(self.inner.build()))
+            .map_err(runtime_error)?;
+        Ok(PyBuildStats { inner })
+    }
+
+    /// Check the project without writing output; returns the diagnostics
+    fn lint(&self, py: Python<'_>) -> PyResult<Vec<PyDiagnostic>> {
+        let report = py
+            .detach(|| self.runtime.block_on(self.inner.lint()))
+            .map_err(runtime_error)?;
+        Ok(report
+            .errors
+            .iter()
+            .map(PyDiagnostic::from)
+            .chain(report.warnings.iter().map(PyDiagnostic::from))
+            .collect())
+    }
+
+    /// Build `content` as the source file at `path`, relative to the source
+    /// directory
+    fn set_source(&mut self, path: PathBuf, content: String) {
+        self.inner.set_source(path, content);
+    }
+
+    fn remove_source(&mut self, path: PathBuf) {
+        self.inner.remove_source(path);
+    }
+}
+
+#[pymodule]
+fn sphinx_ultra(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    module.add_class::<PyBuildConfig>()?;
+    module.add_class::<PyBuildStats>()?;
+    module.add_class::<PyDiagnostic>()?;
+    module.add_class::<PySphinxBuilder>()?;
+    Ok(())
+}
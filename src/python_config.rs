@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-use crate::config::BuildConfig;
+use crate::config::{BibtexStyle, BuildConfig};
 
 /// Python configuration parser that can execute conf.py files
 pub struct PythonConfigParser {
@@ -34,6 +34,8 @@ pub struct ConfPyConfig {
     // HTML output options
     pub html_theme: Option<String>,
     pub html_theme_options: HashMap<String, serde_json::Value>,
+    pub html_additional_pages: HashMap<String, serde_json::Value>,
+    pub html_redirects: HashMap<String, serde_json::Value>,
     pub html_title: Option<String>,
     pub html_short_title: Option<String>,
     pub html_logo: Option<String>,
@@ -48,6 +50,7 @@ pub struct ConfPyConfig {
     pub html_show_sourcelink: Option<bool>,
     pub html_sourcelink_suffix: Option<String>,
     pub html_use_opensearch: Option<String>,
+    pub html_last_updated_fmt: Option<String>,
     pub html_file_suffix: Option<String>,
     pub html_link_suffix: Option<String>,
     pub html_show_copyright: Option<bool>,
@@ -62,6 +65,25 @@ pub struct ConfPyConfig {
     pub html_scaled_image_link: Option<bool>,
     pub html_baseurl: Option<String>,
     pub html_codeblock_linenos_style: Option<String>,
+    pub html_permalinks: Option<bool>,
+    pub html_permalinks_icon: Option<String>,
+    pub smartquotes: Option<bool>,
+    pub smartquotes_action: Option<String>,
+    pub smartquotes_excludes: HashMap<String, serde_json::Value>,
+    pub highlight_language: Option<String>,
+    pub copybutton_prompt_text: Option<String>,
+    pub copybutton_prompt_is_regexp: Option<bool>,
+    pub copybutton_only_copy_prompt_lines: Option<bool>,
+    pub copybutton_selector: Option<String>,
+    pub mathjax_path: Option<String>,
+    pub bibtex_bibfiles: Vec<String>,
+    pub bibtex_default_style: Option<String>,
+    pub issues_github_path: Option<String>,
+    pub issues_default_group_project: Option<String>,
+    pub issues_uri: Option<String>,
+    pub issues_pr_uri: Option<String>,
+    pub issues_commit_uri: Option<String>,
+    pub issues_user_uri: Option<String>,
     pub html_math_renderer: Option<String>,
     pub html_math_renderer_options: HashMap<String, serde_json::Value>,
 
@@ -113,6 +135,23 @@ pub struct ConfPyConfig {
     pub needs_extensions: HashMap<String, String>,
     pub manpages_url: Option<String>,
     pub nitpicky: Option<bool>,
+    pub show_authors: Option<bool>,
+    pub suppress_warnings: Vec<String>,
+    pub spelling_lang: Option<String>,
+    pub spelling_word_list_filename: Option<String>,
+    pub spelling_ignore_acronyms: Option<bool>,
+    pub ogp_site_name: Option<String>,
+    pub ogp_site_url: Option<String>,
+    pub ogp_image: Option<String>,
+    pub ogp_image_alt: Option<String>,
+    pub ogp_description_length: Option<i32>,
+    pub ogp_social_cards: HashMap<String, serde_json::Value>,
+    pub notfound_context: HashMap<String, serde_json::Value>,
+    pub notfound_urls_prefix: Option<String>,
+    pub notfound_no_urls_prefix: Option<bool>,
+    pub smv_tag_whitelist: Option<String>,
+    pub smv_branch_whitelist: Option<String>,
+    pub smv_latest_version: Option<String>,
     pub nitpick_ignore: Vec<(String, String)>,
     pub nitpick_ignore_regex: Vec<(String, String)>,
     pub numfig: Option<bool>,
@@ -193,7 +232,7 @@ impl PythonConfigParser {
             }
 
             if in_multiline {
-                normalized.push_str(" ");
+                normalized.push(' ');
                 normalized.push_str(trimmed);
             } else {
                 if !normalized.is_empty() {
@@ -288,11 +327,86 @@ impl PythonConfigParser {
                     })
                     .collect();
                 return Some((key, serde_json::Value::Array(items)));
+            } else if value_str.starts_with('{') && value_str.ends_with('}') {
+                // Flat dict parsing: {'key': value, ...} with scalar or list values
+                let dict_content = &value_str[1..value_str.len() - 1];
+                let mut map = serde_json::Map::new();
+                for item in Self::split_top_level(dict_content) {
+                    let Some((item_key, item_value)) = item.split_once(':') else {
+                        continue;
+                    };
+                    if let serde_json::Value::String(item_key) = Self::parse_scalar(item_key) {
+                        map.insert(item_key, Self::parse_dict_value(item_value));
+                    }
+                }
+                return Some((key, serde_json::Value::Object(map)));
             }
         }
         None
     }
 
+    /// Parse a dict value: a list or tuple of scalars, or a scalar
+    fn parse_dict_value(value: &str) -> serde_json::Value {
+        let value = value.trim();
+        let items = value
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+            .or_else(|| value.strip_prefix('(').and_then(|rest| rest.strip_suffix(')')));
+        match items {
+            Some(items) => serde_json::Value::Array(Self::split_top_level(items).into_iter().map(Self::parse_scalar).collect()),
+            None => Self::parse_scalar(value),
+        }
+    }
+
+    /// Parse a scalar Python literal (string, bool, None or number)
+    fn parse_scalar(value: &str) -> serde_json::Value {
+        let value = Self::strip_string_prefix(value.trim());
+        if value.len() >= 2
+            && ((value.starts_with('"') && value.ends_with('"'))
+                || (value.starts_with('\'') && value.ends_with('\'')))
+        {
+            return serde_json::Value::String(value[1..value.len() - 1].to_string());
+        }
+        match value {
+            "True" => serde_json::Value::Bool(true),
+            "False" => serde_json::Value::Bool(false),
+            "None" => serde_json::Value::Null,
+            _ => {
+                if let Ok(num) = value.parse::<i64>() {
+                    serde_json::Value::Number(num.into())
+                } else if let Some(num) = value.parse::<f64>().ok().and_then(serde_json::Number::from_f64) {
+                    serde_json::Value::Number(num)
+                } else {
+                    serde_json::Value::String(value.to_string())
+                }
+            }
+        }
+    }
+
+    /// Split on commas that are not inside quotes or brackets
+    fn split_top_level(content: &str) -> Vec<&str> {
+        let mut items = Vec::new();
+        let mut depth = 0;
+        let mut quote = None;
+        let mut start = 0;
+        for (i, ch) in content.char_indices() {
+            match (quote, ch) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(ch),
+                (None, '[' | '(' | '{') => depth += 1,
+                (None, ']' | ')' | '}') => depth -= 1,
+                (None, ',') if depth == 0 => {
+                    items.push(&content[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        items.push(&content[start..]);
+        items.into_iter().filter(|item| !item.trim().is_empty()).collect()
+    }
+
     /// Extract configuration values from the parsed Python namespace
     fn extract_configuration(&self) -> Result<ConfPyConfig> {
         let mut config = ConfPyConfig::default();
@@ -370,6 +484,8 @@ impl PythonConfigParser {
         // Extract HTML output options
         config.html_theme = extract_string("html_theme");
         config.html_theme_options = extract_dict("html_theme_options");
+        config.html_additional_pages = extract_dict("html_additional_pages");
+        config.html_redirects = extract_dict("html_redirects");
         config.html_title = extract_string("html_title");
         config.html_short_title = extract_string("html_short_title");
         config.html_logo = extract_string("html_logo");
@@ -384,6 +500,7 @@ impl PythonConfigParser {
         config.html_show_sourcelink = extract_bool("html_show_sourcelink");
         config.html_sourcelink_suffix = extract_string("html_sourcelink_suffix");
         config.html_use_opensearch = extract_string("html_use_opensearch");
+        config.html_last_updated_fmt = extract_string("html_last_updated_fmt");
         config.html_file_suffix = extract_string("html_file_suffix");
         config.html_link_suffix = extract_string("html_link_suffix");
         config.html_show_copyright = extract_bool("html_show_copyright");
@@ -398,13 +515,53 @@ impl PythonConfigParser {
         config.html_scaled_image_link = extract_bool("html_scaled_image_link");
         config.html_baseurl = extract_string("html_baseurl");
         config.html_codeblock_linenos_style = extract_string("html_codeblock_linenos_style");
+        config.html_permalinks = extract_bool("html_permalinks");
+        config.html_permalinks_icon = extract_string("html_permalinks_icon");
+        config.smartquotes = extract_bool("smartquotes");
+        config.smartquotes_action = extract_string("smartquotes_action");
+        config.smartquotes_excludes = extract_dict("smartquotes_excludes");
+        config.highlight_language = extract_string("highlight_language");
+        config.copybutton_prompt_text = extract_string("copybutton_prompt_text");
+        config.copybutton_prompt_is_regexp = extract_bool("copybutton_prompt_is_regexp");
+        config.copybutton_only_copy_prompt_lines = extract_bool("copybutton_only_copy_prompt_lines");
+        config.copybutton_selector = extract_string("copybutton_selector");
+        config.mathjax_path = extract_string("mathjax_path");
+        config.bibtex_bibfiles = extract_string_list("bibtex_bibfiles");
+        config.bibtex_default_style = extract_string("bibtex_default_style");
+        config.issues_github_path = extract_string("issues_github_path");
+        config.issues_default_group_project = extract_string("issues_default_group_project");
+        config.issues_uri = extract_string("issues_uri");
+        config.issues_pr_uri = extract_string("issues_pr_uri");
+        config.issues_commit_uri = extract_string("issues_commit_uri");
+        config.issues_user_uri = extract_string("issues_user_uri");
         config.html_math_renderer = extract_string("html_math_renderer");
         config.html_math_renderer_options = extract_dict("html_math_renderer_options");
 
         // Extract build options
         config.needs_sphinx = extract_string("needs_sphinx");
         config.nitpicky = extract_bool("nitpicky");
+        config.show_authors = extract_bool("show_authors");
+        config.suppress_warnings = extract_string_list("suppress_warnings");
+        config.spelling_lang = extract_string("spelling_lang");
+        config.spelling_word_list_filename = extract_string("spelling_word_list_filename");
+        config.spelling_ignore_acronyms = extract_bool("spelling_ignore_acronyms");
+        config.ogp_site_name = extract_string("ogp_site_name");
+        config.ogp_site_url = extract_string("ogp_site_url");
+        config.ogp_image = extract_string("ogp_image");
+        config.ogp_image_alt = extract_string("ogp_image_alt");
+        config.ogp_description_length = extract_int("ogp_description_length");
+        config.ogp_social_cards = extract_dict("ogp_social_cards");
+        config.notfound_context = extract_dict("notfound_context");
+        config.notfound_urls_prefix = extract_string("notfound_urls_prefix");
+        config.notfound_no_urls_prefix = extract_bool("notfound_no_urls_prefix");
+        config.smv_tag_whitelist = extract_string("smv_tag_whitelist");
+        config.smv_branch_whitelist = extract_string("smv_branch_whitelist");
+        config.smv_latest_version = extract_string("smv_latest_version");
         config.numfig = extract_bool("numfig");
+        config.numfig_format = extract_dict("numfig_format")
+            .into_iter()
+            .filter_map(|(figtype, format)| Some((figtype, format.as_str()?.to_string())))
+            .collect();
         config.numfig_secnum_depth = extract_int("numfig_secnum_depth");
         config.math_number_all = extract_bool("math_number_all");
         config.math_eqref_format = extract_string("math_eqref_format");
@@ -450,6 +607,8 @@ impl PythonConfigParser {
                 | "gettext_compact"
                 | "html_theme"
                 | "html_theme_options"
+                | "html_additional_pages"
+                | "html_redirects"
                 | "html_title"
                 | "html_short_title"
                 | "html_logo"
@@ -464,6 +623,7 @@ impl PythonConfigParser {
                 | "html_show_sourcelink"
                 | "html_sourcelink_suffix"
                 | "html_use_opensearch"
+                | "html_last_updated_fmt"
                 | "html_file_suffix"
                 | "html_link_suffix"
                 | "html_show_copyright"
@@ -478,11 +638,48 @@ impl PythonConfigParser {
                 | "html_scaled_image_link"
                 | "html_baseurl"
                 | "html_codeblock_linenos_style"
+                | "html_permalinks"
+                | "html_permalinks_icon"
+                | "smartquotes"
+                | "smartquotes_action"
+                | "smartquotes_excludes"
+                | "highlight_language"
+                | "copybutton_prompt_text"
+                | "copybutton_prompt_is_regexp"
+                | "copybutton_only_copy_prompt_lines"
+                | "copybutton_selector"
+                | "mathjax_path"
+                | "bibtex_bibfiles"
+                | "bibtex_default_style"
+                | "issues_github_path"
+                | "issues_default_group_project"
+                | "issues_uri"
+                | "issues_pr_uri"
+                | "issues_commit_uri"
+                | "issues_user_uri"
                 | "html_math_renderer"
                 | "html_math_renderer_options"
                 | "needs_sphinx"
                 | "nitpicky"
+                | "show_authors"
+                | "suppress_warnings"
+                | "spelling_lang"
+                | "spelling_word_list_filename"
+                | "spelling_ignore_acronyms"
+                | "ogp_site_name"
+                | "ogp_site_url"
+                | "ogp_image"
+                | "ogp_image_alt"
+                | "ogp_description_length"
+                | "ogp_social_cards"
+                | "notfound_context"
+                | "notfound_urls_prefix"
+                | "notfound_no_urls_prefix"
+                | "smv_tag_whitelist"
+                | "smv_branch_whitelist"
+                | "smv_latest_version"
                 | "numfig"
+                | "numfig_format"
                 | "numfig_secnum_depth"
                 | "math_number_all"
                 | "math_eqref_format"
@@ -517,6 +714,8 @@ impl Default for ConfPyConfig {
             gettext_compact: Some(true),
             html_theme: Some("alabaster".to_string()),
             html_theme_options: HashMap::new(),
+            html_additional_pages: HashMap::new(),
+            html_redirects: HashMap::new(),
             html_title: None,
             html_short_title: None,
             html_logo: None,
@@ -531,6 +730,7 @@ impl Default for ConfPyConfig {
             html_show_sourcelink: Some(true),
             html_sourcelink_suffix: Some(".txt".to_string()),
             html_use_opensearch: None,
+            html_last_updated_fmt: None,
             html_file_suffix: Some(".html".to_string()),
             html_link_suffix: Some(".html".to_string()),
             html_show_copyright: Some(true),
@@ -545,6 +745,25 @@ impl Default for ConfPyConfig {
             html_scaled_image_link: Some(true),
             html_baseurl: None,
             html_codeblock_linenos_style: Some("table".to_string()),
+            html_permalinks: Some(true),
+            html_permalinks_icon: Some("¶".to_string()),
+            smartquotes: Some(true),
+            smartquotes_action: Some("qDe".to_string()),
+            smartquotes_excludes: HashMap::new(),
+            highlight_language: None,
+            copybutton_prompt_text: None,
+            copybutton_prompt_is_regexp: None,
+            copybutton_only_copy_prompt_lines: None,
+            copybutton_selector: None,
+            mathjax_path: None,
+            bibtex_bibfiles: Vec::new(),
+            bibtex_default_style: None,
+            issues_github_path: None,
+            issues_default_group_project: None,
+            issues_uri: None,
+            issues_pr_uri: None,
+            issues_commit_uri: None,
+            issues_user_uri: None,
             html_math_renderer: Some("mathjax".to_string()),
             html_math_renderer_options: HashMap::new(),
             latex_engine: Some("pdflatex".to_string()),
@@ -588,6 +807,23 @@ impl Default for ConfPyConfig {
             needs_extensions: HashMap::new(),
             manpages_url: None,
             nitpicky: Some(false),
+            show_authors: Some(false),
+            suppress_warnings: Vec::new(),
+            spelling_lang: None,
+            spelling_word_list_filename: None,
+            spelling_ignore_acronyms: None,
+            ogp_site_name: None,
+            ogp_site_url: None,
+            ogp_image: None,
+            ogp_image_alt: None,
+            ogp_description_length: None,
+            ogp_social_cards: HashMap::new(),
+            notfound_context: HashMap::new(),
+            notfound_urls_prefix: None,
+            notfound_no_urls_prefix: None,
+            smv_tag_whitelist: None,
+            smv_branch_whitelist: None,
+            smv_latest_version: None,
             nitpick_ignore: Vec::new(),
             nitpick_ignore_regex: Vec::new(),
             numfig: Some(false),
@@ -651,6 +887,24 @@ impl ConfPyConfig {
             config.output.html_theme = html_theme.clone();
             config.theme.name = html_theme.clone();
         }
+        if !self.html_theme_options.is_empty() {
+            config.theme.options = serde_json::Value::Object(
+                self.html_theme_options
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect(),
+            );
+        }
+        config.html_additional_pages = self
+            .html_additional_pages
+            .iter()
+            .filter_map(|(page, template)| Some((page.clone(), template.as_str()?.to_string())))
+            .collect();
+        config.redirects.pages = self
+            .html_redirects
+            .iter()
+            .filter_map(|(old, new)| Some((old.clone(), new.as_str()?.to_string())))
+            .collect();
         if let Some(html_title) = &self.html_title {
             config.html_title = Some(html_title.clone());
         }
@@ -683,9 +937,59 @@ impl ConfPyConfig {
         if let Some(html_use_index) = self.html_use_index {
             config.html_use_index = Some(html_use_index);
         }
+        if let Some(html_permalinks) = self.html_permalinks {
+            config.html_permalinks = Some(html_permalinks);
+        }
+        if let Some(icon) = &self.html_permalinks_icon {
+            config.html_permalinks_icon = Some(icon.clone());
+        }
+        if let Some(smartquotes) = self.smartquotes {
+            config.smartquotes = Some(smartquotes);
+        }
+        if let Some(action) = &self.smartquotes_action {
+            config.smartquotes_action = Some(action.clone());
+        }
+        // Sphinx's `default` tries Python and falls back to plain text,
+        // which guessing covers
+        if let Some(language) = &self.highlight_language {
+            config.highlight_language = Some(if language == "default" { "guess" } else { language }.to_string());
+        }
+        // Each list given replaces the default one
+        let excluded = |key: &str| -> Option<Vec<String>> {
+            self.smartquotes_excludes.get(key)?.as_array().map(|values| {
+                values.iter().filter_map(|value| value.as_str().map(str::to_string)).collect()
+            })
+        };
+        if let Some(languages) = excluded("languages") {
+            config.smartquotes_excludes.languages = languages;
+        }
+        if let Some(builders) = excluded("builders") {
+            config.smartquotes_excludes.builders = builders;
+        }
+        // sphinx-issues options; its {group}/{project} is our project and
+        // every role's own placeholder is our {id}
+        let issues_project = self.issues_default_group_project.as_ref().or(self.issues_github_path.as_ref());
+        if let Some(project) = issues_project {
+            config.issue_tracker.project = Some(project.clone());
+        }
+        let issues_template = |template: &Option<String>| {
+            template.as_ref().map(|template| {
+                ["{issue}", "{pr}", "{commit}", "{user}", "{n}"]
+                    .iter()
+                    .fold(template.replace("{group}/{project}", "{project}"), |template, placeholder| {
+                        template.replace(placeholder, "{id}")
+                    })
+            })
+        };
+        config.issue_tracker.issue_uri = issues_template(&self.issues_uri);
+        config.issue_tracker.pr_uri = issues_template(&self.issues_pr_uri);
+        config.issue_tracker.commit_uri = issues_template(&self.issues_commit_uri);
+        config.issue_tracker.user_uri = issues_template(&self.issues_user_uri);
         if let Some(html_use_opensearch) = &self.html_use_opensearch {
             config.html_use_opensearch = Some(!html_use_opensearch.is_empty());
         }
+        // Like Sphinx, pages only show a last-updated date when a format is set
+        config.html_last_updated_fmt = self.html_last_updated_fmt.clone();
         if let Some(html_last_updated_fmt) = &self.html_context.get("last_updated") {
             if let Some(fmt_str) = html_last_updated_fmt.as_str() {
                 config.html_last_updated_fmt = Some(fmt_str.to_string());
@@ -703,6 +1007,120 @@ impl ConfPyConfig {
         };
         config.exclude_patterns = self.exclude_patterns.clone();
 
+        config.suppress_warnings = self.suppress_warnings.clone();
+        config.show_authors = self.show_authors.unwrap_or(false);
+
+        // sphinxcontrib-spelling settings configure the built-in spell
+        // checker, which stays opt-in through `spelling.enabled`
+        if self.extensions.iter().any(|ext| ext == "sphinxcontrib.spelling") {
+            if let Some(lang) = &self.spelling_lang {
+                config.spelling.lang = lang.clone();
+            }
+            config.spelling.wordlist = self.spelling_word_list_filename.as_ref().map(PathBuf::from);
+            if let Some(ignore_acronyms) = self.spelling_ignore_acronyms {
+                config.spelling.ignore_acronyms = ignore_acronyms;
+            }
+        }
+
+        // sphinxext-opengraph settings; the tags are only emitted with the
+        // extension
+        config.opengraph.enabled = self.extensions.iter().any(|ext| ext == "sphinxext.opengraph");
+        config.html_baseurl = self.html_baseurl.clone();
+        config.opengraph.site_name = self.ogp_site_name.clone();
+        config.opengraph.site_url = self.ogp_site_url.clone();
+        config.opengraph.image = self.ogp_image.clone();
+        config.opengraph.image_alt = self.ogp_image_alt.clone();
+        if let Some(length) = self.ogp_description_length {
+            config.opengraph.description_length = length.max(0) as usize;
+        }
+        let social_cards = &mut config.opengraph.social_cards;
+        let card_setting = |key: &str| self.ogp_social_cards.get(key).and_then(|v| v.as_str());
+        social_cards.enabled = self
+            .ogp_social_cards
+            .get("enable")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        social_cards.font = card_setting("font").map(PathBuf::from);
+        social_cards.image = card_setting("image").map(String::from);
+        if let Some(color) = card_setting("text_color") {
+            social_cards.text_color = color.to_string();
+        }
+        if let Some(color) = card_setting("line_color") {
+            social_cards.line_color = color.to_string();
+        }
+
+        config.github_pages.enabled = self
+            .extensions
+            .iter()
+            .any(|ext| ext == "sphinx.ext.githubpages");
+
+        // sphinx-copybutton settings; unlike the built-in defaults, the
+        // extension strips no prompts unless told to
+        if self.extensions.iter().any(|ext| ext == "sphinx_copybutton") {
+            let copybutton = &mut config.copybutton;
+            copybutton.enabled = true;
+            let prompt = self.copybutton_prompt_text.clone().filter(|prompt| !prompt.is_empty());
+            copybutton.prompts = Vec::new();
+            if self.copybutton_prompt_is_regexp == Some(true) {
+                copybutton.prompt_regex = prompt;
+            } else {
+                copybutton.prompts.extend(prompt);
+            }
+            if let Some(only_prompt_lines) = self.copybutton_only_copy_prompt_lines {
+                copybutton.only_copy_prompt_lines = only_prompt_lines;
+            }
+            if let Some(selector) = &self.copybutton_selector {
+                copybutton.selector = selector.clone();
+            }
+        }
+        if let Some(path) = &self.mathjax_path {
+            config.mathjax_path = path.clone();
+        }
+
+        // sphinxcontrib-bibtex settings
+        config.bibtex_files = self.bibtex_bibfiles.clone();
+        match self.bibtex_default_style.as_deref() {
+            Some("unsrt") => config.bibtex_default_style = BibtexStyle::Unsrt,
+            Some("alpha") => config.bibtex_default_style = BibtexStyle::Alpha,
+            Some("unsrtalpha") => config.bibtex_default_style = BibtexStyle::Unsrtalpha,
+            _ => {}
+        }
+
+        // Numbered figures
+        config.numfig = self.numfig.unwrap_or(false);
+        for (figtype, format) in &self.numfig_format {
+            match figtype.as_str() {
+                "figure" => config.numfig_format.figure = format.clone(),
+                "table" => config.numfig_format.table = format.clone(),
+                "code-block" => config.numfig_format.code_block = format.clone(),
+                _ => {}
+            }
+        }
+
+        // sphinx-notfound-page settings; the page is only generated with
+        // the extension
+        config.not_found.enabled = self.extensions.iter().any(|ext| ext == "notfound.extension");
+        if let Some(title) = self.notfound_context.get("title").and_then(|v| v.as_str()) {
+            config.not_found.title = title.to_string();
+        }
+        if let Some(body) = self.notfound_context.get("body").and_then(|v| v.as_str()) {
+            config.not_found.body = body.to_string();
+        }
+        config.not_found.urls_prefix = if self.notfound_no_urls_prefix == Some(true) {
+            Some(String::new())
+        } else {
+            self.notfound_urls_prefix.clone()
+        };
+
+        // sphinx-multiversion settings
+        if let Some(pattern) = &self.smv_tag_whitelist {
+            config.versions.tag_pattern = pattern.clone();
+        }
+        if let Some(pattern) = &self.smv_branch_whitelist {
+            config.versions.branch_pattern = pattern.clone();
+        }
+        config.versions.latest = self.smv_latest_version.clone();
+
         config
     }
 }
@@ -751,4 +1169,240 @@ html_extra_path = ['extra_files', '.nojekyll']
         assert_eq!(build_config.html_extra_path[0], std::path::PathBuf::from("extra_files"));
         assert_eq!(build_config.html_extra_path[1], std::path::PathBuf::from(".nojekyll"));
     }
+
+    #[test]
+    fn test_suppress_warnings_converted_to_build_config() {
+        let conf_py_content = r#"
+project = 'Test'
+suppress_warnings = ['missing-alt-text', 'orphaned-document']
+"#;
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".py").unwrap();
+        temp_file.write_all(conf_py_content.as_bytes()).unwrap();
+
+        let mut parser = PythonConfigParser::new().unwrap();
+        let conf = parser.parse_conf_py(temp_file.path()).unwrap();
+        let build_config = conf.to_build_config();
+
+        assert_eq!(
+            build_config.suppress_warnings,
+            vec!["missing-alt-text".to_string(), "orphaned-document".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_spelling_settings_converted_to_build_config() {
+        let conf_py_content = r#"
+project = 'Test'
+extensions = ['sphinxcontrib.spelling']
+spelling_lang = 'en_GB'
+spelling_word_list_filename = 'wordlist.txt'
+spelling_ignore_acronyms = False
+"#;
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".py").unwrap();
+        temp_file.write_all(conf_py_content.as_bytes()).unwrap();
+
+        let mut parser = PythonConfigParser::new().unwrap();
+        let conf = parser.parse_conf_py(temp_file.path()).unwrap();
+        let build_config = conf.to_build_config();
+
+        assert!(!build_config.spelling.enabled);
+        assert_eq!(build_config.spelling.lang, "en_GB");
+        assert_eq!(
+            build_config.spelling.wordlist,
+            Some(std::path::PathBuf::from("wordlist.txt"))
+        );
+        assert!(!build_config.spelling.ignore_acronyms);
+    }
+
+    #[test]
+    fn test_copybutton_settings_converted_to_build_config() {
+        let conf_py_content = r#"
+project = 'Test'
+extensions = ['sphinx_copybutton']
+copybutton_prompt_text = r">>> |\.\.\. "
+copybutton_prompt_is_regexp = True
+copybutton_only_copy_prompt_lines = False
+"#;
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".py").unwrap();
+        temp_file.write_all(conf_py_content.as_bytes()).unwrap();
+
+        let mut parser = PythonConfigParser::new().unwrap();
+        let conf = parser.parse_conf_py(temp_file.path()).unwrap();
+        let build_config = conf.to_build_config();
+
+        assert!(build_config.copybutton.enabled);
+        assert!(build_config.copybutton.prompts.is_empty());
+        assert_eq!(build_config.copybutton.prompt_regex.as_deref(), Some(r">>> |\.\.\. "));
+        assert!(!build_config.copybutton.only_copy_prompt_lines);
+        assert_eq!(build_config.copybutton.selector, "div.highlight pre");
+    }
+
+    #[test]
+    fn test_bibtex_settings_converted_to_build_config() {
+        let conf_py_content = r#"
+project = 'Test'
+extensions = ['sphinxcontrib.bibtex']
+bibtex_bibfiles = ['refs.bib', 'more/other.bib']
+bibtex_default_style = 'unsrtalpha'
+"#;
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".py").unwrap();
+        temp_file.write_all(conf_py_content.as_bytes()).unwrap();
+
+        let mut parser = PythonConfigParser::new().unwrap();
+        let conf = parser.parse_conf_py(temp_file.path()).unwrap();
+        let build_config = conf.to_build_config();
+
+        assert_eq!(build_config.bibtex_files, vec!["refs.bib", "more/other.bib"]);
+        assert_eq!(build_config.bibtex_default_style, BibtexStyle::Unsrtalpha);
+    }
+
+    #[test]
+    fn test_numfig_settings_converted_to_build_config() {
+        let conf_py_content = r#"
+project = 'Test'
+numfig = True
+numfig_format = {'figure': 'Figure %s', 'section': 'Section %s'}
+"#;
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".py").unwrap();
+        temp_file.write_all(conf_py_content.as_bytes()).unwrap();
+
+        let mut parser = PythonConfigParser::new().unwrap();
+        let conf = parser.parse_conf_py(temp_file.path()).unwrap();
+        let build_config = conf.to_build_config();
+
+        assert!(build_config.numfig);
+        assert_eq!(build_config.numfig_format.figure, "Figure %s");
+        assert_eq!(build_config.numfig_format.table, "Table %s");
+    }
+
+    #[test]
+    fn test_opengraph_settings_converted_to_build_config() {
+        let conf_py_content = r#"
+project = 'Test'
+extensions = ['sphinxext.opengraph']
+html_baseurl = 'https://docs.example.org/'
+ogp_site_name = 'Example Docs'
+ogp_image = '_static/card.png'
+ogp_description_length = 120
+ogp_social_cards = {'enable': True, 'line_color': '#ff0000'}
+"#;
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".py").unwrap();
+        temp_file.write_all(conf_py_content.as_bytes()).unwrap();
+
+        let mut parser = PythonConfigParser::new().unwrap();
+        let conf = parser.parse_conf_py(temp_file.path()).unwrap();
+        let build_config = conf.to_build_config();
+
+        assert!(build_config.opengraph.enabled);
+        assert_eq!(build_config.html_baseurl.as_deref(), Some("https://docs.example.org/"));
+        assert_eq!(build_config.opengraph.site_name.as_deref(), Some("Example Docs"));
+        assert_eq!(build_config.opengraph.image.as_deref(), Some("_static/card.png"));
+        assert_eq!(build_config.opengraph.description_length, 120);
+        assert!(build_config.opengraph.enabled);
+        assert!(build_config.opengraph.social_cards.enabled);
+        assert_eq!(build_config.opengraph.social_cards.line_color, "#ff0000");
+        assert_eq!(build_config.opengraph.social_cards.text_color, "#4a4a4a");
+    }
+
+    #[test]
+    fn test_additional_pages_and_notfound_converted_to_build_config() {
+        let conf_py_content = r#"
+project = 'Test'
+extensions = ['notfound.extension']
+html_additional_pages = {'download': 'download.html'}
+html_redirects = {'setup': 'guide/install#linux'}
+notfound_context = {'title': 'Lost?', 'body': '<h1>Nothing here</h1>'}
+notfound_urls_prefix = '/docs/'
+show_authors = True
+html_permalinks_icon = '#'
+smartquotes_action = 'qe'
+highlight_language = 'default'
+smartquotes_excludes = {'languages': ['ja', 'zh']}
+issues_github_path = 'owner/repo'
+issues_pr_uri = 'https://git.example.com/{group}/{project}/-/merge_requests/{pr}'
+"#;
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".py").unwrap();
+        temp_file.write_all(conf_py_content.as_bytes()).unwrap();
+
+        let mut parser = PythonConfigParser::new().unwrap();
+        let conf = parser.parse_conf_py(temp_file.path()).unwrap();
+        let build_config = conf.to_build_config();
+
+        assert_eq!(
+            build_config.html_additional_pages.get("download").map(String::as_str),
+            Some("download.html")
+        );
+        assert!(build_config.not_found.enabled);
+        assert_eq!(build_config.not_found.title, "Lost?");
+        assert_eq!(build_config.not_found.body, "<h1>Nothing here</h1>");
+        assert_eq!(build_config.not_found.urls_prefix.as_deref(), Some("/docs/"));
+        assert_eq!(
+            build_config.redirects.pages.get("setup").map(String::as_str),
+            Some("guide/install#linux")
+        );
+        assert!(build_config.show_authors);
+        assert_eq!(build_config.html_permalinks, Some(true));
+        assert_eq!(build_config.html_permalinks_icon.as_deref(), Some("#"));
+        assert_eq!(build_config.smartquotes_action.as_deref(), Some("qe"));
+        assert_eq!(build_config.highlight_language.as_deref(), Some("guess"));
+        assert_eq!(build_config.smartquotes_excludes.languages, vec!["ja", "zh"]);
+        assert_eq!(build_config.smartquotes_excludes.builders, vec!["man", "text"]);
+        assert_eq!(build_config.issue_tracker.project.as_deref(), Some("owner/repo"));
+        assert_eq!(
+            build_config.issue_tracker.uri_template("pr").as_deref(),
+            Some("https://git.example.com/{project}/-/merge_requests/{id}")
+        );
+        assert_eq!(
+            build_config.issue_tracker.uri_template("issue").as_deref(),
+            Some("https://github.com/{project}/issues/{id}")
+        );
+    }
+
+    #[test]
+    fn test_last_updated_format_only_when_configured() {
+        let mut parser = PythonConfigParser::new().unwrap();
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".py").unwrap();
+        temp_file.write_all(b"project = 'Test'\n").unwrap();
+        let conf = parser.parse_conf_py(temp_file.path()).unwrap();
+        assert_eq!(conf.to_build_config().html_last_updated_fmt, None);
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".py").unwrap();
+        temp_file.write_all(b"project = 'Test'\nhtml_last_updated_fmt = '%Y-%m-%d'\n").unwrap();
+        let conf = parser.parse_conf_py(temp_file.path()).unwrap();
+        assert_eq!(
+            conf.to_build_config().html_last_updated_fmt.as_deref(),
+            Some("%Y-%m-%d")
+        );
+    }
+
+    #[test]
+    fn test_html_theme_options_converted_to_build_config() {
+        let conf_py_content = r#"
+project = 'Test'
+html_theme_options = {
+    'navigation_depth': 2,
+    'collapse_navigation': False,
+    'style_nav_header_background': '#343131',
+}
+"#;
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".py").unwrap();
+        temp_file.write_all(conf_py_content.as_bytes()).unwrap();
+
+        let mut parser = PythonConfigParser::new().unwrap();
+        let conf = parser.parse_conf_py(temp_file.path()).unwrap();
+        let build_config = conf.to_build_config();
+
+        assert_eq!(build_config.theme.options["navigation_depth"], 2);
+        assert_eq!(build_config.theme.options["collapse_navigation"], false);
+        assert_eq!(build_config.theme.options["style_nav_header_background"], "#343131");
+    }
 }
@@ -0,0 +1,165 @@
+//! Redirects from the old paths of moved or removed pages.
+//!
+//! Each redirect gets a stub page at its old path that sends browsers on
+//! with a meta refresh and search engines with a canonical link. Hosts that
+//! support server-side redirects can use a Netlify-style `_redirects` file or
+//! an nginx `map` include instead.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Server-side redirect files written next to the stubs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedirectMapFormat {
+    /// `_redirects`, read by Netlify and Cloudflare Pages
+    Netlify,
+    /// `redirects.map`, for `map $uri $redirect_uri { include redirects.map; }`
+    Nginx,
+}
+
+impl RedirectMapFormat {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            RedirectMapFormat::Netlify => "_redirects",
+            RedirectMapFormat::Nginx => "redirects.map",
+        }
+    }
+}
+
+/// A redirect from the page `from` to `to`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// Document name of the old page, like `guide/setup`
+    pub from: String,
+    /// Target as configured: a document name with an optional `#anchor`, or
+    /// an absolute URL
+    pub to: String,
+}
+
+impl Redirect {
+    /// A redirect from a configured pair; a `.html` suffix on either side is
+    /// optional. The old page must be a path below the output root: `.` and
+    /// `..` components, backslashes and drive letters are rejected.
+    pub fn new(from: &str, to: &str) -> Result<Self> {
+        let from = from.trim_matches('/');
+        let from = from.strip_suffix(".html").unwrap_or(from);
+        if from.is_empty()
+            || from.contains(['\\', ':'])
+            || from.split('/').any(|part| part == "." || part == "..")
+        {
+            bail!("redirect from '{}' is not a page path below the output directory", from);
+        }
+        Ok(Self {
+            from: from.to_string(),
+            to: to.trim_start_matches('/').to_string(),
+        })
+    }
+
+    pub fn is_external(&self) -> bool {
+        self.to.contains("://")
+    }
+
+    /// Document name of an internal target
+    pub fn target_doc(&self) -> Option<&str> {
+        if self.is_external() {
+            return None;
+        }
+        let doc = self.to.split('#').next().unwrap_or_default();
+        Some(doc.strip_suffix(".html").unwrap_or(doc))
+    }
+
+    /// Path of the target from the output root, like `guide/install.html#linux`
+    pub fn target_path(&self) -> String {
+        let (doc, anchor) = match self.to.split_once('#') {
+            Some((doc, anchor)) => (doc, Some(anchor)),
+            None => (self.to.as_str(), None),
+        };
+        let doc = doc.strip_suffix(".html").unwrap_or(doc);
+        match anchor {
+            Some(anchor) => format!("{}.html#{}", doc, anchor),
+            None => format!("{}.html", doc),
+        }
+    }
+
+    /// URL of the target as linked from the stub at the old path
+    pub fn stub_url(&self) -> String {
+        if self.is_external() {
+            return self.to.clone();
+        }
+        let depth = self.from.matches('/').count();
+        format!("{}{}", "../".repeat(depth), self.target_path())
+    }
+
+    /// URL of the target below `prefix`, the path the site is served at
+    fn absolute_url(&self, prefix: &str) -> String {
+        if self.is_external() {
+            self.to.clone()
+        } else {
+            format!("{}{}", prefix, self.target_path())
+        }
+    }
+}
+
+/// A page that sends visitors on to `url`; `canonical` is the absolute URL
+/// of the target when the site's base URL is known
+pub fn stub_html(url: &str, canonical: Option<&str>, title: &str) -> String {
+    let url = html_escape::encode_double_quoted_attribute(url);
+    let canonical = html_escape::encode_double_quoted_attribute(canonical.unwrap_or(&url)).into_owned();
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n<meta http-equiv=\"refresh\" content=\"0; url={0}\" />\n<link rel=\"canonical\" href=\"{1}\" />\n</head>\n<body><a href=\"{0}\">{2}</a></body>\n</html>\n",
+        url,
+        canonical,
+        html_escape::encode_text(title)
+    )
+}
+
+/// Content of the server-side redirect file in `format`, for a site served
+/// at `prefix` (like `/` or `/docs/`)
+pub fn map_file(format: RedirectMapFormat, redirects: &[Redirect], prefix: &str) -> String {
+    let mut content = String::new();
+    for redirect in redirects {
+        let from = format!("{}{}.html", prefix, redirect.from);
+        let to = redirect.absolute_url(prefix);
+        match format {
+            RedirectMapFormat::Netlify => content.push_str(&format!("{} {} 301\n", from, to)),
+            RedirectMapFormat::Nginx => content.push_str(&format!("{} {};\n", from, to)),
+        }
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redirect_urls_and_map_files() {
+        let redirects = [
+            Redirect::new("guide/setup.html", "install#linux").unwrap(),
+            Redirect::new("/faq", "https://example.org/faq/").unwrap(),
+        ];
+        assert_eq!(redirects[0].from, "guide/setup");
+        assert_eq!(redirects[0].target_doc(), Some("install"));
+        assert_eq!(redirects[0].stub_url(), "../install.html#linux");
+        assert_eq!(redirects[1].target_doc(), None);
+        assert_eq!(redirects[1].stub_url(), "https://example.org/faq/");
+
+        assert_eq!(
+            map_file(RedirectMapFormat::Netlify, &redirects, "/docs/"),
+            "/docs/guide/setup.html /docs/install.html#linux 301\n/docs/faq.html https://example.org/faq/ 301\n"
+        );
+        assert_eq!(
+            map_file(RedirectMapFormat::Nginx, &redirects[..1], "/"),
+            "/guide/setup.html /install.html#linux;\n"
+        );
+
+        for outside in ["../../x", "guide/../../x", "./x", "C:/x", r"..\x", ""] {
+            assert!(Redirect::new(outside, "install").is_err(), "{}", outside);
+        }
+
+        let stub = stub_html("../install.html#linux", Some("https://example.org/install.html"), "Install");
+        assert!(stub.contains(r#"content="0; url=../install.html#linux""#));
+        assert!(stub.contains(r#"<link rel="canonical" href="https://example.org/install.html" />"#));
+    }
+}
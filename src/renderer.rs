@@ -1,33 +1,300 @@
 //! AST-to-HTML renderer for RST and Markdown documents.
 
-use crate::config::BuildConfig;
-use crate::directives::{Directive, DirectiveRegistry};
+use crate::assets::{Asset, PageAssets, PageFeature};
+use crate::code_objects::{self, CodeLanguage};
+use crate::config::{BuildConfig, CustomDirectiveConfig, CustomRoleConfig, FootnoteConfig, IssueTrackerConfig};
+use crate::directives::{raw_format_matches, Directive, DirectiveRegistry};
 use crate::document::{DocumentContent, MarkdownContent, MarkdownNode, RstContent, RstNode};
-use crate::parser::Parser;
+use crate::error::WarningType;
+use crate::bibtex::Bibliography;
+use crate::environment::BuildEnvironment;
+use crate::footnotes::{FootnotePlan, FootnoteReferences};
+use crate::genindex;
+use crate::inline::{self, Inline, ReferenceTarget, Syntax};
+use crate::intern::Symbol;
+use crate::parser::{expand_tabs, resolve_include_path, select_include_lines, Parser, DEFAULT_TAB_WIDTH};
+use crate::navigation::{
+    is_external, numbered_depth, relative_uri, resolve_docname, resolve_toctree_target, split_toctree_entry,
+};
 use crate::roles::{Role, RoleRegistry};
+use crate::smartquotes::SmartQuotes;
+use crate::utils;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io;
 use std::path::PathBuf;
+use parking_lot::Mutex;
+use std::sync::Arc;
 use syntect::highlighting::ThemeSet;
-use syntect::html::highlighted_html_for_string;
-use syntect::parsing::SyntaxSet;
+use syntect::html::{
+    css_for_theme_with_class_style, highlighted_html_for_string, ClassStyle, ClassedHTMLGenerator,
+};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+use unicode_normalization::UnicodeNormalization;
+
+mod config_schema;
+pub mod design;
+mod figlist;
+mod math;
+mod notes;
+pub mod tasklist;
+
+lazy_static::lazy_static! {
+    /// Syntax definitions, loaded once and shared by every renderer
+    pub(crate) static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    /// Highlighting themes, loaded once and shared by every renderer
+    pub(crate) static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+
+    static ref SPAN_TAG_RE: Regex = Regex::new(r"<span[^>]*>|</span>").unwrap();
+
+    /// Environment of renderers outside a build, with no documents
+    static ref EMPTY_ENVIRONMENT: Arc<BuildEnvironment> = Arc::default();
+}
+
+/// Classes used by strict CSP highlighting, `hl-` prefixed to avoid clashing
+/// with theme styles (the block itself is `<pre class="hl-code">`)
+const HIGHLIGHT_CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// Highlight language that stands for the one guessed from each block
+pub const GUESS_LANGUAGE: &str = "guess";
+
+/// Highlight `code` into a `<pre>` block styled by classes from
+/// [`HtmlRenderer::highlight_css`] rather than inline styles
+pub(crate) fn highlighted_html_with_classes(
+    code: &str,
+    syntax_set: &SyntaxSet,
+    syntax: &SyntaxReference,
+) -> Result<String, syntect::Error> {
+    let mut generator =
+        ClassedHTMLGenerator::new_with_class_style(syntax, syntax_set, HIGHLIGHT_CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator.parse_html_for_line_which_includes_newline(line)?;
+    }
+    Ok(format!("<pre class=\"hl-code\">{}</pre>", generator.finalize()))
+}
+
+/// Prefix the lines of a highlighted `<pre>` block with
+/// `<span class="linenos">` numbers counting from `first_number`, and wrap
+/// the 0-based `emphasized` lines in `hll_open` ... `</span>`. Token spans
+/// that continue across a line are closed and reopened around the added
+/// markup so it stays nested.
+pub(crate) fn decorate_code_lines(
+    highlighted: &str,
+    first_number: Option<usize>,
+    emphasized: &std::collections::BTreeSet<usize>,
+    hll_open: &str,
+) -> String {
+    let (Some(body_start), Some(body_end)) = (
+        highlighted.find('>').map(|end| end + 1),
+        highlighted.rfind("</pre>"),
+    ) else {
+        return highlighted.to_string();
+    };
+    // A newline right after `<pre>` is not part of the content
+    let body_start = if highlighted[body_start..].starts_with('\n') {
+        body_start + 1
+    } else {
+        body_start
+    };
+    let body = &highlighted[body_start..body_end];
+    let number_width = first_number
+        .map(|first| (first + body.lines().count()).saturating_sub(1).to_string().len())
+        .unwrap_or(0);
+
+    let mut html = String::from(&highlighted[..body_start]);
+    let mut open_spans: Vec<&str> = Vec::new();
+    for (index, code_line) in body.split_inclusive('\n').enumerate() {
+        let emphasize = emphasized.contains(&index);
+        if first_number.is_some() || emphasize {
+            html.push_str(&"</span>".repeat(open_spans.len()));
+            if let Some(first) = first_number {
+                html.push_str(&format!(
+                    "<span class=\"linenos\">{:>width$}</span>",
+                    first + index,
+                    width = number_width
+                ));
+            }
+            if emphasize {
+                html.push_str(hll_open);
+            }
+            html.extend(open_spans.iter().copied());
+        }
+        let (text, newline) = match code_line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (code_line, ""),
+        };
+        html.push_str(text);
+        for tag in SPAN_TAG_RE.find_iter(text) {
+            if tag.as_str() == "</span>" {
+                open_spans.pop();
+            } else {
+                open_spans.push(tag.as_str());
+            }
+        }
+        if emphasize {
+            html.push_str(&"</span>".repeat(open_spans.len()));
+            html.push_str("</span>");
+            html.extend(open_spans.iter().copied());
+        }
+        html.push_str(newline);
+    }
+    html.push_str(&highlighted[body_end..]);
+    html
+}
+
+/// A section of a document as listed under its toctree entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocSection {
+    pub title: Symbol,
+    pub anchor: Symbol,
+    /// Heading level; the document title is level 1
+    pub level: usize,
+}
+
+impl TocSection {
+    pub fn new(title: impl Into<Symbol>, anchor: impl Into<Symbol>, level: usize) -> Self {
+        Self {
+            title: title.into(),
+            anchor: anchor.into(),
+            level,
+        }
+    }
+}
+
+/// A problem found while rendering, reported as a build warning by the caller.
+#[derive(Debug, Clone)]
+pub struct RenderWarning {
+    pub line: Option<usize>,
+    pub message: String,
+    pub warning_type: WarningType,
+}
+
+/// Output directory of the local media files embedded by `video` directives
+pub const MEDIA_DIR: &str = "_videos";
+
+/// Output directory of the local images of `image` and `figure` directives
+pub const IMAGES_DIR: &str = "_images";
+
+/// Whether a URL starts with a scheme like `https:` or `mailto:`
+fn has_url_scheme(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    })
+}
+
+/// Escape the cells of a parsed table row for use as HTML
+fn escape_cells(cells: &[String]) -> Vec<String> {
+    cells.iter().map(|cell| html_escape::encode_text(cell).into_owned()).collect()
+}
+
+/// A local file embedded by a page, copied to the output by the caller.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFile {
+    pub source: PathBuf,
+    /// Path relative to the output directory, like `_videos/media/demo.mp4`
+    pub output: String,
+}
+
+/// Defaults set by the last `highlight` directive for the literal blocks
+/// and argument-less code blocks after it
+#[derive(Default)]
+struct HighlightSetting {
+    language: Option<String>,
+    linenothreshold: Option<usize>,
+}
+
+impl HighlightSetting {
+    /// Whether a block of `code` is long enough to get line numbers
+    fn numbers_lines(&self, code: &str) -> bool {
+        self.linenothreshold
+            .is_some_and(|threshold| code.lines().count() > threshold)
+    }
+}
+
+/// The `contents` directives of a document, rendered before its body
+#[derive(Default)]
+struct ContentsPlan {
+    /// HTML of each `contents` directive by its index in the document
+    topics: HashMap<usize, String>,
+    /// Id each heading links back to, by the title's index in the document
+    backlinks: HashMap<usize, String>,
+}
+
+/// Options of the `contents` directive being rendered
+struct ContentsContext<'a> {
+    /// `entry`, `top` or `none`
+    backlinks: &'a str,
+    topic_id: &'a str,
+    max_depth: Option<usize>,
+    /// Ids taken in the document, for the `idN` ids of the entries
+    slugs: &'a mut SlugRegistry,
+    /// Ids of the sections, by the index of their title
+    section_ids: &'a HashMap<usize, String>,
+    plan: &'a mut ContentsPlan,
+}
+
+/// Map of document paths to their titles
+pub type DocumentTitles = HashMap<Symbol, Symbol>;
+
+/// Map of document paths to their sections, in document order
+pub type DocumentSections = HashMap<Symbol, Vec<TocSection>>;
 
 /// HTML renderer that converts parsed AST to HTML.
 pub struct HtmlRenderer {
     directive_registry: DirectiveRegistry,
     role_registry: RoleRegistry,
-    /// Map of document paths to their titles (e.g., "intro" -> "Introduction")
-    document_titles: HashMap<String, String>,
-    /// Map of document paths to their sections (title, anchor) for nested toctree entries
-    document_sections: HashMap<String, Vec<(String, String)>>,
+    /// Documents of the project with their titles (e.g., "intro" ->
+    /// "Introduction") and sections, for toctrees
+    environment: Arc<BuildEnvironment>,
+    /// Path of the document being rendered, excluded from toctree globs
+    current_doc: Option<String>,
     /// Syntax definitions for code highlighting
-    syntax_set: SyntaxSet,
+    syntax_set: &'static SyntaxSet,
     /// Theme for code highlighting
-    theme_set: ThemeSet,
+    theme_set: &'static ThemeSet,
     /// Name of the theme to use for highlighting
     theme_name: String,
     /// Source directory for resolving relative paths (e.g., for literalinclude)
     source_dir: Option<PathBuf>,
+    /// Parser shared with the builder, used for included files
+    parser: Option<Arc<Parser>>,
+    /// Problems found while rendering, drained with `take_warnings`
+    warnings: Mutex<Vec<RenderWarning>>,
+    /// Local media referenced while rendering, drained with `take_media_files`
+    media_files: Mutex<Vec<MediaFile>>,
+    /// Assets the rendered directives need, drained with `take_page_assets`
+    page_assets: Mutex<PageAssets>,
+    /// Tab sets and tabs rendered so far, numbering their ids
+    tab_counts: Mutex<(usize, usize)>,
+    /// Highlight with CSS classes and avoid `style` attributes, for pages
+    /// served with a strict Content-Security-Policy
+    strict_csp: bool,
+    /// Show the names of `sectionauthor`, `moduleauthor` and `codeauthor`
+    /// directives
+    show_authors: bool,
+    /// Anchors placed for index entries; a term indexed again links to its
+    /// first place
+    index_anchors: Mutex<HashSet<String>>,
+    /// Markup of the links to headings and captions, or `None` without
+    /// permalinks
+    permalink_icon: Option<String>,
+    /// Language of literal blocks and code blocks without one, until a
+    /// `highlight` directive changes it
+    highlight_language: Option<String>,
+    /// Typographic quotes for prose, or `None` to keep text as written
+    smartquotes: Option<SmartQuotes>,
+    /// Labels and numbering of auto-numbered footnotes
+    footnote_config: FootnoteConfig,
+    /// Footnote and citation references of the document being rendered,
+    /// which the content nested in it shares
+    footnote_references: Mutex<Option<FootnoteReferences>>,
+    /// Ids taken in the document being rendered, which the content nested
+    /// in it shares
+    document_ids: Mutex<Option<SlugRegistry>>,
+    /// BibTeX entries that `:cite:` roles reference
+    bibliography: Arc<Bibliography>,
 }
 
 impl Default for HtmlRenderer {
@@ -42,20 +309,107 @@ impl HtmlRenderer {
         Self {
             directive_registry: DirectiveRegistry::new(),
             role_registry: RoleRegistry::new(),
-            document_titles: HashMap::new(),
-            document_sections: HashMap::new(),
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
+            environment: Arc::clone(&EMPTY_ENVIRONMENT),
+            current_doc: None,
+            syntax_set: &SYNTAX_SET,
+            theme_set: &THEME_SET,
             theme_name: "base16-ocean.dark".to_string(),
             source_dir: None,
+            parser: None,
+            warnings: Mutex::new(Vec::new()),
+            media_files: Mutex::new(Vec::new()),
+            page_assets: Mutex::default(),
+            tab_counts: Mutex::default(),
+            strict_csp: false,
+            show_authors: false,
+            index_anchors: Mutex::default(),
+            permalink_icon: Some("¶".to_string()),
+            highlight_language: None,
+            smartquotes: None,
+            footnote_config: FootnoteConfig::default(),
+            footnote_references: Mutex::default(),
+            document_ids: Mutex::default(),
+            bibliography: Arc::default(),
+        }
+    }
+
+    /// Take the warnings collected while rendering.
+    pub fn take_warnings(&self) -> Vec<RenderWarning> {
+        std::mem::take(&mut *self.warnings.lock())
+    }
+
+    /// Take the local media files the rendered pages embed.
+    pub fn take_media_files(&self) -> Vec<MediaFile> {
+        std::mem::take(&mut *self.media_files.lock())
+    }
+
+    /// Link a stylesheet from the page being rendered only
+    pub fn add_css_file(&self, asset: Asset) {
+        self.page_assets.lock().add_css_file(asset);
+    }
+
+    /// Load a script on the page being rendered only
+    pub fn add_js_file(&self, asset: Asset) {
+        self.page_assets.lock().add_js_file(asset);
+    }
+
+    /// Record that the page being rendered uses a feature with assets of
+    /// its own
+    pub(crate) fn use_feature(&self, feature: PageFeature) {
+        self.page_assets.lock().uses(feature);
+    }
+
+    /// Take the assets the rendered pages need besides those of every page.
+    pub fn take_page_assets(&self) -> PageAssets {
+        std::mem::take(&mut *self.page_assets.lock())
+    }
+
+    /// Anchor for index entries, unless one with the id was placed already
+    fn index_anchor(&self, id: &str) -> String {
+        if self.index_anchors.lock().insert(id.to_string()) {
+            format!("<span class=\"target\" id=\"{}\"></span>", html_escape::encode_double_quoted_attribute(id))
+        } else {
+            String::new()
+        }
+    }
+
+    /// Link to the anchor `id`, titled after `what` it is on, or nothing
+    /// when permalinks are off
+    fn permalink(&self, id: &str, what: &str) -> String {
+        match &self.permalink_icon {
+            Some(icon) => format!(
+                "<a class=\"headerlink\" href=\"#{}\" title=\"Link to this {}\">{}</a>",
+                html_escape::encode_double_quoted_attribute(id),
+                what,
+                icon
+            ),
+            None => String::new(),
         }
     }
 
+    fn warn(&self, line: Option<usize>, message: String, warning_type: WarningType) {
+        self.warnings.lock().push(RenderWarning {
+            line,
+            message,
+            warning_type,
+        });
+    }
+
     /// Set the source directory for resolving relative paths in directives like literalinclude.
     pub fn set_source_dir(&mut self, source_dir: PathBuf) {
         self.source_dir = Some(source_dir);
     }
 
+    /// Share a parser for included files instead of creating one per include.
+    pub fn set_parser(&mut self, parser: Arc<Parser>) {
+        self.parser = Some(parser);
+    }
+
+    /// Set the path of the document being rendered (without extension).
+    pub fn set_current_doc(&mut self, path: &str) {
+        self.current_doc = Some(path.to_string());
+    }
+
     /// Set the syntax highlighting theme.
     /// Available themes: "InspiredGitHub", "Solarized (dark)", "Solarized (light)",
     /// "base16-ocean.dark", "base16-eighties.dark", "base16-mocha.dark", "base16-ocean.light"
@@ -65,10 +419,112 @@ impl HtmlRenderer {
         }
     }
 
-    /// Highlight code with syntax highlighting, falling back to plain text if language is unknown.
-    fn highlight_code(&self, code: &str, language: Option<&str>) -> String {
+    /// Emit markup without inline styles: code is highlighted with classes
+    /// styled by [`HtmlRenderer::highlight_css`].
+    pub fn set_strict_csp(&mut self, strict_csp: bool) {
+        self.strict_csp = strict_csp;
+        self.directive_registry.set_strict_csp(strict_csp);
+    }
+
+    /// Show author directives as "Section author: ..." lines, like
+    /// Sphinx's `show_authors`; otherwise they only record metadata.
+    pub fn set_show_authors(&mut self, show_authors: bool) {
+        self.show_authors = show_authors;
+    }
+
+    /// Set the markup of the permalinks next to headings and captions, or
+    /// leave them out with `None`, like Sphinx's `html_permalinks_icon`.
+    pub fn set_permalink_icon(&mut self, icon: Option<String>) {
+        self.permalink_icon = icon;
+    }
+
+    /// Set the language of literal blocks and of code blocks without one,
+    /// like Sphinx's `highlight_language`; [`GUESS_LANGUAGE`] guesses it
+    /// from each block.
+    pub fn set_highlight_language(&mut self, language: Option<String>) {
+        self.highlight_language = language;
+    }
+
+    /// Add the roles defined in the configuration.
+    pub fn set_custom_roles(&mut self, roles: &BTreeMap<String, CustomRoleConfig>) {
+        self.role_registry.register_custom_roles(roles);
+    }
+
+    /// Add the `:issue:`, `:pr:`, `:commit:` and `:user:` roles of the
+    /// configured issue tracker.
+    pub fn set_issue_tracker(&mut self, tracker: &IssueTrackerConfig) {
+        self.role_registry.register_tracker_roles(tracker);
+    }
+
+    /// Add the container directives defined in the configuration.
+    pub fn set_custom_directives(&mut self, directives: &BTreeMap<String, CustomDirectiveConfig>) {
+        self.directive_registry.register_custom_directives(directives);
+    }
+
+    /// Convert quotes, dashes and ellipses in prose; code, literals and
+    /// role content keep them as written.
+    pub fn set_smartquotes(&mut self, smartquotes: Option<SmartQuotes>) {
+        self.smartquotes = smartquotes;
+    }
+
+    /// Set the labels and numbering of auto-numbered footnotes.
+    pub fn set_footnotes(&mut self, config: FootnoteConfig) {
+        self.footnote_config = config;
+    }
+
+    /// Set the BibTeX entries that `:cite:` roles reference and
+    /// `bibliography` directives list.
+    pub fn set_bibliography(&mut self, bibliography: Arc<Bibliography>) {
+        self.bibliography = bibliography;
+    }
+
+    /// Stylesheet for class-based highlighting with the current theme
+    pub fn highlight_css(&self) -> String {
         let theme = &self.theme_set.themes[&self.theme_name];
+        let mut css = css_for_theme_with_class_style(theme, HIGHLIGHT_CLASS_STYLE).unwrap_or_default();
+        css.push_str(&format!(".hl-code .hll {{ background-color: {}; }}\n", self.line_highlight_color()));
+        css
+    }
+
+    /// `language`, or the one guessed from `code` when it is
+    /// [`GUESS_LANGUAGE`]
+    fn code_language<'a>(&self, language: Option<&'a str>, code: &str) -> Option<&'a str> {
+        match language {
+            Some(GUESS_LANGUAGE) => self.guess_language(code),
+            language => language,
+        }
+    }
 
+    /// Language of `code` going by its first line, like a shebang or an XML
+    /// declaration, then by prompts and shapes common in documentation.
+    /// `None` when nothing gives it away.
+    fn guess_language(&self, code: &str) -> Option<&'static str> {
+        let first_line = code.lines().find(|line| !line.trim().is_empty())?.trim_start();
+        if let Some(syntax) = self.syntax_set.find_syntax_by_first_line(first_line) {
+            return syntax.file_extensions.first().map(String::as_str);
+        }
+        let python = code.lines().map(str::trim).any(|line| {
+            (line.starts_with("def ") || line.starts_with("class ")) && line.ends_with(':')
+                || line.starts_with("import ")
+                || line.starts_with("from ") && line.contains(" import ")
+        });
+        if first_line.starts_with(">>> ") || python {
+            Some("python")
+        } else if first_line.starts_with("$ ") {
+            Some("bash")
+        } else if first_line.starts_with(['{', '['])
+            && serde_json::from_str::<serde_json::Value>(code).is_ok()
+        {
+            Some("json")
+        } else if first_line.starts_with('<') && first_line.contains('>') {
+            Some("html")
+        } else {
+            None
+        }
+    }
+
+    /// Highlight code with syntax highlighting, falling back to plain text if language is unknown.
+    fn highlight_code(&self, code: &str, language: Option<&str>) -> String {
         // Try to find a syntax for the language
         let syntax = language
             .and_then(|lang| {
@@ -80,8 +536,19 @@ impl HtmlRenderer {
             // Fall back to plain text
             .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
 
-        // Generate highlighted HTML
-        match highlighted_html_for_string(code, &self.syntax_set, syntax, theme) {
+        self.highlight_with_syntax(code, syntax)
+    }
+
+    /// Highlighted `<pre>` block, styled inline or with classes in strict CSP mode
+    fn highlight_with_syntax(&self, code: &str, syntax: &SyntaxReference) -> String {
+        let highlighted = if self.strict_csp {
+            highlighted_html_with_classes(code, self.syntax_set, syntax)
+        } else {
+            let theme = &self.theme_set.themes[&self.theme_name];
+            highlighted_html_for_string(code, self.syntax_set, syntax, theme)
+        };
+
+        match highlighted {
             Ok(html) => html,
             Err(_) => {
                 // Fallback to plain code block if highlighting fails
@@ -91,30 +558,71 @@ impl HtmlRenderer {
         }
     }
 
+    /// Number and emphasize the lines of a highlighted `<pre>` block, see
+    /// [`decorate_code_lines`]
+    fn decorate_lines(
+        &self,
+        highlighted: &str,
+        first_number: Option<usize>,
+        emphasized: &std::collections::BTreeSet<usize>,
+    ) -> String {
+        let hll_open = if self.strict_csp {
+            "<span class=\"hll\">".to_string()
+        } else {
+            format!("<span class=\"hll\" style=\"background-color:{}\">", self.line_highlight_color())
+        };
+        decorate_code_lines(highlighted, first_number, emphasized, &hll_open)
+    }
+
+    /// Background of emphasized code lines, from the highlighting theme
+    fn line_highlight_color(&self) -> String {
+        let theme = &self.theme_set.themes[&self.theme_name];
+        match theme.settings.line_highlight {
+            Some(color) => format!("rgba({}, {}, {}, {:.2})", color.r, color.g, color.b, color.a as f32 / 255.0),
+            None => "rgba(255, 255, 0, 0.20)".to_string(),
+        }
+    }
+
     /// Register a document title for use in toctree rendering.
     /// The path should be without the .rst extension (e.g., "intro" or "tutorial/getting-started").
     pub fn register_document_title(&mut self, path: &str, title: &str) {
-        self.document_titles.insert(path.to_string(), title.to_string());
+        Arc::make_mut(&mut self.environment)
+            .titles
+            .insert(Symbol::intern(path), Symbol::intern(title));
     }
 
     /// Look up a document title by path. Returns None if not registered.
-    pub fn get_document_title(&self, path: &str) -> Option<&String> {
-        self.document_titles.get(path)
+    pub fn get_document_title(&self, path: &str) -> Option<&Symbol> {
+        self.environment.title(path)
     }
 
-    /// Register document sections for nested toctree entries.
-    /// Each section is a tuple of (title, anchor).
-    pub fn register_document_sections(&mut self, path: &str, sections: Vec<(String, String)>) {
-        self.document_sections.insert(path.to_string(), sections);
+    /// Register document sections for nested toctree entries, in document order.
+    pub fn register_document_sections(&mut self, path: &str, sections: Vec<TocSection>) {
+        Arc::make_mut(&mut self.environment)
+            .sections
+            .insert(Symbol::intern(path), sections);
+    }
+
+    /// Share the environment of the build, with the titles and sections of
+    /// every document collected once, instead of registering them one by
+    /// one on each renderer.
+    pub fn set_environment(&mut self, environment: Arc<BuildEnvironment>) {
+        self.environment = environment;
     }
 
     /// Render document content to HTML.
     pub fn render_document_content(&self, content: &DocumentContent) -> String {
+        render_to_string(|out| self.render_to(content, out))
+    }
+
+    /// Render document content to HTML, writing it node by node into `out`
+    /// instead of building the whole body in memory.
+    pub fn render_to<W: io::Write>(&self, content: &DocumentContent, out: &mut W) -> io::Result<()> {
         match content {
-            DocumentContent::RestructuredText(rst) => self.render_rst(rst),
-            DocumentContent::Markdown(md) => self.render_markdown(md),
+            DocumentContent::RestructuredText(rst) => self.write_rst(rst, out),
+            DocumentContent::Markdown(md) => self.write_markdown(md, out),
             DocumentContent::PlainText(text) => {
-                format!("<p>{}</p>", html_escape::encode_text(text))
+                write!(out, "<p>{}</p>", html_escape::encode_text(text))
             }
         }
     }
@@ -122,18 +630,83 @@ impl HtmlRenderer {
     /// Render RST content to HTML.
     /// Wraps content in hierarchical section tags based on heading levels.
     pub fn render_rst(&self, content: &RstContent) -> String {
-        let mut html = String::new();
-        let mut open_sections: Vec<usize> = Vec::new(); // Stack of open section levels
+        render_to_string(|out| self.write_rst(content, out))
+    }
 
-        for node in &content.ast {
+    fn write_rst<W: io::Write>(&self, content: &RstContent, html: &mut W) -> io::Result<()> {
+        // Footnotes are numbered for the outermost document; content nested
+        // in it resolves its references against that document
+        let plan = {
+            let mut references = self.footnote_references.lock();
+            references.is_none().then(|| {
+                let plan = FootnotePlan::new(&content.ast, &content.raw, &self.footnote_config);
+                *references = Some(FootnoteReferences::new(&plan));
+                plan
+            })
+        };
+        let owns_ids = {
+            let mut ids = self.document_ids.lock();
+            let outermost = ids.is_none();
+            if outermost {
+                *ids = Some(SlugRegistry::new());
+            }
+            outermost
+        };
+        let result = self.write_rst_nodes(content, plan.as_ref(), owns_ids, html);
+        if plan.is_some() {
+            *self.footnote_references.lock() = None;
+        }
+        if owns_ids {
+            *self.document_ids.lock() = None;
+        }
+        result
+    }
+
+    /// Write the nodes of RST content; `outermost` when it is the document
+    /// itself rather than content nested in it, whose headings may be
+    /// numbered
+    fn write_rst_nodes<W: io::Write>(
+        &self,
+        content: &RstContent,
+        footnotes: Option<&FootnotePlan>,
+        outermost: bool,
+        html: &mut W,
+    ) -> io::Result<()> {
+        // Citations go to the bibliography when the document has one
+        let bibliography = content
+            .ast
+            .iter()
+            .any(|node| matches!(node, RstNode::Directive { name, .. } if name == "bibliography"));
+        let mut open_sections: Vec<usize> = Vec::new(); // Stack of open section levels
+        let (section_ids, contents) = {
+            let mut slugs = self.document_ids.lock().take().unwrap_or_default();
+            let section_ids = rst_node_ids(&content.ast, &mut slugs);
+            let contents = self.plan_contents(&content.ast, &section_ids, &mut slugs);
+            *self.document_ids.lock() = Some(slugs);
+            (section_ids, contents)
+        };
+        let mut highlight = HighlightSetting {
+            language: self.highlight_language.clone(),
+            ..HighlightSetting::default()
+        };
+        // Section numbers from `:numbered:` toctrees, the document title's
+        // under the empty anchor
+        let secnumbers = self
+            .current_doc
+            .as_deref()
+            .filter(|_| outermost)
+            .and_then(|doc| self.environment.toc_secnumbers.get(doc));
+        let first_title = content.ast.iter().position(|node| matches!(node, RstNode::Title { .. }));
+
+        for (index, node) in content.ast.iter().enumerate() {
             // Check if this is a title and handle section nesting
-            if let RstNode::Title { level, text, .. } = node {
-                let level = (*level).min(6).max(1);
+            if let RstNode::Title { level, .. } = node {
+                let level = (*level).clamp(1, 6);
 
                 // Close sections that are at the same level or deeper
                 while let Some(&open_level) = open_sections.last() {
                     if open_level >= level {
-                        html.push_str("</section>\n");
+                        html.write_all(b"</section>\n")?;
                         open_sections.pop();
                     } else {
                         break;
@@ -141,43 +714,107 @@ impl HtmlRenderer {
                 }
 
                 // Open a new section for this heading
-                let plain_text = extract_plain_text_for_slug(text);
-                let slug = slugify(&plain_text);
-                html.push_str(&format!("<section id=\"{}\">\n", slug));
+                writeln!(html, "<section id=\"{}\">", section_ids[&index])?;
                 open_sections.push(level);
             }
 
-            html.push_str(&self.render_rst_node(node));
-            html.push('\n');
+            match node {
+                RstNode::Title { text, level, .. } => {
+                    let backlink = contents.backlinks.get(&index).map(String::as_str);
+                    let anchor = if first_title == Some(index) { "" } else { section_ids[&index].as_str() };
+                    let number = secnumbers.and_then(|numbers| numbers.get(anchor)).map(Vec::as_slice);
+                    writeln!(html, "{}", self.render_title(text, *level, &section_ids[&index], backlink, number))?;
+                }
+                RstNode::Directive { name, .. } if name == "contents" => {
+                    writeln!(html, "{}", contents.topics.get(&index).map(String::as_str).unwrap_or_default())?;
+                }
+                RstNode::Footnote { content: body, .. } | RstNode::Citation { content: body, .. }
+                    if footnotes.is_some_and(|plan| plan.definition(index).is_some()) =>
+                {
+                    let citation = matches!(node, RstNode::Citation { .. });
+                    if !(citation && bibliography) {
+                        let plan = footnotes.unwrap();
+                        let note = plan.definition(index).unwrap();
+                        let rendered = self.render_note(Some((note, plan.is_referenced(note))), &note.label, citation, body);
+                        writeln!(html, "{}", if citation { notes::citation_list(&rendered) } else { rendered })?;
+                    }
+                }
+                RstNode::Directive { name, .. } if name == "bibliography" => {
+                    let mut citations: Vec<(usize, &str, &str)> = content
+                        .ast
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(index, node)| match node {
+                            RstNode::Citation { label, content, .. } => Some((index, label.as_str(), content.as_str())),
+                            _ => None,
+                        })
+                        .collect();
+                    citations.sort_by_key(|(_, label, _)| label.to_lowercase());
+                    let mut rendered: Vec<String> = citations
+                        .into_iter()
+                        .map(|(index, label, body)| {
+                            let note = footnotes.and_then(|plan| plan.definition(index).map(|note| (note, plan.is_referenced(note))));
+                            self.render_note(note, label, true, body)
+                        })
+                        .collect();
+                    rendered.extend(self.render_bibtex_entries());
+                    writeln!(html, "{}", notes::citation_list(&rendered.join("\n")))?;
+                }
+                RstNode::Directive { name, options, .. } if name == "tasklist-summary" => {
+                    let progress = tasklist::TaskProgress::of_rst(&content.ast);
+                    writeln!(html, "{}", self.render_tasklist_summary(progress, options))?;
+                }
+                RstNode::Directive { name, args, options, .. } if name == "highlight" => {
+                    highlight = HighlightSetting {
+                        language: args.first().cloned(),
+                        linenothreshold: options.get("linenothreshold").and_then(|value| value.trim().parse().ok()),
+                    };
+                }
+                RstNode::CodeBlock { language: None, content, .. } => {
+                    let language = self.code_language(highlight.language.as_deref(), content);
+                    let highlighted = self.highlight_code(content, language);
+                    if highlight.numbers_lines(content) {
+                        writeln!(html, "{}", self.decorate_lines(&highlighted, Some(1), &Default::default()))?;
+                    } else {
+                        writeln!(html, "{}", highlighted)?;
+                    }
+                }
+                RstNode::Directive { name, args, options, content: code, line }
+                    if matches!(name.as_str(), "code-block" | "code" | "sourcecode") =>
+                {
+                    let mut args = args.clone();
+                    if args.is_empty() {
+                        args.extend(self.code_language(highlight.language.as_deref(), code).map(String::from));
+                    }
+                    let mut options = options.clone();
+                    if highlight.numbers_lines(code) && !options.contains_key("lineno-start") {
+                        options.entry("linenos".to_string()).or_default();
+                    }
+                    let node = RstNode::Directive {
+                        name: name.clone(),
+                        args,
+                        options,
+                        content: code.clone(),
+                        line: *line,
+                    };
+                    writeln!(html, "{}", self.render_rst_node(&node))?;
+                }
+                _ => writeln!(html, "{}", self.render_rst_node(node))?,
+            }
         }
 
         // Close any remaining open sections
         for _ in open_sections {
-            html.push_str("</section>\n");
+            html.write_all(b"</section>\n")?;
         }
 
-        html
+        Ok(())
     }
 
     /// Render a single RST node to HTML.
     fn render_rst_node(&self, node: &RstNode) -> String {
         match node {
-            RstNode::Title { text, level, .. } => {
-                // Extract plain text for slug generation (strips RST markup)
-                let plain_text = extract_plain_text_for_slug(text);
-                let slug = slugify(&plain_text);
-                let level = (*level).min(6).max(1);
-                // Process inline markup in titles (including roles)
-                let rendered_text = self.render_rst_inline(text);
-                // Add headerlink (¶ symbol) like Sphinx does
-                // Note: id is on the parent <section> tag, not the heading
-                format!(
-                    "<h{level}>{text}<a class=\"headerlink\" href=\"#{slug}\" title=\"Link to this heading\">¶</a></h{level}>",
-                    level = level,
-                    slug = slug,
-                    text = rendered_text
-                )
-            }
+            RstNode::Title { text, level, .. } => self.render_title(text, *level, &self.heading_id(text), None, None),
 
             RstNode::Paragraph { content, .. } => {
                 let rendered = self.render_rst_inline(content);
@@ -186,7 +823,10 @@ impl HtmlRenderer {
 
             RstNode::CodeBlock {
                 language, content, ..
-            } => self.highlight_code(content, language.as_deref()),
+            } => {
+                let language = language.as_deref().or(self.highlight_language.as_deref());
+                self.highlight_code(content, self.code_language(language, content))
+            }
 
             RstNode::List {
                 items,
@@ -202,7 +842,10 @@ impl HtmlRenderer {
                             let term = self.render_rst_inline(parts[0]);
                             let nested_items: String = parts[1..]
                                 .iter()
-                                .map(|nested| format!("<li><p>{}</p></li>", self.render_rst_inline(nested)))
+                                .map(|nested| {
+                                    self.render_task_item(nested, |text| self.render_rst_inline(text))
+                                        .unwrap_or_else(|| format!("<li><p>{}</p></li>", self.render_rst_inline(nested)))
+                                })
                                 .collect::<Vec<_>>()
                                 .join("\n");
                             format!(
@@ -210,52 +853,26 @@ impl HtmlRenderer {
                                 term, nested_items
                             )
                         } else {
-                            format!("<li>{}</li>", self.render_rst_inline(item))
+                            self.render_task_item(item, |text| self.render_rst_inline(text))
+                                .unwrap_or_else(|| format!("<li>{}</li>", self.render_rst_inline(item)))
                         }
                     })
                     .collect::<Vec<_>>()
                     .join("\n");
+                let tasks = items.iter().any(|item| tasklist::split_task(item).is_some());
                 // Use class="simple" for unordered lists like Sphinx does
                 if *ordered {
-                    format!("<ol>\n{}\n</ol>", items_html)
+                    format!("<ol{}>\n{}\n</ol>", if tasks { " class=\"contains-task-list\"" } else { "" }, items_html)
+                } else if tasks {
+                    format!("<ul class=\"simple contains-task-list\">\n{}\n</ul>", items_html)
                 } else {
                     format!("<ul class=\"simple\">\n{}\n</ul>", items_html)
                 }
             }
 
             RstNode::Table { headers, rows, .. } => {
-                let mut html = String::from("<table>\n");
-
-                // Render header
-                if !headers.is_empty() {
-                    html.push_str("<thead>\n<tr>\n");
-                    for header in headers {
-                        html.push_str(&format!(
-                            "<th>{}</th>\n",
-                            html_escape::encode_text(header)
-                        ));
-                    }
-                    html.push_str("</tr>\n</thead>\n");
-                }
-
-                // Render body
-                if !rows.is_empty() {
-                    html.push_str("<tbody>\n");
-                    for row in rows {
-                        html.push_str("<tr>\n");
-                        for cell in row {
-                            html.push_str(&format!(
-                                "<td>{}</td>\n",
-                                html_escape::encode_text(cell)
-                            ));
-                        }
-                        html.push_str("</tr>\n");
-                    }
-                    html.push_str("</tbody>\n");
-                }
-
-                html.push_str("</table>");
-                html
+                let rows: Vec<Vec<String>> = rows.iter().map(|row| escape_cells(row)).collect();
+                self.render_table(&escape_cells(headers), &rows, None, None)
             }
 
             RstNode::Directive {
@@ -267,13 +884,92 @@ impl HtmlRenderer {
             } => {
                 // Handle toctree specially since it needs access to document titles
                 if name == "toctree" {
-                    return self.render_toctree(options, content);
+                    return self.render_toctree(options, content, *line);
                 }
 
                 // Handle literalinclude specially since it needs to read files from source_dir
                 if name == "literalinclude" {
                     let filename = args.first().map(|s| s.as_str()).unwrap_or("");
-                    return self.render_literalinclude(filename, options);
+                    return self.render_literalinclude(filename, options, *line);
+                }
+
+                // Handle raw specially since its content may come from a file or URL
+                if name == "raw" {
+                    return self.render_raw(args, options, content, *line);
+                }
+
+                // Index entries get an anchor for the general index to link to
+                if name == "index" {
+                    let target = genindex::index_directive(args, options, content);
+                    for error in target.errors {
+                        self.warn(Some(*line), error, WarningType::InvalidDirective);
+                    }
+                    if target.terms.is_empty() {
+                        return String::new();
+                    }
+                    return self.index_anchor(&target.id);
+                }
+
+                // Author names and centered text are inline markup, which the
+                // directive registry does not render
+                if let Some(label) = author_label(name) {
+                    if !self.show_authors || args.is_empty() {
+                        return String::new();
+                    }
+                    return format!("<p><em>{}{}</em></p>", label, self.render_rst_inline(&args.join(" ")));
+                }
+                if name == "centered" {
+                    return format!(
+                        "<p class=\"centered\"><strong>{}</strong></p>",
+                        self.render_rst_inline(&args.join(" "))
+                    );
+                }
+
+                // Figures, images and tables carry captions and permalinks
+                if name == "figure" || name == "image" {
+                    return self.render_figure(name, args, options, content, *line);
+                }
+                if name == "table" {
+                    return self.render_table_directive(args, options, content, *line);
+                }
+                if name == "list-table" {
+                    return self.render_list_table(args, options, content);
+                }
+                if name == "dropdown" || name == "collapse" {
+                    return self.render_dropdown(name, args, options, content, *line);
+                }
+                if crate::directives::ADMONITIONS.contains(&name.as_str()) {
+                    return self.render_admonition(name, args, options, content, *line);
+                }
+                if design::DIRECTIVES.contains(&name.as_str()) {
+                    return self.render_design(name, args, options, content, *line);
+                }
+                if name == "math" {
+                    return self.render_math(args, options, content);
+                }
+                if name == "mermaid" {
+                    return self.render_mermaid(options, content);
+                }
+                if name == "config-schema" {
+                    return self.render_config_schema(args, options, *line);
+                }
+                if name == "figlist" || name == "tablelist" {
+                    return self.render_figure_list(name, options);
+                }
+                if matches!(name.as_str(), "code-block" | "code" | "sourcecode")
+                    && (options.contains_key("caption") || options.contains_key("name"))
+                {
+                    let mut options = options.clone();
+                    let caption = options.remove("caption").map(|caption| self.render_rst_inline(&caption));
+                    let id = options.remove("name").and_then(|name| self.name_id(&name));
+                    let block = self.render_rst_node(&RstNode::Directive {
+                        name: name.clone(),
+                        args: args.clone(),
+                        options,
+                        content: content.clone(),
+                        line: *line,
+                    });
+                    return self.wrap_code_block(block, caption, id);
                 }
 
                 // Handle include specially since it needs to parse and render RST content
@@ -282,33 +978,31 @@ impl HtmlRenderer {
                     return self.render_include(filename, options);
                 }
 
-                // Pre-process content for inline RST markup (roles like :ref:, :doc:, etc.)
-                // This is needed for admonitions and other directives that contain RST text
-                // Skip processing for directives that should receive raw content (like raw, code-block, literalinclude)
-                let raw_content_directives = ["raw", "code-block", "code", "sourcecode", "literalinclude", "highlight"];
-                let processed_content: Vec<String> = if raw_content_directives.contains(&name.as_str()) {
-                    content.lines().map(String::from).collect()
+                // Processors whose content is markup get it parsed as blocks
+                let children = if self.directive_registry.parses_content(name) {
+                    self.parse_rst_children(name, content)
                 } else {
-                    content
-                        .lines()
-                        .map(|line| self.render_rst_inline(line))
-                        .collect()
+                    Vec::new()
                 };
 
+                let mut arguments = args.clone();
+                let mut options = options.clone();
+                if name == "video" {
+                    self.localize_media(&mut arguments, &mut options, *line);
+                }
+
                 // Convert to Directive struct for processing
                 let directive = Directive {
                     name: name.clone(),
-                    arguments: args.clone(),
-                    options: options.clone(),
-                    content: processed_content,
+                    arguments,
+                    options,
+                    content: content.lines().map(String::from).collect(),
+                    children,
                     line_number: *line,
                     source_file: String::new(),
                 };
 
-                match self.directive_registry.process_directive(&directive) {
-                    Ok(html) => html,
-                    Err(_) => format!("<!-- Error processing directive: {} -->", name),
-                }
+                self.process_directive(directive)
             }
 
             RstNode::LinkTarget { name, .. } => {
@@ -322,6 +1016,12 @@ impl HtmlRenderer {
                 format!("<blockquote>\n<p>{}</p>\n</blockquote>", rendered_content)
             }
 
+            RstNode::Footnote { label, content, .. } => self.render_note(None, label, false, content),
+
+            RstNode::Citation { label, content, .. } => {
+                notes::citation_list(&self.render_note(None, label, true, content))
+            }
+
             RstNode::DefinitionList { items, .. } => {
                 let mut html = String::from("<dl class=\"simple\">\n");
                 for item in items {
@@ -338,91 +1038,252 @@ impl HtmlRenderer {
         }
     }
 
-    /// Render a toctree directive with document title lookup.
-    fn render_toctree(&self, options: &HashMap<String, String>, content: &str) -> String {
-        let caption = options.get("caption");
-        let hidden = options.contains_key("hidden");
+    /// Render a section heading with a permalink to the section `id`;
+    /// `backlink` is the `contents` entry or topic the heading text links
+    /// back to
+    fn render_title(
+        &self,
+        text: &str,
+        level: usize,
+        id: &str,
+        backlink: Option<&str>,
+        number: Option<&[u32]>,
+    ) -> String {
+        let level = level.clamp(1, 6);
+        // Process inline markup in titles (including roles)
+        let mut rendered_text = self.render_rst_inline(text);
+        if let Some(backlink) = backlink {
+            rendered_text = format!(
+                "<a class=\"toc-backref\" href=\"#{}\" role=\"doc-backlink\">{}</a>",
+                backlink, rendered_text
+            );
+        }
+        if let Some(number) = number {
+            rendered_text.insert_str(0, &section_number_prefix(number));
+        }
+        // Add headerlink like Sphinx does
+        // Note: id is on the parent <section> tag, not the heading
+        format!(
+            "<h{level}>{text}{permalink}</h{level}>",
+            level = level,
+            text = rendered_text,
+            permalink = self.permalink(id, "heading")
+        )
+    }
 
-        // Parse document entries from content
-        let entries: Vec<&str> = content
-            .lines()
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty() && !s.starts_with(':'))
+    /// Render the `contents` directives of a document, which list the
+    /// sections of the whole page or, with `:local:`, of the section they
+    /// are in
+    fn plan_contents(
+        &self,
+        ast: &[RstNode],
+        section_ids: &HashMap<usize, String>,
+        slugs: &mut SlugRegistry,
+    ) -> ContentsPlan {
+        let mut plan = ContentsPlan::default();
+        let titles: Vec<(usize, &str, usize)> = ast
+            .iter()
+            .enumerate()
+            .filter_map(|(index, node)| match node {
+                RstNode::Title { text, level, .. } => Some((index, text.as_str(), *level)),
+                _ => None,
+            })
             .collect();
 
-        let mut html = String::new();
+        for (index, node) in ast.iter().enumerate() {
+            let RstNode::Directive { name, args, options, .. } = node else {
+                continue;
+            };
+            if name != "contents" {
+                continue;
+            }
 
-        // Start wrapper div (with "compound" class like Sphinx)
-        if hidden {
-            html.push_str("<div class=\"toctree-wrapper compound\" style=\"display: none;\">\n");
-        } else {
-            html.push_str("<div class=\"toctree-wrapper compound\">\n");
+            let local = options.contains_key("local");
+            let entries: Vec<(usize, &str, usize)> = if local {
+                // Sections after the directive, up to the end of the one it is in
+                let parent_level = titles
+                    .iter()
+                    .rev()
+                    .find(|(title_index, ..)| *title_index < index)
+                    .map_or(0, |(.., level)| *level);
+                titles
+                    .iter()
+                    .filter(|(title_index, ..)| *title_index > index)
+                    .take_while(|(.., level)| *level > parent_level)
+                    .copied()
+                    .collect()
+            } else {
+                // A lone top-level heading is the document title, not a section
+                let top_level = titles.iter().map(|(.., level)| *level).min().unwrap_or(0);
+                let document_title = match titles.iter().filter(|(.., level)| *level == top_level).count() {
+                    1 => titles.iter().find(|(.., level)| *level == top_level).map(|(title_index, ..)| *title_index),
+                    _ => None,
+                };
+                titles
+                    .iter()
+                    .filter(|(title_index, ..)| Some(*title_index) != document_title)
+                    .copied()
+                    .collect()
+            };
+
+            let title = match (args.join(" "), local) {
+                (title, _) if !title.is_empty() => Some(title),
+                (_, false) => Some("Contents".to_string()),
+                (_, true) => None,
+            };
+            let topic_id = slugs.unique(title.as_deref().map_or_else(|| "contents".to_string(), slugify));
+            let backlinks = options.get("backlinks").map(|value| value.trim()).unwrap_or("entry");
+            let max_depth = options.get("depth").and_then(|depth| depth.trim().parse::<usize>().ok());
+
+            let mut classes = vec!["contents".to_string()];
+            if local {
+                classes.push("local".to_string());
+            }
+            if let Some(class) = options.get("class") {
+                classes.extend(class.split_whitespace().map(String::from));
+            }
+
+            let mut html = format!("<nav class=\"{}\" id=\"{}\">\n", classes.join(" "), topic_id);
+            if let Some(title) = &title {
+                html.push_str(&format!("<p class=\"topic-title\">{}</p>\n", self.render_rst_inline(title)));
+            }
+            let mut context = ContentsContext {
+                backlinks,
+                topic_id: &topic_id,
+                max_depth,
+                slugs: &mut *slugs,
+                section_ids,
+                plan: &mut plan,
+            };
+            html.push_str(&self.render_contents_entries(&entries, 1, &mut context));
+            html.push_str("</nav>");
+            plan.topics.insert(index, html);
         }
 
-        // Add caption if present
-        if let Some(caption_text) = caption {
-            html.push_str(&format!(
-                "<p class=\"caption\"><span class=\"caption-text\">{}</span></p>\n",
-                html_escape::encode_text(caption_text)
-            ));
+        plan
+    }
+
+    /// Nested list of `entries`, each section holding the deeper ones up to
+    /// its next sibling
+    fn render_contents_entries(
+        &self,
+        entries: &[(usize, &str, usize)],
+        depth: usize,
+        context: &mut ContentsContext,
+    ) -> String {
+        if entries.is_empty() || context.max_depth.is_some_and(|max| depth > max) {
+            return String::new();
         }
 
-        // Generate the list of links
+        let mut html = String::from(if depth == 1 { "<ul class=\"simple\">\n" } else { "\n<ul>\n" });
+        let mut i = 0;
+        while i < entries.len() {
+            let (title_index, text, level) = entries[i];
+            let end = entries[i + 1..]
+                .iter()
+                .position(|(.., next_level)| *next_level <= level)
+                .map_or(entries.len(), |offset| i + 1 + offset);
+
+            let anchor = &context.section_ids[&title_index];
+            let entry_id = match context.backlinks {
+                "entry" => {
+                    let id = context.slugs.auto_id();
+                    context.plan.backlinks.insert(title_index, id.clone());
+                    format!(" id=\"{}\"", id)
+                }
+                "top" => {
+                    context.plan.backlinks.insert(title_index, context.topic_id.to_string());
+                    String::new()
+                }
+                _ => String::new(),
+            };
+            html.push_str(&format!(
+                "<li><p><a class=\"reference internal\" href=\"#{}\"{}>{}</a></p>",
+                anchor,
+                entry_id,
+                self.render_rst_inline(text)
+            ));
+            html.push_str(&self.render_contents_entries(&entries[i + 1..end], depth + 1, context));
+            html.push_str("</li>\n");
+            i = end;
+        }
+        html.push_str("</ul>\n");
+        html
+    }
+
+    /// Render a toctree directive with document title lookup.
+    fn render_toctree(&self, options: &HashMap<String, String>, content: &str, line: usize) -> String {
+        let caption = options.get("caption");
+        let hidden = options.contains_key("hidden");
+        let titles_only = options.contains_key("titlesonly");
+        // maxdepth of 0 or below means unlimited, as in Sphinx
+        let max_depth = options
+            .get("maxdepth")
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .filter(|depth| *depth > 0)
+            .map(|depth| depth as usize);
+        // A bare :numbered: numbers every level; a value limits the numbering depth
+        let numbered = Some(numbered_depth(options.get("numbered").map(String::as_str))).filter(|depth| *depth > 0);
+
+        let entries = self.toctree_entries(options, content, line);
+
+        let mut html = String::new();
+
+        // Start wrapper div (with "compound" class like Sphinx)
+        if hidden {
+            html.push_str("<div class=\"toctree-wrapper compound toctree-hidden\">\n");
+        } else {
+            html.push_str("<div class=\"toctree-wrapper compound\">\n");
+        }
+
+        // Add caption if present
+        if let Some(caption_text) = caption {
+            html.push_str(&format!(
+                "<p class=\"caption\"><span class=\"caption-text\">{}</span></p>\n",
+                html_escape::encode_text(caption_text)
+            ));
+        }
+
+        // Generate the list of links
         if !entries.is_empty() {
             html.push_str("<ul>\n");
-            for entry in entries {
-                // Handle entries with explicit titles: "Title <path>"
-                let (title, path) = if let Some(angle_pos) = entry.find('<') {
-                    if entry.ends_with('>') {
-                        let title = entry[..angle_pos].trim();
-                        let path = &entry[angle_pos + 1..entry.len() - 1];
-                        (Some(title.to_string()), path.to_string())
-                    } else {
-                        (None, entry.to_string())
-                    }
-                } else {
-                    (None, entry.to_string())
-                };
-
+            for (index, (title, path)) in entries.into_iter().enumerate() {
                 // Determine display title:
                 // 1. Explicit title from "Title <path>" syntax
-                // 2. Look up from document_titles registry
+                // 2. Look up the title in the environment
                 // 3. Fall back to path
                 let display_title = if let Some(explicit_title) = title {
                     explicit_title
-                } else if let Some(registered_title) = self.document_titles.get(&path) {
-                    registered_title.clone()
+                } else if let Some(registered_title) = self.environment.title(&path) {
+                    registered_title.to_string()
                 } else {
                     path.clone()
                 };
 
-                // Convert path to .html link
-                let href = format!("{}.html", path);
+                // Convert path to a link relative to the current page
+                let href = if is_external(&path) {
+                    path.clone()
+                } else {
+                    self.page_uri(&path)
+                };
 
                 // Render inline RST markup in the title (e.g., `code` -> <code>code</code>)
                 let rendered_title = self.render_rst_inline(&display_title);
+                let number = vec![index + 1];
 
                 html.push_str(&format!(
-                    "<li class=\"toctree-l1\"><a class=\"reference internal\" href=\"{}\">{}</a>",
+                    "<li class=\"toctree-l1\"><a class=\"reference internal\" href=\"{}\">{}{}</a>",
                     html_escape::encode_text(&href),
+                    self.toctree_number(&path, "", &number, numbered),
                     rendered_title
                 ));
 
                 // Add nested sections if available
-                if let Some(sections) = self.document_sections.get(&path) {
-                    if !sections.is_empty() {
-                        html.push_str("\n<ul>\n");
-                        for (section_title, section_anchor) in sections {
-                            let section_href = format!("{}.html#{}", path, section_anchor);
-                            let rendered_section_title = self.render_rst_inline(section_title);
-                            html.push_str(&format!(
-                                "<li class=\"toctree-l2\"><a class=\"reference internal\" href=\"{}\">{}</a></li>\n",
-                                html_escape::encode_text(&section_href),
-                                rendered_section_title
-                            ));
-                        }
-                        html.push_str("</ul>\n");
-                    }
+                let show_sections = !titles_only && max_depth.is_none_or(|depth| depth > 1);
+                if let Some(sections) = self.environment.sections.get(path.as_str()).filter(|_| show_sections) {
+                    html.push_str(&self.render_toctree_sections(
+                        &path, sections, 2, max_depth, &number, numbered,
+                    ));
                 }
 
                 html.push_str("</li>\n");
@@ -434,107 +1295,730 @@ impl HtmlRenderer {
         html
     }
 
-    /// Render a literalinclude directive by reading a file and optionally applying filters.
-    fn render_literalinclude(&self, filename: &str, options: &HashMap<String, String>) -> String {
-        // Resolve the file path relative to source_dir
-        let file_path = if let Some(ref source_dir) = self.source_dir {
-            source_dir.join(filename)
-        } else {
-            PathBuf::from(filename)
-        };
+    /// Parse toctree entries into (explicit title, document path) pairs,
+    /// expanding glob patterns when the `:glob:` option is set.
+    fn toctree_entries(
+        &self,
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> Vec<(Option<String>, String)> {
+        let glob = options.contains_key("glob");
+        let mut entries = Vec::new();
+
+        let base_doc = self.current_doc.as_deref().unwrap_or("");
+        let is_document = |doc: &str| self.environment.title(doc).is_some();
+        // Like Sphinx, glob matches skip documents the toctree already lists
+        let mut listed: HashSet<String> = HashSet::new();
+
+        for entry in content
+            .lines()
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty() && !s.starts_with(':'))
+        {
+            // Handle entries with explicit titles: "Title <path>"
+            let (title, target) = split_toctree_entry(entry);
+            if is_external(target) {
+                entries.push((title.map(str::to_string), target.to_string()));
+                continue;
+            }
+            if title.is_some() || !(glob && target.contains(['*', '?', '['])) {
+                let path = resolve_toctree_target(base_doc, target, is_document);
+                listed.insert(path.clone());
+                entries.push((title.map(str::to_string), path));
+                continue;
+            }
 
-        // Read the file content
-        let content = match std::fs::read_to_string(&file_path) {
-            Ok(content) => content,
-            Err(e) => {
-                return format!(
-                    "<!-- literalinclude error: could not read '{}': {} -->",
-                    filename, e
+            // Glob patterns are relative to the current document too
+            let pattern = resolve_docname(base_doc, target);
+            let mut matches: Vec<&Symbol> = match crate::matching::compile_pattern(&pattern) {
+                Ok(pattern) => self
+                    .environment
+                    .titles
+                    .keys()
+                    .filter(|doc| pattern.is_match(doc))
+                    .filter(|doc| self.current_doc.as_deref() != Some(doc.as_str()))
+                    .filter(|doc| !listed.contains(doc.as_str()))
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+            if matches.is_empty() {
+                self.warn(
+                    Some(line),
+                    format!("toctree glob pattern '{}' didn't match any documents", entry),
+                    WarningType::MissingToctreeRef,
                 );
             }
+            matches.sort();
+            listed.extend(matches.iter().map(|doc| doc.to_string()));
+            entries.extend(matches.into_iter().map(|doc| (None, doc.to_string())));
+        }
+
+        if options.contains_key("reversed") {
+            entries.reverse();
+        }
+        entries
+    }
+
+    /// Render the sections of a toctree entry as nested lists, `depth` being
+    /// the toctree level of the first list.
+    fn render_toctree_sections(
+        &self,
+        path: &str,
+        sections: &[TocSection],
+        depth: usize,
+        max_depth: Option<usize>,
+        parent_number: &[usize],
+        numbered: Option<usize>,
+    ) -> String {
+        if sections.is_empty() || max_depth.is_some_and(|max| depth > max) {
+            return String::new();
+        }
+
+        let mut html = String::from("\n<ul>\n");
+        let mut i = 0;
+        let mut count = 0;
+        while i < sections.len() {
+            let section = &sections[i];
+            // Everything deeper than this section up to the next sibling is nested under it
+            let end = sections[i + 1..]
+                .iter()
+                .position(|next| next.level <= section.level)
+                .map_or(sections.len(), |offset| i + 1 + offset);
+
+            count += 1;
+            let mut number = parent_number.to_vec();
+            number.push(count);
+
+            let section_href = format!("{}#{}", self.page_uri(path), section.anchor);
+            html.push_str(&format!(
+                "<li class=\"toctree-l{}\"><a class=\"reference internal\" href=\"{}\">{}{}</a>",
+                depth,
+                html_escape::encode_text(&section_href),
+                self.toctree_number(path, &section.anchor, &number, numbered),
+                self.render_rst_inline(&section.title)
+            ));
+            html.push_str(&self.render_toctree_sections(
+                path,
+                &sections[i + 1..end],
+                depth + 1,
+                max_depth,
+                &number,
+                numbered,
+            ));
+            html.push_str("</li>\n");
+            i = end;
+        }
+        html.push_str("</ul>\n");
+        html
+    }
+
+    /// Render a table from cells that are already HTML, with an optional
+    /// anchor and caption
+    fn render_table(&self, headers: &[String], rows: &[Vec<String>], id: Option<&str>, caption: Option<&str>) -> String {
+        let mut html = match id {
+            Some(id) => format!("<table id=\"{}\">\n", html_escape::encode_double_quoted_attribute(id)),
+            None => String::from("<table>\n"),
         };
+        if let Some(caption) = caption {
+            html.push_str(&format!(
+                "<caption>{}<span class=\"caption-text\">{}</span>{}</caption>\n",
+                self.caption_number("table", id),
+                caption,
+                id.map(|id| self.permalink(id, "table")).unwrap_or_default()
+            ));
+        }
 
-        // Handle :pyobject: option - extract a specific Python object
-        let content = if let Some(pyobject) = options.get("pyobject") {
-            match self.extract_python_object(&content, pyobject) {
-                Some(extracted) => extracted,
-                None => {
-                    return format!(
-                        "<!-- literalinclude error: could not find pyobject '{}' in '{}' -->",
-                        pyobject, filename
-                    );
+        // Render header
+        if !headers.is_empty() {
+            html.push_str("<thead>\n<tr>\n");
+            for header in headers {
+                html.push_str(&format!("<th>{}</th>\n", header));
+            }
+            html.push_str("</tr>\n</thead>\n");
+        }
+
+        // Render body
+        if !rows.is_empty() {
+            html.push_str("<tbody>\n");
+            for row in rows {
+                html.push_str("<tr>\n");
+                for cell in row {
+                    html.push_str(&format!("<td>{}</td>\n", cell));
                 }
+                html.push_str("</tr>\n");
             }
-        } else {
-            content
+            html.push_str("</tbody>\n");
+        }
+
+        html.push_str("</table>");
+        html
+    }
+
+    /// Render a `table` directive: the table in its content, captioned with
+    /// the directive's title
+    fn render_table_directive(
+        &self,
+        args: &[String],
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        let path = PathBuf::from(self.current_doc.as_deref().unwrap_or("table")).with_extension("rst");
+        let ast = match self.parse_rst_fragment(&path, content).map(|doc| doc.content) {
+            Ok(DocumentContent::RestructuredText(rst)) => rst.ast,
+            _ => Vec::new(),
+        };
+        let Some((headers, rows)) = ast.iter().find_map(|node| match node {
+            RstNode::Table { headers, rows, .. } => Some((headers, rows)),
+            _ => None,
+        }) else {
+            self.warn(Some(line), "table: no table found in the directive content".to_string(), WarningType::InvalidDirective);
+            return String::new();
         };
 
-        // Apply line-based filtering
-        let mut lines: Vec<&str> = content.lines().collect();
+        let rows: Vec<Vec<String>> = rows.iter().map(|row| escape_cells(row)).collect();
+        self.render_table(&escape_cells(headers), &rows, self.table_id(options).as_deref(), self.table_caption(args).as_deref())
+    }
 
-        // Handle start-after option (find line containing this text and start after it)
-        if let Some(start_after) = options.get("start-after") {
-            if let Some(pos) = lines.iter().position(|line| line.contains(start_after.as_str())) {
-                lines = lines[pos + 1..].to_vec();
+    /// Render a `list-table` directive, whose rows are the items of a
+    /// two-level bullet list
+    fn render_list_table(&self, args: &[String], options: &HashMap<String, String>, content: &str) -> String {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for line in content.lines() {
+            let trimmed = line.trim_start();
+            if let Some(cell) = trimmed.strip_prefix("* -").or_else(|| (trimmed == "* -").then_some("")) {
+                rows.push(vec![cell.trim().to_string()]);
+            } else if let Some(cell) = trimmed.strip_prefix("- ").or_else(|| (trimmed == "-").then_some("")) {
+                if let Some(row) = rows.last_mut() {
+                    row.push(cell.trim().to_string());
+                }
+            } else if !trimmed.is_empty() {
+                // Continuation of the current cell
+                if let Some(cell) = rows.last_mut().and_then(|row| row.last_mut()) {
+                    if !cell.is_empty() {
+                        cell.push(' ');
+                    }
+                    cell.push_str(trimmed);
+                }
             }
         }
 
-        // Handle start-at option (find line containing this text and start at it, inclusive)
-        if let Some(start_at) = options.get("start-at") {
-            if let Some(pos) = lines.iter().position(|line| line.contains(start_at.as_str())) {
-                lines = lines[pos..].to_vec();
+        let mut rows: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| row.iter().map(|cell| self.render_rst_inline(cell)).collect())
+            .collect();
+        let header_rows = options
+            .get("header-rows")
+            .and_then(|count| count.trim().parse::<usize>().ok())
+            .unwrap_or(0)
+            .min(rows.len());
+        let body = rows.split_off(header_rows);
+        let headers = rows.into_iter().next().unwrap_or_default();
+        self.render_table(&headers, &body, self.table_id(options).as_deref(), self.table_caption(args).as_deref())
+    }
+
+    /// Anchor of a table from its `:name:` option
+    fn table_id(&self, options: &HashMap<String, String>) -> Option<String> {
+        options.get("name").and_then(|name| self.name_id(name))
+    }
+
+    /// Caption of a table from the directive's title
+    fn table_caption(&self, args: &[String]) -> Option<String> {
+        Some(args.join(" "))
+            .filter(|title| !title.trim().is_empty())
+            .map(|title| self.render_rst_inline(title.trim()))
+    }
+
+    /// Wrap a rendered code block with its caption and `:name:` anchor
+    fn wrap_code_block(&self, block: String, caption: Option<String>, id: Option<String>) -> String {
+        let id = id.filter(|id| !id.is_empty());
+        match (caption, id) {
+            (None, None) => block,
+            (None, Some(id)) => block.replacen(
+                "<div",
+                &format!("<div id=\"{}\"", html_escape::encode_double_quoted_attribute(&id)),
+                1,
+            ),
+            (Some(caption), id) => format!(
+                "<div class=\"literal-block-wrapper docutils container\"{}>\n<div class=\"code-block-caption\">{}<span class=\"caption-text\">{}</span>{}</div>\n{}\n</div>",
+                id.as_deref()
+                    .map(|id| format!(" id=\"{}\"", html_escape::encode_double_quoted_attribute(id)))
+                    .unwrap_or_default(),
+                self.caption_number("code-block", id.as_deref()),
+                caption,
+                id.as_deref().map(|id| self.permalink(id, "code")).unwrap_or_default(),
+                block
+            ),
+        }
+    }
+
+    /// Render an `image` or `figure` directive. A figure's first paragraph
+    /// is its caption and the rest of its content the legend.
+    fn render_figure(
+        &self,
+        name: &str,
+        args: &[String],
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        let Some(path) = args.first().map(|arg| arg.trim()).filter(|path| !path.is_empty()) else {
+            self.warn(Some(line), format!("{}: missing image path", name), WarningType::InvalidDirective);
+            return String::new();
+        };
+        let src = self
+            .local_media_url(name, IMAGES_DIR, path, line)
+            .unwrap_or_else(|| path.to_string());
+        let id = options.get("name").and_then(|name| self.name_id(name));
+        let attribute = |value: &str| html_escape::encode_double_quoted_attribute(value).into_owned();
+
+        let mut img = format!("<img src=\"{}\" alt=\"{}\"", attribute(&src), attribute(options.get("alt").map(String::as_str).unwrap_or("")));
+        for dimension in ["width", "height"] {
+            if let Some(value) = options.get(dimension) {
+                img.push_str(&format!(" {}=\"{}\"", dimension, attribute(value.trim())));
             }
         }
+        let mut classes: Vec<String> = options
+            .get("class")
+            .map(|classes| classes.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default();
+        if name == "image" {
+            if let Some(align) = options.get("align") {
+                classes.push(format!("align-{}", align.trim()));
+            }
+            if let Some(id) = &id {
+                img.push_str(&format!(" id=\"{}\"", attribute(id)));
+            }
+        }
+        if !classes.is_empty() {
+            img.push_str(&format!(" class=\"{}\"", attribute(&classes.join(" "))));
+        }
+        img.push_str(" />");
+        if let Some(target) = options.get("target") {
+            img = format!("<a class=\"reference external image-reference\" href=\"{}\">{}</a>", attribute(target.trim()), img);
+        }
+        if name == "image" {
+            return img;
+        }
 
-        // Handle end-before option (find line containing this text and end before it)
-        if let Some(end_before) = options.get("end-before") {
-            if let Some(pos) = lines.iter().position(|line| line.contains(end_before.as_str())) {
-                lines = lines[..pos].to_vec();
+        let align = options.get("align").map(|align| align.trim()).unwrap_or("default");
+        let mut html = format!("<figure class=\"align-{}\"", attribute(align));
+        if let Some(id) = &id {
+            html.push_str(&format!(" id=\"{}\"", attribute(id)));
+        }
+        html.push_str(&format!(">\n{}\n", img));
+
+        // The caption is the first paragraph, the legend whatever follows
+        let content = content.trim();
+        if !content.is_empty() {
+            let (caption, legend) = match content.find("\n\n") {
+                Some(end) => (&content[..end], content[end..].trim()),
+                None => (content, ""),
+            };
+            let caption = caption.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+            html.push_str(&format!(
+                "<figcaption>\n<p>{}<span class=\"caption-text\">{}</span>{}</p>\n",
+                self.caption_number("figure", id.as_deref()),
+                self.render_rst_inline(&caption),
+                id.as_deref().map(|id| self.permalink(id, "image")).unwrap_or_default()
+            ));
+            if !legend.is_empty() {
+                let path = PathBuf::from(self.current_doc.as_deref().unwrap_or("figure")).with_extension("rst");
+                let legend = match self.parse_rst_fragment(&path, legend) {
+                    Ok(doc) => self.render_document_content(&doc.content),
+                    Err(_) => format!("<p>{}</p>", self.render_rst_inline(legend)),
+                };
+                html.push_str(&format!("<div class=\"legend\">\n{}\n</div>\n", legend));
             }
+            html.push_str("</figcaption>\n");
         }
+        html.push_str("</figure>");
+        html
+    }
 
-        // Handle start-line option (0-based: skip first N lines, like Sphinx)
-        if let Some(start_line) = options.get("start-line") {
-            if let Ok(start) = start_line.parse::<usize>() {
-                if start <= lines.len() {
-                    lines = lines[start..].to_vec();
-                }
+    /// Render a collapsible `dropdown` or `collapse` block, whose content
+    /// may hold any block markup
+    fn render_dropdown(
+        &self,
+        name: &str,
+        args: &[String],
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        let directive = Directive {
+            name: name.to_string(),
+            arguments: vec![self.render_rst_inline(&args.join(" "))],
+            options: options.clone(),
+            content: content.lines().map(String::from).collect(),
+            children: self.parse_rst_children(name, content),
+            line_number: line,
+            source_file: String::new(),
+        };
+        self.process_directive(directive)
+    }
+
+    /// Render an admonition with its content parsed as blocks. Text on the
+    /// directive line starts the content, except for the generic
+    /// `admonition`, whose argument is its title; without a `class` option
+    /// that one is also classed by its title, as docutils does.
+    fn render_admonition(
+        &self,
+        name: &str,
+        args: &[String],
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        let argument = args.join(" ");
+        let mut options = options.clone();
+        let (arguments, body) = if name == "admonition" {
+            if !options.contains_key("class") && !argument.trim().is_empty() {
+                let class = format!("admonition-{}", slugify(&extract_plain_text_for_slug(&argument)));
+                options.insert("class".to_string(), class);
+            }
+            (vec![self.render_rst_inline(&argument)], content.to_string())
+        } else if argument.is_empty() {
+            (Vec::new(), content.to_string())
+        } else {
+            (Vec::new(), format!("{}\n\n{}", argument, content))
+        };
+        let directive = Directive {
+            name: name.to_string(),
+            arguments,
+            options,
+            content: body.lines().map(String::from).collect(),
+            children: self.parse_rst_children(name, &body),
+            line_number: line,
+            source_file: String::new(),
+        };
+        self.process_directive(directive)
+    }
+
+    /// HTML of a directive from its processor, recording the page assets it
+    /// needs, or a comment in its place when it fails
+    fn process_directive(&self, mut directive: Directive) -> String {
+        // Processors make the element id from the name, so they get the id
+        // registered for it
+        if let Some(name) = directive.options.get_mut("name") {
+            if let Some(id) = self.name_id(name) {
+                *name = id;
+            }
+        }
+        let name = directive.name.as_str();
+        match self
+            .directive_registry
+            .process_directive(&directive, &|children| self.render_rst_children(children))
+        {
+            Ok(html) => {
+                self.page_assets.lock().extend(self.directive_registry.page_assets(name));
+                html
+            }
+            Err(e) => {
+                self.warn(Some(directive.line_number), format!("{}: {}", name, e), WarningType::InvalidDirective);
+                format!("<!-- Error processing directive: {} -->", name)
             }
         }
+    }
 
-        // Handle end-line option (1-based indexing, exclusive)
-        if let Some(end_line) = options.get("end-line") {
-            if let Ok(end) = end_line.parse::<usize>() {
-                if end > 0 && end <= lines.len() {
-                    lines = lines[..end].to_vec();
+    /// Id of a `:name:` option, unique among the ids of the document being
+    /// rendered
+    fn name_id(&self, name: &str) -> Option<String> {
+        match self.document_ids.lock().as_mut() {
+            Some(ids) => ids.name_id(name),
+            None => Some(slugify(name)).filter(|id| !id.is_empty()),
+        }
+    }
+
+    /// Id of a heading outside the sections of the document being rendered
+    fn heading_id(&self, text: &str) -> String {
+        match self.document_ids.lock().as_mut() {
+            Some(ids) => ids.heading_id(text),
+            None => slugify(&extract_plain_text_for_slug(text)),
+        }
+    }
+
+    /// Point a `video` directive's source and poster at the output copies of
+    /// local files
+    fn localize_media(&self, args: &mut [String], options: &mut HashMap<String, String>, line: usize) {
+        if let Some(src) = args.first_mut() {
+            if let Some(url) = self.local_media_url("video", MEDIA_DIR, src, line) {
+                *src = url;
+            }
+        }
+        if let Some(poster) = options.get_mut("poster") {
+            if let Some(url) = self.local_media_url("video", MEDIA_DIR, poster, line) {
+                *poster = url;
+            }
+        }
+    }
+
+    /// Record a local file embedded by a `directive` for copying below the
+    /// output folder `dir` and return its URL relative to the current page.
+    /// Paths are relative to the document, or to the source directory when
+    /// they start with `/`, as in Sphinx. URLs are left alone.
+    fn local_media_url(&self, directive: &str, dir: &str, path: &str, line: usize) -> Option<String> {
+        if path.is_empty() || path.contains("://") || path.starts_with("//") || path.starts_with("data:") {
+            return None;
+        }
+        let current_doc = self.current_doc.as_deref().unwrap_or("");
+        let doc_dir = std::path::Path::new(current_doc).parent().unwrap_or(std::path::Path::new(""));
+        let joined = match path.strip_prefix('/') {
+            Some(from_root) => PathBuf::from(from_root),
+            None => doc_dir.join(path),
+        };
+
+        // Resolve `..` so the copy stays below the output folder
+        let mut parts: Vec<String> = Vec::new();
+        for component in joined.components() {
+            match component {
+                std::path::Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+                std::path::Component::ParentDir => {
+                    parts.pop();
                 }
+                _ => {}
             }
         }
+        let relative = parts.join("/");
+        let source = match &self.source_dir {
+            Some(source_dir) => source_dir.join(&relative),
+            None => PathBuf::from(&relative),
+        };
+        if !source.is_file() {
+            self.warn(
+                Some(line),
+                format!("{}: media file '{}' not found", directive, path),
+                WarningType::MissingFile,
+            );
+        }
 
-        // Handle :lines: option (e.g., "1-10", "1,3,5-7")
-        if let Some(lines_spec) = options.get("lines") {
-            let selected_lines = self.parse_lines_spec(lines_spec, lines.len());
-            lines = selected_lines
-                .iter()
-                .filter_map(|&i| lines.get(i).copied())
-                .collect();
+        let output = format!("{}/{}", dir, relative);
+        let url = format!("{}{}", "../".repeat(current_doc.matches('/').count()), output);
+        self.media_files.lock().push(MediaFile { source, output });
+        Some(url)
+    }
+
+    /// Path of a file named in a directive: relative to the document, or to
+    /// the source directory when it starts with `/`, as in Sphinx
+    fn source_file_path(&self, path: &str) -> PathBuf {
+        let relative = match path.strip_prefix('/') {
+            Some(from_root) => PathBuf::from(from_root),
+            None => {
+                let current_doc = std::path::Path::new(self.current_doc.as_deref().unwrap_or(""));
+                current_doc.parent().unwrap_or(std::path::Path::new("")).join(path)
+            }
+        };
+        match &self.source_dir {
+            Some(source_dir) => source_dir.join(relative),
+            None => relative,
         }
+    }
 
-        // Handle dedent option
-        if let Some(dedent_str) = options.get("dedent") {
-            if let Ok(dedent) = dedent_str.parse::<usize>() {
-                lines = lines
-                    .iter()
-                    .map(|line| {
-                        if line.len() >= dedent {
-                            &line[dedent.min(line.len() - line.trim_start().len())..]
-                        } else {
-                            line.trim_start()
-                        }
-                    })
-                    .collect();
+    /// Path of a file named by an include or literalinclude directive, found
+    /// like the parser finds it
+    fn include_path(&self, filename: &str) -> PathBuf {
+        let including_file = self.current_doc.as_deref().map(|doc| match &self.source_dir {
+            Some(source_dir) => source_dir.join(doc),
+            None => PathBuf::from(doc),
+        });
+        resolve_include_path(filename, including_file.as_deref(), self.source_dir.as_deref())
+    }
+
+    /// Render a raw directive. Content for other formats than HTML is
+    /// dropped; HTML comes from the directive body, `:file:` or `:url:`.
+    fn render_raw(
+        &self,
+        args: &[String],
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        let formats = args.join(" ");
+        if formats.trim().is_empty() {
+            self.warn(
+                Some(line),
+                "raw: missing output format argument".to_string(),
+                WarningType::InvalidDirective,
+            );
+            return String::new();
+        }
+        if !raw_format_matches(&formats) {
+            return String::new();
+        }
+
+        let encoding = options.get("encoding").map(String::as_str);
+        let (file, url) = (options.get("file"), options.get("url"));
+        let problem = if file.is_some() && url.is_some() {
+            "the :file: and :url: options may not both be specified"
+        } else if (file.is_some() || url.is_some()) && !content.trim().is_empty() {
+            "may not both specify an external file and have content"
+        } else {
+            ""
+        };
+        if !problem.is_empty() {
+            self.warn(Some(line), format!("raw: {}", problem), WarningType::InvalidDirective);
+            return String::new();
+        }
+
+        let external = match (file, url) {
+            (Some(file), _) => utils::read_text_file(&self.source_file_path(file), encoding)
+                .map_err(|e| format!("could not read '{}': {:#}", file, e)),
+            (None, Some(url)) => utils::fetch_text(url, encoding)
+                .map_err(|e| format!("could not fetch '{}': {:#}", url, e)),
+            (None, None) => return content.to_string(),
+        };
+        external.unwrap_or_else(|problem| {
+            self.warn(Some(line), format!("raw: {}", problem), WarningType::MissingFile);
+            String::new()
+        })
+    }
+
+    /// URL of a document's page relative to the page being rendered
+    fn page_uri(&self, doc: &str) -> String {
+        relative_uri(self.current_doc.as_deref().unwrap_or(""), doc)
+    }
+
+    /// Section number prefix of a heading of `doc` in a toctree: the one
+    /// the build assigned to the document's headings, or else `number` as
+    /// this toctree counts
+    fn toctree_number(&self, doc: &str, anchor: &str, number: &[usize], numbered: Option<usize>) -> String {
+        match self.environment.toc_secnumbers.get(doc) {
+            Some(numbers) => numbers.get(anchor).map_or_else(String::new, |number| section_number_prefix(number)),
+            None => Self::section_number(number, numbered),
+        }
+    }
+
+    /// Section number prefix such as "1.2. " when numbering reaches this depth
+    fn section_number(number: &[usize], numbered: Option<usize>) -> String {
+        match numbered {
+            Some(depth) if number.len() <= depth => {
+                let parts: Vec<String> = number.iter().map(|n| n.to_string()).collect();
+                format!("<span class=\"section-number\">{}. </span>", parts.join("."))
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Render a literalinclude directive by reading a file and optionally applying filters.
+    fn render_literalinclude(
+        &self,
+        filename: &str,
+        options: &HashMap<String, String>,
+        line: usize,
+    ) -> String {
+        let file_path = self.include_path(filename);
+
+        // Read the file content
+        let content = match std::fs::read_to_string(&file_path) {
+            Ok(content) => content,
+            Err(e) => {
+                self.warn(
+                    Some(line),
+                    format!("literalinclude: could not read '{}': {}", filename, e),
+                    WarningType::MissingFile,
+                );
+                return format!(
+                    "<!-- literalinclude error: could not read '{}': {} -->",
+                    filename, e
+                );
+            }
+        };
+
+        // Handle :pyobject: and :object: - extract a named definition
+        let object = match (options.get("pyobject"), options.get("object")) {
+            (Some(_), Some(_)) => {
+                self.warn(
+                    Some(line),
+                    format!("literalinclude of '{}': pyobject and object cannot be combined", filename),
+                    WarningType::LiteralInclude,
+                );
+                return format!(
+                    "<!-- literalinclude error: pyobject and object cannot be combined in '{}' -->",
+                    filename
+                );
             }
+            (Some(pyobject), None) => Some(("pyobject", CodeLanguage::Python, pyobject)),
+            (None, Some(object)) => {
+                let language = options.get("language").map(String::as_str).or_else(|| {
+                    std::path::Path::new(filename)
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                });
+                match language.and_then(CodeLanguage::from_name) {
+                    Some(language) => Some(("object", language, object)),
+                    None => {
+                        self.warn(
+                            Some(line),
+                            format!(
+                                "literalinclude of '{}': object needs a language of python, rust, go or javascript",
+                                filename
+                            ),
+                            WarningType::LiteralInclude,
+                        );
+                        return format!(
+                            "<!-- literalinclude error: no object language for '{}' -->",
+                            filename
+                        );
+                    }
+                }
+            }
+            (None, None) => None,
+        };
+        let content = match object {
+            Some((option, language, path)) => match code_objects::find_object(&content, language, path) {
+                Some(extracted) => extracted,
+                None => {
+                    self.warn(
+                        Some(line),
+                        format!("literalinclude: {} '{}' not found in '{}'", option, path, filename),
+                        WarningType::LiteralInclude,
+                    );
+                    return format!(
+                        "<!-- literalinclude error: could not find {} '{}' in '{}' -->",
+                        option, path, filename
+                    );
+                }
+            },
+            None => content,
+        };
+
+        let (mut lines, problems) = self.select_literalinclude_lines(&content, options);
+        for problem in problems {
+            self.warn(
+                Some(line),
+                format!("literalinclude of '{}': {}", filename, problem),
+                WarningType::LiteralInclude,
+            );
+        }
+        if lines.is_empty() && !content.is_empty() {
+            self.warn(
+                Some(line),
+                format!("literalinclude of '{}' selected no lines", filename),
+                WarningType::LiteralInclude,
+            );
+        }
+
+        // Prepended and appended lines count for emphasize-lines, as in Sphinx
+        if let Some(prepend) = options.get("prepend") {
+            lines.insert(0, prepend);
+        }
+        if let Some(append) = options.get("append") {
+            lines.push(append);
         }
+        let emphasized: std::collections::BTreeSet<usize> = match options.get("emphasize-lines") {
+            Some(spec) => {
+                if let Some(problem) = Self::check_lines_spec(spec, lines.len()) {
+                    self.warn(
+                        Some(line),
+                        format!("literalinclude of '{}': emphasize-lines: {}", filename, problem),
+                        WarningType::LiteralInclude,
+                    );
+                }
+                self.parse_lines_spec(spec, lines.len()).into_iter().collect()
+            }
+            None => Default::default(),
+        };
 
         let filtered_content = lines.join("\n");
 
@@ -580,119 +2064,181 @@ impl HtmlRenderer {
             .unwrap_or_else(|| "text".to_string());
 
         // Apply syntax highlighting
-        let theme = &self.theme_set.themes[&self.theme_name];
         let syntax = self
             .syntax_set
             .find_syntax_by_token(&language)
             .or_else(|| self.syntax_set.find_syntax_by_extension(&language))
             .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+        let highlighted = self.highlight_with_syntax(&filtered_content, syntax);
+        let highlighted = if emphasized.is_empty() {
+            highlighted
+        } else {
+            self.decorate_lines(&highlighted, None, &emphasized)
+        };
 
-        let highlighted = match highlighted_html_for_string(&filtered_content, &self.syntax_set, syntax, theme) {
-            Ok(html) => html,
-            Err(_) => {
-                let escaped = html_escape::encode_text(&filtered_content);
-                format!("<pre><code>{}</code></pre>", escaped)
+        let block = format!("<div class=\"highlight-{} notranslate\">{}</div>", language, highlighted);
+        // The caption may name the file with a {filename} placeholder
+        let caption = options
+            .get("caption")
+            .map(|caption| html_escape::encode_text(&caption.replace("{filename}", filename)).into_owned());
+        self.wrap_code_block(block, caption, options.get("name").and_then(|name| self.name_id(name)))
+    }
+
+    /// Apply the line selection options of a literalinclude to `content`,
+    /// returning the selected lines and a description of every option that
+    /// did not match the file.
+    fn select_literalinclude_lines<'a>(
+        &self,
+        content: &'a str,
+        options: &HashMap<String, String>,
+    ) -> (Vec<&'a str>, Vec<String>) {
+        let mut lines: Vec<&str> = content.lines().collect();
+        let mut problems = Vec::new();
+
+        // Marker options find the first line containing the given text
+        let markers = [("start-after", 1, true), ("start-at", 0, true), ("end-before", 0, false)];
+        for (option, offset, keep_tail) in markers {
+            if let Some(marker) = options.get(option) {
+                match lines.iter().position(|line| line.contains(marker.as_str())) {
+                    Some(pos) if keep_tail => lines = lines[pos + offset..].to_vec(),
+                    Some(pos) => lines = lines[..pos].to_vec(),
+                    None => problems.push(format!("{} marker '{}' not found", option, marker)),
+                }
+            }
+        }
+
+        // Handle start-line option (0-based: skip first N lines, like Sphinx)
+        if let Some(start_line) = options.get("start-line") {
+            match start_line.parse::<usize>() {
+                Ok(start) if start <= lines.len() => lines = lines[start..].to_vec(),
+                Ok(start) => problems.push(format!(
+                    "start-line {} is beyond the end of the file ({} lines)",
+                    start,
+                    lines.len()
+                )),
+                Err(_) => problems.push(format!("invalid start-line '{}'", start_line)),
+            }
+        }
+
+        // Handle end-line option (1-based indexing, exclusive)
+        if let Some(end_line) = options.get("end-line") {
+            match end_line.parse::<usize>() {
+                Ok(end) if end > 0 && end <= lines.len() => lines = lines[..end].to_vec(),
+                Ok(end) if end > lines.len() => problems.push(format!(
+                    "end-line {} is beyond the end of the file ({} lines)",
+                    end,
+                    lines.len()
+                )),
+                _ => problems.push(format!("invalid end-line '{}'", end_line)),
+            }
+        }
+
+        // Handle :lines: option (e.g., "1-10", "1,3,5-7")
+        if let Some(lines_spec) = options.get("lines") {
+            let selected_lines = self.parse_lines_spec(lines_spec, lines.len());
+            if let Some(problem) = Self::check_lines_spec(lines_spec, lines.len()) {
+                problems.push(problem);
+            }
+            lines = selected_lines
+                .iter()
+                .filter_map(|&i| lines.get(i).copied())
+                .collect();
+        }
+
+        // Handle dedent option
+        if let Some(dedent_str) = options.get("dedent") {
+            if let Ok(dedent) = dedent_str.parse::<usize>() {
+                lines = lines
+                    .iter()
+                    .map(|line| {
+                        if line.len() >= dedent {
+                            &line[dedent.min(line.len() - line.trim_start().len())..]
+                        } else {
+                            line.trim_start()
+                        }
+                    })
+                    .collect();
+            } else {
+                problems.push(format!("invalid dedent '{}'", dedent_str));
             }
-        };
-
-        // Build the final HTML
-        let mut html = String::new();
-
-        // Add caption if present
-        if let Some(caption) = options.get("caption") {
-            // Replace {filename} placeholder with actual filename
-            let caption_text = caption.replace("{filename}", filename);
-            html.push_str(&format!(
-                "<div class=\"code-block-caption\"><span class=\"caption-text\">{}</span></div>\n",
-                html_escape::encode_text(&caption_text)
-            ));
         }
 
-        html.push_str(&format!(
-            "<div class=\"highlight-{} notranslate\">{}</div>",
-            language, highlighted
-        ));
+        (lines, problems)
+    }
 
-        html
+    /// Describe the first part of a `:lines:` spec that is malformed or
+    /// refers to lines past the end of the file.
+    fn check_lines_spec(spec: &str, total_lines: usize) -> Option<String> {
+        for part in spec.split(',').map(str::trim) {
+            let bounds: Vec<&str> = part.split('-').map(str::trim).collect();
+            let numbers: Vec<usize> = bounds
+                .iter()
+                .filter(|b| !b.is_empty())
+                .filter_map(|b| b.parse::<usize>().ok())
+                .collect();
+            let open_ends = bounds.iter().filter(|b| b.is_empty()).count();
+            if bounds.len() > 2
+                || numbers.is_empty()
+                || numbers.len() + open_ends != bounds.len()
+                || (bounds.len() == 1 && open_ends > 0)
+            {
+                return Some(format!("invalid lines spec '{}'", part));
+            }
+            if numbers.iter().any(|&n| n == 0 || n > total_lines) {
+                return Some(format!(
+                    "lines '{}' out of range (file has {} lines)",
+                    part, total_lines
+                ));
+            }
+        }
+        None
     }
 
     /// Render an include directive by reading a file, optionally filtering lines,
-    /// parsing as RST, and rendering to HTML.
+    /// parsing as RST, and rendering to HTML. `:literal:` and `:code:` render
+    /// the file as a literal block instead.
     fn render_include(&self, filename: &str, options: &HashMap<String, String>) -> String {
-        // Resolve the file path relative to source_dir
-        let file_path = if let Some(ref source_dir) = self.source_dir {
-            source_dir.join(filename)
-        } else {
-            PathBuf::from(filename)
-        };
+        let file_path = self.include_path(filename);
 
         // Read the file content
-        let content = match std::fs::read_to_string(&file_path) {
+        let encoding = options.get("encoding").map(String::as_str);
+        let content = match utils::read_text_file(&file_path, encoding) {
             Ok(content) => content,
             Err(e) => {
                 return format!(
-                    "<!-- include error: could not read '{}': {} -->",
+                    "<!-- include error: could not read '{}': {:#} -->",
                     filename, e
                 );
             }
         };
 
-        // Apply line-based filtering
-        let mut lines: Vec<&str> = content.lines().collect();
-
-        // Handle start-line option (0-based: skip first N lines, like Sphinx)
-        if let Some(start_line) = options.get("start-line") {
-            if let Ok(start) = start_line.parse::<usize>() {
-                if start <= lines.len() {
-                    lines = lines[start..].to_vec();
-                }
-            }
-        }
-
-        // Handle end-line option (1-based indexing, exclusive like Sphinx)
-        if let Some(end_line) = options.get("end-line") {
-            if let Ok(end) = end_line.parse::<usize>() {
-                if end > 0 && end <= lines.len() {
-                    lines = lines[..end].to_vec();
-                }
-            }
-        }
+        // Apply line-based filtering and expand hard tabs
+        let tab_width = options
+            .get("tab-width")
+            .and_then(|width| width.trim().parse::<i64>().ok())
+            .unwrap_or(DEFAULT_TAB_WIDTH);
+        let lines: Vec<String> = select_include_lines(&content, options)
+            .into_iter()
+            .map(|line| expand_tabs(line, tab_width))
+            .collect();
 
-        // Handle start-after option (find line containing this text and start after it)
-        if let Some(start_after) = options.get("start-after") {
-            if let Some(pos) = lines.iter().position(|line| line.contains(start_after.as_str())) {
-                lines = lines[pos + 1..].to_vec();
-            }
+        if let Some(language) = options.get("code") {
+            let language = Some(language.trim()).filter(|language| !language.is_empty());
+            return self.highlight_code(&lines.join("\n"), language);
         }
-
-        // Handle end-before option (find line containing this text and end before it)
-        if let Some(end_before) = options.get("end-before") {
-            if let Some(pos) = lines.iter().position(|line| line.contains(end_before.as_str())) {
-                lines = lines[..pos].to_vec();
-            }
+        if options.contains_key("literal") {
+            return self.highlight_code(&lines.join("\n"), None);
         }
 
         let filtered_content = lines.join("\n");
 
-        // Parse the content as RST
-        let config = BuildConfig::default();
-        let parser = match Parser::new(&config) {
-            Ok(p) => p,
-            Err(e) => {
-                return format!(
-                    "<!-- include error: could not create parser: {} -->",
-                    e
-                );
-            }
-        };
-
         // Parse the included content - use a dummy path with .rst extension for RST parsing
         let dummy_path = file_path.with_extension("rst");
-        let document = match parser.parse(&dummy_path, &filtered_content) {
+        let document = match self.parse_rst_fragment(&dummy_path, &filtered_content) {
             Ok(doc) => doc,
             Err(e) => {
                 return format!(
-                    "<!-- include error: could not parse '{}': {} -->",
+                    "<!-- include error: could not parse '{}': {:#} -->",
                     filename, e
                 );
             }
@@ -702,6 +2248,44 @@ impl HtmlRenderer {
         self.render_document_content(&document.content)
     }
 
+    /// Render the block markup in the content of a `directive`
+    fn render_rst_blocks(&self, directive: &str, content: &str) -> String {
+        self.render_rst_children(&self.parse_rst_children(directive, content))
+    }
+
+    /// The block markup in the content of a `directive`, parsed; content
+    /// that doesn't parse is one paragraph
+    fn parse_rst_children(&self, directive: &str, content: &str) -> Vec<RstNode> {
+        let path = PathBuf::from(self.current_doc.as_deref().unwrap_or(directive)).with_extension("rst");
+        match self.parse_rst_fragment(&path, content).map(|doc| doc.content) {
+            Ok(DocumentContent::RestructuredText(rst)) => rst.ast,
+            _ => vec![RstNode::Paragraph {
+                content: content.to_string(),
+                line: 0,
+            }],
+        }
+    }
+
+    /// Render the parsed content of a directive
+    fn render_rst_children(&self, children: &[RstNode]) -> String {
+        self.render_document_content(&DocumentContent::RestructuredText(RstContent {
+            raw: String::new(),
+            ast: children.to_vec(),
+            directives: Vec::new(),
+            title_styles: Vec::new(),
+            messages: Vec::new(),
+        }))
+    }
+
+    /// Parse RST that is part of the current document, with the shared
+    /// parser if there is one
+    fn parse_rst_fragment(&self, path: &std::path::Path, content: &str) -> anyhow::Result<crate::document::Document> {
+        match &self.parser {
+            Some(parser) => parser.parse(path, content),
+            None => Parser::new(&BuildConfig::default())?.parse(path, content),
+        }
+    }
+
     /// Parse a lines specification like "1-10", "1,3,5-7", "1-10,15,20-25"
     /// Returns 0-based indices
     fn parse_lines_spec(&self, spec: &str, total_lines: usize) -> Vec<usize> {
@@ -710,10 +2294,12 @@ impl HtmlRenderer {
         for part in spec.split(',') {
             let part = part.trim();
             if part.contains('-') {
-                // Range like "1-10"
-                let parts: Vec<&str> = part.split('-').collect();
+                // Range like "1-10"; either end may be omitted ("-5", "3-")
+                let parts: Vec<&str> = part.split('-').map(str::trim).collect();
                 if parts.len() == 2 {
-                    if let (Ok(start), Ok(end)) = (parts[0].trim().parse::<usize>(), parts[1].trim().parse::<usize>()) {
+                    let start = if parts[0].is_empty() { Ok(1) } else { parts[0].parse::<usize>() };
+                    let end = if parts[1].is_empty() { Ok(total_lines) } else { parts[1].parse::<usize>() };
+                    if let (Ok(start), Ok(end)) = (start, end) {
                         for i in start..=end {
                             if i > 0 && i <= total_lines {
                                 result.push(i - 1); // Convert to 0-based
@@ -734,274 +2320,116 @@ impl HtmlRenderer {
         result
     }
 
-    /// Extract a Python object (function, class, or method) from source code.
-    /// Supports formats like "function_name", "ClassName", or "ClassName.method_name"
-    fn extract_python_object(&self, content: &str, pyobject: &str) -> Option<String> {
-        let lines: Vec<&str> = content.lines().collect();
-
-        // Check if we're looking for a method (Class.method format)
-        if let Some(dot_pos) = pyobject.find('.') {
-            let class_name = &pyobject[..dot_pos];
-            let method_name = &pyobject[dot_pos + 1..];
+    /// Render inline RST markup (bold, italic, code, roles, references).
+    pub fn render_rst_inline(&self, text: &str) -> String {
+        self.render_inline(&inline::parse(text, Syntax::Rst))
+    }
 
-            // First find the class
-            if let Some((class_start, class_end)) = self.find_python_object_range(&lines, class_name, 0) {
-                // Then find the method within the class
-                let class_lines: Vec<&str> = lines[class_start..class_end].to_vec();
-                if let Some((method_start, method_end)) = self.find_python_object_range(&class_lines, method_name, 1) {
-                    return Some(class_lines[method_start..method_end].join("\n"));
+    /// Render tokenized inline markup
+    fn render_inline(&self, nodes: &[Inline]) -> String {
+        let mut html = String::new();
+        // Last character before each node, which decides the side of quotes
+        let mut prev = None;
+        for (index, node) in nodes.iter().enumerate() {
+            match node {
+                Inline::Text(text) => match &self.smartquotes {
+                    Some(smartquotes) => {
+                        let after = nodes.get(index + 1).and_then(|next| inline::plain_text(std::slice::from_ref(next)).chars().next());
+                        html.push_str(&html_escape::encode_text(&smartquotes.educate(text, prev, after)))
+                    }
+                    None => html.push_str(&html_escape::encode_text(text)),
+                },
+                Inline::Literal(code) => {
+                    html.push_str(&format!("<code>{}</code>", html_escape::encode_text(code)))
                 }
+                Inline::Emphasis(children) => {
+                    html.push_str(&format!("<em>{}</em>", self.render_inline(children)))
+                }
+                Inline::Strong(children) => {
+                    html.push_str(&format!("<strong>{}</strong>", self.render_inline(children)))
+                }
+                // The default role shows its content as code
+                Inline::Role { name: None, content } => html.push_str(&format!(
+                    "<code class=\"code docutils literal notranslate\"><span class=\"pre\">{}</span></code>",
+                    html_escape::encode_text(content)
+                )),
+                Inline::Role { name: Some(name), content } => html.push_str(&self.render_role(name, content)),
+                Inline::Reference { text, target } => html.push_str(&match target {
+                    ReferenceTarget::Uri(url) => format!(
+                        "<a class=\"reference external\" href=\"{}\">{}</a>",
+                        html_escape::encode_text(url),
+                        html_escape::encode_text(text)
+                    ),
+                    ReferenceTarget::Name(name) => format!(
+                        "<a class=\"reference internal\" href=\"#{}\">{}</a>",
+                        slugify(name),
+                        html_escape::encode_text(text)
+                    ),
+                }),
+                Inline::FootnoteReference(label) => html.push_str(&self.render_footnote_reference(label)),
+                // Links with a scheme are marked external like RST ones
+                Inline::Link { children, url } => html.push_str(&format!(
+                    "<a{} href=\"{}\">{}</a>",
+                    if has_url_scheme(url) { " class=\"reference external\"" } else { "" },
+                    html_escape::encode_text(url),
+                    self.render_inline(children)
+                )),
             }
-            return None;
-        }
-
-        // Looking for a top-level function or class
-        if let Some((start, end)) = self.find_python_object_range(&lines, pyobject, 0) {
-            return Some(lines[start..end].join("\n"));
+            prev = inline::plain_text(std::slice::from_ref(node)).chars().last().or(prev);
         }
-
-        None
+        html
     }
 
-    /// Find the line range (start, end) of a Python object definition.
-    /// `min_indent` is the minimum indentation level to look for (0 for top-level, 1 for methods inside a class)
-    fn find_python_object_range(&self, lines: &[&str], name: &str, min_indent: usize) -> Option<(usize, usize)> {
-        let def_pattern = format!("def {}(", name);
-        let class_pattern = format!("class {}:", name);
-        let class_pattern_paren = format!("class {}(", name);
-
-        let mut start_line = None;
-        let mut start_indent = 0;
-
-        for (i, line) in lines.iter().enumerate() {
-            let trimmed = line.trim_start();
-            let indent = line.len() - trimmed.len();
-            let indent_level = indent / 4; // Assuming 4-space indentation (also handle tabs below)
-
-            // Check if this line defines the object we're looking for
-            if trimmed.starts_with(&def_pattern)
-                || trimmed.starts_with(&class_pattern)
-                || trimmed.starts_with(&class_pattern_paren)
-            {
-                // Check if indentation level matches what we're looking for
-                if indent_level >= min_indent {
-                    start_line = Some(i);
-                    start_indent = indent;
-                    break;
-                }
+    /// Render a role with its content, which may be `title <target>`
+    fn render_role(&self, name: &str, content: &str) -> String {
+        if name == "index" {
+            let (text, target) = genindex::index_role(content);
+            for error in target.errors {
+                self.warn(None, error, WarningType::InvalidRole);
             }
+            return format!("{}{}", self.index_anchor(&target.id), html_escape::encode_text(&text));
         }
-
-        let start = start_line?;
-
-        // Find where this object ends (next line at same or lower indentation that's not empty/comment)
-        let mut end = lines.len();
-        for (i, line) in lines.iter().enumerate().skip(start + 1) {
-            let trimmed = line.trim();
-
-            // Skip empty lines and comments
-            if trimmed.is_empty() || trimmed.starts_with('#') {
-                continue;
-            }
-
-            let indent = line.len() - line.trim_start().len();
-
-            // If we hit a line with same or less indentation, we've exited the object
-            // But we need to handle decorators - they start at same indent as def/class
-            if indent <= start_indent {
-                // Check if it's a decorator for the same object (shouldn't happen after start)
-                // or if it's a new definition/statement
-                let is_decorator = trimmed.starts_with('@');
-                if !is_decorator {
-                    end = i;
-                    break;
-                }
-            }
+        if matches!(name, "cite" | "cite:p" | "cite:t") {
+            return self.render_cite(name == "cite:t", content);
         }
-
-        // Trim trailing empty lines
-        while end > start + 1 && lines[end - 1].trim().is_empty() {
-            end -= 1;
+        if name == "math" {
+            self.use_feature(PageFeature::Math);
         }
 
-        Some((start, end))
-    }
-
-    /// Render inline RST markup (bold, italic, code, roles, references).
-    pub fn render_rst_inline(&self, text: &str) -> String {
-        // Process roles FIRST on unescaped text to preserve angle brackets in "text <target>" format
-        // We use a placeholder to protect the role output from subsequent escaping
-        let role_re = Regex::new(r":([a-zA-Z][a-zA-Z0-9_:-]*):`([^`]+)`").unwrap();
-        let mut role_replacements: Vec<String> = Vec::new();
-
-        let result_with_placeholders = role_re
-            .replace_all(text, |caps: &regex::Captures| {
-                let role_name = &caps[1];
-                let role_content = &caps[2];
-
-                // Parse role content for "text <target>" format
-                let (display_text, target) = if let Some(angle_pos) = role_content.find('<') {
-                    if role_content.ends_with('>') {
-                        let display_text = role_content[..angle_pos].trim();
-                        let target = &role_content[angle_pos + 1..role_content.len() - 1];
-                        (Some(display_text.to_string()), target.to_string())
-                    } else {
-                        (None, role_content.to_string())
-                    }
-                } else {
-                    (None, role_content.to_string())
-                };
-
-                let role = Role {
-                    name: role_name.to_string(),
-                    target,
-                    text: display_text,
-                    line_number: 0,
-                    source_file: String::new(),
-                };
-
-                let html = match self.role_registry.process_role(&role) {
-                    Ok(html) => html,
-                    Err(_) => format!("<!-- Unknown role: {} -->", role_name),
-                };
-
-                // Store the HTML and return a placeholder
-                let placeholder = format!("\x00ROLE{}\x00", role_replacements.len());
-                role_replacements.push(html);
-                placeholder
-            })
-            .to_string();
-
-        // Process references on unescaped text: `text`_ or `text <URL>`_
-        let ref_re = Regex::new(r"`([^`]+)`_").unwrap();
-        let result_with_placeholders = ref_re
-            .replace_all(&result_with_placeholders, |caps: &regex::Captures| {
-                let ref_text = &caps[1];
-
-                // Check for external link format: `text <URL>`_
-                let html = if let Some(angle_pos) = ref_text.rfind('<') {
-                    if ref_text.ends_with('>') {
-                        // External link with explicit URL
-                        let display_text = ref_text[..angle_pos].trim();
-                        let url = &ref_text[angle_pos + 1..ref_text.len() - 1];
-                        format!(
-                            "<a class=\"reference external\" href=\"{}\">{}</a>",
-                            html_escape::encode_text(url),
-                            html_escape::encode_text(display_text)
-                        )
-                    } else {
-                        // Malformed, treat as internal reference
-                        let anchor = slugify(ref_text);
-                        format!(
-                            "<a class=\"reference internal\" href=\"#{}\">{}</a>",
-                            anchor,
-                            html_escape::encode_text(ref_text)
-                        )
-                    }
-                } else {
-                    // Internal reference
-                    let anchor = slugify(ref_text);
-                    format!(
-                        "<a class=\"reference internal\" href=\"#{}\">{}</a>",
-                        anchor,
-                        html_escape::encode_text(ref_text)
-                    )
-                };
-
-                let placeholder = format!("\x00ROLE{}\x00", role_replacements.len());
-                role_replacements.push(html);
-                placeholder
-            })
-            .to_string();
-
-        // Process bare word references: Word_ (without backticks)
-        // These are internal references to link targets
-        let bare_ref_re = Regex::new(r"\b([A-Za-z][A-Za-z0-9_.]*[A-Za-z0-9])_\b").unwrap();
-        let result_with_placeholders = bare_ref_re
-            .replace_all(&result_with_placeholders, |caps: &regex::Captures| {
-                let ref_text = &caps[1];
-                let anchor = slugify(ref_text);
-                let html = format!(
-                    "<a class=\"reference internal\" href=\"#{}\">{}</a>",
-                    anchor,
-                    html_escape::encode_text(ref_text)
-                );
-                let placeholder = format!("\x00ROLE{}\x00", role_replacements.len());
-                role_replacements.push(html);
-                placeholder
-            })
-            .to_string();
-
-        // Now HTML escape the result (placeholders will be preserved since they don't contain special chars)
-        let mut result = html_escape::encode_text(&result_with_placeholders).to_string();
-
-        // Process inline code with placeholders to protect content from bold/italic processing
-        // Double backticks: ``code``
-        let code_re = Regex::new(r"``([^`]+)``").unwrap();
-        result = code_re
-            .replace_all(&result, |caps: &regex::Captures| {
-                let code_content = &caps[1];
-                let html = format!("<code>{}</code>", code_content);
-                let placeholder = format!("\x00ROLE{}\x00", role_replacements.len());
-                role_replacements.push(html);
-                placeholder
-            })
-            .to_string();
-
-        // Single backtick inline code: `code`
-        // References (`text`_) were already processed and replaced with placeholders,
-        // so we can safely match remaining single backticks
-        let single_code_re = Regex::new(r"`([^`]+)`").unwrap();
-        result = single_code_re
-            .replace_all(&result, |caps: &regex::Captures| {
-                let code_content = &caps[1];
-                let html = format!(
-                    "<code class=\"code docutils literal notranslate\"><span class=\"pre\">{}</span></code>",
-                    code_content
-                );
-                let placeholder = format!("\x00ROLE{}\x00", role_replacements.len());
-                role_replacements.push(html);
-                placeholder
-            })
-            .to_string();
-
-        // Process bold: **text** (must be done before italic)
-        let bold_re = Regex::new(r"\*\*([^*]+)\*\*").unwrap();
-        result = bold_re
-            .replace_all(&result, "<strong>$1</strong>")
-            .to_string();
-
-        // Process italic: *text* (after bold replacement, so ** is already gone)
-        let italic_re = Regex::new(r"\*([^*]+)\*").unwrap();
-        result = italic_re.replace_all(&result, "<em>$1</em>").to_string();
-
-        // Restore all HTML from placeholders (roles and code)
-        for (i, html) in role_replacements.iter().enumerate() {
-            let placeholder = format!("\x00ROLE{}\x00", i);
-            result = result.replace(&placeholder, html);
+        let (title, target) = inline::split_title_target(content);
+        let role = Role {
+            name: name.to_string(),
+            target: target.to_string(),
+            text: (title != content.trim()).then(|| title.to_string()),
+            line_number: 0,
+            source_file: String::new(),
+        };
+        match self.role_registry.process_role(&role) {
+            Ok(html) => html,
+            Err(_) => format!("<!-- Unknown role: {} -->", name),
         }
-
-        result
     }
 
     /// Render Markdown content to HTML.
     pub fn render_markdown(&self, content: &MarkdownContent) -> String {
-        let mut html = String::new();
+        render_to_string(|out| self.write_markdown(content, out))
+    }
 
+    fn write_markdown<W: io::Write>(&self, content: &MarkdownContent, html: &mut W) -> io::Result<()> {
+        let mut slugs = SlugRegistry::new();
         for node in &content.ast {
-            html.push_str(&self.render_markdown_node(node));
-            html.push('\n');
+            writeln!(html, "{}", self.render_markdown_node(node, &mut slugs))?;
         }
 
-        html
+        Ok(())
     }
 
     /// Render a single Markdown node to HTML.
-    fn render_markdown_node(&self, node: &MarkdownNode) -> String {
+    fn render_markdown_node(&self, node: &MarkdownNode, slugs: &mut SlugRegistry) -> String {
         match node {
             MarkdownNode::Heading { text, level, .. } => {
-                let slug = slugify(text);
-                let level = (*level).min(6).max(1);
+                let slug = slugs.heading_id(text);
+                let level = (*level).clamp(1, 6);
                 format!(
                     "<h{level} id=\"{slug}\">{text}</h{level}>",
                     level = level,
@@ -1027,10 +2455,17 @@ impl HtmlRenderer {
                 let tag = if *ordered { "ol" } else { "ul" };
                 let items_html: String = items
                     .iter()
-                    .map(|item| format!("<li>{}</li>", self.render_markdown_inline(item)))
+                    .map(|item| {
+                        self.render_task_item(item, |text| self.render_markdown_inline(text))
+                            .unwrap_or_else(|| format!("<li>{}</li>", self.render_markdown_inline(item)))
+                    })
                     .collect::<Vec<_>>()
                     .join("\n");
-                format!("<{}>\n{}\n</{}>", tag, items_html, tag)
+                if items.iter().any(|item| tasklist::split_task(item).is_some()) {
+                    format!("<{} class=\"contains-task-list\">\n{}\n</{}>", tag, items_html, tag)
+                } else {
+                    format!("<{}>\n{}\n</{}>", tag, items_html, tag)
+                }
             }
 
             MarkdownNode::Table { headers, rows, .. } => {
@@ -1068,93 +2503,202 @@ impl HtmlRenderer {
         }
     }
 
-    /// Render inline Markdown markup (bold, italic, code, links).
+    /// Render inline Markdown markup (bold, italic, code, links, roles).
     fn render_markdown_inline(&self, text: &str) -> String {
-        let mut result = html_escape::encode_text(text).to_string();
-
-        // Process inline code: `code`
-        let code_re = Regex::new(r"`([^`]+)`").unwrap();
-        result = code_re
-            .replace_all(&result, "<code>$1</code>")
-            .to_string();
-
-        // Process bold: **text** or __text__ (must be done before italic)
-        let bold_star_re = Regex::new(r"\*\*([^*]+)\*\*").unwrap();
-        result = bold_star_re
-            .replace_all(&result, "<strong>$1</strong>")
-            .to_string();
-        let bold_under_re = Regex::new(r"__([^_]+)__").unwrap();
-        result = bold_under_re
-            .replace_all(&result, "<strong>$1</strong>")
-            .to_string();
-
-        // Process italic: *text* or _text_ (after bold replacement)
-        let italic_star_re = Regex::new(r"\*([^*]+)\*").unwrap();
-        result = italic_star_re
-            .replace_all(&result, "<em>$1</em>")
-            .to_string();
-        let italic_under_re = Regex::new(r"_([^_]+)_").unwrap();
-        result = italic_under_re
-            .replace_all(&result, "<em>$1</em>")
-            .to_string();
-
-        // Process links: [text](url)
-        let link_re = Regex::new(r"\[([^\]]+)\]\(([^)]+)\)").unwrap();
-        result = link_re
-            .replace_all(&result, |caps: &regex::Captures| {
-                let text = &caps[1];
-                let url = &caps[2];
-                format!("<a href=\"{}\">{}</a>", html_escape::encode_text(url), text)
-            })
-            .to_string();
+        self.render_inline(&inline::parse(text, Syntax::Markdown))
+    }
+}
 
-        result
+/// Collect the output of a writer-based render into a string
+/// Text before the name shown by an author directive
+fn author_label(directive: &str) -> Option<&'static str> {
+    match directive {
+        "sectionauthor" => Some("Section author: "),
+        "moduleauthor" => Some("Module author: "),
+        "codeauthor" => Some("Code author: "),
+        _ => None,
     }
 }
 
+fn render_to_string(render: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+    let mut html = Vec::new();
+    // Writing into a Vec cannot fail and the renderers only write UTF-8
+    render(&mut html).expect("rendering into memory failed");
+    String::from_utf8(html).expect("rendered HTML is not UTF-8")
+}
+
 /// Extract plain text from RST markup for use in slugs.
 /// Strips inline code backticks, roles like :ref: and :doc:, etc.
 pub fn extract_plain_text_for_slug(text: &str) -> String {
-    let mut result = text.to_string();
+    inline::plain_text(&inline::parse(text, Syntax::Rst))
+}
+
+/// Hands out the anchor ids of one document: the slug of a heading, or the
+/// slug with `-1`, `-2`, ... appended once it is taken. The renderer, the
+/// table of contents and the navigation all register the headings of a
+/// document in order, so they agree on the ids of repeated headings.
+#[derive(Debug, Default)]
+pub struct SlugRegistry {
+    taken: HashSet<String>,
+    /// Ids registered ahead for `:name:` options, by slug, in document order
+    reserved: HashMap<String, VecDeque<String>>,
+}
 
-    // Remove RST roles like :ref:`text <target>` -> text
-    // Match :role:`display text <target>` or :role:`target`
-    // Use a non-greedy match and trim the display text
-    let role_re = regex::Regex::new(r":(\w+):`([^`<]+?)(?:\s*<[^>]+>)?`").unwrap();
-    result = role_re
-        .replace_all(&result, |caps: &regex::Captures| caps[2].trim().to_string())
-        .to_string();
+impl SlugRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Unique id for a heading, from its text without markup, or an `idN`
+    /// id when the text has no letters or digits to make one from
+    pub fn heading_id(&mut self, text: &str) -> String {
+        self.unique(slugify(&extract_plain_text_for_slug(text)))
+    }
+
+    /// Register the id of a `:name:` option ahead of rendering, so
+    /// [`name_id`](Self::name_id) hands it out when the name comes up
+    pub fn reserve_name(&mut self, name: &str) -> Option<String> {
+        let slug = slugify(name);
+        if slug.is_empty() {
+            return None;
+        }
+        let id = self.unique(slug.clone());
+        self.reserved.entry(slug).or_default().push_back(id.clone());
+        Some(id)
+    }
 
-    // Remove inline code backticks: `text` -> text
-    let code_re = regex::Regex::new(r"`([^`]+)`").unwrap();
-    result = code_re.replace_all(&result, "$1").to_string();
+    /// Id of a `:name:` option: the one reserved for it, else the slug of
+    /// the name made unique. Names without letters or digits have no id.
+    pub fn name_id(&mut self, name: &str) -> Option<String> {
+        let slug = slugify(name);
+        if slug.is_empty() {
+            return None;
+        }
+        match self.reserved.get_mut(&slug).and_then(VecDeque::pop_front) {
+            Some(id) => Some(id),
+            None => Some(self.unique(slug)),
+        }
+    }
+
+    /// The next free id of the form `id1`, `id2`, ...
+    pub fn auto_id(&mut self) -> String {
+        let id = (1..)
+            .map(|number| format!("id{}", number))
+            .find(|candidate| !self.taken.contains(candidate))
+            .unwrap();
+        self.taken.insert(id.clone());
+        id
+    }
+
+    /// `slug` if it is free, else it with the first free numeric suffix; an
+    /// empty slug gets an `idN` id
+    pub fn unique(&mut self, slug: String) -> String {
+        if slug.is_empty() {
+            return self.auto_id();
+        }
+        let id = if self.taken.contains(&slug) {
+            (1..)
+                .map(|suffix| format!("{}-{}", slug, suffix))
+                .find(|candidate| !self.taken.contains(candidate))
+                .unwrap()
+        } else {
+            slug
+        };
+        self.taken.insert(id.clone());
+        id
+    }
+}
 
-    // Remove any remaining backticks
-    result = result.replace('`', "");
+/// Section number prefix, like "1.2. ", of a heading numbered by the build
+pub fn section_number_prefix(number: &[u32]) -> String {
+    let number: Vec<usize> = number.iter().map(|&part| part as usize).collect();
+    HtmlRenderer::section_number(&number, Some(usize::MAX))
+}
 
-    result
+/// Ids of the sections and named directives of an RST document, by the
+/// index of their node, registered in `slugs`. Sections come first, so the
+/// navigation agrees on their ids; a `:name:` that repeats one gets a suffix.
+pub fn rst_node_ids(ast: &[RstNode], slugs: &mut SlugRegistry) -> HashMap<usize, String> {
+    let mut ids: HashMap<usize, String> = ast
+        .iter()
+        .enumerate()
+        .filter_map(|(index, node)| match node {
+            RstNode::Title { text, .. } => Some((index, slugs.heading_id(text))),
+            _ => None,
+        })
+        .collect();
+    for (index, node) in ast.iter().enumerate() {
+        if let RstNode::Directive { options, .. } = node {
+            if let Some(id) = options.get("name").and_then(|name| slugs.reserve_name(name)) {
+                ids.insert(index, id);
+            }
+        }
+    }
+    ids
 }
 
-/// Convert text to a URL-safe slug for anchor IDs.
+/// Convert text to an anchor id the way docutils does for Sphinx: lower
+/// case, with accents removed by Unicode normalization, letters like `ø` or
+/// `ß` spelled in ASCII, every other run of characters turned into a hyphen,
+/// and leading digits and hyphens dropped. Text without ASCII letters has an
+/// empty slug; [`SlugRegistry`] gives it an `idN` id.
 pub fn slugify(text: &str) -> String {
-    text.to_lowercase()
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() {
-                c
-            } else if c.is_whitespace() || c == '-' || c == '_' || c == '.' {
-                // Treat periods as word separators (e.g., "Action.button" -> "action-button")
-                '-'
-            } else {
-                '\0'
-            }
-        })
-        .filter(|c| *c != '\0')
-        .collect::<String>()
-        .split('-')
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<_>>()
-        .join("-")
+    let mut ascii = String::with_capacity(text.len());
+    for c in text.to_lowercase().chars() {
+        match ascii_letters(c) {
+            Some(letters) => ascii.push_str(letters),
+            None => ascii.push(c),
+        }
+    }
+    let ascii: String = ascii.nfkd().filter(char::is_ascii).collect();
+
+    let mut slug = String::with_capacity(ascii.len());
+    for c in ascii.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_start_matches(|c: char| c == '-' || c.is_ascii_digit())
+        .trim_end_matches('-')
+        .to_string()
+}
+
+/// ASCII spelling of the lower-case letters that Unicode does not decompose,
+/// as docutils has them
+fn ascii_letters(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ß' => "sz",
+        'æ' => "ae",
+        'œ' => "oe",
+        'ȸ' => "db",
+        'ȹ' => "qp",
+        'ø' => "o",
+        'đ' => "d",
+        'ħ' => "h",
+        'ı' => "i",
+        'ł' => "l",
+        'ŧ' => "t",
+        'ƀ' | 'ƃ' => "b",
+        'ƈ' | 'ȼ' => "c",
+        'ƌ' => "d",
+        'ƒ' => "f",
+        'ƙ' => "k",
+        'ƚ' | 'ȴ' => "l",
+        'ƞ' | 'ȵ' => "n",
+        'ƥ' => "p",
+        'ƫ' | 'ƭ' | 'ȶ' => "t",
+        'ƴ' | 'ɏ' => "y",
+        'ƶ' | 'ȥ' | 'ɀ' => "z",
+        'ǥ' => "g",
+        'ȷ' | 'ɉ' => "j",
+        'ȿ' => "s",
+        'ɇ' => "e",
+        'ɋ' => "q",
+        'ɍ' => "r",
+        _ => return None,
+    })
 }
 
 #[cfg(test)]
@@ -1172,6 +2716,109 @@ mod tests {
         assert_eq!(slugify("Action.delete"), "action-delete");
     }
 
+    #[test]
+    fn test_slugify_matches_docutils_ids() {
+        assert_eq!(slugify("Café Crème"), "cafe-creme");
+        assert_eq!(slugify("Straße über Łódź"), "strasze-uber-lodz");
+        assert_eq!(slugify("C++ API"), "c-api");
+        assert_eq!(slugify("What's new?"), "what-s-new");
+        assert_eq!(slugify("1. Getting started"), "getting-started");
+        assert_eq!(slugify("ｆｕｌｌ width"), "full-width");
+        assert_eq!(slugify("はじめに"), "");
+
+        let mut slugs = SlugRegistry::new();
+        assert_eq!(slugs.heading_id("はじめに"), "id1");
+        assert_eq!(slugs.heading_id("インストール"), "id2");
+        assert_eq!(slugs.heading_id("2024"), "id3");
+    }
+
+    #[test]
+    fn test_slug_registry_suffixes_taken_ids() {
+        let mut slugs = SlugRegistry::new();
+        assert_eq!(slugs.heading_id("Example"), "example");
+        assert_eq!(slugs.heading_id("``Example``"), "example-1");
+        assert_eq!(slugs.heading_id("Example 2"), "example-2");
+        assert_eq!(slugs.heading_id("Example"), "example-3");
+        assert_eq!(slugs.unique("usage".to_string()), "usage");
+    }
+
+    #[test]
+    fn test_repeated_headings_get_distinct_ids() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let rst_content = "Guide\n=====\n\n.. contents::\n\nInstall\n-------\n\nExample\n~~~~~~~\n\nUsage\n-----\n\nExample\n~~~~~~~\n";
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(Path::new("doc.rst"), rst_content).unwrap();
+
+        let anchors: Vec<&str> = doc.toc.iter().map(|entry| entry.anchor.as_str()).collect();
+        assert_eq!(anchors, vec!["guide", "install", "example", "usage", "example-1"]);
+
+        let renderer = HtmlRenderer::new();
+        let html = renderer.render_document_content(&doc.content);
+        assert!(html.contains("<section id=\"example\">"), "got: {}", html);
+        assert!(html.contains("<section id=\"example-1\">"), "got: {}", html);
+        assert!(html.contains("href=\"#example-1\" title=\"Link to this heading\""), "got: {}", html);
+        assert!(html.contains("<a class=\"reference internal\" href=\"#example-1\""), "got: {}", html);
+    }
+
+    #[test]
+    fn test_named_directives_get_distinct_ids() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let rst_content = "Example\n=======\n\n.. code-block:: python\n   :caption: Setup\n   :name: Example\n\n   x = 1\n\n.. note::\n   :name: example\n\n   Nested.\n\n   .. figure:: plot.png\n      :name: Example\n";
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(Path::new("doc.rst"), rst_content).unwrap();
+
+        let renderer = HtmlRenderer::new();
+        let html = renderer.render_document_content(&doc.content);
+        let ids: Vec<&str> = html
+            .match_indices(" id=\"")
+            .map(|(pos, m)| {
+                let rest = &html[pos + m.len()..];
+                &rest[..rest.find('"').unwrap()]
+            })
+            .collect();
+        assert_eq!(ids, vec!["example", "example-1", "example-2", "example-3"], "got: {}", html);
+
+        // Numbered items link to the same ids
+        let enumerables = crate::numfig::enumerables(&match &doc.content {
+            DocumentContent::RestructuredText(rst) => rst.ast.clone(),
+            _ => unreachable!(),
+        });
+        assert_eq!(enumerables.len(), 1);
+        assert_eq!(enumerables[0].id, "example-1");
+    }
+
+    #[test]
+    fn test_inline_roles_nest_and_keep_backticks() {
+        let renderer = HtmlRenderer::new();
+        assert_eq!(
+            renderer.render_rst_inline("**see :ref:`the intro <intro>`** and :code:`a`b`"),
+            "<strong>see <a class=\"reference internal\" href=\"intro.html#intro\"><span class=\"std std-ref\">the intro</span></a></strong> and <code class=\"docutils literal notranslate\">a`b</code>"
+        );
+        assert_eq!(renderer.render_rst_inline(r"a \*b\* <c>"), "a *b* &lt;c&gt;");
+        assert_eq!(
+            renderer.render_markdown_inline("*see* {ref}`intro`"),
+            "<em>see</em> <a class=\"reference internal\" href=\"intro.html#intro\"><span class=\"std std-ref\">intro</span></a>"
+        );
+    }
+
+    #[test]
+    fn test_smartquotes_leave_code_and_roles_alone() {
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_smartquotes(Some(SmartQuotes::new("qDe", "en")));
+        assert_eq!(
+            renderer.render_rst_inline("\"*Quoted*\" -- ``\"raw\" --`` and :code:`'x'`..."),
+            "“<em>Quoted</em>” – <code>\"raw\" --</code> and <code class=\"docutils literal notranslate\">'x'</code>…"
+        );
+    }
+
     #[test]
     fn test_extract_plain_text_for_slug() {
         // Role with display text and target
@@ -1208,28 +2855,149 @@ mod tests {
             content: "This is a **bold** statement.".to_string(),
             line: 1,
         };
-        let html = renderer.render_rst_node(&node);
-        assert!(html.contains("<strong>bold</strong>"));
-        assert!(html.starts_with("<p>"));
-        assert!(html.ends_with("</p>"));
+        let html = renderer.render_rst_node(&node);
+        assert!(html.contains("<strong>bold</strong>"));
+        assert!(html.starts_with("<p>"));
+        assert!(html.ends_with("</p>"));
+    }
+
+    #[test]
+    fn test_render_rst_code_block() {
+        let renderer = HtmlRenderer::new();
+        let node = RstNode::CodeBlock {
+            language: Some("python".to_string()),
+            content: "print('hello')".to_string(),
+            line: 1,
+        };
+        let html = renderer.render_rst_node(&node);
+        // Syntect generates <pre style="..."> with inline styles
+        assert!(html.contains("<pre"), "should have pre tag");
+        // Code content should be present (possibly with span styling)
+        assert!(html.contains("print"), "should contain 'print'");
+        assert!(html.contains("hello"), "should contain 'hello'");
+        // Syntect uses inline styles for syntax highlighting
+        assert!(html.contains("style="), "should have inline styles for highlighting");
+    }
+
+    #[test]
+    fn test_media_directives() {
+        let source_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(source_dir.path().join("guide/media")).unwrap();
+        std::fs::write(source_dir.path().join("guide/media/demo.mp4"), b"video").unwrap();
+
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_source_dir(source_dir.path().to_path_buf());
+        renderer.set_current_doc("guide/intro");
+
+        let video = RstNode::Directive {
+            name: "video".to_string(),
+            args: vec!["media/demo.mp4".to_string()],
+            options: HashMap::from([
+                ("poster".to_string(), "/missing.png".to_string()),
+                ("muted".to_string(), String::new()),
+            ]),
+            content: String::new(),
+            line: 3,
+        };
+        let html = renderer.render_rst_node(&video);
+        assert!(html.contains("<video class=\"video\" controls muted poster=\"../_videos/missing.png\">"), "got {}", html);
+        assert!(html.contains("<source src=\"../_videos/guide/media/demo.mp4\" type=\"video/mp4\" />"), "got {}", html);
+        assert_eq!(
+            renderer.take_media_files(),
+            vec![
+                MediaFile {
+                    source: source_dir.path().join("guide/media/demo.mp4"),
+                    output: "_videos/guide/media/demo.mp4".to_string(),
+                },
+                MediaFile {
+                    source: source_dir.path().join("missing.png"),
+                    output: "_videos/missing.png".to_string(),
+                },
+            ]
+        );
+        let warnings = renderer.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("missing.png"));
+
+        let youtube = RstNode::Directive {
+            name: "youtube".to_string(),
+            args: vec!["https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1".to_string()],
+            options: HashMap::from([("title".to_string(), "Launch talk".to_string())]),
+            content: String::new(),
+            line: 8,
+        };
+        let html = renderer.render_rst_node(&youtube);
+        assert!(html.contains("src=\"https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ\""), "got {}", html);
+        assert!(html.contains("title=\"Launch talk\""), "got {}", html);
+        assert!(html.contains("sandbox=\"allow-scripts allow-same-origin allow-popups allow-presentation\""), "got {}", html);
+
+        let iframe = RstNode::Directive {
+            name: "iframe".to_string(),
+            args: vec!["https://example.org/demo".to_string()],
+            options: HashMap::from([
+                ("title".to_string(), "Demo".to_string()),
+                ("sandbox".to_string(), String::new()),
+            ]),
+            content: String::new(),
+            line: 12,
+        };
+        let html = renderer.render_rst_node(&iframe);
+        assert_eq!(
+            html,
+            "<div class=\"iframe-embed\"><iframe src=\"https://example.org/demo\" title=\"Demo\" sandbox=\"\" loading=\"lazy\" referrerpolicy=\"strict-origin-when-cross-origin\"></iframe></div>"
+        );
+
+        // Frames only keep their origin when the directive asks for it
+        let iframe = |src: &str, options: &[&str]| RstNode::Directive {
+            name: "iframe".to_string(),
+            args: vec![src.to_string()],
+            options: options.iter().map(|option| (option.to_string(), String::new())).collect(),
+            content: String::new(),
+            line: 14,
+        };
+        let html = renderer.render_rst_node(&iframe("/demo/index.html", &[]));
+        assert!(html.contains("sandbox=\"allow-scripts allow-popups allow-presentation\""), "got {}", html);
+        let html = renderer.render_rst_node(&iframe("https://example.org/demo", &["allow-same-origin"]));
+        assert!(html.contains("sandbox=\"allow-scripts allow-same-origin allow-popups allow-presentation\""), "got {}", html);
+
+        renderer.take_warnings();
+        for src in ["javascript:alert(1)", "JavaScript:alert(1)", "java\tscript:alert(1)", "data:text/html,<p>hi</p>"] {
+            let html = renderer.render_rst_node(&iframe(src, &[]));
+            assert!(!html.contains("<iframe"), "got {}", html);
+        }
+        let warnings = renderer.take_warnings();
+        assert_eq!(warnings.len(), 4);
+        assert!(warnings[0].message.contains("http or https URL, not 'javascript:'"), "{}", warnings[0].message);
     }
 
     #[test]
-    fn test_render_rst_code_block() {
-        let renderer = HtmlRenderer::new();
+    fn test_strict_csp_highlights_with_classes() {
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_strict_csp(true);
+
         let node = RstNode::CodeBlock {
             language: Some("python".to_string()),
-            content: "print('hello')".to_string(),
+            content: "def greet(name):\n    return name".to_string(),
             line: 1,
         };
         let html = renderer.render_rst_node(&node);
-        // Syntect generates <pre style="..."> with inline styles
-        assert!(html.contains("<pre"), "should have pre tag");
-        // Code content should be present (possibly with span styling)
-        assert!(html.contains("print"), "should contain 'print'");
-        assert!(html.contains("hello"), "should contain 'hello'");
-        // Syntect uses inline styles for syntax highlighting
-        assert!(html.contains("style="), "should have inline styles for highlighting");
+        assert!(html.contains("<pre class=\"hl-code\">"), "got {}", html);
+        assert!(html.contains("<span class=\"hl-"), "got {}", html);
+        assert!(!html.contains("style="), "got {}", html);
+
+        let directive = RstNode::Directive {
+            name: "code-block".to_string(),
+            args: vec!["python".to_string()],
+            options: HashMap::new(),
+            content: "x = 1".to_string(),
+            line: 1,
+        };
+        let html = renderer.render_rst_node(&directive);
+        assert!(html.contains("<pre class=\"hl-code\">"), "got {}", html);
+        assert!(!html.contains("style="), "got {}", html);
+
+        let css = renderer.highlight_css();
+        assert!(css.contains(".hl-code"), "got {}", css);
     }
 
     #[test]
@@ -1827,6 +3595,9 @@ Some text after.
             html.contains("hidden") || html.contains("display: none") || html.contains("display:none"),
             "hidden toctree should be hidden"
         );
+        // By a stylesheet class, which a strict Content-Security-Policy allows
+        assert!(html.contains("toctree-wrapper compound toctree-hidden"), "got: {}", html);
+        assert!(!html.contains("style="), "got: {}", html);
     }
 
     #[test]
@@ -1851,134 +3622,587 @@ Some text after.
         let parser = Parser::new(&config).unwrap();
         let doc = parser.parse(temp_file.path(), content).unwrap();
 
-        let renderer = HtmlRenderer::new();
-        let html = renderer.render_document_content(&doc.content);
+        let renderer = HtmlRenderer::new();
+        let html = renderer.render_document_content(&doc.content);
+
+        // Toctree wrapper should have both "toctree-wrapper" and "compound" classes
+        assert!(
+            html.contains("toctree-wrapper compound"),
+            "should have 'toctree-wrapper compound' class, got: {}",
+            html
+        );
+    }
+
+    #[test]
+    fn test_toctree_renders_inline_code_in_titles() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::io::Write;
+
+        // Simulate a toctree where titles contain backticks (inline code)
+        let content = r#"Index
+=====
+
+.. toctree::
+
+   after
+"#;
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".rst").unwrap();
+        temp_file.write_all(content.as_bytes()).unwrap();
+
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(temp_file.path(), content).unwrap();
+
+        let mut renderer = HtmlRenderer::new();
+        // Register a title with backticks (as would come from parsing the referenced doc)
+        renderer.register_document_title("after", "`after`");
+
+        let html = renderer.render_document_content(&doc.content);
+
+        // The backticks should be rendered as inline code, not as literal backticks
+        assert!(
+            html.contains("<code class=\"code docutils literal notranslate\">"),
+            "should render backticks as code element with proper classes, got: {}",
+            html
+        );
+        assert!(
+            html.contains("<span class=\"pre\">after</span>"),
+            "should contain the code content in a span, got: {}",
+            html
+        );
+        assert!(
+            !html.contains("`after`"),
+            "should NOT contain literal backticks in output, got: {}",
+            html
+        );
+    }
+
+    #[test]
+    fn test_toctree_nested_sections() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::io::Write;
+
+        let content = r#"Index
+=====
+
+.. toctree::
+
+   guide
+"#;
+
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".rst").unwrap();
+        temp_file.write_all(content.as_bytes()).unwrap();
+
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(temp_file.path(), content).unwrap();
+
+        let mut renderer = HtmlRenderer::new();
+        renderer.register_document_title("guide", "User Guide");
+        // Register sections for the "guide" document
+        renderer.register_document_sections(
+            "guide",
+            vec![
+                TocSection::new("Installation", "installation", 2),
+                TocSection::new("Configuration", "configuration", 2),
+            ],
+        );
+
+        let html = renderer.render_document_content(&doc.content);
+
+        // Should have toctree-l1 for the main entry
+        assert!(
+            html.contains("toctree-l1"),
+            "should have toctree-l1 class for main entry, got: {}",
+            html
+        );
+
+        // Should have nested ul with toctree-l2 entries for sections
+        assert!(
+            html.contains("toctree-l2"),
+            "should have toctree-l2 class for nested sections, got: {}",
+            html
+        );
+
+        // Should have links to sections with anchors
+        assert!(
+            html.contains("guide.html#installation"),
+            "should have link to installation section, got: {}",
+            html
+        );
+        assert!(
+            html.contains("guide.html#configuration"),
+            "should have link to configuration section, got: {}",
+            html
+        );
+
+        // Should have the section titles
+        assert!(
+            html.contains("Installation"),
+            "should have Installation title, got: {}",
+            html
+        );
+        assert!(
+            html.contains("Configuration"),
+            "should have Configuration title, got: {}",
+            html
+        );
+    }
+
+    /// Render a toctree over a small registered project: "guide" has a
+    /// nested section outline and "api/*" documents are available for globs
+    fn render_project_toctree(toctree: &str) -> (String, Vec<RenderWarning>) {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::io::Write;
+
+        let content = format!("Index\n=====\n\n{}", toctree);
+        let mut temp_file = tempfile::NamedTempFile::with_suffix(".rst").unwrap();
+        temp_file.write_all(content.as_bytes()).unwrap();
+
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(temp_file.path(), &content).unwrap();
+
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_current_doc("index");
+        for (path, title) in [
+            ("index", "Index"),
+            ("guide", "User Guide"),
+            ("api/b", "B API"),
+            ("api/a", "A API"),
+        ] {
+            renderer.register_document_title(path, title);
+        }
+        renderer.register_document_sections(
+            "guide",
+            vec![
+                TocSection::new("Install", "install", 2),
+                TocSection::new("From source", "from-source", 3),
+                TocSection::new("Usage", "usage", 2),
+            ],
+        );
+
+        let html = renderer.render_document_content(&doc.content);
+        (html, renderer.take_warnings())
+    }
+
+    #[test]
+    fn test_toctree_section_depth_and_numbering() {
+        let (html, _) = render_project_toctree(".. toctree::\n   :numbered:\n\n   guide\n");
+        assert!(html.contains("<span class=\"section-number\">1. </span>User Guide"), "got: {}", html);
+        assert!(html.contains("<span class=\"section-number\">1.1. </span>Install"), "got: {}", html);
+        assert!(html.contains("<span class=\"section-number\">1.1.1. </span>From source"), "got: {}", html);
+        assert!(html.contains("<span class=\"section-number\">1.2. </span>Usage"), "got: {}", html);
+        assert!(html.contains("<li class=\"toctree-l3\">"), "got: {}", html);
+
+        let (html, _) = render_project_toctree(".. toctree::\n   :maxdepth: 2\n   :numbered: 1\n\n   guide\n");
+        assert!(html.contains("guide.html#install"), "got: {}", html);
+        assert!(!html.contains("from-source"), "got: {}", html);
+        assert!(!html.contains("1.1. "), "got: {}", html);
+
+        for options in [":maxdepth: 1", ":titlesonly:"] {
+            let (html, _) = render_project_toctree(&format!(".. toctree::\n   {}\n\n   guide\n", options));
+            assert!(html.contains("User Guide"), "got: {}", html);
+            assert!(!html.contains("toctree-l2"), "{} got: {}", options, html);
+        }
+    }
+
+    #[test]
+    fn test_toctree_glob() {
+        let (html, warnings) =
+            render_project_toctree(".. toctree::\n   :glob:\n\n   guide\n   api/*\n   *\n   missing/*\n");
+        let links: Vec<&str> = html
+            .match_indices("toctree-l1\"><a class=\"reference internal\" href=\"")
+            .map(|(pos, m)| {
+                let rest = &html[pos + m.len()..];
+                &rest[..rest.find('"').unwrap()]
+            })
+            .collect();
+        // "*" does not cross directories, never matches the current document
+        // and skips documents listed before it
+        assert_eq!(links, vec!["guide.html", "api/a.html", "api/b.html"]);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].message.contains("'*'"));
+        assert!(warnings[1].message.contains("'missing/*'"));
+
+        // Without :glob: entries are taken literally
+        let (html, _) = render_project_toctree(".. toctree::\n\n   api/*\n");
+        assert!(html.contains("api/*.html"), "got: {}", html);
+    }
+
+    #[test]
+    fn test_contents_directive_lists_sections_with_backlinks() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let rst_content = r#"Guide
+=====
+
+.. contents:: On this page
+   :depth: 2
+
+Install
+-------
+
+From source
+~~~~~~~~~~~
+
+Deep
+^^^^
+
+Usage
+-----
+
+.. contents::
+   :local:
+   :backlinks: top
+
+Basics
+~~~~~~
+
+Advanced ``usage``
+~~~~~~~~~~~~~~~~~~
+"#;
+
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(Path::new("guide.rst"), rst_content).unwrap();
+        let renderer = HtmlRenderer::new();
+        let html = renderer.render_document_content(&doc.content);
+
+        let page_toc = "<nav class=\"contents\" id=\"on-this-page\">\n<p class=\"topic-title\">On this page</p>\n<ul class=\"simple\">\n\
+            <li><p><a class=\"reference internal\" href=\"#install\" id=\"id1\">Install</a></p>\n<ul>\n\
+            <li><p><a class=\"reference internal\" href=\"#from-source\" id=\"id2\">From source</a></p></li>\n</ul>\n</li>\n\
+            <li><p><a class=\"reference internal\" href=\"#usage\" id=\"id3\">Usage</a></p>\n<ul>\n\
+            <li><p><a class=\"reference internal\" href=\"#basics\" id=\"id4\">Basics</a></p></li>\n\
+            <li><p><a class=\"reference internal\" href=\"#advanced-usage\" id=\"id5\">Advanced <code>usage</code></a></p></li>\n</ul>\n</li>\n\
+            </ul>\n</nav>";
+        assert!(html.contains(page_toc), "got: {}", html);
+        assert!(!html.contains("href=\"#guide\" id="), "the document title is not a section: {}", html);
+        assert!(!html.contains("class=\"reference internal\" href=\"#deep\""), "depth limits the entries: {}", html);
+
+        let local_toc = "<nav class=\"contents local\" id=\"contents\">\n<ul class=\"simple\">\n\
+            <li><p><a class=\"reference internal\" href=\"#basics\">Basics</a></p></li>\n";
+        assert!(html.contains(local_toc), "got: {}", html);
+
+        assert!(html.contains("<h2><a class=\"toc-backref\" href=\"#id1\" role=\"doc-backlink\">Install</a>"));
+        assert!(html.contains("<h4>Deep<a class=\"headerlink\""));
+        // The later local contents with `:backlinks: top` takes over its sections
+        assert!(html.contains("<h3><a class=\"toc-backref\" href=\"#contents\" role=\"doc-backlink\">Basics</a>"));
+    }
+
+    #[test]
+    fn test_author_and_centered_directives() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let rst_content = "Title\n=====\n\n.. sectionauthor:: Jane *Doe*\n\n.. codeauthor:: Ravi Rao\n\n.. centered:: LICENSE **AGREEMENT**\n";
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(Path::new("doc.rst"), rst_content).unwrap();
+
+        let mut renderer = HtmlRenderer::new();
+        let html = renderer.render_document_content(&doc.content);
+        assert!(!html.contains("Jane"), "authors are hidden by default: {}", html);
+        assert!(!html.contains("directive"), "no directive text leaks: {}", html);
+        assert!(html.contains("<p class=\"centered\"><strong>LICENSE <strong>AGREEMENT</strong></strong></p>"));
+
+        renderer.set_show_authors(true);
+        let html = renderer.render_document_content(&doc.content);
+        assert!(html.contains("<p><em>Section author: Jane <em>Doe</em></em></p>"), "got: {}", html);
+        assert!(html.contains("<p><em>Code author: Ravi Rao</em></p>"), "got: {}", html);
+    }
+
+    #[test]
+    fn test_highlight_directive_sets_defaults_for_following_blocks() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let rst_content = "Title\n=====\n\nBefore::\n\n    x = 1\n\n.. highlight:: rust\n   :linenothreshold: 2\n\nShort::\n\n    let a = 1;\n\nLong::\n\n    let a = 1;\n    let b = 2;\n    let c = 3;\n\n.. code-block::\n\n   fn main() {}\n\n.. code-block:: python\n   :lineno-start: 9\n\n   x = 1\n";
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(Path::new("doc.rst"), rst_content).unwrap();
+
+        let renderer = HtmlRenderer::new();
+        let html = renderer.render_document_content(&doc.content);
+        assert!(!html.contains("highlight language"), "highlight renders nothing: {}", html);
+        assert_eq!(html.matches("<span class=\"linenos\">1</span>").count(), 1, "only the long block is numbered: {}", html);
+        assert!(html.contains("<span class=\"linenos\">3</span>"), "got: {}", html);
+        assert!(html.contains("<div class=\"highlight-rust notranslate\">"), "got: {}", html);
+        assert!(html.contains("<div class=\"highlight-python notranslate\">"), "got: {}", html);
+        assert!(html.contains("<span class=\"linenos\">9</span>"), "got: {}", html);
+
+        let first_block = &html[..html.find("</pre>").unwrap()];
+        assert!(first_block.contains(">x = 1</span>"), "blocks before the directive stay plain text: {}", html);
+    }
+
+    #[test]
+    fn test_highlight_language_for_literal_blocks() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let rst_content = "Title\n=====\n\nA session::\n\n    >>> print(1)\n\nSome JSON::\n\n    {\"a\": [1, 2]}\n\nA script::\n\n    #!/bin/sh\n    echo hi\n\nProse::\n\n    Just words.\n\n.. highlight:: rust\n\nRust::\n\n    {}\n";
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+        let doc = parser.parse(Path::new("doc.rst"), rst_content).unwrap();
+
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_strict_csp(true);
+        let plain = renderer.render_document_content(&doc.content);
+        assert_eq!(plain.matches("hl-text hl-plain").count(), 4, "got: {}", plain);
+
+        renderer.set_highlight_language(Some(GUESS_LANGUAGE.to_string()));
+        let html = renderer.render_document_content(&doc.content);
+        let blocks: Vec<&str> = html.split("</pre>").collect();
+        assert!(blocks[0].contains("hl-source hl-python"), "got: {}", html);
+        assert!(blocks[1].contains("hl-source hl-json"), "got: {}", html);
+        assert!(blocks[2].contains("hl-source hl-shell"), "got: {}", html);
+        assert!(blocks[3].contains("hl-text hl-plain"), "got: {}", html);
+        assert!(blocks[4].contains("hl-source hl-rust"), "got: {}", html);
+    }
+
+    #[test]
+    fn test_admonitions_render_titles_and_block_content() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let rst_content = "Title\n=====\n\n.. note:: Read *this*\n   first.\n\n   - one\n   - two\n\n   Code::\n\n      x = 1\n\n.. admonition:: Setting ``x``\n   :name: setting\n\n   Text.\n\n.. tip::\n   :class: wide\n\n   Text.\n\n.. admonition::\n\n   No title.\n";
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+        let doc = parser.parse(Path::new("doc.rst"), rst_content).unwrap();
+
+        let renderer = HtmlRenderer::new();
+        let html = renderer.render_document_content(&doc.content);
+        assert!(
+            html.contains("<div class=\"admonition note\">\n<p class=\"admonition-title\">Note</p>\n<p>Read <em>this</em> first.</p>\n<ul"),
+            "got: {}",
+            html
+        );
+        let note = &html[html.find("admonition note").unwrap()..html.find("admonition admonition-").unwrap()];
+        assert!(note.contains("<li>one</li>\n<li>two</li>"), "got: {}", note);
+        assert!(note.contains("<p>Code:</p>") && note.contains("</pre>"), "got: {}", note);
+        assert!(
+            html.contains("<div class=\"admonition admonition-setting-x\" id=\"setting\">\n<p class=\"admonition-title\">Setting <code>x</code></p>\n<p>Text.</p>\n</div>"),
+            "got: {}",
+            html
+        );
+        assert!(html.contains("<div class=\"admonition tip wide\">\n<p class=\"admonition-title\">Tip</p>"), "got: {}", html);
+        let warnings = renderer.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "admonition: a title argument is required");
+    }
+
+    #[test]
+    fn test_directive_content_is_parsed_once() {
+        use crate::config::{BuildConfig, CustomDirectiveConfig};
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let rst_content = "Title\n=====\n\n.. panel::\n\n   Text with *emphasis\n   across lines*, 1 < 2 & ``a <b>``.\n\n   - item\n\n.. warning:: Say **one\n   thing**.\n";
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+        let doc = parser.parse(Path::new("doc.rst"), rst_content).unwrap();
 
-        // Toctree wrapper should have both "toctree-wrapper" and "compound" classes
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_custom_directives(&BTreeMap::from([("panel".to_string(), CustomDirectiveConfig::default())]));
+        let html = renderer.render_document_content(&doc.content);
         assert!(
-            html.contains("toctree-wrapper compound"),
-            "should have 'toctree-wrapper compound' class, got: {}",
+            html.contains("<div class=\"panel\"><p>Text with <em>emphasis across lines</em>, 1 &lt; 2 &amp; <code>a &lt;b&gt;</code>.</p>\n<ul class=\"simple\">\n<li>item</li>\n</ul></div>"),
+            "got: {}",
+            html
+        );
+        assert!(
+            html.contains("<p class=\"admonition-title\">Warning</p>\n<p>Say <strong>one thing</strong>.</p>\n</div>"),
+            "got: {}",
             html
         );
     }
 
     #[test]
-    fn test_toctree_renders_inline_code_in_titles() {
+    fn test_dropdown_renders_details_with_block_content() {
         use crate::config::BuildConfig;
         use crate::parser::Parser;
-        use std::io::Write;
-
-        // Simulate a toctree where titles contain backticks (inline code)
-        let content = r#"Index
-=====
+        use std::path::Path;
 
-.. toctree::
+        let rst_content = "Title\n=====\n\n.. dropdown:: Why *this*?\n   :open:\n   :icon: fa fa-question\n   :class: faq\n   :name: why\n\n   Because.\n\n   .. code-block:: python\n\n      x = 1\n\n.. collapse::\n\n   Hidden.\n";
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(Path::new("faq.rst"), rst_content).unwrap();
 
-   after
-"#;
+        let html = HtmlRenderer::new().render_document_content(&doc.content);
+        assert!(
+            html.contains("<details class=\"dropdown faq\" id=\"why\" open>\n<summary class=\"dropdown-title\"><span class=\"dropdown-icon fa fa-question\" aria-hidden=\"true\"></span>Why <em>this</em>?</summary>\n<div class=\"dropdown-body\">\n<p>Because.</p>"),
+            "got: {}",
+            html
+        );
+        assert!(html.contains("<div class=\"highlight-python notranslate\">"), "got: {}", html);
+        assert!(
+            html.contains("<details class=\"dropdown\">\n<summary class=\"dropdown-title\">Details</summary>\n<div class=\"dropdown-body\">\n<p>Hidden.</p>"),
+            "got: {}",
+            html
+        );
+    }
 
-        let mut temp_file = tempfile::NamedTempFile::with_suffix(".rst").unwrap();
-        temp_file.write_all(content.as_bytes()).unwrap();
+    #[test]
+    fn test_grid_cards_and_button_links() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::path::Path;
 
+        let rst_content = "Title\n=====\n\n.. grid:: 1 2 2 3\n   :gutter: 2\n\n   .. grid-item-card:: Getting *started*\n      :link: guide/intro\n      :link-type: doc\n      :shadow: md\n\n      Header\n      ^^^\n      Install and build.\n      +++\n      Footer\n\n   .. grid-item-card:: Plain\n\n      Text.\n\n.. button-link:: https://example.com\n   :color: primary\n   :outline:\n   :expand:\n\n   Visit **now**\n\n.. grid:: 13\n";
         let config = BuildConfig::default();
         let parser = Parser::new(&config).unwrap();
-        let doc = parser.parse(temp_file.path(), content).unwrap();
-
-        let mut renderer = HtmlRenderer::new();
-        // Register a title with backticks (as would come from parsing the referenced doc)
-        renderer.register_document_title("after", "`after`");
+        let doc = parser.parse(Path::new("index.rst"), rst_content).unwrap();
 
+        let renderer = HtmlRenderer::new();
         let html = renderer.render_document_content(&doc.content);
-
-        // The backticks should be rendered as inline code, not as literal backticks
         assert!(
-            html.contains("<code class=\"code docutils literal notranslate\">"),
-            "should render backticks as code element with proper classes, got: {}",
+            html.contains("<div class=\"sd-container-fluid sd-sphinx-override\">\n<div class=\"sd-row sd-row-cols-1 sd-row-cols-xs-1 sd-row-cols-sm-2 sd-row-cols-md-2 sd-row-cols-lg-3 sd-g-2 sd-g-xs-2 sd-g-sm-2 sd-g-md-2 sd-g-lg-2\">\n<div class=\"sd-col sd-d-flex-row\">\n<div class=\"sd-card sd-sphinx-override sd-w-100 sd-shadow-md sd-card-hover\">\n<div class=\"sd-card-header\">\n<p>Header</p>"),
+            "got: {}",
             html
         );
         assert!(
-            html.contains("<span class=\"pre\">after</span>"),
-            "should contain the code content in a span, got: {}",
+            html.contains("<div class=\"sd-card-title sd-font-weight-bold\">Getting <em>started</em></div>\n<div class=\"sd-card-text\">\n<p>Install and build.</p>"),
+            "got: {}",
             html
         );
+        assert!(html.contains("<div class=\"sd-card-footer\">\n<p>Footer</p>"), "got: {}", html);
         assert!(
-            !html.contains("`after`"),
-            "should NOT contain literal backticks in output, got: {}",
+            html.contains("<a class=\"sd-stretched-link\" href=\"guide/intro.html\" aria-label=\"Getting started\"></a>"),
+            "got: {}",
+            html
+        );
+        assert!(
+            html.contains("<div class=\"sd-card sd-sphinx-override sd-w-100 sd-shadow-sm\">\n<div class=\"sd-card-body\">\n<div class=\"sd-card-title sd-font-weight-bold\">Plain</div>"),
+            "got: {}",
+            html
+        );
+        assert!(
+            html.contains("<p><a class=\"sd-sphinx-override sd-btn sd-text-wrap sd-btn-outline-primary sd-w-100 reference external\" href=\"https://example.com\"><span>Visit <strong>now</strong></span></a></p>"),
+            "got: {}",
             html
         );
+        let warnings = renderer.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message,
+            "grid: invalid columns '13', expected one or four numbers from 0 to 12"
+        );
     }
 
     #[test]
-    fn test_toctree_nested_sections() {
+    fn test_task_lists_and_summary() {
         use crate::config::BuildConfig;
         use crate::parser::Parser;
-        use std::io::Write;
-
-        let content = r#"Index
-=====
-
-.. toctree::
-
-   guide
-"#;
-
-        let mut temp_file = tempfile::NamedTempFile::with_suffix(".rst").unwrap();
-        temp_file.write_all(content.as_bytes()).unwrap();
+        use std::path::Path;
 
         let config = BuildConfig::default();
         let parser = Parser::new(&config).unwrap();
-        let doc = parser.parse(temp_file.path(), content).unwrap();
-
-        let mut renderer = HtmlRenderer::new();
-        renderer.register_document_title("guide", "User Guide");
-        // Register sections for the "guide" document
-        renderer.register_document_sections(
-            "guide",
-            vec![
-                ("Installation".to_string(), "installation".to_string()),
-                ("Configuration".to_string(), "configuration".to_string()),
-            ],
-        );
-
-        let html = renderer.render_document_content(&doc.content);
-
-        // Should have toctree-l1 for the main entry
+        let rst_content = "Runbook\n=======\n\n.. tasklist-summary::\n\n- [x] Drain the node\n- [ ] Upgrade *kernel*\n- [ ] Reboot\n\n.. note::\n\n   - [x] Page the on-call\n";
+        let doc = parser.parse(Path::new("runbook.rst"), rst_content).unwrap();
+        let html = HtmlRenderer::new().render_document_content(&doc.content);
         assert!(
-            html.contains("toctree-l1"),
-            "should have toctree-l1 class for main entry, got: {}",
+            html.contains("<div class=\"tasklist-summary\">\n<progress class=\"tasklist-progress\" max=\"4\" value=\"2\">50%</progress>\n<span class=\"tasklist-summary-text\">2 of 4 tasks done (50%)</span>\n</div>"),
+            "got: {}",
             html
         );
-
-        // Should have nested ul with toctree-l2 entries for sections
         assert!(
-            html.contains("toctree-l2"),
-            "should have toctree-l2 class for nested sections, got: {}",
+            html.contains("<ul class=\"simple contains-task-list\">\n<li class=\"task-list-item\"><input class=\"task-list-item-checkbox\" type=\"checkbox\" disabled=\"disabled\" checked=\"checked\" /> Drain the node</li>\n<li class=\"task-list-item\"><input class=\"task-list-item-checkbox\" type=\"checkbox\" disabled=\"disabled\" /> Upgrade <em>kernel</em></li>"),
+            "got: {}",
             html
         );
 
-        // Should have links to sections with anchors
+        let md_content = "# Runbook\n\n- [x] Drain\n- [ ] Upgrade `kernel`\n- Plain\n";
+        let doc = parser.parse(Path::new("runbook.md"), md_content).unwrap();
+        let html = HtmlRenderer::new().render_document_content(&doc.content);
         assert!(
-            html.contains("guide.html#installation"),
-            "should have link to installation section, got: {}",
+            html.contains("<ul class=\"contains-task-list\">\n<li class=\"task-list-item\"><input class=\"task-list-item-checkbox\" type=\"checkbox\" disabled=\"disabled\" checked=\"checked\" /> Drain</li>\n<li class=\"task-list-item\"><input class=\"task-list-item-checkbox\" type=\"checkbox\" disabled=\"disabled\" /> Upgrade <code>kernel</code></li>\n<li>Plain</li>\n</ul>"),
+            "got: {}",
             html
         );
+    }
+
+    #[test]
+    fn test_footnotes_and_citations() {
+        use crate::config::{BuildConfig, FootnoteStyle};
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let rst_content = "Title\n=====\n\nA claim [#]_, another [#note]_ and a source [Knuth84]_ [Adams79]_.\nAgain [#note]_ and missing [9]_.\n\n.. [#] First *note*.\n\n   Second paragraph.\n\n.. [#note] Named note.\n\n.. [Knuth84] The TeXbook.\n\n.. [Adams79] The Guide.\n\nReferences\n----------\n\n.. bibliography::\n";
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(Path::new("index.rst"), rst_content).unwrap();
+
+        let renderer = HtmlRenderer::new();
+        let html = renderer.render_document_content(&doc.content);
         assert!(
-            html.contains("guide.html#configuration"),
-            "should have link to configuration section, got: {}",
+            html.contains("A claim <a class=\"footnote-reference brackets\" href=\"#footnote-1\" id=\"footnote-reference-1\" role=\"doc-noteref\"><span class=\"fn-bracket\">[</span>1<span class=\"fn-bracket\">]</span></a>, another <a class=\"footnote-reference brackets\" href=\"#footnote-2\" id=\"footnote-reference-2\""),
+            "got: {}",
             html
         );
-
-        // Should have the section titles
+        assert!(html.contains("Again <a class=\"footnote-reference brackets\" href=\"#footnote-2\" id=\"footnote-reference-2-2\""), "got: {}", html);
+        assert!(html.contains("and missing [9].</p>"), "got: {}", html);
         assert!(
-            html.contains("Installation"),
-            "should have Installation title, got: {}",
+            html.contains("<aside class=\"footnote brackets\" id=\"footnote-1\" role=\"doc-footnote\">\n<span class=\"label\"><span class=\"fn-bracket\">[</span><a role=\"doc-backlink\" href=\"#footnote-reference-1\">1</a><span class=\"fn-bracket\">]</span></span>\n<p>First <em>note</em>.</p>\n<p>Second paragraph.</p>\n</aside>"),
+            "got: {}",
             html
         );
+        // Citations are listed by label at the bibliography
         assert!(
-            html.contains("Configuration"),
-            "should have Configuration title, got: {}",
+            html.contains("<h2>References<a class=\"headerlink\" href=\"#references\" title=\"Link to this heading\">¶</a></h2>\n<div role=\"list\" class=\"citation-list\">\n<div class=\"citation\" id=\"citation-adams79\" role=\"doc-biblioentry\">"),
+            "got: {}",
             html
         );
+        assert!(html.find("citation-adams79\" role").unwrap() < html.find("citation-knuth84\" role").unwrap());
+        assert!(html.contains("<a class=\"citation-reference\" href=\"#citation-knuth84\" id=\"citation-reference-knuth84\" role=\"doc-biblioref\">[Knuth84]</a>"), "got: {}", html);
+        let warnings = renderer.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "footnote [9] is not defined");
+
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_footnotes(crate::config::FootnoteConfig {
+            style: FootnoteStyle::Symbol,
+            ..Default::default()
+        });
+        let html = renderer.render_document_content(&doc.content);
+        assert!(html.contains("href=\"#footnote-reference-2\">†</a>"), "got: {}", html);
+    }
+
+    #[test]
+    fn test_permalinks_on_named_figures_tables_and_code_blocks() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let rst_content = "Title\n=====\n\n.. figure:: /img/plot.png\n   :name: Plot One\n   :alt: A plot\n\n   The *plot*.\n\n   More about it.\n\n.. list-table:: Sizes\n   :name: sizes\n   :header-rows: 1\n\n   * - Name\n     - Size\n   * - ``a``\n     - 1\n\n.. code-block:: python\n   :caption: Example\n   :name: example\n\n   x = 1\n\n.. code-block:: python\n   :name: bare\n\n   y = 2\n";
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(Path::new("guide/doc.rst"), rst_content).unwrap();
+
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_current_doc("guide/doc");
+        let html = renderer.render_document_content(&doc.content);
+        assert!(html.contains("<figure class=\"align-default\" id=\"plot-one\">\n<img src=\"../_images/img/plot.png\" alt=\"A plot\" />"), "got: {}", html);
+        assert!(html.contains("<span class=\"caption-text\">The <em>plot</em>.</span><a class=\"headerlink\" href=\"#plot-one\" title=\"Link to this image\">¶</a>"), "got: {}", html);
+        assert!(html.contains("<div class=\"legend\">"), "got: {}", html);
+        assert!(html.contains("<table id=\"sizes\">\n<caption><span class=\"caption-text\">Sizes</span><a class=\"headerlink\" href=\"#sizes\" title=\"Link to this table\">¶</a></caption>"), "got: {}", html);
+        assert!(html.contains("<th>Size</th>") && html.contains("<td><code"), "got: {}", html);
+        assert!(html.contains("<div class=\"literal-block-wrapper docutils container\" id=\"example\">\n<div class=\"code-block-caption\"><span class=\"caption-text\">Example</span><a class=\"headerlink\" href=\"#example\" title=\"Link to this code\">¶</a></div>"), "got: {}", html);
+        assert!(html.contains("<div id=\"bare\" class=\"highlight-python notranslate\">"), "got: {}", html);
+        assert_eq!(renderer.take_media_files()[0].output, "_images/img/plot.png");
+
+        renderer.set_permalink_icon(Some("#".to_string()));
+        let html = renderer.render_document_content(&doc.content);
+        assert!(html.contains("title=\"Link to this heading\">#</a>"), "got: {}", html);
+        renderer.set_permalink_icon(None);
+        let html = renderer.render_document_content(&doc.content);
+        assert!(!html.contains("headerlink"), "got: {}", html);
     }
 
     #[test]
@@ -2029,6 +4253,48 @@ Some text after.
         assert!(!html.contains(".. raw::"), "directive syntax should not appear");
     }
 
+    #[test]
+    fn test_raw_directive_filters_formats_and_reads_files() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("guide")).unwrap();
+        std::fs::write(dir.path().join("guide/widget.html"), b"<b>caf\xe9</b>").unwrap();
+        let content = r#"Title
+=====
+
+.. raw:: latex
+
+   \newpage
+
+.. raw:: latex html
+
+   <hr class="both" />
+
+.. raw:: html
+   :file: widget.html
+   :encoding: latin-1
+
+.. raw:: html
+   :file: missing.html
+"#;
+        let path = dir.path().join("guide/page.rst");
+        let doc = Parser::new(&BuildConfig::default()).unwrap().parse(&path, content).unwrap();
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_source_dir(dir.path().to_path_buf());
+        renderer.set_current_doc("guide/page");
+        let html = renderer.render_document_content(&doc.content);
+
+        assert!(!html.contains("newpage"), "latex leaked into HTML: {}", html);
+        assert!(html.contains(r#"<hr class="both" />"#));
+        assert!(html.contains("<b>café</b>"));
+        let warnings = renderer.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.starts_with("raw: could not read 'missing.html'"));
+        assert_eq!(warnings[0].warning_type, WarningType::MissingFile);
+    }
+
     #[test]
     fn test_ref_role_with_explicit_title() {
         use crate::config::BuildConfig;
@@ -2391,6 +4657,51 @@ Type: `Union[int, str]`
         assert!(!html.contains("def main"), "should NOT contain 'def main', got: {}", html);
     }
 
+    #[test]
+    fn test_literalinclude_prepend_append_and_emphasize_lines() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("example.txt"), "alpha\nbeta\ngamma\n").unwrap();
+        let rst_content = r#"Title
+=====
+
+.. literalinclude:: example.txt
+   :lines: 2-3
+   :prepend: first
+   :append: last
+   :emphasize-lines: 1,3-9
+"#;
+        let rst_file = temp_dir.path().join("doc.rst");
+        let doc = Parser::new(&BuildConfig::default()).unwrap().parse(&rst_file, rst_content).unwrap();
+
+        let tags = Regex::new("<[^>]+>").unwrap();
+        for strict_csp in [false, true] {
+            let mut renderer = HtmlRenderer::new();
+            renderer.set_source_dir(temp_dir.path().to_path_buf());
+            renderer.set_strict_csp(strict_csp);
+            let html = renderer.render_document_content(&doc.content);
+            let text = tags.replace_all(&html, "");
+            assert!(text.contains("first\nbeta\ngamma\nlast"), "got: {}", html);
+
+            let emphasized: Vec<String> = html
+                .match_indices("<span class=\"hll\"")
+                .map(|(start, _)| {
+                    let line = html[start..].split('\n').next().unwrap();
+                    tags.replace_all(line, "").to_string()
+                })
+                .collect();
+            assert_eq!(emphasized, ["first", "gamma", "last"], "got: {}", html);
+            assert_eq!(html.matches("<span").count(), html.matches("</span>").count());
+
+            let warnings = renderer.take_warnings();
+            assert_eq!(warnings.len(), 1);
+            assert!(warnings[0].message.contains("emphasize-lines: lines '3-9' out of range"), "{}", warnings[0].message);
+        }
+    }
+
     #[test]
     fn test_literalinclude_with_caption() {
         use crate::config::BuildConfig;
@@ -2468,6 +4779,64 @@ Type: `Union[int, str]`
         );
     }
 
+    #[test]
+    fn test_literalinclude_missing_markers_warn() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("example.py"),
+            "# Line 1\n# Line 2\n# Line 3\n",
+        )
+        .unwrap();
+
+        let rst_content = r#"Title
+=====
+
+.. literalinclude:: example.py
+   :start-after: # START
+   :lines: 2-9
+"#;
+
+        let rst_file = temp_dir.path().join("doc.rst");
+        std::fs::write(&rst_file, rst_content).unwrap();
+
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(&rst_file, rst_content).unwrap();
+
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_source_dir(temp_dir.path().to_path_buf());
+        renderer.render_document_content(&doc.content);
+
+        let warnings = renderer.take_warnings();
+        let messages: Vec<&str> = warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(warnings.len(), 2, "got: {:?}", messages);
+        assert!(messages[0].contains("start-after marker '# START' not found"));
+        assert!(messages[1].contains("lines '2-9' out of range"));
+        assert_eq!(warnings[0].line, Some(4));
+        assert!(renderer.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_literalinclude_valid_options_do_not_warn() {
+        let renderer = HtmlRenderer::new();
+        let content = "a\nb\nc\nd\n";
+        let mut options = HashMap::new();
+        options.insert("lines".to_string(), "2-".to_string());
+        options.insert("end-before".to_string(), "d".to_string());
+
+        let (lines, problems) = renderer.select_literalinclude_lines(content, &options);
+        assert_eq!(lines, vec!["b", "c"]);
+        assert!(problems.is_empty(), "got: {:?}", problems);
+
+        options.insert("lines".to_string(), "x".to_string());
+        let (_, problems) = renderer.select_literalinclude_lines(content, &options);
+        assert_eq!(problems, vec!["invalid lines spec 'x'".to_string()]);
+    }
+
     #[test]
     fn test_literalinclude_pyobject_function() {
         use crate::config::BuildConfig;
@@ -2810,6 +5179,67 @@ def other_function():
         assert!(!html.contains("other_function"), "should NOT contain 'other_function', got: {}", html);
     }
 
+    #[test]
+    fn test_literalinclude_object_in_other_languages() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("lib.rs"),
+            "struct Config;\n\nimpl Config {\n    fn load() -> Self {\n        Config\n    }\n}\n\nfn unrelated() {}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            temp_dir.path().join("main.txt"),
+            "package main\n\nfunc Serve() {\n}\n\nfunc unrelated() {}\n",
+        )
+        .unwrap();
+
+        let rst_content = r#"Title
+=====
+
+.. literalinclude:: lib.rs
+   :object: Config::load
+
+.. literalinclude:: main.txt
+   :language: go
+   :object: Serve
+
+.. literalinclude:: main.txt
+   :object: Serve
+
+.. literalinclude:: lib.rs
+   :object: missing
+"#;
+
+        let rst_file = temp_dir.path().join("doc.rst");
+        std::fs::write(&rst_file, rst_content).unwrap();
+
+        let config = BuildConfig::default();
+        let parser = Parser::new(&config).unwrap();
+        let doc = parser.parse(&rst_file, rst_content).unwrap();
+
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_source_dir(temp_dir.path().to_path_buf());
+        let html = renderer.render_document_content(&doc.content);
+
+        assert!(html.contains("load"), "should contain the method, got: {}", html);
+        assert!(html.contains("Serve"), "should contain the Go function, got: {}", html);
+        assert!(!html.contains("unrelated"), "should NOT contain other functions, got: {}", html);
+        assert!(!html.contains("struct"), "should NOT contain the struct, got: {}", html);
+
+        let messages: Vec<String> = renderer.take_warnings().into_iter().map(|w| w.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "literalinclude of 'main.txt': object needs a language of python, rust, go or javascript".to_string(),
+                "literalinclude: object 'missing' not found in 'lib.rs'".to_string(),
+            ]
+        );
+    }
+
     #[test]
     fn test_include_basic() {
         use crate::config::BuildConfig;
@@ -2994,4 +5424,57 @@ More content.
             html
         );
     }
+
+    #[test]
+    fn test_include_literal_code_and_encoding_relative_to_document() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let guide = temp_dir.path().join("guide");
+        std::fs::create_dir(&guide).unwrap();
+        std::fs::write(guide.join("snippet.py"), "def f():\n\treturn 1\n").unwrap();
+        std::fs::write(guide.join("notes.txt"), b"Caf\xe9 <menu>\n").unwrap();
+        std::fs::write(temp_dir.path().join("shared.rst"), "Shared *text*.\n").unwrap();
+
+        let rst_content = r#"Title
+=====
+
+.. include:: snippet.py
+   :code: python
+   :tab-width: 4
+
+.. include:: notes.txt
+   :literal:
+   :encoding: latin-1
+
+.. include:: /shared.rst
+"#;
+        let rst_file = guide.join("page.rst");
+        std::fs::write(&rst_file, rst_content).unwrap();
+
+        let mut parser = Parser::new(&BuildConfig::default()).unwrap();
+        parser.set_source_dir(temp_dir.path().to_path_buf());
+        let doc = parser.parse(&rst_file, rst_content).unwrap();
+        let DocumentContent::RestructuredText(rst) = &doc.content else {
+            panic!("expected RST content");
+        };
+        let code_blocks: Vec<(Option<&str>, &str)> = rst
+            .ast
+            .iter()
+            .filter_map(|node| match node {
+                RstNode::CodeBlock { language, content, .. } => Some((language.as_deref(), content.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            code_blocks,
+            [(Some("python"), "def f():\n    return 1"), (None, "Café <menu>")]
+        );
+
+        let html = HtmlRenderer::new().render_document_content(&doc.content);
+        assert!(html.contains("Café &lt;menu&gt;"), "got: {}", html);
+        assert!(html.contains("<em>text</em>"), "got: {}", html);
+    }
 }
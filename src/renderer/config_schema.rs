@@ -0,0 +1,452 @@
+//! Configuration references: the `config-schema` directive lists the options
+//! of a JSON Schema (in JSON or YAML) or of a TOML file of option tables,
+//! each with its type, default and description under a `confval-` anchor
+//! that only depends on the option's name.
+//!
+//! In a TOML file, every table with a `type` key is an option, named by its
+//! path, and any other table groups options:
+//!
+//! ```toml
+//! [server.port]
+//! type = "integer"
+//! default = 8080
+//! description = "Port to listen on"
+//! ```
+
+use super::HtmlRenderer;
+use crate::error::WarningType;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// `$ref` chains followed before giving up on a schema as cyclic
+const MAX_REF_DEPTH: usize = 32;
+
+/// An option of a configuration schema
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigOption {
+    /// Dotted path of the option, like `server.port`
+    pub name: String,
+    /// Types in schema terms, like `integer` or `array of string`
+    pub types: Vec<String>,
+    /// Default value, written as JSON or TOML
+    pub default: Option<String>,
+    /// Allowed values, written like the default
+    pub choices: Vec<String>,
+    pub required: bool,
+    pub description: Option<String>,
+}
+
+impl ConfigOption {
+    /// Anchor of the option: its name, with characters other than letters,
+    /// digits, `.`, `_` and `-` replaced
+    pub fn anchor(&self) -> String {
+        let name: String = self
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        format!("confval-{}", name)
+    }
+}
+
+/// The options of a JSON Schema, in the order of their names
+pub fn json_schema_options(schema: &Value) -> Result<Vec<ConfigOption>, String> {
+    let mut options = Vec::new();
+    collect_json_options(schema, schema, "", &mut options, 0)?;
+    Ok(options)
+}
+
+/// `schema` with its `$ref` to a definition of `root` resolved
+fn resolve_ref<'a>(root: &'a Value, schema: &'a Value, depth: usize) -> Result<&'a Value, String> {
+    let Some(reference) = schema.get("$ref").and_then(Value::as_str) else {
+        return Ok(schema);
+    };
+    if depth > MAX_REF_DEPTH {
+        return Err(format!("'$ref' to '{}' is cyclic", reference));
+    }
+    let target = reference
+        .strip_prefix('#')
+        .and_then(|pointer| root.pointer(pointer))
+        .ok_or_else(|| format!("'$ref' to '{}' cannot be resolved", reference))?;
+    resolve_ref(root, target, depth + 1)
+}
+
+fn collect_json_options(
+    root: &Value,
+    schema: &Value,
+    prefix: &str,
+    options: &mut Vec<ConfigOption>,
+    depth: usize,
+) -> Result<(), String> {
+    let schema = resolve_ref(root, schema, depth)?;
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return Ok(());
+    };
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    for (name, property) in properties {
+        let full_name = format!("{}{}", prefix, name);
+        let property = resolve_ref(root, property, depth)?;
+        if property.get("properties").is_some() {
+            collect_json_options(
+                root,
+                property,
+                &format!("{}.", full_name),
+                options,
+                depth + 1,
+            )?;
+            continue;
+        }
+        options.push(ConfigOption {
+            types: json_types(root, property, depth)?,
+            default: property.get("default").map(Value::to_string),
+            choices: property
+                .get("enum")
+                .and_then(Value::as_array)
+                .map(|values| values.iter().map(Value::to_string).collect())
+                .unwrap_or_default(),
+            required: required.contains(&name.as_str()),
+            description: property
+                .get("description")
+                .or_else(|| property.get("title"))
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            name: full_name,
+        });
+    }
+    Ok(())
+}
+
+/// Types of a property, with the item type of arrays
+fn json_types(root: &Value, property: &Value, depth: usize) -> Result<Vec<String>, String> {
+    let types: Vec<&str> = match property.get("type") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    types
+        .into_iter()
+        .map(|name| {
+            let items = property.get("items").filter(|_| name == "array");
+            Ok(match items {
+                Some(items) => {
+                    let item_types = json_types(root, resolve_ref(root, items, depth)?, depth + 1)?;
+                    if item_types.is_empty() {
+                        name.to_string()
+                    } else {
+                        format!("array of {}", item_types.join(" or "))
+                    }
+                }
+                None => name.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The options of a TOML file of option tables, in the order of their names
+pub fn toml_options(table: &toml::Table) -> Vec<ConfigOption> {
+    let mut options = Vec::new();
+    collect_toml_options(table, "", &mut options);
+    options
+}
+
+fn collect_toml_options(table: &toml::Table, prefix: &str, options: &mut Vec<ConfigOption>) {
+    let mut names: Vec<&String> = table.keys().collect();
+    names.sort();
+    for name in names {
+        let Some(option) = table[name].as_table() else {
+            continue;
+        };
+        let full_name = format!("{}{}", prefix, name);
+        let Some(types) = option.get("type") else {
+            collect_toml_options(option, &format!("{}.", full_name), options);
+            continue;
+        };
+        let types = match types {
+            toml::Value::Array(names) => names
+                .iter()
+                .filter_map(toml::Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            other => vec![other
+                .as_str()
+                .map_or_else(|| other.to_string(), str::to_string)],
+        };
+        options.push(ConfigOption {
+            name: full_name,
+            types,
+            default: option.get("default").map(toml::Value::to_string),
+            choices: option
+                .get("choices")
+                .and_then(toml::Value::as_array)
+                .map(|values| values.iter().map(toml::Value::to_string).collect())
+                .unwrap_or_default(),
+            required: option
+                .get("required")
+                .and_then(toml::Value::as_bool)
+                .unwrap_or(false),
+            description: option
+                .get("description")
+                .and_then(toml::Value::as_str)
+                .map(str::to_string),
+        });
+    }
+}
+
+/// Read the options of a schema, choosing the format by file extension
+pub fn parse_schema(filename: &str, source: &str) -> Result<Vec<ConfigOption>, String> {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase());
+    match extension.as_deref() {
+        Some("json") => {
+            let schema: Value = serde_json::from_str(source).map_err(|err| err.to_string())?;
+            json_schema_options(&schema)
+        }
+        Some("yaml" | "yml") => {
+            let schema: Value = serde_yaml::from_str(source).map_err(|err| err.to_string())?;
+            json_schema_options(&schema)
+        }
+        Some("toml") => {
+            let table: toml::Table = source
+                .parse()
+                .map_err(|err: toml::de::Error| err.message().to_string())?;
+            Ok(toml_options(&table))
+        }
+        _ => Err("expected a .json, .yaml, .yml or .toml file".to_string()),
+    }
+}
+
+impl HtmlRenderer {
+    /// Render `config-schema`: the options of the schema file given as
+    /// argument, with names prefixed by `:prefix:`
+    pub(super) fn render_config_schema(
+        &self,
+        args: &[String],
+        options: &HashMap<String, String>,
+        line: usize,
+    ) -> String {
+        let Some(filename) = args.first() else {
+            self.warn(
+                Some(line),
+                "config-schema: a schema file is required".to_string(),
+                WarningType::InvalidDirective,
+            );
+            return String::new();
+        };
+        let source = match std::fs::read_to_string(self.include_path(filename)) {
+            Ok(source) => source,
+            Err(err) => {
+                self.warn(
+                    Some(line),
+                    format!("config-schema: could not read '{}': {}", filename, err),
+                    WarningType::MissingFile,
+                );
+                return String::new();
+            }
+        };
+        let schema_options = match parse_schema(filename, &source) {
+            Ok(schema_options) => schema_options,
+            Err(err) => {
+                self.warn(
+                    Some(line),
+                    format!("config-schema: invalid schema '{}': {}", filename, err),
+                    WarningType::InvalidDirective,
+                );
+                return String::new();
+            }
+        };
+
+        let prefix = options
+            .get("prefix")
+            .map(|prefix| prefix.trim())
+            .unwrap_or_default();
+        let mut html = String::from("<div class=\"config-schema\">\n");
+        for mut option in schema_options {
+            if !prefix.is_empty() {
+                option.name = format!("{}.{}", prefix.trim_end_matches('.'), option.name);
+            }
+            html.push_str(&self.render_config_option(&option));
+        }
+        html.push_str("</div>");
+        html
+    }
+
+    fn render_config_option(&self, option: &ConfigOption) -> String {
+        let literal = |value: &str| {
+            format!(
+                "<code class=\"docutils literal notranslate\"><span class=\"pre\">{}</span></code>",
+                html_escape::encode_text(value)
+            )
+        };
+        let mut fields = Vec::new();
+        if !option.types.is_empty() {
+            let types: Vec<String> = option.types.iter().map(|name| literal(name)).collect();
+            fields.push(("Type", types.join(" or ")));
+        }
+        if let Some(default) = &option.default {
+            fields.push(("Default", literal(default)));
+        }
+        if !option.choices.is_empty() {
+            let choices: Vec<String> = option
+                .choices
+                .iter()
+                .map(|choice| literal(choice))
+                .collect();
+            fields.push(("Choices", choices.join(", ")));
+        }
+
+        let anchor = option.anchor();
+        let mut html = format!(
+            "<dl class=\"confval\">\n<dt class=\"sig sig-object\" id=\"{}\"><code class=\"sig-name descname\"><span class=\"pre\">{}</span></code>{}{}</dt>\n<dd>\n",
+            anchor,
+            html_escape::encode_text(&option.name),
+            if option.required { " <em class=\"property\">required</em>" } else { "" },
+            self.permalink(&anchor, "option")
+        );
+        if !fields.is_empty() {
+            html.push_str("<dl class=\"field-list simple\">\n");
+            for (name, value) in fields {
+                html.push_str(&format!("<dt>{}</dt>\n<dd>{}</dd>\n", name, value));
+            }
+            html.push_str("</dl>\n");
+        }
+        if let Some(description) = &option.description {
+            html.push_str(&format!(
+                "<p>{}</p>\n",
+                html_escape::encode_text(description)
+            ));
+        }
+        html.push_str("</dd>\n</dl>\n");
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_schema_options() {
+        let schema = r##"{
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": {"type": "string", "description": "Project name"},
+                "server": {"$ref": "#/$defs/server"},
+                "tags": {"type": "array", "items": {"type": "string"}, "default": []}
+            },
+            "$defs": {
+                "server": {
+                    "properties": {
+                        "mode": {"type": ["string", "null"], "enum": ["dev", "prod"], "default": "dev"}
+                    }
+                }
+            }
+        }"##;
+        let options = parse_schema("config.schema.json", schema).unwrap();
+        assert_eq!(
+            options,
+            vec![
+                ConfigOption {
+                    name: "name".to_string(),
+                    types: vec!["string".to_string()],
+                    required: true,
+                    description: Some("Project name".to_string()),
+                    ..ConfigOption::default()
+                },
+                ConfigOption {
+                    name: "server.mode".to_string(),
+                    types: vec!["string".to_string(), "null".to_string()],
+                    default: Some("\"dev\"".to_string()),
+                    choices: vec!["\"dev\"".to_string(), "\"prod\"".to_string()],
+                    ..ConfigOption::default()
+                },
+                ConfigOption {
+                    name: "tags".to_string(),
+                    types: vec!["array of string".to_string()],
+                    default: Some("[]".to_string()),
+                    ..ConfigOption::default()
+                },
+            ]
+        );
+        assert_eq!(options[1].anchor(), "confval-server.mode");
+
+        let cyclic = r##"{"properties": {"a": {"$ref": "#/properties/a"}}}"##;
+        assert_eq!(
+            parse_schema("s.json", cyclic),
+            Err("'$ref' to '#/properties/a' is cyclic".to_string())
+        );
+    }
+
+    #[test]
+    fn test_toml_options() {
+        let source = r#"
+[server.port]
+type = "integer"
+default = 8080
+description = "Port to listen on"
+
+[log_level]
+type = "string"
+choices = ["info", "debug"]
+required = true
+"#;
+        let options = parse_schema("options.toml", source).unwrap();
+        let names: Vec<&str> = options.iter().map(|option| option.name.as_str()).collect();
+        assert_eq!(names, vec!["log_level", "server.port"]);
+        assert_eq!(options[0].choices, vec!["\"info\"", "\"debug\""]);
+        assert!(options[0].required);
+        assert_eq!(options[1].default.as_deref(), Some("8080"));
+        assert_eq!(options[1].types, vec!["integer"]);
+    }
+
+    #[test]
+    fn test_render_config_schema_directive() {
+        use crate::config::BuildConfig;
+        use crate::parser::Parser;
+        use std::path::Path;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("schema.json"),
+            r#"{"properties": {"port": {"type": "integer", "default": 8080, "description": "Port <number>"}}}"#,
+        )
+        .unwrap();
+        let rst = "Options\n=======\n\n.. config-schema:: schema.json\n   :prefix: server\n\n.. config-schema:: missing.toml\n";
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+        let doc = parser.parse(Path::new("index.rst"), rst).unwrap();
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_source_dir(dir.path().to_path_buf());
+        renderer.set_current_doc("index");
+
+        let html = renderer.render_document_content(&doc.content);
+        assert!(
+            html.contains(concat!(
+                "<dl class=\"confval\">\n",
+                "<dt class=\"sig sig-object\" id=\"confval-server.port\"><code class=\"sig-name descname\"><span class=\"pre\">server.port</span></code>",
+                "<a class=\"headerlink\" href=\"#confval-server.port\" title=\"Link to this option\">¶</a></dt>\n",
+                "<dd>\n<dl class=\"field-list simple\">\n",
+                "<dt>Type</dt>\n<dd><code class=\"docutils literal notranslate\"><span class=\"pre\">integer</span></code></dd>\n",
+                "<dt>Default</dt>\n<dd><code class=\"docutils literal notranslate\"><span class=\"pre\">8080</span></code></dd>\n",
+                "</dl>\n<p>Port &lt;number&gt;</p>\n</dd>\n</dl>\n"
+            )),
+            "{}",
+            html
+        );
+        let warnings = renderer.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0]
+            .message
+            .starts_with("config-schema: could not read 'missing.toml'"));
+    }
+}
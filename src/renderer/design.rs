@@ -0,0 +1,631 @@
+//! Grid, card, button and tab layouts: the `grid`, `grid-item`,
+//! `grid-item-card`, `button-link`, `tab-set` and `tab-item` directives of
+//! sphinx-design, rendered with its class names so the bundled `design.css`
+//! (or sphinx-design's own stylesheet) applies unchanged.
+
+use super::{HtmlRenderer, IMAGES_DIR};
+use crate::assets::PageFeature;
+use crate::document::{DocumentContent, RstNode};
+use crate::error::WarningType;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Directives rendered by this module
+pub const DIRECTIVES: [&str; 6] = [
+    "grid",
+    "grid-item",
+    "grid-item-card",
+    "button-link",
+    "tab-set",
+    "tab-item",
+];
+
+/// Colors of the `sd-btn-*` classes
+const COLORS: [&str; 11] = [
+    "primary",
+    "secondary",
+    "success",
+    "info",
+    "warning",
+    "danger",
+    "light",
+    "muted",
+    "dark",
+    "black",
+    "white",
+];
+
+lazy_static::lazy_static! {
+    static ref DESIGN_DIRECTIVE_RE: Regex =
+        Regex::new(r"(?m)^[ \t]*\.\.[ \t]+(?:grid|grid-item|grid-item-card|button-link|tab-set|tab-item)::").unwrap();
+    static ref HREF_RE: Regex = Regex::new(r#"href="([^"]*)""#).unwrap();
+}
+
+/// Whether RST source uses any of the layout directives, so the stylesheet is
+/// only shipped with projects that need it
+pub fn uses_design(source: &str) -> bool {
+    DESIGN_DIRECTIVE_RE.is_match(source)
+}
+
+/// Classes for a setting given either once or per `xs sm md lg` breakpoint,
+/// e.g. `1 2 3 4` for columns. A single value applies to every breakpoint.
+fn breakpoint_classes(prefix: &str, value: &str, max: u8) -> Option<Vec<String>> {
+    let values: Vec<u8> = value
+        .split_whitespace()
+        .map(|v| v.parse().ok().filter(|v| *v <= max))
+        .collect::<Option<_>>()?;
+    let values = match values.as_slice() {
+        [all] => vec![*all; 4],
+        [_, _, _, _] => values,
+        _ => return None,
+    };
+    let mut classes = vec![format!("{}-{}", prefix, values[0])];
+    for (breakpoint, value) in ["xs", "sm", "md", "lg"].iter().zip(&values) {
+        classes.push(format!("{}-{}-{}", prefix, breakpoint, value));
+    }
+    Some(classes)
+}
+
+fn class_attr(classes: &[String]) -> String {
+    html_escape::encode_double_quoted_attribute(&classes.join(" ")).into_owned()
+}
+
+impl HtmlRenderer {
+    pub(super) fn render_design(
+        &self,
+        name: &str,
+        args: &[String],
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        self.use_feature(PageFeature::Design);
+        match name {
+            "grid" => self.render_grid(args, options, content, line),
+            "grid-item" => self.render_grid_item(options, content, line),
+            "grid-item-card" => self.render_card(args, options, content, line),
+            "tab-set" => self.render_tab_set(options, content, line),
+            "tab-item" => {
+                self.warn(
+                    Some(line),
+                    "tab-item: not inside a tab-set".to_string(),
+                    WarningType::InvalidDirective,
+                );
+                self.render_rst_blocks("tab-item", content)
+            }
+            _ => self.render_button_link(args, options, content, line),
+        }
+    }
+
+    /// Responsive classes of a breakpoint setting, warning when it is invalid
+    fn design_breakpoints(
+        &self,
+        directive: &str,
+        option: &str,
+        prefix: &str,
+        value: &str,
+        max: u8,
+        line: usize,
+    ) -> Vec<String> {
+        breakpoint_classes(prefix, value, max).unwrap_or_else(|| {
+            self.warn(
+                Some(line),
+                format!(
+                    "{}: invalid {} '{}', expected one or four numbers from 0 to {}",
+                    directive, option, value, max
+                ),
+                WarningType::InvalidDirective,
+            );
+            Vec::new()
+        })
+    }
+
+    /// Extra classes from a `:class-*:` option
+    fn design_classes(options: &HashMap<String, String>, option: &str) -> Vec<String> {
+        options
+            .get(option)
+            .map(|classes| classes.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    fn render_grid(
+        &self,
+        args: &[String],
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        let mut container = vec![
+            "sd-container-fluid".to_string(),
+            "sd-sphinx-override".to_string(),
+        ];
+        container.extend(Self::design_classes(options, "class-container"));
+
+        let mut row = vec!["sd-row".to_string()];
+        let columns = args.join(" ");
+        if !columns.trim().is_empty() {
+            row.extend(self.design_breakpoints(
+                "grid",
+                "columns",
+                "sd-row-cols",
+                &columns,
+                12,
+                line,
+            ));
+        }
+        if let Some(gutter) = options.get("gutter") {
+            row.extend(self.design_breakpoints("grid", "gutter", "sd-g", gutter, 5, line));
+        }
+        row.extend(Self::design_classes(options, "class-row"));
+
+        format!(
+            "<div class=\"{}\">\n<div class=\"{}\">\n{}\n</div>\n</div>",
+            class_attr(&container),
+            class_attr(&row),
+            self.render_rst_blocks("grid", content)
+        )
+    }
+
+    fn render_grid_item(
+        &self,
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        let mut classes = vec!["sd-col".to_string()];
+        if let Some(columns) = options.get("columns") {
+            classes.extend(self.design_breakpoints(
+                "grid-item",
+                "columns",
+                "sd-col",
+                columns,
+                12,
+                line,
+            ));
+        }
+        classes.extend(Self::design_classes(options, "class"));
+        format!(
+            "<div class=\"{}\">\n{}\n</div>",
+            class_attr(&classes),
+            self.render_rst_blocks("grid-item", content)
+        )
+    }
+
+    /// Render the `tab-item` directives of a tab set as radio buttons with
+    /// their labels and contents, the first `:selected:` one (or the first
+    /// one) checked
+    fn render_tab_set(
+        &self,
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        let path = std::path::PathBuf::from(self.current_doc.as_deref().unwrap_or("tab-set"))
+            .with_extension("rst");
+        let nodes = match self
+            .parse_rst_fragment(&path, content)
+            .map(|doc| doc.content)
+        {
+            Ok(DocumentContent::RestructuredText(rst)) => rst.ast,
+            _ => Vec::new(),
+        };
+        let mut items = Vec::new();
+        for node in &nodes {
+            match node {
+                RstNode::Directive {
+                    name,
+                    args,
+                    options,
+                    content,
+                    ..
+                } if name == "tab-item" => items.push((args.join(" "), options, content)),
+                _ => self.warn(
+                    Some(line),
+                    "tab-set: only tab-item directives are allowed in a tab set".to_string(),
+                    WarningType::InvalidDirective,
+                ),
+            }
+        }
+
+        let set_id = {
+            let mut counts = self.tab_counts.lock();
+            counts.0 += 1;
+            counts.0
+        };
+        let selected = items
+            .iter()
+            .position(|(_, options, _)| options.contains_key("selected"))
+            .unwrap_or(0);
+        let mut classes = vec!["sd-tab-set".to_string(), "docutils".to_string()];
+        classes.extend(Self::design_classes(options, "class"));
+        let mut html = format!("<div class=\"{}\">\n", class_attr(&classes));
+        for (index, (label, options, content)) in items.into_iter().enumerate() {
+            let item_id = {
+                let mut counts = self.tab_counts.lock();
+                counts.1 += 1;
+                counts.1
+            };
+            let mut label_classes = vec!["sd-tab-label".to_string()];
+            label_classes.extend(Self::design_classes(options, "class-label"));
+            let mut content_classes = vec!["sd-tab-content".to_string(), "docutils".to_string()];
+            content_classes.extend(Self::design_classes(options, "class-content"));
+            html.push_str(&format!(
+                "<input {}id=\"sd-tab-item-{}\" name=\"sd-tab-set-{}\" type=\"radio\">\n\
+                 <label class=\"{}\" for=\"sd-tab-item-{}\">{}</label>\n\
+                 <div class=\"{}\">\n{}\n</div>\n",
+                if index == selected {
+                    "checked=\"checked\" "
+                } else {
+                    ""
+                },
+                item_id,
+                set_id,
+                class_attr(&label_classes),
+                item_id,
+                self.render_rst_inline(label.trim()),
+                class_attr(&content_classes),
+                self.render_rst_blocks("tab-item", content)
+            ));
+        }
+        html.push_str("</div>");
+        html
+    }
+
+    fn render_card(
+        &self,
+        args: &[String],
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        let mut column = vec!["sd-col".to_string(), "sd-d-flex-row".to_string()];
+        if let Some(columns) = options.get("columns") {
+            column.extend(self.design_breakpoints(
+                "grid-item-card",
+                "columns",
+                "sd-col",
+                columns,
+                12,
+                line,
+            ));
+        }
+
+        let link = options
+            .get("link")
+            .map(|target| target.trim())
+            .filter(|target| !target.is_empty());
+        let href =
+            link.and_then(|target| self.design_href("grid-item-card", target, options, line));
+
+        let mut card = vec![
+            "sd-card".to_string(),
+            "sd-sphinx-override".to_string(),
+            "sd-w-100".to_string(),
+        ];
+        match options
+            .get("shadow")
+            .map(|shadow| shadow.trim())
+            .unwrap_or("sm")
+        {
+            shadow @ ("none" | "sm" | "md" | "lg") => card.push(format!("sd-shadow-{}", shadow)),
+            shadow => self.warn(
+                Some(line),
+                format!(
+                    "grid-item-card: invalid shadow '{}', expected none, sm, md or lg",
+                    shadow
+                ),
+                WarningType::InvalidDirective,
+            ),
+        }
+        if href.is_some() {
+            card.push("sd-card-hover".to_string());
+        }
+        if let Some(align) = self.design_text_align("grid-item-card", options, line) {
+            card.push(align);
+        }
+        card.extend(Self::design_classes(options, "class-card"));
+
+        // `^^^` ends the header and `+++` starts the footer, as in sphinx-design
+        let mut header = None;
+        let mut footer = None;
+        let mut body = content.to_string();
+        let lines: Vec<&str> = body.lines().collect();
+        if let Some(split) = lines.iter().position(|l| l.trim_start().starts_with("^^^")) {
+            header = Some(lines[..split].join("\n"));
+            body = lines[split + 1..].join("\n");
+        }
+        let lines: Vec<&str> = body.lines().collect();
+        if let Some(split) = lines.iter().position(|l| l.trim_start().starts_with("+++")) {
+            footer = Some(lines[split + 1..].join("\n"));
+            body = lines[..split].join("\n");
+        }
+
+        let image = |option: &str, class: &str| {
+            options.get(option).map(|path| {
+                let path = path.trim();
+                let src = self
+                    .local_media_url("grid-item-card", IMAGES_DIR, path, line)
+                    .unwrap_or_else(|| path.to_string());
+                format!(
+                    "<img alt=\"\" class=\"{}\" src=\"{}\" />\n",
+                    class,
+                    html_escape::encode_double_quoted_attribute(&src)
+                )
+            })
+        };
+        let section = |part: &str, extra: &str, markup: &str| {
+            let mut classes = vec![format!("sd-card-{}", part)];
+            classes.extend(Self::design_classes(options, extra));
+            format!(
+                "<div class=\"{}\">\n{}\n</div>\n",
+                class_attr(&classes),
+                self.render_rst_blocks("grid-item-card", markup)
+            )
+        };
+
+        let mut html = format!(
+            "<div class=\"{}\">\n<div class=\"{}\">\n",
+            class_attr(&column),
+            class_attr(&card)
+        );
+        html.push_str(&image("img-top", "sd-card-img-top").unwrap_or_default());
+        if let Some(header) = &header {
+            html.push_str(&section("header", "class-header", header));
+        }
+        let mut body_classes = vec!["sd-card-body".to_string()];
+        body_classes.extend(Self::design_classes(options, "class-body"));
+        html.push_str(&format!("<div class=\"{}\">\n", class_attr(&body_classes)));
+        let title = args.join(" ");
+        if !title.trim().is_empty() {
+            let mut title_classes = vec![
+                "sd-card-title".to_string(),
+                "sd-font-weight-bold".to_string(),
+            ];
+            title_classes.extend(Self::design_classes(options, "class-title"));
+            html.push_str(&format!(
+                "<div class=\"{}\">{}</div>\n",
+                class_attr(&title_classes),
+                self.render_rst_inline(title.trim())
+            ));
+        }
+        if !body.trim().is_empty() {
+            html.push_str(&format!(
+                "<div class=\"sd-card-text\">\n{}\n</div>\n",
+                self.render_rst_blocks("grid-item-card", &body)
+            ));
+        }
+        html.push_str("</div>\n");
+        if let Some(footer) = &footer {
+            html.push_str(&section("footer", "class-footer", footer));
+        }
+        html.push_str(&image("img-bottom", "sd-card-img-bottom").unwrap_or_default());
+        if let Some(href) = href {
+            let label = match options.get("link-alt") {
+                Some(alt) => alt.trim().to_string(),
+                None => super::extract_plain_text_for_slug(title.trim()),
+            };
+            html.push_str(&format!(
+                "<a class=\"sd-stretched-link\" href=\"{}\" aria-label=\"{}\"></a>\n",
+                html_escape::encode_double_quoted_attribute(&href),
+                html_escape::encode_double_quoted_attribute(&label)
+            ));
+        }
+        html.push_str("</div>\n</div>");
+        html
+    }
+
+    fn render_button_link(
+        &self,
+        args: &[String],
+        options: &HashMap<String, String>,
+        content: &str,
+        line: usize,
+    ) -> String {
+        let target = args.join(" ");
+        let target = target.trim();
+        let href = self
+            .design_href("button-link", target, options, line)
+            .unwrap_or_default();
+
+        let mut classes = vec![
+            "sd-sphinx-override".to_string(),
+            "sd-btn".to_string(),
+            "sd-text-wrap".to_string(),
+        ];
+        let outline = options.contains_key("outline");
+        if let Some(color) = options.get("color").map(|color| color.trim()) {
+            if COLORS.contains(&color) {
+                classes.push(if outline {
+                    format!("sd-btn-outline-{}", color)
+                } else {
+                    format!("sd-btn-{}", color)
+                });
+            } else {
+                self.warn(
+                    Some(line),
+                    format!(
+                        "button-link: invalid color '{}', expected one of {}",
+                        color,
+                        COLORS.join(", ")
+                    ),
+                    WarningType::InvalidDirective,
+                );
+            }
+        }
+        if options.contains_key("expand") {
+            classes.push("sd-w-100".to_string());
+        }
+        if let Some(shadow) = options.get("shadow") {
+            let shadow = shadow.trim();
+            classes.push(format!(
+                "sd-shadow-{}",
+                if shadow.is_empty() { "sm" } else { shadow }
+            ));
+        }
+        classes.extend(Self::design_classes(options, "class"));
+        classes.push("reference".to_string());
+        classes.push("external".to_string());
+
+        let text = if content.trim().is_empty() {
+            html_escape::encode_text(target).into_owned()
+        } else {
+            self.render_rst_inline(content.trim())
+        };
+        let mut link = format!(
+            "<a class=\"{}\" href=\"{}\"",
+            class_attr(&classes),
+            html_escape::encode_double_quoted_attribute(&href)
+        );
+        if let Some(tooltip) = options.get("tooltip") {
+            link.push_str(&format!(
+                " title=\"{}\"",
+                html_escape::encode_double_quoted_attribute(tooltip.trim())
+            ));
+        }
+        link.push_str(&format!("><span>{}</span></a>", text));
+
+        match self.design_text_align("button-link", options, line) {
+            Some(align) => format!("<p class=\"{}\">{}</p>", align, link),
+            None => format!("<p>{}</p>", link),
+        }
+    }
+
+    /// The `sd-text-*` class of an `:align:` or `:text-align:` option
+    fn design_text_align(
+        &self,
+        directive: &str,
+        options: &HashMap<String, String>,
+        line: usize,
+    ) -> Option<String> {
+        let align = options
+            .get("text-align")
+            .or_else(|| options.get("align"))?
+            .trim();
+        if ["left", "right", "center", "justify"].contains(&align) {
+            Some(format!("sd-text-{}", align))
+        } else {
+            self.warn(
+                Some(line),
+                format!(
+                    "{}: invalid alignment '{}', expected left, right, center or justify",
+                    directive, align
+                ),
+                WarningType::InvalidDirective,
+            );
+            None
+        }
+    }
+
+    /// Resolve a link target according to `:link-type:` (or `:ref-type:` for
+    /// buttons): a URL by default, or a document or label like the `doc` and
+    /// `ref` roles
+    fn design_href(
+        &self,
+        directive: &str,
+        target: &str,
+        options: &HashMap<String, String>,
+        line: usize,
+    ) -> Option<String> {
+        let link_type = options
+            .get("link-type")
+            .or_else(|| options.get("ref-type"))
+            .map(|kind| kind.trim())
+            .unwrap_or("url");
+        match link_type {
+            "url" => Some(target.to_string()),
+            "doc" | "ref" => {
+                let html = self.render_role(link_type, target);
+                let href = HREF_RE
+                    .captures(&html)
+                    .map(|caps| html_escape::decode_html_entities(&caps[1]).into_owned());
+                if href.is_none() {
+                    self.warn(
+                        Some(line),
+                        format!(
+                            "{}: could not resolve {} '{}'",
+                            directive, link_type, target
+                        ),
+                        WarningType::BrokenCrossReference,
+                    );
+                }
+                href
+            }
+            other => {
+                self.warn(
+                    Some(line),
+                    format!(
+                        "{}: invalid link type '{}', expected url, doc or ref",
+                        directive, other
+                    ),
+                    WarningType::InvalidDirective,
+                );
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breakpoint_classes() {
+        assert_eq!(
+            breakpoint_classes("sd-row-cols", "2", 12).unwrap(),
+            vec![
+                "sd-row-cols-2",
+                "sd-row-cols-xs-2",
+                "sd-row-cols-sm-2",
+                "sd-row-cols-md-2",
+                "sd-row-cols-lg-2"
+            ]
+        );
+        assert_eq!(
+            breakpoint_classes("sd-g", "1 2 3 4", 5).unwrap(),
+            vec!["sd-g-1", "sd-g-xs-1", "sd-g-sm-2", "sd-g-md-3", "sd-g-lg-4"]
+        );
+        assert!(breakpoint_classes("sd-g", "1 2", 5).is_none());
+        assert!(breakpoint_classes("sd-g", "6", 5).is_none());
+        assert!(breakpoint_classes("sd-col", "auto", 12).is_none());
+    }
+
+    #[test]
+    fn test_uses_design() {
+        assert!(uses_design(
+            "Intro\n\n.. grid:: 2\n\n   .. grid-item-card:: A\n"
+        ));
+        assert!(uses_design(".. button-link:: https://example.com\n"));
+        assert!(!uses_design(".. note::\n\n   The grid:: layout\n"));
+        assert!(uses_design(".. tab-set::\n\n   .. tab-item:: A\n"));
+    }
+
+    #[test]
+    fn test_tab_set() {
+        let renderer = HtmlRenderer::new();
+        let options = HashMap::new();
+        let html = renderer.render_design(
+            "tab-set",
+            &[],
+            &options,
+            ".. tab-item:: Python\n\n   Use *pip*.\n\n.. tab-item:: Rust\n   :selected:\n\n   Use cargo.\n",
+            1,
+        );
+        assert_eq!(
+            html,
+            "<div class=\"sd-tab-set docutils\">\n\
+             <input id=\"sd-tab-item-1\" name=\"sd-tab-set-1\" type=\"radio\">\n\
+             <label class=\"sd-tab-label\" for=\"sd-tab-item-1\">Python</label>\n\
+             <div class=\"sd-tab-content docutils\">\n<p>Use <em>pip</em>.</p>\n\n</div>\n\
+             <input checked=\"checked\" id=\"sd-tab-item-2\" name=\"sd-tab-set-1\" type=\"radio\">\n\
+             <label class=\"sd-tab-label\" for=\"sd-tab-item-2\">Rust</label>\n\
+             <div class=\"sd-tab-content docutils\">\n<p>Use cargo.</p>\n\n</div>\n\
+             </div>"
+        );
+        assert!(renderer.take_warnings().is_empty());
+        assert!(renderer
+            .take_page_assets()
+            .features
+            .contains(&PageFeature::Design));
+    }
+}
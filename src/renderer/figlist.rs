@@ -0,0 +1,80 @@
+//! Numbers of the captioned figures, tables and code blocks, and the
+//! `figlist` and `tablelist` directives listing them with links
+
+use super::HtmlRenderer;
+use crate::navigation::relative_uri;
+use crate::numfig;
+use std::collections::HashMap;
+
+impl HtmlRenderer {
+    /// The number shown before the caption of an item of the current page,
+    /// like `Fig. 2`, or nothing when it is not numbered
+    pub(super) fn caption_number(&self, figtype: &str, id: Option<&str>) -> String {
+        let (Some(docname), Some(id)) = (self.current_doc.as_deref(), id) else {
+            return String::new();
+        };
+        self.number_label(docname, figtype, id)
+            .map(|label| format!("<span class=\"caption-number\">{} </span>", label))
+            .unwrap_or_default()
+    }
+
+    /// Escaped number of an item, with its caption prefix
+    fn number_label(&self, docname: &str, figtype: &str, id: &str) -> Option<String> {
+        let environment = &self.environment;
+        let number = environment.figure_number(docname, figtype, id)?;
+        let format = environment.config.numfig_format.prefix(figtype)?;
+        Some(html_escape::encode_text(&numfig::format_number(format, number)).into_owned())
+    }
+
+    /// Render a `figlist` or `tablelist` directive: links to the figures or
+    /// tables of the documents in reading order, with their numbers and
+    /// captions
+    pub(super) fn render_figure_list(
+        &self,
+        name: &str,
+        options: &HashMap<String, String>,
+    ) -> String {
+        let figtype = if name == "tablelist" {
+            "table"
+        } else {
+            "figure"
+        };
+        let environment = &self.environment;
+        let mut items = Vec::new();
+        for (docname, _) in environment.reading_order() {
+            let Some(enumerables) = environment.enumerables.get(docname.as_str()) else {
+                continue;
+            };
+            let url = match self.current_doc.as_deref() {
+                Some(current) if current != docname => relative_uri(current, &docname),
+                _ => String::new(),
+            };
+            for item in enumerables.iter().filter(|item| item.figtype == figtype) {
+                let number = self
+                    .number_label(&docname, figtype, &item.id)
+                    .map(|label| format!("<span class=\"caption-number\">{} </span>", label))
+                    .unwrap_or_default();
+                items.push(format!(
+                    "<li><a class=\"reference internal\" href=\"{}#{}\">{}<span class=\"caption-text\">{}</span></a></li>",
+                    url,
+                    html_escape::encode_double_quoted_attribute(&item.id),
+                    number,
+                    self.render_rst_inline(&item.caption)
+                ));
+            }
+        }
+
+        let mut html = format!("<div class=\"{}\">\n", name);
+        if let Some(caption) = options.get("caption") {
+            html.push_str(&format!(
+                "<p class=\"caption\"><span class=\"caption-text\">{}</span></p>\n",
+                self.render_rst_inline(caption)
+            ));
+        }
+        if !items.is_empty() {
+            html.push_str(&format!("<ul>\n{}\n</ul>\n", items.join("\n")));
+        }
+        html.push_str("</div>");
+        html
+    }
+}
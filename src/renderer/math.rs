@@ -0,0 +1,94 @@
+//! Display math and `mermaid` diagrams, left in the page as source for
+//! MathJax and mermaid to render in the browser. Only the pages using them
+//! load the scripts.
+
+use super::{slugify, HtmlRenderer};
+use crate::assets::PageFeature;
+use std::collections::HashMap;
+
+impl HtmlRenderer {
+    /// Render a `math` directive the way Sphinx does for MathJax: equations
+    /// separated by blank lines are aligned together
+    pub(super) fn render_math(
+        &self,
+        args: &[String],
+        options: &HashMap<String, String>,
+        content: &str,
+    ) -> String {
+        self.use_feature(PageFeature::Math);
+        let source = [args.join(" "), content.to_string()].join("\n\n");
+        let equations: Vec<String> = source
+            .split("\n\n")
+            .map(|equation| equation.trim().to_string())
+            .filter(|equation| !equation.is_empty())
+            .collect();
+        let math = match equations.as_slice() {
+            [equation] => equation.clone(),
+            _ => format!(
+                "\\begin{{align}}\\begin{{aligned}}{}\\end{{aligned}}\\end{{align}}",
+                equations.join("\\\\")
+            ),
+        };
+        let id = options
+            .get("label")
+            .map(|label| format!(" id=\"equation-{}\"", slugify(label)))
+            .unwrap_or_default();
+        format!(
+            "<div class=\"math notranslate nohighlight\"{}>\n\\[{}\\]</div>",
+            id,
+            html_escape::encode_text(&math)
+        )
+    }
+
+    /// Render a `mermaid` diagram as its source for mermaid to draw
+    pub(super) fn render_mermaid(
+        &self,
+        options: &HashMap<String, String>,
+        content: &str,
+    ) -> String {
+        self.use_feature(PageFeature::Mermaid);
+        let mut classes = vec!["mermaid".to_string()];
+        if let Some(extra) = options.get("class") {
+            classes.extend(extra.split_whitespace().map(str::to_string));
+        }
+        let id = options
+            .get("name")
+            .and_then(|name| self.name_id(name))
+            .map(|id| format!(" id=\"{}\"", id))
+            .unwrap_or_default();
+        format!(
+            "<pre class=\"{}\"{}>\n{}\n</pre>",
+            html_escape::encode_double_quoted_attribute(&classes.join(" ")),
+            id,
+            html_escape::encode_text(content.trim_end())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_math_and_mermaid_blocks() {
+        let renderer = HtmlRenderer::new();
+        let options = HashMap::from([("label".to_string(), "Energy".to_string())]);
+        assert_eq!(
+            renderer.render_math(&[], &options, "e = mc^2"),
+            "<div class=\"math notranslate nohighlight\" id=\"equation-energy\">\n\\[e = mc^2\\]</div>"
+        );
+        assert_eq!(
+            renderer.render_math(&["a < b".to_string()], &HashMap::new(), "c = d"),
+            "<div class=\"math notranslate nohighlight\">\n\\[\\begin{align}\\begin{aligned}a &lt; b\\\\c = d\\end{aligned}\\end{align}\\]</div>"
+        );
+        assert_eq!(
+            renderer.render_mermaid(&HashMap::new(), "graph TD\n  A-->B\n"),
+            "<pre class=\"mermaid\">\ngraph TD\n  A--&gt;B\n</pre>"
+        );
+        let features = renderer.take_page_assets().features;
+        assert_eq!(
+            features.into_iter().collect::<Vec<_>>(),
+            vec![PageFeature::Math, PageFeature::Mermaid]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Footnotes, citations and their references, marked up like docutils
+
+use super::{slugify, HtmlRenderer};
+use crate::bibtex;
+use crate::error::WarningType;
+use crate::footnotes::{Note, NoteLabel};
+use crate::navigation::relative_uri;
+
+/// Wrap rendered citations in the list that holds them
+pub(super) fn citation_list(citations: &str) -> String {
+    format!(
+        "<div role=\"list\" class=\"citation-list\">\n{}\n</div>",
+        citations
+    )
+}
+
+impl HtmlRenderer {
+    /// Render a footnote or citation. `note` has its anchor and label and
+    /// whether anything references it; without it the label is shown as
+    /// written.
+    pub(super) fn render_note(
+        &self,
+        note: Option<(&Note, bool)>,
+        label: &str,
+        citation: bool,
+        content: &str,
+    ) -> String {
+        let shown = html_escape::encode_text(note.map_or(label, |(note, _)| note.label.as_str()))
+            .into_owned();
+        let label_html = match note {
+            Some((note, true)) => format!(
+                "<a role=\"doc-backlink\" href=\"#{}\">{}</a>",
+                note.reference_id, shown
+            ),
+            _ => shown,
+        };
+        let id = note
+            .map(|(note, _)| format!(" id=\"{}\"", note.id))
+            .unwrap_or_default();
+        let (open, close) = if citation {
+            (
+                format!("<div class=\"citation\"{} role=\"doc-biblioentry\">", id),
+                "</div>",
+            )
+        } else {
+            (
+                format!(
+                    "<aside class=\"footnote brackets\"{} role=\"doc-footnote\">",
+                    id
+                ),
+                "</aside>",
+            )
+        };
+        format!(
+            "{}\n<span class=\"label\"><span class=\"fn-bracket\">[</span>{}<span class=\"fn-bracket\">]</span></span>\n{}\n{}",
+            open,
+            label_html,
+            self.render_rst_blocks(if citation { "citation" } else { "footnote" }, content).trim_end(),
+            close
+        )
+    }
+
+    /// Render a `[label]_` reference, linked to its footnote or citation
+    pub(super) fn render_footnote_reference(&self, label: &str) -> String {
+        let text = html_escape::encode_text(label);
+        let Some(kind) = NoteLabel::parse(label) else {
+            return format!("[{}]", text);
+        };
+        let resolved = self
+            .footnote_references
+            .lock()
+            .as_mut()
+            .map(|references| references.resolve(&kind));
+        match resolved {
+            Some(Some((note, id))) if kind.is_citation() => format!(
+                "<a class=\"citation-reference\" href=\"#{}\" id=\"{}\" role=\"doc-biblioref\">[{}]</a>",
+                note.id,
+                id,
+                html_escape::encode_text(&note.label)
+            ),
+            Some(Some((note, id))) => format!(
+                "<a class=\"footnote-reference brackets\" href=\"#{}\" id=\"{}\" role=\"doc-noteref\"><span class=\"fn-bracket\">[</span>{}<span class=\"fn-bracket\">]</span></a>",
+                note.id,
+                id,
+                html_escape::encode_text(&note.label)
+            ),
+            Some(None) => {
+                let what = if kind.is_citation() { "citation" } else { "footnote" };
+                self.warn(
+                    None,
+                    format!("{} [{}] is not defined", what, label),
+                    WarningType::BrokenCrossReference,
+                );
+                format!("[{}]", text)
+            }
+            // Outside a document, as in titles shown elsewhere
+            None => format!("[{}]", text),
+        }
+    }
+
+    /// Render a `:cite:` role: the labels of the BibTeX entries it names in
+    /// brackets, linked to where the bibliography lists them, or for
+    /// `:cite:t:` the authors followed by the label
+    pub(super) fn render_cite(&self, textual: bool, keys: &str) -> String {
+        let bibliography = &self.bibliography;
+        let page_url = match (bibliography.page(), &self.current_doc) {
+            (Some(page), Some(current)) if page != current => Some(relative_uri(current, page)),
+            (Some(_), _) => Some(String::new()),
+            (None, _) => None,
+        };
+        let cites: Vec<(Option<String>, String)> = bibtex::split_keys(keys)
+            .into_iter()
+            .map(|key| {
+                let Some((entry, label)) = bibliography.entry(&key) else {
+                    self.warn(
+                        None,
+                        format!("citation key '{}' is not in the BibTeX files", key),
+                        WarningType::BrokenCrossReference,
+                    );
+                    return (None, html_escape::encode_text(&key).into_owned());
+                };
+                let label = html_escape::encode_text(label.unwrap_or(&key)).into_owned();
+                let label = match &page_url {
+                    Some(url) => format!(
+                        "<a class=\"reference internal\" href=\"{}#bibtex-{}\" role=\"doc-biblioref\">{}</a>",
+                        url,
+                        slugify(&key),
+                        label
+                    ),
+                    None => label,
+                };
+                (Some(html_escape::encode_text(&entry.short_authors()).into_owned()), label)
+            })
+            .collect();
+        if textual {
+            cites
+                .into_iter()
+                .map(|(authors, label)| match authors {
+                    Some(authors) => format!("{} [{}]", authors, label),
+                    None => format!("[{}]", label),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        } else {
+            let labels: Vec<String> = cites.into_iter().map(|(_, label)| label).collect();
+            format!("[{}]", labels.join(", "))
+        }
+    }
+
+    /// Render the cited BibTeX entries, when this is the page listing them
+    pub(super) fn render_bibtex_entries(&self) -> Vec<String> {
+        let bibliography = &self.bibliography;
+        if bibliography.page().is_none() || bibliography.page() != self.current_doc.as_deref() {
+            return Vec::new();
+        }
+        bibliography
+            .listed()
+            .map(|(entry, label)| {
+                format!(
+                    "<div class=\"citation\" id=\"bibtex-{}\" role=\"doc-biblioentry\">\n<span class=\"label\"><span class=\"fn-bracket\">[</span>{}<span class=\"fn-bracket\">]</span></span>\n<p>{}</p>\n</div>",
+                    slugify(&entry.key),
+                    html_escape::encode_text(label),
+                    entry.to_html()
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,179 @@
+//! Task lists: bullet list items starting with `[ ]` or `[x]`, in RST and
+//! Markdown, rendered as checkboxes, and the `tasklist-summary` directive
+//! showing how many tasks of the document are done.
+
+use super::HtmlRenderer;
+use crate::document::RstNode;
+use regex::Regex;
+use std::collections::HashMap;
+
+lazy_static::lazy_static! {
+    static ref TASK_LINE_RE: Regex = Regex::new(r"(?m)^[ \t]*[-*+] \[[ xX]\](?:[ \t]|$)").unwrap();
+    static ref DONE_LINE_RE: Regex = Regex::new(r"(?m)^[ \t]*[-*+] \[[xX]\](?:[ \t]|$)").unwrap();
+}
+
+/// Directives whose content is never markup, so list-like lines in it are
+/// not tasks
+const LITERAL_DIRECTIVES: [&str; 6] = [
+    "code-block",
+    "code",
+    "sourcecode",
+    "literalinclude",
+    "raw",
+    "math",
+];
+
+/// Whether a list item is a task and whether it is done, with its text
+pub fn split_task(item: &str) -> Option<(bool, &str)> {
+    let done = match item.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let text = &item[3..];
+    if !text.is_empty() && !text.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some((done, text.trim_start()))
+}
+
+/// Number of tasks in a document and how many of them are done
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TaskProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl TaskProgress {
+    /// Tasks of the lists in an RST document, including those nested in
+    /// directives such as admonitions
+    pub fn of_rst(ast: &[RstNode]) -> Self {
+        let mut progress = Self::default();
+        for node in ast {
+            match node {
+                RstNode::List { items, .. } => {
+                    for (done, _) in items
+                        .iter()
+                        .flat_map(|item| item.split('\n'))
+                        .filter_map(split_task)
+                    {
+                        progress.add(done);
+                    }
+                }
+                RstNode::Directive { name, content, .. }
+                    if !LITERAL_DIRECTIVES.contains(&name.as_str()) =>
+                {
+                    progress.total += TASK_LINE_RE.find_iter(content).count();
+                    progress.done += DONE_LINE_RE.find_iter(content).count();
+                }
+                _ => {}
+            }
+        }
+        progress
+    }
+
+    fn add(&mut self, done: bool) {
+        self.total += 1;
+        if done {
+            self.done += 1;
+        }
+    }
+
+    /// Share of the tasks done, in whole percent
+    pub fn percent(&self) -> usize {
+        (self.done * 100).checked_div(self.total).unwrap_or(0)
+    }
+}
+
+impl HtmlRenderer {
+    /// Render a list item that is a task, with its checkbox, or `None` for
+    /// any other item
+    pub(super) fn render_task_item(
+        &self,
+        item: &str,
+        render_inline: impl Fn(&str) -> String,
+    ) -> Option<String> {
+        let (done, text) = split_task(item)?;
+        Some(format!(
+            "<li class=\"task-list-item\"><input class=\"task-list-item-checkbox\" type=\"checkbox\" disabled=\"disabled\"{} /> {}</li>",
+            if done { " checked=\"checked\"" } else { "" },
+            render_inline(text)
+        ))
+    }
+
+    /// Render the progress of the document's tasks for `tasklist-summary`
+    pub(super) fn render_tasklist_summary(
+        &self,
+        progress: TaskProgress,
+        options: &HashMap<String, String>,
+    ) -> String {
+        let mut classes = vec!["tasklist-summary".to_string()];
+        if let Some(extra) = options.get("class") {
+            classes.extend(extra.split_whitespace().map(str::to_string));
+        }
+        let class = html_escape::encode_double_quoted_attribute(&classes.join(" ")).into_owned();
+        if progress.total == 0 {
+            return format!(
+                "<div class=\"{}\"><span class=\"tasklist-summary-text\">No tasks</span></div>",
+                class
+            );
+        }
+        format!(
+            "<div class=\"{}\">\n<progress class=\"tasklist-progress\" max=\"{}\" value=\"{}\">{}%</progress>\n<span class=\"tasklist-summary-text\">{} of {} tasks done ({}%)</span>\n</div>",
+            class,
+            progress.total,
+            progress.done,
+            progress.percent(),
+            progress.done,
+            progress.total,
+            progress.percent()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_task() {
+        assert_eq!(split_task("[ ] Write docs"), Some((false, "Write docs")));
+        assert_eq!(split_task("[x] Ship"), Some((true, "Ship")));
+        assert_eq!(split_task("[X]"), Some((true, "")));
+        assert_eq!(split_task("[x]Ship"), None);
+        assert_eq!(split_task("[1] Footnote"), None);
+        assert_eq!(split_task("Plain"), None);
+    }
+
+    #[test]
+    fn test_progress_counts_nested_tasks() {
+        let ast = vec![
+            RstNode::List {
+                items: vec![
+                    "[x] One".to_string(),
+                    "[ ] Two\n[x] Two a".to_string(),
+                    "Other".to_string(),
+                ],
+                ordered: false,
+                line: 1,
+            },
+            RstNode::Directive {
+                name: "note".to_string(),
+                args: Vec::new(),
+                options: HashMap::new(),
+                content: "- [ ] Three\n- [x] Four\n".to_string(),
+                line: 5,
+            },
+            RstNode::Directive {
+                name: "code-block".to_string(),
+                args: vec!["markdown".to_string()],
+                options: HashMap::new(),
+                content: "- [ ] Not a task\n".to_string(),
+                line: 9,
+            },
+        ];
+        let progress = TaskProgress::of_rst(&ast);
+        assert_eq!(progress, TaskProgress { done: 3, total: 5 });
+        assert_eq!(progress.percent(), 60);
+    }
+}
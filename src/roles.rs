@@ -1,7 +1,9 @@
 use anyhow::Result;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::config::{CustomRoleConfig, IssueTrackerConfig, IssueTrackerKind};
 
 /// Represents a parsed Sphinx role
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +52,37 @@ impl RoleRegistry {
         self.processors.get(name).map(|boxed| boxed.as_ref())
     }
 
+    /// Names of all registered roles
+    pub fn names(&self) -> Vec<&str> {
+        self.processors.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// Register the roles defined in the configuration, replacing built-in
+    /// roles of the same name
+    pub fn register_custom_roles(&mut self, roles: &BTreeMap<String, CustomRoleConfig>) {
+        for (name, config) in roles {
+            self.register(Box::new(ConfigRole {
+                name: name.clone(),
+                config: config.clone(),
+            }));
+        }
+    }
+
+    /// Register the `issue`, `pr`, `commit` and `user` roles the tracker has
+    /// URLs for
+    pub fn register_tracker_roles(&mut self, tracker: &IssueTrackerConfig) {
+        for name in ["issue", "pr", "commit", "user"] {
+            if let Some(template) = tracker.uri_template(name) {
+                self.register(Box::new(TrackerRole {
+                    name,
+                    template,
+                    kind: tracker.kind,
+                    project: tracker.project.clone().unwrap_or_default(),
+                }));
+            }
+        }
+    }
+
     pub fn process_role(&self, role: &Role) -> Result<String> {
         if let Some(processor) = self.get(&role.name) {
             processor.process(role)
@@ -74,6 +107,17 @@ impl RoleRegistry {
         // Math roles
         self.register(Box::new(MathRole));
 
+        // Spelling roles
+        self.register(Box::new(SpellingWordRole));
+
+        // Index roles
+        self.register(Box::new(IndexRole));
+
+        // Bibliography roles
+        for name in ["cite", "cite:p", "cite:t"] {
+            self.register(Box::new(CiteRole { name }));
+        }
+
         // Generic emphasis roles
         self.register(Box::new(EmphasisRole::new("emphasis")));
         self.register(Box::new(EmphasisRole::new("strong")));
@@ -250,6 +294,55 @@ impl RoleProcessor for MathRole {
     }
 }
 
+// Spelling roles
+
+/// `:spelling:word:` marks a word as correctly spelled for the spell checker
+/// and renders it as plain text.
+struct SpellingWordRole;
+
+impl RoleProcessor for SpellingWordRole {
+    fn process(&self, role: &Role) -> Result<String> {
+        let display_text = role.text.as_ref().unwrap_or(&role.target);
+        Ok(html_escape::encode_text(display_text).into_owned())
+    }
+
+    fn get_name(&self) -> &str {
+        "spelling:word"
+    }
+}
+
+// Index roles
+
+/// `:index:` shows its text; the renderer adds the anchor its index entries
+/// link to.
+struct IndexRole;
+
+impl RoleProcessor for IndexRole {
+    fn process(&self, role: &Role) -> Result<String> {
+        let display_text = role.text.as_ref().unwrap_or(&role.target);
+        Ok(html_escape::encode_text(display_text.trim_start_matches('!')).into_owned())
+    }
+
+    fn get_name(&self) -> &str {
+        "index"
+    }
+}
+
+// Bibliography roles; the renderer links the keys to their entries
+struct CiteRole {
+    name: &'static str,
+}
+
+impl RoleProcessor for CiteRole {
+    fn process(&self, role: &Role) -> Result<String> {
+        Ok(format!("[{}]", html_escape::encode_text(&role.target)))
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+}
+
 // Generic emphasis roles
 struct EmphasisRole {
     name: String,
@@ -292,3 +385,113 @@ impl RoleProcessor for EmphasisRole {
         &self.name
     }
 }
+
+// Roles defined in the configuration
+struct ConfigRole {
+    name: String,
+    config: CustomRoleConfig,
+}
+
+impl RoleProcessor for ConfigRole {
+    fn process(&self, role: &Role) -> Result<String> {
+        let display_text = role.text.as_ref().unwrap_or(&role.target);
+        let text = html_escape::encode_text(display_text);
+
+        if let Some(template) = &self.config.template {
+            return Ok(template
+                .replace("{text}", &text)
+                .replace("{target}", &html_escape::encode_double_quoted_attribute(&role.target)));
+        }
+
+        // Only a plain tag name can be used as the element
+        let element = self
+            .config
+            .element
+            .as_deref()
+            .filter(|element| !element.is_empty() && element.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or("span");
+        let classes = if self.config.classes.is_empty() {
+            self.name.clone()
+        } else {
+            self.config.classes.join(" ")
+        };
+        Ok(format!(
+            "<{element} class=\"{}\">{}</{element}>",
+            html_escape::encode_double_quoted_attribute(&classes),
+            text,
+            element = element
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A link to an issue, pull request, commit or user of the issue tracker
+struct TrackerRole {
+    name: &'static str,
+    template: String,
+    kind: IssueTrackerKind,
+    project: String,
+}
+
+impl TrackerRole {
+    /// Link text and URL of one target, which may name another repository
+    /// as in `owner/repo#12` or `owner/repo@sha`
+    fn link(&self, target: &str) -> (String, String) {
+        let separator = if self.name == "commit" { '@' } else { '#' };
+        let (project, id, prefix) = match target.split_once(separator) {
+            Some((project, id)) if self.name != "user" && project.contains('/') => (project, id, project),
+            _ => (self.project.as_str(), target, ""),
+        };
+
+        let id = match self.kind {
+            // Jira keys include the project, which plain numbers lack
+            IssueTrackerKind::Jira if self.name == "issue" && id.chars().all(|c| c.is_ascii_digit()) => {
+                format!("{}-{}", project, id)
+            }
+            _ => id.to_string(),
+        };
+        let text = match (self.name, self.kind) {
+            ("user", _) => format!("@{}", id),
+            ("issue", IssueTrackerKind::Jira) => id.clone(),
+            ("pr", IssueTrackerKind::GitLab) => format!("{}!{}", prefix, id),
+            ("commit", _) if prefix.is_empty() => id.chars().take(7).collect(),
+            ("commit", _) => format!("{}@{}", prefix, id.chars().take(7).collect::<String>()),
+            _ => format!("{}#{}", prefix, id),
+        };
+
+        let url = self.template.replace("{project}", project).replace("{id}", &id);
+        (text, url)
+    }
+}
+
+impl RoleProcessor for TrackerRole {
+    fn process(&self, role: &Role) -> Result<String> {
+        // Several targets may be listed, as in :issue:`12, 15`
+        let links: Vec<String> = role
+            .target
+            .split(',')
+            .map(str::trim)
+            .filter(|target| !target.is_empty())
+            .map(|target| {
+                let (text, url) = self.link(target);
+                let text = match &role.text {
+                    Some(display_text) => display_text.as_str(),
+                    None => text.as_str(),
+                };
+                format!(
+                    "<a class=\"reference external\" href=\"{}\">{}</a>",
+                    html_escape::encode_double_quoted_attribute(&url),
+                    html_escape::encode_text(text)
+                )
+            })
+            .collect();
+        Ok(links.join(", "))
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+}
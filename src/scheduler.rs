@@ -0,0 +1,192 @@
+//! Dependency-ordered scheduling of build work.
+//!
+//! Documents are rendered on a work-stealing thread pool, but a document only
+//! starts once every document it depends on is done: included documents before
+//! the documents including them, toctree children before their parents. Work
+//! that is ready is spawned as soon as its last dependency finishes, so the
+//! pool stays busy instead of waiting for whole levels of the graph.
+
+use anyhow::Result;
+use log::debug;
+use rayon::ThreadPool;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Run `task` on every item, each only after the items listed in its
+/// `dependencies` (indices into `items`). Results are returned in item order.
+///
+/// Dependency cycles are broken by ignoring the edges that close them. After
+/// the first failing task no new tasks are started and its error is returned.
+pub fn run_in_dependency_order<T, R, F>(
+    pool: &ThreadPool,
+    items: &[T],
+    dependencies: &[Vec<usize>],
+    task: F,
+) -> Result<Vec<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R> + Sync,
+{
+    let dependencies = acyclic_dependencies(dependencies, items.len());
+
+    let mut dependents = vec![Vec::new(); items.len()];
+    for (index, item_dependencies) in dependencies.iter().enumerate() {
+        for &dependency in item_dependencies {
+            dependents[dependency].push(index);
+        }
+    }
+
+    let schedule = Schedule {
+        items,
+        task,
+        dependents,
+        pending: dependencies.iter().map(|d| AtomicUsize::new(d.len())).collect(),
+        results: items.iter().map(|_| Mutex::new(None)).collect(),
+        failed: AtomicBool::new(false),
+        error: Mutex::new(None),
+    };
+
+    pool.scope(|scope| {
+        for (index, item_dependencies) in dependencies.iter().enumerate() {
+            if item_dependencies.is_empty() {
+                let schedule = &schedule;
+                scope.spawn(move |scope| schedule.run(scope, index));
+            }
+        }
+    });
+
+    if let Some(error) = schedule.error.into_inner().unwrap() {
+        return Err(error);
+    }
+    Ok(schedule
+        .results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap().expect("every task ran"))
+        .collect())
+}
+
+struct Schedule<'a, T, R, F> {
+    items: &'a [T],
+    task: F,
+    /// Items waiting on each item
+    dependents: Vec<Vec<usize>>,
+    /// Number of unfinished dependencies of each item
+    pending: Vec<AtomicUsize>,
+    results: Vec<Mutex<Option<R>>>,
+    failed: AtomicBool,
+    error: Mutex<Option<anyhow::Error>>,
+}
+
+impl<'a, T, R, F> Schedule<'a, T, R, F>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R> + Sync,
+{
+    fn run<'s>(&'s self, scope: &rayon::Scope<'s>, index: usize) {
+        if self.failed.load(Ordering::Relaxed) {
+            return;
+        }
+        match (self.task)(&self.items[index]) {
+            Ok(result) => *self.results[index].lock().unwrap() = Some(result),
+            Err(error) => {
+                self.failed.store(true, Ordering::Relaxed);
+                self.error.lock().unwrap().get_or_insert(error);
+                return;
+            }
+        }
+        for &dependent in &self.dependents[index] {
+            if self.pending[dependent].fetch_sub(1, Ordering::AcqRel) == 1 {
+                scope.spawn(move |scope| self.run(scope, dependent));
+            }
+        }
+    }
+}
+
+/// The dependencies without the edges closing a cycle, found by a depth-first
+/// search. Out-of-range and duplicate dependencies are dropped too.
+fn acyclic_dependencies(dependencies: &[Vec<usize>], count: usize) -> Vec<Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        New,
+        Visiting,
+        Done,
+    }
+
+    let mut state = vec![State::New; count];
+    let mut result = vec![Vec::new(); count];
+    let mut dropped = 0;
+
+    for root in 0..count {
+        if state[root] != State::New {
+            continue;
+        }
+        // Stack of (item, position in its dependency list)
+        let mut stack = vec![(root, 0)];
+        state[root] = State::Visiting;
+        while let Some((item, position)) = stack.last_mut() {
+            let item = *item;
+            let item_dependencies = dependencies.get(item).map_or(&[][..], Vec::as_slice);
+            let Some(&dependency) = item_dependencies.get(*position) else {
+                state[item] = State::Done;
+                stack.pop();
+                continue;
+            };
+            *position += 1;
+            if dependency >= count || result[item].contains(&dependency) {
+                continue;
+            }
+            match state[dependency] {
+                State::Visiting => dropped += 1,
+                State::Done => result[item].push(dependency),
+                State::New => {
+                    result[item].push(dependency);
+                    state[dependency] = State::Visiting;
+                    stack.push((dependency, 0));
+                }
+            }
+        }
+    }
+
+    if dropped > 0 {
+        debug!("Ignored {} dependencies closing a cycle", dropped);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tasks_run_after_their_dependencies() {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let finished = Mutex::new(Vec::new());
+        let items: Vec<usize> = (0..6).collect();
+        // 0 needs 1 and 2, 2 needs 3; 4 and 5 form a cycle
+        let dependencies = vec![vec![1, 2], vec![], vec![3], vec![], vec![5], vec![4]];
+
+        let results = run_in_dependency_order(&pool, &items, &dependencies, |&item| {
+            finished.lock().unwrap().push(item);
+            Ok(item * 10)
+        })
+        .unwrap();
+
+        assert_eq!(results, vec![0, 10, 20, 30, 40, 50]);
+        let order = finished.into_inner().unwrap();
+        let position = |item| order.iter().position(|&i| i == item).unwrap();
+        assert!(position(1) < position(0));
+        assert!(position(3) < position(2) && position(2) < position(0));
+        assert_eq!(order.len(), 6);
+
+        let error = run_in_dependency_order(&pool, &items, &dependencies, |&item| {
+            if item == 3 {
+                anyhow::bail!("cannot render {}", item);
+            }
+            Ok(item)
+        })
+        .unwrap_err();
+        assert_eq!(error.to_string(), "cannot render 3");
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Search index that mirrors Sphinx's search functionality
 #[derive(Debug, Clone, Default)]
@@ -266,26 +266,29 @@ impl SearchIndex {
     }
 
     /// Export search index to JSON format compatible with Sphinx
+    ///
+    /// Maps are written with sorted keys, so the same documents always give
+    /// the same bytes.
     pub fn to_json(&self) -> Result<String> {
         #[derive(Serialize)]
         struct JsonSearchIndex<'a> {
             docnames: &'a Vec<String>,
             filenames: &'a Vec<String>,
             titles: &'a Vec<String>,
-            terms: &'a HashMap<String, Vec<DocumentMatch>>,
-            objects: &'a HashMap<String, ObjectReference>,
-            objnames: &'a HashMap<String, String>,
-            objtypes: &'a HashMap<String, String>,
+            terms: BTreeMap<&'a String, &'a Vec<DocumentMatch>>,
+            objects: BTreeMap<&'a String, &'a ObjectReference>,
+            objnames: BTreeMap<&'a String, &'a String>,
+            objtypes: BTreeMap<&'a String, &'a String>,
         }
 
         let json_index = JsonSearchIndex {
             docnames: &self.docnames,
             filenames: &self.filenames,
             titles: &self.titles,
-            terms: &self.terms,
-            objects: &self.objects,
-            objnames: &self.objnames,
-            objtypes: &self.objtypes,
+            terms: self.terms.iter().collect(),
+            objects: self.objects.iter().collect(),
+            objnames: self.objnames.iter().collect(),
+            objtypes: self.objtypes.iter().collect(),
         };
 
         Ok(serde_json::to_string(&json_index)?)
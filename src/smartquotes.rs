@@ -0,0 +1,155 @@
+//! Typographic quotes, dashes and ellipses for prose, like docutils'
+//! SmartQuotes transform that Sphinx enables by default.
+
+/// Opening and closing double quotes, then opening and closing single quotes
+type Quotes = [&'static str; 4];
+
+const ENGLISH_QUOTES: Quotes = ["“", "”", "‘", "’"];
+
+/// Quotes per language, after docutils' table
+fn language_quotes(language: &str) -> Option<Quotes> {
+    Some(match language {
+        "en" | "nl" | "zh" | "ko" | "tr" | "eo" => ENGLISH_QUOTES,
+        "de" | "cs" | "sk" | "sl" | "lt" | "et" | "is" | "bg" => ["„", "“", "‚", "‘"],
+        "de-ch" | "fr-ch" | "it-ch" => ["«", "»", "‹", "›"],
+        "fr" => ["«\u{a0}", "\u{a0}»", "“", "”"],
+        "it" | "es" | "pt" | "ca" | "el" | "nb" | "no" => ["«", "»", "“", "”"],
+        "ru" | "uk" | "be" => ["«", "»", "„", "“"],
+        "pl" | "ro" | "hu" => ["„", "”", "«", "»"],
+        "da" => ["»", "«", "›", "‹"],
+        "sv" | "fi" => ["”", "”", "’", "’"],
+        "ja" => ["「", "」", "『", "』"],
+        "he" => ["”", "“", "»", "«"],
+        _ => return None,
+    })
+}
+
+/// How `--` and `---` turn into dashes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dashes {
+    None,
+    /// `d`: `--` is an em dash
+    Em,
+    /// `D`: `--` is an en dash and `---` an em dash
+    OldSchool,
+    /// `i`: `--` is an em dash and `---` an en dash
+    Inverted,
+}
+
+/// Converts straight quotes, dashes and dots in text to typographic ones
+#[derive(Debug, Clone)]
+pub struct SmartQuotes {
+    quotes: Option<Quotes>,
+    dashes: Dashes,
+    ellipses: bool,
+}
+
+impl SmartQuotes {
+    /// Conversions chosen by the letters of Sphinx's `smartquotes_action`
+    /// (`q` quotes, `D`, `d` or `i` dashes, `e` ellipses), with the quotes
+    /// of `language`, falling back to English ones
+    pub fn new(action: &str, language: &str) -> Self {
+        let language = language.to_lowercase().replace('_', "-");
+        let primary = language.split('-').next().unwrap_or_default();
+        let quotes = language_quotes(&language)
+            .or_else(|| language_quotes(primary))
+            .unwrap_or(ENGLISH_QUOTES);
+        let dashes = if action.contains('D') {
+            Dashes::OldSchool
+        } else if action.contains('i') {
+            Dashes::Inverted
+        } else if action.contains('d') {
+            Dashes::Em
+        } else {
+            Dashes::None
+        };
+        Self {
+            quotes: action.contains('q').then_some(quotes),
+            dashes,
+            ellipses: action.contains('e'),
+        }
+    }
+
+    /// Convert `text`, which comes between the characters `prev` and `after`
+    /// of the paragraph
+    pub fn educate(&self, text: &str, prev: Option<char>, after: Option<char>) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::with_capacity(text.len());
+        let mut prev = prev;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let rest = &chars[i..];
+            let next = chars.get(i + 1).copied().or(after);
+
+            if self.ellipses && (rest.starts_with(&['.', '.', '.']) || rest.starts_with(&['.', ' ', '.', ' ', '.'])) {
+                out.push('…');
+                i += if rest[1] == ' ' { 5 } else { 3 };
+                prev = Some('…');
+                continue;
+            }
+            if c == '-' && chars.get(i + 1) == Some(&'-') {
+                let triple = chars.get(i + 2) == Some(&'-');
+                let dash = match (self.dashes, triple) {
+                    (Dashes::None, _) => None,
+                    (Dashes::Em, _) => Some(("—", 2)),
+                    (Dashes::OldSchool, true) | (Dashes::Inverted, false) => Some(("—", if triple { 3 } else { 2 })),
+                    (Dashes::OldSchool, false) | (Dashes::Inverted, true) => Some(("–", if triple { 3 } else { 2 })),
+                };
+                if let Some((dash, len)) = dash {
+                    out.push_str(dash);
+                    i += len;
+                    prev = dash.chars().next();
+                    continue;
+                }
+            }
+            if let (Some(quotes), '"' | '\'') = (self.quotes, c) {
+                let opens = prev.is_none_or(opens_quote) && next.is_some_and(|next| !next.is_whitespace());
+                let quote = match c {
+                    '"' if opens => quotes[0],
+                    '"' => quotes[1],
+                    // Apostrophes in words and in decades like '80s
+                    _ if prev.is_some_and(char::is_alphanumeric) || is_decade(&chars[i + 1..]) => "’",
+                    _ if opens => quotes[2],
+                    _ => quotes[3],
+                };
+                out.push_str(quote);
+                prev = Some(c);
+                i += 1;
+                continue;
+            }
+            out.push(c);
+            prev = Some(c);
+            i += 1;
+        }
+        out
+    }
+}
+
+/// Whether a quote after `prev` opens a quotation
+fn opens_quote(prev: char) -> bool {
+    prev.is_whitespace() || "([{<-–—/\u{a0}“‘«‹„‚".contains(prev)
+}
+
+/// Whether text after an apostrophe abbreviates a decade, as in `'80s`
+fn is_decade(rest: &[char]) -> bool {
+    rest.len() >= 3 && rest[0].is_ascii_digit() && rest[1].is_ascii_digit() && rest[2] == 's'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_educate_with_sphinx_default_action() {
+        let smart = SmartQuotes::new("qDe", "en");
+        assert_eq!(
+            smart.educate("\"Hello\" -- it's 'quoted' --- in the '80s...", None, None),
+            "“Hello” – it’s ‘quoted’ — in the ’80s…"
+        );
+        assert_eq!(smart.educate("\" closes", Some('x'), None), "” closes");
+        assert_eq!(SmartQuotes::new("qDe", "de_DE").educate("\"Hallo\"", None, None), "„Hallo“");
+        assert_eq!(SmartQuotes::new("qDe", "fr").educate("\"oui\"", None, None), "«\u{a0}oui\u{a0}»");
+        assert_eq!(SmartQuotes::new("e", "en").educate("\"a\" -- b...", None, None), "\"a\" -- b…");
+    }
+}
@@ -0,0 +1,349 @@
+//! Social card images: per-page OpenGraph previews showing the page title,
+//! description and project branding, rasterized to PNG like the card mode of
+//! `sphinxext-opengraph`.
+
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use std::path::{Path, PathBuf};
+use tiny_skia::{Color, Paint, Pixmap, PixmapPaint, Rect, Transform};
+
+use crate::config::SocialCardsConfig;
+
+/// Card size recommended for `summary_large_image` previews
+pub const CARD_WIDTH: u32 = 1200;
+pub const CARD_HEIGHT: u32 = 630;
+
+/// Output directory of the generated cards
+pub const CARD_DIR: &str = "_images/social_previews";
+
+const MARGIN: f32 = 80.0;
+const LOGO_SIZE: f32 = 140.0;
+
+/// Regular and bold fonts tried, in order, when no font is configured
+const SYSTEM_FONTS: &[(&str, &str)] = &[
+    (
+        "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+        "/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf",
+    ),
+    (
+        "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+        "/usr/share/fonts/dejavu/DejaVuSans-Bold.ttf",
+    ),
+    (
+        "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+        "/usr/share/fonts/truetype/liberation/LiberationSans-Bold.ttf",
+    ),
+    (
+        "/System/Library/Fonts/Supplemental/Arial.ttf",
+        "/System/Library/Fonts/Supplemental/Arial Bold.ttf",
+    ),
+    ("/Library/Fonts/Arial.ttf", "/Library/Fonts/Arial Bold.ttf"),
+    ("C:\\Windows\\Fonts\\arial.ttf", "C:\\Windows\\Fonts\\arialbd.ttf"),
+];
+
+/// Text shown on a card
+#[derive(Debug, Clone, Copy)]
+pub struct SocialCard<'a> {
+    pub title: &'a str,
+    pub description: Option<&'a str>,
+    pub site_name: &'a str,
+    pub site_url: Option<&'a str>,
+}
+
+/// Renders cards with the configured fonts, colors and logo
+pub struct SocialCardRenderer {
+    regular: FontArc,
+    bold: FontArc,
+    logo: Option<Pixmap>,
+    text_color: Color,
+    line_color: Color,
+}
+
+impl std::fmt::Debug for SocialCardRenderer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SocialCardRenderer")
+            .field("has_logo", &self.logo.is_some())
+            .finish()
+    }
+}
+
+impl SocialCardRenderer {
+    /// Load the fonts and logo. `default_logo` (usually `html_logo`) is used
+    /// when the cards configure no image; only PNG logos are drawn.
+    pub fn new(
+        config: &SocialCardsConfig,
+        source_dir: &Path,
+        default_logo: Option<&str>,
+    ) -> Result<Self> {
+        let (regular, bold) = match &config.font {
+            Some(font) => {
+                let font = load_font(&source_dir.join(font))?;
+                (font.clone(), font)
+            }
+            None => system_fonts()?,
+        };
+
+        let logo = config
+            .image
+            .as_deref()
+            .or(default_logo)
+            .map(|image| source_dir.join(image))
+            .and_then(|path| match std::fs::read(&path).map(|data| Pixmap::decode_png(&data)) {
+                Ok(Ok(logo)) => Some(logo),
+                _ => {
+                    debug!("Social cards: skipping logo {}, not a readable PNG", path.display());
+                    None
+                }
+            });
+
+        Ok(Self {
+            regular,
+            bold,
+            logo,
+            text_color: parse_color(&config.text_color)?,
+            line_color: parse_color(&config.line_color)?,
+        })
+    }
+
+    /// Render a card to PNG bytes
+    pub fn render(&self, card: &SocialCard) -> Result<Vec<u8>> {
+        let mut pixmap =
+            Pixmap::new(CARD_WIDTH, CARD_HEIGHT).ok_or_else(|| anyhow!("Invalid card size"))?;
+        pixmap.fill(Color::WHITE);
+        let width = CARD_WIDTH as f32;
+        let height = CARD_HEIGHT as f32;
+
+        // Accent line along the bottom edge
+        let mut paint = Paint::default();
+        paint.set_color(self.line_color);
+        if let Some(line) = Rect::from_xywh(0.0, height - 20.0, width, 20.0) {
+            pixmap.fill_rect(line, &paint, Transform::identity(), None);
+        }
+
+        // Logo in the top right corner, scaled to fit
+        let mut text_width = width - 2.0 * MARGIN;
+        if let Some(logo) = &self.logo {
+            let scale = (LOGO_SIZE / logo.width() as f32).min(LOGO_SIZE / logo.height() as f32);
+            let x = width - MARGIN - logo.width() as f32 * scale;
+            let transform = Transform::from_row(scale, 0.0, 0.0, scale, x, MARGIN - 20.0);
+            pixmap.draw_pixmap(0, 0, logo.as_ref(), &PixmapPaint::default(), transform, None);
+            text_width -= LOGO_SIZE + 20.0;
+        }
+
+        let muted = with_alpha(self.text_color, 0.75);
+        let mut baseline = MARGIN + 10.0;
+        draw_text(&mut pixmap, &self.regular, 36.0, MARGIN, baseline, card.site_name, muted);
+
+        baseline += 120.0;
+        for line in wrap_text(&self.bold, 72.0, card.title, text_width, 3) {
+            draw_text(&mut pixmap, &self.bold, 72.0, MARGIN, baseline, &line, self.text_color);
+            baseline += 86.0;
+        }
+
+        if let Some(description) = card.description {
+            baseline += 10.0;
+            let max_lines = ((height - 110.0 - baseline) / 42.0).max(0.0) as usize;
+            for line in wrap_text(&self.regular, 32.0, description, width - 2.0 * MARGIN, max_lines) {
+                draw_text(&mut pixmap, &self.regular, 32.0, MARGIN, baseline, &line, muted);
+                baseline += 42.0;
+            }
+        }
+
+        if let Some(site_url) = card.site_url {
+            let url = site_url.split("://").last().unwrap_or(site_url).trim_end_matches('/');
+            draw_text(&mut pixmap, &self.regular, 28.0, MARGIN, height - 50.0, url, muted);
+        }
+
+        pixmap
+            .encode_png()
+            .map_err(|e| anyhow!("Failed to encode social card: {}", e))
+    }
+}
+
+/// Draw one line of text with its baseline at `baseline`
+fn draw_text(
+    pixmap: &mut Pixmap,
+    font: &FontArc,
+    size: f32,
+    x: f32,
+    baseline: f32,
+    text: &str,
+    color: Color,
+) {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let (width, height) = (pixmap.width() as i64, pixmap.height() as i64);
+    let data = pixmap.data_mut();
+    let mut caret = x;
+    let mut previous = None;
+
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(size, ab_glyph::point(caret, baseline));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            if px < 0 || py < 0 || px >= width || py >= height {
+                return;
+            }
+            // The card is opaque, so blending straight colors is exact
+            let alpha = coverage.clamp(0.0, 1.0) * color.alpha();
+            let index = ((py * width + px) * 4) as usize;
+            for (channel, value) in [color.red(), color.green(), color.blue()].iter().enumerate() {
+                let dst = data[index + channel] as f32;
+                data[index + channel] = (dst + (value * 255.0 - dst) * alpha).round() as u8;
+            }
+        });
+    }
+}
+
+/// Output path of a page's card, relative to the output directory
+pub fn card_path(doc_path: &str) -> String {
+    format!("{}/{}.png", CARD_DIR, doc_path)
+}
+
+/// Greedily wrap `text` into at most `max_lines` lines no wider than
+/// `max_width`, ending the last line with an ellipsis when text is cut
+fn wrap_text(font: &FontArc, size: f32, text: &str, max_width: f32, max_lines: usize) -> Vec<String> {
+    let measure = |s: &str| text_width(font, size, s);
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut truncated = false;
+
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if measure(&candidate) <= max_width || current.is_empty() {
+            current = candidate;
+            continue;
+        }
+        if lines.len() + 1 == max_lines {
+            truncated = true;
+            break;
+        }
+        lines.push(std::mem::replace(&mut current, word.to_string()));
+    }
+    if !current.is_empty() && lines.len() < max_lines {
+        lines.push(current);
+    }
+
+    if let Some(last) = lines.last_mut() {
+        if truncated || measure(last) > max_width {
+            while !last.is_empty() && measure(&format!("{}…", last)) > max_width {
+                last.pop();
+            }
+            let trimmed = last.trim_end().len();
+            last.truncate(trimmed);
+            last.push('…');
+        }
+    }
+    lines
+}
+
+fn text_width(font: &FontArc, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+fn load_font(path: &Path) -> Result<FontArc> {
+    let data = std::fs::read(path)
+        .with_context(|| format!("Failed to read social card font: {}", path.display()))?;
+    FontArc::try_from_vec(data)
+        .map_err(|_| anyhow!("Invalid social card font: {}", path.display()))
+}
+
+fn system_fonts() -> Result<(FontArc, FontArc)> {
+    for (regular, bold) in SYSTEM_FONTS {
+        let regular = PathBuf::from(regular);
+        if regular.exists() {
+            let regular = load_font(&regular)?;
+            let bold = load_font(Path::new(bold)).unwrap_or_else(|_| regular.clone());
+            return Ok((regular, bold));
+        }
+    }
+    Err(anyhow!(
+        "No font found for social cards; set opengraph.social_cards.font"
+    ))
+}
+
+/// Parse a `#rrggbb` or `#rgb` color
+fn parse_color(color: &str) -> Result<Color> {
+    let hex = color.trim().trim_start_matches('#');
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => String::new(),
+    };
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    match (channel(0), channel(2), channel(4)) {
+        (Some(r), Some(g), Some(b)) => Ok(Color::from_rgba8(r, g, b, 255)),
+        _ => Err(anyhow!("Invalid social card color '{}'", color)),
+    }
+}
+
+fn with_alpha(mut color: Color, alpha: f32) -> Color {
+    color.set_alpha(alpha);
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#ff0000").unwrap(), Color::from_rgba8(255, 0, 0, 255));
+        assert_eq!(parse_color("0f0").unwrap(), Color::from_rgba8(0, 255, 0, 255));
+        assert!(parse_color("red").is_err());
+    }
+
+    #[test]
+    fn test_render_card() {
+        // Cards need a font; skip where the system has none of the defaults
+        let Ok(renderer) =
+            SocialCardRenderer::new(&SocialCardsConfig::default(), Path::new("."), None)
+        else {
+            return;
+        };
+
+        let png = renderer
+            .render(&SocialCard {
+                title: "A rather long page title that has to wrap over several lines of the card",
+                description: Some("What the page is about."),
+                site_name: "Example",
+                site_url: Some("https://docs.example.org/"),
+            })
+            .unwrap();
+        let card = Pixmap::decode_png(&png).unwrap();
+        assert_eq!((card.width(), card.height()), (CARD_WIDTH, CARD_HEIGHT));
+        // Some text was drawn on the white background
+        assert!(card.pixels().iter().any(|p| p.red() < 128 && p.green() < 128));
+
+        let lines = wrap_text(&renderer.bold, 72.0, "word ".repeat(100).trim(), 1040.0, 3);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].ends_with('…'));
+    }
+}
@@ -0,0 +1,144 @@
+//! Timing of the build phases and of individual documents, used to find the
+//! bottlenecks of large builds.
+
+use serde::{Serialize, Serializer};
+use std::time::Duration;
+
+/// Number of slowest documents reported by default
+pub const DEFAULT_SLOWEST_DOCUMENTS: usize = 10;
+
+/// Time spent in each phase of a build.
+///
+/// `parse`, `render` and `write` add up the time of every document, so on a
+/// parallel build they can exceed the wall-clock build time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PhaseTimings {
+    /// Source discovery and dependency graph
+    #[serde(rename = "discovery_ms", serialize_with = "serialize_millis")]
+    pub discovery: Duration,
+    /// Parsing of the sources
+    #[serde(rename = "parse_ms", serialize_with = "serialize_millis")]
+    pub parse: Duration,
+    /// Rendering of the documents to HTML pages
+    #[serde(rename = "render_ms", serialize_with = "serialize_millis")]
+    pub render: Duration,
+    /// Writing of the pages, source copies and media
+    #[serde(rename = "write_ms", serialize_with = "serialize_millis")]
+    pub write: Duration,
+    /// Static assets, extra paths and pre-compression
+    #[serde(rename = "assets_ms", serialize_with = "serialize_millis")]
+    pub assets: Duration,
+    /// Document, directive and content validation
+    #[serde(rename = "validation_ms", serialize_with = "serialize_millis")]
+    pub validation: Duration,
+    /// Indices, additional pages and the search index
+    #[serde(rename = "index_ms", serialize_with = "serialize_millis")]
+    pub index: Duration,
+}
+
+impl PhaseTimings {
+    /// Phases with their names, in build order
+    pub fn phases(&self) -> [(&'static str, Duration); 7] {
+        [
+            ("discovery", self.discovery),
+            ("parse", self.parse),
+            ("render", self.render),
+            ("write", self.write),
+            ("assets", self.assets),
+            ("validation", self.validation),
+            ("index", self.index),
+        ]
+    }
+
+    /// Add the time of one document to the per-document phases
+    pub fn add_document(&mut self, timing: &DocumentTiming) {
+        self.parse += timing.parse;
+        self.render += timing.render;
+        self.write += timing.write;
+    }
+}
+
+/// Time spent on one document
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DocumentTiming {
+    /// Page name without extension, like `guide/intro`
+    pub doc_path: String,
+    #[serde(rename = "parse_ms", serialize_with = "serialize_millis")]
+    pub parse: Duration,
+    #[serde(rename = "render_ms", serialize_with = "serialize_millis")]
+    pub render: Duration,
+    #[serde(rename = "write_ms", serialize_with = "serialize_millis")]
+    pub write: Duration,
+}
+
+impl DocumentTiming {
+    pub fn total(&self) -> Duration {
+        self.parse + self.render + self.write
+    }
+}
+
+/// The `count` slowest documents, slowest first
+pub fn slowest_documents(mut timings: Vec<DocumentTiming>, count: usize) -> Vec<DocumentTiming> {
+    timings.sort_by(|a, b| {
+        b.total()
+            .cmp(&a.total())
+            .then_with(|| a.doc_path.cmp(&b.doc_path))
+    });
+    timings.truncate(count);
+    timings
+}
+
+/// Duration as milliseconds with microsecond precision, for reports
+pub fn millis(duration: Duration) -> f64 {
+    (duration.as_micros() as f64) / 1000.0
+}
+
+fn serialize_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(millis(*duration))
+}
+
+/// Human-readable duration, like `850.2ms` or `12.41s`
+pub fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{:.1}ms", millis(duration))
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(doc_path: &str, millis: u64) -> DocumentTiming {
+        DocumentTiming {
+            doc_path: doc_path.to_string(),
+            parse: Duration::from_millis(millis),
+            render: Duration::from_millis(millis * 2),
+            write: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_slowest_documents_and_serialization() {
+        let timings = vec![timing("a", 5), timing("b", 20), timing("c", 20), timing("d", 1)];
+        let mut phases = PhaseTimings::default();
+        for timing in &timings {
+            phases.add_document(timing);
+        }
+        assert_eq!(phases.parse, Duration::from_millis(46));
+        assert_eq!(phases.render, Duration::from_millis(92));
+
+        let slowest = slowest_documents(timings, 3);
+        let names: Vec<&str> = slowest.iter().map(|t| t.doc_path.as_str()).collect();
+        assert_eq!(names, ["b", "c", "a"]);
+
+        let json = serde_json::to_value(&slowest[0]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"doc_path": "b", "parse_ms": 20.0, "render_ms": 40.0, "write_ms": 0.0})
+        );
+        assert_eq!(format_duration(Duration::from_micros(1500)), "1.5ms");
+        assert_eq!(format_duration(Duration::from_millis(12_346)), "12.35s");
+    }
+}
@@ -0,0 +1,375 @@
+//! Summary printed at the end of a build: the outcome, the warnings and
+//! errors grouped by category, the files with the most of them and the
+//! time spent in each phase, laid out for the width of the terminal.
+
+use console::Style;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::builder::BuildStats;
+use crate::paths;
+use crate::stats;
+
+/// Number of files listed under "Files with most problems"
+pub const DEFAULT_TOP_FILES: usize = 5;
+
+/// Narrowest layout, used when the terminal is narrower still
+const MIN_WIDTH: usize = 40;
+
+/// How the summary is laid out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummaryOptions {
+    /// Color the outcome, the counts and the headings
+    pub color: bool,
+    /// Columns available; longer file names are shortened from the left
+    pub width: usize,
+    /// Number of files with the most problems listed
+    pub top_files: usize,
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        Self {
+            color: false,
+            width: 80,
+            top_files: DEFAULT_TOP_FILES,
+        }
+    }
+}
+
+impl SummaryOptions {
+    /// Options for printing to standard error: colored when it is a terminal
+    /// that takes colors (and `NO_COLOR` is unset) unless `no_color`, and as
+    /// wide as the terminal
+    pub fn for_stderr(no_color: bool) -> Self {
+        let width = console::Term::stderr()
+            .size_checked()
+            .map(|(_, columns)| usize::from(columns))
+            .unwrap_or(80);
+        Self {
+            color: !no_color && console::colors_enabled_stderr(),
+            width,
+            ..Self::default()
+        }
+    }
+}
+
+/// Render the summary of a build of the sources in `source_dir`
+pub fn render(stats: &BuildStats, source_dir: &Path, options: &SummaryOptions) -> String {
+    let width = options.width.max(MIN_WIDTH);
+    let style = |style: Style| style.force_styling(options.color);
+    let heading = style(Style::new().bold());
+    let warning = style(Style::new().yellow());
+    let error = style(Style::new().red().bold());
+
+    let mut out = String::new();
+    let (outcome, outcome_style) = if stats.errors > 0 {
+        ("build succeeded with problems", error.clone())
+    } else if stats.warnings > 0 {
+        ("build succeeded", warning.clone())
+    } else {
+        ("build succeeded", style(Style::new().green().bold()))
+    };
+    let mut parts = vec![outcome_style.apply_to(outcome).to_string()];
+    if stats.warnings > 0 {
+        parts.push(plural(stats.warnings, "warning"));
+    }
+    if stats.errors > 0 {
+        parts.push(plural(stats.errors, "error"));
+    }
+    if let Some(last) = parts.last_mut() {
+        last.push_str(&format!(
+            " in {}.",
+            stats::format_duration(stats.build_time)
+        ));
+    }
+    out.push_str(&wrap(&parts, width, ""));
+    out.push_str(&wrap(
+        &[
+            format!("{} processed", plural(stats.files_processed, "document")),
+            format!("{} from cache", stats.cache_hits),
+            format!("{:.1} MB of output", stats.output_size_mb),
+        ],
+        width,
+        "  ",
+    ));
+
+    let warning_categories = count_by(
+        stats
+            .warning_details
+            .iter()
+            .map(|w| w.warning_type.category().to_string()),
+    );
+    let error_categories = count_by(
+        stats
+            .error_details
+            .iter()
+            .map(|e| e.error_type.category().to_string()),
+    );
+    let files = count_by(
+        stats
+            .warning_details
+            .iter()
+            .map(|w| &w.file)
+            .chain(stats.error_details.iter().map(|e| &e.file))
+            .map(|file| {
+                paths::to_slash(
+                    &paths::relative_to(file, source_dir).unwrap_or_else(|| file.clone()),
+                )
+            }),
+    );
+    let files: Vec<(String, usize)> = files.into_iter().take(options.top_files).collect();
+
+    for (title, rows, count_style) in [
+        ("Warnings by category", &warning_categories, &warning),
+        ("Errors by category", &error_categories, &error),
+        ("Files with most problems", &files, &heading),
+    ] {
+        if rows.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n{}\n", heading.apply_to(title)));
+        let count_width = rows
+            .iter()
+            .map(|(_, count)| count.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let name_width = rows
+            .iter()
+            .map(|(name, _)| console::measure_text_width(name))
+            .max()
+            .unwrap_or(0)
+            .min(width - count_width - 4);
+        for (name, count) in rows {
+            out.push_str(&format!(
+                "  {:<name_width$}  {}\n",
+                shorten_left(name, name_width),
+                count_style.apply_to(format!("{:>count_width$}", count)),
+            ));
+        }
+    }
+
+    out.push_str(&format!("\n{}\n", heading.apply_to("Phases")));
+    let phases = stats.phases.phases();
+    let name_width = phases.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let cell_width = name_width + 10;
+    let columns = ((width - 2 + 3) / (cell_width + 3)).max(1);
+    for row in phases.chunks(columns) {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|(name, duration)| {
+                format!(
+                    "{:<name_width$}{:>10}",
+                    name,
+                    stats::format_duration(*duration)
+                )
+            })
+            .collect();
+        out.push_str(&format!("  {}\n", cells.join("   ").trim_end()));
+    }
+
+    if !stats.slowest_documents.is_empty() {
+        out.push_str(&format!("\n{}\n", heading.apply_to("Slowest documents")));
+        let name_width = stats
+            .slowest_documents
+            .iter()
+            .map(|timing| console::measure_text_width(&timing.doc_path))
+            .max()
+            .unwrap_or(0)
+            .min(width - 14);
+        for timing in &stats.slowest_documents {
+            out.push_str(&format!(
+                "  {:<name_width$}{:>10}\n",
+                shorten_left(&timing.doc_path, name_width),
+                stats::format_duration(timing.total()),
+            ));
+        }
+    }
+    out
+}
+
+/// `parts` joined by ", " on lines that fit `width`
+/// columns, each starting with `indent`
+fn wrap(parts: &[String], width: usize, indent: &str) -> String {
+    let mut out = String::new();
+    let mut line = indent.to_string();
+    for (i, part) in parts.iter().enumerate() {
+        let separator = if i == 0 { "" } else { ", " };
+        let fits = console::measure_text_width(&line)
+            + console::measure_text_width(separator)
+            + console::measure_text_width(part)
+            <= width;
+        if i > 0 && !fits {
+            out.push_str(line.trim_end());
+            out.push('\n');
+            line = format!("{}  {}", indent, part);
+        } else {
+            line.push_str(separator);
+            line.push_str(part);
+        }
+    }
+    out.push_str(&line);
+    out.push('\n');
+    out
+}
+
+/// Counts of each name, most frequent first, then by name
+fn count_by(names: impl Iterator<Item = String>) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        *counts.entry(name).or_default() += 1;
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+/// `text` cut to `width` columns by dropping its start, which for paths
+/// keeps the file name
+fn shorten_left(text: &str, width: usize) -> String {
+    if console::measure_text_width(text) <= width {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let kept = width.saturating_sub(1);
+    format!(
+        "…{}",
+        chars[chars.len() - kept..].iter().collect::<String>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{BuildErrorReport, BuildWarning, ErrorType, WarningType};
+    use crate::stats::{DocumentTiming, PhaseTimings};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn stats(source_dir: &Path) -> BuildStats {
+        let warning = |file: &str, warning_type| {
+            BuildWarning::new(
+                source_dir.join(file),
+                Some(1),
+                "problem".to_string(),
+                warning_type,
+            )
+        };
+        BuildStats {
+            files_processed: 12,
+            files_skipped: 0,
+            build_time: Duration::from_millis(1500),
+            output_size_mb: 2.5,
+            cache_hits: 4,
+            errors: 1,
+            warnings: 4,
+            warning_details: vec![
+                warning("guide/intro.rst", WarningType::BrokenCrossReference),
+                warning("guide/intro.rst", WarningType::BrokenCrossReference),
+                warning("index.rst", WarningType::MissingToctreeRef),
+                warning(
+                    "reference/a/very/deeply/nested/directory/of/generated/pages.rst",
+                    WarningType::BrokenCrossReference,
+                ),
+            ],
+            error_details: vec![BuildErrorReport::new(
+                PathBuf::from("_templates/layout.html"),
+                Some(3),
+                "problem".to_string(),
+                ErrorType::TemplateError,
+            )],
+            phases: PhaseTimings {
+                parse: Duration::from_millis(300),
+                render: Duration::from_millis(900),
+                ..PhaseTimings::default()
+            },
+            slowest_documents: vec![DocumentTiming {
+                doc_path: "guide/intro".to_string(),
+                parse: Duration::from_millis(2),
+                render: Duration::from_millis(10),
+                write: Duration::from_millis(0),
+            }],
+            stale_outputs_removed: 0,
+        }
+    }
+
+    #[test]
+    fn test_summary_groups_problems() {
+        let source_dir = Path::new("/docs");
+        let options = SummaryOptions {
+            width: 60,
+            ..SummaryOptions::default()
+        };
+        let summary = render(&stats(source_dir), source_dir, &options);
+        assert_eq!(
+            summary,
+            "build succeeded with problems, 4 warnings, 1 error in 1.50s.
+  12 documents processed, 4 from cache, 2.5 MB of output
+
+Warnings by category
+  broken-cross-reference  3
+  missing-toctree-ref     1
+
+Errors by category
+  template  1
+
+Files with most problems
+  guide/intro.rst                                          2
+  _templates/layout.html                                   1
+  index.rst                                                1
+  …/a/very/deeply/nested/directory/of/generated/pages.rst  1
+
+Phases
+  discovery      0.0ms   parse        300.0ms
+  render       900.0ms   write          0.0ms
+  assets         0.0ms   validation     0.0ms
+  index          0.0ms
+
+Slowest documents
+  guide/intro    12.0ms
+"
+        );
+        assert!(summary
+            .lines()
+            .all(|line| console::measure_text_width(line) <= 60));
+        assert!(!summary.contains('\u{1b}'));
+    }
+
+    #[test]
+    fn test_summary_colors_and_narrow_terminals() {
+        let source_dir = Path::new("/docs");
+        let options = SummaryOptions {
+            color: true,
+            width: 30,
+            top_files: 1,
+        };
+        let summary = render(&stats(source_dir), source_dir, &options);
+        assert!(
+            summary.contains("\u{1b}[31m\u{1b}[1mbuild succeeded with problems\u{1b}[0m"),
+            "got: {}",
+            summary
+        );
+        assert!(
+            summary.contains("\n  discovery      0.0ms\n  parse        300.0ms\n"),
+            "got: {}",
+            summary
+        );
+        assert!(!summary.contains("index.rst"), "got: {}", summary);
+        let plain = console::strip_ansi_codes(&summary);
+        assert!(
+            plain
+                .lines()
+                .all(|line| console::measure_text_width(line) <= MIN_WIDTH),
+            "got: {}",
+            plain
+        );
+    }
+}
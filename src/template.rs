@@ -1,9 +1,17 @@
-use anyhow::Result;
-use log::info;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use minijinja::value::{Kwargs, Object};
 use minijinja::{Environment, Error as MinijinjaError, ErrorKind, Value};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Read, Seek};
 use std::path::{Path, PathBuf};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::environment::BuildEnvironment;
+use crate::navigation::{NavigationBuilder, ToctreeOptions};
 
 /// Marker type for HTML strings that should not be escaped in templates.
 /// When serialized to JSON and then converted to minijinja Value, this will
@@ -20,11 +28,349 @@ impl SafeHtml {
     }
 }
 
+/// Build the per-page `toctree()` template function. Like Sphinx it accepts
+/// `maxdepth`, `collapse`, `titles_only` and `includehidden` keyword
+/// arguments, each overriding the corresponding value in `defaults`.
+pub fn toctree_function(navigation: Arc<Mutex<NavigationBuilder>>, defaults: ToctreeOptions) -> Value {
+    Value::from_function(move |kwargs: Kwargs| -> Result<Value, MinijinjaError> {
+        let mut options = defaults.clone();
+        if let Some(maxdepth) = kwargs.get::<Option<Value>>("maxdepth")? {
+            // Sphinx uses -1 for unlimited depth
+            let depth = i64::try_from(maxdepth.clone())
+                .ok()
+                .or_else(|| maxdepth.as_str().and_then(|s| s.trim().parse().ok()))
+                .unwrap_or(-1);
+            options.maxdepth = depth.max(0) as usize;
+        }
+        if let Some(collapse) = kwargs.get::<Option<Value>>("collapse")? {
+            options.collapse = value_to_bool(&collapse);
+        }
+        if let Some(titles_only) = kwargs.get::<Option<Value>>("titles_only")? {
+            options.titles_only = value_to_bool(&titles_only);
+        }
+        if let Some(includehidden) = kwargs.get::<Option<Value>>("includehidden")? {
+            options.includehidden = value_to_bool(&includehidden);
+        }
+        kwargs.assert_all_used()?;
+
+        let items = navigation.lock().render_toctree(&options);
+        if items.is_empty() {
+            return Ok(Value::from_safe_string(String::new()));
+        }
+        Ok(Value::from_safe_string(format!("<ul class=\"current\">\n{}</ul>\n", items)))
+    })
+}
+
+/// Extra `<link>`/`<script>` attributes, such as subresource `integrity`
+/// hashes, keyed by asset URL
+pub type AssetAttributes = HashMap<String, Vec<(String, String)>>;
+
+/// `<link>` tag of a stylesheet
+pub fn css_tag(filename: &str, attributes: &[(String, String)]) -> String {
+    format!(
+        r#"<link rel="stylesheet" href="{}" type="text/css"{} />"#,
+        filename,
+        render_attributes(attributes)
+    )
+}
+
+/// `<script>` tag of a script
+pub fn js_tag(filename: &str, attributes: &[(String, String)]) -> String {
+    format!(r#"<script src="{}"{}></script>"#, filename, render_attributes(attributes))
+}
+
+fn render_attributes(attributes: &[(String, String)]) -> String {
+    attributes
+        .iter()
+        .map(|(name, value)| {
+            format!(" {}=\"{}\"", name, html_escape::encode_double_quoted_attribute(value))
+        })
+        .collect()
+}
+
+/// Build a `css_tag(file)`/`js_tag(file)` template function that adds each
+/// asset's attributes to the tag made by `tag`. Files are relative to the
+/// output root and linked from the page of `pagename`.
+pub fn asset_tag_function(
+    attributes: Arc<AssetAttributes>,
+    tag: fn(&str, &[(String, String)]) -> String,
+    pagename: &str,
+) -> Value {
+    let root = root_path(pagename);
+    Value::from_function(move |args: &[Value]| -> Result<Value, MinijinjaError> {
+        let asset = args.first().ok_or_else(|| {
+            MinijinjaError::new(ErrorKind::InvalidOperation, "asset tag requires a file argument")
+        })?;
+        // Use from_safe_string to prevent HTML escaping
+        Ok(Value::from_safe_string(match asset.as_str() {
+            Some(filename) => {
+                let url = if is_external(filename) {
+                    filename.to_string()
+                } else {
+                    format!("{}{}", root, filename)
+                };
+                tag(&url, attributes.get(filename).map(Vec::as_slice).unwrap_or_default())
+            }
+            None => String::new(),
+        }))
+    })
+}
+
+/// Relative path from the page of `pagename` to the output root, like `../`
+fn root_path(pagename: &str) -> String {
+    "../".repeat(pagename.matches('/').count())
+}
+
+/// Whether a link target is a URL or an anchor rather than a path
+fn is_external(target: &str) -> bool {
+    target.contains("://")
+        || target.starts_with('#')
+        || target.starts_with("//")
+        || ["mailto:", "data:"].iter().any(|scheme| target.starts_with(scheme))
+}
+
+/// URL of `target` on the page of `pagename`, like Sphinx's `pathto()`. A
+/// document name, optionally with an `#anchor`, becomes the URL of its page;
+/// with `resource` the target is a file under the output root instead, with
+/// bare names taken from `_static`. URLs and anchors are kept as given.
+pub fn pathto(pagename: &str, target: &str, resource: bool) -> String {
+    if is_external(target) {
+        return target.to_string();
+    }
+    if resource {
+        let root = root_path(pagename);
+        return if target.is_empty() || target.starts_with("_static/") || target.starts_with("_sources/") {
+            format!("{}{}", root, target)
+        } else {
+            format!("{}_static/{}", root, target)
+        };
+    }
+    let (document, anchor) = match target.split_once('#') {
+        Some((document, anchor)) => (document, format!("#{}", anchor)),
+        None => (target, String::new()),
+    };
+    format!("{}{}", crate::navigation::relative_uri(pagename, document), anchor)
+}
+
+/// Build the per-page `pathto(target, resource=false)` template function;
+/// `resource` may also be given as the second argument
+pub fn pathto_function(pagename: &str) -> Value {
+    let pagename = pagename.to_string();
+    Value::from_function(
+        move |target: String, resource: Option<Value>, kwargs: Kwargs| -> Result<Value, MinijinjaError> {
+            let resource = match kwargs.get::<Option<Value>>("resource")? {
+                Some(resource) => Some(resource),
+                None => resource,
+            };
+            kwargs.assert_all_used()?;
+            let resource = resource.as_ref().is_some_and(value_to_bool);
+            // Safe string so the slashes of the path are not escaped
+            Ok(Value::from_safe_string(pathto(&pagename, &target, resource)))
+        },
+    )
+}
+
+/// Build the `hasdoc(name)` template function, true for the documents of
+/// the project and the `pages` generated besides them
+pub fn hasdoc_function(environment: Arc<BuildEnvironment>, pages: Vec<String>) -> Value {
+    Value::from_function(move |name: String| -> bool {
+        environment.has_document(&name) || pages.contains(&name)
+    })
+}
+
+/// A link of the relation bar, like Sphinx's `rellinks`: the page, its
+/// title, an access key and the link text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RelLink(pub String, pub String, pub String, pub String);
+
+/// The relation bar of Sphinx's basic theme: the `rellinks` on the right,
+/// then the root document, the parents and the page itself. Titles are
+/// HTML.
+pub fn render_relbar(
+    pagename: &str,
+    rellinks: &[RelLink],
+    root: (&str, &str),
+    parents: &[crate::navigation::NavLink],
+    title: &str,
+) -> String {
+    let attribute = |value: &str| html_escape::encode_double_quoted_attribute(value).into_owned();
+    let mut html = String::from(
+        "<div class=\"related\" role=\"navigation\" aria-label=\"Related\">\n<h3>Navigation</h3>\n<ul>\n",
+    );
+    for (index, RelLink(page, link_title, key, text)) in rellinks.iter().enumerate() {
+        html.push_str(&format!(
+            "<li class=\"right\"><a href=\"{}\" title=\"{}\" accesskey=\"{}\">{}</a>{}</li>\n",
+            attribute(&pathto(pagename, page, false)),
+            attribute(&strip_tags(link_title)),
+            attribute(key),
+            html_escape::encode_text(text),
+            if index == 0 { "" } else { " |" }
+        ));
+    }
+    html.push_str(&format!(
+        "<li class=\"nav-item nav-item-0\"><a href=\"{}\">{}</a> &#187;</li>\n",
+        attribute(&pathto(pagename, root.0, false)),
+        html_escape::encode_text(root.1)
+    ));
+    for (index, parent) in parents.iter().enumerate() {
+        html.push_str(&format!(
+            "<li class=\"nav-item nav-item-{}\"><a href=\"{}\"{}>{}</a> &#187;</li>\n",
+            index + 1,
+            attribute(&parent.link),
+            if index + 1 == parents.len() { " accesskey=\"U\"" } else { "" },
+            parent.title
+        ));
+    }
+    html.push_str(&format!(
+        "<li class=\"nav-item nav-item-this\"><a href=\"{}\">{}</a></li>\n</ul>\n</div>\n",
+        attribute(&pathto(pagename, pagename, false)),
+        title
+    ));
+    html
+}
+
+/// Build a template function returning fixed HTML, such as `relbar()`
+pub fn html_function(html: String) -> Value {
+    Value::from_function(move || Value::from_safe_string(html.clone()))
+}
+
+/// HTML kept in a file instead of in memory, like the body of a page
+/// spooled as it was rendered. Templates copy it to their output unescaped,
+/// a chunk at a time.
+#[derive(Debug)]
+pub struct SpooledHtml {
+    file: std::fs::File,
+}
+
+impl SpooledHtml {
+    pub fn new(file: std::fs::File) -> Self {
+        Self { file }
+    }
+
+    /// Copy the HTML to `out`
+    pub fn write_to(&self, out: &mut dyn fmt::Write) -> io::Result<()> {
+        let mut file = &self.file;
+        file.seek(io::SeekFrom::Start(0))?;
+        let mut buffer = vec![0; 64 * 1024];
+        // Bytes of a character cut off at the end of the previous chunk
+        let mut pending = 0;
+        loop {
+            let read = file.read(&mut buffer[pending..])?;
+            if read == 0 {
+                break;
+            }
+            let filled = pending + read;
+            let valid = match std::str::from_utf8(&buffer[..filled]) {
+                Ok(text) => text.len(),
+                Err(error) if error.error_len().is_none() => error.valid_up_to(),
+                Err(error) => return Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+            };
+            let text = std::str::from_utf8(&buffer[..valid]).expect("checked to be UTF-8");
+            out.write_str(text).map_err(io::Error::other)?;
+            buffer.copy_within(valid..filled, 0);
+            pending = filled - valid;
+        }
+        if pending > 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "HTML ends in a partial character"));
+        }
+        Ok(())
+    }
+
+    /// The whole HTML, for pages that are rewritten after rendering
+    pub fn read_to_string(&self) -> io::Result<String> {
+        let mut file = &self.file;
+        file.seek(io::SeekFrom::Start(0))?;
+        let mut html = String::new();
+        file.read_to_string(&mut html)?;
+        Ok(html)
+    }
+}
+
+impl Object for SpooledHtml {
+    fn render(self: &Arc<Self>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_to(f).map_err(|_| fmt::Error)
+    }
+}
+
+pub(crate) fn strip_tags(html: &str) -> String {
+    TAG_RE.replace_all(html, "").into_owned()
+}
+
+lazy_static::lazy_static! {
+    static ref TAG_RE: regex::Regex = regex::Regex::new(r"<[^>]*>").unwrap();
+}
+
+/// Template arguments may be booleans or strings like "false" (theme options)
+fn value_to_bool(value: &Value) -> bool {
+    match value.as_str() {
+        Some(s) => matches!(s.trim().to_lowercase().as_str(), "true" | "1" | "yes"),
+        None => value.is_true(),
+    }
+}
+
+/// A template that failed to compile or render, located in the template
+/// that raised it, which may be one the rendered template extends or
+/// includes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    pub name: String,
+    pub line: Option<usize>,
+    pub detail: String,
+    /// Source of the failing expression or tag, when known
+    pub expression: Option<String>,
+}
+
+impl TemplateError {
+    /// Locate a minijinja error, looking up the failing template's source in
+    /// `env` unless it is given
+    fn new(template_name: &str, error: &MinijinjaError, env: &Environment<'_>, source: Option<&str>) -> Self {
+        let name = error.name().unwrap_or(template_name).to_string();
+        let detail = match error.detail() {
+            Some(detail) => format!("{}: {}", error.kind(), detail),
+            None => error.kind().to_string(),
+        };
+        let template = env.get_template(&name).ok();
+        let source = source.or_else(|| template.as_ref().map(|template| template.source()));
+        let expression = source.and_then(|source| {
+            let expression = match error.range() {
+                Some(range) => source.get(range)?,
+                None => source.lines().nth(error.line()?.checked_sub(1)?)?,
+            };
+            let expression = expression.trim();
+            (!expression.is_empty()).then(|| expression.to_string())
+        });
+        Self {
+            name,
+            line: error.line(),
+            detail,
+            expression,
+        }
+    }
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        write!(f, ": {}", self.detail)?;
+        if let Some(ref expression) = self.expression {
+            write!(f, " (in `{}`)", expression)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
 /// Template engine for rendering HTML pages (similar to Jinja2 in Sphinx)
 #[derive(Debug)]
 pub struct TemplateEngine {
     env: Environment<'static>,
     template_dirs: Vec<PathBuf>,
+    /// Files the templates registered from disk were read from, by name
+    template_files: HashMap<String, PathBuf>,
     global_context: HashMap<String, Value>,
 }
 
@@ -58,11 +404,20 @@ impl TemplateEngine {
         // Set up global functions and filters
         Self::setup_template_functions(&mut env);
 
+        // Spooled page bodies are copied to the output as they are
+        env.set_formatter(|out, state, value| match value.downcast_object_ref::<SpooledHtml>() {
+            Some(html) => html
+                .write_to(out)
+                .map_err(|e| MinijinjaError::new(ErrorKind::WriteFailure, e.to_string())),
+            None => minijinja::escape_formatter(out, state, value),
+        });
+
         let global_context = HashMap::new();
 
         Ok(Self {
             env,
             template_dirs,
+            template_files: HashMap::new(),
             global_context,
         })
     }
@@ -127,100 +482,22 @@ impl TemplateEngine {
 
     /// Set up template functions and filters
     fn setup_template_functions(env: &mut Environment<'static>) {
-        // Add pathto function (similar to Sphinx's pathto)
-        env.add_function(
-            "pathto",
-            |args: &[Value]| -> Result<Value, MinijinjaError> {
-                let target = args
-                    .first()
-                    .ok_or_else(|| {
-                        MinijinjaError::new(
-                            ErrorKind::InvalidOperation,
-                            "pathto requires target argument",
-                        )
-                    })?
-                    .as_str()
-                    .ok_or_else(|| {
-                        MinijinjaError::new(ErrorKind::InvalidOperation, "target must be string")
-                    })?;
-
-                // Handle resource argument - can be boolean true or string "true"
-                let resource = args.get(1).map_or(false, |v| {
-                    // Check if it's a string "true" first
-                    if let Some(s) = v.as_str() {
-                        s == "true"
-                    } else {
-                        // Otherwise use is_true() which handles booleans
-                        v.is_true()
-                    }
-                });
-
-                // Simple relative path calculation
-                let path = if resource {
-                    format!("_static/{}", target)
-                } else if target.starts_with("http") {
-                    target.to_string()
-                } else {
-                    format!("{}.html", target)
-                };
-
-                // Return as safe string to prevent over-escaping of URL paths
-                Ok(Value::from_safe_string(path))
-            },
-        );
-
-        // Add css_tag function - returns safe HTML
-        env.add_function(
-            "css_tag",
-            |args: &[Value]| -> Result<Value, MinijinjaError> {
-                let css = args.first().ok_or_else(|| {
-                    MinijinjaError::new(
-                        ErrorKind::InvalidOperation,
-                        "css_tag requires css argument",
-                    )
-                })?;
-
-                let filename = if let Some(css_str) = css.as_str() {
-                    css_str
-                } else {
-                    return Ok(Value::from_safe_string(String::new()));
-                };
-
-                let tag = format!(
-                    r#"<link rel="stylesheet" href="{}" type="text/css" />"#,
-                    filename
-                );
-                // Use from_safe_string to prevent HTML escaping
-                Ok(Value::from_safe_string(tag))
-            },
-        );
+        // Fallback pathto function for templates rendered outside a page,
+        // relative to the output root; pages shadow it with `pathto_function`
+        env.add_global("pathto", pathto_function(""));
+        env.add_function("hasdoc", |_name: String| false);
 
-        // Add js_tag function - returns safe HTML
-        env.add_function(
-            "js_tag",
-            |args: &[Value]| -> Result<Value, MinijinjaError> {
-                let js = args.first().ok_or_else(|| {
-                    MinijinjaError::new(ErrorKind::InvalidOperation, "js_tag requires js argument")
-                })?;
-
-                let filename = if let Some(js_str) = js.as_str() {
-                    js_str
-                } else {
-                    return Ok(Value::from_safe_string(String::new()));
-                };
+        // Asset tag functions without extra attributes; pages shadow them
+        // with `asset_tag_function` to add integrity hashes
+        env.add_global("css_tag", asset_tag_function(Arc::default(), css_tag, ""));
+        env.add_global("js_tag", asset_tag_function(Arc::default(), js_tag, ""));
 
-                let tag = format!(r#"<script src="{}"></script>"#, filename);
-                // Use from_safe_string to prevent HTML escaping
-                Ok(Value::from_safe_string(tag))
-            },
-        );
-
-        // Add toctree function - returns safe HTML
+        // Fallback toctree function for templates rendered outside a page;
+        // pages shadow it with `toctree_function`
         env.add_function(
             "toctree",
-            |_args: &[Value]| -> Result<Value, MinijinjaError> {
-                // TODO: Implement actual toctree generation
-                Ok(Value::from_safe_string("<div class=\"toctree-wrapper compound\"></div>".to_string()))
+            |_kwargs: Kwargs| -> Result<Value, MinijinjaError> {
+                Ok(Value::from_safe_string(String::new()))
             },
         );
 
@@ -257,28 +534,129 @@ impl TemplateEngine {
         });
     }
 
+    /// Whether a template with this name is registered
+    pub fn has_template(&self, template_name: &str) -> bool {
+        self.env.get_template(template_name).is_ok()
+    }
+
+    /// Register the `.html` templates under `dir` by their paths relative to
+    /// it, replacing templates of the same name, like Sphinx's
+    /// `templates_path`. A replaced template stays available as `!name`, so
+    /// an override can extend the original with `{% extends "!layout.html" %}`.
+    /// Templates that fail to compile are skipped with a warning. Returns the
+    /// number of templates registered.
+    pub fn add_override_templates(&mut self, dir: &Path) -> Result<usize> {
+        let mut count = 0;
+        let entries = walkdir::WalkDir::new(dir).sort_by_file_name().into_iter();
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if !path.is_file() || path.extension().is_none_or(|ext| ext != "html") {
+                continue;
+            }
+            let name = path
+                .strip_prefix(dir)?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let original_name = format!("!{}", name);
+            if !self.has_template(&original_name) {
+                if let Ok(original) = self.env.get_template(&name) {
+                    let original = original.source().to_string();
+                    self.env
+                        .add_template_owned(original_name, original)
+                        .map_err(|e| anyhow::anyhow!("Invalid template '{}': {}", name, e))?;
+                }
+            }
+            // Templates minijinja can't compile are skipped, so a project's
+            // unused Sphinx templates don't stop the build
+            match self.add_template_file(&name, path) {
+                Ok(()) => count += 1,
+                Err(err) => warn!("Skipping template {}: {:#}", path.display(), err),
+            }
+        }
+        Ok(count)
+    }
+
+    /// Register a template read from a file under the given name
+    pub fn add_template_file(&mut self, template_name: &str, path: &Path) -> Result<()> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read template: {}", path.display()))?;
+        if let Err(e) = self.env.add_template_owned(template_name.to_string(), source.clone()) {
+            return Err(TemplateError::new(template_name, &e, &self.env, Some(&source)).into());
+        }
+        self.template_files.insert(template_name.to_string(), path.to_path_buf());
+        Ok(())
+    }
+
+    /// File a template was read from, for templates registered from disk
+    pub fn template_file(&self, template_name: &str) -> Option<&Path> {
+        self.template_files.get(template_name).map(PathBuf::as_path)
+    }
+
     /// Render a template with the given context
     pub fn render(
         &self,
         template_name: &str,
         context: &serde_json::Map<String, serde_json::Value>,
     ) -> Result<String> {
+        self.render_with_values(template_name, context, HashMap::new())
+    }
+
+    /// Render a template with the given context plus values that have no JSON
+    /// form, such as per-page callables
+    pub fn render_with_values(
+        &self,
+        template_name: &str,
+        context: &serde_json::Map<String, serde_json::Value>,
+        values: HashMap<String, Value>,
+    ) -> Result<String> {
+        let template = self
+            .env
+            .get_template(template_name)
+            .map_err(|e| anyhow::anyhow!("Template '{}' not found: {}", template_name, e))?;
+
+        let rendered = template
+            .render(self.full_context(context, values))
+            .map_err(|e| TemplateError::new(template_name, &e, &self.env, None))?;
+
+        Ok(rendered)
+    }
+
+    /// Render a template straight into `out`, without holding the whole page
+    /// in memory. On error, `out` may have received part of the page.
+    pub fn render_to<W: std::io::Write>(
+        &self,
+        template_name: &str,
+        context: &serde_json::Map<String, serde_json::Value>,
+        values: HashMap<String, Value>,
+        out: W,
+    ) -> Result<()> {
         let template = self
             .env
             .get_template(template_name)
             .map_err(|e| anyhow::anyhow!("Template '{}' not found: {}", template_name, e))?;
 
-        // Convert context to minijinja Values
+        template
+            .render_captured_to(self.full_context(context, values), out)
+            .map_err(|e| TemplateError::new(template_name, &e, &self.env, None))?;
+
+        Ok(())
+    }
+
+    /// Global context overlaid with the JSON context, converted to minijinja
+    /// values, and the extra values
+    fn full_context(
+        &self,
+        context: &serde_json::Map<String, serde_json::Value>,
+        values: HashMap<String, Value>,
+    ) -> HashMap<String, Value> {
         let mut full_context = self.global_context.clone();
         for (key, value) in context {
             full_context.insert(key.clone(), Self::json_to_value(value));
         }
-
-        let rendered = template
-            .render(&full_context)
-            .map_err(|e| anyhow::anyhow!("Failed to render template '{}': {}", template_name, e))?;
-
-        Ok(rendered)
+        full_context.extend(values);
+        full_context
     }
 
     /// Convert serde_json::Value to minijinja::Value
@@ -307,8 +685,8 @@ impl TemplateEngine {
                         return Value::from_safe_string(s.clone());
                     }
                 }
-                // Convert to a simple map representation
-                let map: HashMap<String, Value> = obj
+                // Sorted, so iterating a map in a template is reproducible
+                let map: BTreeMap<String, Value> = obj
                     .iter()
                     .map(|(k, v)| (k.clone(), Self::json_to_value(v)))
                     .collect();
@@ -423,4 +801,269 @@ mod tests {
         );
         assert_eq!(context.get("count").and_then(|v| v.as_i64()), Some(42));
     }
+
+    #[test]
+    fn test_pathto_resource_keeps_output_folders() {
+        let mut engine = TemplateEngine::new(&BuildConfig::default()).unwrap();
+        engine
+            .env
+            .add_template_owned(
+                "links.html",
+                "{{ pathto('logo.png', resource=true) }}|{{ pathto('_sources/guide/intro.rst.txt', resource=true) }}",
+            )
+            .unwrap();
+
+        let html = engine.render("links.html", &TemplateContext::new().build()).unwrap();
+        assert_eq!(html, "_static/logo.png|_sources/guide/intro.rst.txt");
+    }
+
+    #[test]
+    fn test_page_helpers_are_relative_to_the_page() {
+        let mut engine = TemplateEngine::new(&BuildConfig::default()).unwrap();
+        engine
+            .env
+            .add_template_owned(
+                "links.html",
+                "{{ pathto('index') }}|{{ pathto('guide/intro#setup') }}|{{ pathto('logo.png', 1) }}|\
+                 {{ pathto('', resource=true) }}|{{ pathto('https://example.com') }}|\
+                 {{ hasdoc('guide/intro') }}/{{ hasdoc('genindex') }}/{{ hasdoc('missing') }}|{{ css_tag('_static/a.css') }}",
+            )
+            .unwrap();
+
+        let mut environment = BuildEnvironment::new(BuildConfig::default());
+        environment.add_document("guide/intro", 0.0);
+        let mut values = HashMap::new();
+        values.insert("pathto".to_string(), pathto_function("guide/intro"));
+        values.insert(
+            "hasdoc".to_string(),
+            hasdoc_function(Arc::new(environment), vec!["genindex".to_string()]),
+        );
+        values.insert(
+            "css_tag".to_string(),
+            asset_tag_function(Arc::default(), css_tag, "guide/intro"),
+        );
+        let html = engine
+            .render_with_values("links.html", &TemplateContext::new().build(), values)
+            .unwrap();
+        assert_eq!(
+            html,
+            "../index.html|intro.html#setup|../_static/logo.png|../|https://example.com|True/True/False|\
+             <link rel=\"stylesheet\" href=\"../_static/a.css\" type=\"text/css\" />"
+        );
+    }
+
+    #[test]
+    fn test_relbar_links_parents_and_rellinks() {
+        let rellinks = vec![
+            RelLink("genindex".into(), "General Index".into(), "I".into(), "index".into()),
+            RelLink("guide/next".into(), "<em>Next</em>".into(), "N".into(), "next".into()),
+        ];
+        let parents = vec![crate::navigation::NavLink::new("guide/index", "Guide", "index.html")];
+        let html = render_relbar("guide/intro", &rellinks, ("index", "Docs"), &parents, "Intro");
+        assert!(html.contains(
+            "<li class=\"right\"><a href=\"../genindex.html\" title=\"General Index\" accesskey=\"I\">index</a></li>"
+        ));
+        assert!(html.contains("<a href=\"next.html\" title=\"Next\" accesskey=\"N\">next</a> |</li>"));
+        assert!(!html.contains("style="), "got: {}", html);
+        assert!(html.contains("<li class=\"nav-item nav-item-0\"><a href=\"../index.html\">Docs</a> &#187;</li>"));
+        assert!(html.contains("<li class=\"nav-item nav-item-1\"><a href=\"index.html\" accesskey=\"U\">Guide</a> &#187;</li>"));
+        assert!(html.contains("<li class=\"nav-item nav-item-this\"><a href=\"intro.html\">Intro</a></li>"));
+    }
+
+    #[test]
+    fn test_override_templates_extend_the_originals() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("layout.html"),
+            "{% extends \"!layout.html\" %}{% block extrahead %}<meta name=\"x\" />{% endblock %}",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.path().join("pages")).unwrap();
+        std::fs::write(dir.path().join("pages/extra.html"), "{{ title }}").unwrap();
+        std::fs::write(dir.path().join("broken.html"), "{% if %}").unwrap();
+
+        let mut engine = TemplateEngine::new(&BuildConfig::default()).unwrap();
+        assert_eq!(engine.add_override_templates(dir.path()).unwrap(), 2);
+        assert!(engine.has_template("pages/extra.html") && !engine.has_template("broken.html"));
+        let mut ctx = TemplateContext::new();
+        ctx.insert("title", "Page").unwrap();
+        let html = engine.render("layout.html", &ctx.build()).unwrap();
+        assert!(html.contains("<meta name=\"x\" />\n</head>"), "got: {}", html);
+        assert!(html.contains("<title>Page &#8212;"));
+    }
+
+    #[test]
+    fn test_template_errors_are_located() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.html");
+        std::fs::write(&path, "<p>\n{% if %}\n</p>").unwrap();
+        let mut engine = TemplateEngine::new(&BuildConfig::default()).unwrap();
+        let err = engine.add_template_file("broken.html", &path).unwrap_err();
+        let err = err.downcast_ref::<TemplateError>().unwrap();
+        assert_eq!((err.name.as_str(), err.line), ("broken.html", Some(2)));
+        assert!(err.to_string().starts_with("broken.html:2: syntax error"), "got: {}", err);
+
+        std::fs::write(&path, "{{ 1 }}\n{{ title.missing.key }}").unwrap();
+        engine.add_template_file("broken.html", &path).unwrap();
+        assert_eq!(engine.template_file("broken.html"), Some(path.as_path()));
+        let err = engine.render("broken.html", &TemplateContext::new().build()).unwrap_err();
+        assert_eq!(err.to_string(), "broken.html:2: undefined value (in `title.missing`)");
+    }
+
+    #[test]
+    fn test_toctree_function_keyword_arguments() {
+        let mut engine = TemplateEngine::new(&BuildConfig::default()).unwrap();
+        engine
+            .env
+            .add_template_owned(
+                "nav.html",
+                "{{ toctree() }}|{{ toctree(maxdepth=1, collapse=false) }}|{{ toctree(maxdepth='-1', collapse='False') }}",
+            )
+            .unwrap();
+
+        let mut navigation = NavigationBuilder::new("index");
+        for (path, title) in [("index", "Home"), ("a", "A"), ("a1", "A1"), ("b", "B"), ("b1", "B1")] {
+            navigation.register_document(path, title);
+        }
+        navigation.register_toctree("index", vec!["a".to_string(), "b".to_string()]);
+        navigation.register_toctree("a", vec!["a1".to_string()]);
+        navigation.register_toctree("b", vec!["b1".to_string()]);
+
+        let defaults = ToctreeOptions {
+            current_doc: Some("a1".to_string()),
+            ..Default::default()
+        };
+        let mut values = HashMap::new();
+        values.insert(
+            "toctree".to_string(),
+            toctree_function(Arc::new(Mutex::new(navigation)), defaults),
+        );
+
+        let html = engine
+            .render_with_values("nav.html", &serde_json::Map::new(), values)
+            .unwrap();
+        let parts: Vec<&str> = html.split('|').collect();
+        // Collapsed by default: only the current branch is expanded
+        assert!(parts[0].contains("a1.html") && !parts[0].contains("b1.html"));
+        assert!(parts[0].contains("toctree-l1 current"));
+        // Depth limited to the top level
+        assert!(!parts[1].contains("a1.html") && parts[1].contains("b.html"));
+        // Unlimited and expanded
+        assert!(parts[2].contains("a1.html") && parts[2].contains("b1.html"));
+
+        // Unknown keyword arguments are rejected like in Jinja
+        engine
+            .env
+            .add_template_owned("bad.html", "{{ toctree(depth=2) }}")
+            .unwrap();
+        let mut values = HashMap::new();
+        values.insert(
+            "toctree".to_string(),
+            toctree_function(
+                Arc::new(Mutex::new(NavigationBuilder::new("index"))),
+                ToctreeOptions::default(),
+            ),
+        );
+        assert!(engine
+            .render_with_values("bad.html", &serde_json::Map::new(), values)
+            .is_err());
+    }
+
+    #[test]
+    fn test_render_to_writer_matches_render() {
+        let mut engine = TemplateEngine::new(&BuildConfig::default()).unwrap();
+        engine
+            .env
+            .add_template_owned("page.html", "<h1>{{ title }}</h1>{{ body|safe }}")
+            .unwrap();
+        let mut ctx = TemplateContext::new();
+        ctx.insert("title", "Intro").unwrap();
+        let ctx = ctx.build();
+        let values = || HashMap::from([("body".to_string(), Value::from("<p>Hi</p>"))]);
+
+        let mut streamed = Vec::new();
+        engine
+            .render_to("page.html", &ctx, values(), &mut streamed)
+            .unwrap();
+        let rendered = engine.render_with_values("page.html", &ctx, values()).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), rendered);
+        assert_eq!(rendered, "<h1>Intro</h1><p>Hi</p>");
+    }
+
+    #[test]
+    fn test_spooled_html_is_copied_unescaped() {
+        use std::io::Write;
+
+        let mut engine = TemplateEngine::new(&BuildConfig::default()).unwrap();
+        engine
+            .env
+            .add_template_owned("page.html", "{{ body }}|{{ body|safe }}")
+            .unwrap();
+        // Long enough for characters to straddle the chunks it is copied in
+        let html = format!("<p>{}</p>", "é &amp; ü".repeat(10_000));
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(html.as_bytes()).unwrap();
+        let body = Value::from_object(SpooledHtml::new(file));
+        let values = HashMap::from([("body".to_string(), body)]);
+
+        let mut streamed = Vec::new();
+        engine
+            .render_to("page.html", &serde_json::Map::new(), values, &mut streamed)
+            .unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), format!("{}|{}", html, html));
+    }
+
+    #[test]
+    fn test_context_maps_iterate_in_sorted_order() {
+        let mut engine = TemplateEngine::new(&BuildConfig::default()).unwrap();
+        engine
+            .env
+            .add_template_owned("meta.html", "{% for key in meta %}{{ key }}{% endfor %}")
+            .unwrap();
+        let mut ctx = TemplateContext::new();
+        let meta: serde_json::Map<String, serde_json::Value> = ["zeta", "alpha", "mu", "beta"]
+            .iter()
+            .map(|key| (key.to_string(), serde_json::Value::Bool(true)))
+            .collect();
+        ctx.insert("meta", meta).unwrap();
+        let html = engine
+            .render_with_values("meta.html", &ctx.build(), HashMap::new())
+            .unwrap();
+        assert_eq!(html, "alphabetamuzeta");
+    }
+
+    #[test]
+    fn test_asset_tags_carry_attributes() {
+        let mut engine = TemplateEngine::new(&BuildConfig::default()).unwrap();
+        engine
+            .env
+            .add_template_owned("assets.html", "{{ css_tag('_static/a.css') }}|{{ js_tag('_static/b.js') }}")
+            .unwrap();
+
+        let html = engine.render("assets.html", &TemplateContext::new().build()).unwrap();
+        assert_eq!(
+            html,
+            "<link rel=\"stylesheet\" href=\"_static/a.css\" type=\"text/css\" />|<script src=\"_static/b.js\"></script>"
+        );
+
+        let integrity = vec![
+            ("integrity".to_string(), "sha384-abc".to_string()),
+            ("crossorigin".to_string(), "anonymous".to_string()),
+        ];
+        let attributes = Arc::new(AssetAttributes::from([("_static/b.js".to_string(), integrity)]));
+        let mut values = HashMap::new();
+        values.insert("css_tag".to_string(), asset_tag_function(Arc::clone(&attributes), css_tag, ""));
+        values.insert("js_tag".to_string(), asset_tag_function(attributes, js_tag, ""));
+        let html = engine
+            .render_with_values("assets.html", &serde_json::Map::new(), values)
+            .unwrap();
+        assert_eq!(
+            html,
+            "<link rel=\"stylesheet\" href=\"_static/a.css\" type=\"text/css\" />|<script src=\"_static/b.js\" integrity=\"sha384-abc\" crossorigin=\"anonymous\"></script>"
+        );
+        assert_eq!(
+            crate::utils::subresource_integrity(b"alert('Hello, world.');"),
+            "sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO"
+        );
+    }
 }
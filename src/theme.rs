@@ -6,7 +6,7 @@
 use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 
 /// A theme stylesheet entry
@@ -138,7 +138,7 @@ impl Theme {
             ));
         }
 
-        let content = std::fs::read_to_string(&theme_toml_path)
+        let content = std::fs::read_to_string(theme_toml_path)
             .with_context(|| format!("Failed to read {}", theme_toml_path.display()))?;
 
         let toml: ThemeToml = toml::from_str(&content)
@@ -704,6 +704,137 @@ sys.exit(1)
     }
 }
 
+/// Directory of a project's own themes, under its source directory
+pub const PROJECT_THEMES_DIR: &str = "_themes";
+
+/// Install a theme into `themes_dir` from a `.tar.gz`, `.tgz` or `.tar`
+/// archive, a theme directory or a git URL. The theme may be at the top of
+/// the source or in its only directory; it is installed under its name.
+/// An installed theme of the same name is only replaced with `force`.
+pub fn install_theme(source: &str, themes_dir: &Path, force: bool) -> Result<Theme> {
+    let staging = tempfile::tempdir().context("Failed to create a temporary directory")?;
+    let path = Path::new(source);
+    let root = if path.is_dir() {
+        path.to_path_buf()
+    } else if path.is_file() {
+        unpack_archive(path, staging.path())?;
+        staging.path().to_path_buf()
+    } else if is_git_url(source) {
+        let checkout = staging.path().join("checkout");
+        let output = Command::new("git")
+            .args(["clone", "--depth", "1", source])
+            .arg(&checkout)
+            .output()
+            .context("Failed to run git")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git clone {} failed: {}",
+                source,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        checkout
+    } else {
+        return Err(anyhow!("Theme source '{}' is not an archive, a directory or a git URL", source));
+    };
+
+    let theme_dir = find_theme_dir(&root)
+        .ok_or_else(|| anyhow!("No theme.toml or theme.conf found in '{}'", source))?;
+    let theme = Theme::from_path(&theme_dir)?;
+    // The name comes from the theme being installed, so it may not lead
+    // out of `themes_dir`
+    let mut components = Path::new(&theme.name).components();
+    let single_name = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None));
+    if !single_name || theme.name.contains(['/', '\\', ':']) {
+        return Err(anyhow!("Theme name '{}' is not a valid directory name", theme.name));
+    }
+    let destination = themes_dir.join(&theme.name);
+    if destination.exists() {
+        if !force {
+            return Err(anyhow!(
+                "Theme '{}' is already installed in {}; use --force to replace it",
+                theme.name,
+                destination.display()
+            ));
+        }
+        std::fs::remove_dir_all(&destination)
+            .with_context(|| format!("Failed to remove {}", destination.display()))?;
+    }
+    let git_dir = crate::paths::canonicalize(&theme_dir.join(".git")).ok();
+    crate::utils::copy_files(crate::utils::plan_dir_copy(&theme_dir, &destination, git_dir.as_ref())?)?;
+    Theme::from_path(&destination)
+}
+
+/// Pack the theme in `theme_dir` into a `.tar.gz` archive that
+/// [`install_theme`] accepts, holding the theme's directory under its name.
+/// Without `output`, the archive is `<name>-<version>.tar.gz` in the
+/// current directory. Returns the path of the archive.
+pub fn pack_theme(theme_dir: &Path, output: Option<&Path>) -> Result<PathBuf> {
+    let theme = Theme::from_path(theme_dir)?;
+    let output = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(format!("{}-{}.tar.gz", theme.name, theme.version)));
+    let file = std::fs::File::create(&output)
+        .with_context(|| format!("Failed to create {}", output.display()))?;
+    let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()));
+    // Entries in a fixed order, leaving out version control metadata
+    let entries = walkdir::WalkDir::new(theme_dir)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git");
+    for entry in entries {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(theme_dir)?;
+        archive
+            .append_path_with_name(entry.path(), Path::new(&theme.name).join(relative))
+            .with_context(|| format!("Failed to add {} to the archive", entry.path().display()))?;
+    }
+    archive.into_inner()?.finish()?;
+    Ok(output)
+}
+
+fn unpack_archive(archive: &Path, destination: &Path) -> Result<()> {
+    let file = std::fs::File::open(archive)
+        .with_context(|| format!("Failed to open {}", archive.display()))?;
+    let name = archive.to_string_lossy().to_lowercase();
+    let result = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(destination)
+    } else if name.ends_with(".tar") {
+        tar::Archive::new(file).unpack(destination)
+    } else {
+        return Err(anyhow!("Unsupported theme archive {}; expected .tar.gz, .tgz or .tar", archive.display()));
+    };
+    result.with_context(|| format!("Failed to unpack {}", archive.display()))
+}
+
+fn is_git_url(source: &str) -> bool {
+    source.ends_with(".git")
+        || source.starts_with("git@")
+        || source.starts_with("git://")
+        || source.starts_with("https://")
+        || source.starts_with("http://")
+        || source.starts_with("ssh://")
+}
+
+/// `dir` if it holds a theme, else its only subdirectory holding one
+fn find_theme_dir(dir: &Path) -> Option<PathBuf> {
+    let is_theme = |dir: &Path| dir.join("theme.toml").is_file() || dir.join("theme.conf").is_file();
+    if is_theme(dir) {
+        return Some(dir.to_path_buf());
+    }
+    let themes: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && is_theme(path))
+        .collect();
+    match themes.as_slice() {
+        [theme] => Some(theme.clone()),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -908,4 +1039,44 @@ stylesheet = base.css, theme.css, extra.css
         assert_eq!(theme.stylesheets[1].path, "theme.css");
         assert_eq!(theme.stylesheets[2].path, "extra.css");
     }
+
+    #[test]
+    fn test_pack_and_install_theme() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_theme(temp_dir.path(), "packed", None).unwrap();
+        let theme_dir = temp_dir.path().join("packed");
+        std::fs::write(theme_dir.join("templates/layout.html"), "{{ body }}").unwrap();
+        std::fs::create_dir_all(theme_dir.join(".git")).unwrap();
+        std::fs::write(theme_dir.join(".git/HEAD"), "ref").unwrap();
+
+        let archive = temp_dir.path().join("packed.tar.gz");
+        assert_eq!(pack_theme(&theme_dir, Some(&archive)).unwrap(), archive);
+
+        let themes_dir = temp_dir.path().join("project/_themes");
+        let theme = install_theme(archive.to_str().unwrap(), &themes_dir, false).unwrap();
+        assert_eq!(theme.name, "packed");
+        assert_eq!(theme.path, themes_dir.join("packed"));
+        assert!(themes_dir.join("packed/templates/layout.html").is_file());
+        assert!(!themes_dir.join("packed/.git").exists());
+
+        // Installing again needs --force; a directory works as a source too
+        assert!(install_theme(archive.to_str().unwrap(), &themes_dir, false).is_err());
+        install_theme(theme_dir.to_str().unwrap(), &themes_dir, true).unwrap();
+        assert!(install_theme("missing.zip", &themes_dir, true).is_err());
+
+        // A name that leads out of the themes directory is refused before
+        // anything there is replaced
+        std::fs::create_dir_all(temp_dir.path().join("project/keep")).unwrap();
+        for name in ["../keep", "..", "a/b", r"..\keep"] {
+            let toml = std::fs::read_to_string(theme_dir.join("theme.toml")).unwrap();
+            let toml = toml.replacen(r#"name = "packed""#, &format!("name = {:?}", name), 1);
+            let evil = temp_dir.path().join("evil");
+            std::fs::create_dir_all(&evil).unwrap();
+            std::fs::write(evil.join("theme.toml"), toml).unwrap();
+            let error = install_theme(evil.to_str().unwrap(), &themes_dir, true).unwrap_err();
+            assert!(error.to_string().contains("not a valid directory name"), "{}: {}", name, error);
+        }
+        assert!(temp_dir.path().join("project/keep").is_dir());
+        assert!(themes_dir.join("packed").is_dir());
+    }
 }
@@ -0,0 +1,182 @@
+//! Opt-in trace of a build, for diagnosing slow builds.
+//!
+//! The build phases and the parse, render and write steps of each document
+//! are recorded as spans with the thread they ran on. The trace is written in
+//! the Chrome trace event format, which Perfetto, `chrome://tracing` and
+//! speedscope open, or as folded stacks for flamegraph tools.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use parking_lot::Mutex;
+use std::time::Instant;
+
+/// Category of the spans of build phases
+pub const PHASE: &str = "phase";
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A finished span
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceEvent {
+    pub name: String,
+    #[serde(rename = "cat")]
+    pub category: String,
+    /// Start in microseconds since the trace began
+    #[serde(rename = "ts")]
+    pub start: f64,
+    /// Duration in microseconds
+    #[serde(rename = "dur")]
+    pub duration: f64,
+    #[serde(rename = "tid")]
+    pub thread: u64,
+}
+
+/// Spans recorded during a build
+#[derive(Debug)]
+pub struct BuildTrace {
+    origin: Instant,
+    events: Mutex<Vec<TraceEvent>>,
+    thread_names: Mutex<BTreeMap<u64, String>>,
+}
+
+impl Default for BuildTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BuildTrace {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            events: Mutex::new(Vec::new()),
+            thread_names: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record a span from `start` to `end` on the current thread
+    pub fn record(&self, name: impl Into<String>, category: &str, start: Instant, end: Instant) {
+        let thread = THREAD_ID.with(|id| *id);
+        let event = TraceEvent {
+            name: name.into(),
+            category: category.to_string(),
+            start: micros(start.saturating_duration_since(self.origin)),
+            duration: micros(end.saturating_duration_since(start)),
+            thread,
+        };
+        self.events.lock().push(event);
+        self.thread_names.lock().entry(thread).or_insert_with(|| {
+            std::thread::current()
+                .name()
+                .map_or_else(|| format!("thread-{}", thread), str::to_string)
+        });
+    }
+
+    /// The recorded spans, in start order
+    pub fn events(&self) -> Vec<TraceEvent> {
+        let mut events = self.events.lock().clone();
+        events.sort_by(|a, b| a.start.total_cmp(&b.start).then_with(|| a.thread.cmp(&b.thread)));
+        events
+    }
+
+    /// The trace in the Chrome trace event format
+    pub fn to_chrome_trace(&self) -> serde_json::Value {
+        let names = self.thread_names.lock();
+        let metadata = names.iter().map(|(thread, name)| {
+            serde_json::json!({
+                "name": "thread_name",
+                "ph": "M",
+                "pid": 1,
+                "tid": thread,
+                "args": {"name": name},
+            })
+        });
+        let spans = self.events().into_iter().map(|event| {
+            let mut value = serde_json::to_value(event).unwrap();
+            value["ph"] = "X".into();
+            value["pid"] = 1.into();
+            value
+        });
+        serde_json::json!({
+            "traceEvents": metadata.chain(spans).collect::<Vec<_>>(),
+            "displayTimeUnit": "ms",
+        })
+    }
+
+    /// The trace as folded stacks (`build;category;name microseconds`), the
+    /// input format of flamegraph tools
+    pub fn to_folded(&self) -> String {
+        let mut stacks: BTreeMap<String, f64> = BTreeMap::new();
+        for event in self.events() {
+            let stack = format!("build;{};{}", event.category, event.name.replace(';', ":"));
+            *stacks.entry(stack).or_default() += event.duration;
+        }
+        stacks
+            .into_iter()
+            .map(|(stack, duration)| format!("{} {}\n", stack, duration.round() as u64))
+            .collect()
+    }
+
+    /// Write the trace to `path`: folded stacks for a `.folded` file, a
+    /// Chrome trace otherwise
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = if path.extension().is_some_and(|ext| ext == "folded") {
+            self.to_folded()
+        } else {
+            serde_json::to_string(&self.to_chrome_trace())?
+        };
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write build trace to {}", path.display()))
+    }
+}
+
+fn micros(duration: std::time::Duration) -> f64 {
+    duration.as_nanos() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_formats() {
+        let trace = BuildTrace::new();
+        let start = Instant::now();
+        trace.record("discovery", PHASE, start, Instant::now());
+        std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .name("worker".to_string())
+                .spawn_scoped(scope, || {
+                    trace.record("guide/intro", "render", start, Instant::now())
+                })
+                .unwrap();
+        });
+
+        let events = trace.events();
+        assert_eq!(events.len(), 2);
+        assert_ne!(events[0].thread, events[1].thread);
+
+        let chrome = trace.to_chrome_trace();
+        let trace_events = chrome["traceEvents"].as_array().unwrap();
+        assert!(trace_events
+            .iter()
+            .any(|event| event["ph"] == "M" && event["args"]["name"] == "worker"));
+        let span = trace_events.iter().find(|event| event["name"] == "guide/intro").unwrap();
+        assert_eq!(span["ph"], "X");
+        assert_eq!(span["cat"], "render");
+
+        let folded = trace.to_folded();
+        let stacks: Vec<&str> = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert_eq!(stacks, ["build;phase;discovery", "build;render;guide/intro"]);
+    }
+}
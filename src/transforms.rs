@@ -0,0 +1,478 @@
+//! Transforms of parsed documents.
+//!
+//! After a document is parsed and before anything reads its tree, the tree
+//! passes through the registered [`DocumentTransform`]s, lowest priority
+//! first like docutils transforms, and in registration order among equal
+//! priorities. Rewrites of the document, such as keeping or dropping the
+//! content of `.. only::` directives, are transforms rather than special
+//! cases of the renderer, and extensions register their own.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::config::BuildConfig;
+use crate::document::{Document, DocumentContent, RstNode};
+use crate::error::WarningType;
+use crate::numfig::NameEnumerables;
+use crate::parser::Parser;
+
+/// Priority of transforms that do not choose one
+pub const DEFAULT_PRIORITY: u32 = 500;
+
+/// Tags of the HTML builder, always set for `.. only::` expressions
+pub const BUILDER_TAGS: [&str; 3] = ["html", "format_html", "builder_html"];
+
+/// A problem a transform found in the document
+#[derive(Debug, Clone)]
+pub struct TransformWarning {
+    pub line: usize,
+    pub message: String,
+    pub warning_type: WarningType,
+}
+
+/// The document being transformed and what transforms may need for it
+pub struct TransformContext<'a> {
+    /// Document name without extension, like `guide/intro`
+    pub doc_path: &'a str,
+
+    pub source_path: &'a Path,
+
+    pub config: &'a BuildConfig,
+
+    /// Parser for RST the transforms insert, like the content of directives
+    pub parser: &'a Parser,
+
+    /// Tags set for `.. only::` expressions
+    pub tags: &'a BTreeSet<String>,
+
+    /// Title underline characters of the document, in order of first
+    /// appearance
+    title_styles: Vec<char>,
+
+    /// Files included by the RST the transforms parsed
+    includes: Vec<PathBuf>,
+
+    warnings: Vec<TransformWarning>,
+}
+
+impl<'a> TransformContext<'a> {
+    pub fn new(
+        doc_path: &'a str,
+        source_path: &'a Path,
+        config: &'a BuildConfig,
+        parser: &'a Parser,
+        tags: &'a BTreeSet<String>,
+    ) -> Self {
+        Self {
+            doc_path,
+            source_path,
+            config,
+            parser,
+            tags,
+            title_styles: Vec::new(),
+            includes: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Report a problem at a line of the document
+    pub fn warn(&mut self, line: usize, message: impl Into<String>, warning_type: WarningType) {
+        self.warnings.push(TransformWarning {
+            line,
+            message: message.into(),
+            warning_type,
+        });
+    }
+
+    pub fn into_warnings(self) -> Vec<TransformWarning> {
+        self.warnings
+    }
+
+    /// Parse RST found in the document, with the lines numbered from
+    /// `first_line` on and sections at the levels of the document's
+    pub fn parse_fragment(&mut self, content: &str, first_line: usize) -> Result<Vec<RstNode>> {
+        let (mut nodes, includes) =
+            self.parser
+                .parse_nested(self.source_path, content, &mut self.title_styles)?;
+        self.includes.extend(includes);
+        for node in &mut nodes {
+            *node.line_mut() += first_line.saturating_sub(1);
+        }
+        Ok(nodes)
+    }
+}
+
+/// Rewrites the tree of a parsed RST document
+pub trait DocumentTransform {
+    fn apply(&self, context: &mut TransformContext, nodes: &mut Vec<RstNode>) -> Result<()>;
+    fn get_name(&self) -> &str;
+
+    /// Transforms run from the lowest priority up
+    fn priority(&self) -> u32 {
+        DEFAULT_PRIORITY
+    }
+}
+
+/// Transforms applied to every document, in order of priority
+#[derive(Default)]
+pub struct TransformRegistry {
+    transforms: Vec<Box<dyn DocumentTransform + Send + Sync>>,
+    tags: BTreeSet<String>,
+}
+
+impl TransformRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with the built-in transforms and the configured tags
+    pub fn from_config(config: &BuildConfig) -> Self {
+        let mut registry = Self::new();
+        registry.tags = BUILDER_TAGS
+            .iter()
+            .map(|tag| tag.to_string())
+            .chain(config.tags.iter().cloned())
+            .collect();
+        registry.register(Box::new(OnlyFilter));
+        if config.numfig {
+            registry.register(Box::new(NameEnumerables));
+        }
+        registry
+    }
+
+    /// Add a transform after those of lower or equal priority
+    pub fn register(&mut self, transform: Box<dyn DocumentTransform + Send + Sync>) {
+        let position = self
+            .transforms
+            .partition_point(|other| other.priority() <= transform.priority());
+        self.transforms.insert(position, transform);
+    }
+
+    pub fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    /// Names of the transforms in the order they run
+    pub fn names(&self) -> Vec<&str> {
+        self.transforms
+            .iter()
+            .map(|transform| transform.get_name())
+            .collect()
+    }
+
+    /// Run every transform over an RST document, then extract its title
+    /// and table of contents again. Returns the problems they found.
+    pub fn apply(
+        &self,
+        document: &mut Document,
+        doc_path: &str,
+        config: &BuildConfig,
+        parser: &Parser,
+    ) -> Result<Vec<TransformWarning>> {
+        if self.transforms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let source_path = document.source_path.clone();
+        let DocumentContent::RestructuredText(rst) = &mut document.content else {
+            return Ok(Vec::new());
+        };
+        let mut context = TransformContext::new(doc_path, &source_path, config, parser, &self.tags);
+        context.title_styles = std::mem::take(&mut rst.title_styles);
+        for transform in &self.transforms {
+            transform
+                .apply(&mut context, &mut rst.ast)
+                .with_context(|| {
+                    format!(
+                        "Document transform '{}' failed on {}",
+                        transform.get_name(),
+                        doc_path
+                    )
+                })?;
+        }
+        rst.title_styles = std::mem::take(&mut context.title_styles);
+        document.includes.append(&mut context.includes);
+        document.includes.sort();
+        document.includes.dedup();
+        parser.refresh_outline(document);
+        Ok(context.into_warnings())
+    }
+}
+
+impl std::fmt::Debug for TransformRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// Replaces `.. only::` directives with their content when their
+/// expression holds for the tags, and drops them otherwise. Runs early, so
+/// the other transforms see the content that is kept.
+pub struct OnlyFilter;
+
+impl DocumentTransform for OnlyFilter {
+    fn apply(&self, context: &mut TransformContext, nodes: &mut Vec<RstNode>) -> Result<()> {
+        let mut index = 0;
+        while index < nodes.len() {
+            let RstNode::Directive {
+                name,
+                args,
+                options,
+                content,
+                line,
+            } = &nodes[index]
+            else {
+                index += 1;
+                continue;
+            };
+            if name != "only" {
+                index += 1;
+                continue;
+            }
+
+            let expression = args.join(" ");
+            let keep = evaluate_tags(&expression, context.tags).unwrap_or_else(|error| {
+                context.warn(
+                    *line,
+                    format!("invalid only expression '{}': {}", expression, error),
+                    WarningType::InvalidDirective,
+                );
+                true
+            });
+            let content_line = line + options.len() + 2;
+            let replacement = if keep {
+                context.parse_fragment(content, content_line)?
+            } else {
+                Vec::new()
+            };
+            // The content is looked at again, for nested only directives
+            nodes.splice(index..=index, replacement);
+        }
+        Ok(())
+    }
+
+    fn get_name(&self) -> &str {
+        "only"
+    }
+
+    fn priority(&self) -> u32 {
+        100
+    }
+}
+
+/// Whether an `.. only::` expression like `html and not (draft or latex)`
+/// holds when `tags` are set
+pub fn evaluate_tags(expression: &str, tags: &BTreeSet<String>) -> Result<bool, String> {
+    let spaced = expression.replace('(', " ( ").replace(')', " ) ");
+    let mut evaluator = TagExpression {
+        tokens: spaced.split_whitespace().collect(),
+        position: 0,
+        tags,
+    };
+    let value = evaluator.or_expression()?;
+    match evaluator.tokens.get(evaluator.position) {
+        None => Ok(value),
+        Some(token) => Err(format!("unexpected '{}'", token)),
+    }
+}
+
+/// Recursive descent over the tokens of a tag expression
+struct TagExpression<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+    tags: &'a BTreeSet<String>,
+}
+
+impl<'a> TagExpression<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.tokens.get(self.position).copied();
+        self.position += 1;
+        token
+    }
+
+    fn accept(&mut self, keyword: &str) -> bool {
+        let matches = self.tokens.get(self.position) == Some(&keyword);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn or_expression(&mut self) -> Result<bool, String> {
+        let mut value = self.and_expression()?;
+        while self.accept("or") {
+            value |= self.and_expression()?;
+        }
+        Ok(value)
+    }
+
+    fn and_expression(&mut self) -> Result<bool, String> {
+        let mut value = self.not_expression()?;
+        while self.accept("and") {
+            value &= self.not_expression()?;
+        }
+        Ok(value)
+    }
+
+    fn not_expression(&mut self) -> Result<bool, String> {
+        if self.accept("not") {
+            return Ok(!self.not_expression()?);
+        }
+        match self.next() {
+            Some("(") => {
+                let value = self.or_expression()?;
+                if self.accept(")") {
+                    Ok(value)
+                } else {
+                    Err("missing ')'".to_string())
+                }
+            }
+            Some(token @ (")" | "and" | "or")) => Err(format!("unexpected '{}'", token)),
+            Some(tag)
+                if tag
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.')) =>
+            {
+                Ok(self.tags.contains(tag))
+            }
+            Some(token) => Err(format!("'{}' is not a tag name", token)),
+            None => Err("expected a tag".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn tags(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_evaluate_tags() {
+        let set = tags(&["html", "draft"]);
+        assert_eq!(evaluate_tags("html", &set), Ok(true));
+        assert_eq!(evaluate_tags("latex", &set), Ok(false));
+        assert_eq!(evaluate_tags("html and not draft", &set), Ok(false));
+        assert_eq!(evaluate_tags("latex or html and draft", &set), Ok(true));
+        assert_eq!(evaluate_tags("not (latex or text)", &set), Ok(true));
+        assert_eq!(
+            evaluate_tags("(html and draft) and not(latex)", &set),
+            Ok(true)
+        );
+
+        assert!(evaluate_tags("", &set).is_err());
+        assert!(evaluate_tags("html and", &set).is_err());
+        assert!(evaluate_tags("(html", &set).is_err());
+        assert!(evaluate_tags("html latex", &set).is_err());
+        assert!(evaluate_tags("html == 1", &set).is_err());
+    }
+
+    /// Records the order transforms run in
+    struct Recorder {
+        name: &'static str,
+        priority: u32,
+        log: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl DocumentTransform for Recorder {
+        fn apply(&self, _context: &mut TransformContext, _nodes: &mut Vec<RstNode>) -> Result<()> {
+            self.log.lock().unwrap().push(self.name);
+            Ok(())
+        }
+
+        fn get_name(&self) -> &str {
+            self.name
+        }
+
+        fn priority(&self) -> u32 {
+            self.priority
+        }
+    }
+
+    #[test]
+    fn test_only_filter_and_priorities() {
+        let config = BuildConfig {
+            tags: vec!["draft".to_string()],
+            ..BuildConfig::default()
+        };
+        let parser = Parser::new(&config).unwrap();
+        let mut registry = TransformRegistry::from_config(&config);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        for (name, priority) in [
+            ("late", 900),
+            ("first-default", 500),
+            ("early", 50),
+            ("second-default", 500),
+        ] {
+            registry.register(Box::new(Recorder {
+                name,
+                priority,
+                log: Arc::clone(&log),
+            }));
+        }
+        assert_eq!(
+            registry.names(),
+            vec!["early", "only", "first-default", "second-default", "late"]
+        );
+
+        let source = "\
+Intro.
+
+.. only:: html and draft
+
+   Draft
+   =====
+
+   .. only:: latex
+
+      Print only.
+
+   Draft text.
+
+.. only:: latex
+
+   Print text.
+
+.. only:: html +
+
+   Broken expression.
+";
+        let mut document = parser.parse(Path::new("page.rst"), source).unwrap();
+        let warnings = registry
+            .apply(&mut document, "page", &config, &parser)
+            .unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["early", "first-default", "second-default", "late"]
+        );
+
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            panic!("not RST");
+        };
+        let nodes: Vec<(String, usize)> = rst
+            .ast
+            .iter()
+            .map(|node| match node {
+                RstNode::Title { text, line, .. } => (format!("title {}", text), *line),
+                RstNode::Paragraph { content, line } => (content.clone(), *line),
+                other => (format!("{:?}", other), 0),
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("Intro.".to_string(), 1),
+                ("title Draft".to_string(), 5),
+                ("Draft text.".to_string(), 12),
+                ("Broken expression.".to_string(), 20),
+            ]
+        );
+        assert_eq!(document.toc.len(), 1);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 18);
+        assert!(warnings[0].message.contains("'html +'"));
+    }
+}
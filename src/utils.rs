@@ -1,68 +1,171 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use std::path::Path;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Number of files listed as the largest in a project report
+pub const LARGEST_FILES: usize = 10;
+
+lazy_static::lazy_static! {
+    static ref DIRECTIVE_RE: Regex =
+        Regex::new(r"(?m)^[ \t]*(?:\.\.[ \t]+([A-Za-z][\w:+-]*)::|(?:```|:::)\{([A-Za-z][\w:+-]*)\})").unwrap();
+    static ref LABEL_RE: Regex = Regex::new(r"(?m)^[ \t]*\.\.[ \t]+_([^:`]+|`[^`]+`):").unwrap();
+    static ref DOC_ROLE_RE: Regex = Regex::new(r":(doc|ref):`([^`]+)`").unwrap();
+    static ref MARKDOWN_LINK_RE: Regex =
+        Regex::new(r"\]\(([^)#\s:]+\.(?:md|rst|txt))(?:#[^)]*)?\)").unwrap();
+}
 
-#[derive(Debug)]
+/// Overview of a documentation project: its size, structure and the
+/// markup it uses, for `sphinx-ultra stats` and dashboards
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ProjectStats {
     pub source_files: usize,
     pub total_lines: usize,
+    pub total_words: usize,
     pub avg_file_size_kb: f64,
     pub largest_file_kb: f64,
     pub max_depth: usize,
     pub cross_references: usize,
+    /// Documents and words per directory, `.` being the source directory
+    pub directories: BTreeMap<String, DirectoryStats>,
+    /// Number of headings per section level, 1 being the document titles
+    pub heading_depths: BTreeMap<usize, usize>,
+    /// The largest source files, largest first
+    pub largest_files: Vec<FileStats>,
+    /// Number of uses of each directive
+    pub directives: BTreeMap<String, usize>,
+    /// `:doc:` and `:ref:` targets and Markdown links that no document or
+    /// label of the project matches; an estimate, as extensions and
+    /// intersphinx can provide targets too
+    pub broken_links: Vec<BrokenLink>,
+}
+
+/// Documents and words of a directory, not counting its subdirectories
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DirectoryStats {
+    pub documents: usize,
+    pub words: usize,
+}
+
+/// Size of a source file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileStats {
+    /// Path from the source directory
+    pub path: String,
+    pub size_kb: f64,
+    pub lines: usize,
+    pub words: usize,
+}
+
+/// A link that seems to lead nowhere
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BrokenLink {
+    /// Source file, from the source directory
+    pub file: String,
+    pub line: usize,
+    /// `doc`, `ref` or `link`
+    pub kind: String,
+    pub target: String,
+}
+
+impl ProjectStats {
+    /// Machine-readable report
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
 }
 
 pub async fn analyze_project(source_dir: &Path) -> Result<ProjectStats> {
-    let mut state = AnalysisState {
-        source_files: 0,
-        total_lines: 0,
-        total_size_bytes: 0,
-        largest_file_kb: 0.0,
-        max_depth: 0,
-        cross_references: 0,
-    };
+    let mut state = AnalysisState::default();
 
     // Use synchronous approach to avoid async recursion issues
     analyze_directory_sync(source_dir, source_dir, 0, &mut state)?;
 
-    let avg_file_size_kb = if state.source_files > 0 {
-        (state.total_size_bytes as f64) / (state.source_files as f64) / 1024.0
-    } else {
-        0.0
+    let mut stats = ProjectStats {
+        source_files: state.files.len(),
+        max_depth: state.max_depth,
+        ..ProjectStats::default()
     };
+    let mut total_size_bytes = 0;
+    for file in &state.files {
+        total_size_bytes += file.size_bytes;
+        stats.total_lines += file.lines;
+        stats.total_words += file.words;
+        stats.cross_references += file.cross_references;
+        let directory = match file.path.rsplit_once('/') {
+            Some((directory, _)) => directory.to_string(),
+            None => ".".to_string(),
+        };
+        let directory = stats.directories.entry(directory).or_default();
+        directory.documents += 1;
+        directory.words += file.words;
+        for level in &file.heading_levels {
+            *stats.heading_depths.entry(*level).or_default() += 1;
+        }
+        for directive in &file.directives {
+            *stats.directives.entry(directive.clone()).or_default() += 1;
+        }
+    }
+    if !state.files.is_empty() {
+        stats.avg_file_size_kb = (total_size_bytes as f64) / (state.files.len() as f64) / 1024.0;
+    }
 
-    Ok(ProjectStats {
-        source_files: state.source_files,
-        total_lines: state.total_lines,
-        avg_file_size_kb,
-        largest_file_kb: state.largest_file_kb,
-        max_depth: state.max_depth,
-        cross_references: state.cross_references,
-    })
+    let mut largest: Vec<&FileAnalysis> = state.files.iter().collect();
+    largest.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes).then_with(|| a.path.cmp(&b.path)));
+    stats.largest_file_kb = largest.first().map_or(0.0, |file| file.size_bytes as f64 / 1024.0);
+    stats.largest_files = largest
+        .into_iter()
+        .take(LARGEST_FILES)
+        .map(|file| FileStats {
+            path: file.path.clone(),
+            size_kb: file.size_bytes as f64 / 1024.0,
+            lines: file.lines,
+            words: file.words,
+        })
+        .collect();
+
+    stats.broken_links = broken_links(&state.files);
+    Ok(stats)
+}
+
+/// What the analysis found in one source file
+#[derive(Debug, Default)]
+struct FileAnalysis {
+    /// Path from the source directory, with `/` separators
+    path: String,
+    size_bytes: u64,
+    lines: usize,
+    words: usize,
+    cross_references: usize,
+    heading_levels: Vec<usize>,
+    directives: Vec<String>,
+    labels: Vec<String>,
+    /// `(line, kind, target)` of the references to documents and labels
+    links: Vec<(usize, &'static str, String)>,
 }
 
 /// Analysis state for directory traversal
+#[derive(Default)]
 struct AnalysisState {
-    source_files: usize,
-    total_lines: usize,
-    total_size_bytes: u64,
-    largest_file_kb: f64,
+    files: Vec<FileAnalysis>,
     max_depth: usize,
-    cross_references: usize,
 }
 
 fn analyze_directory_sync(
     dir: &Path,
-    _root_dir: &Path,
+    root_dir: &Path,
     current_depth: usize,
     state: &mut AnalysisState,
 ) -> Result<()> {
     state.max_depth = state.max_depth.max(current_depth);
 
-    for entry in std::fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
         if path.is_dir() {
             // Skip hidden directories
             if let Some(name) = path.file_name() {
@@ -71,28 +174,175 @@ fn analyze_directory_sync(
                 }
             }
 
-            analyze_directory_sync(&path, _root_dir, current_depth + 1, state)?;
+            analyze_directory_sync(&path, root_dir, current_depth + 1, state)?;
         } else if is_source_file(&path) {
-            state.source_files += 1;
-
             let metadata = std::fs::metadata(&path)?;
-            let file_size_bytes = metadata.len();
-            let file_size_kb = file_size_bytes as f64 / 1024.0;
-
-            state.total_size_bytes += file_size_bytes;
-            state.largest_file_kb = state.largest_file_kb.max(file_size_kb);
-
-            // Count lines and cross-references
+            let relative = path.strip_prefix(root_dir).unwrap_or(&path);
+            let mut file = FileAnalysis {
+                path: crate::matching::normalize_path(relative),
+                size_bytes: metadata.len(),
+                ..FileAnalysis::default()
+            };
+
+            // Count lines, words, headings and references
             if let Ok(content) = std::fs::read_to_string(&path) {
-                state.total_lines += content.lines().count();
-                state.cross_references += count_cross_references(&content);
+                analyze_content(&content, is_markdown(&path), &mut file);
             }
+            state.files.push(file);
         }
     }
 
     Ok(())
 }
 
+fn is_markdown(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "md")
+}
+
+/// Whether a line is a section adornment: one punctuation character repeated
+fn is_adornment(line: &str) -> Option<char> {
+    let line = line.trim_end();
+    let first = line.chars().next()?;
+    (line.chars().count() >= 2 && first.is_ascii_punctuation() && line.chars().all(|c| c == first))
+        .then_some(first)
+}
+
+fn analyze_content(content: &str, markdown: bool, file: &mut FileAnalysis) {
+    let lines: Vec<&str> = content.lines().collect();
+    file.lines = lines.len();
+    file.cross_references = count_cross_references(content);
+
+    // Section levels follow the order adornment styles first appear in, as
+    // in the parser
+    let mut styles: Vec<(char, bool)> = Vec::new();
+    let mut in_fence = false;
+    // Lines of Markdown code fences, whose examples are not links
+    let mut fenced = HashSet::new();
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if markdown {
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                in_fence = !in_fence;
+                continue;
+            }
+            if in_fence {
+                fenced.insert(index);
+                continue;
+            }
+            let hashes = trimmed.chars().take_while(|c| *c == '#').count();
+            if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+                file.heading_levels.push(hashes);
+            }
+        } else if let (Some(style), false) = (is_adornment(line), line.starts_with(char::is_whitespace)) {
+            let previous = index.checked_sub(1).map(|i| lines[i].trim()).unwrap_or("");
+            let overlined = index >= 2 && is_adornment(lines[index - 2]) == Some(style);
+            let is_title = !previous.is_empty()
+                && is_adornment(previous).is_none()
+                && !lines[index - 1].starts_with(char::is_whitespace)
+                && trimmed.chars().count() >= previous.chars().count();
+            if is_title {
+                let key = (style, overlined);
+                let level = match styles.iter().position(|s| *s == key) {
+                    Some(position) => position + 1,
+                    None => {
+                        styles.push(key);
+                        styles.len()
+                    }
+                };
+                file.heading_levels.push(level);
+            }
+            continue;
+        }
+        if is_adornment(line).is_none() && !trimmed.starts_with("..") {
+            file.words += trimmed
+                .split_whitespace()
+                .filter(|word| word.chars().any(char::is_alphanumeric))
+                .count();
+        }
+    }
+
+    for caps in DIRECTIVE_RE.captures_iter(content) {
+        let name = caps.get(1).or_else(|| caps.get(2)).map_or("", |name| name.as_str());
+        file.directives.push(name.to_string());
+    }
+    for caps in LABEL_RE.captures_iter(content) {
+        file.labels.push(normalize_label(caps[1].trim_matches('`')));
+    }
+    for (number, line) in lines.iter().enumerate() {
+        if fenced.contains(&number) {
+            continue;
+        }
+        for caps in DOC_ROLE_RE.captures_iter(line) {
+            let text = &caps[2];
+            // `title <target>` links to the target
+            let target = match (text.rfind('<'), text.ends_with('>')) {
+                (Some(start), true) => &text[start + 1..text.len() - 1],
+                _ => text,
+            };
+            let kind = if &caps[1] == "doc" { "doc" } else { "ref" };
+            file.links.push((number + 1, kind, target.trim_start_matches('~').to_string()));
+        }
+        for caps in MARKDOWN_LINK_RE.captures_iter(line) {
+            file.links.push((number + 1, "link", caps[1].to_string()));
+        }
+    }
+}
+
+fn normalize_label(label: &str) -> String {
+    label.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// References to documents, labels and files that the project lacks
+fn broken_links(files: &[FileAnalysis]) -> Vec<BrokenLink> {
+    let paths: HashSet<&str> = files.iter().map(|file| file.path.as_str()).collect();
+    let docnames: HashSet<&str> = files
+        .iter()
+        .map(|file| file.path.rsplit_once('.').map_or(file.path.as_str(), |(stem, _)| stem))
+        .collect();
+    let labels: HashSet<&String> = files.iter().flat_map(|file| &file.labels).collect();
+
+    let mut broken = Vec::new();
+    for file in files {
+        let directory = file.path.rsplit_once('/').map_or("", |(directory, _)| directory);
+        let resolve = |target: &str| match target.strip_prefix('/') {
+            Some(absolute) => absolute.to_string(),
+            None if directory.is_empty() => target.to_string(),
+            None => format!("{}/{}", directory, target),
+        };
+        for (line, kind, target) in &file.links {
+            let found = match *kind {
+                "doc" => docnames.contains(normalize_docname(&resolve(target)).as_str()),
+                "ref" => labels.contains(&normalize_label(target)),
+                _ => paths.contains(normalize_docname(&resolve(target)).as_str()),
+            };
+            if !found {
+                broken.push(BrokenLink {
+                    file: file.path.clone(),
+                    line: *line,
+                    kind: kind.to_string(),
+                    target: target.clone(),
+                });
+            }
+        }
+    }
+    broken
+}
+
+/// Resolve `.` and `..` components of a `/`-separated path
+fn normalize_docname(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
 pub fn is_source_file(path: &Path) -> bool {
     if let Some(ext) = path.extension() {
         matches!(ext.to_string_lossy().as_ref(), "rst" | "md" | "txt")
@@ -128,6 +378,120 @@ pub fn get_file_mtime(path: &Path) -> Result<DateTime<Utc>> {
     Ok(DateTime::from(mtime))
 }
 
+/// Last commit time of every file under `dir` tracked by git, keyed by path
+/// (`dir` joined with the path relative to it). One `git log` call covers the
+/// whole tree; an empty map is returned when git or the repository is missing.
+pub fn git_last_commit_times(dir: &Path) -> HashMap<PathBuf, DateTime<Utc>> {
+    let mut times = HashMap::new();
+    let output = std::process::Command::new("git")
+        .args(["-c", "core.quotePath=false", "-C"])
+        .arg(dir)
+        .args(["log", "--format=%x00%ct", "--name-only", "--relative", "--", "."])
+        .output();
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        _ => return times,
+    };
+
+    let mut commit_time = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(timestamp) = line.strip_prefix('\0') {
+            commit_time = timestamp
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(secs, 0));
+        } else if let (Some(time), false) = (commit_time, line.is_empty()) {
+            // Newest commits come first, so keep the first time seen per file
+            times.entry(dir.join(line)).or_insert(time);
+        }
+    }
+    times
+}
+
+/// The time set by `SOURCE_DATE_EPOCH` (seconds since the Unix epoch), used
+/// instead of the current time for reproducible builds
+pub fn source_date_epoch() -> Option<DateTime<Utc>> {
+    parse_source_date_epoch(&std::env::var("SOURCE_DATE_EPOCH").ok()?)
+}
+
+fn parse_source_date_epoch(value: &str) -> Option<DateTime<Utc>> {
+    let seconds = value.trim().parse::<i64>().ok()?;
+    DateTime::from_timestamp(seconds, 0)
+}
+
+/// `time`, clamped to `SOURCE_DATE_EPOCH` when it is set, so timestamps in
+/// the output never depend on when the build ran
+pub fn reproducible_time(time: DateTime<Utc>) -> DateTime<Utc> {
+    match source_date_epoch() {
+        Some(epoch) => time.min(epoch),
+        None => time,
+    }
+}
+
+/// Format a timestamp with a strftime-style format, falling back to ISO dates
+/// when the format is invalid. An empty format means Sphinx's `%b %d, %Y`.
+pub fn format_timestamp(time: &DateTime<Utc>, fmt: &str) -> String {
+    use std::fmt::Write;
+
+    let fmt = if fmt.is_empty() { "%b %d, %Y" } else { fmt };
+    let mut formatted = String::new();
+    if write!(formatted, "{}", time.format(fmt)).is_err() {
+        return time.format("%Y-%m-%d").to_string();
+    }
+    formatted
+}
+
+/// Subresource integrity value of `content`: `sha384-` followed by the
+/// base64-encoded SHA-384 digest
+pub fn subresource_integrity(content: &[u8]) -> String {
+    use base64::prelude::*;
+    use sha2::{Digest, Sha384};
+
+    format!("sha384-{}", BASE64_STANDARD.encode(Sha384::digest(content)))
+}
+
+/// Decode `bytes` with the encoding named by `label` (like `latin-1` or
+/// `utf-8-sig`), or as UTF-8. A byte order mark is dropped.
+pub fn decode_text(bytes: &[u8], label: Option<&str>) -> Result<String> {
+    let label = label.map(str::trim).unwrap_or("utf-8");
+    // Python codec names, as used in conf.py and directive options
+    let normalized = label.to_ascii_lowercase().replace('_', "-");
+    let normalized = match normalized.trim_end_matches("-sig") {
+        "latin-1" => "latin1",
+        name => name,
+    };
+    let encoding = encoding_rs::Encoding::for_label(normalized.as_bytes())
+        .with_context(|| format!("Unknown encoding '{}'", label))?;
+    let (text, _, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        anyhow::bail!("Content is not valid {}", encoding.name());
+    }
+    Ok(text.into_owned())
+}
+
+/// Read a text file in the encoding named by `label`, or as UTF-8
+pub fn read_text_file(path: &Path, label: Option<&str>) -> Result<String> {
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    decode_text(&bytes, label).with_context(|| format!("Failed to decode {}", path.display()))
+}
+
+/// Download a text document, decoded as [`read_text_file`] does
+pub fn fetch_text(url: &str, label: Option<&str>) -> Result<String> {
+    use std::io::Read;
+
+    let response = ureq::get(url)
+        .timeout(std::time::Duration::from_secs(30))
+        .call()
+        .with_context(|| format!("Failed to fetch {}", url))?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("Failed to fetch {}", url))?;
+    decode_text(&bytes, label).with_context(|| format!("Failed to decode {}", url))
+}
+
 pub async fn calculate_directory_size(dir: &Path) -> Result<u64> {
     // Use synchronous approach
     calculate_directory_size_sync(dir)
@@ -161,17 +525,44 @@ pub async fn copy_dir_recursive_excluding(
     dst: &Path,
     exclude_dir: Option<&std::path::PathBuf>,
 ) -> Result<()> {
-    copy_dir_recursive_sync_excluding(src, dst, exclude_dir)
+    let copies = plan_dir_copy(src, dst, exclude_dir)?;
+    copy_files(copies)?;
+    Ok(())
+}
+
+/// A file to copy into the output
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileCopy {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+}
+
+/// Outcome of [`copy_files`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CopyStats {
+    pub copied: usize,
+    /// Files skipped because the destination already had the same content
+    pub unchanged: usize,
 }
 
-fn copy_dir_recursive_sync_excluding(
+/// The copies of every file under `src` to the same relative path under
+/// `dst`, leaving out `exclude_dir`
+pub fn plan_dir_copy(
     src: &Path,
     dst: &Path,
     exclude_dir: Option<&std::path::PathBuf>,
-) -> Result<()> {
-    std::fs::create_dir_all(dst)
-        .with_context(|| format!("Failed to create directory: {}", dst.display()))?;
+) -> Result<Vec<FileCopy>> {
+    let mut copies = Vec::new();
+    plan_dir_copy_into(src, dst, exclude_dir, &mut copies)?;
+    Ok(copies)
+}
 
+fn plan_dir_copy_into(
+    src: &Path,
+    dst: &Path,
+    exclude_dir: Option<&std::path::PathBuf>,
+    copies: &mut Vec<FileCopy>,
+) -> Result<()> {
     for entry in std::fs::read_dir(src)
         .with_context(|| format!("Failed to read directory: {}", src.display()))?
     {
@@ -182,7 +573,7 @@ fn copy_dir_recursive_sync_excluding(
 
         // Skip excluded directory
         if let Some(excluded) = exclude_dir {
-            if let Ok(canonical_src) = src_path.canonicalize() {
+            if let Ok(canonical_src) = crate::paths::canonicalize(&src_path) {
                 if &canonical_src == excluded || canonical_src.starts_with(excluded) {
                     log::debug!("Skipping excluded directory: {}", src_path.display());
                     continue;
@@ -191,25 +582,96 @@ fn copy_dir_recursive_sync_excluding(
         }
 
         if src_path.is_dir() {
-            copy_dir_recursive_sync_excluding(&src_path, &dst_path, exclude_dir)
-                .with_context(|| format!(
-                    "Failed to copy directory '{}' to '{}'",
-                    src_path.display(),
-                    dst_path.display()
-                ))?;
+            plan_dir_copy_into(&src_path, &dst_path, exclude_dir, copies)?;
         } else {
-            std::fs::copy(&src_path, &dst_path)
-                .with_context(|| format!(
-                    "Failed to copy file '{}' to '{}'",
-                    src_path.display(),
-                    dst_path.display()
-                ))?;
+            copies.push(FileCopy {
+                source: src_path,
+                destination: dst_path,
+            });
         }
     }
 
     Ok(())
 }
 
+/// Copy files in parallel on the current rayon pool. When several copies
+/// target the same destination the last one wins, and destinations that
+/// already have the size and content hash of their source are left alone,
+/// so unchanged output keeps its modification time.
+pub fn copy_files(copies: Vec<FileCopy>) -> Result<CopyStats> {
+    use rayon::prelude::*;
+
+    let mut last_copy: HashMap<PathBuf, usize> = HashMap::new();
+    for (index, copy) in copies.iter().enumerate() {
+        last_copy.insert(copy.destination.clone(), index);
+    }
+    let copies: Vec<FileCopy> = copies
+        .into_iter()
+        .enumerate()
+        .filter(|(index, copy)| last_copy[&copy.destination] == *index)
+        .map(|(_, copy)| copy)
+        .collect();
+
+    let directories: std::collections::BTreeSet<&Path> = copies
+        .iter()
+        .filter_map(|copy| copy.destination.parent())
+        .collect();
+    for directory in directories {
+        std::fs::create_dir_all(directory)
+            .with_context(|| format!("Failed to create directory: {}", directory.display()))?;
+    }
+
+    copies
+        .par_iter()
+        .map(|copy| {
+            if is_same_file_content(&copy.source, &copy.destination) {
+                return Ok(CopyStats { copied: 0, unchanged: 1 });
+            }
+            std::fs::copy(&copy.source, &copy.destination).with_context(|| {
+                format!(
+                    "Failed to copy file '{}' to '{}'",
+                    copy.source.display(),
+                    copy.destination.display()
+                )
+            })?;
+            Ok(CopyStats { copied: 1, unchanged: 0 })
+        })
+        .try_reduce(CopyStats::default, |a, b| {
+            Ok(CopyStats {
+                copied: a.copied + b.copied,
+                unchanged: a.unchanged + b.unchanged,
+            })
+        })
+}
+
+/// Write `contents` to `path` unless the file already holds them, so
+/// unchanged output keeps its modification time. Returns whether the file
+/// was written.
+pub fn write_if_changed(path: &Path, contents: impl AsRef<[u8]>) -> Result<bool> {
+    let contents = contents.as_ref();
+    let unchanged = std::fs::metadata(path).is_ok_and(|meta| meta.len() == contents.len() as u64)
+        && std::fs::read(path).is_ok_and(|existing| blake3::hash(&existing) == blake3::hash(contents));
+    if unchanged {
+        return Ok(false);
+    }
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(true)
+}
+
+/// Whether both files exist with the same size and content hash
+fn is_same_file_content(source: &Path, destination: &Path) -> bool {
+    let (Ok(source_meta), Ok(destination_meta)) =
+        (std::fs::metadata(source), std::fs::metadata(destination))
+    else {
+        return false;
+    };
+    if source_meta.len() != destination_meta.len() {
+        return false;
+    }
+    let hash = |path: &Path| std::fs::read(path).map(|content| blake3::hash(&content));
+    matches!((hash(source), hash(destination)), (Ok(a), Ok(b)) if a == b)
+}
+
 #[allow(dead_code)]
 pub fn format_duration(duration: std::time::Duration) -> String {
     let secs = duration.as_secs();
@@ -244,7 +706,7 @@ pub fn format_bytes(bytes: u64) -> String {
 /// Format a date according to the specified format string and language
 #[allow(dead_code)]
 pub fn format_date(fmt: &str, _language: &Option<String>) -> String {
-    let now = chrono::Utc::now();
+    let now = reproducible_time(chrono::Utc::now());
 
     match fmt {
         "%b %d, %Y" => now.format("%b %d, %Y").to_string(),
@@ -284,11 +746,11 @@ pub fn relative_uri(from: &str, to: &str, suffix: &str) -> String {
     if let Some(rel_path) =
         pathdiff::diff_paths(to_path, from_path.parent().unwrap_or(Path::new("")))
     {
-        let mut result = rel_path.to_string_lossy().to_string();
+        let mut result = crate::paths::to_slash(&rel_path);
         if !suffix.is_empty() && !result.ends_with(suffix) {
             result.push_str(suffix);
         }
-        result.replace('\\', "/") // Ensure forward slashes
+        result
     } else {
         format!("{}{}", to, suffix)
     }
@@ -320,3 +782,105 @@ pub async fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_files_skips_unchanged_and_keeps_last_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("theme");
+        let project = dir.path().join("project");
+        let output = dir.path().join("_static");
+        std::fs::create_dir_all(theme.join("css")).unwrap();
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(theme.join("css/theme.css"), "body {}").unwrap();
+        std::fs::write(theme.join("logo.svg"), "<svg/>").unwrap();
+        std::fs::write(project.join("logo.svg"), "<svg id=\"custom\"/>").unwrap();
+
+        let plan = || {
+            let mut copies = plan_dir_copy(&theme, &output, None).unwrap();
+            copies.extend(plan_dir_copy(&project, &output, None).unwrap());
+            copies
+        };
+        let stats = copy_files(plan()).unwrap();
+        assert_eq!(stats, CopyStats { copied: 2, unchanged: 0 });
+        assert_eq!(std::fs::read_to_string(output.join("css/theme.css")).unwrap(), "body {}");
+        assert_eq!(
+            std::fs::read_to_string(output.join("logo.svg")).unwrap(),
+            "<svg id=\"custom\"/>"
+        );
+
+        // Same size, different content is copied again
+        std::fs::write(theme.join("css/theme.css"), "body {x").unwrap();
+        let stats = copy_files(plan()).unwrap();
+        assert_eq!(stats, CopyStats { copied: 1, unchanged: 1 });
+        assert_eq!(std::fs::read_to_string(output.join("css/theme.css")).unwrap(), "body {x");
+    }
+
+    #[test]
+    fn test_decode_text_with_python_encoding_names() {
+        assert_eq!(decode_text(b"caf\xe9", Some("latin_1")).unwrap(), "café");
+        assert_eq!(decode_text(b"\xef\xbb\xbfcaf\xc3\xa9", Some("utf-8-sig")).unwrap(), "café");
+        assert_eq!(decode_text("café".as_bytes(), None).unwrap(), "café");
+        assert!(decode_text(b"caf\xe9", None).is_err());
+        assert!(decode_text(b"", Some("klingon")).is_err());
+    }
+
+    #[test]
+    fn test_source_date_epoch_parsing() {
+        let epoch = parse_source_date_epoch("1700000000\n").unwrap();
+        assert_eq!(epoch.to_rfc3339(), "2023-11-14T22:13:20+00:00");
+        assert!(parse_source_date_epoch("yesterday").is_none());
+        assert!(parse_source_date_epoch("").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_analyze_project_report() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("guide")).unwrap();
+        std::fs::write(
+            dir.path().join("index.rst"),
+            "=====\nTitle\n=====\n\n.. _intro:\n\nIntro\n-----\n\nSee :doc:`guide/install` and :doc:`missing`.\n\n\
+             .. note::\n\n   A note.\n\n.. note::\n\n   Another.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("guide/install.rst"),
+            "Install\n=======\n\nBack to :ref:`the intro <intro>`, not :ref:`nowhere`.\n\n.. code-block:: bash\n\n   pip install x\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("guide/usage.md"),
+            "# Usage\n\n## Steps\n\nRead [install](install.rst) and [faq](faq.md).\n\n```{note}\nMyST.\n```\n",
+        )
+        .unwrap();
+
+        let stats = analyze_project(dir.path()).await.unwrap();
+        assert_eq!(stats.source_files, 3);
+        assert_eq!(stats.directories["guide"], DirectoryStats { documents: 2, words: 17 });
+        assert_eq!(stats.directories["."].documents, 1);
+        assert_eq!(stats.directories["."].words, 9);
+        assert_eq!(stats.heading_depths, BTreeMap::from([(1, 3), (2, 2)]));
+        assert_eq!(
+            stats.directives,
+            BTreeMap::from([("code-block".to_string(), 1), ("note".to_string(), 3)])
+        );
+        assert_eq!(stats.largest_files[0].path, "index.rst");
+        let broken: Vec<(&str, &str)> = stats
+            .broken_links
+            .iter()
+            .map(|link| (link.file.as_str(), link.target.as_str()))
+            .collect();
+        assert_eq!(
+            broken,
+            vec![
+                ("guide/install.rst", "nowhere"),
+                ("guide/usage.md", "faq.md"),
+                ("index.rst", "missing")
+            ]
+        );
+        assert_eq!(stats.to_json()["directives"]["note"], 3);
+    }
+}
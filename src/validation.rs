@@ -4,8 +4,12 @@
 //! providing schema-based validation, custom constraint rules, and severity-based
 //! actions for validation failures.
 
+pub mod accessibility;
 pub mod constraint_engine;
 pub mod expression_evaluator;
+pub mod rules;
+pub mod spelling;
+pub mod style;
 
 use std::collections::HashMap;
 use std::fmt;
@@ -16,6 +20,7 @@ use crate::error::BuildError;
 
 pub use constraint_engine::ConstraintEngine;
 pub use expression_evaluator::ExpressionEvaluator;
+pub use rules::ContentRules;
 
 /// Represents the severity level of a validation failure
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -0,0 +1,312 @@
+//! Accessibility checks for reStructuredText sources
+//!
+//! Like the style lint, these checks scan the raw source so they also see
+//! images, tables and links nested inside other directives. Literal blocks
+//! and the content of code directives are left out, since markup there is
+//! shown as written.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref DIRECTIVE_REGEX: Regex =
+        Regex::new(r"^(\s*)\.\. (image|figure|list-table|csv-table)::\s*(.*)$").unwrap();
+    static ref OPTION_REGEX: Regex = Regex::new(r"^\s+:([a-zA-Z][a-zA-Z0-9_-]*):\s*(.*)$").unwrap();
+    static ref GRID_BORDER_REGEX: Regex = Regex::new(r"^\s*\+(-+\+)+\s*$").unwrap();
+    static ref GRID_HEADER_REGEX: Regex = Regex::new(r"^\s*\+(=+\+)+\s*$").unwrap();
+    static ref SIMPLE_BORDER_REGEX: Regex = Regex::new(r"^\s*=+( +=+)+\s*$").unwrap();
+    static ref LINK_TEXT_REGEX: Regex =
+        Regex::new(r"(?::(?:ref|doc):)?`([^`<]+?)\s*<[^>`]+>`(?:__?)?").unwrap();
+    static ref CODE_DIRECTIVE_REGEX: Regex = Regex::new(
+        r"^\s*\.\. (code-block|code|sourcecode|parsed-literal|doctest|testcode|testoutput|raw|math)::"
+    )
+    .unwrap();
+}
+
+/// Link texts that say nothing about where the link goes
+const AMBIGUOUS_LINK_TEXTS: &[&str] = &[
+    "here",
+    "click here",
+    "this",
+    "this link",
+    "link",
+    "more",
+    "read more",
+];
+
+/// Kind of accessibility problem found in a source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessibilityIssueKind {
+    /// Image or figure without `:alt:` text
+    MissingAltText,
+    /// Table without a header row
+    TableWithoutHeader,
+    /// Link text that does not describe its target
+    AmbiguousLinkText,
+}
+
+/// An accessibility problem in a source file
+#[derive(Debug, Clone)]
+pub struct AccessibilityIssue {
+    pub kind: AccessibilityIssueKind,
+    /// Line number (1-based)
+    pub line: usize,
+    pub message: String,
+}
+
+/// Lines of `source` with those of literal blocks and code directives
+/// blanked, keeping the line numbers
+fn prose_lines(source: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = source.lines().collect();
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim_end();
+        let opens_literal = CODE_DIRECTIVE_REGEX.is_match(line)
+            || (line.ends_with("::") && !line.trim_start().starts_with(".."));
+        let block_indent = indent(line);
+        i += 1;
+        if !opens_literal {
+            continue;
+        }
+        // The block is every following line indented deeper, and the blank
+        // lines between them
+        while i < lines.len() && (lines[i].trim().is_empty() || indent(lines[i]) > block_indent) {
+            lines[i] = "";
+            i += 1;
+        }
+    }
+    lines
+}
+
+/// Options of the directive starting at `start`, stopping at the first line
+/// that is not an option
+fn directive_options<'a>(lines: &[&'a str], start: usize) -> Vec<(&'a str, &'a str)> {
+    lines[start + 1..]
+        .iter()
+        .map_while(|line| OPTION_REGEX.captures(line))
+        .map(|caps| {
+            (
+                caps.get(1).unwrap().as_str(),
+                caps.get(2).unwrap().as_str(),
+            )
+        })
+        .collect()
+}
+
+fn check_directives(lines: &[&str], issues: &mut Vec<AccessibilityIssue>) {
+    for (i, line) in lines.iter().enumerate() {
+        let Some(caps) = DIRECTIVE_REGEX.captures(line) else {
+            continue;
+        };
+        let name = &caps[2];
+        let argument = caps[3].trim();
+        let options = directive_options(lines, i);
+        let has_option = |wanted: &str| options.iter().any(|(option, _)| *option == wanted);
+
+        match name {
+            "image" | "figure" => {
+                if !has_option("alt") {
+                    issues.push(AccessibilityIssue {
+                        kind: AccessibilityIssueKind::MissingAltText,
+                        line: i + 1,
+                        message: format!("{} '{}' has no :alt: text", name, argument),
+                    });
+                }
+            }
+            _ => {
+                let header_rows = options
+                    .iter()
+                    .find(|(option, _)| *option == "header-rows")
+                    .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                let has_header = header_rows > 0 || (name == "csv-table" && has_option("header"));
+                if !has_header {
+                    issues.push(AccessibilityIssue {
+                        kind: AccessibilityIssueKind::TableWithoutHeader,
+                        line: i + 1,
+                        message: format!("{} has no header row", name),
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn check_tables(lines: &[&str], issues: &mut Vec<AccessibilityIssue>) {
+    let mut i = 0;
+    while i < lines.len() {
+        if GRID_BORDER_REGEX.is_match(lines[i]) {
+            let start = i;
+            let mut has_header = false;
+            while i < lines.len() && !lines[i].trim().is_empty() {
+                has_header |= GRID_HEADER_REGEX.is_match(lines[i]);
+                i += 1;
+            }
+            if !has_header {
+                issues.push(AccessibilityIssue {
+                    kind: AccessibilityIssueKind::TableWithoutHeader,
+                    line: start + 1,
+                    message: "table has no header row".to_string(),
+                });
+            }
+            continue;
+        }
+
+        if SIMPLE_BORDER_REGEX.is_match(lines[i]) {
+            // A simple table ends at a border followed by a blank line
+            let start = i;
+            let mut borders = 1;
+            i += 1;
+            while i < lines.len() {
+                if SIMPLE_BORDER_REGEX.is_match(lines[i]) {
+                    borders += 1;
+                    if lines.get(i + 1).is_none_or(|next| next.trim().is_empty()) {
+                        break;
+                    }
+                }
+                i += 1;
+            }
+            if borders == 2 {
+                issues.push(AccessibilityIssue {
+                    kind: AccessibilityIssueKind::TableWithoutHeader,
+                    line: start + 1,
+                    message: "table has no header row".to_string(),
+                });
+            }
+        }
+
+        i += 1;
+    }
+}
+
+fn check_links(lines: &[&str], issues: &mut Vec<AccessibilityIssue>) {
+    for (i, line) in lines.iter().enumerate() {
+        for caps in LINK_TEXT_REGEX.captures_iter(line) {
+            let text = caps[1].trim();
+            if AMBIGUOUS_LINK_TEXTS.contains(&text.to_lowercase().as_str()) {
+                issues.push(AccessibilityIssue {
+                    kind: AccessibilityIssueKind::AmbiguousLinkText,
+                    line: i + 1,
+                    message: format!(
+                        "link text '{}' does not describe the link target",
+                        text
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Check an RST source for missing alt text, header-less tables and
+/// ambiguous link text outside literal and code blocks. Heading order is
+/// covered by the style lint.
+pub fn check_accessibility(source: &str) -> Vec<AccessibilityIssue> {
+    let lines = prose_lines(source);
+    let mut issues = Vec::new();
+
+    check_directives(&lines, &mut issues);
+    check_tables(&lines, &mut issues);
+    check_links(&lines, &mut issues);
+
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(source: &str) -> Vec<(AccessibilityIssueKind, usize)> {
+        check_accessibility(source)
+            .into_iter()
+            .map(|issue| (issue.kind, issue.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_alt_text() {
+        let source = ".. image:: a.png\n   :width: 10\n\n.. figure:: b.png\n   :alt: B\n\n   Caption\n\n.. note::\n\n   .. image:: c.png\n";
+        assert_eq!(
+            found(source),
+            vec![
+                (AccessibilityIssueKind::MissingAltText, 1),
+                (AccessibilityIssueKind::MissingAltText, 11),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tables_without_headers() {
+        let source = "\
++---+---+
+| a | b |
++===+===+
+| 1 | 2 |
++---+---+
+
++---+---+
+| 1 | 2 |
++---+---+
+
+===  ===
+ a    b
+===  ===
+ 1    2
+===  ===
+
+===  ===
+ 1    2
+===  ===
+
+.. list-table::
+   :header-rows: 1
+
+.. csv-table::
+   :widths: 1 1
+";
+        assert_eq!(
+            found(source),
+            vec![
+                (AccessibilityIssueKind::TableWithoutHeader, 7),
+                (AccessibilityIssueKind::TableWithoutHeader, 17),
+                (AccessibilityIssueKind::TableWithoutHeader, 24),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_link_text() {
+        let source = "See `here <https://example.com>`_ and :doc:`Click Here <other>`.\nRead the `install guide <install>`_.\n";
+        assert_eq!(
+            found(source),
+            vec![
+                (AccessibilityIssueKind::AmbiguousLinkText, 1),
+                (AccessibilityIssueKind::AmbiguousLinkText, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literal_and_code_blocks_are_skipped() {
+        let source = "\
+Example::
+
+    .. image:: a.png
+
+    +---+
+    | 1 |
+    +---+
+
+.. code-block:: rst
+   :caption: Links
+
+   See `here <https://example.com>`_.
+
+   .. figure:: b.png
+
+.. image:: c.png
+";
+        assert_eq!(found(source), vec![(AccessibilityIssueKind::MissingAltText, 16)]);
+    }
+}
@@ -68,6 +68,14 @@ impl ExpressionEvaluator {
             return Ok(!Self::evaluate_expression(inner_expr, context)?);
         }
 
+        // Handle substring checks
+        if expr.contains(" contains ") {
+            let parts: Vec<&str> = expr.splitn(2, " contains ").collect();
+            let left = Self::get_value(parts[0].trim(), context)?;
+            let right = Self::parse_literal(parts[1].trim())?;
+            return Ok(left.to_string().contains(&right.to_string()));
+        }
+
         // Handle comparisons
         if expr.contains(" == ") {
             let parts: Vec<&str> = expr.split(" == ").collect();
@@ -95,6 +103,34 @@ impl ExpressionEvaluator {
             return Ok(!Self::values_equal(&left, &right));
         }
 
+        // Handle ordering comparisons (longest operators first)
+        for op in [" <= ", " >= ", " < ", " > "] {
+            if expr.contains(op) {
+                let parts: Vec<&str> = expr.split(op).collect();
+                if parts.len() != 2 {
+                    return Err(BuildError::ValidationError(format!(
+                        "Invalid comparison: {}",
+                        expr
+                    )));
+                }
+                let left = Self::get_value(parts[0].trim(), context)?;
+                let right = Self::parse_literal(parts[1].trim())?;
+                let (Some(left), Some(right)) = (Self::as_number(&left), Self::as_number(&right))
+                else {
+                    return Err(BuildError::ValidationError(format!(
+                        "Non-numeric comparison: {}",
+                        expr
+                    )));
+                };
+                return Ok(match op.trim() {
+                    "<=" => left <= right,
+                    ">=" => left >= right,
+                    "<" => left < right,
+                    _ => left > right,
+                });
+            }
+        }
+
         // Handle 'in' operations
         if expr.contains(" in ") {
             let parts: Vec<&str> = expr.split(" in ").collect();
@@ -189,6 +225,15 @@ impl ExpressionEvaluator {
         }
     }
 
+    /// Numeric view of a value for ordering comparisons
+    fn as_number(value: &FieldValue) -> Option<f64> {
+        match value {
+            FieldValue::Integer(i) => Some(*i as f64),
+            FieldValue::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
     /// Check if a value is truthy
     fn is_truthy(value: &FieldValue) -> bool {
         match value {
@@ -290,6 +335,20 @@ mod tests {
         assert!(ExpressionEvaluator::evaluate("status in ['open', 'closed']", &item).unwrap());
     }
 
+    #[test]
+    fn test_contains_and_ordering() {
+        let mut item = create_test_item();
+        item.metadata
+            .insert("level".to_string(), FieldValue::Integer(3));
+
+        assert!(ExpressionEvaluator::evaluate("content contains 'content'", &item).unwrap());
+        assert!(ExpressionEvaluator::evaluate("not title contains 'Missing'", &item).unwrap());
+        assert!(ExpressionEvaluator::evaluate("level <= 3", &item).unwrap());
+        assert!(!ExpressionEvaluator::evaluate("level < 3", &item).unwrap());
+        assert!(ExpressionEvaluator::evaluate("level > 2 and level >= 3", &item).unwrap());
+        assert!(ExpressionEvaluator::evaluate("status < 3", &item).is_err());
+    }
+
     #[test]
     fn test_complex_expression() {
         let item = create_test_item();
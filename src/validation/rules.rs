@@ -0,0 +1,519 @@
+//! Project-specific content rules
+//!
+//! Rules declared in the build configuration are compiled into constraint
+//! definitions and evaluated by the [`ConstraintEngine`] against the directives,
+//! headings and paragraphs of every document. Banned directives and terms are
+//! matched directly, since user text can't be written safely into an
+//! expression.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::document::{Document, DocumentContent, RstNode};
+use crate::error::BuildError;
+use crate::validation::{
+    ConstraintActions, ConstraintDefinition, ConstraintEngine, ContentItem, FieldValue,
+    ItemLocation, ValidationConfig, ValidationContext, ValidationSeverity,
+};
+
+/// Rules a project can enforce on its sources
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContentRules {
+    /// Directives that must not be used
+    #[serde(default)]
+    pub banned_directives: Vec<String>,
+
+    /// Options that must be given for a directive, keyed by directive name
+    #[serde(default)]
+    pub required_options: HashMap<String, Vec<String>>,
+
+    /// Underline character expected for each heading level, outermost first
+    #[serde(default)]
+    pub heading_style: Vec<String>,
+
+    /// Deepest heading level allowed
+    #[serde(default)]
+    pub max_heading_depth: Option<usize>,
+
+    /// Terms that must not appear in prose (matched case-insensitively)
+    #[serde(default)]
+    pub banned_terms: Vec<String>,
+
+    /// Severity per rule ("banned-directives", "required-options", "heading-style",
+    /// "max-heading-depth", "banned-terms"); rules not listed report warnings
+    #[serde(default)]
+    pub severity: HashMap<String, ValidationSeverity>,
+
+    /// Additional constraints written as expressions
+    #[serde(default)]
+    pub custom: HashMap<String, CustomRule>,
+}
+
+/// A user-written constraint and the kind of item it applies to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomRule {
+    /// Item kind the rule is evaluated on ("directive", "heading" or "paragraph");
+    /// all kinds when unset
+    #[serde(default)]
+    pub applies_to: Option<String>,
+
+    #[serde(flatten)]
+    pub definition: ConstraintDefinition,
+}
+
+/// A rule violation found in a document
+#[derive(Debug, Clone)]
+pub struct RuleViolation {
+    pub line: usize,
+    pub message: String,
+    pub severity: ValidationSeverity,
+}
+
+impl ContentRules {
+    /// Whether no rules are configured
+    pub fn is_empty(&self) -> bool {
+        self.banned_directives.is_empty()
+            && self.required_options.is_empty()
+            && self.heading_style.is_empty()
+            && self.max_heading_depth.is_none()
+            && self.banned_terms.is_empty()
+            && self.custom.is_empty()
+    }
+
+    fn severity_of(&self, rule: &str) -> ValidationSeverity {
+        self.severity
+            .get(rule)
+            .copied()
+            .unwrap_or(ValidationSeverity::Warning)
+    }
+
+    fn constraint(
+        &self,
+        rule: &str,
+        check: String,
+        error_message: String,
+    ) -> ConstraintDefinition {
+        let mut checks = HashMap::new();
+        checks.insert("check_0".to_string(), check);
+        ConstraintDefinition {
+            checks,
+            severity: self.severity_of(rule),
+            error_message: Some(error_message),
+            description: None,
+        }
+    }
+
+    /// Compile the rules into constraint definitions for the engine.
+    ///
+    /// Only critical failures break the build from inside the engine; all other
+    /// severities are reported by the caller.
+    pub fn validation_config(&self) -> ValidationConfig {
+        let mut config = ValidationConfig::default();
+        for (severity, actions) in config.constraint_failed_options.iter_mut() {
+            if severity != "critical" {
+                *actions = ConstraintActions {
+                    on_fail: vec![],
+                    style_changes: vec![],
+                    force_style: false,
+                };
+            }
+        }
+
+        for (directive, options) in &self.required_options {
+            for option in options {
+                config.constraints.insert(
+                    format!("required-options:{}:{}", directive, option),
+                    self.constraint(
+                        "required-options",
+                        format!("has_option_{}", option),
+                        format!(
+                            "Directive '{}' is missing required option ':{}:'",
+                            directive, option
+                        ),
+                    ),
+                );
+            }
+        }
+
+        for (index, adornment) in self.heading_style.iter().enumerate() {
+            let level = index + 1;
+            config.constraints.insert(
+                format!("heading-style:{}", level),
+                self.constraint(
+                    "heading-style",
+                    format!("adornment == {}", quote(adornment)),
+                    format!(
+                        "Heading '{{{{ title }}}}' at level {} should be underlined with '{}'",
+                        level, adornment
+                    ),
+                ),
+            );
+        }
+
+        if let Some(max_depth) = self.max_heading_depth {
+            config.constraints.insert(
+                "max-heading-depth".to_string(),
+                self.constraint(
+                    "max-heading-depth",
+                    format!("level <= {}", max_depth),
+                    format!(
+                        "Heading '{{{{ title }}}}' is nested deeper than the allowed {} levels",
+                        max_depth
+                    ),
+                ),
+            );
+        }
+
+        for (name, rule) in &self.custom {
+            config
+                .constraints
+                .insert(format!("custom:{}", name), rule.definition.clone());
+        }
+
+        config
+    }
+
+    /// Names of the constraints that apply to an item of the given kind
+    fn constraints_for(&self, kind: &str, name: &str, level: usize) -> Vec<String> {
+        let mut constraints = Vec::new();
+
+        match kind {
+            "directive" => {
+                if let Some(options) = self.required_options.get(name) {
+                    for option in options {
+                        constraints.push(format!("required-options:{}:{}", name, option));
+                    }
+                }
+            }
+            "heading" => {
+                if (1..=self.heading_style.len()).contains(&level) {
+                    constraints.push(format!("heading-style:{}", level));
+                }
+                if self.max_heading_depth.is_some() {
+                    constraints.push("max-heading-depth".to_string());
+                }
+            }
+            _ => {}
+        }
+
+        let mut custom: Vec<&String> = self
+            .custom
+            .iter()
+            .filter(|(_, rule)| rule.applies_to.as_deref().is_none_or(|k| k == kind))
+            .map(|(name, _)| name)
+            .collect();
+        custom.sort();
+        constraints.extend(custom.into_iter().map(|name| format!("custom:{}", name)));
+
+        constraints
+    }
+
+    /// Turn a document into the items the constraints are evaluated on
+    pub fn content_items(&self, doc: &Document, docname: &str) -> Vec<ContentItem> {
+        let rst = match &doc.content {
+            DocumentContent::RestructuredText(rst) => rst,
+            _ => return Vec::new(),
+        };
+        let raw_lines: Vec<&str> = rst.raw.lines().collect();
+        let mut items = Vec::new();
+
+        for node in &rst.ast {
+            let (kind, line, title, mut metadata, text) = match node {
+                RstNode::Directive {
+                    name,
+                    args,
+                    options,
+                    line,
+                    ..
+                } => {
+                    let mut metadata = HashMap::new();
+                    metadata.insert("name".to_string(), FieldValue::String(name.clone()));
+                    metadata.insert(
+                        "argument".to_string(),
+                        FieldValue::String(args.join(" ")),
+                    );
+                    if let Some(required) = self.required_options.get(name) {
+                        for option in required {
+                            metadata.insert(
+                                format!("has_option_{}", option),
+                                FieldValue::Boolean(false),
+                            );
+                        }
+                    }
+                    for (option, value) in options {
+                        metadata.insert(
+                            format!("has_option_{}", option),
+                            FieldValue::Boolean(true),
+                        );
+                        metadata.insert(
+                            format!("option_{}", option),
+                            FieldValue::String(value.clone()),
+                        );
+                    }
+                    ("directive", *line, name.clone(), metadata, String::new())
+                }
+                RstNode::Title { text, level, line } => {
+                    // The underline follows the title text line
+                    let adornment = raw_lines
+                        .get(*line)
+                        .and_then(|l| l.trim().chars().next())
+                        .map(|c| c.to_string())
+                        .unwrap_or_default();
+                    let mut metadata = HashMap::new();
+                    metadata.insert("level".to_string(), FieldValue::Integer(*level as i64));
+                    metadata.insert("adornment".to_string(), FieldValue::String(adornment));
+                    ("heading", *line, text.clone(), metadata, text.clone())
+                }
+                RstNode::Paragraph { content, line } | RstNode::BlockQuote { content, line } => {
+                    ("paragraph", *line, String::new(), HashMap::new(), content.clone())
+                }
+                RstNode::List { items, line, .. } => (
+                    "paragraph",
+                    *line,
+                    String::new(),
+                    HashMap::new(),
+                    items.join("\n"),
+                ),
+                RstNode::DefinitionList { items, line } => (
+                    "paragraph",
+                    *line,
+                    String::new(),
+                    HashMap::new(),
+                    items
+                        .iter()
+                        .map(|item| format!("{}\n{}", item.term, item.definition))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+                _ => continue,
+            };
+
+            let level = match metadata.get("level") {
+                Some(FieldValue::Integer(level)) => *level as usize,
+                _ => 0,
+            };
+            let constraints = self.constraints_for(kind, &title, level);
+            let matched_directly = if kind == "directive" {
+                !self.banned_directives.is_empty()
+            } else {
+                !self.banned_terms.is_empty()
+            };
+            if constraints.is_empty() && !matched_directly {
+                continue;
+            }
+
+            metadata.insert("kind".to_string(), FieldValue::String(kind.to_string()));
+            metadata.insert("text".to_string(), FieldValue::String(text.to_lowercase()));
+
+            items.push(ContentItem {
+                id: format!("{}:{}", docname, line),
+                title,
+                content: text,
+                metadata,
+                constraints,
+                relationships: HashMap::new(),
+                location: ItemLocation {
+                    docname: docname.to_string(),
+                    lineno: Some(line as u32),
+                    source_path: Some(doc.source_path.display().to_string()),
+                },
+                style: None,
+            });
+        }
+
+        items
+    }
+
+    /// Evaluate the rules against a document
+    pub fn check_document(
+        &self,
+        engine: &mut ConstraintEngine,
+        config: &ValidationConfig,
+        doc: &Document,
+        docname: &str,
+    ) -> Result<Vec<RuleViolation>, BuildError> {
+        let all_items = HashMap::new();
+        let mut violations = Vec::new();
+
+        for item in self.content_items(doc, docname) {
+            let line = item.location.lineno.unwrap_or(0) as usize;
+            let banned = self.banned_in(&item);
+            let is_directive = matches!(item.metadata.get("kind"), Some(FieldValue::String(kind)) if kind == "directive");
+            if is_directive {
+                violations.extend(banned.iter().map(|(rule, message)| self.violation(rule, line, message)));
+            }
+
+            let context = ValidationContext {
+                current_item: &item,
+                all_items: &all_items,
+                config,
+                variables: HashMap::new(),
+            };
+            let (_, failures) = engine.process_constraints(&item, &context)?;
+            for failure in failures {
+                violations.push(RuleViolation {
+                    line,
+                    message: failure
+                        .result
+                        .error_message
+                        .unwrap_or_else(|| format!("Rule '{}' failed", failure.rule.name)),
+                    severity: failure.severity,
+                });
+            }
+
+            if !is_directive {
+                violations.extend(banned.iter().map(|(rule, message)| self.violation(rule, line, message)));
+            }
+        }
+
+        Ok(violations)
+    }
+
+    /// The banned directive or terms an item uses, as the rule and the
+    /// message, compared case-insensitively
+    fn banned_in(&self, item: &ContentItem) -> Vec<(&'static str, String)> {
+        if let Some(FieldValue::String(kind)) = item.metadata.get("kind") {
+            if kind == "directive" {
+                let name = &item.title;
+                if self.banned_directives.iter().any(|banned| banned.eq_ignore_ascii_case(name)) {
+                    return vec![(
+                        "banned-directives",
+                        format!("Directive '{}' is not allowed in this project", name),
+                    )];
+                }
+                return Vec::new();
+            }
+        }
+        let text = item.content.to_lowercase();
+        self.banned_terms
+            .iter()
+            .filter(|term| text.contains(&term.to_lowercase()))
+            .map(|term| ("banned-terms", format!("Use of banned term '{}'", term)))
+            .collect()
+    }
+
+    fn violation(&self, rule: &str, line: usize, message: &str) -> RuleViolation {
+        RuleViolation {
+            line,
+            message: message.to_string(),
+            severity: self.severity_of(rule),
+        }
+    }
+}
+
+/// Quote a value as an expression string literal
+fn quote(value: &str) -> String {
+    if value.contains('\'') {
+        format!("\"{}\"", value)
+    } else {
+        format!("'{}'", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BuildConfig;
+    use crate::parser::Parser;
+    use tempfile::TempDir;
+
+    fn check(rules: &ContentRules, source: &str) -> Vec<RuleViolation> {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("index.rst");
+        std::fs::write(&path, source).unwrap();
+
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+        let doc = parser.parse(&path, source).unwrap();
+        let mut engine = ConstraintEngine::new();
+        let config = rules.validation_config();
+        rules
+            .check_document(&mut engine, &config, &doc, "index")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_banned_directive_and_required_option() {
+        let mut rules = ContentRules {
+            banned_directives: vec!["raw".to_string()],
+            ..Default::default()
+        };
+        rules
+            .required_options
+            .insert("image".to_string(), vec!["alt".to_string()]);
+
+        let source = "Title\n=====\n\n.. raw:: html\n\n   <b>x</b>\n\n.. image:: a.png\n   :width: 10\n\n.. image:: b.png\n   :alt: B\n";
+        let violations = check(&rules, source);
+
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].line, 4);
+        assert!(violations[0].message.contains("'raw' is not allowed"));
+        assert_eq!(violations[1].line, 8);
+        assert!(violations[1].message.contains(":alt:"));
+    }
+
+    #[test]
+    fn test_heading_rules() {
+        let mut rules = ContentRules {
+            heading_style: vec!["=".to_string(), "-".to_string()],
+            max_heading_depth: Some(2),
+            ..Default::default()
+        };
+        rules
+            .severity
+            .insert("max-heading-depth".to_string(), ValidationSeverity::Error);
+
+        let source = "Title\n=====\n\nSection\n~~~~~~~\n\nDeep\n^^^^\n";
+        let violations = check(&rules, source);
+
+        assert_eq!(violations.len(), 2);
+        assert!(violations[0].message.contains("underlined with '-'"));
+        assert_eq!(violations[0].severity, ValidationSeverity::Warning);
+        assert!(violations[1].message.contains("'Deep'"));
+        assert_eq!(violations[1].severity, ValidationSeverity::Error);
+    }
+
+    #[test]
+    fn test_banned_terms_and_custom_rules() {
+        let mut rules = ContentRules {
+            banned_terms: vec!["Utilize".to_string()],
+            ..Default::default()
+        };
+        let custom: CustomRule = serde_json::from_str(
+            r#"{"applies_to": "directive", "checks": {"check_0": "name != 'note' or argument"},
+                "severity": "warning", "error_message": "Notes need a title line"}"#,
+        )
+        .unwrap();
+        rules.custom.insert("titled-notes".to_string(), custom);
+
+        let source = "Title\n=====\n\nWe utilize things.\n\n.. note::\n\n   Body\n";
+        let violations = check(&rules, source);
+
+        assert_eq!(violations.len(), 2);
+        assert!(violations[0].message.contains("banned term 'Utilize'"));
+        assert_eq!(violations[1].message, "Notes need a title line");
+    }
+
+    #[test]
+    fn test_banned_terms_with_operators_and_quotes() {
+        let rules = ContentRules {
+            banned_terms: vec!["black and white".to_string(), r#"it's "fine""#.to_string()],
+            banned_directives: vec!["RAW".to_string()],
+            ..Default::default()
+        };
+
+        let source = "Title\n=====\n\nShot in Black and White.\n\nIt's \"fine\" or not.\n\n.. raw:: html\n\n   <b>x</b>\n";
+        let messages: Vec<(usize, String)> = check(&rules, source)
+            .into_iter()
+            .map(|violation| (violation.line, violation.message))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (4, "Use of banned term 'black and white'".to_string()),
+                (6, r#"Use of banned term 'it's "fine"'"#.to_string()),
+                (8, "Directive 'raw' is not allowed in this project".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,591 @@
+//! Spell checking of rendered document text
+//!
+//! Words are checked against hunspell dictionaries (`.dic` word lists with
+//! the prefix and suffix rules of the matching `.aff` file), a per-project
+//! word list and the words a document marks with the `:spelling:word:` role.
+//! Checking runs on the rendered HTML so that markup, code and literal blocks
+//! never count as prose.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::config::SpellingConfig;
+
+lazy_static! {
+    static ref SPELLING_WORD_ROLE_REGEX: Regex = Regex::new(r":spelling:word:`([^`]+)`").unwrap();
+    static ref TAG_REGEX: Regex = Regex::new(r"(?s)<!--.*?-->|<(/?)([a-zA-Z][a-zA-Z0-9]*)[^>]*>").unwrap();
+}
+
+/// Elements whose text is code or markup rather than prose
+const SKIPPED_ELEMENTS: &[&str] = &["pre", "code", "kbd", "samp", "tt", "script", "style", "math"];
+
+/// Directories searched for `<lang>.dic` after `$DICPATH`
+const DICTIONARY_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/local/share/hunspell",
+    "/Library/Spelling",
+];
+
+/// Word list looked up in the source directory when none is configured
+const DEFAULT_WORDLIST: &str = "spelling_wordlist.txt";
+
+/// How affix flags are written in a hunspell dictionary (`FLAG` in the `.aff` file)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlagFormat {
+    Char,
+    Long,
+    Numeric,
+}
+
+impl FlagFormat {
+    fn split(self, flags: &str) -> Vec<String> {
+        match self {
+            FlagFormat::Char => flags.chars().map(String::from).collect(),
+            FlagFormat::Long => {
+                let chars: Vec<char> = flags.chars().collect();
+                chars.chunks(2).map(|pair| pair.iter().collect()).collect()
+            }
+            FlagFormat::Numeric => flags.split(',').map(|flag| flag.trim().to_string()).collect(),
+        }
+    }
+}
+
+/// A single prefix or suffix rule from an `.aff` file
+#[derive(Debug, Clone)]
+struct AffixRule {
+    strip: String,
+    add: String,
+    condition: Option<Regex>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct AffixClass {
+    cross_product: bool,
+    rules: Vec<AffixRule>,
+}
+
+/// Prefix and suffix classes keyed by flag
+#[derive(Debug, Default)]
+struct Affixes {
+    flag_format: Option<FlagFormat>,
+    prefixes: HashMap<String, AffixClass>,
+    suffixes: HashMap<String, AffixClass>,
+}
+
+impl Affixes {
+    fn parse(content: &str) -> Self {
+        let mut affixes = Affixes::default();
+
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", format, ..] => {
+                    affixes.flag_format = match *format {
+                        "long" => Some(FlagFormat::Long),
+                        "num" => Some(FlagFormat::Numeric),
+                        _ => Some(FlagFormat::Char),
+                    };
+                }
+                [kind @ ("PFX" | "SFX"), flag, strip, add, rest @ ..] => {
+                    let classes = if *kind == "PFX" {
+                        &mut affixes.prefixes
+                    } else {
+                        &mut affixes.suffixes
+                    };
+
+                    // Class header: `SFX A Y 3`
+                    if rest.is_empty() && add.parse::<usize>().is_ok() {
+                        classes.entry(flag.to_string()).or_default().cross_product = *strip == "Y";
+                        continue;
+                    }
+
+                    let strip = if *strip == "0" { "" } else { strip };
+                    // Continuation flags on the affix are not supported
+                    let add = add.split('/').next().unwrap_or("");
+                    let add = if add == "0" { "" } else { add };
+                    let condition = match rest.first() {
+                        None | Some(&".") => None,
+                        Some(condition) => {
+                            let pattern = if *kind == "PFX" {
+                                format!("^{}", condition)
+                            } else {
+                                format!("{}$", condition)
+                            };
+                            match Regex::new(&pattern) {
+                                Ok(regex) => Some(regex),
+                                Err(_) => continue,
+                            }
+                        }
+                    };
+                    classes.entry(flag.to_string()).or_default().rules.push(AffixRule {
+                        strip: strip.to_string(),
+                        add: add.to_string(),
+                        condition,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        affixes
+    }
+
+    fn apply_suffixes<'a>(&'a self, word: &str, flags: &[String]) -> Vec<(String, &'a AffixClass)> {
+        let mut forms = Vec::new();
+        for flag in flags {
+            let Some(class) = self.suffixes.get(flag) else {
+                continue;
+            };
+            for rule in &class.rules {
+                if rule.condition.as_ref().is_some_and(|c| !c.is_match(word)) {
+                    continue;
+                }
+                if let Some(stem) = word.strip_suffix(rule.strip.as_str()) {
+                    forms.push((format!("{}{}", stem, rule.add), class));
+                }
+            }
+        }
+        forms
+    }
+
+    fn apply_prefixes(&self, word: &str, flags: &[String], cross_product_only: bool) -> Vec<String> {
+        let mut forms = Vec::new();
+        for flag in flags {
+            let Some(class) = self.prefixes.get(flag) else {
+                continue;
+            };
+            if cross_product_only && !class.cross_product {
+                continue;
+            }
+            for rule in &class.rules {
+                if rule.condition.as_ref().is_some_and(|c| !c.is_match(word)) {
+                    continue;
+                }
+                if let Some(stem) = word.strip_prefix(rule.strip.as_str()) {
+                    forms.push(format!("{}{}", rule.add, stem));
+                }
+            }
+        }
+        forms
+    }
+}
+
+/// Set of correctly spelled words
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a hunspell `.dic` file, expanding affixes from the `.aff` file
+    /// next to it when there is one.
+    pub fn load_hunspell(dic_path: &Path) -> Result<Self> {
+        let dic = read_lossy(dic_path)
+            .with_context(|| format!("Failed to read dictionary: {}", dic_path.display()))?;
+        let aff_path = dic_path.with_extension("aff");
+        let affixes = if aff_path.exists() {
+            let aff = read_lossy(&aff_path)
+                .with_context(|| format!("Failed to read affix file: {}", aff_path.display()))?;
+            Affixes::parse(&aff)
+        } else {
+            Affixes::default()
+        };
+
+        let mut dictionary = Self::new();
+        dictionary.add_hunspell_words(&dic, &affixes);
+        Ok(dictionary)
+    }
+
+    fn add_hunspell_words(&mut self, dic: &str, affixes: &Affixes) {
+        let flag_format = affixes.flag_format.unwrap_or(FlagFormat::Char);
+
+        // The first line holds the approximate word count
+        for line in dic.lines().skip(1) {
+            // Morphological fields follow the word after whitespace
+            let Some(entry) = line.split_whitespace().next() else {
+                continue;
+            };
+            let (word, flags) = match entry.split_once('/') {
+                Some((word, flags)) => (word, flag_format.split(flags)),
+                None => (entry, Vec::new()),
+            };
+            if word.is_empty() {
+                continue;
+            }
+
+            self.words.insert(word.to_string());
+            if flags.is_empty() {
+                continue;
+            }
+            for (form, class) in affixes.apply_suffixes(word, &flags) {
+                if class.cross_product {
+                    self.words.extend(affixes.apply_prefixes(&form, &flags, true));
+                }
+                self.words.insert(form);
+            }
+            self.words.extend(affixes.apply_prefixes(word, &flags, false));
+        }
+    }
+
+    /// Add the words of a plain word list; blank lines and `#` comments are ignored
+    pub fn add_wordlist(&mut self, content: &str) {
+        for line in content.lines() {
+            let word = line.trim();
+            if !word.is_empty() && !word.starts_with('#') {
+                self.words.insert(word.to_string());
+            }
+        }
+    }
+
+    pub fn add_word(&mut self, word: &str) {
+        self.words.insert(word.to_string());
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Whether a word from running text is spelled correctly. Capitalised and
+    /// all-caps words also match their lowercase dictionary entry.
+    pub fn contains(&self, word: &str) -> bool {
+        if self.words.contains(word) {
+            return true;
+        }
+        let lower = word.to_lowercase();
+        if lower != word && self.words.contains(&lower) {
+            return true;
+        }
+        // Possessives of known words
+        word.strip_suffix("'s")
+            .is_some_and(|stem| !stem.is_empty() && self.contains(stem))
+    }
+}
+
+/// A misspelled word in a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misspelling {
+    pub word: String,
+    /// First source line (1-based) where the word appears, if it can be found
+    pub line: Option<usize>,
+    pub occurrences: usize,
+}
+
+/// Spell checker built from the project configuration
+#[derive(Debug, Clone)]
+pub struct SpellChecker {
+    dictionary: Dictionary,
+    ignore_acronyms: bool,
+}
+
+impl SpellChecker {
+    pub fn new(dictionary: Dictionary, ignore_acronyms: bool) -> Self {
+        Self {
+            dictionary,
+            ignore_acronyms,
+        }
+    }
+
+    /// Load the configured dictionaries and project word list
+    pub fn from_config(config: &SpellingConfig, source_dir: &Path) -> Result<Self> {
+        let dic_paths = if config.dictionaries.is_empty() {
+            let path = find_dictionary(&config.lang).with_context(|| {
+                format!(
+                    "No hunspell dictionary found for '{}'; set spelling.dictionaries or DICPATH",
+                    config.lang
+                )
+            })?;
+            vec![path]
+        } else {
+            config
+                .dictionaries
+                .iter()
+                .map(|path| resolve(source_dir, path))
+                .collect()
+        };
+
+        let mut dictionary = Dictionary::new();
+        for path in &dic_paths {
+            dictionary.words.extend(Dictionary::load_hunspell(path)?.words);
+        }
+
+        let wordlist = match &config.wordlist {
+            Some(path) => Some(resolve(source_dir, path)),
+            None => Some(source_dir.join(DEFAULT_WORDLIST)).filter(|path| path.exists()),
+        };
+        if let Some(path) = wordlist {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read spelling word list: {}", path.display()))?;
+            dictionary.add_wordlist(&content);
+        }
+
+        Ok(Self::new(dictionary, config.ignore_acronyms))
+    }
+
+    /// Check the rendered HTML of a document. `source` is the document's
+    /// markup, used for `:spelling:word:` entries and line numbers.
+    pub fn check_document(&self, html: &str, source: &str) -> Vec<Misspelling> {
+        let mut tally = self.tally(source);
+        let mut last = 0;
+        for tag in TAG_REGEX.find_iter(html) {
+            tally.push_text(&html[last..tag.start()]);
+            tally.push_tag(tag.as_str());
+            last = tag.end();
+        }
+        tally.push_text(&html[last..]);
+        tally.finish()
+    }
+
+    /// Start checking a document whose rendered HTML comes in pieces, like
+    /// a page streamed to its output. `source` is as for `check_document`.
+    pub fn tally<'a>(&'a self, source: &'a str) -> WordTally<'a> {
+        WordTally {
+            checker: self,
+            source,
+            document_words: document_words(source),
+            skipped: Vec::new(),
+            counts: HashMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    fn is_accepted(&self, word: &str) -> bool {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() < 2 {
+            return true;
+        }
+        let has_lowercase = chars.iter().any(|c| c.is_lowercase());
+        if self.ignore_acronyms && !has_lowercase {
+            return true;
+        }
+        // Mixed-case words such as `JavaScript` or `iPhone` are identifiers
+        if has_lowercase && chars[1..].iter().any(|c| c.is_uppercase()) {
+            return true;
+        }
+        self.dictionary.contains(word)
+    }
+}
+
+/// Words a document marks as correct with the `:spelling:word:` role
+fn document_words(source: &str) -> HashSet<&str> {
+    SPELLING_WORD_ROLE_REGEX
+        .captures_iter(source)
+        .flat_map(|caps| caps.get(1).unwrap().as_str().split_whitespace())
+        .collect()
+}
+
+fn read_lossy(path: &Path) -> std::io::Result<String> {
+    // Older dictionaries are often ISO 8859-1 rather than UTF-8
+    std::fs::read(path).map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn resolve(source_dir: &Path, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        source_dir.join(path)
+    }
+}
+
+fn find_dictionary(lang: &str) -> Option<PathBuf> {
+    let file_name = format!("{}.dic", lang);
+    let dicpath = std::env::var("DICPATH").unwrap_or_default();
+    std::env::split_paths(&dicpath)
+        .chain(DICTIONARY_DIRS.iter().map(PathBuf::from))
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// The misspelled words of a document's rendered HTML, counted as the HTML
+/// is fed in tag by tag and text by text. Code, literals and comments are
+/// skipped.
+pub struct WordTally<'a> {
+    checker: &'a SpellChecker,
+    source: &'a str,
+    document_words: HashSet<&'a str>,
+    /// Open elements whose text is not prose
+    skipped: Vec<String>,
+    counts: HashMap<String, usize>,
+    order: Vec<String>,
+}
+
+impl WordTally<'_> {
+    /// Take in a tag or comment
+    pub fn push_tag(&mut self, tag: &str) {
+        let Some(caps) = TAG_REGEX.captures(tag) else {
+            return;
+        };
+        let Some(name) = caps.get(2) else {
+            return;
+        };
+        let name = name.as_str().to_lowercase();
+        let closing = !caps[1].is_empty();
+        if closing {
+            if self.skipped.last() == Some(&name) {
+                self.skipped.pop();
+            }
+        } else if SKIPPED_ELEMENTS.contains(&name.as_str()) || tag.contains("class=\"math") {
+            self.skipped.push(name);
+        }
+    }
+
+    /// Take in text between tags, which must not stop in the middle of a
+    /// word unless a tag follows
+    pub fn push_text(&mut self, text: &str) {
+        if !self.skipped.is_empty() {
+            return;
+        }
+        for word in words(&html_escape::decode_html_entities(text)) {
+            if self.document_words.contains(word.as_str()) || self.checker.is_accepted(&word) {
+                continue;
+            }
+            let count = self.counts.entry(word.clone()).or_insert(0);
+            if *count == 0 {
+                self.order.push(word);
+            }
+            *count += 1;
+        }
+    }
+
+    /// The misspellings found, in order of first occurrence
+    pub fn finish(self) -> Vec<Misspelling> {
+        self.order
+            .into_iter()
+            .map(|word| Misspelling {
+                line: source_line(self.source, &word),
+                occurrences: self.counts[&word],
+                word,
+            })
+            .collect()
+    }
+}
+
+/// Split prose into words, skipping tokens that look like URLs, paths,
+/// e-mail addresses or identifiers
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for token in text.split_whitespace() {
+        let token = token.replace(['\u{2019}', '\u{2018}'], "'");
+        let looks_technical = token.contains("://")
+            || token.contains('@')
+            || token.contains('_')
+            || token.contains('/')
+            || token.chars().any(|c| c.is_ascii_digit())
+            || token
+                .trim_end_matches(|c: char| !c.is_alphanumeric())
+                .contains('.');
+        if looks_technical {
+            continue;
+        }
+
+        for part in token.split(|c: char| !(c.is_alphabetic() || c == '\'')) {
+            let word = part.trim_matches('\'');
+            if !word.is_empty() {
+                words.push(word.to_string());
+            }
+        }
+    }
+    words
+}
+
+fn source_line(source: &str, word: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|line| {
+            line.match_indices(word).any(|(start, _)| {
+                let before = line[..start].chars().next_back();
+                let after = line[start + word.len()..].chars().next();
+                !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+            })
+        })
+        .map(|index| index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8\n\nPFX U Y 1\nPFX U 0 un .\n\nSFX D Y 2\nSFX D 0 ed [^ey]\nSFX D y ied y\n\nSFX S N 1\nSFX S 0 s .\n";
+    const DIC: &str = "5\nthe\ndoc/S\nhappy\nlock/DU\ncarry/D\n";
+
+    fn dictionary() -> Dictionary {
+        let mut dictionary = Dictionary::new();
+        dictionary.add_hunspell_words(DIC, &Affixes::parse(AFF));
+        dictionary
+    }
+
+    #[test]
+    fn test_affix_expansion() {
+        let dictionary = dictionary();
+        for word in ["doc", "docs", "lock", "locked", "unlock", "unlocked", "carried", "The"] {
+            assert!(dictionary.contains(word), "expected '{}' to be known", word);
+        }
+        for word in ["carryed", "undoc", "lockes", "Lockd"] {
+            assert!(!dictionary.contains(word), "expected '{}' to be unknown", word);
+        }
+    }
+
+    #[test]
+    fn test_check_document_skips_code_and_marked_words() {
+        let mut dictionary = dictionary();
+        dictionary.add_wordlist("# project words\nsphinx\nsee\nand\n");
+        let checker = SpellChecker::new(dictionary, true);
+
+        let source = "The docs\n\nThe :spelling:word:`frobnicator` happy teh docs.\n\nSee ``hapy`` and HTML and JavaScript.\n\nteh wrold\n";
+        let html = "<p>The docs</p>\n<p>The frobnicator happy teh docs &amp; Sphinx.</p>\n<p>See <code>hapy</code> and HTML and JavaScript, https://example.com/x.</p>\n<pre>misspeled code</pre>\n<p>teh wrold</p>";
+
+        let found = checker.check_document(html, source);
+        assert_eq!(
+            found,
+            vec![
+                Misspelling {
+                    word: "teh".to_string(),
+                    line: Some(3),
+                    occurrences: 2,
+                },
+                Misspelling {
+                    word: "wrold".to_string(),
+                    line: Some(7),
+                    occurrences: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_from_config() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("en_TEST.dic"), DIC).unwrap();
+        std::fs::write(temp_dir.path().join("en_TEST.aff"), AFF).unwrap();
+        std::fs::write(temp_dir.path().join(DEFAULT_WORDLIST), "frobnicate\n").unwrap();
+
+        let config = SpellingConfig {
+            enabled: true,
+            dictionaries: vec![PathBuf::from("en_TEST.dic")],
+            ..Default::default()
+        };
+        let checker = SpellChecker::from_config(&config, temp_dir.path()).unwrap();
+        assert!(checker.dictionary.contains("unlocked"));
+        assert!(checker.dictionary.contains("frobnicate"));
+
+        let missing = SpellingConfig {
+            enabled: true,
+            lang: "xx_NONE".to_string(),
+            ..Default::default()
+        };
+        assert!(SpellChecker::from_config(&missing, temp_dir.path()).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+//! Style linting for reStructuredText sources
+//!
+//! These checks work on the raw source rather than the parsed document, since
+//! the parser discards the adornment details (underline characters, lengths,
+//! trailing whitespace) that style problems are about.
+
+/// Characters docutils accepts for section adornments
+const ADORNMENT_CHARS: &str = "=-~^\"'*+#<>`:._";
+
+/// Kind of style problem found in a source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StyleIssueKind {
+    /// A heading jumps more than one level deeper than the previous one
+    SkippedHeadingLevel,
+    /// More than one top-level title in a document
+    MultipleTitles,
+    /// Title text ends with whitespace
+    TitleTrailingWhitespace,
+    /// Underline (or overline) length differs from the title length
+    UnderlineLength,
+}
+
+/// A style problem with a hint on how to fix it
+#[derive(Debug, Clone)]
+pub struct StyleIssue {
+    pub kind: StyleIssueKind,
+    /// Line number (1-based) of the title text
+    pub line: usize,
+    pub message: String,
+    pub fix_hint: String,
+}
+
+/// A section title as written in the source
+struct Heading<'a> {
+    line: usize,
+    text: &'a str,
+    adornment: char,
+    overline: Option<usize>,
+    underline: usize,
+}
+
+fn adornment_char(line: &str) -> Option<char> {
+    let trimmed = line.trim_end();
+    let first = trimmed.chars().next()?;
+    if ADORNMENT_CHARS.contains(first) && trimmed.chars().all(|c| c == first) {
+        Some(first)
+    } else {
+        None
+    }
+}
+
+/// Docutils only treats a short underline as a (too short) title underline
+/// once it is at least four characters long.
+fn is_title_underline(text: &str, underline: usize) -> bool {
+    underline >= text.trim_end().chars().count() || underline >= 4
+}
+
+fn find_headings(source: &str) -> Vec<Heading<'_>> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut headings = Vec::new();
+    let mut i = 0;
+
+    while i + 1 < lines.len() {
+        let line = lines[i];
+
+        // Overlined title: adornment, title, matching adornment
+        if let Some(c) = adornment_char(line) {
+            if i + 2 < lines.len()
+                && !lines[i + 1].trim().is_empty()
+                && adornment_char(lines[i + 2]) == Some(c)
+            {
+                headings.push(Heading {
+                    line: i + 2,
+                    text: lines[i + 1].trim_start(),
+                    adornment: c,
+                    overline: Some(line.trim_end().chars().count()),
+                    underline: lines[i + 2].trim_end().chars().count(),
+                });
+                i += 3;
+                continue;
+            }
+        }
+
+        // Underlined title: unindented text followed by an adornment line
+        let is_text = !line.trim().is_empty()
+            && !line.starts_with(char::is_whitespace)
+            && !line.starts_with("..")
+            && adornment_char(line).is_none();
+        if is_text {
+            if let Some(c) = adornment_char(lines[i + 1]) {
+                let underline = lines[i + 1].trim_end().chars().count();
+                let preceded_by_blank = i == 0 || lines[i - 1].trim().is_empty();
+                if preceded_by_blank && is_title_underline(line, underline) {
+                    headings.push(Heading {
+                        line: i + 1,
+                        text: line,
+                        adornment: c,
+                        overline: None,
+                        underline,
+                    });
+                    i += 2;
+                    continue;
+                }
+            }
+        }
+
+        i += 1;
+    }
+
+    headings
+}
+
+/// Lint heading hierarchy and title formatting in an RST source
+pub fn lint_headings(source: &str) -> Vec<StyleIssue> {
+    let mut issues = Vec::new();
+    // Adornment styles in order of first appearance; (char, overlined) defines a level
+    let mut styles: Vec<(char, bool)> = Vec::new();
+    let mut previous_level = 0;
+    let mut top_level_seen = false;
+
+    for heading in find_headings(source) {
+        let style = (heading.adornment, heading.overline.is_some());
+        let level = match styles.iter().position(|s| *s == style) {
+            Some(pos) => pos + 1,
+            None => {
+                styles.push(style);
+                styles.len()
+            }
+        };
+        let title = heading.text.trim_end();
+        let title_len = title.chars().count();
+
+        if level > previous_level + 1 {
+            let (expected_char, expected_overline) = styles[previous_level];
+            issues.push(StyleIssue {
+                kind: StyleIssueKind::SkippedHeadingLevel,
+                line: heading.line,
+                message: format!(
+                    "Heading '{}' skips from level {} to level {}",
+                    title, previous_level, level
+                ),
+                fix_hint: format!(
+                    "Use the level {} style ({}'{}' underline)",
+                    previous_level + 1,
+                    if expected_overline { "overlined, " } else { "" },
+                    expected_char
+                ),
+            });
+        }
+
+        if level == 1 {
+            if top_level_seen {
+                let hint = match styles.get(1) {
+                    Some((c, _)) => format!(
+                        "Demote it to a section ('{}' underline) or move it to its own page",
+                        c
+                    ),
+                    None => "Demote it to a section or move it to its own page".to_string(),
+                };
+                issues.push(StyleIssue {
+                    kind: StyleIssueKind::MultipleTitles,
+                    line: heading.line,
+                    message: format!("Document has more than one top-level title: '{}'", title),
+                    fix_hint: hint,
+                });
+            }
+            top_level_seen = true;
+        }
+
+        if heading.text != title {
+            issues.push(StyleIssue {
+                kind: StyleIssueKind::TitleTrailingWhitespace,
+                line: heading.line,
+                message: format!("Title '{}' has trailing whitespace", title),
+                fix_hint: "Remove the whitespace after the title text".to_string(),
+            });
+        }
+
+        let lengths_match = heading.underline == title_len
+            && heading.overline.is_none_or(|overline| overline == title_len);
+        if !lengths_match {
+            let what = if heading.overline.is_some() {
+                "Overline and underline"
+            } else {
+                "Underline"
+            };
+            issues.push(StyleIssue {
+                kind: StyleIssueKind::UnderlineLength,
+                line: heading.line,
+                message: format!(
+                    "{} length does not match title '{}' ({} characters)",
+                    what, title, title_len
+                ),
+                fix_hint: format!(
+                    "Use {}",
+                    heading.adornment.to_string().repeat(title_len)
+                ),
+            });
+        }
+
+        previous_level = level;
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<(StyleIssueKind, usize)> {
+        lint_headings(source)
+            .into_iter()
+            .map(|issue| (issue.kind, issue.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_document() {
+        let source = "=====\nTitle\n=====\n\nSection\n-------\n\nSub\n~~~\n\nOther\n-----\n";
+        assert!(kinds(source).is_empty());
+    }
+
+    #[test]
+    fn test_skipped_level_and_multiple_titles() {
+        let source = "Title\n=====\n\nA\n-\n\nB\n~\n\nAgain\n=====\n\nDeep\n~~~~\n";
+        let issues = lint_headings(source);
+        let found: Vec<_> = issues.iter().map(|i| (i.kind, i.line)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (StyleIssueKind::MultipleTitles, 10),
+                (StyleIssueKind::SkippedHeadingLevel, 13),
+            ]
+        );
+        assert_eq!(issues[1].fix_hint, "Use the level 2 style ('-' underline)");
+    }
+
+    #[test]
+    fn test_whitespace_and_underline_length() {
+        let source = "Title  \n=======\n\nLonger section\n=========\n\nShort\n==========\n";
+        let issues = lint_headings(source);
+        let found: Vec<_> = issues.iter().map(|i| (i.kind, i.line)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (StyleIssueKind::TitleTrailingWhitespace, 1),
+                (StyleIssueKind::UnderlineLength, 1),
+                (StyleIssueKind::MultipleTitles, 4),
+                (StyleIssueKind::UnderlineLength, 4),
+                (StyleIssueKind::MultipleTitles, 7),
+                (StyleIssueKind::UnderlineLength, 7),
+            ]
+        );
+        assert_eq!(issues[1].fix_hint, "Use =====");
+        assert_eq!(issues[3].fix_hint, "Use ==============");
+    }
+
+    #[test]
+    fn test_ignores_short_rules_in_text() {
+        let source = "Some paragraph text\n--\n\n::\n\n    code\n    ====\n";
+        assert!(kinds(source).is_empty());
+    }
+}
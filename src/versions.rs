@@ -0,0 +1,396 @@
+//! Multi-version documentation builds.
+//!
+//! Every git tag and branch selected by [`VersionsConfig`] is exported from
+//! the repository and built into its own subdirectory of the output. A
+//! `versions.json` manifest in the version-switcher format understood by
+//! pydata-sphinx-theme and similar themes is written next to them, and the
+//! pages of each version get `versions`/`current_version` template variables
+//! for an in-page switcher.
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::builder::{BuildStats, SphinxBuilder};
+use crate::config::{BuildConfig, VersionsConfig};
+use crate::paths;
+use crate::redirects;
+
+/// A git tag or local branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRef {
+    /// Short name, like `v1.2` or `main`
+    pub name: String,
+
+    /// Full ref name, like `refs/tags/v1.2`
+    pub refname: String,
+
+    /// Whether the ref is a tag rather than a branch
+    pub is_tag: bool,
+}
+
+impl GitRef {
+    /// Parse a full ref name from `refs/tags/` or `refs/heads/`
+    pub fn from_refname(refname: &str) -> Option<Self> {
+        let (name, is_tag) = match refname.strip_prefix("refs/tags/") {
+            Some(name) => (name, true),
+            None => (refname.strip_prefix("refs/heads/")?, false),
+        };
+        Some(Self {
+            name: name.to_string(),
+            refname: refname.to_string(),
+            is_tag,
+        })
+    }
+
+    /// Output subdirectory of the version; slashes in branch names would
+    /// nest directories, so they become dashes
+    pub fn dir_name(&self) -> String {
+        self.name.replace('/', "-")
+    }
+
+    fn kind(&self) -> &'static str {
+        if self.is_tag {
+            "tag"
+        } else {
+            "branch"
+        }
+    }
+}
+
+/// One entry of `versions.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionEntry {
+    /// Name shown in the switcher
+    pub name: String,
+
+    /// Version identifier
+    pub version: String,
+
+    /// Root of the version's pages, relative to the output root
+    pub url: String,
+
+    /// Whether this is the recommended version
+    #[serde(default)]
+    pub preferred: bool,
+}
+
+/// A switcher entry as seen from one page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionLink {
+    pub name: String,
+    pub url: String,
+    pub current: bool,
+    pub preferred: bool,
+}
+
+/// Version switcher state for the pages of one version
+#[derive(Debug, Clone)]
+pub struct VersionSwitcher {
+    /// Name of the version being built
+    pub current: String,
+
+    /// All versions in the build
+    pub versions: Vec<VersionEntry>,
+}
+
+impl VersionSwitcher {
+    /// Switcher entries linking to each version's root page, relative to the
+    /// page at `doc_path` inside the current version's directory
+    pub fn links(&self, doc_path: &str) -> Vec<VersionLink> {
+        let up = "../".repeat(doc_path.matches('/').count() + 1);
+        self.versions
+            .iter()
+            .map(|entry| VersionLink {
+                name: entry.name.clone(),
+                url: format!("{}{}index.html", up, entry.url),
+                current: entry.name == self.current,
+                preferred: entry.preferred,
+            })
+            .collect()
+    }
+}
+
+/// Local branches of a repository, most recently committed first, then its
+/// tags, highest version first
+pub fn list_refs(repo: &Path) -> Result<Vec<GitRef>> {
+    let branches = git(
+        repo,
+        &["for-each-ref", "--sort=-committerdate", "--format=%(refname)", "refs/heads"],
+    )?;
+    let tags = git(
+        repo,
+        &["for-each-ref", "--sort=-v:refname", "--format=%(refname)", "refs/tags"],
+    )?;
+    Ok(branches
+        .lines()
+        .chain(tags.lines())
+        .filter_map(GitRef::from_refname)
+        .collect())
+}
+
+/// Keep the tags and branches whose names match the configured patterns.
+/// Fails when two of them would be built into the same directory, like a
+/// tag and a branch of one name, or `feature/x` and `feature-x`.
+pub fn select_refs(refs: Vec<GitRef>, config: &VersionsConfig) -> Result<Vec<GitRef>> {
+    let tag_pattern = Regex::new(&config.tag_pattern)
+        .with_context(|| format!("Invalid tag pattern '{}'", config.tag_pattern))?;
+    let branch_pattern = Regex::new(&config.branch_pattern)
+        .with_context(|| format!("Invalid branch pattern '{}'", config.branch_pattern))?;
+
+    let selected: Vec<GitRef> = refs
+        .into_iter()
+        .filter(|git_ref| {
+            let pattern = if git_ref.is_tag { &tag_pattern } else { &branch_pattern };
+            pattern.is_match(&git_ref.name)
+        })
+        .collect();
+
+    let mut dirs: HashMap<String, &GitRef> = HashMap::new();
+    for git_ref in &selected {
+        if let Some(other) = dirs.insert(git_ref.dir_name(), git_ref) {
+            bail!(
+                "The {} '{}' and the {} '{}' would both be built into '{}'; narrow the version patterns to select only one",
+                other.kind(),
+                other.name,
+                git_ref.kind(),
+                git_ref.name,
+                git_ref.dir_name()
+            );
+        }
+    }
+    Ok(selected)
+}
+
+/// The `versions.json` entries for the selected refs. The preferred version
+/// is `latest` when configured, otherwise the newest tag, otherwise the first
+/// branch.
+pub fn manifest(refs: &[GitRef], latest: Option<&str>) -> Vec<VersionEntry> {
+    let preferred = latest
+        .map(str::to_string)
+        .or_else(|| refs.iter().find(|git_ref| git_ref.is_tag).map(|git_ref| git_ref.name.clone()))
+        .or_else(|| refs.first().map(|git_ref| git_ref.name.clone()));
+
+    refs.iter()
+        .map(|git_ref| VersionEntry {
+            name: git_ref.name.clone(),
+            version: git_ref.name.trim_start_matches('v').to_string(),
+            url: format!("{}/", git_ref.dir_name()),
+            preferred: preferred.as_deref() == Some(git_ref.name.as_str()),
+        })
+        .collect()
+}
+
+/// Builds every selected version of a project into its own output directory
+pub struct MultiVersionBuilder {
+    config: VersionsConfig,
+    source_dir: PathBuf,
+    output_dir: PathBuf,
+    parallel_jobs: Option<usize>,
+}
+
+impl MultiVersionBuilder {
+    pub fn new(config: &BuildConfig, source_dir: PathBuf, output_dir: PathBuf) -> Self {
+        Self {
+            config: config.versions.clone(),
+            source_dir,
+            output_dir,
+            parallel_jobs: None,
+        }
+    }
+
+    pub fn set_parallel_jobs(&mut self, jobs: usize) {
+        self.parallel_jobs = Some(jobs);
+    }
+
+    /// Build all versions, returning the statistics of each by name
+    pub async fn build(&self) -> Result<Vec<(String, BuildStats)>> {
+        let repo_root = PathBuf::from(git(&self.source_dir, &["rev-parse", "--show-toplevel"])?.trim());
+        let source_in_repo = paths::canonicalize(&self.source_dir)
+            .with_context(|| format!("Source directory not found: {}", self.source_dir.display()))?
+            .strip_prefix(paths::canonicalize(&repo_root)?)
+            .context("Source directory is not inside the git repository")?
+            .to_path_buf();
+        let tree_path = paths::to_slash(&source_in_repo);
+
+        // Only refs that contain the documentation can be built
+        let refs: Vec<GitRef> = select_refs(list_refs(&repo_root)?, &self.config)?
+            .into_iter()
+            .filter(|git_ref| {
+                let tree = format!("{}:{}", git_ref.refname, tree_path);
+                git(&repo_root, &["cat-file", "-e", &tree]).is_ok()
+            })
+            .collect();
+        if refs.is_empty() {
+            bail!(
+                "No git tags or branches match the version patterns (tags: '{}', branches: '{}')",
+                self.config.tag_pattern,
+                self.config.branch_pattern
+            );
+        }
+
+        let versions = manifest(&refs, self.config.latest.as_deref());
+        std::fs::create_dir_all(&self.output_dir)
+            .with_context(|| format!("Failed to create output directory: {}", self.output_dir.display()))?;
+        let manifest_path = self.output_dir.join("versions.json");
+        std::fs::write(&manifest_path, serde_json::to_string_pretty(&versions)?)
+            .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+
+        let mut results = Vec::new();
+        for git_ref in &refs {
+            info!("Building version {}", git_ref.name);
+            let checkout = tempfile::tempdir().context("Failed to create a temporary directory")?;
+            export_ref(&repo_root, &git_ref.refname, checkout.path())?;
+
+            let source_dir = checkout.path().join(&source_in_repo);
+            let config = BuildConfig::auto_detect(&source_dir)
+                .with_context(|| format!("Failed to load the configuration of version {}", git_ref.name))?;
+            let mut builder = SphinxBuilder::new(config, source_dir, self.output_dir.join(git_ref.dir_name()))?;
+            if let Some(jobs) = self.parallel_jobs {
+                builder.set_parallel_jobs(jobs);
+            }
+            builder.set_version_switcher(VersionSwitcher {
+                current: git_ref.name.clone(),
+                versions: versions.clone(),
+            });
+            let stats = builder
+                .build()
+                .await
+                .with_context(|| format!("Failed to build version {}", git_ref.name))?;
+            results.push((git_ref.name.clone(), stats));
+        }
+
+        // The output root sends visitors to the preferred version
+        if let Some(preferred) = versions.iter().find(|entry| entry.preferred) {
+            let url = format!("{}index.html", preferred.url);
+            let redirect = redirects::stub_html(&url, None, &preferred.name);
+            let index_path = self.output_dir.join("index.html");
+            std::fs::write(&index_path, redirect)
+                .with_context(|| format!("Failed to write {}", index_path.display()))?;
+        }
+
+        Ok(results)
+    }
+}
+
+/// Write the tree of a ref into `destination`
+fn export_ref(repo: &Path, refname: &str, destination: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["archive", "--format=tar", refname])
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git archive {} failed: {}",
+            refname,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    tar::Archive::new(output.stdout.as_slice())
+        .unpack(destination)
+        .with_context(|| format!("Failed to export {}", refname))
+}
+
+/// Run a git command in `dir` and return its standard output
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refs(names: &[&str]) -> Vec<GitRef> {
+        names.iter().filter_map(|name| GitRef::from_refname(name)).collect()
+    }
+
+    #[test]
+    fn test_select_refs_and_manifest() {
+        let all = refs(&[
+            "refs/tags/v2.0",
+            "refs/tags/v1.0",
+            "refs/tags/nightly",
+            "refs/heads/main",
+            "refs/heads/feature/x",
+            "refs/remotes/origin/main",
+        ]);
+        assert_eq!(all.len(), 5);
+
+        let config = VersionsConfig {
+            tag_pattern: r"^v\d+".to_string(),
+            branch_pattern: "^main$".to_string(),
+            latest: None,
+        };
+        let selected = select_refs(all, &config).unwrap();
+        let names: Vec<&str> = selected.iter().map(|git_ref| git_ref.name.as_str()).collect();
+        assert_eq!(names, ["v2.0", "v1.0", "main"]);
+
+        let versions = manifest(&selected, None);
+        assert_eq!(
+            versions[0],
+            VersionEntry {
+                name: "v2.0".to_string(),
+                version: "2.0".to_string(),
+                url: "v2.0/".to_string(),
+                preferred: true,
+            }
+        );
+        assert!(!versions[2].preferred);
+        assert!(manifest(&selected, Some("main"))[2].preferred);
+
+        let invalid = VersionsConfig {
+            tag_pattern: "(".to_string(),
+            ..VersionsConfig::default()
+        };
+        assert!(select_refs(selected, &invalid).is_err());
+
+        let all_branches = VersionsConfig {
+            tag_pattern: ".*".to_string(),
+            branch_pattern: ".*".to_string(),
+            latest: None,
+        };
+        let error = select_refs(refs(&["refs/heads/feature/x", "refs/heads/feature-x"]), &all_branches)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("'feature/x' and the branch 'feature-x' would both be built into 'feature-x'"), "{}", error);
+        let error = select_refs(refs(&["refs/heads/v1.0", "refs/tags/v1.0"]), &all_branches)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("The branch 'v1.0' and the tag 'v1.0'"), "{}", error);
+    }
+
+    #[test]
+    fn test_switcher_links_are_relative_to_the_page() {
+        let branch = GitRef::from_refname("refs/heads/feature/x").unwrap();
+        assert_eq!(branch.dir_name(), "feature-x");
+
+        let switcher = VersionSwitcher {
+            current: "v1.0".to_string(),
+            versions: manifest(&refs(&["refs/tags/v1.0", "refs/heads/main"]), None),
+        };
+        let links = switcher.links("guide/intro");
+        assert_eq!(links[0].url, "../../v1.0/index.html");
+        assert!(links[0].current && links[0].preferred);
+        assert_eq!(links[1].url, "../../main/index.html");
+        assert_eq!(switcher.links("index")[1].url, "../main/index.html");
+    }
+}
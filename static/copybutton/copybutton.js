@@ -0,0 +1,77 @@
+/* Copy-to-clipboard buttons for code blocks; COPYBUTTON holds the settings */
+(function() {
+    var settings = (typeof COPYBUTTON !== 'undefined') ? COPYBUTTON : {};
+    var selector = settings.selector || 'div.highlight pre';
+    var prompt = settings.prompt_regex ? new RegExp('^(' + settings.prompt_regex + ')') : null;
+
+    /* Text of a block without line numbers */
+    function blockText(block) {
+        var copy = block.cloneNode(true);
+        copy.querySelectorAll('.linenos, .lineno').forEach(function(number) {
+            number.remove();
+        });
+        return copy.textContent;
+    }
+
+    /* Remove prompts; when some lines have one, other lines are output */
+    function stripPrompts(text) {
+        if (!prompt) return text;
+        var lines = text.split('\n');
+        var prompted = lines.some(function(line) { return prompt.test(line); });
+        if (!prompted) return text;
+        var kept = [];
+        lines.forEach(function(line) {
+            if (prompt.test(line)) {
+                kept.push(line.replace(prompt, ''));
+            } else if (!settings.only_copy_prompt_lines) {
+                kept.push(line);
+            }
+        });
+        return kept.join('\n');
+    }
+
+    function copy(text, button) {
+        var done = function() {
+            button.classList.add('success');
+            button.setAttribute('title', 'Copied!');
+            setTimeout(function() {
+                button.classList.remove('success');
+                button.setAttribute('title', 'Copy to clipboard');
+            }, 2000);
+        };
+        if (navigator.clipboard) {
+            navigator.clipboard.writeText(text).then(done);
+            return;
+        }
+        var area = document.createElement('textarea');
+        area.value = text;
+        document.body.appendChild(area);
+        area.select();
+        document.execCommand('copy');
+        area.remove();
+        done();
+    }
+
+    function addButtons() {
+        document.querySelectorAll(selector).forEach(function(block) {
+            if (block.closest('.linenodiv')) return;
+            var container = block.parentNode;
+            container.classList.add('copybtn-container');
+            var button = document.createElement('button');
+            button.className = 'copybtn';
+            button.type = 'button';
+            button.setAttribute('title', 'Copy to clipboard');
+            button.setAttribute('aria-label', 'Copy to clipboard');
+            button.addEventListener('click', function() {
+                copy(stripPrompts(blockText(block)).replace(/\n$/, ''), button);
+            });
+            container.insertBefore(button, block);
+        });
+    }
+
+    if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', addButtons);
+    } else {
+        addButtons();
+    }
+})();